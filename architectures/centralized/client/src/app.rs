@@ -12,7 +12,9 @@ use psyche_core::NodeIdentity;
 use psyche_event_sourcing::event;
 use psyche_event_sourcing::events::RpcCallType;
 use psyche_metrics::ClientMetrics;
-use psyche_network::{EndpointId, NetworkTUIState, NetworkTui, SecretKey, TcpClient, allowlist};
+use psyche_network::{
+    ClientTlsConfig, EndpointId, NetworkTUIState, NetworkTui, SecretKey, TcpClient, allowlist,
+};
 use psyche_tui::logging::LoggerWidget;
 use psyche_tui::{CustomWidget, TabbedWidget};
 use psyche_watcher::{Backend as WatcherBackend, CoordinatorTui, OpportunisticData};
@@ -22,7 +24,7 @@ use tokio::sync::mpsc::Sender;
 use tokio::time::interval;
 use tokio::{select, sync::mpsc, time::Interval};
 use tokio_util::sync::CancellationToken;
-use tracing::debug;
+use tracing::{debug, info};
 
 pub(super) type Tabs = TabbedWidget<(ClientTUI, CoordinatorTui, NetworkTui, LoggerWidget)>;
 pub const TAB_NAMES: [&str; 4] = ["Client", "Coordinator", "Network", "Logger"];
@@ -89,18 +91,24 @@ pub struct App {
 pub async fn build_app(
     cancel: CancellationToken,
     server_addr: String,
+    tls: Option<ClientTlsConfig>,
     tx_tui_state: Option<Sender<TabsData>>,
     p: TrainArgs,
 ) -> Result<(App, allowlist::AllowDynamic, NC, RunInitConfig)> {
     let metrics = Arc::new(ClientMetrics::new(
         p.metrics_local_port,
+        p.metrics_http_port,
         Some(Duration::from_secs(30)),
     ));
+    if let Some(port) = p.metrics_ws_port {
+        metrics.start_ws_server(port);
+    }
     let identity_secret_key = read_identity_secret_key(p.identity_secret_key_path.as_ref())?
         .unwrap_or_else(|| SecretKey::generate(&mut rand::rng()));
-    let server_conn = TcpClient::<ClientToServerMessage, ServerToClientMessage>::connect(
+    let server_conn = TcpClient::<ClientToServerMessage, ServerToClientMessage>::connect_with_tls(
         &server_addr,
         identity_secret_key.clone(),
+        tls,
     )
     .await?;
 
@@ -114,6 +122,7 @@ pub async fn build_app(
     ))?;
 
     let allowlist = allowlist::AllowDynamic::new();
+    let network_config = p.network_config();
 
     let p2p = NC::init(
         &p.run_id,
@@ -126,6 +135,7 @@ pub async fn build_app(
         allowlist.clone(),
         metrics.clone(),
         Some(cancel.clone()),
+        network_config,
     )
     .await?;
 
@@ -133,18 +143,28 @@ pub async fn build_app(
         data_parallelism: p.data_parallelism,
         tensor_parallelism: p.tensor_parallelism,
         micro_batch_size: p.micro_batch_size,
+        gradient_accumulation_steps: p.gradient_accumulation_steps,
         write_gradients_dir: p.write_gradients_dir,
+        record_events: p.record_events,
+        replay_events: p.replay_events,
         eval_tasks,
         eval_task_max_docs: p.eval_task_max_docs,
         prompt_task: p.prompt_task,
         checkpoint_config,
+        resume_checkpoint: p.resume_checkpoint,
         hub_read_token,
         hub_max_concurrent_downloads: p.hub_max_concurrent_downloads,
         wandb_info,
+        write_events_log: p.write_events_log,
         identity: NodeIdentity::from_single_key(*identity_secret_key.public().as_bytes()),
         p2p_secret_key: identity_secret_key,
         optim_stats_every_n_steps: p.optim_stats_steps,
         grad_accum_in_fp32: p.grad_accum_in_fp32,
+        gradient_clip_norm: p.gradient_clip_norm,
+        ema_decay: p.ema_decay,
+        activation_checkpointing: p.activation_checkpointing,
+        distro_int8_quantization: p.distro_int8_quantization,
+        find_lr: p.find_lr_config(),
         dummy_training_delay_secs: p.dummy_training_delay_secs,
         max_concurrent_parameter_requests: p.max_concurrent_parameter_requests,
         device: p.device,
@@ -295,6 +315,9 @@ impl App {
                 self.coordinator_state = *state;
                 let _ = tx.send(*state);
             }
+            ServerToClientMessage::ConfigUpdated(config) => {
+                info!("Server updated the run config: {config:?}");
+            }
         }
     }
 }
@@ -1,10 +1,10 @@
 use crate::app::{TAB_NAMES, Tabs, build_app};
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
 use psyche_client::{TrainArgs, print_identity_keys, read_identity_secret_key};
 use psyche_event_sourcing::{EventStore, FileBackend, RunStarted};
-use psyche_network::SecretKey;
+use psyche_network::{ClientTlsConfig, SecretKey};
 use psyche_tui::{
     LogOutput, ServiceInfo,
     logging::{MetricsDestination, OpenTelemetry, RemoteLogsDestination, TraceDestination},
@@ -42,6 +42,14 @@ enum Commands {
 
         #[clap(long, env)]
         server_addr: String,
+
+        /// PEM CA certificate used to verify the server's TLS certificate. Enables TLS.
+        #[clap(long, env, conflicts_with = "tls_insecure")]
+        tls_ca: Option<PathBuf>,
+
+        /// Connect over TLS without verifying the server's certificate, for self-signed certificates.
+        #[clap(long, env)]
+        tls_insecure: bool,
     },
     // Prints the help, optionally as markdown. Used for docs generation.
     #[clap(hide = true)]
@@ -49,6 +57,9 @@ enum Commands {
         #[arg(long, required = true)]
         markdown: bool,
     },
+    // Prints a shell completion script. Used for packaging.
+    #[clap(hide = true)]
+    GenerateCompletions { shell: clap_complete::Shell },
 }
 
 async fn async_main() -> Result<()> {
@@ -58,9 +69,18 @@ async fn async_main() -> Result<()> {
         Commands::ShowIdentity {
             identity_secret_key_path,
         } => print_identity_keys(identity_secret_key_path.as_ref()),
-        Commands::Train { args, server_addr } => {
+        Commands::Train {
+            args,
+            server_addr,
+            tls_ca,
+            tls_insecure,
+        } => {
             psyche_client::prepare_environment();
 
+            if args.dry_run {
+                return psyche_client::dry_run(&args).await.finish();
+            }
+
             info!(
                 "============ Client Startup at {} ============",
                 OffsetDateTime::now_utc()
@@ -90,6 +110,7 @@ async fn async_main() -> Result<()> {
             let logger = psyche_tui::logging()
                 .with_output(args.logs)
                 .with_log_file(args.write_log.clone())
+                .with_prometheus_exporter(args.metrics_http_port.is_some())
                 .with_metrics_destination(args.oltp_metrics_url.clone().map(|endpoint| {
                     MetricsDestination::OpenTelemetry(OpenTelemetry {
                         endpoint,
@@ -121,12 +142,18 @@ async fn async_main() -> Result<()> {
                 })
                 .init()?;
 
+            let tls = match (tls_ca, tls_insecure) {
+                (Some(ca), _) => Some(ClientTlsConfig::Ca(ca)),
+                (None, true) => Some(ClientTlsConfig::Insecure),
+                (None, false) => None,
+            };
+
             let (cancel, tx_tui_state) = maybe_start_render_loop(
                 (args.logs == LogOutput::TUI).then(|| Tabs::new(Default::default(), &TAB_NAMES)),
             )?;
 
             let (mut app, allowlist, p2p, state_options) =
-                build_app(cancel, server_addr, tx_tui_state, args)
+                build_app(cancel, server_addr, tls, tx_tui_state, args)
                     .await
                     .unwrap();
 
@@ -143,6 +170,15 @@ async fn async_main() -> Result<()> {
 
             Ok(())
         }
+        Commands::GenerateCompletions { shell } => {
+            clap_complete::generate(
+                shell,
+                &mut Args::command(),
+                "psyche-centralized-client",
+                &mut std::io::stdout(),
+            );
+            Ok(())
+        }
     }
 }
 
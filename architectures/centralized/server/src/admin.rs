@@ -0,0 +1,117 @@
+use std::{collections::HashMap, convert::Infallible, net::SocketAddr};
+
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::{
+    Method, Request, Response, StatusCode, body::Incoming, header::CONTENT_TYPE,
+    server::conn::http1, service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use psyche_coordinator::Coordinator;
+use psyche_core::NodeIdentity;
+use serde_json::{Map, Value, json};
+use tokio::{net::TcpListener, sync::watch};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// Runtime state of the coordinator that isn't part of its saved state,
+/// in the same shape as the `status` of the run manager's `json-dump-run`
+pub fn runtime_status(coordinator: &Coordinator, last_seen: &HashMap<NodeIdentity, u64>) -> Value {
+    let round = coordinator.current_round().map(|round| {
+        json!({
+            "height": round.height,
+            "witnesses_received_this_step": round.witnesses.len(),
+            "expected_witnesses_this_step": coordinator.num_witness_nodes(),
+            "bloom_occupancy": round.broadcast_bloom_occupancy(),
+        })
+    });
+    let time_in_current_state_secs = match coordinator.run_state_start_unix_timestamp {
+        0 => None,
+        start => {
+            Some((chrono::Utc::now().timestamp_millis() as f64 / 1000.0 - start as f64).max(0.0))
+        }
+    };
+    let clients = Map::from_iter(last_seen.iter().map(|(id, last_seen)| {
+        let state = coordinator
+            .epoch_state
+            .clients
+            .iter()
+            .find(|client| client.id == *id)
+            .map(|client| client.state.to_string());
+        (
+            id.to_string(),
+            json!({
+                "state": state,
+                "last_seen_unix_timestamp": last_seen,
+            }),
+        )
+    }));
+
+    json!({
+        "run_id": String::from(&coordinator.run_id),
+        "state": coordinator.run_state.to_string(),
+        "epoch": coordinator.progress.epoch,
+        "step": coordinator.progress.step,
+        "time_in_current_state_secs": time_in_current_state_secs,
+        "round": round,
+        "clients": clients,
+    })
+}
+
+/// Serves the latest value of `status` as JSON at `GET /status`, until `cancel` is cancelled.
+pub fn start_admin_server(port: u16, status: watch::Receiver<Value>, cancel: CancellationToken) {
+    tokio::spawn(async move {
+        let addr = SocketAddr::from(([0, 0, 0, 0], port));
+        let listener = match TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                warn!(
+                    "[admin http server] Failed to bind HTTP server on {}: {} -- Continuing without it",
+                    addr, e
+                );
+                return;
+            }
+        };
+        info!("[admin http server] serving /status on {}", addr);
+
+        loop {
+            let stream = tokio::select! {
+                _ = cancel.cancelled() => return,
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!("[admin http server] Failed to accept connection: {}", e);
+                        continue;
+                    }
+                },
+            };
+            let status = status.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |req| serve_status(req, status.clone()));
+                if let Err(e) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    warn!("[admin http server] Connection error: {}", e);
+                }
+            });
+        }
+    });
+}
+
+async fn serve_status(
+    req: Request<Incoming>,
+    status: watch::Receiver<Value>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    if req.method() != Method::GET || req.uri().path() != "/status" {
+        let mut response = Response::new(Full::new(Bytes::new()));
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        return Ok(response);
+    }
+
+    let body = status.borrow().to_string();
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body)))
+        .expect("static response parts are valid"))
+}
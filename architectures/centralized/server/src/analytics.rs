@@ -0,0 +1,232 @@
+use anyhow::{Context, Result, anyhow};
+use psyche_coordinator::{Coordinator, RunState, WitnessMetadata};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::warn;
+
+/// Columns of the per-step analytics files, in order.
+pub const STEP_CSV_HEADER: &str = "step,epoch,loss,clients,witnesses,duration_secs,checkpoint";
+
+/// The file in `save_state_dir` that step analytics of `run_id` are appended to while the server runs.
+pub fn step_analytics_path(save_state_dir: &Path, run_id: &str) -> PathBuf {
+    save_state_dir.join(format!("{run_id}-steps.csv"))
+}
+
+/// What happened in a single training step, as seen by the coordinator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepRow {
+    pub step: u32,
+    pub epoch: u16,
+    /// Mean loss reported by the step's witnesses, if any reported a finite one
+    pub loss: Option<f32>,
+    pub clients: u16,
+    pub witnesses: usize,
+    pub duration_secs: f64,
+    /// Whether the run went into cooldown, and so checkpointed, after this step
+    pub checkpoint: bool,
+}
+
+impl StepRow {
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{:.3},{}",
+            self.step,
+            self.epoch,
+            // left empty rather than written as inf, so it's read as a missing value
+            self.loss.map(|loss| loss.to_string()).unwrap_or_default(),
+            self.clients,
+            self.witnesses,
+            self.duration_secs,
+            self.checkpoint
+        )
+    }
+
+    fn from_csv(line: &str) -> Result<Self> {
+        let fields: Vec<&str> = line.split(',').collect();
+        let [
+            step,
+            epoch,
+            loss,
+            clients,
+            witnesses,
+            duration_secs,
+            checkpoint,
+        ] = fields[..]
+        else {
+            return Err(anyhow!("expected 7 fields, got {}", fields.len()));
+        };
+        Ok(Self {
+            step: step.parse()?,
+            epoch: epoch.parse()?,
+            loss: match loss {
+                "" => None,
+                loss => Some(loss.parse()?),
+            },
+            clients: clients.parse()?,
+            witnesses: witnesses.parse()?,
+            duration_secs: duration_secs.parse()?,
+            checkpoint: checkpoint.parse()?,
+        })
+    }
+}
+
+/// Follows the coordinator from step to step, appending a [`StepRow`] for every finished step.
+pub struct StepRecorder {
+    path: PathBuf,
+    step: u32,
+    started: Option<Instant>,
+    losses: Vec<f32>,
+}
+
+impl StepRecorder {
+    pub fn new(save_state_dir: &Path, coordinator: &Coordinator) -> Self {
+        Self {
+            path: step_analytics_path(save_state_dir, &String::from(&coordinator.run_id)),
+            step: coordinator.progress.step,
+            started: None,
+            losses: Vec::new(),
+        }
+    }
+
+    pub fn witness(&mut self, metadata: &WitnessMetadata) {
+        if metadata.step == self.step && metadata.loss.is_finite() {
+            self.losses.push(metadata.loss);
+        }
+    }
+
+    pub fn update(&mut self, coordinator: &Coordinator) {
+        if coordinator.progress.step == self.step {
+            if self.started.is_none() && coordinator.run_state == RunState::RoundTrain {
+                self.started = Some(Instant::now());
+            }
+            return;
+        }
+        // the finished step's round stays current, unless the next round started in the same tick
+        let round = match coordinator.run_state {
+            RunState::RoundTrain => coordinator.previous_round(),
+            _ => coordinator.current_round(),
+        };
+        let finished = coordinator.progress.step > self.step;
+        if let (true, Some(started), Some(round)) = (finished, self.started, round) {
+            let row = StepRow {
+                step: self.step,
+                epoch: coordinator.progress.epoch,
+                loss: (!self.losses.is_empty())
+                    .then(|| self.losses.iter().sum::<f32>() / self.losses.len() as f32),
+                clients: round.clients_len,
+                witnesses: round.witnesses.len(),
+                duration_secs: started.elapsed().as_secs_f64(),
+                checkpoint: coordinator.run_state == RunState::Cooldown,
+            };
+            if let Err(err) = self.append(&row) {
+                warn!("Error saving step analytics: {err:#}");
+            }
+        }
+        self.step = coordinator.progress.step;
+        self.started = None;
+        self.losses.clear();
+    }
+
+    fn append(&self, row: &StepRow) -> Result<()> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        if file.metadata()?.len() == 0 {
+            writeln!(file, "{STEP_CSV_HEADER}")?;
+        }
+        writeln!(file, "{}", row.to_csv())?;
+        Ok(())
+    }
+}
+
+/// Writes the step analytics of `run_id` saved in `save_state_dir` to `output`, one row per step
+/// in step order. If a step was recorded more than once, e.g. after the server restarted from an
+/// earlier state, its latest row is kept.
+pub fn export_csv(save_state_dir: &Path, run_id: &str, output: &Path) -> Result<usize> {
+    let path = step_analytics_path(save_state_dir, run_id);
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read step analytics from {}", path.display()))?;
+    let mut rows = BTreeMap::new();
+    for (line_index, line) in contents.lines().enumerate() {
+        if line.is_empty() || line == STEP_CSV_HEADER {
+            continue;
+        }
+        let row = StepRow::from_csv(line).with_context(|| {
+            format!(
+                "Invalid row on line {} of {}",
+                line_index + 1,
+                path.display()
+            )
+        })?;
+        rows.insert(row.step, row);
+    }
+
+    let mut csv = format!("{STEP_CSV_HEADER}\n");
+    for row in rows.values() {
+        csv.push_str(&row.to_csv());
+        csv.push('\n');
+    }
+    std::fs::write(output, csv).with_context(|| format!("Failed to write {}", output.display()))?;
+    Ok(rows.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(step: u32, loss: Option<f32>) -> StepRow {
+        StepRow {
+            step,
+            epoch: 1,
+            loss,
+            clients: 4,
+            witnesses: 2,
+            duration_secs: 12.5,
+            checkpoint: false,
+        }
+    }
+
+    #[test]
+    fn test_step_row_csv_round_trip() {
+        for row in [row(3, Some(2.75)), row(4, None)] {
+            let line = row.to_csv();
+            assert_eq!(line.split(',').count(), STEP_CSV_HEADER.split(',').count());
+            assert_eq!(StepRow::from_csv(&line).unwrap(), row);
+        }
+        assert_eq!(row(4, None).to_csv(), "4,1,,4,2,12.500,false");
+        assert!(StepRow::from_csv("4,1,,4,2,12.500").is_err());
+        assert!(StepRow::from_csv("4,1,nope,4,2,12.500,false").is_err());
+    }
+
+    #[test]
+    fn test_export_csv_keeps_latest_row_per_step() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("export.csv");
+        // the server restarted from a state saved before step 2 was recorded, and recorded it again
+        let recorded = [
+            STEP_CSV_HEADER.to_string(),
+            row(2, Some(3.0)).to_csv(),
+            row(3, Some(2.5)).to_csv(),
+            row(1, Some(4.0)).to_csv(),
+            row(2, Some(2.9)).to_csv(),
+        ];
+        std::fs::write(
+            step_analytics_path(dir.path(), "run"),
+            recorded.join("\n") + "\n",
+        )
+        .unwrap();
+
+        assert_eq!(export_csv(dir.path(), "run", &output).unwrap(), 3);
+        let exported = std::fs::read_to_string(&output).unwrap();
+        let mut lines = exported.lines();
+        assert_eq!(lines.next(), Some(STEP_CSV_HEADER));
+        let rows: Vec<StepRow> = lines.map(|line| StepRow::from_csv(line).unwrap()).collect();
+        assert_eq!(
+            rows,
+            [row(1, Some(4.0)), row(2, Some(2.9)), row(3, Some(2.5))]
+        );
+    }
+}
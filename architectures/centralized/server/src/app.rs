@@ -1,38 +1,43 @@
-use anyhow::{Result, anyhow, bail};
+use anyhow::{Context, Result, anyhow, bail};
 use async_trait::async_trait;
 use psyche_centralized_shared::{ClientToServerMessage, ServerToClientMessage};
 use psyche_coordinator::model::{self, Checkpoint, LLM, LLMTrainingDataLocation, Model};
 use psyche_coordinator::{
-    Client, ClientState, Coordinator, CoordinatorError, HealthChecks, Round, RunState,
-    SOLANA_MAX_NUM_CLIENTS, TickResult,
+    Client, ClientState, ConfigUpdate, Coordinator, CoordinatorConfig, CoordinatorError,
+    HealthChecks, ImmutableFieldChanged, Round, RunState, SOLANA_MAX_NUM_CLIENTS, TickResult,
 };
 
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use psyche_core::{FixedVec, NodeIdentity, Shuffle, SizedIterator, TokenSize};
 use psyche_data_provider::{
     DataProviderTcpServer, DataServerTui, LocalDataProvider, download_model_from_gcs_async,
     download_model_repo_async,
 };
-use psyche_network::{ClientNotification, PublicKey, TcpServer};
+#[cfg(feature = "testing")]
+use psyche_network::ServerFault;
+use psyche_network::{ClientNotification, PublicKey, ServerTlsConfig, TcpServer};
 use psyche_tui::{
     CustomWidget, MaybeTui, TabbedWidget, logging::LoggerWidget, maybe_start_render_loop,
 };
 use psyche_watcher::{CoordinatorTui, OpportunisticData};
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::hash::{DefaultHasher, Hasher};
 use std::net::{Ipv4Addr, SocketAddr};
 use std::ops::ControlFlow;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::sync::Notify;
-use tokio::sync::mpsc::{Receiver, Sender, UnboundedSender, channel};
+use tokio::sync::mpsc::{Receiver, Sender, UnboundedReceiver, UnboundedSender, channel};
+use tokio::sync::{Notify, watch};
 use tokio::time::{MissedTickBehavior, interval};
 use tokio::{select, time::Interval};
 use tokio_util::sync::CancellationToken;
-use tracing::{Instrument, debug, info, info_span, warn};
+use tracing::{Instrument, debug, error, info, info_span, warn};
 
+use crate::admin::{runtime_status, start_admin_server};
+use crate::analytics::StepRecorder;
 use crate::dashboard::{DashboardState, DashboardTui};
 
 pub(super) type TabWidgetTypes = (
@@ -98,11 +103,61 @@ pub struct App {
     backend: Backend,
     training_data_server: Option<(Sender<Coordinator>, DataServer)>,
     save_state_dir: Option<PathBuf>,
+    step_recorder: Option<StepRecorder>,
     coordinator_writer: Option<UnboundedSender<Coordinator>>,
     last_coordinator_hash: u64,
     original_warmup_time: u64,
     withdraw_on_disconnect: bool,
     pause: Option<Arc<Notify>>,
+    state_watcher: Option<StateFileWatcher>,
+    /// Unix timestamp of the last message from each client
+    last_seen: HashMap<NodeIdentity, u64>,
+    admin_status: Option<watch::Sender<serde_json::Value>>,
+}
+
+/// Watches the coordinator state TOML so config changes can be applied without a restart
+struct StateFileWatcher {
+    path: PathBuf,
+    /// The state as last read from the file, before any runtime changes
+    loaded: Coordinator,
+    /// A reloaded config whose `witness_nodes` and `min_clients` wait for the epoch to end
+    deferred_config: Option<CoordinatorConfig>,
+    events: UnboundedReceiver<notify::Result<notify::Event>>,
+    _watcher: RecommendedWatcher,
+}
+
+impl StateFileWatcher {
+    fn new(path: PathBuf, loaded: Coordinator) -> Result<Self> {
+        let (tx, events) = tokio::sync::mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        })
+        .context("Failed to create state file watcher")?;
+        // watch the directory rather than the file, since editors often
+        // save by replacing the file, which would end a watch on the file itself
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch state file {}", path.display()))?;
+        Ok(Self {
+            path,
+            loaded,
+            deferred_config: None,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    fn is_state_file_change(&self, event: &notify::Event) -> bool {
+        matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+            && event
+                .paths
+                .iter()
+                .any(|path| path.file_name() == self.path.file_name())
+    }
 }
 
 /// Methods intended for testing purposes only.
@@ -148,6 +203,11 @@ impl App {
     pub fn get_coordinator(&self) -> Coordinator {
         self.coordinator
     }
+
+    #[cfg(feature = "testing")]
+    pub fn get_fault_injector(&self) -> UnboundedSender<ServerFault> {
+        self.backend.net_server.fault_injector()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -156,6 +216,9 @@ pub struct DataServerInfo {
     pub token_size: TokenSize,
     pub seq_len: usize,
     pub shuffle_seed: [u8; 32],
+    /// number of recently served samples to keep in memory, 0 to disable the cache
+    #[serde(default)]
+    pub sample_cache_size: usize,
 }
 
 impl App {
@@ -169,8 +232,15 @@ impl App {
         events_dir: Option<PathBuf>,
         init_warmup_time: Option<u64>,
         withdraw_on_disconnect: bool,
+        tls: Option<ServerTlsConfig>,
+        state_path: Option<PathBuf>,
+        admin_port: Option<u16>,
     ) -> Result<Self> {
         async {
+            let state_watcher = state_path
+                .map(|path| StateFileWatcher::new(path, coordinator))
+                .transpose()?;
+
             Self::reset_ephemeral(&mut coordinator);
 
             debug!("potentially launching data server...");
@@ -220,7 +290,8 @@ impl App {
                             dir,
                             seq_len,
                             shuffle_seed,
-                            token_size
+                            token_size,
+                            sample_cache_size,
                         } = data_server_config.ok_or_else(|| anyhow!(
                             "Coordinator state requires we host training data, but no --data-config passed."
                         ))?;
@@ -230,7 +301,8 @@ impl App {
                             token_size,
                             seq_len,
                             Shuffle::Seeded(shuffle_seed),
-                        )?;
+                        )?
+                        .with_cache(sample_cache_size);
 
                         let (tx, backend) = ChannelCoordinatorBackend::new();
                         let data_server =
@@ -262,11 +334,12 @@ impl App {
             update_tui_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
             let net_server =
-                TcpServer::<ClientToServerMessage, ServerToClientMessage>::start(
+                TcpServer::<ClientToServerMessage, ServerToClientMessage>::start_with_tls(
                     SocketAddr::new(
                         std::net::IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
                         coordinator_server_port.unwrap_or(0),
                     ),
+                    tls,
                 )
                 .await?;
 
@@ -325,6 +398,16 @@ impl App {
                 None
             };
 
+            let step_recorder = save_state_dir
+                .as_deref()
+                .map(|dir| StepRecorder::new(dir, &coordinator));
+
+            let admin_status = admin_port.map(|port| {
+                let (tx, rx) = watch::channel(runtime_status(&coordinator, &HashMap::new()));
+                start_admin_server(port, rx, cancel.clone());
+                tx
+            });
+
             Ok(Self {
                 cancel,
                 training_data_server,
@@ -337,11 +420,15 @@ impl App {
                     pending_clients: HashSet::new(),
                 },
                 save_state_dir,
+                step_recorder,
                 coordinator_writer,
                 last_coordinator_hash: 0,
                 original_warmup_time,
                 withdraw_on_disconnect,
                 pause,
+                state_watcher,
+                last_seen: HashMap::new(),
+                admin_status,
             })
         }.instrument(info_span!("App::new")).await
     }
@@ -370,6 +457,9 @@ impl App {
                     ClientNotification::Disconnected(from) => {
                         self.on_disconnect(from)?;
                     }
+                    ClientNotification::ConnectionUpgraded(addr) => {
+                        debug!("TLS connection established with {addr}");
+                    }
                 }
             }
             _ = self.tick_interval.tick() => {
@@ -388,10 +478,144 @@ impl App {
             _ = async { self.pause.as_ref().unwrap().notified().await }, if self.pause.is_some() => {
                 self.pause();
             }
+            Some(event) = async { self.state_watcher.as_mut().unwrap().events.recv().await }, if self.state_watcher.is_some() => {
+                match event {
+                    Ok(event) => {
+                        if self.state_watcher.as_ref().is_some_and(|w| w.is_state_file_change(&event)) {
+                            self.reload_state_file().await;
+                        }
+                    }
+                    Err(err) => warn!("Error watching state file: {err}"),
+                }
+            }
         }
         Ok(ControlFlow::Continue(()))
     }
 
+    /// Re-reads the state file and applies any config changes that are safe
+    /// to make mid-run, broadcasting them to clients.
+    async fn reload_state_file(&mut self) {
+        let Some(watcher) = &self.state_watcher else {
+            return;
+        };
+        let path = watcher.path.clone();
+        let loaded = watcher.loaded;
+
+        let new_state: Coordinator = match std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| Ok(toml::from_str(&contents)?))
+        {
+            Ok(state) => state,
+            Err(err) => {
+                warn!("Failed to reload state file {}: {err:#}", path.display());
+                return;
+            }
+        };
+        if let Err(err) = new_state.config.check_error() {
+            warn!(
+                "Ignoring invalid coordinator config in {}: {err:?}",
+                path.display()
+            );
+            return;
+        }
+        if new_state.run_id != loaded.run_id {
+            error!(
+                "Not reloading {}: {}",
+                path.display(),
+                ImmutableFieldChanged { field: "run_id" }
+            );
+            return;
+        }
+        if toml::Value::try_from(new_state.model).ok() != toml::Value::try_from(loaded.model).ok() {
+            error!(
+                "Not reloading {}: {}",
+                path.display(),
+                ImmutableFieldChanged { field: "model" }
+            );
+            return;
+        }
+
+        let new_config = new_state.config;
+        let mut old_config = self.coordinator.config;
+        // the running warmup time may be the temporary --init-warmup-time override
+        let warmup_override =
+            (old_config.warmup_time != self.original_warmup_time).then_some(old_config.warmup_time);
+        old_config.warmup_time = self.original_warmup_time;
+        if new_config == old_config {
+            debug!("State file changed, but its config is unchanged");
+            return;
+        }
+
+        let update = match self.coordinator.update_mutable_config(new_config) {
+            Ok(update) => update,
+            Err(err) => {
+                error!("Not reloading {}: {err}", path.display());
+                return;
+            }
+        };
+        info!(
+            "Reloaded coordinator config from {}: {old_config:?} -> {new_config:?}",
+            path.display()
+        );
+        if update == ConfigUpdate::Deferred {
+            info!(
+                witness_nodes = new_config.witness_nodes,
+                min_clients = new_config.min_clients,
+                "witness_nodes and min_clients will change once the epoch ends"
+            );
+        }
+        self.original_warmup_time = new_config.warmup_time;
+        if let Some(warmup_time) = warmup_override {
+            if !self.coordinator.active() {
+                self.coordinator.config.warmup_time = warmup_time;
+            }
+        }
+        if let Some(watcher) = &mut self.state_watcher {
+            watcher.loaded.config = new_config;
+            watcher.deferred_config = (update == ConfigUpdate::Deferred).then_some(new_config);
+        }
+
+        self.broadcast_config().await;
+        self.post_state_change(true).await;
+    }
+
+    /// Applies the `witness_nodes` and `min_clients` of a reloaded config once its epoch has ended
+    async fn apply_deferred_config(&mut self) {
+        let Some(watcher) = &mut self.state_watcher else {
+            return;
+        };
+        let Some(mut config) = watcher.deferred_config.take() else {
+            return;
+        };
+        // everything else was already applied, including any warmup time override
+        config.warmup_time = self.coordinator.config.warmup_time;
+        match self.coordinator.update_mutable_config(config) {
+            Ok(ConfigUpdate::Applied) => {
+                info!(
+                    witness_nodes = config.witness_nodes,
+                    min_clients = config.min_clients,
+                    "Applied deferred coordinator config changes"
+                );
+                self.broadcast_config().await;
+            }
+            Ok(ConfigUpdate::Deferred) => watcher.deferred_config = Some(config),
+            Err(err) => error!("Dropping deferred config update: {err}"),
+        }
+    }
+
+    async fn broadcast_config(&mut self) {
+        if let Err(err) = self
+            .backend
+            .net_server
+            .broadcast(ServerToClientMessage::ConfigUpdated(
+                self.coordinator.config,
+            ))
+            .await
+        {
+            warn!("Failed to broadcast config update: {err:#}");
+        }
+    }
+
     async fn update_tui(&mut self) -> Result<()> {
         if let Some(tx_tui_state) = &self.tx_tui_state {
             let states = (
@@ -430,6 +654,7 @@ impl App {
 
     async fn on_client_message(&mut self, from: PublicKey, event: ClientToServerMessage) {
         let from_identity = NodeIdentity::from_single_key(*from.as_bytes());
+        self.last_seen.insert(from_identity, Self::get_timestamp());
         let broadcast = match event {
             ClientToServerMessage::Join { run_id } => {
                 // TODO: check whitelist
@@ -445,9 +670,18 @@ impl App {
             ClientToServerMessage::Witness(witness) => {
                 let state_before = self.coordinator.run_state;
                 if let Err(error) = match *witness {
-                    OpportunisticData::WitnessStep(witness, _witness_metadata) => self
-                        .coordinator
-                        .witness(&from_identity, witness, Self::get_timestamp()),
+                    OpportunisticData::WitnessStep(witness, witness_metadata) => {
+                        let result = self.coordinator.witness(
+                            &from_identity,
+                            witness,
+                            Self::get_timestamp(),
+                        );
+                        // only witnesses the coordinator accepted count towards the step's loss
+                        if let (Ok(_), Some(step_recorder)) = (&result, &mut self.step_recorder) {
+                            step_recorder.witness(&witness_metadata);
+                        }
+                        result
+                    }
                     OpportunisticData::WarmupStep(witness) => self.coordinator.warmup_witness(
                         &from_identity,
                         witness,
@@ -536,7 +770,11 @@ impl App {
             Ok(TickResult::Ticked) | Err(CoordinatorError::Halted) => {}
             Err(err) => warn!("Coordinator tick error: {err}"),
         }
+        self.apply_deferred_config().await;
         self.post_state_change(true).await;
+        if let Some(admin_status) = &self.admin_status {
+            admin_status.send_replace(runtime_status(&self.coordinator, &self.last_seen));
+        }
     }
 
     fn get_timestamp() -> u64 {
@@ -551,6 +789,9 @@ impl App {
             // reset to original values if we changed them to something special for init
             self.coordinator.config.warmup_time = self.original_warmup_time;
         }
+        if let Some(step_recorder) = &mut self.step_recorder {
+            step_recorder.update(&self.coordinator);
+        }
         if broadcast {
             if let Err(err) = self
                 .backend
@@ -1,2 +1,4 @@
+pub mod admin;
+pub mod analytics;
 pub mod app;
 pub mod dashboard;
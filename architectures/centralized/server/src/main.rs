@@ -1,10 +1,13 @@
+mod admin;
+mod analytics;
 mod app;
 mod dashboard;
 
 use anyhow::{Context, Result, bail};
 use app::{App, DataServerInfo};
-use clap::{ArgAction, Parser};
+use clap::{ArgAction, CommandFactory, Parser};
 use psyche_coordinator::Coordinator;
+use psyche_network::ServerTlsConfig;
 use psyche_tui::{
     LogOutput, ServiceInfo,
     logging::{MetricsDestination, OpenTelemetry, RemoteLogsDestination, TraceDestination},
@@ -40,12 +43,26 @@ enum Commands {
         #[command(flatten)]
         run_args: RunArgs,
     },
+    /// Writes the per-step analytics the server saved for a run to a CSV file.
+    /// Steps are recorded in `save_state_dir` while the server runs.
+    ExportCsv {
+        /// Id of the run to export.
+        run_id: String,
+        /// Path of the CSV file to write.
+        output: PathBuf,
+        /// The `--save-state-dir` the server ran with.
+        #[clap(long)]
+        save_state_dir: PathBuf,
+    },
     // Prints the help, optionally as markdown. Used for docs generation.
     #[clap(hide = true)]
     PrintAllHelp {
         #[arg(long, required = true)]
         markdown: bool,
     },
+    // Prints a shell completion script. Used for packaging.
+    #[clap(hide = true)]
+    GenerateCompletions { shell: clap_complete::Shell },
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -96,6 +113,18 @@ struct RunArgs {
     )]
     withdraw_on_disconnect: bool,
 
+    /// PEM certificate to serve client connections over TLS. Requires --tls-key.
+    #[clap(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --tls-cert.
+    #[clap(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Port to serve the coordinator's runtime status as JSON at `GET /status`, e.g. for monitoring.
+    #[clap(long)]
+    admin_port: Option<u16>,
+
     /// An auth header string for an opentelemetry endpoint. Used for both logging and metrics.
     #[clap(long, env)]
     pub oltp_auth_header: Option<String>,
@@ -175,7 +204,7 @@ async fn main() -> Result<()> {
             }
         }
         Commands::Run { run_args } => {
-            let config = load_config_state(run_args.state, run_args.data_config);
+            let config = load_config_state(run_args.state.clone(), run_args.data_config);
             let logger = psyche_tui::logging::logging()
                 .with_output(if run_args.tui {
                     LogOutput::TUI
@@ -223,6 +252,12 @@ async fn main() -> Result<()> {
                         run_args.events_dir,
                         run_args.init_warmup_time,
                         run_args.withdraw_on_disconnect,
+                        run_args
+                            .tls_cert
+                            .zip(run_args.tls_key)
+                            .map(|(cert, key)| ServerTlsConfig { cert, key }),
+                        Some(run_args.state),
+                        run_args.admin_port,
                     )
                     .await?
                     .run()
@@ -232,6 +267,15 @@ async fn main() -> Result<()> {
             }
             logger.shutdown()?;
         }
+        Commands::ExportCsv {
+            run_id,
+            output,
+            save_state_dir,
+        } => {
+            let _ = psyche_tui::logging::logging().init()?;
+            let rows = analytics::export_csv(&save_state_dir, &run_id, &output)?;
+            info!("Wrote {rows} steps of run {run_id} to {}", output.display());
+        }
         Commands::PrintAllHelp { markdown } => {
             // This is a required argument for the time being.
             assert!(markdown);
@@ -240,6 +284,15 @@ async fn main() -> Result<()> {
 
             return Ok(());
         }
+        Commands::GenerateCompletions { shell } => {
+            clap_complete::generate(
+                shell,
+                &mut Args::command(),
+                "psyche-centralized-server",
+                &mut std::io::stdout(),
+            );
+            return Ok(());
+        }
     }
 
     Ok(())
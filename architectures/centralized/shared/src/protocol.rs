@@ -1,4 +1,4 @@
-use psyche_coordinator::{Coordinator, HealthChecks, model};
+use psyche_coordinator::{Coordinator, CoordinatorConfig, HealthChecks, model};
 use psyche_watcher::OpportunisticData;
 use serde::{Deserialize, Serialize};
 
@@ -13,4 +13,6 @@ pub enum ClientToServerMessage {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ServerToClientMessage {
     Coordinator(Box<Coordinator>),
+    /// The server reloaded its state file and applied these config changes
+    ConfigUpdated(CoordinatorConfig),
 }
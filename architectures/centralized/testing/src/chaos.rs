@@ -0,0 +1,88 @@
+//! Fault injection for centralized runs.
+//!
+//! [`ChaosController`] drives the coordinator's [`TcpServer`] through its fault
+//! injection channel to disconnect clients or drop their messages, and holds
+//! per-blob download delays that clients spawned with
+//! [`ClientHandle::new_with_download_delays`] pick up.
+//!
+//! [`TcpServer`]: psyche_network::TcpServer
+//! [`ClientHandle::new_with_download_delays`]: crate::client::ClientHandle::new_with_download_delays
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use psyche_core::NodeIdentity;
+use psyche_network::{DownloadDelays, Hash, PublicKey, ServerFault};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+pub struct ChaosController {
+    cancel: CancellationToken,
+    faults: mpsc::UnboundedSender<ServerFault>,
+    download_delays: DownloadDelays,
+}
+
+impl ChaosController {
+    /// All injected faults are cleared once `cancel` is cancelled.
+    pub fn new(cancel: CancellationToken, faults: mpsc::UnboundedSender<ServerFault>) -> Self {
+        let download_delays = DownloadDelays::default();
+        tokio::spawn({
+            let cancel = cancel.clone();
+            let faults = faults.clone();
+            let download_delays = download_delays.clone();
+            async move {
+                cancel.cancelled().await;
+                info!("Clearing injected faults");
+                let _ = faults.send(ServerFault::Clear);
+                download_delays.lock().unwrap().clear();
+            }
+        });
+        Self {
+            cancel,
+            faults,
+            download_delays,
+        }
+    }
+
+    /// Drops the node's connection to the server, refusing to let it back in until `duration` has passed
+    pub fn disconnect_client(&self, node_id: NodeIdentity, duration: Duration) -> Result<()> {
+        self.send(ServerFault::Disconnect {
+            client: public_key(&node_id)?,
+            duration,
+        })
+    }
+
+    /// Silently drops `fraction` of the messages between the node and the server, in both directions
+    pub fn drop_message_rate(&self, node_id: NodeIdentity, fraction: f64) -> Result<()> {
+        self.send(ServerFault::DropMessages {
+            client: public_key(&node_id)?,
+            fraction,
+        })
+    }
+
+    /// Makes every client sharing our download delays wait `extra_delay` before fetching this blob
+    pub fn inject_slow_download(&self, hash: Hash, extra_delay: Duration) {
+        self.download_delays
+            .lock()
+            .unwrap()
+            .insert(hash, extra_delay);
+    }
+
+    pub fn download_delays(&self) -> DownloadDelays {
+        self.download_delays.clone()
+    }
+
+    fn send(&self, fault: ServerFault) -> Result<()> {
+        if self.cancel.is_cancelled() {
+            return Ok(());
+        }
+        self.faults
+            .send(fault)
+            .context("Coordinator server has shut down")
+    }
+}
+
+fn public_key(node_id: &NodeIdentity) -> Result<PublicKey> {
+    PublicKey::from_bytes(node_id.signer()).context("Node identity isn't a valid public key")
+}
@@ -5,7 +5,7 @@ use psyche_centralized_client::app::App as ClientApp;
 use psyche_centralized_client::app::build_app as build_client_app;
 use psyche_client::NC;
 use psyche_client::RunInitConfig;
-use psyche_network::allowlist;
+use psyche_network::{DownloadDelays, allowlist};
 use tokio::select;
 use tokio::task::JoinHandle;
 use tracing::debug;
@@ -26,6 +26,7 @@ impl Client {
             client_app_params.cancel,
             client_app_params.server_addr,
             None,
+            None,
             client_app_params.train_args,
         )
         .await
@@ -45,6 +46,7 @@ impl Client {
             client_app_params.cancel,
             client_app_params.server_addr,
             None,
+            None,
             client_app_params.train_args,
         )
         .await
@@ -53,6 +55,16 @@ impl Client {
         (Self { inner: client_app }, allowlist, p2p, state_options)
     }
 
+    pub async fn new_with_download_delays(
+        server_port: u16,
+        run_id: &str,
+        download_delays: DownloadDelays,
+    ) -> (Self, allowlist::AllowDynamic, NC, RunInitConfig) {
+        let (client, allowlist, mut p2p, state_options) = Self::default(server_port, run_id).await;
+        p2p.set_download_delays(download_delays);
+        (client, allowlist, p2p, state_options)
+    }
+
     pub async fn run(
         &mut self,
         allowlist: allowlist::AllowDynamic,
@@ -100,4 +112,17 @@ impl ClientHandle {
         debug!("new client spawned!");
         Self { client_handle }
     }
+
+    pub async fn new_with_download_delays(
+        server_port: u16,
+        run_id: &str,
+        download_delays: DownloadDelays,
+    ) -> Self {
+        let (mut client, allowlist, p2p, state_options) =
+            Client::new_with_download_delays(server_port, run_id, download_delays).await;
+        let client_handle =
+            tokio::spawn(async move { client.run(allowlist, p2p, state_options).await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        Self { client_handle }
+    }
 }
@@ -0,0 +1,188 @@
+//! Simulated network latency between clients and the coordinator server.
+//!
+//! [`LatencySimulator`] is a TCP proxy: clients connect to its port instead of
+//! the server's, and every chunk of data going through it in either direction
+//! is held back for a delay drawn from a [`DelayDistribution`] before being
+//! forwarded. Ordering within a connection is preserved.
+//!
+//! In release builds the proxy still forwards traffic but never adds delay.
+
+use std::{ops::Range, time::Duration};
+
+use anyhow::Result;
+use rand::Rng;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+    task::JoinHandle,
+    time::Instant,
+};
+use tracing::{debug, warn};
+
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone)]
+pub enum DelayDistribution {
+    Constant(Duration),
+    Uniform(Range<Duration>),
+    /// Normally distributed delay, clamped at zero
+    Normal {
+        mean_ms: f64,
+        std_ms: f64,
+    },
+}
+
+impl DelayDistribution {
+    pub fn sample(&self, rng: &mut impl Rng) -> Duration {
+        match self {
+            DelayDistribution::Constant(delay) => *delay,
+            DelayDistribution::Uniform(range) if range.is_empty() => range.start,
+            DelayDistribution::Uniform(range) => rng.random_range(range.clone()),
+            DelayDistribution::Normal { mean_ms, std_ms } => {
+                // Box-Muller transform
+                let u1: f64 = 1.0 - rng.random::<f64>();
+                let u2: f64 = rng.random();
+                let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+                Duration::from_secs_f64((mean_ms + z * std_ms).max(0.0) / 1000.0)
+            }
+        }
+    }
+}
+
+pub struct LatencySimulatorBuilder {
+    target_port: u16,
+    client_to_server: DelayDistribution,
+    server_to_client: DelayDistribution,
+}
+
+impl LatencySimulatorBuilder {
+    /// Sets the delay for traffic in both directions
+    pub fn delay(mut self, delay: DelayDistribution) -> Self {
+        self.client_to_server = delay.clone();
+        self.server_to_client = delay;
+        self
+    }
+
+    pub fn client_to_server_delay(mut self, delay: DelayDistribution) -> Self {
+        self.client_to_server = delay;
+        self
+    }
+
+    pub fn server_to_client_delay(mut self, delay: DelayDistribution) -> Self {
+        self.server_to_client = delay;
+        self
+    }
+
+    /// Starts listening on a random local port
+    pub async fn build(self) -> Result<LatencySimulator> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let target = format!("127.0.0.1:{}", self.target_port);
+        let client_to_server = effective_delay(self.client_to_server);
+        let server_to_client = effective_delay(self.server_to_client);
+
+        let accept_handle = tokio::spawn(async move {
+            while let Ok((client, peer)) = listener.accept().await {
+                let target = target.clone();
+                let client_to_server = client_to_server.clone();
+                let server_to_client = server_to_client.clone();
+                tokio::spawn(async move {
+                    let server = match TcpStream::connect(&target).await {
+                        Ok(server) => server,
+                        Err(err) => {
+                            warn!("Latency simulator failed to connect to {target}: {err}");
+                            return;
+                        }
+                    };
+                    debug!("Latency simulator proxying {peer} to {target}");
+                    let (client_read, client_write) = client.into_split();
+                    let (server_read, server_write) = server.into_split();
+                    tokio::join!(
+                        forward_delayed(client_read, server_write, client_to_server),
+                        forward_delayed(server_read, client_write, server_to_client),
+                    );
+                });
+            }
+        });
+
+        Ok(LatencySimulator {
+            port,
+            accept_handle,
+        })
+    }
+}
+
+/// Delays are only simulated in debug builds
+fn effective_delay(delay: DelayDistribution) -> DelayDistribution {
+    if cfg!(debug_assertions) {
+        delay
+    } else {
+        DelayDistribution::Constant(Duration::ZERO)
+    }
+}
+
+/// A delaying TCP proxy in front of the coordinator server.
+/// Stops accepting connections when dropped.
+pub struct LatencySimulator {
+    port: u16,
+    accept_handle: JoinHandle<()>,
+}
+
+impl LatencySimulator {
+    pub fn builder(target_port: u16) -> LatencySimulatorBuilder {
+        LatencySimulatorBuilder {
+            target_port,
+            client_to_server: DelayDistribution::Constant(Duration::ZERO),
+            server_to_client: DelayDistribution::Constant(Duration::ZERO),
+        }
+    }
+
+    /// The port clients should connect to instead of the server's
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl Drop for LatencySimulator {
+    fn drop(&mut self) {
+        self.accept_handle.abort();
+    }
+}
+
+/// Copies `reader` to `writer`, holding each chunk back until its delay has passed
+async fn forward_delayed(
+    mut reader: impl AsyncRead + Unpin,
+    mut writer: impl AsyncWrite + Unpin + Send + 'static,
+    delay: DelayDistribution,
+) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<(Instant, Vec<u8>)>();
+
+    let writer_task = tokio::spawn(async move {
+        // never deliver before a chunk that was read earlier
+        let mut last_deadline = Instant::now();
+        while let Some((deadline, chunk)) = rx.recv().await {
+            last_deadline = last_deadline.max(deadline);
+            tokio::time::sleep_until(last_deadline).await;
+            if writer.write_all(&chunk).await.is_err() {
+                break;
+            }
+        }
+        let _ = writer.shutdown().await;
+    });
+
+    let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+    loop {
+        match reader.read(&mut buffer).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                let deadline = Instant::now() + delay.sample(&mut rand::rng());
+                if tx.send((deadline, buffer[..n].to_vec())).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    drop(tx);
+    let _ = writer_task.await;
+}
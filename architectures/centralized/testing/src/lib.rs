@@ -1,4 +1,6 @@
+pub mod chaos;
 pub mod client;
+pub mod latency;
 pub mod server;
 pub mod test_utils;
 
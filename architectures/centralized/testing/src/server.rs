@@ -4,10 +4,12 @@ use bytemuck::Zeroable;
 use psyche_centralized_server::app::App as ServerApp;
 use psyche_coordinator::{Client, Round};
 use psyche_coordinator::{
-    Coordinator, CoordinatorConfig, CoordinatorEpochState, RunState, SOLANA_MAX_NUM_CLIENTS,
+    Coordinator, CoordinatorConfig, CoordinatorEpochState, MixedPrecisionDtype, RunState,
+    SOLANA_MAX_NUM_CLIENTS,
     model::{Checkpoint, LLM, Model},
 };
-use psyche_core::{FixedVec, NodeIdentity};
+use psyche_core::{DistroExtensions, FixedVec, NodeIdentity};
+use psyche_network::ServerFault;
 use std::{collections::HashSet, mem::Discriminant, ops::ControlFlow};
 use tokio::{
     select,
@@ -49,6 +51,9 @@ enum TestingQueryMsg {
     Coordinator {
         respond_to: oneshot::Sender<Coordinator>,
     },
+    FaultInjector {
+        respond_to: oneshot::Sender<mpsc::UnboundedSender<ServerFault>>,
+    },
 }
 
 struct CoordinatorServer {
@@ -79,6 +84,11 @@ impl CoordinatorServer {
             witness_nodes,
             total_steps: 100,
             waiting_for_members_extra_time: 2,
+            straggler_fraction: 0.0,
+            straggler_grace_period_secs: 0,
+            adaptive_witness_quorum: false,
+            mixed_precision: MixedPrecisionDtype::Disabled,
+            distro_extensions: DistroExtensions::default(),
             epoch_time: 30,
         };
 
@@ -107,6 +117,9 @@ impl CoordinatorServer {
             None,
             Some(WARMUP_TIME),
             true,
+            None,
+            None,
+            None,
         )
         .await
         .unwrap();
@@ -164,6 +177,10 @@ impl CoordinatorServer {
                 let coordinator = self.inner.get_coordinator();
                 respond_to.send(coordinator).unwrap();
             }
+            TestingQueryMsg::FaultInjector { respond_to } => {
+                let fault_injector = self.inner.get_fault_injector();
+                respond_to.send(fault_injector).unwrap();
+            }
         }
     }
 
@@ -298,4 +315,11 @@ impl CoordinatorServerHandle {
         let _ = self.query_chan_sender.send(msg).await;
         recv.await.expect("Coordinator actor task has been killed")
     }
+
+    pub async fn get_fault_injector(&self) -> mpsc::UnboundedSender<ServerFault> {
+        let (send, recv) = oneshot::channel::<mpsc::UnboundedSender<ServerFault>>();
+        let msg = TestingQueryMsg::FaultInjector { respond_to: send };
+        let _ = self.query_chan_sender.send(msg).await;
+        recv.await.expect("Coordinator actor task has been killed")
+    }
 }
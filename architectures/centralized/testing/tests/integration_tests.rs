@@ -2,7 +2,9 @@ use std::time::Duration;
 
 use psyche_centralized_testing::{
     COOLDOWN_TIME, MAX_ROUND_TRAIN_TIME, ROUND_WITNESS_TIME,
+    chaos::ChaosController,
     client::ClientHandle,
+    latency::{DelayDistribution, LatencySimulator},
     server::CoordinatorServerHandle,
     test_utils::{
         assert_with_retries, assert_witnesses_healthy_score, spawn_clients,
@@ -13,6 +15,7 @@ use psyche_coordinator::{
     RunState,
     model::{Checkpoint, HubRepo},
 };
+use tokio_util::sync::CancellationToken;
 use tracing::info;
 
 #[test_log::test(tokio::test(flavor = "multi_thread"))]
@@ -738,3 +741,128 @@ async fn two_clients_join_in_training_and_get_model_using_p2p() {
     // check that the clients length shows the new joined client trained with new p2p shared model
     assert_with_retries(|| server_handle.get_clients_len(), 4).await;
 }
+
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+async fn round_completes_with_client_dropout() {
+    let num_clients = 5;
+    // drop 20% of the clients
+    let num_dropped = 1;
+    let init_min_clients = (num_clients - num_dropped) as u16;
+    let global_batch_size = 4;
+    let witness_nodes = 2;
+    let training_delay = 2;
+    let server_handle =
+        CoordinatorServerHandle::new(init_min_clients, global_batch_size, witness_nodes).await;
+    let cancel = CancellationToken::new();
+    let chaos = ChaosController::new(cancel.clone(), server_handle.get_fault_injector().await);
+
+    let server_port = server_handle.server_port;
+    let run_id = &server_handle.run_id;
+    let _client_handles =
+        spawn_clients_with_training_delay(num_clients, server_port, run_id, training_delay).await;
+
+    assert_with_retries(|| server_handle.get_clients_len(), num_clients).await;
+    assert_with_retries(|| server_handle.get_run_state(), RunState::RoundTrain).await;
+    let start_step = server_handle.get_coordinator().await.progress.step;
+
+    // disconnect clients mid-round for longer than the round lasts
+    let clients = server_handle.get_clients().await;
+    for client in clients.iter().take(num_dropped) {
+        chaos
+            .disconnect_client(client.id, Duration::from_secs(60))
+            .unwrap();
+    }
+    assert_with_retries(
+        || server_handle.get_clients_len(),
+        num_clients - num_dropped,
+    )
+    .await;
+
+    // the remaining clients should finish the round without the dropped ones
+    assert_with_retries(
+        || async { server_handle.get_coordinator().await.progress.step > start_step },
+        true,
+    )
+    .await;
+    assert_ne!(
+        server_handle.get_run_state().await,
+        RunState::WaitingForMembers
+    );
+
+    cancel.cancel();
+}
+
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+async fn round_completes_with_network_latency() {
+    let init_min_clients = 2;
+    let global_batch_size = 2;
+    let witness_nodes = 1;
+    let training_delay = 2;
+    let server_handle =
+        CoordinatorServerHandle::new(init_min_clients, global_batch_size, witness_nodes).await;
+
+    // every client talks to the server through the same delaying proxy
+    let latency = LatencySimulator::builder(server_handle.server_port)
+        .delay(DelayDistribution::Uniform(
+            Duration::from_millis(20)..Duration::from_millis(200),
+        ))
+        .build()
+        .await
+        .unwrap();
+
+    let run_id = &server_handle.run_id;
+    let _client_handles = spawn_clients_with_training_delay(
+        init_min_clients as usize,
+        latency.port(),
+        run_id,
+        training_delay,
+    )
+    .await;
+
+    assert_with_retries(
+        || server_handle.get_clients_len(),
+        init_min_clients as usize,
+    )
+    .await;
+    assert_with_retries(|| server_handle.get_run_state(), RunState::RoundTrain).await;
+    assert_with_retries(|| server_handle.get_rounds_head(), 1).await;
+}
+
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+async fn latency_simulator_delays_both_directions() {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    let echo = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let echo_port = echo.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let (mut stream, _) = echo.accept().await.unwrap();
+        let mut buffer = [0u8; 5];
+        stream.read_exact(&mut buffer).await.unwrap();
+        stream.write_all(&buffer).await.unwrap();
+    });
+
+    let delay = Duration::from_millis(150);
+    let latency = LatencySimulator::builder(echo_port)
+        .client_to_server_delay(DelayDistribution::Constant(delay))
+        .server_to_client_delay(DelayDistribution::Constant(delay))
+        .build()
+        .await
+        .unwrap();
+
+    let mut stream = TcpStream::connect(("127.0.0.1", latency.port()))
+        .await
+        .unwrap();
+    let start = std::time::Instant::now();
+    stream.write_all(b"hello").await.unwrap();
+    let mut reply = [0u8; 5];
+    stream.read_exact(&mut reply).await.unwrap();
+
+    assert_eq!(&reply, b"hello");
+    // delays are only simulated in debug builds
+    if cfg!(debug_assertions) {
+        assert!(start.elapsed() >= delay * 2);
+    }
+}
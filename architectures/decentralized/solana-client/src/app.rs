@@ -1,4 +1,4 @@
-use psyche_solana_rpc::SolanaBackend;
+use psyche_solana_rpc::{PsycheSolanaError, SolanaBackend};
 
 use anchor_client::{
     Cluster,
@@ -31,7 +31,7 @@ use tokio::{
     sync::mpsc::Sender,
     time::{Interval, MissedTickBehavior, interval},
 };
-use tokio_util::sync::CancellationToken;
+use tokio_util::{sync::CancellationToken, task::AbortOnDropHandle};
 use tracing::{debug, info, warn};
 
 pub(super) type Tabs = TabbedWidget<(ClientTUI, CoordinatorTui, NetworkTui, LoggerWidget)>;
@@ -47,6 +47,7 @@ pub struct App {
     update_tui_interval: Interval,
     tx_tui_state: Option<Sender<TabsData>>,
     authorizer: Option<Pubkey>,
+    _balance_monitor: AbortOnDropHandle<()>,
     metrics: Arc<ClientMetrics>,
     allowlist: allowlist::AllowDynamic,
     p2p: NC,
@@ -61,6 +62,8 @@ pub struct AppParams {
     pub backup_clusters: Vec<Cluster>,
     pub tx_tui_state: Option<Sender<TabsData>>,
     pub authorizer: Option<Pubkey>,
+    pub balance_warn_threshold_sol: f64,
+    pub balance_error_threshold_sol: f64,
     pub train_args: TrainArgs,
 }
 
@@ -72,6 +75,8 @@ pub async fn build_app(
         backup_clusters,
         tx_tui_state,
         authorizer,
+        balance_warn_threshold_sol,
+        balance_error_threshold_sol,
         train_args: p,
     }: AppParams,
 ) -> Result<App> {
@@ -98,10 +103,26 @@ pub async fn build_app(
 
     let metrics = Arc::new(ClientMetrics::new(
         p.metrics_local_port,
+        p.metrics_http_port,
         Some(Duration::from_secs(30)),
     ));
+    if let Some(port) = p.metrics_ws_port {
+        metrics.start_ws_server(port);
+    }
+    let balance_monitor = SolanaBackend::new(
+        cluster.clone(),
+        backup_clusters.clone(),
+        wallet_keypair.clone(),
+        CommitmentConfig::confirmed(),
+    )?
+    .start_balance_monitor(
+        metrics.clone(),
+        balance_warn_threshold_sol,
+        balance_error_threshold_sol,
+    );
 
     let allowlist = allowlist::AllowDynamic::new();
+    let network_config = p.network_config();
 
     let p2p = NC::init(
         &p.run_id,
@@ -114,6 +135,7 @@ pub async fn build_app(
         allowlist.clone(),
         metrics.clone(),
         Some(cancel.clone()),
+        network_config,
     )
     .await?;
 
@@ -121,18 +143,28 @@ pub async fn build_app(
         data_parallelism: p.data_parallelism,
         tensor_parallelism: p.tensor_parallelism,
         micro_batch_size: p.micro_batch_size,
+        gradient_accumulation_steps: p.gradient_accumulation_steps,
         write_gradients_dir: p.write_gradients_dir,
+        record_events: p.record_events,
+        replay_events: p.replay_events,
         eval_tasks,
         eval_task_max_docs: p.eval_task_max_docs,
         prompt_task: p.prompt_task,
         checkpoint_config,
+        resume_checkpoint: p.resume_checkpoint,
         hub_read_token,
         hub_max_concurrent_downloads: p.hub_max_concurrent_downloads,
         wandb_info,
+        write_events_log: p.write_events_log,
         identity,
         p2p_secret_key: identity_secret_key,
         optim_stats_every_n_steps: p.optim_stats_steps,
         grad_accum_in_fp32: p.grad_accum_in_fp32,
+        gradient_clip_norm: p.gradient_clip_norm,
+        ema_decay: p.ema_decay,
+        activation_checkpointing: p.activation_checkpointing,
+        distro_int8_quantization: p.distro_int8_quantization,
+        find_lr: p.find_lr_config(),
         dummy_training_delay_secs: p.dummy_training_delay_secs,
         max_concurrent_parameter_requests: p.max_concurrent_parameter_requests,
         device: p.device,
@@ -151,6 +183,7 @@ pub async fn build_app(
         tx_tui_state,
         update_tui_interval: interval(Duration::from_millis(150)),
         authorizer,
+        _balance_monitor: balance_monitor,
         allowlist,
         metrics,
         p2p,
@@ -170,9 +203,7 @@ impl App {
         )?;
         let coordinator_instance_pubkey =
             psyche_solana_coordinator::find_coordinator_instance(&self.run_id);
-        let coordinator_instance = backend
-            .get_coordinator_instance(&coordinator_instance_pubkey)
-            .await?;
+        let coordinator_instance = backend.get_run_coordinator_instance(&self.run_id).await?;
 
         let coordinator_account = coordinator_instance.coordinator_account;
         let coordinator_account_pubkey = coordinator_instance.coordinator_account;
@@ -193,7 +224,11 @@ impl App {
                 tracing::error!(
                     client_version = %client_version,
                     coordinator_client_version = %coordinator_client_version,
-                    "Version mismatch detected. Client version does not match coordinator version."
+                    "{}",
+                    PsycheSolanaError::ClientVersionMismatch {
+                        coordinator_version: coordinator_client_version.clone(),
+                        client_version: client_version.clone(),
+                    }
                 );
                 std::process::exit(10);
             }
@@ -0,0 +1,102 @@
+use anyhow::{Context, Result};
+use psyche_coordinator::{CoordinatorConfig, model::Model};
+use psyche_solana_rpc::SolanaBackend;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+/// The parts of a run that `update-config` can change, laid out like its config TOML files.
+#[derive(Serialize, Deserialize)]
+struct RunConfig {
+    config: CoordinatorConfig,
+    model: Model,
+}
+
+/// Loads `source` as a config TOML file if there's a file at that path, otherwise fetches the
+/// on-chain config of the run with that id.
+async fn load(backend: &SolanaBackend, source: &str) -> Result<RunConfig> {
+    let path = Path::new(source);
+    if path.is_file() {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config toml file {path:?}"))?;
+        return toml::from_str(&contents)
+            .with_context(|| format!("failed to parse config toml file {path:?}"));
+    }
+
+    let coordinator_instance = backend.get_run_coordinator_instance(source).await?;
+    let coordinator = backend
+        .get_coordinator_account(&coordinator_instance.coordinator_account)
+        .await?
+        .state
+        .coordinator;
+    Ok(RunConfig {
+        config: coordinator.config,
+        model: coordinator.model,
+    })
+}
+
+/// Flattens `value` into `path = value` entries, one per leaf, e.g. `config.min_clients = 4`.
+fn flatten(path: String, value: &Value, out: &mut BTreeMap<String, String>) {
+    match value {
+        Value::Object(fields) => {
+            for (name, value) in fields {
+                flatten(format!("{path}.{name}"), value, out);
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                flatten(format!("{path}[{index}]"), item, out);
+            }
+        }
+        leaf => {
+            out.insert(path, leaf.to_string());
+        }
+    }
+}
+
+fn flattened(run_config: &RunConfig) -> Result<BTreeMap<String, String>> {
+    let mut out = BTreeMap::new();
+    flatten(
+        "config".to_string(),
+        &serde_json::to_value(&run_config.config)?,
+        &mut out,
+    );
+    flatten(
+        "model".to_string(),
+        &serde_json::to_value(&run_config.model)?,
+        &mut out,
+    );
+    Ok(out)
+}
+
+/// Prints every field that differs between `a` and `b`, each a run id or a config TOML file,
+/// as a `-` line with its value in `a` and a `+` line with its value in `b`.
+pub async fn diff_config(backend: &SolanaBackend, a: &str, b: &str) -> Result<()> {
+    let old = flattened(&load(backend, a).await?)?;
+    let new = flattened(&load(backend, b).await?)?;
+
+    println!("--- {a}");
+    println!("+++ {b}");
+    let mut changed = 0;
+    let paths = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+    for path in paths {
+        let (old_value, new_value) = (old.get(path), new.get(path));
+        if old_value == new_value {
+            continue;
+        }
+        changed += 1;
+        if let Some(value) = old_value {
+            println!("- {path} = {value}");
+        }
+        if let Some(value) = new_value {
+            println!("+ {path} = {value}");
+        }
+    }
+    if changed == 0 {
+        println!("No differences");
+    }
+    Ok(())
+}
@@ -10,12 +10,12 @@ use anchor_client::{
     },
 };
 use anyhow::{Result, bail};
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, CommandFactory, Parser, Subcommand};
 use psyche_client::{TrainArgs, print_identity_keys};
 use psyche_coordinator::model::{Checkpoint, Model};
 use psyche_event_sourcing::{EventStore, FileBackend, RunStarted};
 use psyche_network::SecretKey;
-use psyche_solana_rpc::SolanaBackend;
+use psyche_solana_rpc::{PsycheSolanaError, SolanaBackend};
 use psyche_tui::{
     LogOutput, ServiceInfo,
     logging::{MetricsDestination, OpenTelemetry, RemoteLogsDestination, TraceDestination},
@@ -25,9 +25,10 @@ use std::sync::Arc;
 use std::{io::Cursor, path::PathBuf, time::Duration};
 use time::OffsetDateTime;
 use tokio::runtime::Builder;
-use tracing::info;
+use tracing::{debug, error, info};
 
 mod app;
+mod diff_config;
 
 #[global_allocator]
 static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
@@ -82,6 +83,12 @@ enum Commands {
         ws_rpc_3: String,
         #[clap(long, env)]
         authorizer: Option<Pubkey>,
+        /// Log a warning when the wallet balance drops below this many SOL
+        #[clap(long, env, default_value_t = 0.1)]
+        balance_warn_threshold_sol: f64,
+        /// Log an error when the wallet balance drops below this many SOL
+        #[clap(long, env, default_value_t = 0.01)]
+        balance_error_threshold_sol: f64,
     },
     Predownload {
         #[clap(flatten)]
@@ -99,12 +106,28 @@ enum Commands {
         #[clap(long, env, default_value_t = 3)]
         hub_max_concurrent_downloads: usize,
     },
+    /// Prints the config and model fields that differ between two runs, as `-`/`+` lines.
+    /// Either side can be a run id, or the path to a config TOML file like `update-config` takes.
+    DiffConfig {
+        #[clap(flatten)]
+        cluster: ClusterArgs,
+
+        run_id_a: String,
+        run_id_b: String,
+    },
+    /// Prints a Grafana dashboard for the client's Prometheus metrics as JSON.
+    GenerateGrafanaDashboard,
     // Prints the help, optionally as markdown. Used for docs generation.
     #[clap(hide = true)]
     PrintAllHelp {
         #[arg(long, required = true)]
         markdown: bool,
     },
+    // Prints a shell completion script. Used for packaging.
+    #[clap(hide = true)]
+    GenerateCompletions {
+        shell: clap_complete::Shell,
+    },
 }
 
 impl From<ClusterArgs> for Cluster {
@@ -162,6 +185,13 @@ async fn async_main() -> Result<()> {
             println!("Wrote secret key to {}", save_path.display());
             Ok(())
         }
+        Commands::GenerateGrafanaDashboard => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&psyche_metrics::grafana_dashboard())?
+            );
+            Ok(())
+        }
         Commands::Train {
             cluster,
             wallet,
@@ -171,8 +201,18 @@ async fn async_main() -> Result<()> {
             rpc_3,
             ws_rpc_3,
             authorizer,
+            balance_warn_threshold_sol,
+            balance_error_threshold_sol,
         } => {
             psyche_client::prepare_environment();
+
+            if args.dry_run {
+                let mut report = psyche_client::dry_run(&args).await;
+                let wallet: Result<Keypair> = wallet.try_into();
+                report.check("Solana wallet", wallet.map(|_| ()));
+                return report.finish();
+            }
+
             info!(
                 "============ Client Startup at {} ============",
                 OffsetDateTime::now_utc()
@@ -201,6 +241,7 @@ async fn async_main() -> Result<()> {
             let logger = psyche_tui::logging()
                 .with_output(args.logs)
                 .with_log_file(args.write_log.clone())
+                .with_prometheus_exporter(args.metrics_http_port.is_some())
                 .with_metrics_destination(args.oltp_metrics_url.clone().map(|endpoint| {
                     MetricsDestination::OpenTelemetry(OpenTelemetry {
                         endpoint,
@@ -254,15 +295,42 @@ async fn async_main() -> Result<()> {
                 cluster: cluster.into(),
                 backup_clusters,
                 authorizer,
+                balance_warn_threshold_sol,
+                balance_error_threshold_sol,
                 train_args: args,
             })
             .await?;
 
-            app.run().await?;
+            if let Err(err) = app.run().await {
+                let Some(solana_error) = err.downcast_ref::<PsycheSolanaError>() else {
+                    return Err(err);
+                };
+                // the full error chain is only useful when debugging
+                debug!("{err:?}");
+                error!("{solana_error}");
+                logger.shutdown()?;
+                std::process::exit(1);
+            }
             logger.shutdown()?;
 
             Ok(())
         }
+        Commands::DiffConfig {
+            cluster,
+            run_id_a,
+            run_id_b,
+        } => {
+            use anchor_client::solana_sdk::commitment_config::CommitmentConfig;
+
+            // read-only, no wallet needed
+            let backend = SolanaBackend::new(
+                cluster.into(),
+                vec![],
+                Arc::new(Keypair::new()),
+                CommitmentConfig::confirmed(),
+            )?;
+            diff_config::diff_config(&backend, &run_id_a, &run_id_b).await
+        }
         Commands::Predownload {
             cluster,
             run_id,
@@ -347,7 +415,7 @@ async fn async_main() -> Result<()> {
             }
 
             if let Some(eval_tasks) = eval_tasks {
-                let _ = TrainArgs::eval_tasks_from_args(&eval_tasks, 0)?;
+                let _ = TrainArgs::eval_tasks_from_args(&eval_tasks, 0, None)?;
                 println!("Eval tasks `{eval_tasks}` predownloaded successfully.");
             }
 
@@ -358,6 +426,15 @@ async fn async_main() -> Result<()> {
             clap_markdown::print_help_markdown::<CliArgs>();
             Ok(())
         }
+        Commands::GenerateCompletions { shell } => {
+            clap_complete::generate(
+                shell,
+                &mut CliArgs::command(),
+                "psyche-solana-client",
+                &mut std::io::stdout(),
+            );
+            Ok(())
+        }
     }
 }
 
@@ -1,8 +1,11 @@
+use crate::error::PsycheSolanaError;
 use crate::instructions::{self, coordinator_tick};
-use crate::retry::{RetryError, retry_function_with_params};
+use crate::retry::RetryError;
 use anchor_client::anchor_lang::AccountDeserialize;
 use anchor_client::solana_sdk::hash::hash;
 use anchor_client::solana_sdk::instruction::Instruction;
+use anchor_client::solana_sdk::message::Message;
+use anchor_client::solana_sdk::native_token::lamports_to_sol;
 use anchor_client::solana_sdk::program_pack::Pack;
 use anchor_client::{
     Client, ClientError, Cluster, Program,
@@ -10,7 +13,6 @@ use anchor_client::{
     solana_client::{
         nonblocking::pubsub_client::PubsubClient,
         rpc_config::{RpcAccountInfoConfig, RpcTransactionConfig},
-        rpc_response::Response as RpcResponse,
     },
     solana_sdk::{
         commitment_config::CommitmentConfig,
@@ -19,27 +21,34 @@ use anchor_client::{
     },
 };
 use anyhow::{Context, Result, anyhow};
+use backon::{BackoffBuilder, ExponentialBuilder, Retryable};
 use futures_util::StreamExt;
 use psyche_coordinator::model::{self, Checkpoint};
 use psyche_coordinator::{CommitteeProof, Coordinator, HealthChecks};
 use psyche_core::IntegrationTestLogMarker;
 use psyche_event_sourcing::event;
 use psyche_event_sourcing::events::RpcCallType;
+use psyche_metrics::ClientMetrics;
 use psyche_watcher::{Backend as WatcherBackend, OpportunisticData};
-use solana_account_decoder_client_types::{UiAccount, UiAccountEncoding};
+use solana_account_decoder_client_types::UiAccountEncoding;
 use solana_transaction_status_client_types::UiTransactionEncoding;
 use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::{cmp::min, sync::Arc, time::Duration};
 use tokio::{
     sync::{broadcast, mpsc},
-    time::timeout,
+    time::{Instant, MissedTickBehavior, interval, interval_at, timeout},
 };
-use tracing::{error, info, trace, warn};
+use tokio_util::task::AbortOnDropHandle;
+use tracing::{debug, error, info, trace, warn};
 
 type ProgramCoordinator = Arc<Program<Arc<Keypair>>>;
-const RPC_RETRY_ATTEMPTS: usize = 3;
+const RPC_MAX_ATTEMPTS: usize = 10;
 const RPC_INITIAL_BACKOFF_MS: u64 = 500;
-const RPC_MAX_BACKOFF_MS: u64 = 10_000;
+const RPC_MAX_BACKOFF_MS: u64 = 30_000;
+const RPC_BACKOFF_FACTOR: f32 = 2.0;
+const COORDINATOR_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const BALANCE_MONITOR_INTERVAL: Duration = Duration::from_secs(60);
 
 #[derive(Clone)]
 pub struct SolanaBackend {
@@ -61,8 +70,9 @@ async fn subscribe_to_account(
     url: String,
     commitment: CommitmentConfig,
     coordinator_account: &Pubkey,
-    tx: mpsc::UnboundedSender<RpcResponse<UiAccount>>,
+    tx: mpsc::UnboundedSender<Vec<u8>>,
     id: u64,
+    active_subscriptions: Arc<AtomicUsize>,
 ) {
     let mut retries: u64 = 0;
     loop {
@@ -111,6 +121,7 @@ async fn subscribe_to_account(
             url: url.to_string(),
             status: SubscriptionStatus::Up,
         });
+        active_subscriptions.fetch_add(1, Ordering::SeqCst);
         info!(
             integration_test_log_marker = %IntegrationTestLogMarker::SolanaSubscription,
             url = url,
@@ -124,10 +135,14 @@ async fn subscribe_to_account(
             tokio::select! {
                 update = notifications.next() => {
                     match update {
-                        Some(data) => {
-                                if tx.send(data).is_err() {
-                                    break;
-                                }
+                        Some(update) => {
+                            let Some(data) = update.value.data.decode() else {
+                                error!("Error decoding coordinator account");
+                                continue;
+                            };
+                            if tx.send(data).is_err() {
+                                break;
+                            }
                         }
                         None => {
                             event!(coordinator::SolanaSubscriptionChanged {
@@ -145,6 +160,46 @@ async fn subscribe_to_account(
                 }
             }
         }
+        active_subscriptions.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Polls the coordinator account over HTTP RPC whenever none of the websocket
+/// subscriptions are connected.
+async fn poll_coordinator_account(
+    backend: SolanaBackend,
+    coordinator_account: Pubkey,
+    tx: mpsc::UnboundedSender<Vec<u8>>,
+    active_subscriptions: Arc<AtomicUsize>,
+) {
+    // give the subscriptions a chance to connect before polling
+    let mut interval = interval_at(
+        Instant::now() + COORDINATOR_POLL_INTERVAL,
+        COORDINATOR_POLL_INTERVAL,
+    );
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut polling = false;
+    loop {
+        interval.tick().await;
+        if active_subscriptions.load(Ordering::SeqCst) > 0 {
+            if polling {
+                info!("Solana subscription restored, stopped polling coordinator account");
+                polling = false;
+            }
+            continue;
+        }
+        if !polling {
+            warn!("No Solana subscription available, falling back to polling coordinator account");
+            polling = true;
+        }
+        match backend.get_data(&coordinator_account).await {
+            Ok(data) => {
+                if tx.send(data).is_err() {
+                    break;
+                }
+            }
+            Err(err) => warn!("Error polling coordinator account: {err:#}"),
+        }
     }
 }
 
@@ -188,8 +243,10 @@ impl SolanaBackend {
         let commitment_config = self.get_commitment_config();
 
         let (tx_subscribe, mut rx_subscribe) = mpsc::unbounded_channel();
+        let active_subscriptions = Arc::new(AtomicUsize::new(0));
 
         let tx_subscribe_ = tx_subscribe.clone();
+        let active_subscriptions_ = active_subscriptions.clone();
 
         let mut subscription_number = 1;
         let url = self.cluster.clone().ws_url().to_string();
@@ -200,6 +257,7 @@ impl SolanaBackend {
                 &coordinator_account,
                 tx_subscribe_,
                 subscription_number,
+                active_subscriptions_,
             )
             .await
         });
@@ -207,6 +265,7 @@ impl SolanaBackend {
         for cluster in self.backup_clusters.clone() {
             subscription_number += 1;
             let tx_subscribe_ = tx_subscribe.clone();
+            let active_subscriptions_ = active_subscriptions.clone();
             tokio::spawn(async move {
                 subscribe_to_account(
                     cluster.ws_url().to_string().clone(),
@@ -214,34 +273,36 @@ impl SolanaBackend {
                     &coordinator_account,
                     tx_subscribe_,
                     subscription_number,
+                    active_subscriptions_,
                 )
                 .await
             });
         }
+        tokio::spawn(poll_coordinator_account(
+            self.clone(),
+            coordinator_account,
+            tx_subscribe,
+            active_subscriptions,
+        ));
         tokio::spawn(async move {
             let mut last_nonce = 0;
-            while let Some(update) = rx_subscribe.recv().await {
-                match update.value.data.decode() {
-                    Some(data) => {
-                        match psyche_solana_coordinator::coordinator_account_from_bytes(&data) {
-                            Ok(account) => {
-                                if account.nonce > last_nonce {
-                                    trace!(
-                                        nonce = account.nonce,
-                                        last_nonce = last_nonce,
-                                        "Coordinator account update"
-                                    );
-                                    if let Err(err) = tx_update.send(account.state.coordinator) {
-                                        error!("Error sending coordinator update: {err:#}");
-                                        break;
-                                    }
-                                    last_nonce = account.nonce;
-                                }
+            while let Some(data) = rx_subscribe.recv().await {
+                match psyche_solana_coordinator::coordinator_account_from_bytes(&data) {
+                    Ok(account) => {
+                        if account.nonce > last_nonce {
+                            trace!(
+                                nonce = account.nonce,
+                                last_nonce = last_nonce,
+                                "Coordinator account update"
+                            );
+                            if let Err(err) = tx_update.send(account.state.coordinator) {
+                                error!("Error sending coordinator update: {err:#}");
+                                break;
                             }
-                            Err(err) => error!("Error deserializing coordinator account: {err:#}"),
+                            last_nonce = account.nonce;
                         }
                     }
-                    None => error!("Error decoding coordinator account"),
+                    Err(err) => error!("Error deserializing coordinator account: {err:#}"),
                 }
             }
             error!("No subscriptions available");
@@ -418,6 +479,32 @@ impl SolanaBackend {
             .map_err(|error| anyhow!("Unable to decode coordinator instance data: {error}"))
     }
 
+    /// The coordinator instance of the run `run_id`, failing with
+    /// [`PsycheSolanaError::RunNotFound`] if there's no such run.
+    pub async fn get_run_coordinator_instance(
+        &self,
+        run_id: &str,
+    ) -> Result<psyche_solana_coordinator::CoordinatorInstance> {
+        let address = psyche_solana_coordinator::find_coordinator_instance(run_id);
+        let account = self
+            .rpc_with_fallback("get_run_coordinator_instance", |coord| async move {
+                coord
+                    .rpc()
+                    .get_account_with_commitment(&address, coord.rpc().commitment())
+                    .await
+                    .map(|response| response.value)
+                    .map_err(Into::into)
+            })
+            .await?
+            .ok_or_else(|| PsycheSolanaError::RunNotFound {
+                run_id: run_id.to_string(),
+            })?;
+        psyche_solana_coordinator::CoordinatorInstance::try_deserialize(
+            &mut account.data.as_slice(),
+        )
+        .map_err(|error| anyhow!("Unable to decode coordinator instance data: {error}"))
+    }
+
     pub async fn get_coordinator_account(
         &self,
         coordinator_account: &Pubkey,
@@ -489,6 +576,49 @@ impl SolanaBackend {
         })
     }
 
+    /// Polls the wallet balance every 60 seconds, recording it in `metrics`
+    /// and logging when it drops below `warn_threshold_sol` or
+    /// `error_threshold_sol`. Stops when the returned handle is dropped.
+    pub fn start_balance_monitor(
+        &self,
+        metrics: Arc<ClientMetrics>,
+        warn_threshold_sol: f64,
+        error_threshold_sol: f64,
+    ) -> AbortOnDropHandle<()> {
+        let backend = self.clone();
+        AbortOnDropHandle::new(tokio::spawn(async move {
+            let wallet = backend.get_payer();
+            let mut interval = interval(BALANCE_MONITOR_INTERVAL);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let balance = match backend.get_balance(&wallet).await {
+                    Ok(lamports) => lamports_to_sol(lamports),
+                    Err(err) => {
+                        warn!("Failed to fetch wallet balance: {err:#}");
+                        continue;
+                    }
+                };
+                metrics.record_wallet_balance(balance);
+                if balance < error_threshold_sol {
+                    error!(
+                        wallet = %wallet,
+                        balance_sol = balance,
+                        threshold_sol = error_threshold_sol,
+                        "Wallet balance critically low, transactions will start failing"
+                    );
+                } else if balance < warn_threshold_sol {
+                    warn!(
+                        wallet = %wallet,
+                        balance_sol = balance,
+                        threshold_sol = warn_threshold_sol,
+                        "Wallet balance is running low"
+                    );
+                }
+            }
+        }))
+    }
+
     pub fn get_payer(&self) -> Pubkey {
         self.wallet.pubkey()
     }
@@ -524,6 +654,40 @@ impl SolanaBackend {
         .await
     }
 
+    /// Estimates the fee, in lamports, of a transaction made of `instructions`
+    /// and paid by this backend's wallet.
+    pub async fn estimate_fees(&self, instructions: &[Instruction]) -> Result<u64> {
+        let payer = self.get_payer();
+        let instructions: Arc<[Instruction]> = instructions.to_vec().into();
+        self.rpc_with_fallback("estimate_fees", |coord| {
+            let instructions = instructions.clone();
+            async move {
+                let mut message = Message::new(&instructions, Some(&payer));
+                message.recent_blockhash = coord.rpc().get_latest_blockhash().await?;
+                coord
+                    .rpc()
+                    .get_fee_for_message(&message)
+                    .await
+                    .map_err(Into::into)
+            }
+        })
+        .await
+    }
+
+    /// Fails with [`PsycheSolanaError::InsufficientBalance`] if the payer holds
+    /// less than `required_lamports`.
+    pub async fn check_sufficient_balance(&self, required_lamports: u64) -> Result<()> {
+        let available = self.get_balance(&self.get_payer()).await?;
+        if available < required_lamports {
+            return Err(PsycheSolanaError::InsufficientBalance {
+                required: required_lamports,
+                available,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
     pub async fn get_data(&self, address: &Pubkey) -> Result<Vec<u8>> {
         let address = *address;
         self.rpc_with_fallback("get_data", |coord| async move {
@@ -625,54 +789,78 @@ impl SolanaBackend {
         });
     }
 
-    /// Tries the operation against each RPC in order, with max retries per RPC.
-    /// Only falls back to the next RPC on retryable errors (network/timeout).
-    async fn rpc_with_fallback<T, F, Fut>(&self, name: &str, f: F) -> Result<T>
+    /// Retries `f` with exponential backoff (500ms doubling up to 30s, at most
+    /// 10 attempts) and full jitter. Only retryable errors are retried.
+    pub async fn with_retry<F, Fut, T>(f: F) -> Result<T>
     where
-        F: Fn(ProgramCoordinator) -> Fut,
+        F: Fn() -> Fut,
         Fut: Future<Output = Result<T, RetryError<ClientError>>>,
     {
-        let mut last_error = None;
-        for (i, coordinator) in self.program_coordinators.iter().enumerate() {
-            let coordinator = coordinator.clone();
-            let result = retry_function_with_params(
-                name,
-                || {
-                    let coord = coordinator.clone();
-                    f(coord)
-                },
-                RPC_INITIAL_BACKOFF_MS,
-                1.5,
-                RPC_RETRY_ATTEMPTS,
-                RPC_MAX_BACKOFF_MS,
-            )
-            .await;
-
-            match result {
-                Ok(value) => return Ok(value),
-                Err(RetryError::Retryable(e)) => {
-                    warn!(
-                        integration_test_log_marker = %IntegrationTestLogMarker::RpcFallback,
-                        failed_rpc_index = i,
-                        next_rpc_index = i + 1,
-                        error = %e,
-                        "RPC {i} exhausted retries, trying next: {e}",
-                    );
-                    last_error = Some(e);
+        // full jitter: sleep a uniformly random duration in [0, backoff]
+        let backoff = ExponentialBuilder::default()
+            .with_min_delay(Duration::from_millis(RPC_INITIAL_BACKOFF_MS))
+            .with_factor(RPC_BACKOFF_FACTOR)
+            .with_max_delay(Duration::from_millis(RPC_MAX_BACKOFF_MS))
+            .with_max_times(RPC_MAX_ATTEMPTS - 1)
+            .build()
+            .map(|delay| Duration::from_millis(rand::random_range(0..=delay.as_millis() as u64)));
+
+        f.retry(backoff)
+            .sleep(tokio::time::sleep)
+            .when(|e| matches!(e, RetryError::Retryable(_)))
+            .notify(|err: &RetryError<ClientError>, dur: Duration| {
+                warn!("[RETRY] RPC call failed, retrying after {dur:?}: {err}");
+            })
+            .await
+            .map_err(|err| {
+                let (RetryError::Retryable(inner)
+                | RetryError::NonRetryable(inner)
+                | RetryError::Fatal(inner)) = &err;
+                if let Some(error) = PsycheSolanaError::from_client_error(inner) {
+                    // the raw RPC error is only useful when debugging
+                    debug!("RPC call failed: {err}");
+                    return error.into();
                 }
-                Err(RetryError::NonRetryable(e)) => {
-                    return Err(anyhow!("non-retryable RPC error: {e}"));
+                match err {
+                    RetryError::Retryable(e) => {
+                        anyhow!("RPC call failed after {RPC_MAX_ATTEMPTS} attempts: {e}")
+                    }
+                    RetryError::NonRetryable(e) => anyhow!("non-retryable RPC error: {e}"),
+                    RetryError::Fatal(e) => anyhow!("fatal RPC error: {e}"),
                 }
-                Err(RetryError::Fatal(e)) => {
-                    return Err(anyhow!("fatal RPC error: {e}"));
+            })
+    }
+
+    /// Tries the operation against each RPC in order, retrying the whole chain
+    /// through [`Self::with_retry`]. Only falls back to the next RPC on
+    /// retryable errors (network/timeout).
+    async fn rpc_with_fallback<T, F, Fut>(&self, name: &str, f: F) -> Result<T>
+    where
+        F: Fn(ProgramCoordinator) -> Fut,
+        Fut: Future<Output = Result<T, RetryError<ClientError>>>,
+    {
+        let f = &f;
+        let coordinators = &self.program_coordinators;
+        let last = coordinators.len() - 1;
+        Self::with_retry(move || async move {
+            for (i, coordinator) in coordinators.iter().enumerate() {
+                match f(coordinator.clone()).await {
+                    Err(RetryError::Retryable(e)) if i < last => {
+                        warn!(
+                            integration_test_log_marker = %IntegrationTestLogMarker::RpcFallback,
+                            failed_rpc_index = i,
+                            next_rpc_index = i + 1,
+                            error = %e,
+                            "{name}: RPC {i} failed, trying next: {e}",
+                        );
+                    }
+                    result => return result,
                 }
             }
-        }
-
-        Err(anyhow!(
-            "all RPCs exhausted: {}",
-            last_error.map(|e| e.to_string()).unwrap_or_default()
-        ))
+            unreachable!("SolanaBackend always has at least one RPC")
+        })
+        .await
+        .with_context(|| format!("{name} failed"))
     }
 }
 
@@ -0,0 +1,155 @@
+use anchor_client::ClientError;
+use anchor_client::solana_client::client_error::ClientErrorKind;
+use anchor_client::solana_client::rpc_request::{RpcError, RpcResponseErrorData};
+use anchor_client::solana_sdk::native_token::lamports_to_sol;
+use thiserror::Error;
+
+/// Errors worth explaining to whoever runs the client, instead of passing on raw RPC errors.
+#[derive(Error, Debug)]
+pub enum PsycheSolanaError {
+    #[error(
+        "Insufficient balance: transaction requires {required} lamports ({:.9} SOL) but the payer only has {available} lamports ({:.9} SOL)",
+        lamports_to_sol(*required),
+        lamports_to_sol(*available)
+    )]
+    InsufficientBalance { required: u64, available: u64 },
+
+    #[error(
+        "Client version mismatch: this client is version {client_version}, but the run requires version {coordinator_version}. Please update your client."
+    )]
+    ClientVersionMismatch {
+        coordinator_version: String,
+        client_version: String,
+    },
+
+    #[error("Run not found: no run with id {run_id:?} exists on this cluster")]
+    RunNotFound { run_id: String },
+
+    #[error("Not authorized: this wallet isn't allowed to join this run")]
+    NotAuthorized,
+
+    #[error("Transaction rejected by the program: {message} ({code})")]
+    Program { code: String, message: String },
+}
+
+impl PsycheSolanaError {
+    /// Finds why a transaction failed from the `AnchorError` line in its program logs, e.g.
+    /// `Program log: AnchorError caused by account: authorization. Error Code: ConstraintRaw. Error Number: 2003. Error Message: A raw constraint was violated.`
+    ///
+    /// Joining a run checks the user's authorizer grant through the account named `authorization`,
+    /// so only a missing or invalid account of that name is reported as [`Self::NotAuthorized`].
+    pub fn from_program_logs(logs: &[String]) -> Option<Self> {
+        let line = logs.iter().find(|line| line.contains("AnchorError"))?;
+        let code = log_field(line, "Error Code: ")?;
+        if log_field(line, "caused by account: ") == Some("authorization")
+            && matches!(code, "ConstraintRaw" | "AccountNotInitialized")
+        {
+            return Some(Self::NotAuthorized);
+        }
+        // the message is last, and may have sentences of its own
+        let message = line
+            .split_once("Error Message: ")
+            .map(|(_, message)| message.trim_end_matches('.'))
+            .unwrap_or_default();
+        Some(Self::Program {
+            code: code.to_string(),
+            message: message.to_string(),
+        })
+    }
+
+    /// Recognizes a transaction that failed in simulation because the program rejected it.
+    pub(crate) fn from_client_error(error: &ClientError) -> Option<Self> {
+        let ClientError::SolanaClientError(error) = error else {
+            return None;
+        };
+        let ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            data: RpcResponseErrorData::SendTransactionPreflightFailure(simulation),
+            ..
+        }) = &error.kind
+        else {
+            return None;
+        };
+        Self::from_program_logs(simulation.logs.as_deref()?)
+    }
+}
+
+/// The value after `name` in an `AnchorError` log line, up to the end of its sentence.
+fn log_field<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let rest = &line[line.find(name)? + name.len()..];
+    Some(rest.split_once(". ").map_or(rest, |(value, _)| value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logs(anchor_error: &str) -> Vec<String> {
+        vec![
+            "Program HR8RN2TP9E9zsi2kjhvPbirJWA1R6L6ruf4xNNGpjU5Y invoke [1]".to_string(),
+            "Program log: Instruction: JoinRun".to_string(),
+            anchor_error.to_string(),
+            "Program HR8RN2TP9E9zsi2kjhvPbirJWA1R6L6ruf4xNNGpjU5Y consumed 6029 of 200000 compute units"
+                .to_string(),
+        ]
+    }
+
+    #[test]
+    fn invalid_authorization_is_not_authorized() {
+        let error = PsycheSolanaError::from_program_logs(&logs(
+            "Program log: AnchorError caused by account: authorization. Error Code: ConstraintRaw. Error Number: 2003. Error Message: A raw constraint was violated.",
+        ));
+        assert!(matches!(error, Some(PsycheSolanaError::NotAuthorized)));
+
+        let error = PsycheSolanaError::from_program_logs(&logs(
+            "Program log: AnchorError caused by account: authorization. Error Code: AccountNotInitialized. Error Number: 3012. Error Message: The program expected this account to be already initialized.",
+        ));
+        assert!(matches!(error, Some(PsycheSolanaError::NotAuthorized)));
+    }
+
+    #[test]
+    fn other_account_errors_are_program_errors() {
+        let error = PsycheSolanaError::from_program_logs(&logs(
+            "Program log: AnchorError caused by account: coordinator_account. Error Code: ConstraintRaw. Error Number: 2003. Error Message: A raw constraint was violated.",
+        ));
+        let Some(PsycheSolanaError::Program { code, message }) = error else {
+            panic!("expected a program error, got {error:?}");
+        };
+        assert_eq!(code, "ConstraintRaw");
+        assert_eq!(message, "A raw constraint was violated");
+    }
+
+    #[test]
+    fn program_error_code_and_message() {
+        let error = PsycheSolanaError::from_program_logs(&logs(
+            "Program log: AnchorError thrown in programs/solana-coordinator/src/lib.rs:164. Error Code: ClientsFull. Error Number: 6003. Error Message: Clients list full.",
+        ));
+        let Some(PsycheSolanaError::Program { code, message }) = error else {
+            panic!("expected a program error, got {error:?}");
+        };
+        assert_eq!(code, "ClientsFull");
+        assert_eq!(message, "Clients list full");
+    }
+
+    #[test]
+    fn message_with_several_sentences_is_kept_whole() {
+        // a program's own #[msg] can be more than one sentence
+        let error = PsycheSolanaError::from_program_logs(&logs(
+            "Program log: AnchorError occurred. Error Code: RunPaused. Error Number: 6040. Error Message: Run is paused. Try again later.",
+        ));
+        let Some(PsycheSolanaError::Program { code, message }) = error else {
+            panic!("expected a program error, got {error:?}");
+        };
+        assert_eq!(code, "RunPaused");
+        assert_eq!(message, "Run is paused. Try again later");
+    }
+
+    #[test]
+    fn logs_without_an_anchor_error() {
+        assert!(
+            PsycheSolanaError::from_program_logs(
+                &["Program log: Instruction: JoinRun".to_string()]
+            )
+            .is_none()
+        );
+    }
+}
@@ -53,6 +53,25 @@ pub fn coordinator_close_run(
     )
 }
 
+pub fn coordinator_migrate(
+    coordinator_instance: &Pubkey,
+    coordinator_account: &Pubkey,
+    payer: &Pubkey,
+) -> Instruction {
+    anchor_instruction(
+        psyche_solana_coordinator::ID,
+        psyche_solana_coordinator::accounts::MigrateCoordinatorAccounts {
+            payer: *payer,
+            coordinator_instance: *coordinator_instance,
+            coordinator_account: *coordinator_account,
+            system_program: system_program::ID,
+        },
+        psyche_solana_coordinator::instruction::MigrateCoordinator {
+            params: psyche_solana_coordinator::logic::MigrateCoordinatorParams {},
+        },
+    )
+}
+
 pub fn coordinator_update(
     run_id: &str,
     coordinator_account: &Pubkey,
@@ -1,6 +1,7 @@
 #![deny(unused_crate_dependencies)]
 // Shared Solana blockchain infrastructure for Psyche
 pub mod backend;
+pub mod error;
 pub mod instructions;
 pub mod retry;
 pub mod utils;
@@ -8,3 +9,4 @@ pub mod utils;
 // Re-exports for convenience
 pub use backend::SolanaBackend;
 pub use backend::SolanaBackendRunner;
+pub use error::PsycheSolanaError;
@@ -1,18 +1,9 @@
-use std::future::Future;
-use std::time::Duration;
-
 use anchor_client::ClientError;
 use anchor_client::solana_client::client_error::ClientErrorKind as ErrorKind;
 use anchor_client::solana_client::rpc_request::{RpcError, RpcResponseErrorData};
 use anchor_client::solana_sdk::transaction::TransactionError;
-use backon::{ExponentialBuilder, Retryable};
 use tracing::{error, warn};
 
-const DEFAULT_MAX_TIMES: usize = 5;
-const DEFAULT_INITIAL_BACKOFF_MS: u64 = 500; // 0.5 seconds
-const DEFAULT_MAX_BACKOFF_MS: u64 = 10000; // 10 seconds
-const DEFAULT_BACKOFF_FACTOR: f32 = 1.5;
-
 #[derive(Debug)]
 pub enum RetryError<E> {
     Retryable(E),
@@ -176,51 +167,3 @@ impl RetryError<String> {
         RetryError::Fatal(format!("FATAL {msg}"))
     }
 }
-
-#[allow(dead_code)] // TODO (vbrunet) - shall we re-use this for the generic retry mechanism
-pub async fn retry_function<FutureFn, Fut, T, E>(
-    log_str: &str,
-    function: FutureFn,
-) -> Result<T, RetryError<E>>
-where
-    Fut: Future<Output = Result<T, RetryError<E>>>,
-    FutureFn: FnMut() -> Fut,
-{
-    retry_function_with_params(
-        log_str,
-        function,
-        DEFAULT_INITIAL_BACKOFF_MS,
-        DEFAULT_BACKOFF_FACTOR,
-        DEFAULT_MAX_TIMES,
-        DEFAULT_MAX_BACKOFF_MS,
-    )
-    .await
-}
-
-pub async fn retry_function_with_params<FutureFn, Fut, T, E>(
-    log_str: &str,
-    function: FutureFn,
-    min_delay: u64,
-    factor: f32,
-    max_times: usize,
-    max_delay: u64,
-) -> Result<T, RetryError<E>>
-where
-    Fut: Future<Output = Result<T, RetryError<E>>>,
-    FutureFn: FnMut() -> Fut,
-{
-    let backoff = ExponentialBuilder::default()
-        .with_min_delay(Duration::from_millis(min_delay))
-        .with_max_times(max_times)
-        .with_factor(factor)
-        .with_max_delay(Duration::from_millis(max_delay));
-
-    function
-        .retry(backoff)
-        .sleep(tokio::time::sleep)
-        .when(|e| matches!(e, RetryError::Retryable(_)))
-        .notify(|_err: &RetryError<E>, dur: Duration| {
-            warn!("[RETRY] {} retrying after {:?}", log_str, dur);
-        })
-        .await
-}
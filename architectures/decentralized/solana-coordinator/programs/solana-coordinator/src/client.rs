@@ -25,7 +25,8 @@ use ts_rs::TS;
 #[ts(rename = "SolanaClient")]
 pub struct Client {
     pub id: NodeIdentity,
-    pub _unused: [u8; 8],
+    /// Unix timestamp of the client's last transaction, 0 if it hasn't sent one since this was added
+    pub last_seen: u64,
     pub earned: u64,
     pub slashed: u64,
     pub active: u64,
@@ -38,6 +39,7 @@ impl Debug for Client {
             .field("earned", &self.earned)
             .field("slashed", &self.slashed)
             .field("active", &self.active)
+            .field("last_seen", &self.last_seen)
             .finish()
     }
 }
@@ -79,4 +79,14 @@ impl ClientsState {
             None => err!(ProgramError::SignerNotAClient),
         }
     }
+    /// Records that the client signed by `signer` sent a transaction at `unix_timestamp`
+    pub fn mark_seen(&mut self, signer: &Pubkey, unix_timestamp: u64) {
+        if let Some(client) = self
+            .clients
+            .iter_mut()
+            .find(|x| *x.id.signer() == signer.to_bytes())
+        {
+            client.last_seen = unix_timestamp;
+        }
+    }
 }
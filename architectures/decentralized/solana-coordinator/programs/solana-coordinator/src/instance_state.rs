@@ -229,6 +229,8 @@ impl CoordinatorInstanceState {
         let id = self.clients_state.find_signer(payer)?;
 
         let clock: Clock = Clock::get()?;
+        self.clients_state
+            .mark_seen(payer, clock.unix_timestamp as u64);
         self.coordinator
             .witness(&id, witness, clock.unix_timestamp as u64)
             .map_err(|err| anchor_lang::error!(ProgramError::from(err)))?;
@@ -244,6 +246,8 @@ impl CoordinatorInstanceState {
         let id = self.clients_state.find_signer(payer)?;
 
         let clock: Clock = Clock::get()?;
+        self.clients_state
+            .mark_seen(payer, clock.unix_timestamp as u64);
         self.coordinator
             .warmup_witness(
                 &id,
@@ -341,6 +345,7 @@ impl CoordinatorInstanceState {
                     // we must re-set this to ensure that the p2p key is up to date.
                     client.id = id;
                     client.active = self.clients_state.next_active;
+                    client.last_seen = Clock::get()?.unix_timestamp as u64;
                     msg!("Existing client {} re-joined", id);
                     true
                 },
@@ -360,7 +365,7 @@ impl CoordinatorInstanceState {
                 earned: 0,
                 slashed: 0,
                 active: self.clients_state.next_active,
-                _unused: Default::default(),
+                last_seen: Clock::get()?.unix_timestamp as u64,
             };
 
             if self.clients_state.clients.push(new_client).is_err() {
@@ -387,6 +392,8 @@ impl CoordinatorInstanceState {
     ) -> Result<()> {
         // O(n) on clients, reconsider
         let id = self.clients_state.find_signer(payer)?;
+        self.clients_state
+            .mark_seen(payer, Clock::get()?.unix_timestamp as u64);
 
         self.coordinator
             .health_check(&id, checks)
@@ -401,6 +408,8 @@ impl CoordinatorInstanceState {
     ) -> Result<()> {
         // O(n) on clients, reconsider
         let id = self.clients_state.find_signer(payer)?;
+        self.clients_state
+            .mark_seen(payer, Clock::get()?.unix_timestamp as u64);
         let index = self
             .coordinator
             .epoch_state
@@ -13,6 +13,7 @@ pub use program_error::ProgramError;
 use psyche_coordinator::Committee;
 use psyche_coordinator::CommitteeProof;
 use psyche_coordinator::CoordinatorConfig;
+use psyche_coordinator::CoordinatorEpochState;
 use psyche_coordinator::CoordinatorProgress;
 use psyche_coordinator::SOLANA_MAX_NUM_CLIENTS;
 use psyche_coordinator::SOLANA_MAX_STRING_LEN;
@@ -123,6 +124,83 @@ pub fn coordinator_account_from_bytes_mut(
     Ok(coordinator_account)
 }
 
+/// Byte ranges, as `(offset, length)` into the account data, of the fields
+/// added to the coordinator account since version 1, in layout order. Fields
+/// are only ever appended at the end of their struct, so each range starts at
+/// the old end of that struct.
+fn fields_added_since_v1(
+    coordinator_account: &CoordinatorAccount,
+) -> [(usize, usize); 2] {
+    let start = coordinator_account as *const CoordinatorAccount as usize
+        - CoordinatorAccount::DISCRIMINATOR.len();
+    let coordinator = &coordinator_account.state.coordinator;
+    let config = &coordinator.config;
+    let config_added =
+        &config.straggler_fraction as *const f64 as usize - start;
+    let config_end = config as *const CoordinatorConfig as usize - start
+        + std::mem::size_of::<CoordinatorConfig>();
+    let epoch_state = &coordinator.epoch_state;
+    let epoch_state_added = &epoch_state.straggler_grace_start_timestamp
+        as *const u64 as usize
+        - start;
+    let epoch_state_end = epoch_state as *const CoordinatorEpochState as usize
+        - start
+        + std::mem::size_of::<CoordinatorEpochState>();
+    [
+        (config_added, config_end - config_added),
+        (epoch_state_added, epoch_state_end - epoch_state_added),
+    ]
+}
+
+/// Upgrades a version 1 coordinator account in place. `bytes` must already
+/// be resized to the current account size, with the version 1 data at its
+/// start. Every field added since then starts out zeroed.
+pub fn migrate_coordinator_account_from_v1(
+    bytes: &mut [u8],
+) -> std::result::Result<(), DeserializeCoordinatorFromBytes> {
+    validate_coordinator_account_bytes(bytes)?;
+    let coordinator_account: &CoordinatorAccount = bytemuck::try_from_bytes(
+        &bytes[CoordinatorAccount::DISCRIMINATOR.len()
+            ..CoordinatorAccount::space_with_discriminator()],
+    )?;
+    // The version is the first field, so it's at the same place in both layouts
+    if coordinator_account.version != 1 {
+        return Err(DeserializeCoordinatorFromBytes::InvalidVersion {
+            expected: 1,
+            actual: coordinator_account.version,
+        });
+    }
+    let added = fields_added_since_v1(coordinator_account);
+    let added_len: usize = added.iter().map(|(_, len)| len).sum();
+    if bytes.len() - added_len
+        != CoordinatorAccount::SPACE_WITH_DISCRIMINATOR_V1
+    {
+        return Err(DeserializeCoordinatorFromBytes::IncorrectSize {
+            expected: CoordinatorAccount::SPACE_WITH_DISCRIMINATOR_V1
+                + added_len,
+            actual: bytes.len(),
+        });
+    }
+    // Walk the added ranges from the back, shifting everything that follows
+    // each one into its final place before zeroing the range itself
+    let mut end = bytes.len();
+    let mut shift = added_len;
+    for (offset, len) in added.into_iter().rev() {
+        let tail = offset + len;
+        bytes.copy_within(tail - shift..end - shift, tail);
+        bytes[offset..tail].fill(0);
+        shift -= len;
+        end = offset;
+    }
+    let coordinator_account: &mut CoordinatorAccount =
+        bytemuck::try_from_bytes_mut(
+            &mut bytes[CoordinatorAccount::DISCRIMINATOR.len()
+                ..CoordinatorAccount::space_with_discriminator()],
+        )?;
+    coordinator_account.version = CoordinatorAccount::VERSION;
+    Ok(())
+}
+
 #[account(zero_copy)]
 #[repr(C)]
 #[derive(Serialize, Deserialize, TS)]
@@ -133,7 +211,11 @@ pub struct CoordinatorAccount {
 }
 
 impl CoordinatorAccount {
-    pub const VERSION: u64 = 1;
+    pub const VERSION: u64 = 2;
+
+    /// Size of a version 1 account, before the straggler and adaptive witness
+    /// quorum fields were added
+    pub const SPACE_WITH_DISCRIMINATOR_V1: usize = 119160;
 
     pub fn space_with_discriminator() -> usize {
         CoordinatorAccount::DISCRIMINATOR.len()
@@ -181,6 +263,13 @@ pub mod psyche_solana_coordinator {
         free_coordinator_processor(context, params)
     }
 
+    pub fn migrate_coordinator(
+        context: Context<MigrateCoordinatorAccounts>,
+        params: MigrateCoordinatorParams,
+    ) -> Result<()> {
+        migrate_coordinator_processor(context, params)
+    }
+
     pub fn update(
         ctx: Context<OwnerCoordinatorAccounts>,
         metadata: Option<RunMetadata>,
@@ -0,0 +1,81 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+
+use crate::CoordinatorAccount;
+use crate::CoordinatorInstance;
+use crate::ProgramError;
+use crate::bytes_from_string;
+use crate::migrate_coordinator_account_from_v1;
+
+#[derive(Accounts)]
+#[instruction(params: MigrateCoordinatorParams)]
+pub struct MigrateCoordinatorAccounts<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [
+            CoordinatorInstance::SEEDS_PREFIX,
+            bytes_from_string(&coordinator_instance.run_id)
+        ],
+        bump = coordinator_instance.bump,
+    )]
+    pub coordinator_instance: Box<Account<'info, CoordinatorInstance>>,
+
+    /// CHECK: an account in an older layout can't be loaded by anchor, the
+    /// processor checks its size, discriminator and version instead
+    #[account(
+        mut,
+        owner = crate::ID,
+        constraint = coordinator_instance.coordinator_account == coordinator_account.key(),
+    )]
+    pub coordinator_account: UncheckedAccount<'info>,
+
+    #[account()]
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct MigrateCoordinatorParams {}
+
+pub fn migrate_coordinator_processor(
+    context: Context<MigrateCoordinatorAccounts>,
+    _params: MigrateCoordinatorParams,
+) -> Result<()> {
+    let coordinator_account =
+        context.accounts.coordinator_account.to_account_info();
+    if coordinator_account.data_len()
+        != CoordinatorAccount::SPACE_WITH_DISCRIMINATOR_V1
+    {
+        return err!(ProgramError::CoordinatorAccountIncorrectSize);
+    }
+    // Top up the rent for the extra space before growing the account
+    let space = CoordinatorAccount::space_with_discriminator();
+    let missing_lamports = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(coordinator_account.lamports());
+    if missing_lamports > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                context.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: context.accounts.payer.to_account_info(),
+                    to: coordinator_account.clone(),
+                },
+            ),
+            missing_lamports,
+        )?;
+    }
+    coordinator_account.realloc(space, true)?;
+    // Shift the old data into the new layout
+    let mut data = coordinator_account.try_borrow_mut_data()?;
+    if let Err(error) = migrate_coordinator_account_from_v1(&mut data) {
+        msg!("Failed to migrate coordinator account: {}", error);
+        return err!(ProgramError::CoordinatorAccountMigrationFailed);
+    }
+    msg!(
+        "Migrated coordinator account to version {}",
+        CoordinatorAccount::VERSION
+    );
+    Ok(())
+}
@@ -1,7 +1,9 @@
 pub mod free_coordinator;
 pub mod init_coordinator;
 pub mod join_run;
+pub mod migrate_coordinator;
 
 pub use free_coordinator::*;
 pub use init_coordinator::*;
 pub use join_run::*;
+pub use migrate_coordinator::*;
@@ -74,6 +74,9 @@ pub enum ProgramError {
 
     #[msg("run_id must be 32 bytes or less")]
     RunIdInvalidLength,
+
+    #[msg("Coordinator account can't be migrated to the current version")]
+    CoordinatorAccountMigrationFailed,
 }
 
 impl From<CoordinatorError> for ProgramError {
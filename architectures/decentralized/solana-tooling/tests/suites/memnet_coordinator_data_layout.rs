@@ -1,3 +1,4 @@
+use psyche_coordinator::MixedPrecisionDtype;
 use psyche_coordinator::Round;
 use psyche_coordinator::RunState;
 use psyche_coordinator::model::Checkpoint;
@@ -7,6 +8,7 @@ use psyche_coordinator::model::LLMTrainingDataLocation;
 use psyche_coordinator::model::LLMTrainingDataType;
 use psyche_coordinator::model::Model;
 use psyche_core::CosineLR;
+use psyche_core::DistroExtensions;
 use psyche_core::FixedString;
 use psyche_core::FixedVec;
 use psyche_core::LearningRateSchedule;
@@ -16,11 +18,20 @@ use psyche_core::SmallBoolean;
 use psyche_core::TokenSize;
 use psyche_solana_coordinator::CoordinatorAccount;
 use psyche_solana_coordinator::coordinator_account_from_bytes;
+use psyche_solana_coordinator::migrate_coordinator_account_from_v1;
 
 #[tokio::test]
 pub async fn run() {
-    let coordinator_bytes =
+    let mut coordinator_bytes =
         include_bytes!("../fixtures/coordinator-account-v1.so").to_vec();
+    assert_eq!(
+        coordinator_bytes.len(),
+        CoordinatorAccount::SPACE_WITH_DISCRIMINATOR_V1
+    );
+    // A version 1 account can't be read until it's migrated
+    assert!(coordinator_account_from_bytes(&coordinator_bytes).is_err());
+    coordinator_bytes.resize(CoordinatorAccount::space_with_discriminator(), 0);
+    migrate_coordinator_account_from_v1(&mut coordinator_bytes).unwrap();
     let coordinator_account =
         coordinator_account_from_bytes(&coordinator_bytes).unwrap();
     eprintln!("coordinator_account.state:{:#?}", coordinator_account.state);
@@ -127,6 +138,17 @@ pub async fn run() {
     assert_eq!(coordinator.config.global_batch_size_end, 2048);
     assert_eq!(coordinator.config.verification_percent, 0);
     assert_eq!(coordinator.config.waiting_for_members_extra_time, 3);
+    assert_eq!(coordinator.config.straggler_fraction, 0.0);
+    assert_eq!(coordinator.config.straggler_grace_period_secs, 0);
+    assert!(!coordinator.config.adaptive_witness_quorum);
+    assert_eq!(
+        coordinator.config.mixed_precision,
+        MixedPrecisionDtype::Disabled
+    );
+    assert_eq!(
+        coordinator.config.distro_extensions,
+        DistroExtensions::default()
+    );
     // Coordinator progress
     assert_eq!(coordinator.progress.epoch, 0);
     assert_eq!(coordinator.progress.step, 0);
@@ -142,6 +164,11 @@ pub async fn run() {
     assert_eq!(epoch_state.start_timestamp, 0);
     assert_eq!(epoch_state.first_round, SmallBoolean::FALSE);
     assert_eq!(epoch_state.cold_start_epoch, SmallBoolean::FALSE);
+    assert_eq!(epoch_state.straggler_grace_start_timestamp, 0);
+    assert_eq!(epoch_state.excluded_stragglers, FixedVec::default());
+    assert_eq!(epoch_state.witness_success_history, 0);
+    assert_eq!(epoch_state.witness_success_history_len, 0);
+    assert_eq!(epoch_state.witness_quorum_reduced, SmallBoolean::FALSE);
     // Coordinator clients state
     let clients_state = state.clients_state;
     assert_eq!(clients_state.clients.len(), 0);
@@ -1,5 +1,6 @@
 use psyche_coordinator::CommitteeSelection;
 use psyche_coordinator::CoordinatorConfig;
+use psyche_coordinator::MixedPrecisionDtype;
 use psyche_coordinator::SOLANA_MAX_NUM_WITNESSES;
 use psyche_coordinator::WAITING_FOR_MEMBERS_EXTRA_SECONDS;
 use psyche_coordinator::model::Checkpoint;
@@ -10,6 +11,7 @@ use psyche_coordinator::model::LLMTrainingDataLocation;
 use psyche_coordinator::model::LLMTrainingDataType;
 use psyche_coordinator::model::Model;
 use psyche_core::ConstantLR;
+use psyche_core::DistroExtensions;
 use psyche_core::LearningRateSchedule;
 use psyche_core::NodeIdentity;
 use psyche_core::OptimizerDefinition;
@@ -106,6 +108,11 @@ pub async fn run() {
             waiting_for_members_extra_time: WAITING_FOR_MEMBERS_EXTRA_SECONDS
                 as u8,
             total_steps: 100,
+            straggler_fraction: 0.0,
+            straggler_grace_period_secs: 0,
+            adaptive_witness_quorum: false,
+            mixed_precision: MixedPrecisionDtype::Disabled,
+            distro_extensions: DistroExtensions::default(),
         }),
         Some(Model::LLM(LLM {
             architecture: LLMArchitecture::HfLlama,
@@ -1,4 +1,5 @@
 use psyche_coordinator::CoordinatorConfig;
+use psyche_coordinator::MixedPrecisionDtype;
 use psyche_coordinator::WAITING_FOR_MEMBERS_EXTRA_SECONDS;
 use psyche_coordinator::model::Checkpoint;
 use psyche_coordinator::model::HubRepo;
@@ -8,6 +9,7 @@ use psyche_coordinator::model::LLMTrainingDataLocation;
 use psyche_coordinator::model::LLMTrainingDataType;
 use psyche_coordinator::model::Model;
 use psyche_core::ConstantLR;
+use psyche_core::DistroExtensions;
 use psyche_core::LearningRateSchedule;
 use psyche_core::OptimizerDefinition;
 use psyche_solana_coordinator::CoordinatorAccount;
@@ -53,6 +55,11 @@ pub async fn run() {
             total_steps: 100,
             waiting_for_members_extra_time: WAITING_FOR_MEMBERS_EXTRA_SECONDS
                 as u8,
+            straggler_fraction: 0.0,
+            straggler_grace_period_secs: 0,
+            adaptive_witness_quorum: false,
+            mixed_precision: MixedPrecisionDtype::Disabled,
+            distro_extensions: DistroExtensions::default(),
         }),
         model: Some(Model::LLM(LLM {
             architecture: LLMArchitecture::HfLlama,
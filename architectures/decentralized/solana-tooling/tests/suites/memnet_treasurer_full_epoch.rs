@@ -2,6 +2,7 @@ use std::vec;
 
 use psyche_coordinator::CommitteeSelection;
 use psyche_coordinator::CoordinatorConfig;
+use psyche_coordinator::MixedPrecisionDtype;
 use psyche_coordinator::SOLANA_MAX_NUM_WITNESSES;
 use psyche_coordinator::WAITING_FOR_MEMBERS_EXTRA_SECONDS;
 use psyche_coordinator::model::Checkpoint;
@@ -12,6 +13,7 @@ use psyche_coordinator::model::LLMTrainingDataLocation;
 use psyche_coordinator::model::LLMTrainingDataType;
 use psyche_coordinator::model::Model;
 use psyche_core::ConstantLR;
+use psyche_core::DistroExtensions;
 use psyche_core::LearningRateSchedule;
 use psyche_core::NodeIdentity;
 use psyche_core::OptimizerDefinition;
@@ -228,6 +230,11 @@ pub async fn run() {
                 epoch_time,
                 total_steps: 100,
                 waiting_for_members_extra_time: 3,
+                straggler_fraction: 0.0,
+                straggler_grace_period_secs: 0,
+                adaptive_witness_quorum: false,
+                mixed_precision: MixedPrecisionDtype::Disabled,
+                distro_extensions: DistroExtensions::default(),
             }),
             model: Some(Model::LLM(LLM {
                 architecture: LLMArchitecture::HfLlama,
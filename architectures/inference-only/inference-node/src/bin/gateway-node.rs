@@ -21,9 +21,10 @@ use iroh::EndpointAddr;
 use psyche_inference::{
     INFERENCE_ALPN, InferenceGossipMessage, InferenceMessage, InferenceRequest, InferenceResponse,
 };
+use psyche_inference_node::InferenceRouter;
 use psyche_metrics::ClientMetrics;
 use psyche_network::{
-    DiscoveryMode, EndpointId, NetworkConnection, NetworkEvent, RelayKind, allowlist,
+    DiscoveryMode, EndpointId, NetworkConfig, NetworkConnection, NetworkEvent, RelayKind, allowlist,
 };
 use std::{collections::HashMap, fs, path::PathBuf, sync::Arc, time::Duration};
 use tokio::{
@@ -46,6 +47,10 @@ struct Args {
     #[arg(long, env = "IROH_RELAY", default_value = "psyche")]
     relay_kind: RelayKind,
 
+    /// run id whose gossip topic the inference network uses
+    #[arg(long, env = "PSYCHE_INFERENCE_RUN_ID", default_value = "inference")]
+    run_id: String,
+
     #[arg(long)]
     bootstrap_peer_file: Option<PathBuf>,
 
@@ -53,19 +58,8 @@ struct Args {
     write_endpoint_file: Option<PathBuf>,
 }
 
-#[derive(Clone, Debug)]
-struct InferenceNodeInfo {
-    peer_id: EndpointId,
-    model_name: Option<String>,
-    #[allow(dead_code)]
-    checkpoint_id: Option<String>,
-    #[allow(dead_code)]
-    capabilities: Vec<String>,
-    last_seen: std::time::Instant,
-}
-
 struct GatewayState {
-    available_nodes: RwLock<HashMap<EndpointId, InferenceNodeInfo>>,
+    router: RwLock<InferenceRouter>,
     pending_requests: RwLock<HashMap<String, mpsc::Sender<InferenceResponse>>>,
     network_tx: mpsc::Sender<(EndpointId, InferenceMessage)>,
     gossip_tx: mpsc::Sender<InferenceGossipMessage>,
@@ -121,6 +115,12 @@ struct LoadModelRequest {
     source: LoadModelSource,
 }
 
+#[derive(serde::Deserialize)]
+struct ReloadCheckpointRequest {
+    checkpoint_id: String,
+    checkpoint_source: String,
+}
+
 #[derive(serde::Serialize)]
 struct ChatCompletionChoice {
     index: usize,
@@ -143,22 +143,15 @@ async fn handle_inference(
     State(state): State<Arc<GatewayState>>,
     Json(req): Json<ChatCompletionRequest>,
 ) -> Result<Json<ChatCompletionResponse>, AppError> {
-    let nodes = state.available_nodes.read().await;
-
-    let nodes_with_model: Vec<(EndpointId, String)> = nodes
-        .values()
-        .filter_map(|n| Some((n.peer_id, n.model_name.clone()?)))
-        .collect();
+    let router = state.router.read().await;
 
-    if nodes_with_model.is_empty() {
-        // No nodes have models loaded yet
-        return Err(AppError::NoNodesAvailable);
-    }
-
-    // Select first available node with a model
-    // TODO: Add load balancing and model-specific routing in the future
-    let (target_peer_id, node_model_name) = &nodes_with_model[0];
-    let target_peer_id = *target_peer_id;
+    // No nodes have models loaded yet
+    // TODO: Add model-specific routing in the future
+    let target_peer_id = router.pick_node(&[]).ok_or(AppError::NoNodesAvailable)?;
+    let node_model_name = router
+        .node(&target_peer_id)
+        .and_then(|n| n.model_name.clone())
+        .unwrap_or_default();
 
     let model_name = req.model.clone().unwrap_or_else(|| node_model_name.clone());
 
@@ -167,7 +160,7 @@ async fn handle_inference(
         target_peer_id.fmt_short(),
         node_model_name
     );
-    drop(nodes);
+    drop(router);
 
     let messages: Vec<psyche_inference::ChatMessage> = req
         .messages
@@ -273,6 +266,38 @@ async fn handle_load_model(
     ))
 }
 
+/// Tells every inference node to load the weights of a new checkpoint of the model it serves.
+/// Nodes only act on this if this gateway's endpoint id is one of their checkpoint authorities.
+#[axum::debug_handler]
+async fn handle_reload_checkpoint(
+    State(state): State<Arc<GatewayState>>,
+    Json(req): Json<ReloadCheckpointRequest>,
+) -> Result<String, AppError> {
+    info!(
+        "Admin API: Received ReloadCheckpoint request for checkpoint: {} (source: {})",
+        req.checkpoint_id, req.checkpoint_source
+    );
+
+    let reload_msg = InferenceGossipMessage::ReloadCheckpoint {
+        checkpoint_id: req.checkpoint_id.clone(),
+        checkpoint_source: req.checkpoint_source,
+    };
+
+    state.gossip_tx.send(reload_msg).await.map_err(|e| {
+        error!("Failed to broadcast ReloadCheckpoint message: {:#}", e);
+        AppError::InternalError
+    })?;
+
+    info!(
+        "Successfully broadcasted ReloadCheckpoint message for: {}",
+        req.checkpoint_id
+    );
+    Ok(format!(
+        "ReloadCheckpoint broadcast sent for checkpoint: {}",
+        req.checkpoint_id
+    ))
+}
+
 #[axum::debug_handler]
 async fn handle_bootstrap(State(state): State<Arc<GatewayState>>) -> Json<EndpointAddr> {
     info!(
@@ -392,7 +417,7 @@ async fn run_gateway() -> Result<()> {
 
     info!("Initializing P2P network...");
     let metrics = Arc::new(ClientMetrics::default());
-    let run_id = "inference";
+    let run_id = args.run_id.as_str();
 
     type P2PNetwork = NetworkConnection<InferenceGossipMessage, ()>;
 
@@ -407,6 +432,7 @@ async fn run_gateway() -> Result<()> {
         allowlist::AllowAll,
         metrics.clone(),
         Some(cancel.clone()),
+        NetworkConfig::default(),
     )
     .await
     .context("Failed to initialize P2P network")?;
@@ -450,7 +476,7 @@ async fn run_gateway() -> Result<()> {
     let endpoint_addr = network.router().endpoint().addr();
 
     let state = Arc::new(GatewayState {
-        available_nodes: RwLock::new(HashMap::new()),
+        router: RwLock::new(InferenceRouter::new()),
         pending_requests: RwLock::new(HashMap::new()),
         network_tx,
         gossip_tx,
@@ -469,6 +495,9 @@ async fn run_gateway() -> Result<()> {
             let mut cleanup_interval = tokio::time::interval(Duration::from_secs(15));
             cleanup_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
+            let mut health_check_interval = tokio::time::interval(Duration::from_secs(30));
+            health_check_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
             loop {
                 tokio::select! {
                     _ = cancel.cancelled() => {
@@ -480,27 +509,34 @@ async fn run_gateway() -> Result<()> {
 
                     _ = cleanup_interval.tick() => {
                         let stale_threshold = Duration::from_secs(90);
-                        let mut nodes = state.available_nodes.write().await;
-                        let now = std::time::Instant::now();
-
-                        let stale_nodes: Vec<(EndpointId, Duration)> = nodes
-                            .iter()
-                            .filter_map(|(id, info)| {
-                                let age = now.duration_since(info.last_seen);
-                                if age > stale_threshold {
-                                    Some((*id, age))
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect();
+                        let stale_nodes = state.router.write().await.remove_stale(stale_threshold);
 
                         for (node_id, age) in stale_nodes {
                             warn!("Removing stale node {} (no heartbeat for {:?})", node_id.fmt_short(), age);
-                            nodes.remove(&node_id);
                         }
                     }
 
+                    _ = health_check_interval.tick() => {
+                        let node_ids = state.router.read().await.node_ids();
+                        if node_ids.is_empty() {
+                            continue;
+                        }
+                        let endpoint = network.router().endpoint().clone();
+                        let state_clone = state.clone();
+                        task_set.spawn(async move {
+                            let unresponsive = psyche_inference_node::router::unresponsive_nodes(
+                                &endpoint,
+                                node_ids,
+                                Duration::from_secs(10),
+                            ).await;
+                            let mut router = state_clone.router.write().await;
+                            for node_id in unresponsive {
+                                warn!("Removing unresponsive node {} (failed health check)", node_id.fmt_short());
+                                router.remove(&node_id);
+                            }
+                        });
+                    }
+
                     Some((target_peer_id, msg)) = network_rx.recv() => {
                         match msg {
                             InferenceMessage::Request(req) => {
@@ -556,8 +592,8 @@ async fn run_gateway() -> Result<()> {
                             Ok(Some(NetworkEvent::MessageReceived((peer_id, msg)))) => {
                                 info!("Received gossip message from {}", peer_id.fmt_short());
                                 match msg {
-                                    InferenceGossipMessage::NodeAvailable { model_name, checkpoint_id, capabilities, timestamp_ms: _ } => {
-                                        let is_new = !state.available_nodes.read().await.contains_key(&peer_id);
+                                    InferenceGossipMessage::NodeAvailable { ref model_name, ref checkpoint_id, ref capabilities, timestamp_ms: _ } => {
+                                        let is_new = state.router.write().await.handle_gossip(peer_id, &msg);
 
                                         if is_new {
                                             info!("Discovered NEW inference node!");
@@ -570,19 +606,10 @@ async fn run_gateway() -> Result<()> {
                                                 peer_id.fmt_short(),
                                                 model_name.as_deref().unwrap_or("<idle>"));
                                         }
-
-                                        let node_info = InferenceNodeInfo {
-                                            peer_id,
-                                            model_name,
-                                            checkpoint_id,
-                                            capabilities,
-                                            last_seen: std::time::Instant::now(),
-                                        };
-                                        state.available_nodes.write().await.insert(peer_id, node_info);
                                     }
                                     InferenceGossipMessage::NodeUnavailable => {
                                         info!("Inference node {} went offline", peer_id.fmt_short());
-                                        state.available_nodes.write().await.remove(&peer_id);
+                                        state.router.write().await.handle_gossip(peer_id, &msg);
                                     }
                                     InferenceGossipMessage::LoadModel { .. } => {
                                         debug!("Ignoring LoadModel message (gateways don't load models)");
@@ -609,6 +636,7 @@ async fn run_gateway() -> Result<()> {
     let app = Router::new()
         .route("/v1/chat/completions", post(handle_inference))
         .route("/admin/load-model", post(handle_load_model))
+        .route("/admin/reload-checkpoint", post(handle_reload_checkpoint))
         .route("/bootstrap", get(handle_bootstrap))
         .with_state(state.clone());
 
@@ -10,7 +10,9 @@ use anyhow::{Context, Result};
 use clap::Parser;
 use psyche_inference::InferenceGossipMessage;
 use psyche_metrics::ClientMetrics;
-use psyche_network::{DiscoveryMode, NetworkConnection, NetworkEvent, RelayKind, allowlist};
+use psyche_network::{
+    DiscoveryMode, NetworkConfig, NetworkConnection, NetworkEvent, RelayKind, allowlist,
+};
 use std::{fs, path::PathBuf, sync::Arc, time::Duration};
 use tokio::time::sleep;
 use tokio_util::sync::CancellationToken;
@@ -82,6 +84,7 @@ async fn main() -> Result<()> {
         allowlist::AllowAll,
         metrics.clone(),
         Some(cancel.clone()),
+        NetworkConfig::default(),
     )
     .await
     .context("Failed to initialize P2P network")?;
@@ -1,3 +1,8 @@
+pub mod openai;
+pub mod router;
+
+pub use router::InferenceRouter;
+
 use anyhow::{Context, Result};
 use iroh::EndpointAddr;
 use std::{fs, path::PathBuf};
@@ -13,8 +13,10 @@ use clap::{Args as ClapArgs, Parser, Subcommand};
 use psyche_inference::{
     INFERENCE_ALPN, InferenceGossipMessage, InferenceNode, InferenceProtocol, ModelSource,
 };
-use psyche_metrics::ClientMetrics;
-use psyche_network::{DiscoveryMode, NetworkConnection, NetworkEvent, RelayKind, allowlist};
+use psyche_metrics::{ClientMetrics, InferenceMetrics};
+use psyche_network::{
+    DiscoveryMode, EndpointId, NetworkConfig, NetworkConnection, NetworkEvent, RelayKind, allowlist,
+};
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::{fs, time::Duration};
@@ -84,6 +86,18 @@ struct RunArgs {
     #[arg(long, default_value = "")]
     capabilities: String,
 
+    /// run id whose gossip topic the inference network uses
+    #[arg(long, env = "PSYCHE_INFERENCE_RUN_ID", default_value = "inference")]
+    run_id: String,
+
+    /// warn when an inference request takes longer than this
+    #[arg(long, env = "PSYCHE_INFERENCE_MAX_LATENCY_MS")]
+    max_acceptable_latency_ms: Option<u64>,
+
+    /// address to serve the OpenAI-compatible HTTP API on (e.g. "0.0.0.0:8001")
+    #[arg(long, env = "PSYCHE_INFERENCE_API_ADDR")]
+    api_listen_addr: Option<String>,
+
     /// gateway HTTP URL to fetch bootstrap peer from
     #[arg(long, env = "PSYCHE_GATEWAY_URL")]
     bootstrap_url: Option<String>,
@@ -95,6 +109,144 @@ struct RunArgs {
     /// write endpoint address to file for other nodes to bootstrap from
     #[arg(long)]
     write_endpoint_file: Option<PathBuf>,
+
+    /// endpoint ids allowed to make this node reload its checkpoint (comma-separated).
+    /// checkpoint reloads from anyone else are ignored.
+    #[arg(
+        long,
+        env = "PSYCHE_INFERENCE_CHECKPOINT_AUTHORITY",
+        value_delimiter = ','
+    )]
+    checkpoint_authority: Vec<EndpointId>,
+}
+
+async fn loaded_model_name(model_state: &RwLock<ModelLoadState>) -> Option<String> {
+    match &*model_state.read().await {
+        ModelLoadState::Loaded(name) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+fn availability_message(
+    model_name: Option<String>,
+    checkpoint_id: Option<String>,
+    capabilities: Vec<String>,
+) -> InferenceGossipMessage {
+    InferenceGossipMessage::NodeAvailable {
+        model_name,
+        checkpoint_id,
+        capabilities,
+        timestamp_ms: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64,
+    }
+}
+
+/// Replaces the current engine with one loaded from `model_path` in the background,
+/// since loading can take 10-60+ seconds and must not block heartbeats.
+#[allow(clippy::too_many_arguments)]
+fn spawn_model_load(
+    inference_node_shared: Arc<RwLock<Option<InferenceNode>>>,
+    model_state: Arc<RwLock<ModelLoadState>>,
+    current_checkpoint: Arc<RwLock<Option<String>>>,
+    model_name: String,
+    model_path: String,
+    tensor_parallel_size: usize,
+    gpu_memory_utilization: f64,
+    metrics: Arc<InferenceMetrics>,
+    max_acceptable_latency_ms: Option<u64>,
+) {
+    tokio::spawn(async move {
+        // Shutdown old model if exists
+        let old_node = inference_node_shared.write().await.take();
+        if let Some(mut old_node) = old_node {
+            info!("Shutting down existing model");
+            if let Err(e) = old_node.shutdown() {
+                error!("Error shutting down old model: {:#}", e);
+            }
+            // Give vLLM time to release GPU memory before loading new model
+            // This prevents OOM when switching between large models
+            info!("Waiting 5s for GPU memory to be released...");
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+
+        // Load new model (blocking operation)
+        let load_result = (|| -> Result<InferenceNode> {
+            let mut new_node = InferenceNode::new(
+                model_path.clone(),
+                Some(tensor_parallel_size),
+                Some(gpu_memory_utilization),
+            )
+            .with_latency_metrics(metrics, max_acceptable_latency_ms);
+
+            new_node.initialize(Some(tensor_parallel_size), Some(gpu_memory_utilization))?;
+
+            Ok(new_node)
+        })();
+
+        match load_result {
+            Ok(new_node) => {
+                *inference_node_shared.write().await = Some(new_node);
+                *model_state.write().await = ModelLoadState::Loaded(model_name.clone());
+                *current_checkpoint.write().await = None;
+
+                info!(
+                    "Successfully loaded model: {} from {}",
+                    model_name, model_path
+                );
+                // Note: NodeAvailable will be broadcast on next heartbeat (every 30s)
+                // or the node can be manually queried to verify the model is loaded
+            }
+            Err(e) => {
+                error!(
+                    "Failed to load model {} from {}: {:#}",
+                    model_name, model_path, e
+                );
+                // Set back to Idle on failure
+                *model_state.write().await = ModelLoadState::Idle;
+                *current_checkpoint.write().await = None;
+            }
+        }
+    });
+}
+
+/// Loads a new checkpoint's weights into the running engine in the background.
+/// Inference waits on the engine lock until the update is done.
+fn spawn_weight_update(
+    inference_node_shared: Arc<RwLock<Option<InferenceNode>>>,
+    current_checkpoint: Arc<RwLock<Option<String>>>,
+    checkpoint_id: String,
+    checkpoint_source: String,
+) {
+    tokio::spawn(async move {
+        let mut node = inference_node_shared.write_owned().await;
+        let source = checkpoint_source.clone();
+        let result = tokio::task::spawn_blocking(move || match node.as_mut() {
+            Some(node) => node.update_weights(&source),
+            None => Err(anyhow::anyhow!("model was unloaded")),
+        })
+        .await;
+
+        match result {
+            Ok(Ok(())) => {
+                info!(
+                    "Updated weights to checkpoint {} from {}",
+                    checkpoint_id, checkpoint_source
+                );
+                *current_checkpoint.write().await = Some(checkpoint_id);
+            }
+            Ok(Err(e)) => {
+                error!(
+                    "Failed to update weights to checkpoint {} from {}: {:#}",
+                    checkpoint_id, checkpoint_source, e
+                );
+            }
+            Err(e) => {
+                error!("Weight update task for {} panicked: {}", checkpoint_id, e);
+            }
+        }
+    });
 }
 
 #[tokio::main]
@@ -169,12 +321,17 @@ async fn main() -> Result<()> {
     pyo3::prepare_freethreaded_python();
     info!("Python interpreter initialized");
 
+    let inference_metrics = Arc::new(InferenceMetrics::new());
     let inference_node_shared = if let Some(ref model_name) = run_args.model_name {
         info!("Initializing vLLM engine with model: {}...", model_name);
         let mut inference_node = InferenceNode::new(
             model_name.clone(),
             Some(run_args.tensor_parallel_size),
             Some(run_args.gpu_memory_utilization),
+        )
+        .with_latency_metrics(
+            inference_metrics.clone(),
+            run_args.max_acceptable_latency_ms,
         );
 
         inference_node
@@ -196,13 +353,15 @@ async fn main() -> Result<()> {
     } else {
         ModelLoadState::Idle
     }));
+    let current_checkpoint: Arc<RwLock<Option<String>>> = Arc::new(RwLock::new(None));
     let tensor_parallel_size = run_args.tensor_parallel_size;
     let gpu_memory_utilization = run_args.gpu_memory_utilization;
 
     info!("Initializing P2P network...");
 
     let metrics = Arc::new(ClientMetrics::default());
-    let run_id = "inference";
+    let run_id = run_args.run_id.as_str();
+    info!("Run ID: {}", run_id);
 
     type P2PNetwork = NetworkConnection<InferenceGossipMessage, ()>;
 
@@ -220,6 +379,7 @@ async fn main() -> Result<()> {
         allowlist::AllowAll, // No allowlist for inference network
         metrics.clone(),
         Some(cancel.clone()),
+        NetworkConfig::default(),
         (INFERENCE_ALPN, inference_protocol),
     )
     .await
@@ -237,22 +397,27 @@ async fn main() -> Result<()> {
         info!("Wrote endpoint to {:?}", endpoint_file);
     }
 
+    if let Some(api_listen_addr) = run_args.api_listen_addr.clone() {
+        let node = inference_node_shared.clone();
+        let cancel = cancel.clone();
+        tokio::spawn(async move {
+            if let Err(e) =
+                psyche_inference_node::openai::serve(&api_listen_addr, node, cancel).await
+            {
+                error!("{:#}", e);
+            }
+        });
+    }
+
     tokio::time::sleep(Duration::from_secs(2)).await;
 
     // announce availability via gossip
-    let model_name_for_broadcast = match &*model_state.read().await {
-        ModelLoadState::Loaded(name) => Some(name.clone()),
-        _ => None,
-    };
-    let availability_msg = InferenceGossipMessage::NodeAvailable {
-        model_name: model_name_for_broadcast.clone(),
-        checkpoint_id: None,
-        capabilities: capabilities.clone(),
-        timestamp_ms: std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64,
-    };
+    let model_name_for_broadcast = loaded_model_name(&model_state).await;
+    let availability_msg = availability_message(
+        model_name_for_broadcast.clone(),
+        current_checkpoint.read().await.clone(),
+        capabilities.clone(),
+    );
 
     network
         .broadcast(&availability_msg)
@@ -286,19 +451,13 @@ async fn main() -> Result<()> {
             }
 
             _ = heartbeat_interval.tick() => {
-                let model_name_for_broadcast = match &*model_state.read().await {
-                    ModelLoadState::Loaded(name) => Some(name.clone()),
-                    _ => None,
-                };
-                let availability_msg = InferenceGossipMessage::NodeAvailable {
-                    model_name: model_name_for_broadcast.clone(),
-                    checkpoint_id: None,
-                    capabilities: capabilities.clone(),
-                    timestamp_ms: std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_millis() as u64,
-                };
+                let model_name_for_broadcast = loaded_model_name(&model_state).await;
+                let checkpoint_id = current_checkpoint.read().await.clone();
+                let availability_msg = availability_message(
+                    model_name_for_broadcast.clone(),
+                    checkpoint_id,
+                    capabilities.clone(),
+                );
                 if let Err(e) = network.broadcast(&availability_msg) {
                     warn!("Failed to broadcast: {:#}", e);
                 } else if let Some(ref model) = model_name_for_broadcast {
@@ -359,67 +518,57 @@ async fn main() -> Result<()> {
                                 if should_load {
                                     *model_state.write().await = ModelLoadState::Loading(requested_model.clone());
                                     info!("Loading new model: {} (background task)", requested_model);
-
-                                    // Spawn background task to avoid blocking the event loop
-                                    // Model loading can take 10-60+ seconds, so we don't want to block heartbeats
-                                    let inference_node_shared_clone = inference_node_shared.clone();
-                                    let model_state_clone = model_state.clone();
-                                    let requested_model_clone = requested_model.clone();
-
-                                    tokio::spawn(async move {
-                                        // Shutdown old model if exists
-                                        let old_node = inference_node_shared_clone.write().await.take();
-                                        if let Some(mut old_node) = old_node {
-                                            info!("Shutting down existing model");
-                                            if let Err(e) = old_node.shutdown() {
-                                                error!("Error shutting down old model: {:#}", e);
-                                            }
-                                            // Give vLLM time to release GPU memory before loading new model
-                                            // This prevents OOM when switching between large models
-                                            info!("Waiting 5s for GPU memory to be released...");
-                                            tokio::time::sleep(Duration::from_secs(5)).await;
+                                    spawn_model_load(
+                                        inference_node_shared.clone(),
+                                        model_state.clone(),
+                                        current_checkpoint.clone(),
+                                        requested_model,
+                                        model_path,
+                                        tensor_parallel_size,
+                                        gpu_memory_utilization,
+                                        inference_metrics.clone(),
+                                        run_args.max_acceptable_latency_ms,
+                                    );
+                                }
+                            }
+                            InferenceGossipMessage::ReloadCheckpoint { checkpoint_id, checkpoint_source } => {
+                                if !run_args.checkpoint_authority.contains(&peer_id) {
+                                    warn!("Ignoring checkpoint reload {} from {}, which is not a checkpoint authority",
+                                          checkpoint_id, peer_id.fmt_short());
+                                } else {
+                                    info!("Received checkpoint reload request: {} from {}",
+                                          checkpoint_id, checkpoint_source);
+
+                                    let model_loaded = match &*model_state.read().await {
+                                        ModelLoadState::Loaded(_) => true,
+                                        ModelLoadState::Loading(name) => {
+                                            info!("Model load already in progress ({}), skipping checkpoint reload {}",
+                                                  name, checkpoint_id);
+                                            false
                                         }
-
-                                        // Load new model (blocking operation)
-                                        let load_result = (|| -> Result<InferenceNode> {
-                                            let mut new_node = InferenceNode::new(
-                                                model_path.clone(),
-                                                Some(tensor_parallel_size),
-                                                Some(gpu_memory_utilization),
+                                        ModelLoadState::Idle => {
+                                            warn!("No model loaded, ignoring checkpoint reload {}", checkpoint_id);
+                                            false
+                                        }
+                                    };
+                                    let already_loaded =
+                                        current_checkpoint.read().await.as_deref() == Some(checkpoint_id.as_str());
+
+                                    if model_loaded {
+                                        if already_loaded {
+                                            info!("Checkpoint {} already loaded, skipping", checkpoint_id);
+                                        } else {
+                                            info!("Updating weights to checkpoint {} (background task)", checkpoint_id);
+                                            spawn_weight_update(
+                                                inference_node_shared.clone(),
+                                                current_checkpoint.clone(),
+                                                checkpoint_id,
+                                                checkpoint_source,
                                             );
-
-                                            new_node.initialize(
-                                                Some(tensor_parallel_size),
-                                                Some(gpu_memory_utilization),
-                                            )?;
-
-                                            Ok(new_node)
-                                        })();
-
-                                        match load_result {
-                                            Ok(new_node) => {
-                                                *inference_node_shared_clone.write().await = Some(new_node);
-                                                *model_state_clone.write().await = ModelLoadState::Loaded(requested_model_clone.clone());
-
-                                                info!("Successfully loaded model: {}", requested_model_clone);
-                                                // Note: NodeAvailable will be broadcast on next heartbeat (every 30s)
-                                                // or the node can be manually queried to verify the model is loaded
-                                            }
-                                            Err(e) => {
-                                                error!("Failed to load model {}: {:#}", requested_model_clone, e);
-                                                // Set back to Idle on failure
-                                                *model_state_clone.write().await = ModelLoadState::Idle;
-                                            }
                                         }
-                                    });
+                                    }
                                 }
                             }
-                            InferenceGossipMessage::ReloadCheckpoint { checkpoint_id, checkpoint_source } => {
-                                info!("Received checkpoint reload request: {} from {}",
-                                      checkpoint_id, checkpoint_source);
-                                // TODO: implement checkpoint reloading for RL training
-                                warn!("Checkpoint reloading not yet implemented");
-                            }
                         }
                     }
                     Ok(Some(NetworkEvent::DownloadComplete(_))) => {
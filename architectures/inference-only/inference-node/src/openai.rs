@@ -0,0 +1,444 @@
+//! OpenAI-compatible HTTP API served directly by an inference node
+//!
+//! Exposes `POST /v1/chat/completions` and `POST /v1/completions`, running
+//! requests on the node's local vLLM engine. When `stream: true` is set the
+//! completion is sent as server-sent events while it's generated, terminated
+//! by `data: [DONE]`.
+
+use anyhow::{Context, Result};
+use axum::{
+    Json, Router,
+    extract::State,
+    http::StatusCode,
+    response::{
+        IntoResponse, Response,
+        sse::{Event, Sse},
+    },
+    routing::post,
+};
+use futures::StreamExt;
+use psyche_inference::{ChatMessage, Generation, InferenceNode, Prompt, SamplingParams};
+use serde::{Deserialize, Serialize};
+use std::{convert::Infallible, sync::Arc};
+use tokio::sync::{OwnedRwLockReadGuard, RwLock, mpsc};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+
+pub type SharedInferenceNode = Arc<RwLock<Option<InferenceNode>>>;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum StopSequences {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl From<StopSequences> for Vec<String> {
+    fn from(value: StopSequences) -> Self {
+        match value {
+            StopSequences::One(stop) => vec![stop],
+            StopSequences::Many(stops) => stops,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatCompletionRequest {
+    #[allow(dead_code)]
+    model: Option<String>,
+    messages: Vec<ChatMessage>,
+    max_tokens: Option<usize>,
+    temperature: Option<f64>,
+    top_p: Option<f64>,
+    stop: Option<StopSequences>,
+    #[serde(default)]
+    stream: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct CompletionRequest {
+    #[allow(dead_code)]
+    model: Option<String>,
+    prompt: String,
+    max_tokens: Option<usize>,
+    temperature: Option<f64>,
+    top_p: Option<f64>,
+    stop: Option<StopSequences>,
+    #[serde(default)]
+    stream: bool,
+}
+
+fn sampling_params(
+    max_tokens: Option<usize>,
+    temperature: Option<f64>,
+    top_p: Option<f64>,
+    stop: Option<StopSequences>,
+) -> SamplingParams {
+    let defaults = SamplingParams::default();
+    SamplingParams {
+        temperature: temperature.unwrap_or(defaults.temperature),
+        top_p: top_p.unwrap_or(defaults.top_p),
+        max_tokens: max_tokens.unwrap_or(defaults.max_tokens),
+        stop: stop.map(Into::into).unwrap_or_default(),
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
+struct Usage {
+    prompt_tokens: usize,
+    completion_tokens: usize,
+    total_tokens: usize,
+}
+
+impl From<&Generation> for Usage {
+    fn from(generation: &Generation) -> Self {
+        Self {
+            prompt_tokens: generation.prompt_tokens,
+            completion_tokens: generation.completion_tokens,
+            total_tokens: generation.prompt_tokens + generation.completion_tokens,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ChatCompletionChoice {
+    index: usize,
+    message: ChatMessage,
+    finish_reason: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ChatCompletionResponse {
+    id: String,
+    object: &'static str,
+    created: u64,
+    model: String,
+    choices: Vec<ChatCompletionChoice>,
+    usage: Usage,
+}
+
+#[derive(Serialize, Default)]
+struct ChatDelta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ChatCompletionChunkChoice {
+    index: usize,
+    delta: ChatDelta,
+    finish_reason: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ChatCompletionChunk {
+    id: String,
+    object: &'static str,
+    created: u64,
+    model: String,
+    choices: Vec<ChatCompletionChunkChoice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage: Option<Usage>,
+}
+
+#[derive(Serialize)]
+struct CompletionChoice {
+    index: usize,
+    text: String,
+    finish_reason: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CompletionResponse {
+    id: String,
+    object: &'static str,
+    created: u64,
+    model: String,
+    choices: Vec<CompletionChoice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage: Option<Usage>,
+}
+
+#[derive(Debug)]
+enum ApiError {
+    NoModelLoaded,
+    Inference(anyhow::Error),
+}
+
+impl ApiError {
+    fn status_and_body(self) -> (StatusCode, serde_json::Value) {
+        let (status, kind, message) = match self {
+            ApiError::NoModelLoaded => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "service_unavailable",
+                "No model loaded on this inference node".to_string(),
+            ),
+            ApiError::Inference(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "server_error",
+                format!("{e:#}"),
+            ),
+        };
+        let body = serde_json::json!({
+            "error": {
+                "message": message,
+                "type": kind,
+            }
+        });
+        (status, body)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, body) = self.status_and_body();
+        (status, Json(body)).into_response()
+    }
+}
+
+fn unix_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// The loaded engine, read-locked so weight updates wait for the generations using it
+type LockedNode = OwnedRwLockReadGuard<Option<InferenceNode>, InferenceNode>;
+
+async fn lock_node(node: &SharedInferenceNode) -> Result<LockedNode, ApiError> {
+    OwnedRwLockReadGuard::try_map(node.clone().read_owned().await, Option::as_ref)
+        .map_err(|_| ApiError::NoModelLoaded)
+}
+
+/// Runs a generation on a blocking thread, which keeps `node` locked until it's done.
+/// With `on_text`, each new piece of the completion is passed on as it's generated.
+async fn run_generation(
+    node: LockedNode,
+    prompt: Prompt,
+    params: SamplingParams,
+    on_text: Option<mpsc::UnboundedSender<String>>,
+) -> Result<Generation, ApiError> {
+    debug!("Running API generation with {params:?}");
+    // generation holds the GIL and blocks until vLLM is done
+    tokio::task::spawn_blocking(move || match on_text {
+        Some(on_text) => node.generate_streaming(&prompt, &params, move |text| {
+            let _ = on_text.send(text);
+        }),
+        None => node.generate(&prompt, &params),
+    })
+    .await
+    .map_err(|e| ApiError::Inference(e.into()))?
+    .map_err(ApiError::Inference)
+}
+
+/// A piece of a streamed completion
+enum StreamPart<'a> {
+    Text(String),
+    Finished(&'a Generation),
+}
+
+fn json_event(data: &impl Serialize) -> Event {
+    Event::default()
+        .json_data(data)
+        .expect("chunk is serializable")
+}
+
+/// Streams a generation as server-sent events: `first` if any, a chunk for every piece of
+/// text as it's generated, then the finishing chunk and `[DONE]`.
+fn stream_generation<T: Serialize>(
+    node: LockedNode,
+    prompt: Prompt,
+    params: SamplingParams,
+    first: Option<T>,
+    to_chunk: impl Fn(StreamPart) -> T + Send + 'static,
+) -> Response {
+    let (events_tx, events_rx) = mpsc::unbounded_channel();
+    if let Some(first) = first {
+        let _ = events_tx.send(json_event(&first));
+    }
+    tokio::spawn(async move {
+        let (text_tx, mut text_rx) = mpsc::unbounded_channel();
+        let generation = run_generation(node, prompt, params, Some(text_tx));
+        tokio::pin!(generation);
+        let result = loop {
+            tokio::select! {
+                biased;
+                Some(text) = text_rx.recv() => {
+                    let _ = events_tx.send(json_event(&to_chunk(StreamPart::Text(text))));
+                }
+                result = &mut generation => break result,
+            }
+        };
+        while let Ok(text) = text_rx.try_recv() {
+            let _ = events_tx.send(json_event(&to_chunk(StreamPart::Text(text))));
+        }
+        let _ = events_tx.send(match result {
+            Ok(generation) => json_event(&to_chunk(StreamPart::Finished(&generation))),
+            // the response has already started, so errors can only be reported in the stream
+            Err(e) => json_event(&e.status_and_body().1),
+        });
+    });
+    let events = futures::stream::unfold(events_rx, |mut events_rx| async move {
+        let event = events_rx.recv().await?;
+        Some((event, events_rx))
+    })
+    .chain(futures::stream::once(async {
+        Event::default().data("[DONE]")
+    }))
+    .map(Ok::<_, Infallible>);
+    Sse::new(events).into_response()
+}
+
+async fn handle_chat_completions(
+    State(node): State<SharedInferenceNode>,
+    Json(req): Json<ChatCompletionRequest>,
+) -> Result<Response, ApiError> {
+    let params = sampling_params(req.max_tokens, req.temperature, req.top_p, req.stop);
+    let node = lock_node(&node).await?;
+    let prompt = Prompt::Chat(req.messages);
+
+    let id = format!("chatcmpl-{}", uuid::Uuid::new_v4());
+    let created = unix_timestamp();
+    let model = node.model_name().to_string();
+
+    if !req.stream {
+        let generation = run_generation(node, prompt, params, None).await?;
+        return Ok(Json(ChatCompletionResponse {
+            id,
+            object: "chat.completion",
+            created,
+            model,
+            usage: Usage::from(&generation),
+            choices: vec![ChatCompletionChoice {
+                index: 0,
+                message: ChatMessage {
+                    role: "assistant".to_string(),
+                    content: generation.generated_text,
+                },
+                finish_reason: generation.finish_reason,
+            }],
+        })
+        .into_response());
+    }
+
+    let chunk = move |delta, finish_reason, usage| ChatCompletionChunk {
+        id: id.clone(),
+        object: "chat.completion.chunk",
+        created,
+        model: model.clone(),
+        choices: vec![ChatCompletionChunkChoice {
+            index: 0,
+            delta,
+            finish_reason,
+        }],
+        usage,
+    };
+    let first = chunk(
+        ChatDelta {
+            role: Some("assistant".to_string()),
+            content: None,
+        },
+        None,
+        None,
+    );
+    Ok(stream_generation(
+        node,
+        prompt,
+        params,
+        Some(first),
+        move |part| match part {
+            StreamPart::Text(text) => chunk(
+                ChatDelta {
+                    role: None,
+                    content: Some(text),
+                },
+                None,
+                None,
+            ),
+            StreamPart::Finished(generation) => chunk(
+                ChatDelta::default(),
+                generation.finish_reason.clone(),
+                Some(Usage::from(generation)),
+            ),
+        },
+    ))
+}
+
+async fn handle_completions(
+    State(node): State<SharedInferenceNode>,
+    Json(req): Json<CompletionRequest>,
+) -> Result<Response, ApiError> {
+    let params = sampling_params(req.max_tokens, req.temperature, req.top_p, req.stop);
+    let node = lock_node(&node).await?;
+    let prompt = Prompt::Text(req.prompt);
+
+    let id = format!("cmpl-{}", uuid::Uuid::new_v4());
+    let created = unix_timestamp();
+    let model = node.model_name().to_string();
+
+    let response = move |text, finish_reason, usage| CompletionResponse {
+        id: id.clone(),
+        object: "text_completion",
+        created,
+        model: model.clone(),
+        choices: vec![CompletionChoice {
+            index: 0,
+            text,
+            finish_reason,
+        }],
+        usage,
+    };
+
+    if !req.stream {
+        let generation = run_generation(node, prompt, params, None).await?;
+        let usage = Usage::from(&generation);
+        return Ok(Json(response(
+            generation.generated_text,
+            generation.finish_reason,
+            Some(usage),
+        ))
+        .into_response());
+    }
+
+    Ok(stream_generation(
+        node,
+        prompt,
+        params,
+        None,
+        move |part| match part {
+            StreamPart::Text(text) => response(text, None, None),
+            StreamPart::Finished(generation) => response(
+                String::new(),
+                generation.finish_reason.clone(),
+                Some(Usage::from(generation)),
+            ),
+        },
+    ))
+}
+
+pub fn router(node: SharedInferenceNode) -> Router {
+    Router::new()
+        .route("/v1/chat/completions", post(handle_chat_completions))
+        .route("/v1/completions", post(handle_completions))
+        .with_state(node)
+}
+
+/// Serves the OpenAI-compatible API on `listen_addr` until `cancel` is triggered
+pub async fn serve(
+    listen_addr: &str,
+    node: SharedInferenceNode,
+    cancel: CancellationToken,
+) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(listen_addr)
+        .await
+        .context("Failed to bind OpenAI API server")?;
+    info!("OpenAI-compatible API listening on {}", listen_addr);
+
+    axum::serve(listener, router(node))
+        .with_graceful_shutdown(cancel.cancelled_owned())
+        .await
+        .context("OpenAI API server error")
+}
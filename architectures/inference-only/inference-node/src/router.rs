@@ -0,0 +1,247 @@
+//! Request routing across the inference nodes announced over gossip
+
+use anyhow::{Context, Result};
+use psyche_inference::{INFERENCE_ALPN, InferenceGossipMessage, InferenceMessage};
+use psyche_network::EndpointId;
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+use tracing::{debug, info};
+
+#[derive(Clone, Debug)]
+pub struct RoutedNode {
+    pub model_name: Option<String>,
+    pub checkpoint_id: Option<String>,
+    pub capabilities: Vec<String>,
+    pub last_seen: Instant,
+}
+
+/// Tracks the inference nodes available on the network and spreads requests
+/// across them round-robin.
+#[derive(Debug, Default)]
+pub struct InferenceRouter {
+    nodes: HashMap<EndpointId, RoutedNode>,
+    next: AtomicUsize,
+}
+
+impl InferenceRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates the set of known nodes from a gossip message.
+    /// Returns true if the message announced a node we didn't know about.
+    pub fn handle_gossip(&mut self, peer_id: EndpointId, message: &InferenceGossipMessage) -> bool {
+        match message {
+            InferenceGossipMessage::NodeAvailable {
+                model_name,
+                checkpoint_id,
+                capabilities,
+                timestamp_ms: _,
+            } => self
+                .nodes
+                .insert(
+                    peer_id,
+                    RoutedNode {
+                        model_name: model_name.clone(),
+                        checkpoint_id: checkpoint_id.clone(),
+                        capabilities: capabilities.clone(),
+                        last_seen: Instant::now(),
+                    },
+                )
+                .is_none(),
+            InferenceGossipMessage::NodeUnavailable => {
+                self.remove(&peer_id);
+                false
+            }
+            InferenceGossipMessage::LoadModel { .. }
+            | InferenceGossipMessage::ReloadCheckpoint { .. } => false,
+        }
+    }
+
+    /// Picks the next node, round-robin, among those with a model loaded and
+    /// all of `required_capabilities`.
+    pub fn pick_node(&self, required_capabilities: &[String]) -> Option<EndpointId> {
+        let mut candidates: Vec<EndpointId> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| {
+                node.model_name.is_some()
+                    && required_capabilities
+                        .iter()
+                        .all(|required| node.capabilities.contains(required))
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        // sort so the rotation order doesn't depend on hashmap iteration order
+        candidates.sort();
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+        Some(candidates[index])
+    }
+
+    pub fn node(&self, id: &EndpointId) -> Option<&RoutedNode> {
+        self.nodes.get(id)
+    }
+
+    pub fn node_ids(&self) -> Vec<EndpointId> {
+        self.nodes.keys().copied().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn remove(&mut self, id: &EndpointId) -> Option<RoutedNode> {
+        self.nodes.remove(id)
+    }
+
+    /// Removes nodes that haven't announced themselves within `threshold`,
+    /// returning them with how long ago they were last seen.
+    pub fn remove_stale(&mut self, threshold: Duration) -> Vec<(EndpointId, Duration)> {
+        let now = Instant::now();
+        let stale: Vec<(EndpointId, Duration)> = self
+            .nodes
+            .iter()
+            .filter_map(|(id, node)| {
+                let age = now.duration_since(node.last_seen);
+                (age > threshold).then_some((*id, age))
+            })
+            .collect();
+        for (id, _) in &stale {
+            self.nodes.remove(id);
+        }
+        stale
+    }
+}
+
+/// Checks that an inference node is reachable and answering on the inference protocol.
+pub async fn ping_node(endpoint: &iroh::Endpoint, peer_id: EndpointId) -> Result<()> {
+    let connection = endpoint
+        .connect(peer_id, INFERENCE_ALPN)
+        .await
+        .context("Failed to connect to peer")?;
+    let (mut send, mut recv) = connection
+        .open_bi()
+        .await
+        .context("Failed to open bidirectional stream")?;
+
+    let request_bytes = postcard::to_allocvec(&InferenceMessage::Ping)?;
+    send.write_all(&request_bytes).await?;
+    send.finish()?;
+
+    let response_bytes = recv.read_to_end(1024).await?;
+    match postcard::from_bytes(&response_bytes).context("Failed to deserialize ping response")? {
+        InferenceMessage::Pong => {
+            debug!("Ping to {} succeeded", peer_id.fmt_short());
+            Ok(())
+        }
+        _ => anyhow::bail!("Unexpected response to ping"),
+    }
+}
+
+/// Pings every node in `node_ids` concurrently, returning the ones that
+/// failed to answer within `timeout`.
+pub async fn unresponsive_nodes(
+    endpoint: &iroh::Endpoint,
+    node_ids: Vec<EndpointId>,
+    timeout: Duration,
+) -> Vec<EndpointId> {
+    let pings = node_ids.into_iter().map(|id| async move {
+        match tokio::time::timeout(timeout, ping_node(endpoint, id)).await {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => {
+                info!("Health check for {} failed: {:#}", id.fmt_short(), e);
+                Some(id)
+            }
+            Err(_) => {
+                info!("Health check for {} timed out", id.fmt_short());
+                Some(id)
+            }
+        }
+    });
+    futures::future::join_all(pings)
+        .await
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use psyche_network::SecretKey;
+
+    fn node_id(seed: u8) -> EndpointId {
+        SecretKey::from_bytes(&[seed; 32]).public()
+    }
+
+    fn available(model_name: Option<&str>, capabilities: &[&str]) -> InferenceGossipMessage {
+        InferenceGossipMessage::NodeAvailable {
+            model_name: model_name.map(str::to_string),
+            checkpoint_id: None,
+            capabilities: capabilities.iter().map(|c| c.to_string()).collect(),
+            timestamp_ms: 0,
+        }
+    }
+
+    #[test]
+    fn test_gossip_adds_and_removes_nodes() {
+        let mut router = InferenceRouter::new();
+        let id = node_id(1);
+
+        assert!(router.handle_gossip(id, &available(Some("gpt2"), &[])));
+        // heartbeats from a known node only refresh it
+        assert!(!router.handle_gossip(id, &available(Some("gpt2"), &["streaming"])));
+        assert_eq!(router.len(), 1);
+        assert_eq!(router.node(&id).unwrap().capabilities, vec!["streaming"]);
+
+        assert!(!router.handle_gossip(id, &InferenceGossipMessage::NodeUnavailable));
+        assert!(router.is_empty());
+    }
+
+    #[test]
+    fn test_pick_node_round_robins_over_capable_nodes() {
+        let mut router = InferenceRouter::new();
+        let (a, b, no_model, no_streaming) = (node_id(1), node_id(2), node_id(3), node_id(4));
+        router.handle_gossip(a, &available(Some("gpt2"), &["streaming"]));
+        router.handle_gossip(b, &available(Some("gpt2"), &["streaming"]));
+        router.handle_gossip(no_model, &available(None, &["streaming"]));
+        router.handle_gossip(no_streaming, &available(Some("gpt2"), &[]));
+
+        let required = vec!["streaming".to_string()];
+        let picks: Vec<_> = (0..4)
+            .map(|_| router.pick_node(&required).unwrap())
+            .collect();
+        assert_eq!(picks[0], picks[2]);
+        assert_eq!(picks[1], picks[3]);
+        assert_ne!(picks[0], picks[1]);
+        assert!(picks.iter().all(|id| *id == a || *id == b));
+
+        assert!(router.pick_node(&["vision".to_string()]).is_none());
+        assert!(InferenceRouter::new().pick_node(&[]).is_none());
+    }
+
+    #[test]
+    fn test_remove_stale_only_drops_old_nodes() {
+        let mut router = InferenceRouter::new();
+        let (fresh, stale) = (node_id(1), node_id(2));
+        router.handle_gossip(fresh, &available(Some("gpt2"), &[]));
+        router.handle_gossip(stale, &available(Some("gpt2"), &[]));
+        router.nodes.get_mut(&stale).unwrap().last_seen -= Duration::from_secs(60);
+
+        let removed = router.remove_stale(Duration::from_secs(30));
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].0, stale);
+        assert!(removed[0].1 >= Duration::from_secs(60));
+        assert_eq!(router.node_ids(), vec![fresh]);
+    }
+}
@@ -72,6 +72,9 @@
 
                 self'.packages.solana-toolbox-cli
 
+                # bash/zsh/fish completions for the psyche binaries
+                self'.packages.psyche-shell-completions
+
                 # for ci emulation
                 inputs'.garnix-cli.packages.default
 
@@ -118,6 +118,7 @@ lib.makeScope pkgs.newScope (
           solana-distributor-idl = self.callPackage ../architectures/decentralized/solana-distributor { };
 
           psyche-book = self.callPackage ../psyche-book { inherit rustPackages; };
+          psyche-shell-completions = self.callPackage ./shell-completions.nix { inherit rustPackages; };
         }
         rustPackages
         externalRustPackages
@@ -0,0 +1,38 @@
+{
+  lib,
+  stdenvNoCC,
+  installShellFiles,
+
+  # custom args
+  rustPackages,
+}:
+stdenvNoCC.mkDerivation {
+  name = "psyche-shell-completions";
+
+  dontUnpack = true;
+
+  nativeBuildInputs = [ installShellFiles ];
+
+  installPhase = ''
+    runHook preInstall
+
+    # we set HOME to a writable directory to avoid cache dir permission issues
+    export HOME=$TMPDIR
+
+    ${lib.concatMapStringsSep "\n"
+      (name: ''
+        installShellCompletion --cmd ${name} \
+          --bash <(${rustPackages.${name}}/bin/${name} generate-completions bash) \
+          --zsh <(${rustPackages.${name}}/bin/${name} generate-completions zsh) \
+          --fish <(${rustPackages.${name}}/bin/${name} generate-completions fish)
+      '')
+      [
+        "psyche-centralized-client"
+        "psyche-centralized-server"
+        "psyche-solana-client"
+      ]
+    }
+
+    runHook postInstall
+  '';
+}
@@ -1,4 +1,6 @@
-use psyche_core::{Barrier, BatchId, ClosedInterval, LearningRateSchedule, OptimizerDefinition};
+use psyche_core::{
+    Barrier, BatchId, ClosedInterval, DistroExtensions, LearningRateSchedule, OptimizerDefinition,
+};
 use psyche_modeling::{
     Batch, BatchData, BatchDataGPU, CausalLM, NopBarrier, ParallelModels, PythonCausalLM,
 };
@@ -133,9 +135,15 @@ impl Trainer {
             },
             lr_scheduler,
             optimizer,
+            DistroExtensions::default(),
             micro_batch_size,
+            1,
             None,
             grad_accum_in_fp32,
+            None,
+            None,
+            None,
+            false,
         );
 
         Ok(Self {
@@ -1,4 +1,6 @@
+import glob
 import logging
+import os
 import ray
 import time
 from itertools import count
@@ -19,6 +21,32 @@ except ImportError:
     RequestOutput = Any
 
 
+def _iterate_checkpoint_weights(checkpoint_path: str):
+    from safetensors.torch import safe_open
+
+    if not os.path.isdir(checkpoint_path):
+        from huggingface_hub import snapshot_download
+
+        checkpoint_path = snapshot_download(
+            checkpoint_path, allow_patterns=["*.safetensors"]
+        )
+
+    files = sorted(glob.glob(os.path.join(checkpoint_path, "*.safetensors")))
+    if not files:
+        raise FileNotFoundError(f"No .safetensors files in {checkpoint_path}")
+    for file in files:
+        with safe_open(file, framework="pt") as f:
+            for name in f.keys():
+                yield name, f.get_tensor(name)
+
+
+# Runs on every worker, so each tensor parallel rank loads its own shard.
+def _load_checkpoint_weights(worker, checkpoint_path: str):
+    worker.model_runner.model.load_weights(
+        weights=_iterate_checkpoint_weights(checkpoint_path)
+    )
+
+
 # A wrapper around vLLM's LLMEngine that supports dynamic weight updates.
 class UpdatableLLMEngine:
     def __init__(
@@ -77,6 +105,16 @@ class UpdatableLLMEngine:
     def abort_request(self, request_id: str):
         self.engine.abort_request(request_id)
 
+    # Loads new weights for the same architecture into the running engine,
+    # keeping the KV cache allocation and compiled graphs.
+    def update_weights(self, checkpoint_path: str):
+        if self.engine.has_unfinished_requests():
+            raise RuntimeError("Can't update weights with requests in flight")
+        logger.info(f"Updating weights from {checkpoint_path}")
+        self.engine.collective_rpc(_load_checkpoint_weights, args=(checkpoint_path,))
+        self.engine.reset_prefix_cache()
+        logger.info("Weights updated")
+
     def get_tokenizer(self):
         return self.engine.tokenizer
 
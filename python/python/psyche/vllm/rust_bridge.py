@@ -1,5 +1,5 @@
 import logging
-from typing import Dict, Any, Optional
+from typing import Callable, Dict, Any, Optional
 
 logger = logging.getLogger(__name__)
 
@@ -42,10 +42,13 @@ def create_engine(
 
 def run_inference(
     engine_id: str,
-    messages: list,
+    messages: Optional[list] = None,
     temperature: float = 1.0,
     top_p: float = 1.0,
     max_tokens: int = 100,
+    prompt: Optional[str] = None,
+    stop: Optional[list] = None,
+    on_text: Optional[Callable[[str], None]] = None,
 ) -> Dict[str, Any]:
     try:
         if engine_id not in _engines:
@@ -57,8 +60,11 @@ def run_inference(
 
         tokenizer = engine.get_tokenizer()
 
+        if prompt is not None:
+            # raw completion, no chat formatting
+            formatted_prompt = prompt
         # apply chat template if available
-        if hasattr(tokenizer, "chat_template") and tokenizer.chat_template:
+        elif hasattr(tokenizer, "chat_template") and tokenizer.chat_template:
             formatted_prompt = tokenizer.apply_chat_template(
                 messages, tokenize=False, add_generation_prompt=True
             )
@@ -80,7 +86,7 @@ def run_inference(
         if hasattr(tokenizer, "eos_token_id") and tokenizer.eos_token_id is not None:
             stop_token_ids.append(tokenizer.eos_token_id)
 
-        stop_strings = []
+        stop_strings = list(stop) if stop else []
         if hasattr(tokenizer, "eos_token") and tokenizer.eos_token:
             stop_strings.append(tokenizer.eos_token)
 
@@ -96,9 +102,20 @@ def run_inference(
         request_id = engine.add_request(formatted_prompt, sampling_params)
 
         outputs = []
+        # length of the text already passed to on_text
+        streamed = 0
         while engine.has_unfinished_requests():
             batch_outputs = engine.step()
             outputs.extend(batch_outputs)
+            if on_text is None:
+                continue
+            for request_output in batch_outputs:
+                if request_output.request_id != request_id:
+                    continue
+                text = request_output.outputs[0].text
+                if len(text) > streamed:
+                    on_text(text[streamed:])
+                    streamed = len(text)
 
         if outputs:
             # Use the last output as it contains the final result
@@ -113,6 +130,9 @@ def run_inference(
                 "request_id": request_id,
                 "generated_text": output.text,
                 "full_text": formatted_prompt + output.text,
+                "finish_reason": output.finish_reason,
+                "prompt_tokens": len(final_output.prompt_token_ids or []),
+                "completion_tokens": len(output.token_ids),
             }
         else:
             return {
@@ -150,6 +170,23 @@ def shutdown_engine(engine_id: str) -> Dict[str, Any]:
         return {"status": "error", "error": error_msg}
 
 
+def update_weights(engine_id: str, checkpoint_path: str) -> Dict[str, Any]:
+    try:
+        if engine_id not in _engines:
+            error_msg = f"Engine '{engine_id}' not found"
+            logger.error(error_msg)
+            return {"status": "error", "error": error_msg}
+
+        _engines[engine_id].update_weights(checkpoint_path)
+
+        return {"status": "success", "engine_id": engine_id}
+
+    except Exception as e:
+        error_msg = f"Failed to update weights for engine '{engine_id}': {e}"
+        logger.error(error_msg)
+        return {"status": "error", "error": error_msg}
+
+
 def get_engine_stats(engine_id: str) -> Dict[str, Any]:
     try:
         if engine_id not in _engines:
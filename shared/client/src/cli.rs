@@ -1,4 +1,4 @@
-use crate::{CheckpointConfig, WandBInfo};
+use crate::{CheckpointConfig, FindLrConfig, WandBInfo};
 
 use crate::UploadInfo;
 use anyhow::{Result, anyhow, bail};
@@ -6,9 +6,9 @@ use clap::Args;
 use psyche_data_provider::{GcsUploadInfo, HubUploadInfo};
 use psyche_eval::tasktype_from_name;
 use psyche_modeling::Devices;
-use psyche_network::{DiscoveryMode, RelayKind, SecretKey};
+use psyche_network::{DiscoveryMode, NetworkConfig, RelayKind, SecretKey};
 use psyche_tui::LogOutput;
-use std::{path::PathBuf, time::Duration};
+use std::{num::NonZeroU32, path::PathBuf, time::Duration};
 
 pub fn read_identity_secret_key(
     identity_secret_key_path: Option<&PathBuf>,
@@ -71,6 +71,50 @@ pub struct TrainArgs {
     #[clap(long, env, default_value = "n0")]
     pub iroh_discovery: DiscoveryMode,
 
+    /// How many gossip peers to stay directly connected to. Runs with hundreds of clients want more than the default of 8,
+    /// or the gossip network can partition.
+    #[clap(long, env)]
+    pub gossip_active_view_capacity: Option<usize>,
+
+    /// How often, in seconds, to swap gossip peers with a random neighbor. Defaults to 30.
+    #[clap(long, env)]
+    pub gossip_shuffle_interval_secs: Option<u64>,
+
+    /// How long, in milliseconds, to wait before pulling a gossip message we've only heard about from a peer that has it. Defaults to 200.
+    #[clap(long, env)]
+    pub gossip_graft_timeout_ms: Option<u64>,
+
+    /// Largest gossip message in bytes, bigger ones are dropped. Defaults to 4096.
+    /// Every client in a run should use the same value.
+    #[clap(long, env)]
+    pub gossip_max_message_size: Option<usize>,
+
+    /// Seconds a p2p connection may go without traffic before it's closed. Defaults to 120.
+    #[clap(long = "p2p-idle-timeout", env = "P2P_IDLE_TIMEOUT")]
+    pub p2p_idle_timeout_secs: Option<u64>,
+
+    /// Seconds between keep-alive pings on idle p2p connections. Defaults to 5.
+    /// Strict NATs that drop mappings quickly may need this lower.
+    #[clap(long = "p2p-keep-alive-interval", env = "P2P_KEEP_ALIVE_INTERVAL")]
+    pub p2p_keep_alive_interval_secs: Option<u64>,
+
+    /// Milliseconds between garbage collections of the in-memory blob store. Defaults to 10 seconds.
+    /// Large models with many parameter shards may want this longer to avoid stutter during training.
+    #[clap(long, env)]
+    pub blobs_gc_interval_millis: Option<u64>,
+
+    /// Warn when the in-memory blob store holds more than this many bytes. Defaults to 4 GiB.
+    #[clap(long, env)]
+    pub blob_store_warn_bytes: Option<u64>,
+
+    /// Most gossip broadcasts this client may send per second. Unlimited if unset, and can't be 0.
+    #[clap(long, env)]
+    pub max_broadcasts_per_second: Option<NonZeroU32>,
+
+    /// Most p2p blob downloads transferring at once, with DisTrO results served before model sharing. Defaults to 64.
+    #[clap(long, env)]
+    pub max_concurrent_downloads: Option<usize>,
+
     /// Sets clients logs interface
     /// tui: Enables a terminal-based graphical interface for monitoring analytics.
     /// console: standard logs
@@ -110,6 +154,15 @@ pub struct TrainArgs {
     #[clap(long, env)]
     pub metrics_local_port: Option<u16>,
 
+    /// If present, serve Prometheus-compatible metrics over HTTP at `/metrics` on this port.
+    #[clap(long, env)]
+    pub metrics_http_port: Option<u16>,
+
+    /// If present, push the same JSON metrics as `--metrics-local-port` over a WebSocket on this port
+    /// whenever the round state, bandwidth or peer connections change. Useful for live dashboards.
+    #[clap(long, env)]
+    pub metrics_ws_port: Option<u16>,
+
     /// A unique identifier for the training run. This ID allows the client to join a specific active run.
     #[clap(long, env, value_parser = parse_trim_quotes)]
     pub run_id: String,
@@ -120,19 +173,40 @@ pub struct TrainArgs {
     #[clap(long, default_value_t = 1, env)]
     pub tensor_parallelism: usize,
 
+    /// Sequences per micro batch. Each assigned batch is split into micro batches of this size,
+    /// accumulating gradients across them before the optimizer step.
     #[clap(long, env, default_value_t = 1)]
     pub micro_batch_size: usize,
 
+    /// Forward/backward passes to split each micro batch into, for models where a full micro batch
+    /// doesn't fit in GPU memory. Must divide --micro-batch-size.
+    #[clap(long, env, default_value_t = 1)]
+    pub gradient_accumulation_steps: usize,
+
     /// If provided, every shared gradient this client sees will be written to this directory.
     #[clap(long, env)]
     pub write_gradients_dir: Option<PathBuf>,
 
+    /// If provided, every network event this client sees is recorded to this file as NDJSON, for use with --replay-events.
+    #[clap(long, env, conflicts_with = "replay_events")]
+    pub record_events: Option<PathBuf>,
+
+    /// Instead of live gossip, feed the run the network events recorded with --record-events, in their recorded order.
+    #[clap(long, env)]
+    pub replay_events: Option<PathBuf>,
+
     #[clap(long, env)]
     pub eval_tasks: Option<String>,
 
     #[clap(long, default_value_t = 42, env)]
     pub eval_seed: u64,
 
+    /// Number of solved examples to prepend to every eval prompt. They're picked at random for each
+    /// step from a task's training split, or are the first of its curated examples.
+    /// Defaults to 5 for mmlu_pro and 0 (zero-shot) for every other task.
+    #[clap(long, env)]
+    pub eval_num_fewshot: Option<usize>,
+
     #[clap(long, env)]
     pub eval_task_max_docs: Option<usize>,
 
@@ -144,6 +218,11 @@ pub struct TrainArgs {
     #[clap(long, env)]
     pub checkpoint_dir: Option<PathBuf>,
 
+    /// Local checkpoint directory (as written to `--checkpoint-dir`) to load model parameters and optimizer state from instead of the run's checkpoint.
+    /// The checkpoint's step has to be the one right before the run's current step.
+    #[clap(long, env)]
+    pub resume_checkpoint: Option<PathBuf>,
+
     /// Path to the Hugging Face repository containing model data and configuration.
     #[clap(long, env)]
     pub hub_repo: Option<String>,
@@ -174,12 +253,47 @@ pub struct TrainArgs {
     #[clap(long, env)]
     pub write_log: Option<PathBuf>,
 
+    /// If provided, training lifecycle events (steps, checkpoints, evals, run state changes) are appended to this file as NDJSON.
+    #[clap(long, env)]
+    pub write_events_log: Option<PathBuf>,
+
     #[clap(long, env)]
     pub optim_stats_steps: Option<u32>,
 
     #[clap(long, default_value_t = false, env)]
     pub grad_accum_in_fp32: bool,
 
+    /// If present, clip gradients to this global norm before each optimizer step, overriding the run's optimizer config.
+    #[clap(long, env)]
+    pub gradient_clip_norm: Option<f64>,
+
+    /// If present, keep an exponential moving average of the model weights with this decay, and run evals on it.
+    #[clap(long, env)]
+    pub ema_decay: Option<f64>,
+
+    /// Recompute activations during the backward pass instead of keeping them all, trading compute for GPU memory.
+    #[clap(long, default_value_t = false, env)]
+    pub activation_checkpointing: bool,
+
+    /// Send this client's Distro sparse values as int8 with a per-tensor scale instead of the model's dtype.
+    /// Has no effect if the run uses 1-bit quantization. Clients that predate int8 results can't read them.
+    #[clap(long, default_value_t = false, env)]
+    pub distro_int8_quantization: bool,
+
+    /// Before training, sweep the learning rate over the first batches of the run's data and log each step's loss as JSON.
+    /// The sweep stops when warmup ends. The model's parameters and optimizer are reset afterwards, and nothing is submitted to the coordinator during the sweep.
+    #[clap(long, default_value_t = false, env)]
+    pub find_lr: bool,
+
+    #[clap(long, default_value_t = 1.0e-7, env)]
+    pub find_lr_min_lr: f64,
+
+    #[clap(long, default_value_t = 1.0, env)]
+    pub find_lr_max_lr: f64,
+
+    #[clap(long, default_value_t = 100, env)]
+    pub find_lr_steps: usize,
+
     #[clap(long, env)]
     pub dummy_training_delay_secs: Option<u64>,
 
@@ -205,6 +319,11 @@ pub struct TrainArgs {
     #[clap(long, default_value_t = 3, env)]
     pub keep_steps: u32,
 
+    /// Upload checkpoints one at a time from a dedicated task, keeping at most two waiting.
+    /// If uploads can't keep up, the oldest waiting checkpoint is skipped (it stays on disk).
+    #[clap(long, env)]
+    pub background_upload: bool,
+
     /// If provided, events will be written to a subdir in here, named after the node's ID.
     #[clap(long, env)]
     pub events_dir: Option<PathBuf>,
@@ -212,6 +331,10 @@ pub struct TrainArgs {
     /// Number of epoch event files to keep on disk. Older files are deleted during rotation.
     #[clap(long, env, default_value = "5")]
     pub keep_event_files: Option<usize>,
+
+    /// Validate the configuration (keys, checkpoint dirs, HF token, a CPU training step) and exit without joining the run.
+    #[clap(long, env)]
+    pub dry_run: bool,
 }
 
 impl TrainArgs {
@@ -240,6 +363,47 @@ impl TrainArgs {
         Ok(wandb_info)
     }
 
+    pub fn network_config(&self) -> NetworkConfig {
+        let mut config = NetworkConfig::default();
+        if let Some(capacity) = self.gossip_active_view_capacity {
+            config.gossip.membership.active_view_capacity = capacity;
+        }
+        if let Some(secs) = self.gossip_shuffle_interval_secs {
+            config.gossip.membership.shuffle_interval = Duration::from_secs(secs);
+        }
+        if let Some(ms) = self.gossip_graft_timeout_ms {
+            config.gossip.broadcast.graft_timeout_2 = Duration::from_millis(ms);
+        }
+        if let Some(size) = self.gossip_max_message_size {
+            config.gossip.max_message_size = size;
+        }
+        if let Some(secs) = self.p2p_idle_timeout_secs {
+            config.max_idle_timeout = Duration::from_secs(secs);
+        }
+        if let Some(secs) = self.p2p_keep_alive_interval_secs {
+            config.keep_alive_interval = Duration::from_secs(secs);
+        }
+        if let Some(ms) = self.blobs_gc_interval_millis {
+            config.gc_interval = Duration::from_millis(ms);
+        }
+        if let Some(bytes) = self.blob_store_warn_bytes {
+            config.blob_store_warn_bytes = bytes;
+        }
+        if let Some(max) = self.max_concurrent_downloads {
+            config.max_concurrent_downloads = max;
+        }
+        config.max_broadcasts_per_second = self.max_broadcasts_per_second;
+        config
+    }
+
+    pub fn find_lr_config(&self) -> Option<FindLrConfig> {
+        self.find_lr.then_some(FindLrConfig {
+            min_lr: self.find_lr_min_lr,
+            max_lr: self.find_lr_max_lr,
+            num_steps: self.find_lr_steps,
+        })
+    }
+
     pub fn checkpoint_config(&self) -> Result<Option<CheckpointConfig>> {
         let hub_read_token = std::env::var("HF_TOKEN").ok();
 
@@ -273,6 +437,7 @@ impl TrainArgs {
             upload_info,
             delete_old_steps: self.delete_old_steps,
             keep_steps: self.keep_steps,
+            background_upload: self.background_upload,
         }))
     }
 
@@ -312,7 +477,9 @@ impl TrainArgs {
 
     pub fn eval_tasks(&self) -> Result<Vec<psyche_eval::Task>> {
         let eval_tasks = match &self.eval_tasks {
-            Some(eval_tasks) => Self::eval_tasks_from_args(eval_tasks, self.eval_seed)?,
+            Some(eval_tasks) => {
+                Self::eval_tasks_from_args(eval_tasks, self.eval_seed, self.eval_num_fewshot)?
+            }
             None => Vec::new(),
         };
         Ok(eval_tasks)
@@ -321,14 +488,15 @@ impl TrainArgs {
     pub fn eval_tasks_from_args(
         eval_tasks: &str,
         eval_seed: u64,
+        num_fewshot: Option<usize>,
     ) -> Result<Vec<psyche_eval::Task>> {
         let result: Result<Vec<psyche_eval::Task>> = eval_tasks
             .split(",")
             .map(|eval_task| {
-                let fewshot = match eval_task {
+                let fewshot = num_fewshot.unwrap_or(match eval_task {
                     "mmlu_pro" => 5,
                     _ => 0,
-                };
+                });
                 tasktype_from_name(eval_task)
                     .map(|task_type| psyche_eval::Task::new(task_type, fewshot, eval_seed))
             })
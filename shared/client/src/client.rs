@@ -1,6 +1,10 @@
 use crate::{
     Broadcast, BroadcastType, ClientTUIState, Finished, NC, RunInitConfig, RunInitConfigAndIO,
     TrainingResult,
+    event_replay::{
+        MessageKey, NetworkEventRecorder, NetworkEventReplayer, RecordedNetworkEvent,
+        decode_payload,
+    },
     state::{ApplyMessageOutcome, DistroBroadcastAndPayload, FinishedBroadcast, RunManager},
 };
 use anyhow::anyhow;
@@ -12,12 +16,14 @@ use psyche_event_sourcing::event;
 use psyche_metrics::{ClientMetrics, ClientRoleInRound, PeerConnection};
 use psyche_network::{
     DownloadComplete, DownloadSchedulerHandle, DownloadType, EndpointId, ModelRequestType,
-    NetworkEvent, NetworkTUIState, PeerManagerHandle, RetryConfig, RetryQueueResult, SharableModel,
-    TransmittableDownload, allowlist, blob_ticket_param_request_task, raw_p2p_verify,
+    NetworkError, NetworkEvent, NetworkTUIState, PeerManagerHandle, RetryConfig, RetryQueueResult,
+    SharableModel, TransmittableDownload, allowlist, blob_ticket_param_request_task,
+    raw_p2p_verify,
 };
 use psyche_watcher::{Backend, BackendWatcher};
 use tokenizers::Tokenizer;
 
+use futures::{StreamExt, stream::FuturesUnordered};
 use iroh_blobs::api::Tag;
 use rand::{Rng, RngCore, seq::SliceRandom};
 use std::{
@@ -100,6 +106,17 @@ impl Client {
                 let mut current_downloaded_parameters = 0_u64;
                 let mut total_parameters = None;
 
+                let mut event_recorder = init_config
+                    .record_events
+                    .as_deref()
+                    .map(NetworkEventRecorder::create)
+                    .transpose()?;
+                let mut event_replayer = init_config
+                    .replay_events
+                    .as_deref()
+                    .map(NetworkEventReplayer::open)
+                    .transpose()?;
+
                 let mut run = RunManager::new(RunInitConfigAndIO {
                     init_config,
                     metrics: metrics.clone(),
@@ -244,7 +261,18 @@ impl Client {
 
                         res = p2p.poll_next() => {
                             if let Some(message) = res? {
+                                if let Some(recorder) = &mut event_recorder {
+                                    recorder.record_network_event(&message);
+                                }
                                 match message {
+                                    NetworkEvent::MessageReceived((from, _)) if event_replayer.is_some() => {
+                                        trace!("Ignoring live broadcast from {from} while replaying recorded events");
+                                    }
+                                    NetworkEvent::DownloadComplete(DownloadComplete {
+                                        data: TransmittableDownload::DistroResult(_), hash, ..
+                                    }) if event_replayer.is_some() => {
+                                        trace!("Ignoring live distro result download {hash} while replaying recorded events");
+                                    }
                                     NetworkEvent::MessageReceived((from, broadcast)) => {
                                         let _ = trace_span!("NetworkEvent::MessageReceived", from=%from).entered();
                                         metrics.record_broadcast_seen();
@@ -265,7 +293,11 @@ impl Client {
                                                         event!(p2p::GossipFinishedReceived);
                                                     }
                                                 }
+                                                let message_key = MessageKey::new(from, &broadcast);
                                                 let apply_result = run.apply_message(client.id, broadcast)?;
+                                                if let Some(recorder) = &mut event_recorder {
+                                                    recorder.record_outcome(message_key, apply_result);
+                                                }
                                                 match apply_result {
                                                     ApplyMessageOutcome::Ignored => {
                                                         metrics.record_apply_message_ignored(broadcast_kind);
@@ -440,7 +472,7 @@ impl Client {
                                 broadcast_merkle: merkle, warmup
                             })};
 
-                            p2p.broadcast(&training_result)?;
+                            broadcast_unless_rate_limited(&p2p, &[&training_result])?;
                             event!(p2p::GossipFinishedSent);
                             broadcasts.push((training_result.clone(), step));
 
@@ -448,43 +480,54 @@ impl Client {
                             run.apply_message(identity,  training_result)?;
                         }
 
-                        Some(DistroBroadcastAndPayload { step, batch_id, commitment_data_hash, proof, distro_result, original_distro_result }) = rx_distro_result.recv() => {
+                        Some(distro_result_and_payload) = rx_distro_result.recv() => {
+                            // results for several batches are often ready together,
+                            // so everything already queued goes out in a single gossip message
+                            let mut training_results = Vec::new();
+                            let mut next = Some(distro_result_and_payload);
+                            while let Some(DistroBroadcastAndPayload { step, batch_id, commitment_data_hash, proof, distro_result, original_distro_result }) = next.take() {
 
-                            let transmittable_distro_result = TransmittableDownload::DistroResult(distro_result.clone());
+                                let transmittable_distro_result = TransmittableDownload::DistroResult(distro_result.clone());
 
-                            let tag_name = format!("distro-result_{step}");
-                            let (ticket, size) = p2p.add_downloadable(transmittable_distro_result, Tag::from(tag_name)).await?;
+                                let tag_name = format!("distro-result_{step}");
+                                let (ticket, size) = p2p.add_downloadable(transmittable_distro_result, Tag::from(tag_name)).await?;
 
-                            let hash = ticket.hash();
-                            info!(
-                                client_id = %identity, step = step,
-                                "Broadcasting payload batch id {batch_id} hash 0x{} ({:.3} MB)",
-                                hex::encode(hash),
-                                (size as f64 ) / 1_000_000f64
-                            );
+                                let hash = ticket.hash();
+                                info!(
+                                    client_id = %identity, step = step,
+                                    "Broadcasting payload batch id {batch_id} hash 0x{} ({:.3} MB)",
+                                    hex::encode(hash),
+                                    (size as f64 ) / 1_000_000f64
+                                );
 
-                            let signature = p2p_secret_key.sign(&commitment_data_hash).to_bytes();
-                            let commitment = Commitment { data_hash: commitment_data_hash, signature};
+                                let signature = p2p_secret_key.sign(&commitment_data_hash).to_bytes();
+                                let commitment = Commitment { data_hash: commitment_data_hash, signature};
 
-                            let hash = ticket.hash();
-                            event!(p2p::BlobAddedToStore {
-                                blob: hash,
-                                model_parameter: format!("distro-result-batch-{batch_id}"),
-                            });
-                            let training_result = Broadcast { step, proof, nonce: rand::rng().random(), commitment, data: BroadcastType::TrainingResult(TrainingResult { batch_id, ticket })};
+                                let hash = ticket.hash();
+                                event!(p2p::BlobAddedToStore {
+                                    blob: hash,
+                                    model_parameter: format!("distro-result-batch-{batch_id}"),
+                                });
+                                let training_result = Broadcast { step, proof, nonce: rand::rng().random(), commitment, data: BroadcastType::TrainingResult(TrainingResult { batch_id, ticket })};
 
-                            p2p.broadcast(&training_result)?;
-                            broadcasts.push((training_result.clone(), step));
+                                training_results.push(training_result.clone());
+                                broadcasts.push((training_result.clone(), step));
 
-                            event!(p2p::GossipTrainingResultSent);
+                                // simulate us recving it & apply like anyone else's
+                                run.apply_message(identity, training_result)?;
 
-                            // simulate us recving it & apply like anyone else's
-                            run.apply_message(identity, training_result)?;
+                                // VERY IMPORTANT -- we pass the "original" distro result, which is unquantized
+                                // even if quantization is turned on (distro_result is quantized).
+                                // this is because distro needs the unquantized version for lookahead
+                                run.apply_distro_result(hash, distro_result, Some(original_distro_result));
+
+                                next = rx_distro_result.try_recv().ok();
+                            }
 
-                            // VERY IMPORTANT -- we pass the "original" distro result, which is unquantized
-                            // even if quantization is turned on (distro_result is quantized).
-                            // this is because distro needs the unquantized version for lookahead
-                            run.apply_distro_result(hash, distro_result, Some(original_distro_result));
+                            broadcast_unless_rate_limited(&p2p, &training_results.iter().collect::<Vec<_>>())?;
+                            for _ in &training_results {
+                                event!(p2p::GossipTrainingResultSent);
+                            }
                         }
 
                         _ = sharing_downloadable_interval.tick() => {
@@ -501,7 +544,7 @@ impl Client {
                                         BroadcastType::TrainingResult(training_result) => trace!(client_id = %identity, step = broadcast.step, nonce = broadcast.nonce, batch_id = %training_result.batch_id, "Rebroadcasting training result"),
                                         BroadcastType::Finished(finished) => trace!(client_id = %identity, step = broadcast.step, nonce = broadcast.nonce, warmup = finished.warmup, "Rebroadcasting finished"),
                                     }
-                                    p2p.broadcast(broadcast)?;
+                                    broadcast_unless_rate_limited(&p2p, &[broadcast])?;
                                 }
                             }
                         }
@@ -547,7 +590,8 @@ impl Client {
                             let other_possible_nodes = other_possible_nodes.into_iter().filter(|addr| *addr != self_endpoint_id).collect();
                             let kind = DownloadType::DistroResult(other_possible_nodes);
                             metrics.record_download_started(download_ticket.hash(), kind.kind());
-                            p2p.start_download(download_ticket, tag, kind);
+                            let priority = kind.default_priority();
+                            p2p.start_download(download_ticket, tag, kind, priority);
                         }
                         Some(opportunistic_data) = rx_witness.recv() => {
                             metrics.record_witness_send(opportunistic_data.kind());
@@ -579,21 +623,36 @@ impl Client {
                             let tx_params_download = tx_params_download.clone();
 
                             tokio::spawn(async move {
-                                for param_name in param_names {
-                                    if let Err(e) = download_scheduler.wait_for_capacity().await {
-                                        error!("Download scheduler shut down, aborting parameter requests: {e}");
-                                        break;
-                                    }
-
+                                // Each request takes a download scheduler slot before asking for its blob ticket, and the
+                                // slot is released once the download finishes, so at most max_concurrent_parameter_requests
+                                // are in flight. A new request starts as soon as any slot frees up.
+                                let mut in_flight = param_names.into_iter().map(|param_name| {
                                     let router = router.clone();
+                                    let peer_manager = peer_manager.clone();
+                                    let param_requests_cancel_token = param_requests_cancel_token.clone();
+                                    let download_scheduler = download_scheduler.clone();
+                                    async move {
+                                        download_scheduler.wait_for_capacity().await?;
+                                        event!(warmup::P2PParamInfoRequest { from: router.endpoint().id() });
+                                        let result = blob_ticket_param_request_task(
+                                            ModelRequestType::Parameter(param_name.clone()),
+                                            router,
+                                            peer_manager,
+                                            param_requests_cancel_token
+                                        ).await;
+                                        anyhow::Ok((param_name, result))
+                                    }
+                                }).collect::<FuturesUnordered<_>>();
 
-                                    event!(warmup::P2PParamInfoRequest { from: router.endpoint().id() });
-                                    match blob_ticket_param_request_task(
-                                        ModelRequestType::Parameter(param_name.clone()),
-                                        router,
-                                        peer_manager.clone(),
-                                        param_requests_cancel_token.clone()
-                                    ).await {
+                                while let Some(request) = in_flight.next().await {
+                                    let (param_name, result) = match request {
+                                        Ok(request) => request,
+                                        Err(e) => {
+                                            error!("Download scheduler shut down, aborting parameter requests: {e}");
+                                            break;
+                                        }
+                                    };
+                                    match result {
                                         Ok((blob_ticket, request_type)) => {
                                             // Send the download request
                                             if tx_params_download.send((blob_ticket, request_type)).is_err() {
@@ -642,13 +701,49 @@ impl Client {
                             let kind = DownloadType::ModelSharing(request_type.clone());
                             metrics.record_download_started(ticket.hash(), kind.kind());
                             if let ModelRequestType::Parameter(parameter_name) = request_type {
-                                p2p.start_download(ticket, Tag::from(format!("model-{parameter_name}")), kind);
+                                let priority = kind.default_priority();
+                                p2p.start_download(ticket, Tag::from(format!("model-{parameter_name}")), kind, priority);
                             }
                         }
                         Some(config_blob_ticket) = rx_config_download.recv() => {
                             let kind = DownloadType::ModelSharing(ModelRequestType::Config);
                             metrics.record_download_started(config_blob_ticket.hash(), kind.kind());
-                            p2p.start_download(config_blob_ticket, Tag::from("model-config"), kind);
+                            let priority = kind.default_priority();
+                            p2p.start_download(config_blob_ticket, Tag::from("model-config"), kind, priority);
+                        }
+                        Some(recorded) = async { event_replayer.as_mut().unwrap().next().await }, if event_replayer.is_some() => {
+                            match recorded {
+                                RecordedNetworkEvent::MessageReceived { from, payload } => {
+                                    let broadcast: Broadcast = match decode_payload(&payload) {
+                                        Ok(broadcast) => broadcast,
+                                        Err(err) => {
+                                            warn!("Skipping undecodable recorded broadcast from {from}: {err:#}");
+                                            continue;
+                                        }
+                                    };
+                                    if let Some(client) = watcher.get_client_for_p2p_public_key(from.as_bytes()) {
+                                        let message_key = MessageKey::new(from, &broadcast);
+                                        let apply_result = run.apply_message(client.id, broadcast)?;
+                                        if let Some(replayer) = &mut event_replayer {
+                                            replayer.check_outcome(message_key, apply_result);
+                                        }
+                                    } else {
+                                        trace!("Replayed broadcast from unknown client {from}");
+                                    }
+                                }
+                                RecordedNetworkEvent::DownloadComplete { hash, payload: Some(payload), .. } => {
+                                    match decode_payload(&payload) {
+                                        Ok(TransmittableDownload::DistroResult(distro_result)) => {
+                                            debug!("Replayed download: step {} batch id {}", distro_result.step, distro_result.batch_id);
+                                            run.apply_distro_result(hash, distro_result, None);
+                                        }
+                                        Ok(_) => {}
+                                        Err(err) => warn!("Skipping undecodable recorded download {hash}: {err:#}"),
+                                    }
+                                }
+                                // model sharing and download failures need live peers to mean anything
+                                other => trace!("Not replaying {other:?}"),
+                            }
                         }
                         _ = param_requests_cancel_token.cancelled() => bail!("Peers were unreachable for P2P parameter requests. Try joining again"),
                         _ = check_connection_interval.tick() => {
@@ -679,6 +774,10 @@ impl Client {
 
                 info!("Main client loop ended");
 
+                if let Some(recorder) = event_recorder {
+                    recorder.close().await;
+                }
+
                 let p2p_shutdown = p2p.shutdown();
 
                 if wait_for_checkpoint {
@@ -733,6 +832,22 @@ impl Client {
     }
 }
 
+/// Sends `broadcasts` together, see [`NC::broadcast_batch`].
+/// Our own broadcasts are kept and periodically rebroadcast, so ones refused by the broadcast rate
+/// limit will still go out later.
+fn broadcast_unless_rate_limited(p2p: &NC, broadcasts: &[&Broadcast]) -> Result<()> {
+    match p2p.broadcast_batch(broadcasts) {
+        Err(err) if matches!(err.downcast_ref(), Some(NetworkError::RateLimited)) => {
+            warn!(
+                steps = ?broadcasts.iter().map(|broadcast| broadcast.step).collect::<Vec<_>>(),
+                "Broadcast rate limited, it'll be rebroadcast later"
+            );
+            Ok(())
+        }
+        result => result,
+    }
+}
+
 fn ensure_gossip_connected(
     run_state: &Coordinator,
     p2p: &mut NC,
@@ -786,14 +901,22 @@ fn ensure_gossip_connected(
         .filter(|id| *id != &my_endpoint_id)
         .filter(|id| !gossip_neighbors.contains(*id))
         .collect::<Vec<_>>();
+    // gossip over a relay adds latency and relay load, so prefer peers we have a direct path to.
+    // shuffle first so peers with the same connection type are still picked at random.
     to_connect.shuffle(&mut rand::rng());
-    let to_connect = to_connect
+    let mut to_connect = to_connect
         .into_iter()
-        .take(num_peers_to_add)
-        .cloned()
+        .map(|id| (p2p.conn_type(id), *id))
         .collect::<Vec<_>>();
+    to_connect.sort_by_key(|(conn_type, _)| *conn_type);
+    to_connect.truncate(num_peers_to_add);
 
     if !to_connect.is_empty() {
+        debug!(
+            new_peers = ?to_connect,
+            "Picked new gossip peers by connection type"
+        );
+        let to_connect = to_connect.into_iter().map(|(_, id)| id).collect::<Vec<_>>();
         info!(num_new_peers = to_connect.len(), "Connecting to new peers");
         p2p.add_peers(to_connect);
     }
@@ -0,0 +1,200 @@
+use crate::{TrainArgs, read_identity_secret_key};
+
+use anyhow::{Result, anyhow, bail};
+use psyche_core::{
+    Barrier, BatchId, CancellableBarrier, ClosedInterval, ConstantLR, DistroExtensions,
+    LearningRateSchedule, OptimizerDefinition,
+};
+use psyche_modeling::{
+    AutoConfig, Batch, BatchData, BatchDataCPU, CausalLM, LlamaConfig, LlamaForCausalLM,
+    LocalTrainer, ParallelModels, PretrainedSource, Trainer, get_dummy_parameters,
+};
+use std::{path::Path, sync::Arc};
+use tch::{Device, Kind};
+use tokio_util::sync::CancellationToken;
+
+const HF_WHOAMI_URL: &str = "https://huggingface.co/api/whoami-v2";
+
+/// Outcome of each check done by `--dry-run`, in the order they ran.
+#[derive(Debug, Default)]
+pub struct DryRunReport {
+    checks: Vec<(String, Result<()>)>,
+}
+
+impl DryRunReport {
+    pub fn check(&mut self, name: impl Into<String>, result: Result<()>) {
+        self.checks.push((name.into(), result));
+    }
+
+    /// Prints a pass/fail line per check, failing if any check did.
+    pub fn finish(self) -> Result<()> {
+        let mut failed = 0;
+        println!("Dry run summary:");
+        for (name, result) in &self.checks {
+            match result {
+                Ok(()) => println!("  [PASS] {name}"),
+                Err(err) => {
+                    failed += 1;
+                    println!("  [FAIL] {name}: {err:#}");
+                }
+            }
+        }
+        if failed > 0 {
+            bail!("{failed} of {} dry run checks failed", self.checks.len());
+        }
+        println!("All {} checks passed.", self.checks.len());
+        Ok(())
+    }
+}
+
+/// Validates `args` without joining a run or allocating GPU memory.
+pub async fn dry_run(args: &TrainArgs) -> DryRunReport {
+    let mut report = DryRunReport::default();
+
+    report.check(
+        "identity secret key",
+        read_identity_secret_key(args.identity_secret_key_path.as_ref()).map(|_| ()),
+    );
+
+    match args.checkpoint_config() {
+        Ok(Some(checkpoint_config)) => {
+            report.check(
+                format!(
+                    "checkpoint dir {} is writable",
+                    checkpoint_config.checkpoint_dir.display()
+                ),
+                check_dir_writable(&checkpoint_config.checkpoint_dir).await,
+            );
+        }
+        Ok(None) => {}
+        Err(err) => report.check("checkpoint config", Err(err)),
+    }
+    if let Some(dir) = &args.write_gradients_dir {
+        report.check(
+            format!("gradients dir {} is writable", dir.display()),
+            check_dir_writable(dir).await,
+        );
+    }
+    if let Some(dir) = &args.resume_checkpoint {
+        report.check(
+            format!("resume checkpoint {} exists", dir.display()),
+            check_dir_exists(dir).await,
+        );
+    }
+
+    if let Ok(token) = std::env::var("HF_TOKEN") {
+        report.check("HF_TOKEN is valid", check_hf_token(&token).await);
+    }
+
+    report.check(
+        "CPU forward/backward pass",
+        tokio::task::spawn_blocking(train_tiny_model_on_cpu)
+            .await
+            .map_err(|_| anyhow!("training thread crashed"))
+            .and_then(|result| result),
+    );
+
+    report
+}
+
+async fn check_dir_exists(dir: &Path) -> Result<()> {
+    match tokio::fs::metadata(dir).await {
+        Ok(metadata) if metadata.is_dir() => Ok(()),
+        Ok(_) => bail!("not a directory"),
+        Err(err) => Err(err.into()),
+    }
+}
+
+async fn check_dir_writable(dir: &Path) -> Result<()> {
+    tokio::fs::create_dir_all(dir).await?;
+    let probe = dir.join(".psyche-dry-run");
+    tokio::fs::write(&probe, b"").await?;
+    tokio::fs::remove_file(&probe).await?;
+    Ok(())
+}
+
+async fn check_hf_token(token: &str) -> Result<()> {
+    let response = reqwest::Client::new()
+        .head(HF_WHOAMI_URL)
+        .bearer_auth(token)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        bail!("Hugging Face rejected the token ({})", response.status());
+    }
+    Ok(())
+}
+
+fn train_tiny_model_on_cpu() -> Result<()> {
+    let config = LlamaConfig {
+        hidden_size: 8,
+        intermediate_size: 16,
+        vocab_size: 16,
+        num_attention_heads: 2,
+        num_key_value_heads: Some(2),
+        max_position_embeddings: 16,
+        ..LlamaConfig::dummy()
+    };
+    let vocab_size = config.vocab_size as i32;
+    #[allow(clippy::arc_with_non_send_sync)]
+    let source = PretrainedSource::ConfigAndTensors(
+        AutoConfig::Llama(config),
+        Arc::new(get_dummy_parameters()),
+    );
+    let model = LlamaForCausalLM::from_pretrained(
+        &source.try_into()?,
+        Some(Kind::Float),
+        None,
+        Some(Device::Cpu),
+        None,
+        None,
+    )?;
+
+    let trainer: Trainer = LocalTrainer::new(
+        ParallelModels {
+            models: vec![Box::new(model) as Box<dyn CausalLM>],
+            barrier: Arc::new(CancellableBarrier::new(1)) as Arc<dyn Barrier>,
+            data_parallel: None,
+        },
+        LearningRateSchedule::Constant(ConstantLR::new(1.0e-4, 0, 0.0)),
+        OptimizerDefinition::AdamW {
+            betas: [0.9, 0.95],
+            weight_decay: 0.0,
+            eps: 1.0e-8,
+            clip_grad_norm: Some(1.0),
+        },
+        DistroExtensions::default(),
+        1,
+        1,
+        None,
+        false,
+        None,
+        None,
+        None,
+        false,
+    )
+    .into();
+
+    let output = trainer.train(
+        0,
+        Batch {
+            id: BatchId(ClosedInterval::new(0, 0)),
+            data: BatchData::CPU(vec![BatchDataCPU {
+                input_ids: (0..8).map(|i| i % vocab_size).collect(),
+                labels: None,
+                position_ids: None,
+                sequence_lengths: None,
+            }]),
+        },
+        None,
+        false,
+        Vec::new(),
+        None,
+        CancellationToken::new(),
+    )?;
+    if !output.loss.is_finite() {
+        bail!("loss is {}", output.loss);
+    }
+    output.trainer.optimize(0, None, None)?;
+    Ok(())
+}
@@ -0,0 +1,318 @@
+//! Recording the network events a client sees, and replaying them in the same
+//! order in place of live gossip, to reproduce distributed training bugs.
+
+use crate::{Broadcast, state::ApplyMessageOutcome};
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use psyche_network::{Hash, NetworkEvent, Networkable, PublicKey, TransmittableDownload};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+use tokio::{
+    sync::mpsc,
+    task::JoinHandle,
+    time::{Instant, sleep_until},
+};
+use tracing::{info, warn};
+
+/// Identifies a broadcast, so the outcome of applying it in a replay is compared with the
+/// outcome recorded for the same message, whatever order the messages were applied in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MessageKey {
+    pub from: PublicKey,
+    pub step: u32,
+    pub data_hash: [u8; 32],
+}
+
+impl MessageKey {
+    pub fn new(from: PublicKey, broadcast: &Broadcast) -> Self {
+        Self {
+            from,
+            step: broadcast.step,
+            data_hash: broadcast.commitment.data_hash,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "event")]
+pub enum RecordedNetworkEvent {
+    MessageReceived {
+        from: PublicKey,
+        payload: String,
+    },
+    DownloadComplete {
+        from: PublicKey,
+        hash: Hash,
+        /// Only kept for distro results, model parameters are too big to be worth recording
+        payload: Option<String>,
+    },
+    DownloadFailed {
+        hash: Hash,
+        error: String,
+    },
+    ParameterRequest {
+        parameter_name: String,
+    },
+    ModelConfigRequest,
+    /// The result of applying the [`RecordedNetworkEvent::MessageReceived`] with this key to the run
+    MessageApplied {
+        message: MessageKey,
+        outcome: ApplyMessageOutcome,
+    },
+}
+
+impl RecordedNetworkEvent {
+    fn from_network_event(event: &NetworkEvent<Broadcast, TransmittableDownload>) -> Self {
+        match event {
+            NetworkEvent::MessageReceived((from, broadcast)) => Self::MessageReceived {
+                from: *from,
+                payload: encode_payload(broadcast),
+            },
+            NetworkEvent::DownloadComplete(download) => Self::DownloadComplete {
+                from: download.from,
+                hash: download.hash,
+                payload: match &download.data {
+                    TransmittableDownload::DistroResult(_) => Some(encode_payload(&download.data)),
+                    _ => None,
+                },
+            },
+            NetworkEvent::DownloadFailed(failed) => Self::DownloadFailed {
+                hash: failed.blob_ticket.hash(),
+                error: format!("{:#}", failed.error),
+            },
+            NetworkEvent::ParameterRequest(parameter_name, _) => Self::ParameterRequest {
+                parameter_name: parameter_name.clone(),
+            },
+            NetworkEvent::ModelConfigRequest(_) => Self::ModelConfigRequest,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordedLine {
+    timestamp: String,
+    /// Time since recording started, used to pace the replay
+    elapsed_ms: u64,
+    #[serde(flatten)]
+    event: RecordedNetworkEvent,
+}
+
+fn encode_payload<T: Networkable>(value: &T) -> String {
+    hex::encode(value.to_bytes())
+}
+
+pub fn decode_payload<T: Networkable>(payload: &str) -> Result<T> {
+    T::from_bytes(&hex::decode(payload)?)
+}
+
+/// Writes every network event a client sees to a file as newline-delimited JSON.
+/// The file is written from a blocking task, so recording never blocks the client's loop.
+pub struct NetworkEventRecorder {
+    start: Instant,
+    tx: mpsc::UnboundedSender<RecordedLine>,
+    writer: JoinHandle<()>,
+}
+
+impl NetworkEventRecorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let (tx, mut rx) = mpsc::unbounded_channel::<RecordedLine>();
+        let writer = tokio::task::spawn_blocking(move || {
+            while let Some(line) = rx.blocking_recv() {
+                let mut result = write_line(&mut file, &line);
+                // flush once whatever else is already queued has been written
+                while let Ok(line) = rx.try_recv() {
+                    result = result.and(write_line(&mut file, &line));
+                }
+                if let Err(err) = result.and(file.flush()) {
+                    warn!("Failed to record network event: {err}");
+                }
+            }
+        });
+        Ok(Self {
+            start: Instant::now(),
+            tx,
+            writer,
+        })
+    }
+
+    pub fn record_network_event(&mut self, event: &NetworkEvent<Broadcast, TransmittableDownload>) {
+        self.record(RecordedNetworkEvent::from_network_event(event));
+    }
+
+    pub fn record_outcome(&mut self, message: MessageKey, outcome: ApplyMessageOutcome) {
+        self.record(RecordedNetworkEvent::MessageApplied { message, outcome });
+    }
+
+    fn record(&mut self, event: RecordedNetworkEvent) {
+        let line = RecordedLine {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            elapsed_ms: self.start.elapsed().as_millis() as u64,
+            event,
+        };
+        if self.tx.send(line).is_err() {
+            warn!("Failed to record network event: the writer has stopped");
+        }
+    }
+
+    /// Waits for every recorded event to be written out
+    pub async fn close(self) {
+        drop(self.tx);
+        if let Err(err) = self.writer.await {
+            warn!("Network event writer failed: {err}");
+        }
+    }
+}
+
+fn write_line(file: &mut impl Write, line: &RecordedLine) -> io::Result<()> {
+    serde_json::to_writer(&mut *file, line)?;
+    file.write_all(b"\n")
+}
+
+/// Hands back the events of a recording with the same timing they were recorded
+/// with, and checks that applying them gives the same outcomes as the original run.
+pub struct NetworkEventReplayer {
+    start: Instant,
+    events: VecDeque<(Duration, RecordedNetworkEvent)>,
+    expected_outcomes: HashMap<MessageKey, VecDeque<ApplyMessageOutcome>>,
+    matched: usize,
+    mismatched: usize,
+    finished: bool,
+}
+
+impl NetworkEventReplayer {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("failed to open event recording {}", path.display()))?;
+        let mut events = VecDeque::new();
+        let mut expected_outcomes: HashMap<_, VecDeque<_>> = HashMap::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let recorded: RecordedLine = serde_json::from_str(&line)
+                .with_context(|| format!("invalid event on line {}", index + 1))?;
+            match recorded.event {
+                RecordedNetworkEvent::MessageApplied { message, outcome } => expected_outcomes
+                    .entry(message)
+                    .or_default()
+                    .push_back(outcome),
+                event => events.push_back((Duration::from_millis(recorded.elapsed_ms), event)),
+            }
+        }
+        info!(
+            "Replaying {} network events from {}",
+            events.len(),
+            path.display()
+        );
+        Ok(Self {
+            start: Instant::now(),
+            events,
+            expected_outcomes,
+            matched: 0,
+            mismatched: 0,
+            finished: false,
+        })
+    }
+
+    /// Waits until the next event is due, or returns `None` once the recording is exhausted
+    pub async fn next(&mut self) -> Option<RecordedNetworkEvent> {
+        let Some((elapsed, _)) = self.events.front() else {
+            if !self.finished {
+                self.finished = true;
+                info!(
+                    "Finished replaying network events: {} outcomes matched the recording, {} didn't",
+                    self.matched, self.mismatched
+                );
+            }
+            return None;
+        };
+        sleep_until(self.start + *elapsed).await;
+        self.events.pop_front().map(|(_, event)| event)
+    }
+
+    /// Compares the outcome of applying a replayed message with the one recorded for it.
+    /// A message received more than once is compared with its recorded outcomes in order.
+    pub fn check_outcome(&mut self, message: MessageKey, outcome: ApplyMessageOutcome) {
+        let expected = self
+            .expected_outcomes
+            .get_mut(&message)
+            .and_then(|outcomes| outcomes.pop_front());
+        match expected {
+            Some(expected) if expected == outcome => self.matched += 1,
+            expected => {
+                self.mismatched += 1;
+                warn!(
+                    "Replay diverged from the recording: expected outcome {expected:?} for step {} message from {}, got {outcome:?}",
+                    message.step, message.from
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use psyche_network::SecretKey;
+
+    fn message(step: u32, data: u8) -> MessageKey {
+        MessageKey {
+            from: SecretKey::generate(&mut rand::rng()).public(),
+            step,
+            data_hash: [data; 32],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        let first = message(1, 1);
+        let second = message(1, 2);
+
+        let mut recorder = NetworkEventRecorder::create(&path).unwrap();
+        for message in [first, second] {
+            recorder.record(RecordedNetworkEvent::MessageReceived {
+                from: message.from,
+                payload: hex::encode(message.data_hash),
+            });
+        }
+        recorder.record_outcome(second, ApplyMessageOutcome::Invalid);
+        recorder.record_outcome(first, ApplyMessageOutcome::Applied);
+        // the same message gossiped to us twice
+        recorder.record_outcome(first, ApplyMessageOutcome::Ignored);
+        recorder.close().await;
+
+        let mut replayer = NetworkEventReplayer::open(&path).unwrap();
+        let mut replayed = Vec::new();
+        while let Some(event) = replayer.next().await {
+            replayed.push(event);
+        }
+        assert_eq!(replayed.len(), 2);
+        for (event, message) in replayed.iter().zip([first, second]) {
+            let RecordedNetworkEvent::MessageReceived { from, payload } = event else {
+                panic!("expected a received message, got {event:?}");
+            };
+            assert_eq!(*from, message.from);
+            assert_eq!(*payload, hex::encode(message.data_hash));
+        }
+
+        // outcomes are matched to their message, not to the order they were recorded in
+        replayer.check_outcome(first, ApplyMessageOutcome::Applied);
+        replayer.check_outcome(second, ApplyMessageOutcome::Invalid);
+        replayer.check_outcome(first, ApplyMessageOutcome::Ignored);
+        assert_eq!((replayer.matched, replayer.mismatched), (3, 0));
+
+        replayer.check_outcome(second, ApplyMessageOutcome::Applied);
+        replayer.check_outcome(message(2, 3), ApplyMessageOutcome::Applied);
+        assert_eq!((replayer.matched, replayer.mismatched), (3, 2));
+    }
+}
@@ -1,7 +1,8 @@
-use psyche_coordinator::{Coordinator, get_batch_ids_for_node};
+use psyche_coordinator::{Coordinator, get_batch_ids_for_node, get_data_index_for_step};
 use psyche_core::{BatchId, NodeIdentity};
 use psyche_data_provider::{DataProvider, TokenizedDataProvider};
 use psyche_event_sourcing::event;
+use psyche_metrics::ClientMetrics;
 use psyche_modeling::{Batch, BatchData, BatchDataCPU};
 use std::{
     collections::{BTreeMap, HashSet},
@@ -25,14 +26,20 @@ pub struct DataFetcher {
     data_provider: Arc<Mutex<DataProvider>>,
     active_fetch_task: Option<(BatchStep, JoinHandle<()>)>,
     buffer_size: usize,
+    metrics: Arc<ClientMetrics>,
 }
 
 impl DataFetcher {
-    pub fn new(data_provider: DataProvider, buffer_size: usize) -> Self {
+    pub fn new(
+        data_provider: DataProvider,
+        buffer_size: usize,
+        metrics: Arc<ClientMetrics>,
+    ) -> Self {
         Self {
             data_provider: Arc::new(Mutex::new(data_provider)),
             active_fetch_task: None,
             buffer_size,
+            metrics,
         }
     }
 
@@ -67,8 +74,20 @@ impl DataFetcher {
             tokio::spawn({
                 trace!("New fetch task for step {step} has been spawned");
                 let data_provider = self.data_provider.clone(); // only one of these tasks will acquire the lock at once. once one dies, the lock is released for sure.
+                let metrics = self.metrics.clone();
+                let state = *state;
 
                 async move {
+                    // move the data provider's curriculum (if any) to this step before fetching
+                    // its data, so every client draws the same samples for it.
+                    if data_provider
+                        .lock()
+                        .await
+                        .set_step(step, |step| get_data_index_for_step(&state, step))
+                    {
+                        debug!("Data provider entered a new curriculum stage at step {step}");
+                    }
+
                     loop {
                         let batch_id = {
                             match assigned_batch_ids.pop() {
@@ -83,9 +102,13 @@ impl DataFetcher {
                         let mut retry_count = 0;
                         let batch = loop {
                             event!(train::BatchDataDownloadStart);
-                            match data_provider.lock().await.get_samples(batch_id).await {
+                            let mut provider = data_provider.lock().await;
+                            match provider.get_samples(batch_id).await {
                                 Ok(batch) => {
                                     event!(train::BatchDataDownloadComplete{result: Ok(())});
+                                    if let Some(ratio) = provider.cache_hit_ratio() {
+                                        metrics.record_data_cache_hit_ratio(ratio);
+                                    }
                                     break batch;
                                 }
                                 Err(err) if retry_count < MAX_RETRIES => {
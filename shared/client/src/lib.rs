@@ -1,5 +1,7 @@
 mod cli;
 mod client;
+mod dry_run;
+mod event_replay;
 mod fetch_data;
 mod protocol;
 mod state;
@@ -7,10 +9,11 @@ mod tui;
 
 pub use cli::{TrainArgs, prepare_environment, print_identity_keys, read_identity_secret_key};
 pub use client::Client;
+pub use dry_run::{DryRunReport, dry_run};
 pub use protocol::{Broadcast, BroadcastType, Finished, NC, TrainingResult};
 pub use state::{
-    CheckpointConfig, GcsUploadInfo, HubUploadInfo, InitRunError, RoundState, RunInitConfig,
-    RunInitConfigAndIO, UploadInfo,
+    CheckpointConfig, FindLrConfig, GcsUploadInfo, HubUploadInfo, InitRunError, RoundState,
+    RunInitConfig, RunInitConfigAndIO, TrainingEvent, TrainingEventLogger, UploadInfo,
 };
 pub use tui::{ClientTUI, ClientTUIState};
 
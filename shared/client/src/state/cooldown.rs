@@ -8,25 +8,29 @@ use psyche_event_sourcing::event;
 #[cfg(feature = "python")]
 use psyche_modeling::CausalLM;
 use psyche_modeling::{
-    SaveSafetensorsError, Trainer, TrainerThreadCommunicationError, save_tensors_into_safetensors,
+    EMA_PREFIX, SaveSafetensorsError, Trainer, TrainerThreadCommunicationError,
+    save_tensors_into_safetensors,
 };
 use std::{
     cmp::Reverse,
-    collections::{BinaryHeap, HashMap},
+    collections::{BinaryHeap, HashMap, VecDeque},
     path::PathBuf,
     sync::Arc,
 };
 use tch::Tensor;
 use thiserror::Error;
 use tokio::{
-    sync::{Mutex, mpsc},
+    sync::{Mutex, Notify, mpsc},
     task::JoinHandle,
 };
-use tracing::{Instrument, info, info_span, warn};
+use tokio_util::task::AbortOnDropHandle;
+use tracing::{Instrument, error, info, info_span, warn};
 
 use super::{
     CheckpointConfig,
     evals::{ModelTaskRunner, RunningEvals},
+    training_events::{TrainingEvent, TrainingEventLogger},
+    types::{CHECKPOINT_METADATA_FILENAME, CheckpointMetadata, OPTIMIZER_STATE_DIRNAME},
 };
 
 #[derive(Error, Debug)]
@@ -48,6 +52,7 @@ pub struct CooldownStepMetadata {
     checkpoint_extra_files: Vec<PathBuf>,
 
     model_task_runner: ModelTaskRunner,
+    training_events: Option<TrainingEventLogger>,
     // use a heap here as a best-effort attempt to ensure we get rid of the lowest step number dir even if we spawn multiple tasks
     // which may not finish writing their dirs in order. We note that even if we were to take the more complicated
     // route of actually enumerating the checkpoint_dir there would still be a race condition, unless we took a lockfile
@@ -55,6 +60,7 @@ pub struct CooldownStepMetadata {
     // we don't really expect there to be contention on this lock or real race conditions in practice though
     // as by the time one task spawns after a training round the previous write/upload task(s) should (hopefully) be long done
     delete_queue: Arc<Mutex<BinaryHeap<Reverse<u32>>>>,
+    background_uploader: Option<BackgroundUploader>,
 }
 
 impl CooldownStepMetadata {
@@ -64,16 +70,161 @@ impl CooldownStepMetadata {
         checkpoint_info: Option<CheckpointConfig>,
         checkpoint_extra_files: Vec<PathBuf>,
         model_task_runner: ModelTaskRunner,
+        training_events: Option<TrainingEventLogger>,
     ) -> Self {
+        let delete_queue = Arc::new(Mutex::new(BinaryHeap::new()));
+        let background_uploader = checkpoint_info
+            .as_ref()
+            .filter(|config| config.background_upload && config.upload_info.is_some())
+            .map(|config| {
+                BackgroundUploader::spawn(config, delete_queue.clone(), tx_checkpoint.clone())
+            });
         Self {
             tx_checkpoint,
             tx_model,
             checkpoint_info,
             checkpoint_extra_files,
             model_task_runner,
-            delete_queue: Arc::new(Mutex::new(BinaryHeap::new())),
+            training_events,
+            delete_queue,
+            background_uploader,
         }
     }
+
+    /// Whether the background uploader has a checkpoint uploading or waiting to be uploaded.
+    pub fn has_background_uploads(&self) -> bool {
+        self.background_uploader
+            .as_ref()
+            .is_some_and(BackgroundUploader::is_busy)
+    }
+}
+
+/// How many checkpoints may wait for the background uploader. When a new one arrives while
+/// it's full, the oldest waiting one is skipped.
+const BACKGROUND_UPLOAD_QUEUE_DEPTH: usize = 2;
+
+struct PendingUpload {
+    upload_info: UploadInfo,
+    manifest_metadata: GcsManifestMetadata,
+    local: Vec<PathBuf>,
+    step: u32,
+}
+
+#[derive(Default)]
+struct UploadQueue {
+    waiting: VecDeque<PendingUpload>,
+    uploading: bool,
+}
+
+impl UploadQueue {
+    /// Queues `upload`, returning the waiting upload it displaced if the queue was full.
+    fn push(&mut self, upload: PendingUpload) -> Option<PendingUpload> {
+        let skipped = match self.waiting.len() >= BACKGROUND_UPLOAD_QUEUE_DEPTH {
+            true => self.waiting.pop_front(),
+            false => None,
+        };
+        self.waiting.push_back(upload);
+        skipped
+    }
+
+    /// Takes the next upload to start, if any, marking the queue as uploading while it runs.
+    fn start_next(&mut self) -> Option<PendingUpload> {
+        let next = self.waiting.pop_front();
+        self.uploading = next.is_some();
+        next
+    }
+}
+
+/// Uploads checkpoints one at a time from a dedicated task, so slow uploads can't pile up
+/// behind each other. Old step dirs are only cleaned up once their upload is done or skipped.
+/// The task is aborted once the last clone is dropped.
+#[derive(Clone)]
+struct BackgroundUploader {
+    queue: Arc<std::sync::Mutex<UploadQueue>>,
+    notify: Arc<Notify>,
+    // the worker holds on to a tx_checkpoint, which would otherwise keep that channel open forever
+    _worker: Arc<AbortOnDropHandle<()>>,
+}
+
+impl BackgroundUploader {
+    fn spawn(
+        config: &CheckpointConfig,
+        delete_queue: Arc<Mutex<BinaryHeap<Reverse<u32>>>>,
+        tx_checkpoint: mpsc::UnboundedSender<model::Checkpoint>,
+    ) -> Self {
+        let queue: Arc<std::sync::Mutex<UploadQueue>> = Default::default();
+        let notify: Arc<Notify> = Default::default();
+        let CheckpointConfig {
+            checkpoint_dir,
+            delete_old_steps,
+            keep_steps,
+            ..
+        } = config.clone();
+        let worker = tokio::task::spawn({
+            let queue = queue.clone();
+            let notify = notify.clone();
+            async move {
+                loop {
+                    let next = queue.lock().unwrap().start_next();
+                    let Some(PendingUpload {
+                        upload_info,
+                        manifest_metadata,
+                        local,
+                        step,
+                    }) = next
+                    else {
+                        notify.notified().await;
+                        continue;
+                    };
+                    let run_id = manifest_metadata.run_id.clone();
+                    if let Err(err) = upload_checkpoint(
+                        upload_info,
+                        manifest_metadata,
+                        local,
+                        step as u64,
+                        tx_checkpoint.clone(),
+                    )
+                    .await
+                    {
+                        error!("Background upload of step {step} failed: {err}");
+                    }
+                    cleanup_dirs(
+                        delete_queue.clone(),
+                        keep_steps,
+                        run_id,
+                        delete_old_steps,
+                        step,
+                        checkpoint_dir.clone(),
+                    )
+                    .await;
+                }
+            }
+            .instrument(info_span!("background_upload"))
+        });
+        Self {
+            queue,
+            notify,
+            _worker: Arc::new(AbortOnDropHandle::new(worker)),
+        }
+    }
+
+    /// Queues `upload`, returning the waiting upload it displaced if the queue was full.
+    fn push(&self, upload: PendingUpload) -> Option<PendingUpload> {
+        let skipped = self.queue.lock().unwrap().push(upload);
+        if let Some(skipped) = &skipped {
+            warn!(
+                "Checkpoint uploads can't keep up, skipping upload of step {} (it's still saved locally)",
+                skipped.step
+            );
+        }
+        self.notify.notify_one();
+        skipped
+    }
+
+    fn is_busy(&self) -> bool {
+        let queue = self.queue.lock().unwrap();
+        queue.uploading || !queue.waiting.is_empty()
+    }
 }
 
 #[derive(Error, Debug)]
@@ -93,6 +244,9 @@ pub enum CheckpointError {
     #[error("Writing extra file to disk failed: {0}")]
     WriteExtraFile(#[from] tokio::io::Error),
 
+    #[error("Serializing checkpoint metadata failed: {0}")]
+    SerializeMetadata(#[from] serde_json::Error),
+
     #[error("Couldn't upload model to huggingface or GCS: {0}")]
     UploadError(#[from] UploadError),
 
@@ -136,6 +290,7 @@ impl CooldownStepMetadata {
         };
 
         let step = state.progress.step - 1;
+        let eval_step = state.progress.step;
         let run_id = String::from(&state.run_id);
         let epoch = state.progress.epoch as u32;
         let checkpoint_extra_files = self.checkpoint_extra_files.clone();
@@ -144,14 +299,18 @@ impl CooldownStepMetadata {
         let tx_model = self.tx_model.clone();
         let model_task_runner = self.model_task_runner.clone();
         let delete_queue = self.delete_queue.clone();
+        let training_events = self.training_events.clone();
+        let background_uploader = self.background_uploader.clone();
+        let save_optimizer_state = checkpoint_info.is_some();
 
         let checkpointing_and_evals: CheckpointAndEvalsHandle = tokio::task::spawn(
             async move {
                 info!("Extracting full model...");
                 event!(cooldown::ModelSerializationStarted);
-                let (variables, trainer) =
-                    tokio::task::spawn_blocking::<_, Result<_, CheckpointError>>(|| {
+                let (variables, optimizer_state, trainer, mut trainers) =
+                    tokio::task::spawn_blocking::<_, Result<_, CheckpointError>>(move || {
                         let mut trainer = trainer;
+                        let mut trainers = trainers;
                         trainer.truncate_bf16()?;
                         let variables: HashMap<String, Tensor> = trainer
                             .extract()?
@@ -159,7 +318,12 @@ impl CooldownStepMetadata {
                             .map(|(name, tensor)| (name, tensor.to_kind(tch::Kind::BFloat16)))
                             .collect();
                         info!("Model extracted; {} parameters", variables.len());
-                        Ok((variables, trainer))
+                        // only saved locally, for resuming
+                        let optimizer_state = match save_optimizer_state {
+                            true => extract_shared_optimizer_state(&mut trainer, &mut trainers)?,
+                            false => None,
+                        };
+                        Ok((variables, optimizer_state, trainer, trainers))
                     })
                     .await
                     .map_err(|_| {
@@ -174,6 +338,12 @@ impl CooldownStepMetadata {
                     error_string: None
                 });
 
+                // averaged weights are ours alone; peers and the main checkpoint get the model's own
+                let (ema_variables, variables): (HashMap<String, Tensor>, HashMap<String, Tensor>) =
+                    variables
+                        .into_iter()
+                        .partition(|(name, _)| name.starts_with(EMA_PREFIX));
+
                 let variables_clone: HashMap<String, Tensor> = variables
                     .iter()
                     .map(|(name, var)| (name.clone(), var.shallow_clone()))
@@ -197,13 +367,14 @@ impl CooldownStepMetadata {
                 };
 
                 trainers.push(trainer);
-                let evals = model_task_runner.start(trainers);
+                let evals = model_task_runner.start(trainers, eval_step);
 
                 let Some(CheckpointConfig {
                     upload_info,
                     checkpoint_dir,
                     delete_old_steps,
                     keep_steps,
+                    ..
                 }) = checkpoint_info
                 else {
                     return Ok((evals, None));
@@ -211,33 +382,77 @@ impl CooldownStepMetadata {
 
                 let upload_handle = tokio::task::spawn(async move {
                     let path = checkpoint_dir.join(format!("{run_id}-step{step}"));
-                    let local =
-                        save_checkpoint_locally(path, variables, checkpoint_extra_files).await?;
+                    if !ema_variables.is_empty() {
+                        save_ema_locally(path.join("ema"), ema_variables).await?;
+                    }
+                    match optimizer_state {
+                        Some(state) if !state.is_empty() => {
+                            save_optimizer_state_locally(path.join(OPTIMIZER_STATE_DIRNAME), state)
+                                .await?
+                        }
+                        Some(_) => {}
+                        None => warn!(
+                            "This optimizer's state can't be saved, so the checkpoint of step {step} can't be resumed from"
+                        ),
+                    }
+                    let metadata = CheckpointMetadata {
+                        run_id: run_id.clone(),
+                        epoch,
+                        step,
+                    };
+                    let local = save_checkpoint_locally(
+                        path.clone(),
+                        variables,
+                        checkpoint_extra_files,
+                        metadata,
+                    )
+                    .await?;
+                    if let Some(training_events) = &training_events {
+                        training_events.log(TrainingEvent::CheckpointSaved { step, path });
+                    }
 
+                    // the step that's done with its dir, if any; a queued upload still needs its files
+                    let mut finished_step = Some(step);
                     if let Some(upload_info) = upload_info {
                         let manifest_metadata = GcsManifestMetadata {
                             epoch,
                             run_id: run_id.clone(),
                         };
-                        upload_checkpoint(
-                            upload_info,
-                            manifest_metadata,
-                            local.clone(),
-                            step as u64,
-                            tx_checkpoint,
-                        )
-                        .await?;
+                        match &background_uploader {
+                            Some(uploader) => {
+                                finished_step = uploader
+                                    .push(PendingUpload {
+                                        upload_info,
+                                        manifest_metadata,
+                                        local: local.clone(),
+                                        step,
+                                    })
+                                    .map(|skipped| skipped.step);
+                            }
+                            None => {
+                                upload_checkpoint(
+                                    upload_info,
+                                    manifest_metadata,
+                                    local.clone(),
+                                    step as u64,
+                                    tx_checkpoint,
+                                )
+                                .await?;
+                            }
+                        }
                     }
 
-                    cleanup_dirs(
-                        delete_queue,
-                        keep_steps,
-                        run_id,
-                        delete_old_steps,
-                        step,
-                        checkpoint_dir,
-                    )
-                    .await;
+                    if let Some(finished_step) = finished_step {
+                        cleanup_dirs(
+                            delete_queue,
+                            keep_steps,
+                            run_id,
+                            delete_old_steps,
+                            finished_step,
+                            checkpoint_dir,
+                        )
+                        .await;
+                    }
 
                     Ok(())
                 });
@@ -257,6 +472,7 @@ async fn save_checkpoint_locally(
     path: PathBuf,
     variables: HashMap<String, Tensor>,
     checkpoint_extra_files: Vec<PathBuf>,
+    metadata: CheckpointMetadata,
 ) -> Result<Vec<PathBuf>, CheckpointError> {
     info!("Saving to {}", path.display());
     event!(cooldown::CheckpointWriteStarted);
@@ -287,6 +503,20 @@ async fn save_checkpoint_locally(
         local.push(to);
     }
 
+    // only needed for resuming locally, so it's not part of the uploaded files
+    tokio::fs::write(
+        path.join(CHECKPOINT_METADATA_FILENAME),
+        serde_json::to_vec_pretty(&metadata)?,
+    )
+    .await
+    .map_err(|e| {
+        event!(cooldown::CheckpointWriteFinished {
+            success: false,
+            error_string: Some(e.to_string())
+        });
+        CheckpointError::WriteExtraFile(e)
+    })?;
+
     event!(cooldown::CheckpointWriteFinished {
         success: true,
         error_string: None
@@ -294,6 +524,63 @@ async fn save_checkpoint_locally(
     Ok(local)
 }
 
+async fn save_ema_locally(
+    path: PathBuf,
+    ema_variables: HashMap<String, Tensor>,
+) -> Result<(), CheckpointError> {
+    info!("Saving EMA weights to {}", path.display());
+    let variables = ema_variables
+        .into_iter()
+        .map(|(name, tensor)| match name.strip_prefix(EMA_PREFIX) {
+            Some(stripped) => (stripped.to_owned(), tensor),
+            None => (name, tensor),
+        })
+        .collect();
+    tokio::task::spawn_blocking(move || save_tensors_into_safetensors(variables, path))
+        .await
+        .map_err(|_| CheckpointError::WriteThreadCrashed)??;
+    Ok(())
+}
+
+/// Resuming restores one saved optimizer state into every trainer. That's only right because they
+/// all apply the same all-reduced updates, so their states have to match exactly. `None` if the
+/// state can't be extracted, or if the trainers disagree.
+fn extract_shared_optimizer_state(
+    trainer: &mut Trainer,
+    others: &mut [Trainer],
+) -> Result<Option<HashMap<String, Tensor>>, CheckpointError> {
+    let Some(state) = trainer.extract_optimizer_state()? else {
+        return Ok(None);
+    };
+    for other in others {
+        let Some(other_state) = other.extract_optimizer_state()? else {
+            return Ok(None);
+        };
+        let same = other_state.len() == state.len()
+            && other_state.iter().all(|(name, tensor)| {
+                state
+                    .get(name)
+                    .is_some_and(|expected| expected.equal(tensor))
+            });
+        if !same {
+            warn!("Data parallel trainers ended up with different optimizer states");
+            return Ok(None);
+        }
+    }
+    Ok(Some(state))
+}
+
+async fn save_optimizer_state_locally(
+    path: PathBuf,
+    state: HashMap<String, Tensor>,
+) -> Result<(), CheckpointError> {
+    info!("Saving optimizer state to {}", path.display());
+    tokio::task::spawn_blocking(move || save_tensors_into_safetensors(state, path))
+        .await
+        .map_err(|_| CheckpointError::WriteThreadCrashed)??;
+    Ok(())
+}
+
 async fn upload_checkpoint(
     upload_info: UploadInfo,
     manifest_metadata: GcsManifestMetadata,
@@ -358,3 +645,69 @@ impl CooldownStep {
         Ok((running_evals, upload_handle))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use psyche_data_provider::HubUploadInfo;
+
+    fn pending(step: u32) -> PendingUpload {
+        PendingUpload {
+            upload_info: UploadInfo::Hub(HubUploadInfo {
+                hub_repo: "repo".to_string(),
+                hub_token: "token".to_string(),
+            }),
+            manifest_metadata: GcsManifestMetadata {
+                epoch: 0,
+                run_id: "run".to_string(),
+            },
+            local: vec![],
+            step,
+        }
+    }
+
+    #[test]
+    fn full_upload_queue_skips_the_oldest_waiting_upload() {
+        let mut queue = UploadQueue::default();
+        assert!(queue.push(pending(1)).is_none());
+
+        // step 1 is uploading, so it can't be skipped
+        assert_eq!(queue.start_next().map(|upload| upload.step), Some(1));
+        assert!(queue.push(pending(2)).is_none());
+        assert!(queue.push(pending(3)).is_none());
+        assert_eq!(queue.push(pending(4)).map(|upload| upload.step), Some(2));
+        assert_eq!(queue.push(pending(5)).map(|upload| upload.step), Some(3));
+
+        assert_eq!(queue.start_next().map(|upload| upload.step), Some(4));
+        assert_eq!(queue.start_next().map(|upload| upload.step), Some(5));
+        assert!(queue.start_next().is_none());
+        assert!(!queue.uploading);
+    }
+
+    #[tokio::test]
+    async fn cleanup_deletes_the_lowest_steps_whatever_order_they_finish_in() {
+        let checkpoint_dir = tempfile::tempdir().unwrap();
+        for step in 1..=4 {
+            std::fs::create_dir(checkpoint_dir.path().join(format!("run-step{step}"))).unwrap();
+        }
+        let delete_queue = Arc::new(Mutex::new(BinaryHeap::new()));
+        for step in [3, 1, 4, 2] {
+            cleanup_dirs(
+                delete_queue.clone(),
+                2,
+                "run".to_string(),
+                true,
+                step,
+                checkpoint_dir.path().to_path_buf(),
+            )
+            .await;
+        }
+
+        let mut remaining: Vec<_> = std::fs::read_dir(checkpoint_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        remaining.sort();
+        assert_eq!(remaining, ["run-step3", "run-step4"]);
+    }
+}
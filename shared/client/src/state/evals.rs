@@ -62,6 +62,7 @@ impl EvalTask {
         cancel: CancellationToken,
         skip_and_step_by: Option<(usize, usize)>,
         limit: Option<usize>,
+        eval_step: u32,
     ) {
         let result = self.task.run(
             EvalTaskOptions {
@@ -71,6 +72,9 @@ impl EvalTask {
                 cancel: Some(cancel),
                 limit,
                 shared_progress_bar: None,
+                metric_computer: None,
+                num_shots: self.task.num_fewshot,
+                eval_step,
             },
             false,
         );
@@ -223,14 +227,15 @@ impl ModelTaskRunner {
         }
     }
 
-    pub fn start_if_not_running(&self, trainers: MaybeRunningEvals) -> RunningEvals {
+    /// `step` is the training step the evals are started at, see [`EvalTaskOptions::eval_step`]
+    pub fn start_if_not_running(&self, trainers: MaybeRunningEvals, step: u32) -> RunningEvals {
         match trainers {
-            MaybeRunningEvals::NotRunning(trainers) => self.start(trainers),
+            MaybeRunningEvals::NotRunning(trainers) => self.start(trainers, step),
             MaybeRunningEvals::Running(evals) => evals,
         }
     }
 
-    pub fn start(&self, trainers: Vec<Trainer>) -> RunningEvals {
+    pub fn start(&self, trainers: Vec<Trainer>, step: u32) -> RunningEvals {
         let cancel = CancellationToken::new();
         trace!("Starting evals!");
 
@@ -281,11 +286,13 @@ impl ModelTaskRunner {
                                             } else {
                                                 Some(10)
                                             };
+                                            trainer.set_forward_on_ema_weights(true);
                                             eval_task.run(
                                                 &mut trainer,
                                                 cancel.clone(),
                                                 Some((next_index, data_parallelism)),
                                                 limit,
+                                                step,
                                             );
                                             trace!("Done eval task {}", eval_task.task.name());
                                         }
@@ -303,6 +310,7 @@ impl ModelTaskRunner {
                                                 *prompt.selected_prompt.read().unwrap()
                                             );
 
+                                            trainer.set_forward_on_ema_weights(false);
                                             prompt.run(&mut trainer, cancel.clone());
                                             *prompt.is_running.lock().unwrap() = false;
                                         }
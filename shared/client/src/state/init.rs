@@ -1,27 +1,35 @@
 use crate::{WandBInfo, fetch_data::DataFetcher};
+use anyhow::Context;
 use psyche_coordinator::{
-    Coordinator, HealthChecks,
+    Coordinator, HealthChecks, MixedPrecisionDtype, RunState,
     model::{self, HttpLLMTrainingDataLocation, LLMTrainingDataLocation},
 };
 use psyche_core::{
-    Barrier, CancellableBarrier, IntegrationTestLogMarker, NodeIdentity, Shuffle, TokenSize,
+    Barrier, BatchId, CancellableBarrier, ClosedInterval, IntegrationTestLogMarker, NodeIdentity,
+    Shuffle, TokenSize,
 };
 use psyche_data_provider::{
-    DataProvider, DataProviderTcpClient, DownloadError, DummyDataProvider,
-    PreprocessedDataProvider, Split, WeightedDataProvider, download_dataset_repo_async,
-    download_model_from_gcs_async, download_model_repo_async,
+    DataProvider, DataProviderTcpClient, DownloadConfig, DownloadError, DummyDataProvider,
+    PreprocessedDataProvider, Split, TokenizedDataProvider, WeightedDataProvider,
+    download_dataset_repo_async, download_model_from_gcs_async, download_model_repo_async,
     http::{FileURLs, HttpDataProvider},
 };
 use psyche_event_sourcing::event;
 use psyche_metrics::ClientMetrics;
 use psyche_modeling::{
-    AttentionImplementation, AutoConfig, AutoTokenizerError, CausalLM, CommunicatorId,
-    DataParallel, DeepseekForCausalLM, Devices, DummyModel, LlamaConfig, LlamaForCausalLM,
-    LocalTrainer, ModelLoadError, ParallelModels, PretrainedSource, Trainer, auto_tokenizer,
+    AmpDtype, AttentionImplementation, AutoConfig, AutoTokenizerError, Batch, BatchData,
+    BatchDataCPU, CausalLM, CommunicatorId, DataParallel, DeepseekForCausalLM, Devices, DummyModel,
+    LlamaConfig, LlamaForCausalLM, LocalTrainer, ModelLoadError, ParallelModels, PretrainedSource,
+    Trainer, auto_tokenizer,
 };
 use psyche_network::{BlobTicket, SecretKey};
 use psyche_watcher::OpportunisticData;
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 use tch::{Kind, Tensor};
 use thiserror::Error;
 use tokenizers::{ModelWrapper, Tokenizer, models::wordlevel::WordLevel};
@@ -30,12 +38,22 @@ use tokio::{
     sync::{mpsc::UnboundedSender, oneshot},
     task::{JoinError, JoinHandle},
 };
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use super::{
-    CheckpointConfig, FinishedBroadcast, cooldown::CooldownStepMetadata, evals::ModelTaskRunner,
-    stats::StatsLogger, steps::StepStateMachine, train::TrainingStepMetadata,
-    types::DistroBroadcastAndPayload, warmup::WarmupStepMetadata, witness::WitnessStepMetadata,
+    CheckpointConfig, FindLrConfig, FinishedBroadcast,
+    cooldown::CooldownStepMetadata,
+    evals::ModelTaskRunner,
+    stats::StatsLogger,
+    steps::StepStateMachine,
+    train::TrainingStepMetadata,
+    training_events::TrainingEventLogger,
+    types::{
+        CHECKPOINT_METADATA_FILENAME, CheckpointMetadata, DistroBroadcastAndPayload,
+        OPTIMIZER_STATE_DIRNAME,
+    },
+    warmup::WarmupStepMetadata,
+    witness::WitnessStepMetadata,
 };
 use iroh_blobs::api::Tag;
 
@@ -54,8 +72,15 @@ pub struct RunInitConfig {
     pub data_parallelism: usize,
     pub tensor_parallelism: usize,
     pub micro_batch_size: usize,
+    /// Forward/backward passes each micro batch is split into, accumulating gradients across them
+    pub gradient_accumulation_steps: usize,
     pub optim_stats_every_n_steps: Option<u32>,
     pub grad_accum_in_fp32: bool,
+    pub gradient_clip_norm: Option<f64>,
+    pub ema_decay: Option<f64>,
+    pub activation_checkpointing: bool,
+    pub distro_int8_quantization: bool,
+    pub find_lr: Option<FindLrConfig>,
 
     // evaluation
     pub eval_task_max_docs: Option<usize>,
@@ -64,12 +89,17 @@ pub struct RunInitConfig {
 
     // logging
     pub wandb_info: Option<WandBInfo>,
+    pub write_events_log: Option<PathBuf>,
 
     // debugging
     pub write_gradients_dir: Option<PathBuf>,
+    pub record_events: Option<PathBuf>,
+    pub replay_events: Option<PathBuf>,
 
     // checkpointing
     pub checkpoint_config: Option<CheckpointConfig>,
+    // load parameters from this local checkpoint instead of the run's checkpoint
+    pub resume_checkpoint: Option<PathBuf>,
 
     // configurable dummy training time (in seconds) for this client - relevant just for testing
     pub dummy_training_delay_secs: Option<u64>,
@@ -107,6 +137,29 @@ pub enum InitRunError {
     #[error("Couldn't initialize data provider: {0}")]
     DataProviderConnect(anyhow::Error),
 
+    #[error("failed to fetch data for the learning rate sweep: {0}")]
+    FindLrData(anyhow::Error),
+
+    #[error("learning rate sweep failed: {0}")]
+    FindLr(anyhow::Error),
+
+    #[error("learning rate sweep thread crashed")]
+    FindLrThreadCrashed(JoinError),
+
+    #[error(
+        "resume checkpoint continues at step {checkpoint_step}, but the run is at step {run_step}"
+    )]
+    ResumeStepMismatch { checkpoint_step: u32, run_step: u32 },
+
+    #[error("failed to restore optimizer state from resume checkpoint: {0}")]
+    ResumeOptimizerState(anyhow::Error),
+
+    #[error("optimizer state restore thread crashed")]
+    ResumeOptimizerStateThreadCrashed(JoinError),
+
+    #[error("failed to open training events log: {0}")]
+    TrainingEventsLog(io::Error),
+
     #[error("wandb setup thread crashed")]
     WandbThreadCrashed(JoinError),
 
@@ -122,6 +175,17 @@ pub enum InitRunError {
     #[error("Unsupported architecture: {0}")]
     UnsupportedArchitecture(String),
 
+    #[error("This run trains with mixed precision, which isn't supported for {0} models")]
+    MixedPrecisionUnsupported(String),
+
+    #[error(
+        "micro batch size {micro_batch_size} can't be split into {gradient_accumulation_steps} gradient accumulation steps"
+    )]
+    GradientAccumulationSteps {
+        micro_batch_size: usize,
+        gradient_accumulation_steps: usize,
+    },
+
     #[cfg(feature = "python")]
     #[error("Python distributed error: {0}")]
     PythonDistributedError(#[from] psyche_modeling::PythonDistributedCausalLMError),
@@ -190,6 +254,15 @@ impl RunInitConfigAndIO {
 
         tch::manual_seed(1337);
 
+        if init_config.gradient_accumulation_steps == 0
+            || init_config.micro_batch_size % init_config.gradient_accumulation_steps != 0
+        {
+            return Err(InitRunError::GradientAccumulationSteps {
+                micro_batch_size: init_config.micro_batch_size,
+                gradient_accumulation_steps: init_config.gradient_accumulation_steps,
+            });
+        }
+
         // Check device availability early
         if !init_config.device.is_probably_available() {
             return Err(InitRunError::ModelLoad(
@@ -199,6 +272,53 @@ impl RunInitConfigAndIO {
 
         let model::Model::LLM(llm) = state.model;
 
+        let distro_extensions = state.config.distro_extensions;
+        let amp_dtype = match state.config.mixed_precision {
+            MixedPrecisionDtype::Disabled => None,
+            MixedPrecisionDtype::BF16 => Some(AmpDtype::BF16),
+            MixedPrecisionDtype::FP16 => Some(AmpDtype::FP16),
+        };
+        if amp_dtype.is_some()
+            && matches!(
+                llm.architecture,
+                model::LLMArchitecture::HfAuto | model::LLMArchitecture::Torchtitan
+            )
+        {
+            return Err(InitRunError::MixedPrecisionUnsupported(
+                llm.architecture.to_string(),
+            ));
+        }
+        // with mixed precision the loaded weights are the FP32 masters
+        let model_kind = match amp_dtype {
+            Some(_) => Kind::Float,
+            None => Kind::BFloat16,
+        };
+
+        let resume_checkpoint = match &init_config.resume_checkpoint {
+            Some(dir) => {
+                let metadata: CheckpointMetadata = serde_json::from_slice(
+                    &tokio::fs::read(dir.join(CHECKPOINT_METADATA_FILENAME)).await?,
+                )?;
+                // checkpoints are written during cooldown, after their step has been trained
+                let next_step = metadata.step + 1;
+                info!(
+                    "Resuming from checkpoint {} saved at step {}, next step lr {}",
+                    dir.display(),
+                    metadata.step,
+                    Trainer::get_lr(&llm.lr_schedule, next_step, None)
+                );
+                // the coordinator owns the run's progress, so the checkpoint has to match it
+                if state.progress.step != next_step {
+                    return Err(InitRunError::ResumeStepMismatch {
+                        checkpoint_step: next_step,
+                        run_step: state.progress.step,
+                    });
+                }
+                Some(dir.clone())
+            }
+            None => None,
+        };
+
         let hub_read_token = init_config.hub_read_token.clone();
         let hub_max_concurrent_downloads = init_config.hub_max_concurrent_downloads;
         let data_future = async {
@@ -247,6 +367,7 @@ impl RunInitConfigAndIO {
                             hub_read_token,
                             Some(hub_max_concurrent_downloads),
                             false,
+                            DownloadConfig::default(),
                         )
                         .await?
                         .first()
@@ -318,10 +439,40 @@ impl RunInitConfigAndIO {
                 | model::Checkpoint::P2P(_)
                 | model::Checkpoint::P2PGcs(_)
                 | model::Checkpoint::Gcs(_) => {
-                    let checkpoint = llm.checkpoint;
+                    // a resume checkpoint takes precedence over the run's
+                    let checkpoint = (llm.checkpoint, resume_checkpoint.clone());
                     tokio::spawn(async move {
                         let (source, tokenizer, checkpoint_extra_files) = match checkpoint {
-                            model::Checkpoint::Hub(hub_repo) => {
+                            (_, Some(dir)) => {
+                                info!("Loading model from resume checkpoint {}", dir.display());
+                                let mut repo_files = Vec::new();
+                                let mut read_dir = tokio::fs::read_dir(dir).await?;
+                                while let Some(dir_entry) = read_dir.next_entry().await? {
+                                    // skip the ema/ and optimizer/ dirs saved alongside the model
+                                    if dir_entry.file_type().await?.is_file() {
+                                        repo_files.push(dir_entry.path())
+                                    }
+                                }
+                                let checkpoint_extra_files = repo_files
+                                    .iter()
+                                    .filter(|file| {
+                                        file.ends_with("config.json")
+                                            || file.ends_with("tokenizer.json")
+                                            || file.ends_with("tokenizer_config.json")
+                                            || file.ends_with("special_tokens_map.json")
+                                            || file.ends_with("generation_config.json")
+                                            || file.ends_with(".py")
+                                    })
+                                    .cloned()
+                                    .collect();
+                                let tokenizer = Arc::new(auto_tokenizer(&repo_files)?);
+                                (
+                                    PretrainedSource::<AutoConfig>::RepoFiles(repo_files),
+                                    tokenizer,
+                                    checkpoint_extra_files,
+                                )
+                            }
+                            (model::Checkpoint::Hub(hub_repo), None) => {
                                 let repo_id: String = (&hub_repo.repo_id).into();
                                 let potential_local_path = PathBuf::from(repo_id.clone());
                                 let revision = hub_repo.revision.map(|bytes| (&bytes).into());
@@ -386,7 +537,7 @@ impl RunInitConfigAndIO {
                                     checkpoint_extra_files,
                                 )
                             }
-                            model::Checkpoint::P2P(_) | model::Checkpoint::P2PGcs(_) => {
+                            (model::Checkpoint::P2P(_) | model::Checkpoint::P2PGcs(_), None) => {
                                 let (tx_model_config_response, rx_model_config_response) =
                                     oneshot::channel();
                                 info!("Checkpoint is p2p, requesting model config over network");
@@ -452,7 +603,7 @@ impl RunInitConfigAndIO {
                                     vec![],
                                 )
                             }
-                            model::Checkpoint::Gcs(gcs_repo) => {
+                            (model::Checkpoint::Gcs(gcs_repo), None) => {
                                 let bucket: String = (&gcs_repo.bucket).into();
                                 let prefix: Option<String> = gcs_repo.prefix.map(|p| (&p).into());
 
@@ -628,7 +779,7 @@ impl RunInitConfigAndIO {
                                                 model::LLMArchitecture::HfLlama => {
                                                     LlamaForCausalLM::from_pretrained(
                                                         &source.try_into()?,
-                                                        Some(Kind::BFloat16),
+                                                        Some(model_kind),
                                                         attn_implementation,
                                                         Some(device),
                                                         tensor_parallelism_world,
@@ -639,7 +790,7 @@ impl RunInitConfigAndIO {
                                                 model::LLMArchitecture::HfDeepseek => {
                                                     DeepseekForCausalLM::from_pretrained(
                                                         &source.try_into()?,
-                                                        Some(Kind::BFloat16),
+                                                        Some(model_kind),
                                                         attn_implementation,
                                                         Some(device),
                                                         tensor_parallelism_world,
@@ -754,8 +905,24 @@ impl RunInitConfigAndIO {
         } = models.map_err(InitRunError::ModelLoadingThreadCrashed)??;
 
         // TODO add data fetching for verifying, too..
-        let data_provider = data.map_err(InitRunError::DataProviderConnect)?;
-        let data_fetcher = DataFetcher::new(data_provider, init_config.data_parallelism * 2);
+        let mut data_provider = data.map_err(InitRunError::DataProviderConnect)?;
+        let find_lr_batches = match &init_config.find_lr {
+            Some(find_lr) => Some(
+                find_lr_batches(
+                    &mut data_provider,
+                    find_lr.num_steps,
+                    init_config.micro_batch_size,
+                )
+                .await
+                .map_err(InitRunError::FindLrData)?,
+            ),
+            None => None,
+        };
+        let data_fetcher = DataFetcher::new(
+            data_provider,
+            init_config.data_parallelism * 2,
+            metrics.clone(),
+        );
 
         let trainers: Vec<Trainer> = match models {
             RawLoadedModelType::ParallelNativeModels(models) => {
@@ -824,9 +991,15 @@ impl RunInitConfigAndIO {
                             },
                             llm.lr_schedule,
                             llm.optimizer,
+                            distro_extensions,
                             init_config.micro_batch_size,
+                            init_config.gradient_accumulation_steps,
                             init_config.optim_stats_every_n_steps,
                             init_config.grad_accum_in_fp32,
+                            amp_dtype,
+                            init_config.gradient_clip_norm,
+                            init_config.ema_decay,
+                            init_config.activation_checkpointing,
                         )
                         .into()
                     })
@@ -843,37 +1016,77 @@ impl RunInitConfigAndIO {
                         },
                         llm.lr_schedule,
                         llm.optimizer,
+                        distro_extensions,
                         init_config.micro_batch_size,
+                        init_config.gradient_accumulation_steps,
                         init_config.optim_stats_every_n_steps,
                         init_config.grad_accum_in_fp32,
+                        None,
+                        init_config.gradient_clip_norm,
+                        init_config.ema_decay,
+                        init_config.activation_checkpointing,
                     )
                     .into(),
                 ]
             }
             #[cfg(feature = "python")]
             RawLoadedModelType::PythonDistributed(model) => {
+                if init_config.ema_decay.is_some() {
+                    warn!(
+                        "EMA weights aren't supported with Python distributed training, ignoring"
+                    );
+                }
                 vec![
                     psyche_modeling::PythonDistributedTrainer::new(
                         model,
                         llm.lr_schedule,
                         llm.optimizer,
+                        distro_extensions,
                         init_config.micro_batch_size,
+                        init_config.gradient_accumulation_steps,
                         init_config.optim_stats_every_n_steps,
                         init_config.grad_accum_in_fp32,
+                        init_config.gradient_clip_norm,
                     )?
                     .into(),
                 ]
             }
         };
 
+        let trainers = match (init_config.find_lr, find_lr_batches) {
+            (Some(find_lr), Some(batches)) => {
+                // the sweep has to be done before warmup ends, or we'd miss the first round
+                let warmup_end = match state.run_state {
+                    RunState::Warmup => {
+                        Some(state.run_state_start_unix_timestamp + state.config.warmup_time)
+                    }
+                    _ => None,
+                };
+                run_find_lr(trainers, find_lr, batches, warmup_end).await?
+            }
+            _ => trainers,
+        };
+
+        let trainers = match resume_checkpoint {
+            Some(dir) => load_resume_optimizer_state(trainers, dir).await?,
+            None => trainers,
+        };
+
         let wandb_run = wandb_run.map_err(InitRunError::WandbThreadCrashed)??;
 
+        let training_events = init_config
+            .write_events_log
+            .map(|path| TrainingEventLogger::new(&path, String::from(&state.run_id)))
+            .transpose()
+            .map_err(InitRunError::TrainingEventsLog)?;
+
         let stats_logger = StatsLogger::new(
             tokenizer,
             model_task_runner.clone(),
             llm.lr_schedule,
             wandb_run,
             metrics,
+            training_events.clone(),
         );
 
         let warmup = WarmupStepMetadata {
@@ -884,6 +1097,7 @@ impl RunInitConfigAndIO {
             data_fetcher,
             identity: init_config.identity,
             write_gradients_dir: init_config.write_gradients_dir,
+            distro_int8_quantization: init_config.distro_int8_quantization,
             tx_health_check,
             tx_distro_result,
 
@@ -902,6 +1116,7 @@ impl RunInitConfigAndIO {
             init_config.checkpoint_config,
             checkpoint_extra_files,
             model_task_runner,
+            training_events,
         );
 
         Ok(StepStateMachine::new(
@@ -919,3 +1134,116 @@ impl RunInitConfigAndIO {
         ))
     }
 }
+
+/// The first `num_batches` batches of `micro_batch_size` sequences from the start of the dataset.
+async fn find_lr_batches(
+    data_provider: &mut DataProvider,
+    num_batches: usize,
+    micro_batch_size: usize,
+) -> anyhow::Result<Vec<Batch>> {
+    let mut batches = Vec::with_capacity(num_batches);
+    for index in 0..num_batches as u64 {
+        let start = index * micro_batch_size as u64;
+        let id = BatchId(ClosedInterval::new(
+            start,
+            start + micro_batch_size as u64 - 1,
+        ));
+        let samples = data_provider.get_samples(id).await?;
+        batches.push(Batch {
+            id,
+            data: BatchData::CPU(
+                samples
+                    .into_iter()
+                    .map(|sample| BatchDataCPU {
+                        input_ids: sample.input_ids,
+                        labels: sample.labels,
+                        position_ids: sample.position_ids,
+                        sequence_lengths: sample.sequence_lengths,
+                    })
+                    .collect(),
+            ),
+        });
+    }
+    Ok(batches)
+}
+
+/// Restores the optimizer state saved alongside a local checkpoint into every trainer.
+/// Checkpoints only hold it if every trainer's state matched when it was saved.
+async fn load_resume_optimizer_state(
+    mut trainers: Vec<Trainer>,
+    dir: PathBuf,
+) -> Result<Vec<Trainer>, InitRunError> {
+    tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<Trainer>> {
+        let state_dir = dir.join(OPTIMIZER_STATE_DIRNAME);
+        let mut state = HashMap::new();
+        for entry in std::fs::read_dir(&state_dir)
+            .with_context(|| format!("no optimizer state in {}", state_dir.display()))?
+        {
+            let path = entry?.path();
+            if path.extension().is_some_and(|x| x == "safetensors") {
+                state.extend(Tensor::read_safetensors(&path)?);
+            }
+        }
+        info!("Restoring optimizer state from {}", state_dir.display());
+        for trainer in &mut trainers {
+            let state = state
+                .iter()
+                .map(|(name, tensor)| (name.clone(), tensor.shallow_clone()))
+                .collect();
+            trainer.load_optimizer_state(state)?;
+        }
+        Ok(trainers)
+    })
+    .await
+    .map_err(InitRunError::ResumeOptimizerStateThreadCrashed)?
+    .map_err(InitRunError::ResumeOptimizerState)
+}
+
+/// Runs the learning rate sweep on the first trainer and logs its results, handing the trainers back afterwards.
+/// The sweep stops at `warmup_end` (a unix timestamp), and is skipped if there's no warmup left.
+async fn run_find_lr(
+    mut trainers: Vec<Trainer>,
+    find_lr: FindLrConfig,
+    batches: Vec<Batch>,
+    warmup_end: Option<u64>,
+) -> Result<Vec<Trainer>, InitRunError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let Some(time_left) = warmup_end.and_then(|end| end.checked_sub(now)) else {
+        warn!("Warmup is already over, skipping the learning rate sweep");
+        return Ok(trainers);
+    };
+    let deadline = Instant::now() + Duration::from_secs(time_left);
+    tokio::task::spawn_blocking(move || {
+        match trainers.first_mut() {
+            Some(Trainer::Local(trainer)) => {
+                info!(
+                    "Sweeping learning rate from {} to {} over {} steps, for at most {time_left}s",
+                    find_lr.min_lr, find_lr.max_lr, find_lr.num_steps
+                );
+                let results = trainer
+                    .find_lr(
+                        batches,
+                        find_lr.min_lr,
+                        find_lr.max_lr,
+                        find_lr.num_steps,
+                        Some(deadline),
+                    )
+                    .map_err(InitRunError::FindLr)?;
+                let results = serde_json::Value::Array(
+                    results
+                        .into_iter()
+                        .map(|(lr, loss)| serde_json::json!({ "lr": lr, "loss": loss }))
+                        .collect(),
+                );
+                info!(results = %results, "Learning rate sweep finished");
+            }
+            _ => warn!("Learning rate sweep is only supported with native trainers, skipping it"),
+        }
+        Ok(trainers)
+    })
+    .await
+    .map_err(InitRunError::FindLrThreadCrashed)?
+}
@@ -10,6 +10,7 @@ mod prompt_texts;
 mod round_state;
 mod stats;
 mod train;
+mod training_events;
 mod warmup;
 mod witness;
 
@@ -17,4 +18,7 @@ pub use init::{InitRunError, RunInitConfig, RunInitConfigAndIO};
 pub use psyche_data_provider::{GcsUploadInfo, HubUploadInfo};
 pub use round_state::RoundState;
 pub use steps::{ApplyMessageOutcome, RunManager};
-pub use types::{CheckpointConfig, DistroBroadcastAndPayload, FinishedBroadcast, UploadInfo};
+pub use training_events::{TrainingEvent, TrainingEventLogger};
+pub use types::{
+    CheckpointConfig, DistroBroadcastAndPayload, FindLrConfig, FinishedBroadcast, UploadInfo,
+};
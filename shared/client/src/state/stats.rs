@@ -12,12 +12,16 @@ use wandb::{DataValue, LogData};
 
 use crate::state::evals::{EnumModelTask, PROMPT_TASK_NAME};
 
-use super::evals::ModelTaskRunner;
+use super::{
+    evals::ModelTaskRunner,
+    training_events::{TrainingEvent, TrainingEventLogger},
+};
 
 pub struct StatsLogger {
     tokenizer: Arc<Tokenizer>,
     wandb_run: Option<Arc<wandb::Run>>,
     pub metrics: Arc<ClientMetrics>,
+    training_events: Option<TrainingEventLogger>,
     model_task_runner: ModelTaskRunner,
 
     step_durations: BoundedQueue<Duration, 16>,
@@ -38,6 +42,7 @@ impl StatsLogger {
         lr_schedule: LearningRateSchedule,
         wandb_run: Option<wandb::Run>,
         metrics: Arc<ClientMetrics>,
+        training_events: Option<TrainingEventLogger>,
     ) -> Self {
         Self {
             tokenizer,
@@ -51,6 +56,13 @@ impl StatsLogger {
             last_optim_stats: HashMap::new(),
             endpoint_info: Vec::new(),
             metrics,
+            training_events,
+        }
+    }
+
+    pub fn log_training_event(&self, event: TrainingEvent) {
+        if let Some(training_events) = &self.training_events {
+            training_events.log(event);
         }
     }
 
@@ -70,7 +82,13 @@ impl StatsLogger {
             round_log.insert("train/confidence", confidence_val);
 
             // Log to metrics
-            self.metrics.record_training_loss(loss_val as f64);
+            let step = state.progress.step;
+            let in_lr_warmup = step < self.lr_schedule.get_warmup_steps()
+                || state
+                    .get_cold_start_warmup_bounds()
+                    .is_some_and(|(start, end)| (start..end).contains(&step));
+            self.metrics
+                .record_training_loss(step, loss_val, in_lr_warmup);
             self.metrics
                 .record_training_perplexity(perplexity_val as f64);
             self.metrics
@@ -85,6 +103,18 @@ impl StatsLogger {
         round_log.insert("train/lr", lr);
         self.metrics.record_learning_rate(lr);
 
+        if let (Some(loss), Some(duration)) = (
+            self.losses().last(),
+            self.training_round_durations.iter().last(),
+        ) {
+            self.log_training_event(TrainingEvent::StepComplete {
+                step: state.progress.step,
+                loss: *loss,
+                duration_secs: duration.as_secs_f64(),
+                lr,
+            });
+        }
+
         let total_tokens_val = total_tokens(state);
         let tokens_per_sec_val = self.global_tokens_per_second(state);
         let token_batch_size_val = token_batch_size(state);
@@ -236,8 +266,13 @@ impl StatsLogger {
 
     /// only call this once per step
     /// take the current eval results and push them
-    pub fn push_eval_results(&mut self) {
+    pub fn push_eval_results(&mut self, step: u32) {
         for (key, value) in self.current_eval_results() {
+            self.log_training_event(TrainingEvent::EvalComplete {
+                step,
+                task: key.clone(),
+                score: value,
+            });
             self.eval_history
                 .entry(key.clone())
                 .or_default()
@@ -4,12 +4,15 @@ use crate::{
 };
 
 use iroh_blobs::api::Tag;
-use psyche_coordinator::{Committee, Coordinator, RunState, Witness, WitnessProof};
+use psyche_coordinator::{
+    BLOOM_FALSE_RATE, Committee, Coordinator, RunState, Witness, WitnessBloom, WitnessProof,
+};
 use psyche_core::{IntegrationTestLogMarker, MerkleRoot, MerkleTree, NodeIdentity, sha256};
 use psyche_event_sourcing::event;
 use psyche_modeling::{DistroResult, Trainer};
 use psyche_network::{BlobTicket, Hash, P2PEndpointInfo, TransmittableDistroResult};
 use psyche_watcher::OpportunisticData;
+use serde::{Deserialize, Serialize};
 use std::{
     fmt,
     sync::{Arc, Mutex},
@@ -31,6 +34,7 @@ use super::{
     round_state::RoundState,
     stats::StatsLogger,
     train::{TrainError, TrainingStep, TrainingStepMetadata},
+    training_events::TrainingEvent,
     types::PayloadState,
     warmup::{WarmupStep, WarmupStepMetadata},
     witness::{WitnessStep, WitnessStepMetadata, WitnessingError},
@@ -113,6 +117,7 @@ pub enum OpportunisticWitnessError {
     ApplyState(#[from] ApplyStateError),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ApplyMessageOutcome {
     Applied,
     /// Maybe we're not warmed up, or we've already applied this message
@@ -138,8 +143,12 @@ impl StepStateMachine {
         let mut previous_round = RoundState::default();
         let mut current_round = RoundState::default();
 
-        let active_step =
-            ActiveStep::Warmup(warmup.start(trainers, &mut previous_round, &mut current_round));
+        let active_step = ActiveStep::Warmup(warmup.start(
+            trainers,
+            coordinator_state.progress.step,
+            &mut previous_round,
+            &mut current_round,
+        ));
 
         Self {
             identity,
@@ -700,7 +709,29 @@ impl StepStateMachine {
         });
     }
 
+    /// Records how full this round's witness blooms are, until they're taken to send our witness.
+    fn record_bloom_occupancy(&self) {
+        let Some((participant_bloom, broadcast_bloom)) = *self.current_round.blooms.lock().unwrap()
+        else {
+            return;
+        };
+        let capacity = WitnessBloom::capacity(BLOOM_FALSE_RATE);
+        let stats_logger = self.stats_logger.lock().unwrap();
+        stats_logger.metrics.record_bloom_occupancy(
+            "participant",
+            participant_bloom.estimated_count(),
+            capacity,
+        );
+        stats_logger.metrics.record_bloom_occupancy(
+            "broadcast",
+            broadcast_bloom.estimated_count(),
+            capacity,
+        );
+    }
+
     async fn apply_state(&mut self, state: Coordinator) -> Result<(), StepError> {
+        self.record_bloom_occupancy();
+
         let client_index = match state
             .epoch_state
             .clients
@@ -735,6 +766,7 @@ impl StepStateMachine {
                         }
                         ActiveStep::Warmup(self.warmup.start(
                             trainers,
+                            state.progress.step,
                             &mut self.previous_round,
                             &mut self.current_round,
                         ))
@@ -745,6 +777,7 @@ impl StepStateMachine {
                         );
                         ActiveStep::Warmup(self.warmup.start(
                             training.finish().await?.evals_or_trainers,
+                            state.progress.step,
                             &mut self.previous_round,
                             &mut self.current_round,
                         ))
@@ -755,6 +788,7 @@ impl StepStateMachine {
                         );
                         ActiveStep::Warmup(self.warmup.start(
                             witness.finish().await?,
+                            state.progress.step,
                             &mut self.previous_round,
                             &mut self.current_round,
                         ))
@@ -766,6 +800,16 @@ impl StepStateMachine {
             }
         };
 
+        if self.coordinator_state.run_state != state.run_state {
+            self.stats_logger
+                .lock()
+                .map_err(|_| StepError::StatsLoggerMutex)?
+                .log_training_event(TrainingEvent::RoundStateChanged {
+                    old: self.coordinator_state.run_state,
+                    new: state.run_state,
+                });
+        }
+
         let new_step: ActiveStep = match (std::mem::take(&mut self.active_step), state.run_state) {
             // start training at the beginning of an epoch
             (ActiveStep::Warmup(warmup), RunState::RoundTrain) => {
@@ -776,7 +820,7 @@ impl StepStateMachine {
                 self.stats_logger
                     .lock()
                     .map_err(|_| StepError::StatsLoggerMutex)?
-                    .push_eval_results();
+                    .push_eval_results(state.progress.step);
                 ActiveStep::Training(self.training.start(
                     client_index,
                     &state,
@@ -792,7 +836,9 @@ impl StepStateMachine {
                     evals_or_trainers,
                     round_losses,
                     optim_stats,
+                    grad_norm,
                     round_duration,
+                    tokens_per_second,
                 } = training.finish().await?;
                 let step_duration = self
                     .step_finish_time
@@ -803,6 +849,27 @@ impl StepStateMachine {
                     .lock()
                     .map_err(|_| StepError::StatsLoggerMutex)?
                     .push_round_stats(&round_losses, round_duration, step_duration, optim_stats);
+                if let Some(step_duration) = step_duration {
+                    self.stats_logger
+                        .lock()
+                        .map_err(|_| StepError::StatsLoggerMutex)?
+                        .metrics
+                        .record_step_duration(state.progress.step, step_duration);
+                }
+                if let Some(grad_norm) = grad_norm {
+                    self.stats_logger
+                        .lock()
+                        .map_err(|_| StepError::StatsLoggerMutex)?
+                        .metrics
+                        .record_gradient_norm(grad_norm.before_clip, grad_norm.after_clip);
+                }
+                if let Some(tokens_per_second) = tokens_per_second {
+                    self.stats_logger
+                        .lock()
+                        .map_err(|_| StepError::StatsLoggerMutex)?
+                        .metrics
+                        .record_local_tokens_per_second(tokens_per_second);
+                }
 
                 info!(
                     integration_test_log_marker = %IntegrationTestLogMarker::Loss,
@@ -810,6 +877,7 @@ impl StepStateMachine {
                     epoch = state.progress.epoch,
                     step = state.progress.step,
                     loss = loss.unwrap_or(f32::NAN),
+                    tokens_per_second = tokens_per_second.unwrap_or(f64::NAN),
                     "client_loss",
                 );
                 self.stats_logger
@@ -860,6 +928,7 @@ impl StepStateMachine {
                 }
                 ActiveStep::Warmup(self.warmup.start(
                     trainers,
+                    state.progress.step,
                     &mut self.previous_round,
                     &mut self.current_round,
                 ))
@@ -1108,7 +1177,7 @@ impl RunManager {
                     .iter()
                     .any(|handle| !handle.is_finished());
 
-                has_pending_uploads
+                has_pending_uploads || step_state_machine.cooldown.has_background_uploads()
             }
             _ => false,
         }
@@ -1162,6 +1231,10 @@ impl From<&RunManager> for ClientTUIState {
                     token_batch_size: coordinator.get_sequence_length()
                         * coordinator.get_target_global_batch_size(coordinator.current_round())
                             as u32,
+                    gpu_util_history: stats_guard
+                        .as_ref()
+                        .map(|s| s.metrics.gpu_util_history())
+                        .unwrap_or_default(),
                 }
             }
             _ => Default::default(),
@@ -11,7 +11,7 @@ use psyche_coordinator::{
 use psyche_core::{BatchId, Bloom, IntegrationTestLogMarker, NodeIdentity, OptimizerDefinition};
 use psyche_event_sourcing::event;
 use psyche_modeling::{
-    ApplyDistroResultError, Batch, BatchData, DistroResult, TrainOutput, Trainer,
+    ApplyDistroResultError, Batch, BatchData, DistroResult, GradNorm, TrainOutput, Trainer,
     TrainerThreadCommunicationError,
 };
 use psyche_network::{
@@ -43,7 +43,10 @@ pub struct FinishedTrainers {
     pub evals_or_trainers: MaybeRunningEvals,
     pub round_losses: Vec<f32>,
     pub optim_stats: HashMap<String, f64>,
+    pub grad_norm: Option<GradNorm>,
     pub round_duration: Duration,
+    /// How fast we trained on the batch whose results we sent, if any
+    pub tokens_per_second: Option<f64>,
 }
 
 #[derive(Error, Debug)]
@@ -98,6 +101,7 @@ pub struct TrainingStepMetadata {
     pub tx_distro_result: mpsc::UnboundedSender<DistroBroadcastAndPayload>,
 
     pub write_gradients_dir: Option<PathBuf>,
+    pub distro_int8_quantization: bool,
 
     pub model_task_runner: ModelTaskRunner,
 }
@@ -264,6 +268,7 @@ impl TrainingStepMetadata {
             });
         }
         let model_task_runner = self.model_task_runner.clone();
+        let eval_step = state.progress.step;
         let finished = Arc::new(AtomicBool::new(false));
 
         let prev_self_distro_results = previous_round.self_distro_results.clone();
@@ -277,13 +282,15 @@ impl TrainingStepMetadata {
                     debug!("Training for round finished, duration {:?}", round_duration);
                     finished.store(true, Ordering::SeqCst);
                     Ok(FinishedTrainers {
-                        evals_or_trainers: MaybeRunningEvals::Running(
-                            model_task_runner
-                                .start(applying.await.map_err(|_| TrainError::ApplyCrashed)??),
-                        ),
+                        evals_or_trainers: MaybeRunningEvals::Running(model_task_runner.start(
+                            applying.await.map_err(|_| TrainError::ApplyCrashed)??,
+                            eval_step,
+                        )),
                         round_losses: vec![],
                         optim_stats: HashMap::new(),
+                        grad_norm: None,
                         round_duration,
+                        tokens_per_second: None,
                     })
                 })
             } else {
@@ -297,6 +304,7 @@ impl TrainingStepMetadata {
                         _ => false,
                     },
                 };
+                let quantize_int8 = self.distro_int8_quantization;
                 let finished = finished.clone();
 
                 let TrainingDataForStep {
@@ -309,6 +317,8 @@ impl TrainingStepMetadata {
                 tokio::task::spawn(async move {
                     let mut round_losses: Vec<f32> = Vec::new();
                     let mut optim_stats: HashMap<String, f64> = HashMap::new();
+                    let mut round_grad_norm: Option<GradNorm> = None;
+                    let mut round_tokens_per_second: Option<f64> = None;
 
                     let mut available_trainers =
                         applying.await.map_err(|_| TrainError::ApplyCrashed)??;
@@ -389,8 +399,10 @@ impl TrainingStepMetadata {
                                 loss,
                                 step,
                                 distro_results,
+                                grad_norm,
                                 cancelled,
                                 nonce,
+                                tokens_per_second,
                             } = completed_trainer.map_err(|_| TrainError::TrainCrashed)??;
 
                             event!(train::TrainingFinished {
@@ -399,12 +411,15 @@ impl TrainingStepMetadata {
                                 loss: Some(loss.into())
                             });
 
-                            debug!(step=step, loss=loss, batch_id=%batch_id, "Got training output, DisTrO results generated");
+                            debug!(step=step, loss=loss, tokens_per_second=tokens_per_second, batch_id=%batch_id, "Got training output, DisTrO results generated");
 
                             available_trainers.push(trainer);
 
                             if !sent_results {
                                 let distro_results = distro_results.unwrap_or_default();
+                                if grad_norm.is_some() {
+                                    round_grad_norm = grad_norm;
+                                }
 
                                 for result in &distro_results {
                                     if let Some(stats) = &result.stats {
@@ -426,7 +441,13 @@ impl TrainingStepMetadata {
                                         return Ok(());
                                     }
 
-                                    let to_transmit = if quantize { Trainer::quantize_results(&distro_results) } else { distro_results.clone()};
+                                    let to_transmit = if quantize {
+                                        Trainer::quantize_results(&distro_results)
+                                    } else if quantize_int8 {
+                                        Trainer::quantize_results_int8(&distro_results)
+                                    } else {
+                                        distro_results.clone()
+                                    };
 
                                     let transmittable_distro_result = TransmittableDistroResult {
                                         step,
@@ -470,6 +491,7 @@ impl TrainingStepMetadata {
                                 res?;
 
                                 round_losses.push(loss);
+                                round_tokens_per_second = Some(tokens_per_second);
                                 sent_results = true;
                             }
                         }
@@ -480,7 +502,9 @@ impl TrainingStepMetadata {
                         MaybeRunningEvals::NotRunning(available_trainers)
                     } else {
                         // we finished before getting cancelled, have some time to start evals.
-                        MaybeRunningEvals::Running(model_task_runner.start(available_trainers))
+                        MaybeRunningEvals::Running(
+                            model_task_runner.start(available_trainers, eval_step),
+                        )
                     };
                     let round_duration = Instant::now() - round_start;
                     debug!("Training for round finished, duration {:?}", round_duration);
@@ -489,7 +513,9 @@ impl TrainingStepMetadata {
                         evals_or_trainers: evals,
                         round_losses,
                         optim_stats,
+                        grad_norm: round_grad_norm,
                         round_duration,
+                        tokens_per_second: round_tokens_per_second,
                     })
                 })
             };
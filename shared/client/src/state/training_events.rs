@@ -0,0 +1,159 @@
+use chrono::{SecondsFormat, Utc};
+use psyche_coordinator::RunState;
+use serde::Serialize;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use tracing::warn;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event")]
+pub enum TrainingEvent {
+    StepComplete {
+        step: u32,
+        loss: f32,
+        duration_secs: f64,
+        lr: f64,
+    },
+    CheckpointSaved {
+        step: u32,
+        path: PathBuf,
+    },
+    EvalComplete {
+        step: u32,
+        task: String,
+        score: f64,
+    },
+    RoundStateChanged {
+        old: RunState,
+        new: RunState,
+    },
+}
+
+#[derive(Serialize)]
+struct TrainingEventLine<'a> {
+    timestamp: String,
+    run_id: &'a str,
+    #[serde(flatten)]
+    event: &'a TrainingEvent,
+}
+
+/// Appends [`TrainingEvent`]s to a file as newline-delimited JSON.
+/// Cheap to clone; all clones write to the same file.
+#[derive(Debug, Clone)]
+pub struct TrainingEventLogger {
+    run_id: String,
+    file: Arc<Mutex<File>>,
+}
+
+impl TrainingEventLogger {
+    pub fn new(path: &Path, run_id: String) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            run_id,
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    pub fn log(&self, event: TrainingEvent) {
+        if let Err(err) = self.write(&event) {
+            warn!("Failed to write training event {event:?}: {err}");
+        }
+    }
+
+    fn write(&self, event: &TrainingEvent) -> io::Result<()> {
+        let mut line = serde_json::to_vec(&TrainingEventLine {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            run_id: &self.run_id,
+            event,
+        })?;
+        line.push(b'\n');
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| io::Error::other("training event log mutex poisoned"))?;
+        // a single write per line, so concurrent writers never interleave within a line
+        file.write_all(&line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn events_are_written_as_one_json_line_each() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        let logger = TrainingEventLogger::new(&path, "run".to_string()).unwrap();
+        logger.log(TrainingEvent::StepComplete {
+            step: 3,
+            loss: 0.5,
+            duration_secs: 1.5,
+            lr: 0.25,
+        });
+        // clones append to the same file
+        logger.clone().log(TrainingEvent::RoundStateChanged {
+            old: RunState::Warmup,
+            new: RunState::RoundTrain,
+        });
+
+        let lines: Vec<Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        for line in &lines {
+            assert_eq!(line["run_id"], "run");
+            assert!(line["timestamp"].is_string());
+        }
+        assert_eq!(lines[0]["event"], "StepComplete");
+        assert_eq!(lines[0]["step"], 3);
+        assert_eq!(lines[0]["loss"], 0.5);
+        assert_eq!(lines[0]["duration_secs"], 1.5);
+        assert_eq!(lines[0]["lr"], 0.25);
+        assert_eq!(lines[1]["event"], "RoundStateChanged");
+        assert_eq!(
+            lines[1]["old"],
+            serde_json::to_value(RunState::Warmup).unwrap()
+        );
+        assert_eq!(
+            lines[1]["new"],
+            serde_json::to_value(RunState::RoundTrain).unwrap()
+        );
+    }
+
+    #[test]
+    fn reopening_appends_instead_of_truncating() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        TrainingEventLogger::new(&path, "run".to_string())
+            .unwrap()
+            .log(TrainingEvent::EvalComplete {
+                step: 1,
+                task: "hellaswag".to_string(),
+                score: 0.5,
+            });
+        TrainingEventLogger::new(&path, "run".to_string())
+            .unwrap()
+            .log(TrainingEvent::CheckpointSaved {
+                step: 2,
+                path: PathBuf::from("/checkpoints/run-step2"),
+            });
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let events: Vec<String> = contents
+            .lines()
+            .map(|line| {
+                let value: Value = serde_json::from_str(line).unwrap();
+                value["event"].as_str().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(events, ["EvalComplete", "CheckpointSaved"]);
+    }
+}
@@ -5,6 +5,7 @@ use psyche_core::{BatchId, MerkleRoot, NodeIdentity};
 use psyche_data_provider::{GcsUploadInfo, HubUploadInfo};
 use psyche_modeling::DistroResult;
 use psyche_network::{BlobTicket, TransmittableDistroResult};
+use serde::{Deserialize, Serialize};
 use tch::TchError;
 use thiserror::Error;
 use tokio::task::JoinHandle;
@@ -21,6 +22,29 @@ pub struct CheckpointConfig {
     pub checkpoint_dir: PathBuf,
     pub delete_old_steps: bool,
     pub keep_steps: u32,
+    /// Queue uploads for a single background uploader instead of starting one per checkpoint
+    pub background_upload: bool,
+}
+
+/// Learning rate range test to run before training, see [`psyche_modeling::LocalTrainer::find_lr`].
+#[derive(Debug, Clone, Copy)]
+pub struct FindLrConfig {
+    pub min_lr: f64,
+    pub max_lr: f64,
+    pub num_steps: usize,
+}
+
+pub const CHECKPOINT_METADATA_FILENAME: &str = "checkpoint_metadata.json";
+
+/// Subdirectory of a local checkpoint holding the optimizer state, for resuming.
+pub const OPTIMIZER_STATE_DIRNAME: &str = "optimizer";
+
+/// Written next to the parameters of every local checkpoint, so a client can resume from it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointMetadata {
+    pub run_id: String,
+    pub epoch: u32,
+    pub step: u32,
 }
 
 #[derive(Debug)]
@@ -11,6 +11,7 @@ impl WarmupStepMetadata {
     pub fn start(
         &self,
         evals_or_trainers: impl Into<MaybeRunningEvals>,
+        step: u32,
         previous_round: &mut RoundState,
         current_round: &mut RoundState,
     ) -> WarmupStep {
@@ -20,7 +21,7 @@ impl WarmupStepMetadata {
 
         let evals = self
             .model_task_runner
-            .start_if_not_running(evals_or_trainers.into());
+            .start_if_not_running(evals_or_trainers.into(), step);
         WarmupStep { evals }
     }
 }
@@ -44,7 +44,7 @@ impl WitnessStepMetadata {
     pub fn start(
         &self,
         _client_index: u64,
-        _state: &Coordinator,
+        state: &Coordinator,
         trainers: MaybeRunningEvals,
         previous_round: &mut RoundState,
         current_round: &mut RoundState,
@@ -54,7 +54,9 @@ impl WitnessStepMetadata {
             return Err(WitnessingError::NoTrainers);
         }
 
-        let evals = self.model_task_runner.start_if_not_running(trainers);
+        let evals = self
+            .model_task_runner
+            .start_if_not_running(trainers, state.progress.step);
 
         let sending_witness = if let Some(witness) =
             WitnessStep::get_witness_to_send(previous_round, current_round)
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 use psyche_coordinator::Committee;
 use psyche_tui::ratatui::{
@@ -7,7 +7,7 @@ use psyche_tui::ratatui::{
     style::{Style, Stylize},
     symbols,
     text::Line,
-    widgets::{Axis, Chart, Dataset, GraphType, LegendPosition, Paragraph, Widget},
+    widgets::{Axis, Chart, Dataset, GraphType, LegendPosition, Paragraph, Sparkline, Widget},
 };
 use psyche_watcher::TuiRunState;
 
@@ -15,6 +15,9 @@ lazy_static::lazy_static! {
     static ref GRAPH_COLORS: [Style; 4] = [Style::default().red(), Style::default().magenta(), Style::default().green(), Style::default().cyan()];
 }
 
+/// Rows given to each GPU's utilization sparkline
+const GPU_SPARKLINE_HEIGHT: u16 = 2;
+
 #[derive(Default, Debug)]
 pub struct ClientTUI;
 
@@ -66,15 +69,16 @@ impl psyche_tui::CustomWidget for ClientTUI {
             .map(|key| key.len())
             .max_by(|x, y| x.cmp(y))
             .unwrap_or(6) as u16;
-        let coord_split = Layout::vertical(match state.evals.is_empty() {
-            true => vec![Constraint::Fill(1), Constraint::Length(2)],
-            false => vec![
-                Constraint::Fill(1),
-                Constraint::Length(2),
-                Constraint::Fill(1),
-            ],
-        })
-        .split(area);
+        let mut coord_constraints = vec![Constraint::Fill(1), Constraint::Length(2)];
+        if !state.evals.is_empty() {
+            coord_constraints.push(Constraint::Fill(1));
+        }
+        if !state.gpu_util_history.is_empty() {
+            coord_constraints.push(Constraint::Length(
+                GPU_SPARKLINE_HEIGHT * state.gpu_util_history.len() as u16,
+            ));
+        }
+        let coord_split = Layout::vertical(coord_constraints).split(area);
         {
             let plot_split =
                 Layout::horizontal([Constraint::Fill(1), Constraint::Length(right_size)])
@@ -244,6 +248,30 @@ impl psyche_tui::CustomWidget for ClientTUI {
                 .render(vsplit[index + 1], buf);
             }
         }
+        if !state.gpu_util_history.is_empty() {
+            let rows = Layout::vertical(vec![
+                Constraint::Length(GPU_SPARKLINE_HEIGHT);
+                state.gpu_util_history.len()
+            ])
+            .split(coord_split[coord_split.len() - 1]);
+            for (index, samples) in state.gpu_util_history.iter().enumerate() {
+                let row_split =
+                    Layout::horizontal([Constraint::Fill(1), Constraint::Length(right_size)])
+                        .split(rows[index]);
+                let data = samples.iter().map(|util| *util as u64).collect::<Vec<_>>();
+                Sparkline::default()
+                    .data(&data)
+                    .max(100)
+                    .style(GRAPH_COLORS[index % GRAPH_COLORS.len()])
+                    .render(row_split[0], buf);
+                Paragraph::new(vec![
+                    Line::from(format!("GPU {index}")),
+                    Line::from(format!("{:.0}%", samples.back().unwrap_or(&0.0))),
+                ])
+                .centered()
+                .render(row_split[1], buf);
+            }
+        }
     }
 }
 
@@ -259,4 +287,6 @@ pub struct ClientTUIState {
     pub global_tokens_per_second: f32,
     pub token_batch_size: u32,
     pub total_tokens: u64,
+    /// Recent utilization percentages, one deque per GPU
+    pub gpu_util_history: Vec<VecDeque<f64>>,
 }
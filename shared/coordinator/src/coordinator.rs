@@ -8,7 +8,9 @@ use anchor_lang::{
     prelude::{borsh, msg},
 };
 use bytemuck::{Pod, Zeroable};
-use psyche_core::{Bloom, FixedString, FixedVec, MerkleRoot, NodeIdentity, SmallBoolean, sha256};
+use psyche_core::{
+    Bloom, DistroExtensions, FixedString, FixedVec, MerkleRoot, NodeIdentity, SmallBoolean, sha256,
+};
 use serde::{Deserialize, Serialize};
 use std::{collections::HashSet, hash::Hash};
 use ts_rs::TS;
@@ -22,6 +24,8 @@ pub const SOLANA_RUN_ID_MAX_LEN: usize = 32;
 
 pub const BLOOM_FALSE_RATE: f64 = 0.01f64;
 pub const WITNESS_QUORUM_RAIO: f64 = 2.0f64 / 3.0f64;
+/// How many rounds the adaptive witness quorum looks back over
+pub const WITNESS_SUCCESS_HISTORY_LEN: u8 = 20;
 pub const WAITING_FOR_MEMBERS_EXTRA_SECONDS: u64 = 10;
 // max amount of tokens to send in a witness message
 pub const MAX_TOKENS_TO_SEND: usize = 16;
@@ -237,7 +241,16 @@ pub type HealthChecks = Vec<(NodeIdentity, CommitteeProof)>;
 pub const NUM_STORED_ROUNDS: usize = 4;
 
 #[derive(
-    Clone, Debug, Zeroable, Copy, Serialize, Deserialize, AnchorDeserialize, AnchorSerialize, TS,
+    Clone,
+    Debug,
+    Zeroable,
+    Copy,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    AnchorDeserialize,
+    AnchorSerialize,
+    TS,
 )]
 #[repr(C)]
 pub struct CoordinatorConfig {
@@ -260,6 +273,61 @@ pub struct CoordinatorConfig {
 
     pub verification_percent: u8,
     pub waiting_for_members_extra_time: u8,
+
+    /// Once all but this fraction of a round's witnesses have witnessed, the round waits at most
+    /// `straggler_grace_period_secs` for the rest before moving on without them.
+    /// 0 turns this off, which is what accounts created before it existed read.
+    #[serde(default = "default_straggler_fraction")]
+    pub straggler_fraction: f64,
+    #[serde(default = "default_straggler_grace_period_secs")]
+    pub straggler_grace_period_secs: u64,
+
+    /// Lowers the witness quorum by one while too many recent rounds missed it, see
+    /// [`Coordinator::witness_quorum`].
+    #[serde(default)]
+    pub adaptive_witness_quorum: bool,
+
+    #[serde(default)]
+    pub mixed_precision: MixedPrecisionDtype,
+
+    #[serde(default)]
+    pub distro_extensions: DistroExtensions,
+}
+
+/// Whether clients train from FP32 master weights, running forward and backward passes in a lower
+/// precision. This is set per run, not per client: clients holding their weights at different
+/// precisions apply the same DisTrO updates differently and drift apart.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Zeroable,
+    AnchorDeserialize,
+    AnchorSerialize,
+    Serialize,
+    Deserialize,
+    InitSpace,
+    TS,
+)]
+#[serde(rename_all = "lowercase")]
+#[repr(u8)]
+pub enum MixedPrecisionDtype {
+    /// Weights are held and trained in BF16
+    #[default]
+    Disabled = 0,
+    BF16 = 1,
+    /// Losses are scaled to keep FP16 gradients from underflowing
+    FP16 = 2,
+}
+
+fn default_straggler_fraction() -> f64 {
+    0.1
+}
+
+fn default_straggler_grace_period_secs() -> u64 {
+    30
 }
 
 #[derive(
@@ -282,6 +350,22 @@ pub struct CoordinatorEpochState {
     pub start_timestamp: u64,
     pub first_round: SmallBoolean,
     pub cold_start_epoch: SmallBoolean,
+    /// When enough of the current round's witnesses arrived to start the straggler grace period,
+    /// or 0 if it hasn't started.
+    #[serde(default)]
+    pub straggler_grace_start_timestamp: u64,
+    /// Witnesses that rounds of this epoch moved on without, once the grace period ran out.
+    #[serde(default)]
+    pub excluded_stragglers: FixedVec<NodeIdentity, { SOLANA_MAX_NUM_WITNESSES }>,
+    /// Whether each of the last `witness_success_history_len` rounds met the witness quorum, most
+    /// recent in the lowest bit. Unlike the rest of the epoch state, carried over between epochs.
+    #[serde(default)]
+    pub witness_success_history: u32,
+    #[serde(default)]
+    pub witness_success_history_len: u8,
+    /// Whether the adaptive witness quorum is currently lowered. Carried over between epochs.
+    #[serde(default)]
+    pub witness_quorum_reduced: SmallBoolean,
 }
 
 #[derive(
@@ -382,6 +466,35 @@ impl std::fmt::Display for CoordinatorError {
 
 impl std::error::Error for CoordinatorError {}
 
+/// Returned when a config update touches a field that can't change mid-run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImmutableFieldChanged {
+    pub field: &'static str,
+}
+
+impl std::fmt::Display for ImmutableFieldChanged {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} can't be changed while a run is in progress",
+            self.field
+        )
+    }
+}
+
+impl std::error::Error for ImmutableFieldChanged {}
+
+/// What [`Coordinator::update_mutable_config`] did with a config update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigUpdate {
+    /// The whole config was replaced
+    Applied,
+    /// Everything but `witness_nodes` and `min_clients` was replaced. Those decide
+    /// who witnesses and when the epoch ends, so they only change between epochs;
+    /// update again once the coordinator isn't [`Coordinator::active`].
+    Deferred,
+}
+
 impl std::fmt::Display for RunState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -409,6 +522,11 @@ impl Default for CoordinatorEpochState {
             start_step: Default::default(),
             last_step: Default::default(),
             start_timestamp: Default::default(),
+            straggler_grace_start_timestamp: Default::default(),
+            excluded_stragglers: Default::default(),
+            witness_success_history: Default::default(),
+            witness_success_history_len: Default::default(),
+            witness_quorum_reduced: Default::default(),
         }
     }
 }
@@ -433,6 +551,22 @@ impl Client {
     }
 }
 
+impl Round {
+    /// Mean occupancy of the broadcast blooms witnessed so far, 0 without witnesses
+    pub fn broadcast_bloom_occupancy(&self) -> f64 {
+        match self.witnesses.len() {
+            0 => 0.0,
+            len => {
+                self.witnesses
+                    .iter()
+                    .map(|witness| witness.broadcast_bloom.occupancy())
+                    .sum::<f64>()
+                    / len as f64
+            }
+        }
+    }
+}
+
 impl Coordinator {
     pub fn tick<'a, 'b>(
         &'a mut self,
@@ -474,11 +608,7 @@ impl Coordinator {
             return Err(CoordinatorError::InvalidRunState);
         }
 
-        let witness_nodes = if self.config.witness_nodes == 0 {
-            self.epoch_state.clients.len().min(SOLANA_MAX_NUM_WITNESSES)
-        } else {
-            self.config.witness_nodes as usize
-        };
+        let witness_nodes = self.num_witness_nodes();
 
         // Everyone can send a witness in the warmup phase so we don't need to check for the committee
         let round = self.current_round().unwrap();
@@ -511,11 +641,7 @@ impl Coordinator {
             return Err(CoordinatorError::Halted);
         }
 
-        let witness_nodes = if self.config.witness_nodes == 0 {
-            self.epoch_state.clients.len().min(SOLANA_MAX_NUM_WITNESSES)
-        } else {
-            self.config.witness_nodes as usize
-        };
+        let witness_nodes = self.num_witness_nodes();
 
         if !matches!(
             self.run_state,
@@ -545,8 +671,16 @@ impl Coordinator {
             .push(witness)
             .map_err(|_| CoordinatorError::WitnessesFull)?;
 
-        if round.witnesses.len() == witness_nodes && !(self.run_state == RunState::RoundWitness) {
+        let num_witnesses = round.witnesses.len();
+        if num_witnesses == witness_nodes && !(self.run_state == RunState::RoundWitness) {
             self.change_state(unix_timestamp, RunState::RoundWitness);
+        } else if self.run_state == RunState::RoundTrain
+            && self.epoch_state.straggler_grace_start_timestamp == 0
+            && self
+                .straggler_threshold()
+                .is_some_and(|threshold| num_witnesses >= threshold)
+        {
+            self.epoch_state.straggler_grace_start_timestamp = unix_timestamp;
         }
         Ok(())
     }
@@ -651,6 +785,10 @@ impl Coordinator {
         }
     }
 
+    /// Pauses the run. An active run isn't stopped mid-round: it finishes the current round,
+    /// cools down and checkpoints, then pauses, so nothing in flight has to be saved. Resuming
+    /// starts a cold-start epoch from that checkpoint. Pausing in the middle of a round, and
+    /// carrying its blooms and pending witnesses over to the resumed run, isn't supported.
     pub fn pause(&mut self, unix_timestamp: u64) -> std::result::Result<(), CoordinatorError> {
         if !self.halted() {
             if self.active() {
@@ -707,17 +845,94 @@ impl Coordinator {
         }
     }
 
+    /// How many witnesses a round waits for before moving on.
+    pub fn num_witness_nodes(&self) -> usize {
+        if self.config.witness_nodes == 0 {
+            self.epoch_state.clients.len().min(SOLANA_MAX_NUM_WITNESSES)
+        } else {
+            self.config.witness_nodes as usize
+        }
+    }
+
+    /// How many witnesses a round needs before the rest are treated as stragglers,
+    /// or `None` if straggler mitigation is off.
+    pub fn straggler_threshold(&self) -> Option<usize> {
+        if self.config.straggler_fraction <= 0.0 {
+            return None;
+        }
+        let witness_nodes = self.num_witness_nodes();
+        let threshold =
+            ((1.0 - self.config.straggler_fraction) * witness_nodes as f64).ceil() as usize;
+        Some(threshold.max(1))
+    }
+
+    /// How many witnesses a round needs. With `adaptive_witness_quorum` on, this is one lower
+    /// (but at least 1) while the quorum is reduced, see [`Self::record_witness_quorum_met`].
     pub fn witness_quorum(&self, num_witnesses: u16) -> u16 {
         let witness_nodes = match self.config.witness_nodes {
             0 => num_witnesses,
             witness_nodes => witness_nodes,
         };
-        match witness_nodes {
+        let quorum = match witness_nodes {
             0 => unreachable!(),
             1 => 1,
             2 => 2,
             3 => 2,
             witness_nodes => ((witness_nodes as f64 * WITNESS_QUORUM_RAIO) as u16).max(1),
+        };
+        if self.config.adaptive_witness_quorum && self.epoch_state.witness_quorum_reduced.is_true()
+        {
+            quorum.saturating_sub(1).max(1)
+        } else {
+            quorum
+        }
+    }
+
+    /// The fraction of the last [`WITNESS_SUCCESS_HISTORY_LEN`] rounds that met the witness
+    /// quorum, or `None` until that many rounds have been recorded.
+    pub fn witness_success_rate(&self) -> Option<f64> {
+        let epoch_state = &self.epoch_state;
+        if epoch_state.witness_success_history_len < WITNESS_SUCCESS_HISTORY_LEN {
+            return None;
+        }
+        Some(
+            epoch_state.witness_success_history.count_ones() as f64
+                / WITNESS_SUCCESS_HISTORY_LEN as f64,
+        )
+    }
+
+    /// Records whether a round met the witness quorum. With `adaptive_witness_quorum` on, the
+    /// quorum is lowered once fewer than 80% of recent rounds met it, and restored once more than
+    /// 95% do.
+    pub(crate) fn record_witness_quorum_met(&mut self, met: bool) {
+        let epoch_state = &mut self.epoch_state;
+        epoch_state.witness_success_history = ((epoch_state.witness_success_history << 1)
+            | met as u32)
+            & ((1 << WITNESS_SUCCESS_HISTORY_LEN) - 1);
+        epoch_state.witness_success_history_len =
+            (epoch_state.witness_success_history_len + 1).min(WITNESS_SUCCESS_HISTORY_LEN);
+
+        if !self.config.adaptive_witness_quorum {
+            return;
+        }
+        let Some(success_rate) = self.witness_success_rate() else {
+            return;
+        };
+        let reduced = self.epoch_state.witness_quorum_reduced.is_true();
+        if !reduced && success_rate < 0.8 {
+            self.epoch_state.witness_quorum_reduced = true.into();
+            msg!(
+                "Only {:.0}% of recent rounds met the witness quorum, lowering it to {}",
+                success_rate * 100.0,
+                self.witness_quorum(self.num_witness_nodes().max(1) as u16)
+            );
+        } else if reduced && success_rate > 0.95 {
+            self.epoch_state.witness_quorum_reduced = false.into();
+            msg!(
+                "{:.0}% of recent rounds met the witness quorum, restoring it to {}",
+                success_rate * 100.0,
+                self.witness_quorum(self.num_witness_nodes().max(1) as u16)
+            );
         }
     }
 
@@ -817,6 +1032,53 @@ impl Coordinator {
         }
     }
 
+    /// Replaces the config with `new_config`, as long as it only changes
+    /// fields that are safe to change mid-run. The batch size schedule and
+    /// total steps decide which data each step trains on, so they're fixed.
+    /// Changes to `witness_nodes` and `min_clients` wait for the epoch to end,
+    /// see [`ConfigUpdate::Deferred`].
+    pub fn update_mutable_config(
+        &mut self,
+        mut new_config: CoordinatorConfig,
+    ) -> std::result::Result<ConfigUpdate, ImmutableFieldChanged> {
+        let old = &self.config;
+        if new_config.total_steps != old.total_steps {
+            return Err(ImmutableFieldChanged {
+                field: "total_steps",
+            });
+        }
+        if new_config.global_batch_size_start != old.global_batch_size_start
+            || new_config.global_batch_size_end != old.global_batch_size_end
+            || new_config.global_batch_size_warmup_tokens != old.global_batch_size_warmup_tokens
+        {
+            return Err(ImmutableFieldChanged {
+                field: "global_batch_size",
+            });
+        }
+        if new_config.mixed_precision != old.mixed_precision {
+            return Err(ImmutableFieldChanged {
+                field: "mixed_precision",
+            });
+        }
+        if new_config.distro_extensions != old.distro_extensions {
+            return Err(ImmutableFieldChanged {
+                field: "distro_extensions",
+            });
+        }
+        let update = if self.active()
+            && (new_config.witness_nodes != old.witness_nodes
+                || new_config.min_clients != old.min_clients)
+        {
+            new_config.witness_nodes = old.witness_nodes;
+            new_config.min_clients = old.min_clients;
+            ConfigUpdate::Deferred
+        } else {
+            ConfigUpdate::Applied
+        };
+        self.config = new_config;
+        Ok(update)
+    }
+
     pub fn active(&self) -> bool {
         !matches!(
             self.run_state,
@@ -960,9 +1222,15 @@ impl Coordinator {
             }
 
             let cold_start_epoch = self.epoch_state.cold_start_epoch;
+            let witness_success_history = self.epoch_state.witness_success_history;
+            let witness_success_history_len = self.epoch_state.witness_success_history_len;
+            let witness_quorum_reduced = self.epoch_state.witness_quorum_reduced;
             bytemuck::write_zeroes(&mut self.epoch_state);
             self.epoch_state.first_round = true.into();
             self.epoch_state.cold_start_epoch = cold_start_epoch;
+            self.epoch_state.witness_success_history = witness_success_history;
+            self.epoch_state.witness_success_history_len = witness_success_history_len;
+            self.epoch_state.witness_quorum_reduced = witness_quorum_reduced;
             self.epoch_state.start_step = self.progress.step;
             self.epoch_state.start_timestamp = unix_timestamp;
             self.epoch_state
@@ -1005,10 +1273,52 @@ impl Coordinator {
     ) -> std::result::Result<TickResult, CoordinatorError> {
         if self.check_timeout(unix_timestamp, self.config.max_round_train_time) {
             self.change_state(unix_timestamp, RunState::RoundWitness);
+        } else if self.epoch_state.straggler_grace_start_timestamp != 0
+            && unix_timestamp
+                >= self.epoch_state.straggler_grace_start_timestamp
+                    + self.config.straggler_grace_period_secs
+        {
+            self.exclude_stragglers();
+            self.change_state(unix_timestamp, RunState::RoundWitness);
         }
         Ok(TickResult::Ticked)
     }
 
+    /// Records the current round's witnesses that haven't witnessed yet as excluded stragglers.
+    fn exclude_stragglers(&mut self) {
+        let Ok(selection) = CommitteeSelection::from_coordinator(self, 0) else {
+            return;
+        };
+        let round = self.current_round_unchecked();
+        let height = round.height;
+        let stragglers: Vec<NodeIdentity> = self
+            .epoch_state
+            .clients
+            .iter()
+            .enumerate()
+            .take(round.clients_len as usize)
+            .filter(|(index, _)| {
+                selection.get_witness(*index as u64).witness.is_true()
+                    && !round
+                        .witnesses
+                        .iter()
+                        .any(|witness| witness.proof.index == *index as u64)
+            })
+            .map(|(_, client)| client.id)
+            .collect();
+        for id in stragglers {
+            msg!(
+                "Round {} moved on without straggling witness {}",
+                height,
+                id
+            );
+            if !self.epoch_state.excluded_stragglers.contains(&id) {
+                // once full, stragglers are only logged
+                let _ = self.epoch_state.excluded_stragglers.push(id);
+            }
+        }
+    }
+
     fn tick_round_witness(
         &mut self,
         unix_timestamp: u64,
@@ -1022,6 +1332,9 @@ impl Coordinator {
             let height = current_round.height;
             let num_witnesses = current_round.witnesses.len() as u16;
             self.move_clients_to_exited(height);
+            let witness_quorum_met =
+                num_witnesses > 0 && num_witnesses >= self.witness_quorum(num_witnesses);
+            self.record_witness_quorum_met(witness_quorum_met);
 
             // If there are not witnesses, then we can't distinguish from
             // the situation where only witness nodes disconnected or everyone
@@ -1054,7 +1367,7 @@ impl Coordinator {
             // If we don't reach the min number of clients or registered witnesses for the current round,
             // we change to Cooldown
             if self.epoch_state.clients.len() < self.config.min_clients as usize
-                || num_witnesses < self.witness_quorum(num_witnesses)
+                || !witness_quorum_met
                 || self.progress.step >= self.config.total_steps
                 || self.pending_pause.is_true()
             {
@@ -1147,6 +1460,7 @@ impl Coordinator {
         round.tie_breaker_tasks = tie_breaker_tasks;
         round.random_seed = random_seed;
         round.witnesses.clear();
+        self.epoch_state.straggler_grace_start_timestamp = 0;
         self.change_state(unix_timestamp, RunState::RoundTrain);
     }
 
@@ -1219,6 +1533,8 @@ pub enum ConfigError {
     WitnessNodes,
     CooldownTime,
     WaitingForMembersExtraTime,
+    StragglerFraction,
+    DistroExtensions,
 }
 
 impl CoordinatorConfig {
@@ -1268,6 +1584,12 @@ impl CoordinatorConfig {
         if self.waiting_for_members_extra_time == 0 {
             return Err(ConfigError::WaitingForMembersExtraTime);
         }
+        if !(0.0..1.0).contains(&self.straggler_fraction) {
+            return Err(ConfigError::StragglerFraction);
+        }
+        if !self.distro_extensions.check() {
+            return Err(ConfigError::DistroExtensions);
+        }
         Ok(())
     }
 
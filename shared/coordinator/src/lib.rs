@@ -5,15 +5,18 @@ mod committee_selection;
 mod coordinator;
 mod data_selection;
 pub mod model;
+#[cfg(test)]
+mod tests;
 
 pub use commitment::Commitment;
 pub use committee_selection::{
     COMMITTEE_SALT, Committee, CommitteeProof, CommitteeSelection, WITNESS_SALT, WitnessProof,
 };
 pub use coordinator::{
-    BLOOM_FALSE_RATE, Client, ClientState, Coordinator, CoordinatorConfig, CoordinatorEpochState,
-    CoordinatorError, CoordinatorProgress, HealthChecks, MAX_TOKENS_TO_SEND, NUM_STORED_ROUNDS,
-    Round, RunState, SOLANA_MAX_NUM_CLIENTS, SOLANA_MAX_NUM_WITNESSES, SOLANA_MAX_STRING_LEN,
+    BLOOM_FALSE_RATE, Client, ClientState, ConfigUpdate, Coordinator, CoordinatorConfig,
+    CoordinatorEpochState, CoordinatorError, CoordinatorProgress, HealthChecks,
+    ImmutableFieldChanged, MAX_TOKENS_TO_SEND, MixedPrecisionDtype, NUM_STORED_ROUNDS, Round,
+    RunState, SOLANA_MAX_NUM_CLIENTS, SOLANA_MAX_NUM_WITNESSES, SOLANA_MAX_STRING_LEN,
     SOLANA_RUN_ID_MAX_LEN, TickResult, WAITING_FOR_MEMBERS_EXTRA_SECONDS, Witness, WitnessBloom,
     WitnessEvalResult, WitnessMetadata,
 };
@@ -0,0 +1,557 @@
+//! Property tests for the coordinator state machine, driving it through
+//! arbitrary sequences of ticks, joins, leaves and witnesses the way the
+//! centralized server does.
+
+use crate::{
+    ClientState, CommitteeSelection, ConfigUpdate, Coordinator, RunState, Witness,
+    coordinator::ConfigError,
+};
+use bytemuck::Zeroable;
+use proptest::{prelude::*, test_runner::TestCaseError};
+use psyche_core::{FixedString, LayerTopK, NodeIdentity};
+use std::collections::HashSet;
+
+const MAX_NODES: u8 = 8;
+
+#[derive(Debug, Clone)]
+enum Action {
+    Join(u8),
+    Leave(u8),
+    Tick { seconds: u64, seed: u64 },
+    WitnessAll,
+}
+
+fn action() -> impl Strategy<Value = Action> {
+    prop_oneof![
+        1 => (0..MAX_NODES).prop_map(Action::Join),
+        1 => (0..MAX_NODES).prop_map(Action::Leave),
+        3 => (0..=6u64, any::<u64>()).prop_map(|(seconds, seed)| Action::Tick { seconds, seed }),
+        2 => Just(Action::WitnessAll),
+    ]
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Config {
+    init_min_clients: u16,
+    min_clients: u16,
+    witness_nodes: u16,
+}
+
+fn config() -> impl Strategy<Value = Config> {
+    (1..=4u16)
+        .prop_flat_map(|init_min_clients| (Just(init_min_clients), 1..=init_min_clients))
+        .prop_flat_map(|(init_min_clients, min_clients)| {
+            (0..=min_clients).prop_map(move |witness_nodes| Config {
+                init_min_clients,
+                min_clients,
+                witness_nodes,
+            })
+        })
+}
+
+fn node(id: u8) -> NodeIdentity {
+    let mut key = [0u8; 32];
+    key[0] = id;
+    NodeIdentity::from_single_key(key)
+}
+
+/// A coordinator plus the bookkeeping the server keeps around it
+struct Sim {
+    coordinator: Coordinator,
+    pending: Vec<NodeIdentity>,
+    now: u64,
+}
+
+impl Sim {
+    fn new(config: Config) -> Self {
+        let mut coordinator = Coordinator::zeroed();
+        coordinator.config.warmup_time = 5;
+        coordinator.config.cooldown_time = 5;
+        coordinator.config.max_round_train_time = 3;
+        coordinator.config.round_witness_time = 2;
+        coordinator.config.epoch_time = 60;
+        coordinator.config.total_steps = 1000;
+        coordinator.config.init_min_clients = config.init_min_clients;
+        coordinator.config.min_clients = config.min_clients;
+        coordinator.config.witness_nodes = config.witness_nodes;
+        coordinator.config.global_batch_size_start = 4;
+        coordinator.config.global_batch_size_end = 4;
+        coordinator.config.waiting_for_members_extra_time = 1;
+        coordinator.config.check_error().unwrap();
+        coordinator.run_state = RunState::WaitingForMembers;
+        Self {
+            coordinator,
+            pending: Vec::new(),
+            now: 1,
+        }
+    }
+
+    fn apply(&mut self, action: &Action) {
+        match *action {
+            Action::Join(id) => {
+                let id = node(id);
+                if !self.pending.contains(&id) {
+                    self.pending.push(id);
+                }
+            }
+            Action::Leave(id) => {
+                let id = node(id);
+                self.pending.retain(|pending| *pending != id);
+                let index = self
+                    .coordinator
+                    .epoch_state
+                    .clients
+                    .iter()
+                    .position(|client| client.id == id);
+                if let Some(index) = index {
+                    let _ = self.coordinator.withdraw(index as u64);
+                }
+            }
+            Action::Tick { seconds, seed } => {
+                self.now += seconds;
+                let _ = self
+                    .coordinator
+                    .tick(Some(self.pending.iter()), self.now, seed);
+            }
+            Action::WitnessAll => self.witness_some(usize::MAX),
+        }
+    }
+
+    /// Has up to `limit` of the clients that are allowed to witness the current round do so,
+    /// in client order, stopping early if that moves the coordinator to another state
+    fn witness_some(&mut self, limit: usize) {
+        let state = self.coordinator.run_state;
+        let clients: Vec<NodeIdentity> = self
+            .coordinator
+            .epoch_state
+            .clients
+            .iter()
+            .map(|client| client.id)
+            .collect();
+        let mut witnessed = 0;
+        for (index, id) in clients.iter().enumerate() {
+            if self.coordinator.run_state != state || witnessed == limit {
+                return;
+            }
+            match state {
+                RunState::Warmup => {
+                    let mut witness = Witness::default();
+                    witness.proof.index = index as u64;
+                    let _ = self
+                        .coordinator
+                        .warmup_witness(id, witness, self.now, index as u64);
+                    witnessed += 1;
+                }
+                RunState::RoundTrain | RunState::RoundWitness => {
+                    let Ok(selection) = CommitteeSelection::from_coordinator(&self.coordinator, 0)
+                    else {
+                        return;
+                    };
+                    let proof = selection.get_witness(index as u64);
+                    if proof.witness.is_true() {
+                        let witness = Witness {
+                            proof,
+                            ..Default::default()
+                        };
+                        let _ = self.coordinator.witness(id, witness, self.now);
+                        witnessed += 1;
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+}
+
+/// Whether the coordinator may go from `from` to `to` in a single call
+fn valid_transition(from: RunState, to: RunState) -> bool {
+    use RunState::*;
+    from == to
+        || matches!(
+            (from, to),
+            (WaitingForMembers, Warmup)
+                | (Warmup, RoundTrain)
+                // warmup ends early if clients drop below the minimum,
+                // possibly passing through RoundTrain in the same tick
+                | (Warmup, WaitingForMembers)
+                | (Warmup, Finished)
+                | (RoundTrain, RoundWitness)
+                | (RoundWitness, RoundTrain)
+                | (RoundWitness, Cooldown)
+                | (Cooldown, WaitingForMembers)
+                | (Cooldown, Finished)
+        )
+}
+
+/// Checks what must hold between any two actions, whatever state the coordinator is in
+fn check_invariants(coordinator: &Coordinator) -> Result<(), TestCaseError> {
+    let clients = &coordinator.epoch_state.clients;
+    let ids: HashSet<_> = clients.iter().map(|client| client.id).collect();
+    prop_assert_eq!(ids.len(), clients.len(), "duplicate clients in the epoch");
+    prop_assert!(
+        coordinator
+            .epoch_state
+            .exited_clients
+            .iter()
+            .all(|client| client.state != ClientState::Healthy),
+        "healthy client among the exited ones"
+    );
+
+    if matches!(
+        coordinator.run_state,
+        RunState::RoundTrain | RunState::RoundWitness
+    ) {
+        let round = coordinator.current_round().unwrap();
+        // clients only leave between rounds
+        prop_assert_eq!(round.clients_len as usize, clients.len());
+        prop_assert!(
+            clients.len() >= coordinator.config.min_clients as usize,
+            "round running with {} clients, below min_clients",
+            clients.len()
+        );
+        if let Some(previous) = coordinator.previous_round() {
+            if coordinator.epoch_state.first_round.is_false() {
+                prop_assert_eq!(previous.height + 1, round.height);
+                prop_assert!(previous.data_index < round.data_index);
+            }
+        }
+
+        let indices: HashSet<_> = round
+            .witnesses
+            .iter()
+            .map(|witness| witness.proof.index)
+            .collect();
+        prop_assert_eq!(
+            indices.len(),
+            round.witnesses.len(),
+            "client witnessed twice"
+        );
+        prop_assert!(
+            indices
+                .iter()
+                .all(|&index| (index as usize) < clients.len())
+        );
+        prop_assert!(
+            round.witnesses.len() <= coordinator.num_witness_nodes(),
+            "{} witnesses for a round that waits for {}",
+            round.witnesses.len(),
+            coordinator.num_witness_nodes()
+        );
+    }
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(256))]
+
+    #[test]
+    fn test_coordinator_invariants(
+        config in config(),
+        actions in prop::collection::vec(action(), 1..300),
+    ) {
+        let mut sim = Sim::new(config);
+        for action in &actions {
+            let before = sim.coordinator.run_state;
+            let step_before = sim.coordinator.progress.step;
+            sim.apply(action);
+            let after = sim.coordinator.run_state;
+
+            prop_assert!(
+                valid_transition(before, after),
+                "invalid transition {before:?} -> {after:?} on {action:?}"
+            );
+            prop_assert!(sim.coordinator.progress.step >= step_before);
+            check_invariants(&sim.coordinator)?;
+        }
+    }
+
+    #[test]
+    fn test_round_completes_with_min_clients(
+        config in config(),
+        extra_clients in 0..4u8,
+        seed in any::<u64>(),
+    ) {
+        let mut sim = Sim::new(config);
+        for id in 0..config.init_min_clients as u8 + extra_clients {
+            sim.apply(&Action::Join(id));
+        }
+
+        let start_step = sim.coordinator.progress.step;
+        for _ in 0..100 {
+            sim.apply(&Action::Tick { seconds: 1, seed });
+            sim.apply(&Action::WitnessAll);
+            if sim.coordinator.progress.step > start_step {
+                break;
+            }
+        }
+        prop_assert!(
+            sim.coordinator.progress.step > start_step,
+            "no round completed, stuck in {:?}",
+            sim.coordinator.run_state
+        );
+    }
+}
+
+/// A run where all `num_clients` clients are witnesses, ticked into its first training round
+fn sim_in_round_train(num_clients: u8) -> Sim {
+    let num_clients_u16 = num_clients as u16;
+    let mut sim = Sim::new(Config {
+        init_min_clients: num_clients_u16,
+        min_clients: num_clients_u16,
+        witness_nodes: num_clients_u16,
+    });
+    sim.coordinator.config.max_round_train_time = 20;
+    for id in 0..num_clients {
+        sim.apply(&Action::Join(id));
+    }
+    for _ in 0..10 {
+        sim.apply(&Action::Tick {
+            seconds: 1,
+            seed: 0,
+        });
+        sim.apply(&Action::WitnessAll);
+        if sim.coordinator.run_state == RunState::RoundTrain {
+            break;
+        }
+    }
+    assert_eq!(sim.coordinator.run_state, RunState::RoundTrain);
+    sim
+}
+
+#[test]
+fn test_round_moves_on_without_stragglers_after_grace_period() {
+    let mut sim = sim_in_round_train(4);
+    sim.coordinator.config.straggler_fraction = 0.25;
+    sim.coordinator.config.straggler_grace_period_secs = 2;
+
+    sim.witness_some(3);
+    assert_eq!(sim.coordinator.run_state, RunState::RoundTrain);
+    sim.apply(&Action::Tick {
+        seconds: 1,
+        seed: 0,
+    });
+    assert_eq!(sim.coordinator.run_state, RunState::RoundTrain);
+    sim.apply(&Action::Tick {
+        seconds: 1,
+        seed: 0,
+    });
+    assert_eq!(sim.coordinator.run_state, RunState::RoundWitness);
+
+    let straggler = sim.coordinator.epoch_state.clients[3].id;
+    assert_eq!(
+        &sim.coordinator.epoch_state.excluded_stragglers[..],
+        &[straggler]
+    );
+}
+
+#[test]
+fn test_grace_period_waits_for_straggler_threshold() {
+    let mut sim = sim_in_round_train(4);
+    sim.coordinator.config.straggler_fraction = 0.25;
+    sim.coordinator.config.straggler_grace_period_secs = 2;
+
+    sim.witness_some(2);
+    for _ in 0..5 {
+        sim.apply(&Action::Tick {
+            seconds: 1,
+            seed: 0,
+        });
+    }
+    assert_eq!(sim.coordinator.run_state, RunState::RoundTrain);
+    assert!(sim.coordinator.epoch_state.excluded_stragglers.is_empty());
+}
+
+#[test]
+fn test_no_grace_period_without_straggler_fraction() {
+    let mut sim = sim_in_round_train(4);
+    sim.coordinator.config.straggler_grace_period_secs = 2;
+
+    sim.witness_some(3);
+    for _ in 0..5 {
+        sim.apply(&Action::Tick {
+            seconds: 1,
+            seed: 0,
+        });
+    }
+    assert_eq!(sim.coordinator.run_state, RunState::RoundTrain);
+    assert!(sim.coordinator.epoch_state.excluded_stragglers.is_empty());
+}
+
+fn coordinator_with_witness_nodes(witness_nodes: u16) -> Coordinator {
+    let mut coordinator = Coordinator::zeroed();
+    coordinator.config.witness_nodes = witness_nodes;
+    coordinator.config.adaptive_witness_quorum = true;
+    coordinator
+}
+
+#[test]
+fn test_adaptive_witness_quorum_hysteresis() {
+    let mut coordinator = coordinator_with_witness_nodes(4);
+    let full_quorum = coordinator.witness_quorum(4);
+
+    // 16/20 rounds is exactly 80%, which is not below the threshold
+    for _ in 0..16 {
+        coordinator.record_witness_quorum_met(true);
+    }
+    for _ in 0..4 {
+        coordinator.record_witness_quorum_met(false);
+    }
+    assert_eq!(coordinator.witness_success_rate(), Some(0.8));
+    assert_eq!(coordinator.witness_quorum(4), full_quorum);
+
+    // 15/20 rounds drops below 80%
+    coordinator.record_witness_quorum_met(false);
+    assert_eq!(coordinator.witness_success_rate(), Some(0.75));
+    assert_eq!(coordinator.witness_quorum(4), full_quorum - 1);
+
+    // 19/20 rounds is exactly 95%, which is not above the threshold
+    for _ in 0..19 {
+        coordinator.record_witness_quorum_met(true);
+    }
+    assert_eq!(coordinator.witness_success_rate(), Some(0.95));
+    assert_eq!(coordinator.witness_quorum(4), full_quorum - 1);
+
+    coordinator.record_witness_quorum_met(true);
+    assert_eq!(coordinator.witness_success_rate(), Some(1.0));
+    assert_eq!(coordinator.witness_quorum(4), full_quorum);
+}
+
+#[test]
+fn test_adaptive_witness_quorum_needs_full_history() {
+    let mut coordinator = coordinator_with_witness_nodes(4);
+    let full_quorum = coordinator.witness_quorum(4);
+    for _ in 0..19 {
+        coordinator.record_witness_quorum_met(false);
+    }
+    assert_eq!(coordinator.witness_success_rate(), None);
+    assert_eq!(coordinator.witness_quorum(4), full_quorum);
+}
+
+#[test]
+fn test_adaptive_witness_quorum_disabled() {
+    let mut coordinator = coordinator_with_witness_nodes(4);
+    coordinator.config.adaptive_witness_quorum = false;
+    let full_quorum = coordinator.witness_quorum(4);
+    for _ in 0..20 {
+        coordinator.record_witness_quorum_met(false);
+    }
+    assert_eq!(coordinator.witness_success_rate(), Some(0.0));
+    assert_eq!(coordinator.witness_quorum(4), full_quorum);
+}
+
+#[test]
+fn test_adaptive_witness_quorum_never_below_one() {
+    let mut coordinator = coordinator_with_witness_nodes(1);
+    for _ in 0..20 {
+        coordinator.record_witness_quorum_met(false);
+    }
+    assert_eq!(coordinator.witness_quorum(1), 1);
+}
+
+#[test]
+fn test_distro_extensions_are_checked_and_immutable() {
+    let sim = Sim::new(Config {
+        init_min_clients: 1,
+        min_clients: 1,
+        witness_nodes: 1,
+    });
+    let mut coordinator = sim.coordinator;
+
+    let mut config = coordinator.config;
+    config.distro_extensions.topk_retain_fraction = 1.5;
+    assert!(matches!(
+        config.check_error(),
+        Err(ConfigError::DistroExtensions)
+    ));
+    config.distro_extensions.topk_retain_fraction = 0.9;
+    config.distro_extensions.max_delta_norm = -1.0;
+    assert!(matches!(
+        config.check_error(),
+        Err(ConfigError::DistroExtensions)
+    ));
+    config.distro_extensions.max_delta_norm = 1.0;
+    config
+        .distro_extensions
+        .layer_topk
+        .push(LayerTopK {
+            pattern: FixedString::try_from("*embed*").unwrap(),
+            topk: 0,
+        })
+        .unwrap();
+    assert!(matches!(
+        config.check_error(),
+        Err(ConfigError::DistroExtensions)
+    ));
+    config.distro_extensions.layer_topk[0].topk = 32;
+    config.check_error().unwrap();
+
+    let err = coordinator.update_mutable_config(config).unwrap_err();
+    assert_eq!(err.field, "distro_extensions");
+}
+
+#[test]
+fn test_update_mutable_config() {
+    let mut sim = sim_in_round_train(2);
+    let original = sim.coordinator.config;
+
+    let mut config = original;
+    config.total_steps += 1;
+    let err = sim.coordinator.update_mutable_config(config).unwrap_err();
+    assert_eq!(err.field, "total_steps");
+
+    let mut config = original;
+    config.global_batch_size_end += 1;
+    let err = sim.coordinator.update_mutable_config(config).unwrap_err();
+    assert_eq!(err.field, "global_batch_size");
+    assert_eq!(sim.coordinator.config, original);
+
+    let mut config = original;
+    config.max_round_train_time = 30;
+    config.cooldown_time = 7;
+    assert_eq!(
+        sim.coordinator.update_mutable_config(config),
+        Ok(ConfigUpdate::Applied)
+    );
+    assert_eq!(sim.coordinator.config, config);
+}
+
+#[test]
+fn test_witness_nodes_and_min_clients_change_between_epochs() {
+    let mut sim = sim_in_round_train(2);
+    let mut config = sim.coordinator.config;
+    config.witness_nodes = 1;
+    config.min_clients = 1;
+    config.cooldown_time = 7;
+
+    assert_eq!(
+        sim.coordinator.update_mutable_config(config),
+        Ok(ConfigUpdate::Deferred)
+    );
+    assert_eq!(sim.coordinator.config.cooldown_time, 7);
+    assert_eq!(sim.coordinator.config.witness_nodes, 2);
+    assert_eq!(sim.coordinator.config.min_clients, 2);
+
+    // the old min_clients still ends the epoch when a client leaves
+    sim.apply(&Action::Leave(1));
+    for _ in 0..30 {
+        if !sim.coordinator.active() {
+            break;
+        }
+        assert_eq!(
+            sim.coordinator.update_mutable_config(config),
+            Ok(ConfigUpdate::Deferred)
+        );
+        sim.apply(&Action::Tick {
+            seconds: 1,
+            seed: 0,
+        });
+        sim.apply(&Action::WitnessAll);
+    }
+    assert_eq!(sim.coordinator.run_state, RunState::WaitingForMembers);
+
+    assert_eq!(
+        sim.coordinator.update_mutable_config(config),
+        Ok(ConfigUpdate::Applied)
+    );
+    assert_eq!(sim.coordinator.config, config);
+}
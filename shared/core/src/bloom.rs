@@ -310,6 +310,26 @@ impl<const U: usize, const K: usize> Bloom<U, K> {
         }
         true
     }
+
+    /// Fraction of the filter's bits that are set, from 0 (empty) to 1 (saturated).
+    pub fn occupancy(&self) -> f64 {
+        self.bits.0.count_ones() as f64 / self.bits.0.len() as f64
+    }
+
+    /// Estimate of how many distinct items have been added, from how many bits are set
+    /// (Swamidass & Baldi, 2007). A saturated filter gives the largest estimate the bits allow.
+    pub fn estimated_count(&self) -> usize {
+        let m = self.bits.0.len() as f64;
+        let x = (self.bits.0.count_ones() as f64).min(m - 1.0);
+        (-(m / K as f64) * (1.0 - x / m).ln()).round() as usize
+    }
+
+    /// How many items the filter can hold before its false positive rate goes over `false_rate`.
+    pub fn capacity(false_rate: f64) -> usize {
+        // solves false_rate = (1 - e^(-K * n / m)) ^ K for n
+        let m = Self::max_bits() as f64;
+        (-(m / K as f64) * (1.0 - false_rate.powf(1.0 / K as f64)).ln()).floor() as usize
+    }
 }
 
 fn slice_hash(slice: &[u8], hash_index: u64) -> u64 {
@@ -348,6 +368,29 @@ mod tests {
         assert!(!bloom.contains(&item2));
     }
 
+    #[test]
+    fn test_bloom_occupancy() {
+        let mut bloom = Bloom::<8, 2>::new(100, &[1, 2]);
+        assert_eq!(bloom.occupancy(), 0.0);
+
+        bloom.add(&vec![1, 2, 3]);
+        let occupancy = bloom.occupancy();
+        assert!(occupancy > 0.0 && occupancy <= 2.0 / Bloom::<8, 2>::max_bits() as f64);
+    }
+
+    #[test]
+    fn test_bloom_estimated_count() {
+        let mut bloom = Bloom::<16, 8>::new(100, &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(bloom.estimated_count(), 0);
+
+        for i in 0u32..50 {
+            bloom.add(&i.to_le_bytes());
+        }
+        let estimate = bloom.estimated_count();
+        assert!((40..=60).contains(&estimate), "estimated {estimate} of 50");
+        assert!(estimate < Bloom::<16, 8>::capacity(0.01));
+    }
+
     #[test]
     fn test_bloom_clear() {
         let mut bloom = Bloom::<8, 2>::new(100, &[1, 2]);
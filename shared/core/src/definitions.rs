@@ -1,3 +1,4 @@
+use crate::{FixedString, FixedVec};
 use anchor_lang::{AnchorDeserialize, AnchorSerialize, InitSpace, prelude::borsh};
 use bytemuck::Zeroable;
 use serde::{Deserialize, Serialize};
@@ -273,6 +274,72 @@ impl LearningRateScheduler for WarmupStableDecayLR {
     }
 }
 
+/// SGDR: cosine annealing from `initial_lr` down to `eta_min` over `t_0` steps, then restarting
+/// at `initial_lr`, with each period `t_mult` times longer than the one before.
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    InitSpace,
+    Serialize,
+    Deserialize,
+    Clone,
+    Debug,
+    Zeroable,
+    Copy,
+    PartialEq,
+    TS,
+)]
+#[repr(C)]
+pub struct CosineAnnealingWarmRestartsLR {
+    initial_lr: f64,
+    eta_min: f64,
+    t_0: u32,
+    t_mult: u32,
+}
+
+impl CosineAnnealingWarmRestartsLR {
+    pub fn new(initial_lr: f64, t_0: u32, t_mult: u32, eta_min: f64) -> Self {
+        CosineAnnealingWarmRestartsLR {
+            initial_lr,
+            eta_min,
+            t_0,
+            t_mult,
+        }
+    }
+
+    pub fn get_warmup_steps(&self) -> u32 {
+        0
+    }
+
+    pub fn get_warmup_init_lr(&self) -> f64 {
+        self.initial_lr
+    }
+
+    // (steps into the current period, length of the current period)
+    fn period_position(&self, step: u32) -> (u64, u64) {
+        let t_0 = self.t_0.max(1) as u64;
+        let step = step as u64;
+        if self.t_mult <= 1 {
+            return (step % t_0, t_0);
+        }
+        let mut t_cur = step;
+        let mut t_i = t_0;
+        while t_cur >= t_i {
+            t_cur -= t_i;
+            t_i *= self.t_mult as u64;
+        }
+        (t_cur, t_i)
+    }
+}
+
+impl LearningRateScheduler for CosineAnnealingWarmRestartsLR {
+    fn get_lr(&self, step: u32) -> f64 {
+        let (t_cur, t_i) = self.period_position(step);
+        let cosine_decay = 0.5 * (1.0 + (PI * t_cur as f64 / t_i as f64).cos());
+        self.eta_min + (self.initial_lr - self.eta_min) * cosine_decay
+    }
+}
+
 #[derive(
     AnchorSerialize,
     AnchorDeserialize,
@@ -291,6 +358,7 @@ pub enum LearningRateSchedule {
     Linear(LinearLR),
     Cosine(CosineLR),
     WarmupStableDecay(WarmupStableDecayLR),
+    CosineAnnealingWarmRestarts(CosineAnnealingWarmRestartsLR),
 }
 
 impl LearningRateSchedule {
@@ -300,6 +368,7 @@ impl LearningRateSchedule {
             Self::Linear(l) => l.get_lr(step),
             Self::Cosine(l) => l.get_lr(step),
             Self::WarmupStableDecay(l) => l.get_lr(step),
+            Self::CosineAnnealingWarmRestarts(l) => l.get_lr(step),
         }
     }
 
@@ -309,6 +378,7 @@ impl LearningRateSchedule {
             Self::Linear(l) => l.get_warmup_steps(),
             Self::Cosine(l) => l.get_warmup_steps(),
             Self::WarmupStableDecay(l) => l.get_warmup_steps(),
+            Self::CosineAnnealingWarmRestarts(l) => l.get_warmup_steps(),
         }
     }
 
@@ -318,6 +388,7 @@ impl LearningRateSchedule {
             Self::Linear(l) => l.get_warmup_init_lr(),
             Self::Cosine(l) => l.get_warmup_init_lr(),
             Self::WarmupStableDecay(l) => l.get_warmup_init_lr(),
+            Self::CosineAnnealingWarmRestarts(l) => l.get_warmup_init_lr(),
         }
     }
 }
@@ -340,6 +411,12 @@ impl From<ConstantLR> for LearningRateSchedule {
     }
 }
 
+impl From<CosineAnnealingWarmRestartsLR> for LearningRateSchedule {
+    fn from(value: CosineAnnealingWarmRestartsLR) -> Self {
+        Self::CosineAnnealingWarmRestarts(value)
+    }
+}
+
 #[derive(
     AnchorSerialize,
     AnchorDeserialize,
@@ -371,6 +448,73 @@ pub enum OptimizerDefinition {
     },
 }
 
+/// Number of per-layer top-k overrides a run can configure in [`DistroExtensions::layer_topk`].
+pub const MAX_LAYER_TOPK_OVERRIDES: usize = 4;
+
+/// Top-k for every parameter whose name matches the glob `pattern`, e.g. `"*.embed*"`.
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Zeroable,
+    PartialEq,
+    TS,
+)]
+#[repr(C)]
+pub struct LayerTopK {
+    pub pattern: FixedString<32>,
+    pub topk: u16,
+}
+
+/// DisTrO settings added after [`OptimizerDefinition::Distro`], which can't grow without changing
+/// the on-chain layout of every existing run. All zeroes, the default, is DisTrO as it was before.
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Zeroable,
+    PartialEq,
+    TS,
+)]
+#[repr(C)]
+pub struct DistroExtensions {
+    /// If nonzero, each tensor keeps the fewest of its largest DCT values that hold this fraction
+    /// of its energy, instead of `compression_topk` of them.
+    #[serde(default)]
+    pub topk_retain_fraction: f32,
+    /// If nonzero, the delta across all layers is scaled down to at most this L2 norm before
+    /// it's compressed.
+    #[serde(default)]
+    pub max_delta_norm: f32,
+    /// Overrides of `compression_topk` by parameter name. The first matching pattern wins.
+    #[serde(default)]
+    pub layer_topk: FixedVec<LayerTopK, MAX_LAYER_TOPK_OVERRIDES>,
+    /// Apply the majority sign of every client's update instead of their average.
+    #[serde(default)]
+    pub sign_sgd: bool,
+}
+
+impl DistroExtensions {
+    pub fn check(&self) -> bool {
+        (0.0..=1.0).contains(&self.topk_retain_fraction)
+            && self.max_delta_norm >= 0.0
+            && self
+                .layer_topk
+                .iter()
+                .all(|layer| !layer.pattern.is_empty() && layer.topk > 0)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -460,6 +604,47 @@ mod tests {
         assert_relative_eq!(scheduler.get_lr(250), 0.0);
     }
 
+    #[test]
+    fn test_cosine_annealing_warm_restarts_lr() {
+        let scheduler = CosineAnnealingWarmRestartsLR::new(0.01, 10, 2, 0.001);
+
+        // first period, 10 steps
+        assert_relative_eq!(scheduler.get_lr(0), 0.01);
+        assert_relative_eq!(scheduler.get_lr(5), 0.001 + (0.01 - 0.001) * 0.5);
+
+        // restart, second period is 20 steps
+        assert_relative_eq!(scheduler.get_lr(10), 0.01);
+        assert_relative_eq!(scheduler.get_lr(20), 0.001 + (0.01 - 0.001) * 0.5);
+
+        // restart, third period is 40 steps
+        assert_relative_eq!(scheduler.get_lr(30), 0.01);
+        assert_relative_eq!(scheduler.get_lr(50), 0.001 + (0.01 - 0.001) * 0.5);
+
+        // without a multiplier every period is the same length
+        let scheduler = CosineAnnealingWarmRestartsLR::new(0.01, 10, 1, 0.0);
+        assert_relative_eq!(scheduler.get_lr(25), 0.005);
+        assert_relative_eq!(scheduler.get_lr(1_000_000_000), 0.01);
+    }
+
+    #[test]
+    fn test_cosine_annealing_warm_restarts_from_toml() {
+        let schedule: LearningRateSchedule = toml::from_str(
+            r#"
+            [CosineAnnealingWarmRestarts]
+            initial_lr = 4.0e-4
+            t_0 = 1000
+            t_mult = 2
+            eta_min = 4.0e-5
+            "#,
+        )
+        .unwrap();
+        assert!(matches!(
+            schedule,
+            LearningRateSchedule::CosineAnnealingWarmRestarts(lr)
+                if lr == CosineAnnealingWarmRestartsLR::new(4.0e-4, 1000, 2, 4.0e-5)
+        ));
+    }
+
     #[test]
     fn test_edge_cases() {
         // zero warmup steps
@@ -31,7 +31,8 @@ pub use boxed_future::BoxedFuture;
 pub use cancellable_barrier::{Barrier, CancellableBarrier, CancelledBarrier};
 pub use data_shuffle::Shuffle;
 pub use definitions::{
-    ConstantLR, CosineLR, LearningRateSchedule, LearningRateScheduler, LinearLR,
+    ConstantLR, CosineAnnealingWarmRestartsLR, CosineLR, DistroExtensions, LayerTopK,
+    LearningRateSchedule, LearningRateScheduler, LinearLR, MAX_LAYER_TOPK_OVERRIDES,
     OptimizerDefinition,
 };
 pub use deterministic_shuffle::deterministic_shuffle;
@@ -25,4 +25,21 @@ impl TokenizedDataProvider for DataProvider {
             DataProvider::Preprocessed(provider) => provider.get_samples(data_ids).await,
         }
     }
+
+    fn cache_hit_ratio(&self) -> Option<f64> {
+        match self {
+            DataProvider::Local(provider) => provider.cache_hit_ratio(),
+            _ => None,
+        }
+    }
+}
+
+impl DataProvider {
+    /// See [`WeightedDataProvider::set_step`]. Providers without a curriculum ignore this.
+    pub fn set_step(&mut self, step: u32, data_index_for_step: impl Fn(u32) -> u64) -> bool {
+        match self {
+            DataProvider::WeightedHttp(provider) => provider.set_step(step, data_index_for_step),
+            _ => false,
+        }
+    }
 }
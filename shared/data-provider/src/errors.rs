@@ -39,6 +39,16 @@ pub enum DownloadError {
     #[error("failed to connect to HF hub: {0}")]
     HfHub(#[from] hf_hub::api::tokio::ApiError),
 
+    #[error("failed to connect to HF hub: {0}")]
+    HfHubSync(#[from] hf_hub::api::sync::ApiError),
+
+    #[error("checksum mismatch for {filename}: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        filename: String,
+        expected: String,
+        actual: String,
+    },
+
     #[error("GCS authentication failed: {0}")]
     GcsAuth(#[from] google_cloud_storage::client::google_cloud_auth::error::Error),
 
@@ -1,2 +1,4 @@
 pub const DATA_FILE_EXTENSIONS: [&str; 3] = ["npy", "bin", "ds"];
 pub const PARQUET_EXTENSION: &str = "parquet";
+pub const TEXT_FILE_EXTENSIONS: [&str; 2] = ["txt", "jsonl"];
+pub const TOKENIZED_JSONL_EXTENSIONS: [&str; 2] = ["jsonl", "jsonl.gz"];
@@ -1,4 +1,4 @@
-use crate::errors::UploadError;
+use crate::errors::{DownloadError, UploadError};
 use crate::hub::model::HubRepo;
 use hf_hub::{
     Cache, Repo, RepoType,
@@ -9,12 +9,35 @@ use hf_hub::{
 };
 use psyche_coordinator::model;
 use psyche_core::FixedString;
-use std::{path::PathBuf, time::Instant};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Instant,
+};
 use tokio::sync::mpsc;
-use tracing::{error, info};
+use tracing::{debug, error, info};
 
 const MODEL_EXTENSIONS: [&str; 3] = [".safetensors", ".json", ".py"];
 const DATASET_EXTENSIONS: [&str; 1] = [".parquet"];
+const DATASET_INFOS_FILENAME: &str = "dataset_infos.json";
+
+#[derive(Debug, Clone)]
+pub struct DownloadConfig {
+    /// Check downloaded files against the SHA256 checksums in the repo's `dataset_infos.json`, if it has one.
+    pub verify_checksums: bool,
+    /// Only download this subset, i.e. the files under `<subset>/` in the parquet converter layout.
+    pub subset: Option<String>,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self {
+            verify_checksums: true,
+            subset: None,
+        }
+    }
+}
 
 /// Strip leading/trailing whitespace and control characters from a repo identifier.
 /// TODO: Remove once https://github.com/PsycheFoundation/nousnet/pull/636 is merged
@@ -37,34 +60,38 @@ fn check_extensions(sibling: &Siblings, extensions: &[&'static str]) -> bool {
     }
 }
 
-async fn download_repo_async(
+fn async_api_repo(
     repo: Repo,
     cache: Option<PathBuf>,
     token: Option<String>,
-    max_concurrent_downloads: Option<usize>,
     progress_bar: bool,
-    extensions: &[&'static str],
-) -> Result<Vec<PathBuf>, ApiError> {
+) -> Result<hf_hub::api::tokio::ApiRepo, ApiError> {
     let builder = hf_hub::api::tokio::ApiBuilder::new();
     let cache = match cache {
         Some(cache) => Cache::new(cache),
         None => Cache::default(),
     };
-    let api = builder
+    Ok(builder
         .with_cache_dir(cache.path().clone())
         .with_token(token.or(cache.token()))
         .with_progress(progress_bar)
         .build()?
-        .repo(repo);
-    let siblings = api
-        .info()
-        .await?
-        .siblings
-        .into_iter()
+        .repo(repo))
+}
+
+/// Downloads every file in the repo with one of `extensions`, returning their names in the repo and local paths.
+async fn download_files_async(
+    api: &hf_hub::api::tokio::ApiRepo,
+    siblings: &[Siblings],
+    max_concurrent_downloads: Option<usize>,
+    extensions: &[&'static str],
+) -> Result<Vec<(String, PathBuf)>, ApiError> {
+    let siblings = siblings
+        .iter()
         .filter(|x| check_extensions(x, extensions))
         .collect::<Vec<_>>();
-    let mut ret: Vec<PathBuf> = Vec::new();
-    for chunk in siblings.chunks(max_concurrent_downloads.unwrap_or(siblings.len())) {
+    let mut ret = Vec::new();
+    for chunk in siblings.chunks(max_concurrent_downloads.unwrap_or(siblings.len()).max(1)) {
         let futures = chunk
             .iter()
             .map(|x| async {
@@ -79,7 +106,7 @@ async fn download_repo_async(
                         "Finished downloading file from hub"
                     );
                 }
-                res
+                res.map(|path| (x.rfilename.clone(), path))
             })
             .collect::<Vec<_>>();
         for future in futures {
@@ -89,6 +116,25 @@ async fn download_repo_async(
     Ok(ret)
 }
 
+async fn download_repo_async(
+    repo: Repo,
+    cache: Option<PathBuf>,
+    token: Option<String>,
+    max_concurrent_downloads: Option<usize>,
+    progress_bar: bool,
+    extensions: &[&'static str],
+) -> Result<Vec<PathBuf>, ApiError> {
+    let api = async_api_repo(repo, cache, token, progress_bar)?;
+    let siblings = api.info().await?.siblings;
+    Ok(
+        download_files_async(&api, &siblings, max_concurrent_downloads, extensions)
+            .await?
+            .into_iter()
+            .map(|(_, path)| path)
+            .collect(),
+    )
+}
+
 pub async fn download_model_repo_async(
     repo_id: &str,
     revision: Option<String>,
@@ -119,49 +165,182 @@ pub async fn download_dataset_repo_async(
     token: Option<String>,
     max_concurrent_downloads: Option<usize>,
     progress_bar: bool,
-) -> Result<Vec<PathBuf>, ApiError> {
+    config: DownloadConfig,
+) -> Result<Vec<PathBuf>, DownloadError> {
     let repo_id = sanitize_repo_id(&repo_id);
-    download_repo_async(
+    let api = async_api_repo(
         match revision {
             Some(revision) => Repo::with_revision(repo_id.clone(), RepoType::Dataset, revision),
-            None => Repo::new(repo_id, RepoType::Dataset),
+            None => Repo::new(repo_id.clone(), RepoType::Dataset),
         },
         cache,
         token,
-        max_concurrent_downloads,
         progress_bar,
+    )?;
+    let mut siblings = api.info().await?.siblings;
+    retain_subset(&mut siblings, config.subset.as_deref());
+    let files = download_files_async(
+        &api,
+        &siblings,
+        max_concurrent_downloads,
         &DATASET_EXTENSIONS,
     )
-    .await
+    .await?;
+
+    if config.verify_checksums {
+        if has_dataset_infos(&siblings) {
+            let dataset_infos = api.get(DATASET_INFOS_FILENAME).await?;
+            let files = files.clone();
+            // hashing large files would block the runtime
+            tokio::task::spawn_blocking(move || verify_dataset_checksums(&dataset_infos, &files))
+                .await
+                .map_err(std::io::Error::other)??;
+        } else {
+            debug!(
+                repo_id,
+                "Dataset has no {DATASET_INFOS_FILENAME}, skipping checksum verification"
+            );
+        }
+    }
+
+    Ok(files.into_iter().map(|(_, path)| path).collect())
 }
 
-fn download_repo_sync(
+fn sync_api_repo(
     repo: Repo,
     cache: Option<PathBuf>,
     token: Option<String>,
     progress_bar: bool,
-    extensions: &[&'static str],
-) -> Result<Vec<PathBuf>, hf_hub::api::sync::ApiError> {
+) -> Result<hf_hub::api::sync::ApiRepo, hf_hub::api::sync::ApiError> {
     let builder = hf_hub::api::sync::ApiBuilder::new();
     let cache = match cache {
         Some(cache) => Cache::new(cache),
         None => Cache::default(),
     };
-    let api = builder
+    Ok(builder
         .with_cache_dir(cache.path().clone())
         .with_token(token.or(cache.token()))
         .with_progress(progress_bar)
         .build()?
-        .repo(repo);
-    let res: Result<Vec<PathBuf>, _> = api
-        .info()?
-        .siblings
-        .into_iter()
+        .repo(repo))
+}
+
+/// Downloads every file in the repo with one of `extensions`, returning their names in the repo and local paths.
+fn download_files_sync(
+    api: &hf_hub::api::sync::ApiRepo,
+    siblings: &[Siblings],
+    extensions: &[&'static str],
+) -> Result<Vec<(String, PathBuf)>, hf_hub::api::sync::ApiError> {
+    siblings
+        .iter()
         .filter(|x| check_extensions(x, extensions))
-        .map(|x| api.get(&x.rfilename))
-        .collect();
+        .map(|x| Ok((x.rfilename.clone(), api.get(&x.rfilename)?)))
+        .collect()
+}
+
+fn download_repo_sync(
+    repo: Repo,
+    cache: Option<PathBuf>,
+    token: Option<String>,
+    progress_bar: bool,
+    extensions: &[&'static str],
+) -> Result<Vec<PathBuf>, hf_hub::api::sync::ApiError> {
+    let api = sync_api_repo(repo, cache, token, progress_bar)?;
+    let siblings = api.info()?.siblings;
+    Ok(download_files_sync(&api, &siblings, extensions)?
+        .into_iter()
+        .map(|(_, path)| path)
+        .collect())
+}
+
+/// Only keeps the files of `subset`, and the repo's `dataset_infos.json`
+fn retain_subset(siblings: &mut Vec<Siblings>, subset: Option<&str>) {
+    if let Some(subset) = subset {
+        let prefix = format!("{subset}/");
+        siblings
+            .retain(|x| x.rfilename.starts_with(&prefix) || x.rfilename == DATASET_INFOS_FILENAME);
+    }
+}
+
+fn has_dataset_infos(siblings: &[Siblings]) -> bool {
+    siblings
+        .iter()
+        .any(|x| x.rfilename == DATASET_INFOS_FILENAME)
+}
+
+/// The path within the repo of a file listed in `dataset_infos.json`'s `download_checksums`,
+/// which are either hub URLs like `https://huggingface.co/datasets/<repo>/resolve/<revision>/<path>`
+/// or paths relative to the repo.
+fn repo_path_of_checksum_key(key: &str) -> &str {
+    match key.split_once("/resolve/") {
+        Some((_, rest)) => {
+            // skip the revision, which may be an unescaped `refs/<kind>/<name>`
+            let revision_components = if rest.starts_with("refs/") { 3 } else { 1 };
+            rest.splitn(revision_components + 1, '/')
+                .nth(revision_components)
+                .unwrap_or(rest)
+        }
+        None => key.trim_start_matches("./"),
+    }
+}
 
-    res
+/// Reads the expected SHA256 of every file listed in a `dataset_infos.json`,
+/// keyed by its path within the repo.
+fn read_dataset_checksums(dataset_infos: &Path) -> Result<HashMap<String, String>, DownloadError> {
+    let infos: serde_json::Value = serde_json::from_reader(std::fs::File::open(dataset_infos)?)?;
+    let mut checksums = HashMap::new();
+    for config in infos
+        .as_object()
+        .into_iter()
+        .flat_map(|configs| configs.values())
+    {
+        let Some(download_checksums) = config.get("download_checksums").and_then(|c| c.as_object())
+        else {
+            continue;
+        };
+        for (url, info) in download_checksums {
+            if let Some(checksum) = info.get("checksum").and_then(|c| c.as_str()) {
+                checksums.insert(
+                    repo_path_of_checksum_key(url).to_string(),
+                    checksum.to_lowercase(),
+                );
+            }
+        }
+    }
+    Ok(checksums)
+}
+
+fn sha256_file(path: &Path) -> Result<String, DownloadError> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Checks every downloaded file that `dataset_infos.json` has a checksum for.
+fn verify_dataset_checksums(
+    dataset_infos: &Path,
+    files: &[(String, PathBuf)],
+) -> Result<(), DownloadError> {
+    let checksums = read_dataset_checksums(dataset_infos)?;
+    for (filename, path) in files {
+        let Some(expected) = checksums.get(filename) else {
+            debug!(
+                filename,
+                "No checksum listed for downloaded file, skipping verification"
+            );
+            continue;
+        };
+        let actual = sha256_file(path)?;
+        if actual != *expected {
+            return Err(DownloadError::ChecksumMismatch {
+                filename: filename.clone(),
+                expected: expected.clone(),
+                actual,
+            });
+        }
+        debug!(filename, "Verified checksum of downloaded file");
+    }
+    Ok(())
 }
 
 pub fn download_model_repo_sync(
@@ -190,18 +369,35 @@ pub fn download_dataset_repo_sync(
     cache: Option<PathBuf>,
     token: Option<String>,
     progress_bar: bool,
-) -> Result<Vec<PathBuf>, hf_hub::api::sync::ApiError> {
+    config: DownloadConfig,
+) -> Result<Vec<PathBuf>, DownloadError> {
     let repo_id = sanitize_repo_id(repo_id);
-    download_repo_sync(
+    let api = sync_api_repo(
         match revision {
             Some(revision) => Repo::with_revision(repo_id.clone(), RepoType::Dataset, revision),
-            None => Repo::new(repo_id, RepoType::Dataset),
+            None => Repo::new(repo_id.clone(), RepoType::Dataset),
         },
         cache,
         token,
         progress_bar,
-        &DATASET_EXTENSIONS,
-    )
+    )?;
+    let mut siblings = api.info()?.siblings;
+    retain_subset(&mut siblings, config.subset.as_deref());
+    let files = download_files_sync(&api, &siblings, &DATASET_EXTENSIONS)?;
+
+    if config.verify_checksums {
+        if has_dataset_infos(&siblings) {
+            let dataset_infos = api.get(DATASET_INFOS_FILENAME)?;
+            verify_dataset_checksums(&dataset_infos, &files)?;
+        } else {
+            debug!(
+                repo_id,
+                "Dataset has no {DATASET_INFOS_FILENAME}, skipping checksum verification"
+            );
+        }
+    }
+
+    Ok(files.into_iter().map(|(_, path)| path).collect())
 }
 
 #[derive(Debug, Clone)]
@@ -274,3 +470,89 @@ pub async fn upload_to_hub(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    fn write_dataset_infos(dir: &Path, download_checksums: serde_json::Value) -> PathBuf {
+        let path = dir.join(DATASET_INFOS_FILENAME);
+        let infos = serde_json::json!({
+            "default": { "download_checksums": download_checksums },
+            "no_checksums": {},
+        });
+        std::fs::write(&path, infos.to_string()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_read_dataset_checksums() {
+        let dir = tempfile::tempdir().unwrap();
+        let dataset_infos = write_dataset_infos(
+            dir.path(),
+            serde_json::json!({
+                "https://huggingface.co/datasets/org/name/resolve/main/data/train.parquet":
+                    { "num_bytes": 5, "checksum": "ABC" },
+                "https://huggingface.co/datasets/org/name/resolve/refs/convert/parquet/default/test/0000.parquet":
+                    { "num_bytes": 5, "checksum": "def" },
+                "data/validation.parquet": { "num_bytes": 5, "checksum": "123" },
+                "data/no_checksum.parquet": { "num_bytes": 5, "checksum": null },
+            }),
+        );
+
+        let checksums = read_dataset_checksums(&dataset_infos).unwrap();
+        assert_eq!(
+            checksums,
+            HashMap::from([
+                ("data/train.parquet".to_string(), "abc".to_string()),
+                ("default/test/0000.parquet".to_string(), "def".to_string()),
+                ("data/validation.parquet".to_string(), "123".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_verify_dataset_checksums() {
+        let dir = tempfile::tempdir().unwrap();
+        let train = dir.path().join("train.parquet");
+        std::fs::write(&train, "hello").unwrap();
+        let dataset_infos = write_dataset_infos(
+            dir.path(),
+            serde_json::json!({
+                "https://huggingface.co/datasets/org/name/resolve/main/data/train.parquet":
+                    { "checksum": HELLO_SHA256 },
+                "data/test.parquet": { "checksum": HELLO_SHA256 },
+            }),
+        );
+
+        verify_dataset_checksums(
+            &dataset_infos,
+            &[("data/train.parquet".to_string(), train.clone())],
+        )
+        .unwrap();
+
+        // files without a listed checksum aren't checked, even if another file's path ends with their name
+        verify_dataset_checksums(
+            &dataset_infos,
+            &[("train.parquet".to_string(), dir.path().join("missing"))],
+        )
+        .unwrap();
+
+        std::fs::write(&train, "corrupted").unwrap();
+        match verify_dataset_checksums(&dataset_infos, &[("data/train.parquet".to_string(), train)])
+        {
+            Err(DownloadError::ChecksumMismatch {
+                filename,
+                expected,
+                actual,
+            }) => {
+                assert_eq!(filename, "data/train.parquet");
+                assert_eq!(expected, HELLO_SHA256);
+                assert_ne!(actual, HELLO_SHA256);
+            }
+            other => panic!("expected a checksum mismatch, got {other:?}"),
+        }
+    }
+}
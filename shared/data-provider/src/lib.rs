@@ -9,6 +9,7 @@ mod hub;
 mod local;
 mod preprocessed;
 mod remote;
+mod streaming;
 mod traits;
 mod weighted;
 
@@ -16,18 +17,21 @@ pub use data_provider::DataProvider;
 pub use dataset::{Dataset, Field, Row, Split};
 pub use dummy::DummyDataProvider;
 pub use errors::{DownloadError, UploadError};
-pub use file_extensions::{DATA_FILE_EXTENSIONS, PARQUET_EXTENSION};
+pub use file_extensions::{
+    DATA_FILE_EXTENSIONS, PARQUET_EXTENSION, TEXT_FILE_EXTENSIONS, TOKENIZED_JSONL_EXTENSIONS,
+};
 pub use gcs::{
     GcsCheckpointManifest, GcsManifestMetadata, GcsUploadInfo, ManifestFileEntry, ManifestMetadata,
     download_model_from_gcs_async, download_model_from_gcs_sync, upload_to_gcs,
 };
 pub use hub::{
-    HubUploadInfo, download_dataset_repo_async, download_dataset_repo_sync,
+    DownloadConfig, HubUploadInfo, download_dataset_repo_async, download_dataset_repo_sync,
     download_model_repo_async, download_model_repo_sync, upload_to_hub,
 };
-pub use local::LocalDataProvider;
+pub use local::{DEFAULT_JSONL_TOKENS_FIELD, LocalDataProvider, SEQUENCE_INDEX_EXTENSION};
 pub use parquet::record::{ListAccessor, MapAccessor, RowAccessor};
 pub use preprocessed::PreprocessedDataProvider;
 pub use remote::{DataProviderTcpClient, DataProviderTcpServer, DataServerTui};
+pub use streaming::StreamingDataProvider;
 pub use traits::{LengthKnownDataProvider, TokenizedData, TokenizedDataProvider};
 pub use weighted::{WeightedDataProvider, http::WeightedHttpProvidersConfig};
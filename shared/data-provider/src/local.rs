@@ -1,17 +1,31 @@
 use anyhow::{Result, anyhow, bail};
+use flate2::read::GzDecoder;
+use lru::LruCache;
 use psyche_core::{BatchId, ClosedInterval, Shuffle, TokenSize};
 use rand::seq::SliceRandom;
 use rand_chacha::ChaCha8Rng;
 use rand_chacha::rand_core::SeedableRng;
-use std::fs;
+use std::{
+    fs,
+    io::{BufRead, BufReader},
+    num::NonZeroUsize,
+    path::Path,
+};
 use tracing::info;
 
 use crate::{
     TokenizedData,
-    file_extensions::DATA_FILE_EXTENSIONS,
+    file_extensions::{DATA_FILE_EXTENSIONS, TOKENIZED_JSONL_EXTENSIONS},
     traits::{LengthKnownDataProvider, TokenizedDataProvider},
 };
 
+/// Field of each `.jsonl` line that holds its tokens, unless told otherwise.
+pub const DEFAULT_JSONL_TOKENS_FIELD: &str = "tokens";
+
+/// Extension of the file next to a data file (e.g. `train.index` for `train.bin`) that holds
+/// the byte offset of every sequence in it, as little-endian `u64`s.
+pub const SEQUENCE_INDEX_EXTENSION: &str = "index";
+
 fn is_truthy_env_bool(value: &str) -> bool {
     matches!(value.to_lowercase().as_str(), "1" | "true" | "yes")
 }
@@ -36,16 +50,137 @@ fn mmap_file(p: &std::path::PathBuf) -> Result<Box<dyn AsRef<[u8]> + Send>> {
     }
 }
 
+fn is_jsonl(p: &Path) -> bool {
+    let name = p.file_name().and_then(|s| s.to_str()).unwrap_or_default();
+    TOKENIZED_JSONL_EXTENSIONS
+        .iter()
+        .any(|extension| name.ends_with(&format!(".{extension}")))
+}
+
+/// Reads the token arrays of every line of a `.jsonl` (or `.jsonl.gz`) file,
+/// packing them back to back in the same layout as the binary data files.
+fn read_jsonl_file(
+    p: &Path,
+    tokens_field: &str,
+    token_size_in_bytes: TokenSize,
+) -> Result<Box<dyn AsRef<[u8]> + Send>> {
+    let file = std::fs::File::open(p)?;
+    let reader: Box<dyn BufRead> = match p.extension().and_then(|s| s.to_str()) {
+        Some("gz") => Box::new(BufReader::new(GzDecoder::new(file))),
+        _ => Box::new(BufReader::new(file)),
+    };
+
+    let mut data = Vec::new();
+    for (line_index, line) in reader.lines().enumerate() {
+        let line_number = line_index + 1;
+        let line = line.map_err(|e| anyhow!("failed to read {}: {e}", p.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: serde_json::Value = serde_json::from_str(&line)
+            .map_err(|e| anyhow!("invalid JSON on line {line_number} of {}: {e}", p.display()))?;
+        let tokens = value
+            .get(tokens_field)
+            .and_then(|tokens| tokens.as_array())
+            .ok_or_else(|| {
+                anyhow!(
+                    "line {line_number} of {} has no \"{tokens_field}\" array",
+                    p.display()
+                )
+            })?;
+        for token in tokens {
+            let token = token.as_u64().ok_or_else(|| {
+                anyhow!(
+                    "invalid token {token} on line {line_number} of {}",
+                    p.display()
+                )
+            })?;
+            let out_of_range = || {
+                anyhow!(
+                    "token {token} on line {line_number} of {} doesn't fit in {} bytes",
+                    p.display(),
+                    usize::from(token_size_in_bytes)
+                )
+            };
+            match token_size_in_bytes {
+                TokenSize::TwoBytes => data.extend_from_slice(
+                    &u16::try_from(token)
+                        .map_err(|_| out_of_range())?
+                        .to_le_bytes(),
+                ),
+                TokenSize::FourBytes => data.extend_from_slice(
+                    &u32::try_from(token)
+                        .map_err(|_| out_of_range())?
+                        .to_le_bytes(),
+                ),
+            }
+        }
+    }
+    Ok(Box::new(data))
+}
+
+/// Reads the sequence start offsets of a [`SEQUENCE_INDEX_EXTENSION`] file, checking they're
+/// increasing, whole tokens, and inside a data file of `file_len` bytes.
+fn read_sequence_index(
+    index: &Path,
+    file_len: usize,
+    token_size_in_bytes: TokenSize,
+) -> Result<Vec<usize>> {
+    let bytes = std::fs::read(index)
+        .map_err(|e| anyhow!("failed to read sequence index {}: {e}", index.display()))?;
+    if bytes.len() % size_of::<u64>() != 0 {
+        bail!(
+            "sequence index {} isn't a list of u64 offsets ({} bytes)",
+            index.display(),
+            bytes.len()
+        );
+    }
+    let offsets: Vec<usize> = bytemuck::pod_collect_to_vec::<u8, u64>(&bytes)
+        .into_iter()
+        .map(|offset| u64::from_le(offset) as usize)
+        .collect();
+    let token_size = usize::from(token_size_in_bytes);
+    for (i, &offset) in offsets.iter().enumerate() {
+        if offset % token_size != 0 || offset >= file_len {
+            bail!(
+                "offset {offset} of sequence {i} in {} isn't a token inside its {file_len} byte data file",
+                index.display()
+            );
+        }
+        if i > 0 && offset <= offsets[i - 1] {
+            bail!(
+                "offsets in sequence index {} must be increasing, but sequence {i} starts at {offset}",
+                index.display()
+            );
+        }
+    }
+    Ok(offsets)
+}
+
+/// Shuffles the whole collection of sequences, to avoid bias from a specific file.
+fn shuffle_sequences(sequences: &mut [SequencePointer], shuffle: Shuffle) {
+    if let Shuffle::Seeded(random_seed) = shuffle {
+        sequences.shuffle(&mut ChaCha8Rng::from_seed(random_seed));
+    }
+}
+
 struct SequencePointer {
     file_index: usize,
     byte_offset: usize,
+    num_tokens: usize,
+}
+
+struct SequenceCache {
+    sequences: LruCache<u64, Vec<i32>>,
+    hits: u64,
+    lookups: u64,
 }
 
 pub struct LocalDataProvider {
     data_files: Vec<Box<dyn AsRef<[u8]> + Send>>,
     sequences: Vec<SequencePointer>,
-    seq_len: usize,
     token_size_in_bytes: TokenSize,
+    cache: Option<SequenceCache>,
 }
 
 impl LengthKnownDataProvider for LocalDataProvider {
@@ -55,11 +190,29 @@ impl LengthKnownDataProvider for LocalDataProvider {
 }
 
 impl LocalDataProvider {
+    /// Loads binary token files and `.jsonl`/`.jsonl.gz` files with a [`DEFAULT_JSONL_TOKENS_FIELD`] array per line.
     pub fn new_from_directory(
         dir: impl AsRef<std::path::Path>,
         token_size_in_bytes: TokenSize,
         num_tokens_per_sequence: usize, // num tokens per sequence
         shuffle: Shuffle,
+    ) -> Result<Self> {
+        Self::new_from_directory_with_jsonl_field(
+            dir,
+            token_size_in_bytes,
+            num_tokens_per_sequence,
+            shuffle,
+            DEFAULT_JSONL_TOKENS_FIELD,
+        )
+    }
+
+    /// Like [`Self::new_from_directory`], but reads the tokens of `.jsonl` lines from `jsonl_tokens_field`.
+    pub fn new_from_directory_with_jsonl_field(
+        dir: impl AsRef<std::path::Path>,
+        token_size_in_bytes: TokenSize,
+        num_tokens_per_sequence: usize, // num tokens per sequence
+        shuffle: Shuffle,
+        jsonl_tokens_field: &str,
     ) -> Result<Self> {
         let dir = std::fs::canonicalize(&dir)
             .map_err(|e| anyhow!("Failed to open data directory {:?}: {e}", dir.as_ref()))?;
@@ -69,7 +222,9 @@ impl LocalDataProvider {
             .flatten()
         {
             let file = file.path();
-            if let Some(extension) = file.extension().and_then(|s| s.to_str()) {
+            if is_jsonl(&file) {
+                bin_files.push(file);
+            } else if let Some(extension) = file.extension().and_then(|s| s.to_str()) {
                 if DATA_FILE_EXTENSIONS.contains(&extension) {
                     bin_files.push(file);
                 }
@@ -77,7 +232,10 @@ impl LocalDataProvider {
         }
         let data_files = bin_files
             .iter()
-            .map(mmap_file)
+            .map(|file| match is_jsonl(file) {
+                true => read_jsonl_file(file, jsonl_tokens_field, token_size_in_bytes),
+                false => mmap_file(file),
+            })
             .collect::<Result<Vec<_>>>()?;
 
         if data_files.is_empty() {
@@ -94,77 +252,197 @@ impl LocalDataProvider {
             dir.display()
         );
 
-        let deterministic_rng = match shuffle {
-            Shuffle::Seeded(random_seed) => Some(ChaCha8Rng::from_seed(random_seed)),
-            Shuffle::DontShuffle => None,
-        };
         let seq_len_in_bytes = num_tokens_per_sequence * usize::from(token_size_in_bytes);
 
-        let sequences: Vec<SequencePointer> = {
-            let mut all_indexes: Vec<_> = data_files
-                .iter()
-                .enumerate()
-                // find every sequence in every file
-                .flat_map(|(file_index, current_tokens)| {
-                    (0..current_tokens.as_ref().as_ref().len() - seq_len_in_bytes)
-                        .step_by(seq_len_in_bytes)
-                        .map(move |byte_offset| SequencePointer {
-                            file_index,
-                            byte_offset,
-                        })
-                })
-                .collect();
-            // and shuffle the whole collection, to avoid bias from a specific file
-            if let Some(mut deterministic_rng) = deterministic_rng {
-                all_indexes.shuffle(&mut deterministic_rng);
+        let mut sequences: Vec<SequencePointer> = data_files
+            .iter()
+            .enumerate()
+            // find every sequence in every file
+            .flat_map(|(file_index, current_tokens)| {
+                (0..current_tokens
+                    .as_ref()
+                    .as_ref()
+                    .len()
+                    .saturating_sub(seq_len_in_bytes))
+                    .step_by(seq_len_in_bytes)
+                    .map(move |byte_offset| SequencePointer {
+                        file_index,
+                        byte_offset,
+                        num_tokens: num_tokens_per_sequence,
+                    })
+            })
+            .collect();
+        shuffle_sequences(&mut sequences, shuffle);
+
+        Ok(Self {
+            data_files,
+            sequences,
+            token_size_in_bytes,
+            cache: None,
+        })
+    }
+
+    /// Memory-maps every binary data file in `dir` that has a [`SEQUENCE_INDEX_EXTENSION`] file
+    /// next to it, with one sample per indexed sequence. A sequence runs from its offset to the
+    /// next one (or the end of the file), so unlike [`Self::new_from_directory`] samples keep
+    /// the lengths they were tokenized with.
+    /// Nothing is read up front but the indexes; the page cache holds whatever tokens were read
+    /// recently, so datasets don't need to fit in memory.
+    pub fn mmap(
+        dir: impl AsRef<Path>,
+        token_size_in_bytes: TokenSize,
+        shuffle: Shuffle,
+    ) -> Result<Self> {
+        let dir = std::fs::canonicalize(&dir)
+            .map_err(|e| anyhow!("Failed to open data directory {:?}: {e}", dir.as_ref()))?;
+        let mut indexed_files = vec![];
+        for file in std::fs::read_dir(&dir)
+            .map_err(|e| anyhow!("couldn't load training data from {}: {e}", dir.display()))?
+            .flatten()
+        {
+            let file = file.path();
+            let Some(extension) = file.extension().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let index = file.with_extension(SEQUENCE_INDEX_EXTENSION);
+            if DATA_FILE_EXTENSIONS.contains(&extension) && index.is_file() {
+                indexed_files.push((file, index));
             }
-            all_indexes
-        };
+        }
+        if indexed_files.is_empty() {
+            bail!(
+                "No training data files with a .{SEQUENCE_INDEX_EXTENSION} file in directory {}",
+                dir.display()
+            );
+        }
+        // read_dir order isn't specified, and every client must number the sequences the same way
+        indexed_files.sort();
+
+        let mut data_files = Vec::with_capacity(indexed_files.len());
+        let mut sequences = vec![];
+        for (file_index, (file, index)) in indexed_files.iter().enumerate() {
+            let data = mmap_file(file)?;
+            let file_len = data.as_ref().as_ref().len();
+            let offsets = read_sequence_index(index, file_len, token_size_in_bytes)?;
+            let ends = offsets.iter().skip(1).copied().chain([file_len]);
+            sequences.extend(
+                offsets
+                    .iter()
+                    .zip(ends)
+                    .map(|(&byte_offset, end)| SequencePointer {
+                        file_index,
+                        byte_offset,
+                        num_tokens: (end - byte_offset) / usize::from(token_size_in_bytes),
+                    }),
+            );
+            data_files.push(data);
+        }
+        shuffle_sequences(&mut sequences, shuffle);
+
+        info!(
+            "Memory-mapped {} sequences from {} files of training data in directory {}",
+            sequences.len(),
+            data_files.len(),
+            dir.display()
+        );
 
         Ok(Self {
             data_files,
             sequences,
-            seq_len: num_tokens_per_sequence,
             token_size_in_bytes,
+            cache: None,
         })
     }
 
-    fn internal_get_samples(&self, data_ids: BatchId) -> Result<Vec<TokenizedData>> {
-        let mut ret: Vec<_> = Vec::new();
-        for data_id in data_ids.iter() {
-            let SequencePointer {
-                byte_offset,
-                file_index,
-            } = self.sequences.get(data_id as usize).ok_or_else(|| {
-                anyhow!(
-                    "index {data_id} is out of bounds, we only have {} samples.",
-                    self.sequences.len()
-                )
-            })?;
+    /// Keeps the `capacity` most recently read sequences in memory, keyed by sample index.
+    /// Worth it when the same samples are read repeatedly, e.g. over several epochs of a small dataset
+    /// or from files that don't mmap (`.jsonl`) and aren't in the page cache.
+    pub fn with_cache(mut self, capacity: usize) -> Self {
+        self.cache = NonZeroUsize::new(capacity).map(|capacity| SequenceCache {
+            sequences: LruCache::new(capacity),
+            hits: 0,
+            lookups: 0,
+        });
+        self
+    }
+
+    fn read_sequence(&self, data_id: u64) -> Result<Vec<i32>> {
+        let SequencePointer {
+            byte_offset,
+            file_index,
+            num_tokens,
+        } = self.sequences.get(data_id as usize).ok_or_else(|| {
+            anyhow!(
+                "index {data_id} is out of bounds, we only have {} samples.",
+                self.sequences.len()
+            )
+        })?;
+
+        let file = &self.data_files[*file_index];
+        let data_len = usize::from(self.token_size_in_bytes) * num_tokens;
+        let data = &file.as_ref().as_ref()[*byte_offset..*byte_offset + data_len];
+
+        // mapped files are page aligned, and index offsets are checked to be whole tokens,
+        // but buffers read from `.jsonl` files may not be aligned for a zero-copy cast
+        use TokenSize::*;
+        Ok(match self.token_size_in_bytes {
+            TwoBytes => match bytemuck::try_cast_slice::<u8, u16>(data) {
+                Ok(tokens) => tokens.iter().map(|&t| u16::from_le(t) as i32).collect(),
+                Err(_) => data
+                    .chunks(2)
+                    .map(|t| u16::from_le_bytes(t.try_into().unwrap()) as i32)
+                    .collect(),
+            },
+            FourBytes => match bytemuck::try_cast_slice::<u8, u32>(data) {
+                Ok(tokens) => tokens.iter().map(|&t| u32::from_le(t) as i32).collect(),
+                Err(_) => data
+                    .chunks(4)
+                    .map(|t| u32::from_le_bytes(t.try_into().unwrap()) as i32)
+                    .collect(),
+            },
+        })
+    }
 
-            let file = &self.data_files[*file_index];
-            let data_len = usize::from(self.token_size_in_bytes) * self.seq_len;
-            let data = &file.as_ref().as_ref()[*byte_offset..*byte_offset + data_len];
-
-            let tokens: Vec<i32> = data
-                .chunks(self.token_size_in_bytes.into())
-                .map(|t| {
-                    use TokenSize::*;
-                    match self.token_size_in_bytes {
-                        TwoBytes => u16::from_le_bytes(t.try_into().unwrap()) as i32,
-                        FourBytes => u32::from_le_bytes(t.try_into().unwrap()) as i32,
-                    }
-                })
-                .collect();
-            ret.push(TokenizedData::from_input_ids(tokens));
+    fn read_sequence_cached(&mut self, data_id: u64) -> Result<Vec<i32>> {
+        if let Some(cache) = &mut self.cache {
+            cache.lookups += 1;
+            if let Some(tokens) = cache.sequences.get(&data_id) {
+                cache.hits += 1;
+                return Ok(tokens.clone());
+            }
         }
-        Ok(ret)
+        let tokens = self.read_sequence(data_id)?;
+        if let Some(cache) = &mut self.cache {
+            cache.sequences.put(data_id, tokens.clone());
+        }
+        Ok(tokens)
+    }
+
+    fn internal_get_samples(&self, data_ids: BatchId) -> Result<Vec<TokenizedData>> {
+        data_ids
+            .iter()
+            .map(|data_id| Ok(TokenizedData::from_input_ids(self.read_sequence(data_id)?)))
+            .collect()
     }
 }
 
 impl TokenizedDataProvider for LocalDataProvider {
     async fn get_samples(&mut self, data_ids: BatchId) -> Result<Vec<TokenizedData>> {
-        self.internal_get_samples(data_ids)
+        data_ids
+            .iter()
+            .map(|data_id| {
+                Ok(TokenizedData::from_input_ids(
+                    self.read_sequence_cached(data_id)?,
+                ))
+            })
+            .collect()
+    }
+
+    fn cache_hit_ratio(&self) -> Option<f64> {
+        self.cache
+            .as_ref()
+            .filter(|cache| cache.lookups > 0)
+            .map(|cache| cache.hits as f64 / cache.lookups as f64)
     }
 }
 
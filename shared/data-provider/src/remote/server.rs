@@ -57,7 +57,8 @@ where
                     ClientNotification::Message((from, message)) => {
                         self.handle_client_message(from, message).await;
                     }
-                    ClientNotification::Disconnected(_) => {
+                    ClientNotification::Disconnected(_)
+                    | ClientNotification::ConnectionUpgraded(_) => {
                         // noop :)
                     }
                 }
@@ -24,12 +24,19 @@ impl psyche_tui::CustomWidget for DataServerTui {
             {
                 let split =
                     Layout::horizontal(Constraint::from_fills([1, 1])).split(global_stats[0]);
-                Paragraph::new(Text::from(vec![
+                let mut stats = vec![
                     Line::from(format!("Total samples: {}", state.total_samples)),
                     Line::from(format!("Provided samples: {}", state.given_samples)),
-                ]))
-                .block(Block::bordered().title("Stats"))
-                .render(split[0], buf);
+                ];
+                if let Some(ratio) = state.cache_hit_ratio {
+                    stats.push(Line::from(format!(
+                        "Cache hit ratio: {:.1}%",
+                        ratio * 100.0
+                    )));
+                }
+                Paragraph::new(Text::from(stats))
+                    .block(Block::bordered().title("Stats"))
+                    .render(split[0], buf);
 
                 Gauge::default()
                     .block(Block::bordered().title("Percent of data given out"))
@@ -76,6 +83,7 @@ pub struct DataServerTuiState {
 
     pub total_samples: usize,
     pub given_samples: usize,
+    pub cache_hit_ratio: Option<f64>,
 }
 
 impl<D, W> From<&DataProviderTcpServer<D, W>> for DataServerTuiState
@@ -97,6 +105,7 @@ where
                 .collect(),
             total_samples: v.local_data_provider.num_sequences(),
             given_samples: v.provided_sequences.values().fold(0, |acc, ele| acc + *ele),
+            cache_hit_ratio: v.local_data_provider.cache_hit_ratio(),
         }
     }
 }
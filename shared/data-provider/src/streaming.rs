@@ -0,0 +1,291 @@
+use anyhow::{Result, anyhow, bail};
+use psyche_core::BatchId;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokenizers::Tokenizer;
+use tokio::sync::mpsc;
+use tracing::info;
+
+use crate::{
+    TokenizedData,
+    file_extensions::TEXT_FILE_EXTENSIONS,
+    traits::{LengthKnownDataProvider, TokenizedDataProvider},
+};
+
+/// Tokenizes a directory of `.txt` (one document per file) or `.jsonl` (one document per line, in its `text` field)
+/// files on the fly, in a background thread, and serves them as fixed-length windows of tokens.
+///
+/// Every file is tokenized once up front to count its windows, so [`LengthKnownDataProvider::num_sequences`] is exact
+/// and every window has a fixed index. Windows never span two files - each file's trailing partial window is dropped.
+///
+/// Samples are cheapest to read in increasing order. Asking for an index before one already read, or in a later file,
+/// restarts the stream at the start of the file that holds it.
+/// When used with [`crate::WeightedDataProvider`], prefer [`psyche_core::Shuffle::DontShuffle`].
+pub struct StreamingDataProvider {
+    files: Arc<Vec<PathBuf>>,
+    tokenizer: Arc<Tokenizer>,
+    seq_len: usize,
+    look_ahead: usize,
+    // index of the first window of each file, plus the total number of windows at the end
+    file_offsets: Vec<u64>,
+
+    stream: mpsc::Receiver<Result<Vec<i32>>>,
+    // index of the next window `stream` will yield
+    next_index: u64,
+}
+
+impl LengthKnownDataProvider for StreamingDataProvider {
+    fn num_sequences(&self) -> usize {
+        self.total_sequences() as usize
+    }
+}
+
+impl StreamingDataProvider {
+    /// `look_ahead` is the number of tokenized sequences buffered ahead of the reader.
+    pub fn new_from_directory(
+        dir: impl AsRef<Path>,
+        tokenizer: Arc<Tokenizer>,
+        sequence_length: usize,
+        look_ahead: usize,
+    ) -> Result<Self> {
+        if sequence_length == 0 {
+            bail!("sequence length must be greater than zero");
+        }
+        let dir = std::fs::canonicalize(&dir)
+            .map_err(|e| anyhow!("Failed to open data directory {:?}: {e}", dir.as_ref()))?;
+        let mut files = vec![];
+        for file in std::fs::read_dir(&dir)
+            .map_err(|e| anyhow!("couldn't load training data from {}: {e}", dir.display()))?
+            .flatten()
+        {
+            let file = file.path();
+            if let Some(extension) = file.extension().and_then(|s| s.to_str()) {
+                if TEXT_FILE_EXTENSIONS.contains(&extension) {
+                    files.push(file);
+                }
+            }
+        }
+        // read_dir order is platform-dependent, and every client must see the same stream.
+        files.sort();
+
+        if files.is_empty() {
+            bail!("No text training data files in directory {:?}", dir);
+        }
+
+        info!(
+            "Counting sequences in {} files of text training data from directory {}",
+            files.len(),
+            dir.display()
+        );
+        let windows_per_file = files
+            .par_iter()
+            .map(|file| count_windows(file, &tokenizer, sequence_length))
+            .collect::<Result<Vec<u64>>>()?;
+        let file_offsets: Vec<u64> = std::iter::once(0)
+            .chain(windows_per_file.iter().scan(0, |offset, windows| {
+                *offset += windows;
+                Some(*offset)
+            }))
+            .collect();
+        let total_sequences = *file_offsets.last().unwrap();
+        if total_sequences == 0 {
+            bail!("no sequences in dataset, every file is shorter than {sequence_length} tokens");
+        }
+
+        info!(
+            "Streaming {} files of text training data from directory {}, {total_sequences} sequences",
+            files.len(),
+            dir.display()
+        );
+
+        let files = Arc::new(files);
+        let look_ahead = look_ahead.max(1);
+        let stream = spawn_tokenizer(
+            files.clone(),
+            0,
+            tokenizer.clone(),
+            sequence_length,
+            look_ahead,
+        );
+
+        Ok(Self {
+            files,
+            tokenizer,
+            seq_len: sequence_length,
+            look_ahead,
+            file_offsets,
+            stream,
+            next_index: 0,
+        })
+    }
+
+    fn total_sequences(&self) -> u64 {
+        *self.file_offsets.last().unwrap()
+    }
+
+    /// index of the file holding window `data_id`
+    fn file_of(&self, data_id: u64) -> usize {
+        // offsets are sorted; empty files share their offset with the next one, so take the last match.
+        self.file_offsets
+            .partition_point(|&offset| offset <= data_id)
+            - 1
+    }
+
+    fn seek_to_file(&mut self, file: usize) {
+        self.stream = spawn_tokenizer(
+            self.files.clone(),
+            file,
+            self.tokenizer.clone(),
+            self.seq_len,
+            self.look_ahead,
+        );
+        self.next_index = self.file_offsets[file];
+    }
+
+    async fn get_sample(&mut self, data_id: u64) -> Result<Vec<i32>> {
+        let data_id = data_id % self.total_sequences();
+        let file = self.file_of(data_id);
+        if data_id < self.next_index || self.file_offsets[file] > self.next_index {
+            self.seek_to_file(file);
+        }
+        loop {
+            match self.stream.recv().await {
+                Some(Ok(window)) => {
+                    let index = self.next_index;
+                    self.next_index += 1;
+                    if index == data_id {
+                        return Ok(window);
+                    }
+                }
+                Some(Err(err)) => {
+                    self.seek_to_file(file);
+                    return Err(err);
+                }
+                None => {
+                    let index = self.next_index;
+                    self.seek_to_file(0);
+                    bail!(
+                        "stream ended at sequence {index} before reaching {data_id}, were the data files modified?"
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl TokenizedDataProvider for StreamingDataProvider {
+    async fn get_samples(&mut self, data_ids: BatchId) -> Result<Vec<TokenizedData>> {
+        let mut ret = Vec::with_capacity(data_ids.len());
+        for data_id in data_ids.iter() {
+            ret.push(TokenizedData::from_input_ids(
+                self.get_sample(data_id).await?,
+            ));
+        }
+        Ok(ret)
+    }
+}
+
+fn spawn_tokenizer(
+    files: Arc<Vec<PathBuf>>,
+    start_file: usize,
+    tokenizer: Arc<Tokenizer>,
+    seq_len: usize,
+    look_ahead: usize,
+) -> mpsc::Receiver<Result<Vec<i32>>> {
+    let (tx, rx) = mpsc::channel(look_ahead);
+    std::thread::spawn(move || {
+        let mut tokens: Vec<i32> = Vec::with_capacity(seq_len * 2);
+        for file in &files[start_file..] {
+            // windows never span files, so the trailing partial window of the last one is dropped.
+            tokens.clear();
+            for document in documents(file) {
+                let ids = document.and_then(|text| tokenize(&tokenizer, text));
+                match ids {
+                    Ok(ids) => tokens.extend(ids),
+                    Err(err) => {
+                        let _ = tx.blocking_send(Err(err));
+                        return;
+                    }
+                }
+                while tokens.len() >= seq_len {
+                    let window = tokens.drain(..seq_len).collect();
+                    if tx.blocking_send(Ok(window)).is_err() {
+                        // the provider seeked or was dropped, nobody's listening.
+                        return;
+                    }
+                }
+            }
+        }
+    });
+    rx
+}
+
+fn count_windows(file: &Path, tokenizer: &Tokenizer, seq_len: usize) -> Result<u64> {
+    let mut tokens = 0;
+    for document in documents(file) {
+        tokens += tokenize(tokenizer, document?)?.len();
+    }
+    Ok((tokens / seq_len) as u64)
+}
+
+fn tokenize(tokenizer: &Tokenizer, text: String) -> Result<Vec<i32>> {
+    let encoding = tokenizer
+        .encode(text, true)
+        .map_err(|e| anyhow!("failed to tokenize document: {e}"))?;
+    Ok(encoding.get_ids().iter().map(|&id| id as i32).collect())
+}
+
+/// Every document in `path`, in order.
+fn documents(path: &Path) -> Box<dyn Iterator<Item = Result<String>> + '_> {
+    match path.extension().and_then(|s| s.to_str()) {
+        Some("jsonl") => match File::open(path) {
+            Ok(file) => Box::new(jsonl_documents(path, BufReader::new(file))),
+            Err(err) => Box::new(std::iter::once(Err(anyhow!(
+                "failed to open {}: {err}",
+                path.display()
+            )))),
+        },
+        _ => Box::new(std::iter::once(read_text_document(path))),
+    }
+}
+
+fn read_text_document(path: &Path) -> Result<String> {
+    let mut text = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .map_err(|e| anyhow!("failed to read {}: {e}", path.display()))?;
+    Ok(text)
+}
+
+fn jsonl_documents<'a>(
+    path: &'a Path,
+    reader: impl BufRead + 'a,
+) -> impl Iterator<Item = Result<String>> + 'a {
+    reader
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(move |(line_number, line)| {
+            let line = line.map_err(|e| anyhow!("failed to read {}: {e}", path.display()))?;
+            let mut value: serde_json::Value = serde_json::from_str(&line).map_err(|e| {
+                anyhow!(
+                    "invalid JSON on line {} of {}: {e}",
+                    line_number + 1,
+                    path.display()
+                )
+            })?;
+            match value.get_mut("text").map(serde_json::Value::take) {
+                Some(serde_json::Value::String(text)) => Ok(text),
+                _ => Err(anyhow!(
+                    "line {} of {} has no \"text\" string field",
+                    line_number + 1,
+                    path.display()
+                )),
+            }
+        })
+}
@@ -49,6 +49,12 @@ pub trait TokenizedDataProvider {
         &mut self,
         data_ids: BatchId,
     ) -> impl std::future::Future<Output = Result<Vec<TokenizedData>>> + Send;
+
+    /// Fraction of sample reads served from an in-memory cache.
+    /// `None` for providers without one, or that haven't been read from yet.
+    fn cache_hit_ratio(&self) -> Option<f64> {
+        None
+    }
 }
 
 pub trait LengthKnownDataProvider {
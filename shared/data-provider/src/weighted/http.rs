@@ -46,7 +46,12 @@ impl WeightedDataProvider<HttpDataProvider> {
                 .into(),
             None => http_providers.into(),
         };
-        Ok(WeightedDataProvider::new(providers, config.shuffle))
+        let provider =
+            WeightedDataProvider::new(providers, config.shuffle, config.deduplicate).await?;
+        Ok(match config.curriculum {
+            Some(curriculum) => provider.with_curriculum(curriculum)?,
+            None => provider,
+        })
     }
 
     pub async fn from_config_url(url: &str, max_seq_len: u32) -> Result<Self> {
@@ -61,6 +66,13 @@ impl WeightedDataProvider<HttpDataProvider> {
 pub struct WeightedHttpProvidersConfig {
     shuffle: Shuffle,
     providers: HttpProviderConfigs,
+    /// Leave out samples that are exact copies of another sample. Requires fetching every sample on startup.
+    #[serde(default)]
+    deduplicate: bool,
+    /// `(min_step, weights)` pairs that change the provider weights as training progresses.
+    /// See [`WeightedDataProvider::with_curriculum`].
+    #[serde(default)]
+    curriculum: Option<Vec<(u32, Vec<f64>)>>,
 }
 
 #[derive(Serialize, Deserialize, TS, Debug)]
@@ -161,6 +173,8 @@ mod tests {
                         })
                         .collect(),
                 ),
+                deduplicate: false,
+                curriculum: None,
             };
 
             let multi_config_json = serde_json::to_string(&multi_config).unwrap();
@@ -4,14 +4,28 @@ use crate::{
 };
 use anyhow::{Result, anyhow};
 use psyche_core::{BatchId, ClosedInterval, Shuffle};
-use rand::{Rng, SeedableRng};
+use rand::{Rng, SeedableRng, seq::SliceRandom};
 use rand_chacha::ChaCha8Rng;
+use rayon::{
+    iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator},
+    slice::{ParallelSlice, ParallelSliceMut},
+};
+use std::collections::HashSet;
+use twox_hash::XxHash64;
 
 pub mod http;
 pub struct WeightedDataProvider<T: TokenizedDataProvider + LengthKnownDataProvider> {
     providers: Vec<T>,
     dataset_index: Vec<usize>,
     dataset_sample_index: Vec<u64>,
+    shuffle_kind: Shuffle,
+    duplicates: HashSet<(usize, u64)>,
+    /// `(min_step, weights)` pairs, sorted by `min_step`
+    curriculum: Vec<(u32, Vec<f64>)>,
+    /// index into `curriculum` of the weights the index was last built with
+    curriculum_stage: Option<usize>,
+    /// one past the highest index fetched through this provider so far
+    current_pos: usize,
 }
 
 pub enum Providers<T: TokenizedDataProvider + LengthKnownDataProvider> {
@@ -55,11 +69,17 @@ impl<T: TokenizedDataProvider + LengthKnownDataProvider> Providers<T> {
 }
 
 impl<T: TokenizedDataProvider + LengthKnownDataProvider> WeightedDataProvider<T> {
-    pub fn new(weighted_providers: impl Into<Providers<T>>, shuffle_kind: Shuffle) -> Self {
+    /// If `deduplicate` is set, every sample of every provider is fetched and hashed up front,
+    /// and samples whose tokens exactly match an earlier sample are left out of the index.
+    pub async fn new(
+        weighted_providers: impl Into<Providers<T>>,
+        shuffle_kind: Shuffle,
+        deduplicate: bool,
+    ) -> Result<Self> {
         let weighted_providers = weighted_providers.into();
         // normalize weights if provided, otherwise use dataset lengths as weights
         let weights = weighted_providers.weights();
-        let providers = weighted_providers.providers();
+        let mut providers = weighted_providers.providers();
         assert_eq!(
             providers.len(),
             weights.len(),
@@ -76,8 +96,12 @@ impl<T: TokenizedDataProvider + LengthKnownDataProvider> WeightedDataProvider<T>
             build_weighted_index(samples_per_epoch, &weights, &dataset_lengths);
 
         if let Shuffle::Seeded(random_seed) = shuffle_kind {
-            let mut rng = ChaCha8Rng::from_seed(random_seed);
-            shuffle(&mut dataset_index, &mut dataset_sample_index, &mut rng);
+            shuffle_weighted_index(
+                &mut dataset_index,
+                &mut dataset_sample_index,
+                weights.len(),
+                random_seed,
+            );
         }
 
         let mut full_dataset_index = Vec::with_capacity(num_samples);
@@ -92,13 +116,195 @@ impl<T: TokenizedDataProvider + LengthKnownDataProvider> WeightedDataProvider<T>
         full_dataset_index.truncate(num_samples);
         full_dataset_sample_index.truncate(num_samples);
 
-        tracing::info!(num_samples = num_samples, "Created weighted data provider",);
+        let mut duplicates = HashSet::new();
+        if deduplicate {
+            duplicates = find_duplicate_samples(&mut providers).await?;
+            let (dataset_index, dataset_sample_index): (Vec<_>, Vec<_>) = full_dataset_index
+                .into_iter()
+                .zip(full_dataset_sample_index)
+                .filter(|sample| !duplicates.contains(sample))
+                .unzip();
+            tracing::info!(
+                duplicate_samples = duplicates.len(),
+                removed_entries = num_samples - dataset_index.len(),
+                "Removed duplicate samples from weighted data provider",
+            );
+            full_dataset_index = dataset_index;
+            full_dataset_sample_index = dataset_sample_index;
+        }
+
+        tracing::info!(
+            num_samples = full_dataset_index.len(),
+            "Created weighted data provider",
+        );
 
-        Self {
+        Ok(Self {
             providers,
             dataset_index: full_dataset_index,
             dataset_sample_index: full_dataset_sample_index,
+            shuffle_kind,
+            duplicates,
+            curriculum: Vec::new(),
+            curriculum_stage: None,
+            current_pos: 0,
+        })
+    }
+
+    /// Changes the provider weights as training progresses.
+    /// Each `(min_step, weights)` pair applies from `min_step` until the next pair's `min_step`,
+    /// with weights normalized like [`Providers::ExplicitlyWeighted`].
+    /// Until the first `min_step` is reached, the weights the provider was created with are used.
+    pub fn with_curriculum(mut self, schedule: Vec<(u32, Vec<f64>)>) -> Result<Self> {
+        let mut curriculum: Vec<(u32, Vec<f64>)> = schedule
+            .into_iter()
+            .map(|(min_step, weights)| {
+                self.check_weights(&weights)
+                    .map_err(|err| anyhow!("Curriculum stage at step {min_step}: {err}"))?;
+                Ok((min_step, normalize(&weights)))
+            })
+            .collect::<Result<_>>()?;
+        curriculum.sort_by_key(|(min_step, _)| *min_step);
+        self.curriculum = curriculum;
+        self.set_step(0, |_| 0);
+        Ok(self)
+    }
+
+    /// Moves the curriculum to `step`. `data_index_for_step` gives the index of the first
+    /// sample of a step.
+    /// If this enters a new stage of the curriculum, every sample from that stage's first step
+    /// onwards is redrawn with the new weights, and true is returned. Samples from earlier steps
+    /// are left as they are.
+    /// Every stage passed along the way is applied in order, so the index only depends on
+    /// `step`, regardless of which steps this was called for before.
+    pub fn set_step(&mut self, step: u32, data_index_for_step: impl Fn(u32) -> u64) -> bool {
+        let Some(stage) = self
+            .curriculum
+            .iter()
+            .rposition(|(min_step, _)| *min_step <= step)
+        else {
+            return false;
+        };
+        let first_new_stage = match self.curriculum_stage {
+            Some(current) if current >= stage => return false,
+            Some(current) => current + 1,
+            None => 0,
+        };
+
+        for stage in first_new_stage..=stage {
+            let (min_step, weights) = self.curriculum[stage].clone();
+            let data_index = data_index_for_step(min_step);
+            self.rebuild_index_from(data_index as usize, &weights);
+            tracing::info!(
+                step,
+                min_step,
+                data_index,
+                ?weights,
+                "Entered new curriculum stage in weighted data provider",
+            );
         }
+        self.curriculum_stage = Some(stage);
+        true
+    }
+
+    /// Changes the provider weights for every sample after the last one fetched so far,
+    /// leaving the samples up to it as they are. Weights are normalized like
+    /// [`Providers::ExplicitlyWeighted`].
+    /// Clients only fetch their own batches, so their positions differ; in a distributed run
+    /// use [`Self::with_curriculum`], which reweights at the same data index on every client.
+    pub fn reweight(&mut self, weights: &[f64]) -> Result<()> {
+        self.check_weights(weights)?;
+        let weights = normalize(weights);
+        self.rebuild_index_from(self.current_pos, &weights);
+        tracing::info!(
+            data_index = self.current_pos,
+            ?weights,
+            "Reweighted weighted data provider",
+        );
+        Ok(())
+    }
+
+    /// Checks that `weights` has one entry per provider and can be normalized
+    fn check_weights(&self, weights: &[f64]) -> Result<()> {
+        if weights.len() != self.providers.len() {
+            return Err(anyhow!(
+                "Got {} weights for {} providers",
+                weights.len(),
+                self.providers.len()
+            ));
+        }
+        if weights.iter().any(|w| !w.is_finite() || *w < 0.0) || weights.iter().sum::<f64>() <= 0.0
+        {
+            return Err(anyhow!(
+                "Weights must be non-negative with a positive sum, got {weights:?}"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Redraws every sample from `start` onwards using `weights`, preferring samples that
+    /// weren't drawn before `start`.
+    fn rebuild_index_from(&mut self, start: usize, weights: &[f64]) {
+        let start = start.min(self.dataset_index.len());
+        let remaining = self.dataset_index.len() - start;
+        let dataset_lengths: Vec<usize> =
+            self.providers.iter().map(|p| p.num_sequences()).collect();
+
+        let num_providers = self.providers.len();
+        let drawn: Vec<HashSet<u64>> = self.dataset_index[..start]
+            .par_iter()
+            .zip(&self.dataset_sample_index[..start])
+            .fold(
+                || vec![HashSet::new(); num_providers],
+                |mut drawn, (&provider_idx, &sample_idx)| {
+                    drawn[provider_idx].insert(sample_idx);
+                    drawn
+                },
+            )
+            .reduce(
+                || vec![HashSet::new(); num_providers],
+                |mut drawn, other| {
+                    for (drawn, other) in drawn.iter_mut().zip(other) {
+                        drawn.extend(other);
+                    }
+                    drawn
+                },
+            );
+        // for each provider, the samples not drawn yet, then every sample over and over
+        let mut next_samples: Vec<_> = dataset_lengths
+            .iter()
+            .zip(drawn)
+            .map(|(&len, drawn)| {
+                let len = len as u64;
+                (0..len)
+                    .filter(move |sample_idx| !drawn.contains(sample_idx))
+                    .chain((0..len).cycle())
+            })
+            .collect();
+
+        let (mut dataset_index, _) = build_weighted_index(remaining, weights, &dataset_lengths);
+        let mut dataset_sample_index: Vec<u64> = dataset_index
+            .iter()
+            .map(|&provider_idx| next_samples[provider_idx].next().unwrap_or_default())
+            .collect();
+
+        if let Shuffle::Seeded(random_seed) = self.shuffle_kind {
+            let mut rng = ChaCha8Rng::from_seed(random_seed);
+            // a different stream per rebuild point, so the redrawn samples aren't shuffled
+            // the same way as the initial index
+            rng.set_stream(start as u64);
+            shuffle(&mut dataset_index, &mut dataset_sample_index, &mut rng);
+        }
+
+        let (dataset_index, dataset_sample_index): (Vec<_>, Vec<_>) = dataset_index
+            .into_iter()
+            .zip(dataset_sample_index)
+            .filter(|sample| !self.duplicates.contains(sample))
+            .unzip();
+
+        self.dataset_index.truncate(start);
+        self.dataset_index.extend(dataset_index);
+        self.dataset_sample_index.truncate(start);
+        self.dataset_sample_index.extend(dataset_sample_index);
     }
 
     fn get_sample_info(&self, index: u64) -> (usize, u64) {
@@ -170,10 +376,43 @@ impl<T: TokenizedDataProvider + LengthKnownDataProvider + Send> TokenizedDataPro
             return Err(anyhow!("Failed to get all requested samples"));
         }
 
+        self.current_pos = self.current_pos.max(data_ids.0.end as usize + 1);
         Ok(results)
     }
 }
 
+const DEDUPLICATION_BATCH_SIZE: u64 = 1024;
+
+/// Returns the (provider index, sample index) of every sample whose tokens match an earlier one,
+/// in provider order then sample order.
+async fn find_duplicate_samples<T: TokenizedDataProvider + LengthKnownDataProvider>(
+    providers: &mut [T],
+) -> Result<HashSet<(usize, u64)>> {
+    let mut seen = HashSet::new();
+    let mut duplicates = HashSet::new();
+    for (provider_idx, provider) in providers.iter_mut().enumerate() {
+        let num_sequences = provider.num_sequences() as u64;
+        for start in (0..num_sequences).step_by(DEDUPLICATION_BATCH_SIZE as usize) {
+            let end = (start + DEDUPLICATION_BATCH_SIZE).min(num_sequences) - 1;
+            let samples = provider
+                .get_samples(BatchId(ClosedInterval::new(start, end)))
+                .await?;
+            let hashes: Vec<u64> = samples
+                .par_iter()
+                .map(|sample| XxHash64::oneshot(0, bytemuck::cast_slice(&sample.input_ids)))
+                .collect();
+            // hashes are checked in a fixed order (rather than in the parallel loop)
+            // so every client agrees on which copy of a sample is kept.
+            for (sample_idx, hash) in (start..).zip(hashes) {
+                if !seen.insert(hash) {
+                    duplicates.insert((provider_idx, sample_idx));
+                }
+            }
+        }
+    }
+    Ok(duplicates)
+}
+
 fn normalize(weights: &[f64]) -> Vec<f64> {
     let sum: f64 = weights.iter().sum();
     weights.iter().map(|w| w / sum).collect()
@@ -199,7 +438,8 @@ fn build_weighted_index(
         let mut max_error = f64::NEG_INFINITY;
         let mut chosen_provider_idx = 0;
         for i in 0..num_providers {
-            if dataset_sizes[i] == 0 {
+            // a zero weight would still win ties, e.g. when a curriculum stage drops a provider
+            if dataset_sizes[i] == 0 || weights[i] == 0.0 {
                 continue;
             }
             let error = weights[i] * sample_idx_float - total_samples_drawn[i] as f64;
@@ -233,6 +473,81 @@ fn build_weighted_index(
     (dataset_index, dataset_sample_index)
 }
 
+/// Shuffles an index built by [`build_weighted_index`]. Each dataset's draws are shuffled among
+/// themselves on their own rayon task, seeded with `seed` xor the dataset's index, and the order
+/// datasets are drawn in is shuffled separately. Every position then takes the next of its
+/// dataset's shuffled draws, filled in parallel a chunk at a time.
+fn shuffle_weighted_index(
+    dataset_index: &mut [usize],
+    dataset_sample_index: &mut [u64],
+    num_providers: usize,
+    seed: [u8; 32],
+) {
+    const CHUNK_SIZE: usize = 1 << 16;
+
+    let mut draws = vec![Vec::new(); num_providers];
+    for (&provider_idx, &sample_idx) in dataset_index.iter().zip(dataset_sample_index.iter()) {
+        draws[provider_idx].push(sample_idx);
+    }
+    rayon::scope(|scope| {
+        for (provider_idx, draws) in draws.iter_mut().enumerate() {
+            scope.spawn(move |_| {
+                draws.shuffle(&mut ChaCha8Rng::from_seed(dataset_seed(seed, provider_idx)));
+            });
+        }
+    });
+
+    let mut rng = ChaCha8Rng::from_seed(seed);
+    // dataset 0's draws were shuffled with this same seed, so use another stream
+    rng.set_stream(u64::MAX);
+    dataset_index.shuffle(&mut rng);
+
+    // how many draws of each dataset the chunks before each chunk use up
+    let chunk_counts: Vec<Vec<usize>> = dataset_index
+        .par_chunks(CHUNK_SIZE)
+        .map(|chunk| {
+            let mut counts = vec![0; num_providers];
+            for &provider_idx in chunk {
+                counts[provider_idx] += 1;
+            }
+            counts
+        })
+        .collect();
+    let mut used = vec![0; num_providers];
+    let chunk_offsets: Vec<Vec<usize>> = chunk_counts
+        .into_iter()
+        .map(|counts| {
+            let offsets = used.clone();
+            for (used, count) in used.iter_mut().zip(counts) {
+                *used += count;
+            }
+            offsets
+        })
+        .collect();
+
+    dataset_sample_index
+        .par_chunks_mut(CHUNK_SIZE)
+        .zip(dataset_index.par_chunks(CHUNK_SIZE))
+        .zip(chunk_offsets)
+        .for_each(|((sample_indices, provider_indices), mut next)| {
+            for (sample_idx, &provider_idx) in sample_indices.iter_mut().zip(provider_indices) {
+                *sample_idx = draws[provider_idx][next[provider_idx]];
+                next[provider_idx] += 1;
+            }
+        });
+}
+
+fn dataset_seed(seed: [u8; 32], dataset_idx: usize) -> [u8; 32] {
+    let mut dataset_seed = seed;
+    for (byte, index_byte) in dataset_seed
+        .iter_mut()
+        .zip((dataset_idx as u64).to_le_bytes())
+    {
+        *byte ^= index_byte;
+    }
+    dataset_seed
+}
+
 fn shuffle<T: Rng>(dataset_index: &mut [usize], dataset_sample_index: &mut [u64], rng: &mut T) {
     let n = dataset_index.len();
     for i in (1..n).rev() {
@@ -1,8 +1,8 @@
-use std::path::PathBuf;
+use std::{io::Write, path::PathBuf};
 
 use pretty_assertions::assert_eq;
 use psyche_core::{BatchId, Shuffle, TokenSize};
-use psyche_data_provider::{LocalDataProvider, TokenizedDataProvider};
+use psyche_data_provider::{LengthKnownDataProvider, LocalDataProvider, TokenizedDataProvider};
 use tokenizers::Tokenizer;
 use tokio::fs::read_to_string;
 
@@ -101,3 +101,131 @@ async fn loads_fineweb_subset() {
         );
     }
 }
+
+#[tokio::test]
+async fn loads_jsonl_and_jsonl_gz() {
+    let dir = tempfile::tempdir().unwrap();
+    let lines = |offset: i32| {
+        (0..4)
+            .map(|i| {
+                let ids = [offset + i * 2, offset + i * 2 + 1];
+                serde_json::json!({ "text": "ignored", "ids": ids }).to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    std::fs::write(dir.path().join("a.jsonl"), lines(0)).unwrap();
+    let mut gz = flate2::write::GzEncoder::new(
+        std::fs::File::create(dir.path().join("b.jsonl.gz")).unwrap(),
+        flate2::Compression::default(),
+    );
+    gz.write_all(lines(100).as_bytes()).unwrap();
+    gz.finish().unwrap();
+
+    let mut data_loader = LocalDataProvider::new_from_directory_with_jsonl_field(
+        dir.path(),
+        TokenSize::TwoBytes,
+        4,
+        Shuffle::DontShuffle,
+        "ids",
+    )
+    .unwrap();
+    // each file packs 8 tokens, the last window of a file is left out
+    assert_eq!(data_loader.num_sequences(), 2);
+
+    let mut samples = data_loader
+        .get_samples(BatchId((0, 1).into()))
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|sample| sample.input_ids)
+        .collect::<Vec<_>>();
+    samples.sort();
+    assert_eq!(samples, vec![vec![0, 1, 2, 3], vec![100, 101, 102, 103]]);
+
+    assert!(
+        LocalDataProvider::new_from_directory(
+            dir.path(),
+            TokenSize::TwoBytes,
+            4,
+            Shuffle::DontShuffle
+        )
+        .is_err(),
+        "lines have no \"tokens\" field"
+    );
+}
+
+#[tokio::test]
+async fn cache_serves_repeated_reads() {
+    let data_dir = test_path(&["resources", "dolma", "data"]);
+    let uncached = LocalDataProvider::new_from_directory(
+        &data_dir,
+        TokenSize::TwoBytes,
+        2048,
+        Shuffle::Seeded(SEED),
+    )
+    .unwrap();
+    let mut cached = LocalDataProvider::new_from_directory(
+        &data_dir,
+        TokenSize::TwoBytes,
+        2048,
+        Shuffle::Seeded(SEED),
+    )
+    .unwrap()
+    .with_cache(2);
+    assert_eq!(cached.cache_hit_ratio(), None);
+
+    let first = cached.get_samples(BatchId((0, 1).into())).await.unwrap();
+    assert_eq!(cached.cache_hit_ratio(), Some(0.0));
+    let second = cached.get_samples(BatchId((0, 1).into())).await.unwrap();
+    assert_eq!(cached.cache_hit_ratio(), Some(0.5));
+    assert_eq!(first, second);
+
+    let expected: Vec<_> = uncached.into_iter().take(2).collect();
+    assert_eq!(second, expected);
+    assert_eq!(
+        LocalDataProvider::new_from_directory(
+            &data_dir,
+            TokenSize::TwoBytes,
+            2048,
+            Shuffle::DontShuffle
+        )
+        .unwrap()
+        .with_cache(0)
+        .cache_hit_ratio(),
+        None
+    );
+}
+
+#[tokio::test]
+async fn mmap_reads_indexed_sequences() {
+    let dir = tempfile::tempdir().unwrap();
+    let tokens: Vec<u16> = (0..10).collect();
+    std::fs::write(dir.path().join("a.bin"), bytemuck::cast_slice(&tokens)).unwrap();
+    // sequences of 3, 5 and 2 tokens
+    let offsets: [u64; 3] = [0, 6, 16];
+    std::fs::write(dir.path().join("a.index"), bytemuck::cast_slice(&offsets)).unwrap();
+    // files without an index are left out
+    std::fs::write(dir.path().join("b.bin"), bytemuck::cast_slice(&tokens)).unwrap();
+
+    let mut data_loader =
+        LocalDataProvider::mmap(dir.path(), TokenSize::TwoBytes, Shuffle::DontShuffle).unwrap();
+    assert_eq!(data_loader.num_sequences(), 3);
+    let samples = data_loader
+        .get_samples(BatchId((0, 2).into()))
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|sample| sample.input_ids)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        samples,
+        vec![vec![0, 1, 2], vec![3, 4, 5, 6, 7], vec![8, 9]]
+    );
+
+    // an offset in the middle of a token
+    std::fs::write(dir.path().join("a.index"), bytemuck::cast_slice(&[0u64, 3])).unwrap();
+    assert!(
+        LocalDataProvider::mmap(dir.path(), TokenSize::TwoBytes, Shuffle::DontShuffle).is_err()
+    );
+}
@@ -0,0 +1,165 @@
+use std::{path::PathBuf, sync::Arc};
+
+use pretty_assertions::assert_eq;
+use psyche_core::{BatchId, ClosedInterval, Shuffle};
+use psyche_data_provider::{
+    LengthKnownDataProvider, StreamingDataProvider, TokenizedDataProvider, WeightedDataProvider,
+};
+use tokenizers::Tokenizer;
+
+const SEQ_LEN: usize = 16;
+
+fn test_path(path: &[&str]) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "tests"]
+        .iter()
+        .chain(path)
+        .collect()
+}
+
+fn tokenizer() -> Arc<Tokenizer> {
+    Arc::new(
+        Tokenizer::from_file(test_path(&["resources", "llama2_tokenizer.json"]))
+            .expect("tokenizer json exists"),
+    )
+}
+
+fn documents() -> Vec<String> {
+    (0..20)
+        .map(|i| format!("Document number {i} talks about the weather, and how it's nice today."))
+        .collect()
+}
+
+/// writes the first half of the documents as .txt files, the rest as a single .jsonl file
+fn write_dataset(dir: &std::path::Path) {
+    let documents = documents();
+    let (txt, jsonl) = documents.split_at(documents.len() / 2);
+    for (i, doc) in txt.iter().enumerate() {
+        std::fs::write(dir.join(format!("a{i:02}.txt")), doc).unwrap();
+    }
+    let jsonl = jsonl
+        .iter()
+        .map(|doc| serde_json::json!({ "text": doc, "id": 1 }).to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+    std::fs::write(dir.join("b.jsonl"), jsonl).unwrap();
+}
+
+/// every file is windowed on its own, in file name order
+fn expected_windows(tokenizer: &Tokenizer) -> Vec<Vec<i32>> {
+    let documents = documents();
+    let (txt, jsonl) = documents.split_at(documents.len() / 2);
+    let tokenize = |doc: &String| -> Vec<i32> {
+        tokenizer
+            .encode(doc.as_str(), true)
+            .unwrap()
+            .get_ids()
+            .iter()
+            .map(|&id| id as i32)
+            .collect()
+    };
+    let files = txt
+        .iter()
+        .map(tokenize)
+        .chain(std::iter::once(jsonl.iter().flat_map(tokenize).collect()));
+    files
+        .flat_map(|tokens: Vec<i32>| {
+            tokens
+                .chunks_exact(SEQ_LEN)
+                .map(|chunk| chunk.to_vec())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn streams_fixed_length_windows_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    write_dataset(dir.path());
+    let tokenizer = tokenizer();
+    let expected = expected_windows(&tokenizer);
+
+    let mut provider =
+        StreamingDataProvider::new_from_directory(dir.path(), tokenizer, SEQ_LEN, 2).unwrap();
+    assert_eq!(provider.num_sequences(), expected.len());
+
+    let samples = provider
+        .get_samples(BatchId(ClosedInterval::new(0, expected.len() as u64 - 1)))
+        .await
+        .unwrap();
+    let samples: Vec<Vec<i32>> = samples.into_iter().map(|s| s.input_ids).collect();
+    assert_eq!(samples, expected);
+}
+
+#[tokio::test]
+async fn rereading_restarts_and_reading_past_the_end_wraps() {
+    let dir = tempfile::tempdir().unwrap();
+    write_dataset(dir.path());
+    let tokenizer = tokenizer();
+    let expected = expected_windows(&tokenizer);
+
+    let mut provider =
+        StreamingDataProvider::new_from_directory(dir.path(), tokenizer, SEQ_LEN, 2).unwrap();
+
+    let third = provider
+        .get_samples(BatchId(ClosedInterval::new(2, 2)))
+        .await
+        .unwrap();
+    let first = provider
+        .get_samples(BatchId(ClosedInterval::new(0, 0)))
+        .await
+        .unwrap();
+    assert_eq!(third[0].input_ids, expected[2]);
+    assert_eq!(first[0].input_ids, expected[0]);
+
+    let past_the_end = expected.len() as u64 + 1;
+    let wrapped = provider
+        .get_samples(BatchId(ClosedInterval::new(past_the_end, past_the_end)))
+        .await
+        .unwrap();
+    assert_eq!(wrapped[0].input_ids, expected[1]);
+    assert_eq!(provider.num_sequences(), expected.len());
+}
+
+#[tokio::test]
+async fn works_with_weighted_provider() {
+    let tokenizer = tokenizer();
+    let dirs: Vec<_> = (0..2).map(|_| tempfile::tempdir().unwrap()).collect();
+    let providers = dirs
+        .iter()
+        .map(|dir| {
+            write_dataset(dir.path());
+            StreamingDataProvider::new_from_directory(dir.path(), tokenizer.clone(), SEQ_LEN, 4)
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    let mut weighted = WeightedDataProvider::new(providers, Shuffle::DontShuffle, false)
+        .await
+        .unwrap();
+    let samples = weighted
+        .get_samples(BatchId(ClosedInterval::new(0, 7)))
+        .await
+        .unwrap();
+    assert_eq!(samples.len(), 8);
+    assert!(samples.iter().all(|s| s.input_ids.len() == SEQ_LEN));
+}
+
+#[tokio::test]
+async fn seeks_to_the_file_holding_a_sample() {
+    let dir = tempfile::tempdir().unwrap();
+    write_dataset(dir.path());
+    let tokenizer = tokenizer();
+    let expected = expected_windows(&tokenizer);
+
+    let mut provider =
+        StreamingDataProvider::new_from_directory(dir.path(), tokenizer, SEQ_LEN, 2).unwrap();
+
+    // jump straight into the .jsonl file, then back into one of the .txt files.
+    for index in [expected.len() - 1, expected.len() - 3, 4, 5, 0] {
+        let sample = provider
+            .get_samples(BatchId(ClosedInterval::new(index as u64, index as u64)))
+            .await
+            .unwrap();
+        assert_eq!(sample[0].input_ids, expected[index]);
+    }
+}
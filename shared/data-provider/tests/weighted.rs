@@ -61,7 +61,9 @@ async fn test_weighted_data_provider_equal_weights() -> Result<()> {
     let mut weighted_provider = WeightedDataProvider::new(
         vec![(provider1, 0.5), (provider2, 0.5)],
         Shuffle::Seeded(TEST_SEED),
-    );
+        false,
+    )
+    .await?;
     assert_eq!(weighted_provider.num_sequences(), 200);
 
     let batch_id = BatchId(ClosedInterval { start: 0, end: 99 }); // get 100 samples
@@ -96,7 +98,9 @@ async fn test_weighted_data_provider_unequal_weights() -> Result<()> {
     let mut weighted_provider = WeightedDataProvider::new(
         vec![(provider1, 0.75), (provider2, 0.25)],
         Shuffle::Seeded(TEST_SEED),
-    );
+        false,
+    )
+    .await?;
     assert_eq!(weighted_provider.num_sequences(), 200);
 
     let batch_id = BatchId(ClosedInterval { start: 0, end: 99 });
@@ -125,8 +129,12 @@ async fn test_weighted_data_provider_auto_weights() -> Result<()> {
     let provider1 = MockDataProvider::new(1, 100, vec![0, 1, 2, 3]);
     let provider2 = MockDataProvider::new(2, 300, vec![0, 1, 2, 3]); // 3x larger
 
-    let mut weighted_provider =
-        WeightedDataProvider::new(vec![provider1, provider2], Shuffle::Seeded(TEST_SEED));
+    let mut weighted_provider = WeightedDataProvider::new(
+        vec![provider1, provider2],
+        Shuffle::Seeded(TEST_SEED),
+        false,
+    )
+    .await?;
     assert_eq!(weighted_provider.num_sequences(), 400);
 
     let batch_id = BatchId(ClosedInterval { start: 0, end: 99 });
@@ -162,7 +170,9 @@ async fn test_weighted_data_provider_consistency() -> Result<()> {
     let mut weighted_provider1 = WeightedDataProvider::new(
         vec![(provider1, 0.5), (provider2, 0.5)],
         Shuffle::Seeded(seed),
-    );
+        false,
+    )
+    .await?;
     assert_eq!(weighted_provider1.num_sequences(), 200);
 
     let batch_id = BatchId(ClosedInterval { start: 0, end: 9 });
@@ -174,7 +184,9 @@ async fn test_weighted_data_provider_consistency() -> Result<()> {
     let mut weighted_provider2 = WeightedDataProvider::new(
         vec![(provider3, 0.5), (provider4, 0.5)],
         Shuffle::Seeded(seed),
-    );
+        false,
+    )
+    .await?;
     assert_eq!(weighted_provider2.num_sequences(), 200);
 
     let samples2 = weighted_provider2.get_samples(batch_id).await?;
@@ -191,7 +203,9 @@ async fn test_weighted_data_provider_with_dummy_provider() -> Result<()> {
     let mut weighted_provider = WeightedDataProvider::new(
         vec![(dummy1, 0.5), (dummy2, 0.5)],
         Shuffle::Seeded(TEST_SEED),
-    );
+        false,
+    )
+    .await?;
     assert_eq!(weighted_provider.num_sequences(), 100);
 
     let batch_id = BatchId(ClosedInterval { start: 0, end: 9 });
@@ -213,7 +227,9 @@ async fn test_weighted_data_provider_multi_batch() -> Result<()> {
     let mut weighted_provider = WeightedDataProvider::new(
         vec![(provider1, 0.5), (provider2, 0.5)],
         Shuffle::Seeded(TEST_SEED),
-    );
+        false,
+    )
+    .await?;
     assert_eq!(weighted_provider.num_sequences(), 200);
 
     let batch1 = BatchId(ClosedInterval { start: 0, end: 9 });
@@ -242,7 +258,9 @@ async fn test_weighted_data_provider_exhaustive() -> Result<()> {
     let mut weighted_provider = WeightedDataProvider::new(
         vec![(provider1, 0.5), (provider2, 0.5)],
         Shuffle::Seeded(TEST_SEED),
-    );
+        false,
+    )
+    .await?;
     assert_eq!(weighted_provider.num_sequences(), 10);
 
     let batch = BatchId(ClosedInterval { start: 0, end: 9 });
@@ -268,6 +286,45 @@ async fn test_weighted_data_provider_exhaustive() -> Result<()> {
     Ok(())
 }
 
+/// The first token of every sample of one epoch over three providers of different sizes
+async fn first_epoch_tokens(shuffle: Shuffle) -> Result<Vec<i32>> {
+    let mut weighted_provider = WeightedDataProvider::new(
+        vec![
+            (MockDataProvider::new(1, 5, vec![0]), 0.5),
+            (MockDataProvider::new(2, 20, vec![0]), 0.3),
+            (MockDataProvider::new(3, 40, vec![0]), 0.2),
+        ],
+        shuffle,
+        false,
+    )
+    .await?;
+    let batch = BatchId(ClosedInterval { start: 0, end: 64 });
+    Ok(weighted_provider
+        .get_samples(batch)
+        .await?
+        .iter()
+        .map(|sample| sample.input_ids[0])
+        .collect())
+}
+
+#[test(tokio::test)]
+async fn test_weighted_data_provider_shuffle_keeps_draws() -> Result<()> {
+    let mut unshuffled = first_epoch_tokens(Shuffle::DontShuffle).await?;
+    let mut shuffled = first_epoch_tokens(Shuffle::Seeded(TEST_SEED)).await?;
+    assert_ne!(shuffled, unshuffled);
+    assert_eq!(
+        shuffled,
+        first_epoch_tokens(Shuffle::Seeded(TEST_SEED)).await?
+    );
+
+    // shuffling only reorders the samples each provider was drawn for
+    shuffled.sort();
+    unshuffled.sort();
+    assert_eq!(shuffled, unshuffled);
+
+    Ok(())
+}
+
 #[test(tokio::test)]
 async fn test_weighted_data_provider_exhausts_small_dataset_before_repeat() -> Result<()> {
     let provider1 = MockDataProvider::new(1, 5, vec![0]);
@@ -281,8 +338,10 @@ async fn test_weighted_data_provider_exhausts_small_dataset_before_repeat() -> R
     let mut weighted_provider = WeightedDataProvider::new(
         vec![(provider1, 0.5), (provider2, 0.5)],
         Shuffle::DontShuffle, // use Shuffle::DontShuffle to observe the direct output of the
-                              // index generation logic without randomization
-    );
+        // index generation logic without randomization
+        false,
+    )
+    .await?;
 
     let total_samples_in_epoch = weighted_provider.num_sequences();
     assert_eq!(
@@ -390,3 +449,168 @@ async fn test_weighted_data_provider_exhausts_small_dataset_before_repeat() -> R
 
     Ok(())
 }
+
+#[test(tokio::test)]
+async fn test_weighted_data_provider_deduplicates() -> Result<()> {
+    // same id, so provider2's first 10 samples are identical to provider1's
+    let provider1 = MockDataProvider::new(1, 10, vec![0, 1, 2, 3]);
+    let provider2 = MockDataProvider::new(1, 20, vec![0, 1, 2, 3]);
+
+    let mut weighted_provider =
+        WeightedDataProvider::new(vec![provider1, provider2], Shuffle::Seeded(TEST_SEED), true)
+            .await?;
+    assert_eq!(weighted_provider.num_sequences(), 20);
+
+    let batch_id = BatchId(ClosedInterval { start: 0, end: 19 });
+    let samples = weighted_provider.get_samples(batch_id).await?;
+    let unique: std::collections::HashSet<Vec<i32>> =
+        samples.into_iter().map(|s| s.input_ids).collect();
+    assert_eq!(unique.len(), 20);
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_weighted_data_provider_curriculum() -> Result<()> {
+    let provider1 = MockDataProvider::new(1, 100, vec![0, 1, 2, 3]);
+    let provider2 = MockDataProvider::new(2, 100, vec![0, 1, 2, 3]);
+
+    // only provider 1 until step 10, then only provider 2
+    let mut weighted_provider = WeightedDataProvider::new(
+        vec![(provider1, 0.5), (provider2, 0.5)],
+        Shuffle::Seeded(TEST_SEED),
+        false,
+    )
+    .await?
+    .with_curriculum(vec![(0, vec![1.0, 0.0]), (10, vec![0.0, 1.0])])?;
+    assert_eq!(weighted_provider.num_sequences(), 200);
+
+    // 10 samples per step
+    let data_index_for_step = |step: u32| step as u64 * 10;
+    let provider_ids = |samples: Vec<TokenizedData>| {
+        samples
+            .into_iter()
+            .map(|s| s.input_ids[0] / 1000)
+            .collect::<Vec<_>>()
+    };
+
+    assert!(!weighted_provider.set_step(5, data_index_for_step));
+    let before = weighted_provider
+        .get_samples(BatchId(ClosedInterval { start: 0, end: 99 }))
+        .await?;
+    assert!(provider_ids(before.clone()).iter().all(|&id| id == 1));
+
+    assert!(weighted_provider.set_step(12, data_index_for_step));
+    assert!(!weighted_provider.set_step(13, data_index_for_step));
+    assert_eq!(weighted_provider.num_sequences(), 200);
+
+    // samples from before step 10 are untouched
+    let after = weighted_provider
+        .get_samples(BatchId(ClosedInterval { start: 0, end: 99 }))
+        .await?;
+    assert_eq!(
+        before[..100]
+            .iter()
+            .map(|s| &s.input_ids)
+            .collect::<Vec<_>>(),
+        after[..100]
+            .iter()
+            .map(|s| &s.input_ids)
+            .collect::<Vec<_>>()
+    );
+    let rest = weighted_provider
+        .get_samples(BatchId(ClosedInterval {
+            start: 100,
+            end: 199,
+        }))
+        .await?;
+    assert!(provider_ids(rest).iter().all(|&id| id == 2));
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_weighted_data_provider_rejects_bad_curriculum() -> Result<()> {
+    let new_provider = || async {
+        WeightedDataProvider::new(
+            vec![
+                MockDataProvider::new(1, 100, vec![0, 1, 2, 3]),
+                MockDataProvider::new(2, 100, vec![0, 1, 2, 3]),
+            ],
+            Shuffle::Seeded(TEST_SEED),
+            false,
+        )
+        .await
+    };
+
+    // wrong number of weights
+    assert!(
+        new_provider()
+            .await?
+            .with_curriculum(vec![(0, vec![1.0, 0.0]), (10, vec![1.0])])
+            .is_err()
+    );
+    // can't be normalized
+    assert!(
+        new_provider()
+            .await?
+            .with_curriculum(vec![(10, vec![0.0, 0.0])])
+            .is_err()
+    );
+    assert!(
+        new_provider()
+            .await?
+            .with_curriculum(vec![(10, vec![-1.0, 2.0])])
+            .is_err()
+    );
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_weighted_data_provider_reweight() -> Result<()> {
+    let provider1 = MockDataProvider::new(1, 100, vec![0, 1, 2, 3]);
+    let provider2 = MockDataProvider::new(2, 100, vec![0, 1, 2, 3]);
+
+    let mut weighted_provider = WeightedDataProvider::new(
+        vec![(provider1, 1.0), (provider2, 0.0)],
+        Shuffle::Seeded(TEST_SEED),
+        false,
+    )
+    .await?;
+    assert!(weighted_provider.reweight(&[1.0]).is_err());
+    assert!(weighted_provider.reweight(&[0.0, 0.0]).is_err());
+
+    let provider_ids = |samples: Vec<TokenizedData>| {
+        samples
+            .into_iter()
+            .map(|s| s.input_ids[0] / 1000)
+            .collect::<Vec<_>>()
+    };
+
+    let consumed = weighted_provider
+        .get_samples(BatchId(ClosedInterval { start: 0, end: 49 }))
+        .await?;
+    assert!(provider_ids(consumed.clone()).iter().all(|&id| id == 1));
+
+    weighted_provider.reweight(&[0.0, 2.0])?;
+    assert_eq!(weighted_provider.num_sequences(), 200);
+
+    // already consumed samples are unchanged, later ones come from the new weights
+    let again = weighted_provider
+        .get_samples(BatchId(ClosedInterval { start: 0, end: 49 }))
+        .await?;
+    assert_eq!(
+        consumed.iter().map(|s| &s.input_ids).collect::<Vec<_>>(),
+        again.iter().map(|s| &s.input_ids).collect::<Vec<_>>()
+    );
+    let rest = weighted_provider
+        .get_samples(BatchId(ClosedInterval {
+            start: 50,
+            end: 199,
+        }))
+        .await?;
+    assert!(provider_ids(rest).iter().all(|&id| id == 2));
+
+    Ok(())
+}
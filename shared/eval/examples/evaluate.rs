@@ -4,7 +4,8 @@ use indicatif::{ProgressBar, ProgressStyle};
 use psyche_core::RunningAverage;
 use psyche_data_provider::{download_model_from_gcs_sync, download_model_repo_sync};
 use psyche_eval::{
-    ALL_TASK_NAMES, EvalTaskOptions, Task, progress_bar_template_with_task, tasktype_from_name,
+    ALL_TASK_NAMES, AccuracyMetric, EvalTaskOptions, HumanEval, MetricComputer, Task,
+    progress_bar_template_with_task, tasktype_from_name,
 };
 use psyche_modeling::{CausalLM, auto_model_for_causal_lm_from_pretrained, auto_tokenizer};
 use std::collections::HashMap;
@@ -34,7 +35,12 @@ struct Args {
     #[arg(long)]
     gcs_prefix: Option<String>,
 
-    #[arg(long, default_value_t = ALL_TASK_NAMES.join(","))]
+    /// Defaults to every task except HumanEval, which runs generated code and has to be asked for.
+    #[arg(long, default_value_t = ALL_TASK_NAMES
+        .into_iter()
+        .filter(|name| *name != HumanEval::name())
+        .collect::<Vec<_>>()
+        .join(","))]
     tasks: String,
 
     #[arg(long)]
@@ -52,6 +58,10 @@ struct Args {
     #[arg(long, default_value_t = 1)]
     data_parallelism: usize,
 
+    /// Also report the fraction of answers that exactly match the expected one, per worker
+    #[arg(long, default_value_t = false)]
+    exact_match: bool,
+
     #[cfg(feature = "python")]
     #[clap(long)]
     python: bool,
@@ -155,6 +165,7 @@ fn main() -> Result<()> {
         args.quiet,
         args.seed,
         args.limit,
+        args.exact_match,
         python,
         python_arch,
     )?;
@@ -170,6 +181,7 @@ fn run_data_parallel(
     quiet: bool,
     seed: u64,
     limit: Option<usize>,
+    exact_match: bool,
     python: bool,
     python_arch: String,
 ) -> Result<()> {
@@ -270,7 +282,7 @@ fn run_data_parallel(
             for (task_idx, (task_name, num_fewshot, seed)) in task_info.into_iter().enumerate() {
                 let task_type = tasktype_from_name(&task_name)?;
                 let task = Task::new(task_type, num_fewshot, seed + task_idx as u64);
-                let _ = task.prepare(&tokenizer, None).run(
+                let result = task.prepare(&tokenizer, None).run(
                     EvalTaskOptions {
                         model: model.as_mut(),
                         skip_and_step_by: Some((gpu_id, threads)),
@@ -278,9 +290,18 @@ fn run_data_parallel(
                         cancel: None,
                         limit,
                         shared_progress_bar: shared_progress_bars[task_idx].clone(),
+                        metric_computer: exact_match
+                            .then(|| Box::new(AccuracyMetric) as Box<dyn MetricComputer>),
+                        num_shots: num_fewshot,
+                        // not evaluating during training
+                        eval_step: 0,
                     },
                     !quiet,
                 );
+                // computed metrics only cover the documents this worker evaluated
+                if let Some(exact_match) = result.scores.get("exact_match") {
+                    println!("{task_name} (worker {gpu_id}): exact_match {exact_match:.4}");
+                }
             }
 
             Ok(())
@@ -1,12 +1,15 @@
-use crate::traits::{Document, GenerateUntilTask, LogLikelihoodTask};
+use crate::metrics::MetricComputer;
+use crate::traits::{
+    CompletionChecker, Document, GenerateUntilTask, LogLikelihoodTask, PromptFormatter,
+};
 use crate::{
-    ASCII_UPPERCASE, ArcChallenge, ArcEasy, BoolQ, Hellaswag, MMLU, MMLUCF, MMLUPro, OpenbookQA,
-    PIQA,
+    ArcChallenge, ArcEasy, BoolQ, GSM8K, Hellaswag, LAMBADA, MMLU, MMLUCF, MMLUPro, OpenbookQA,
+    PIQA, Perplexity, TruthfulQA, WinoGrande,
 };
 use indicatif::{ProgressBar, ProgressStyle};
 use psyche_core::RunningAverage;
 use psyche_modeling::{CausalLM, LogitsProcessor, Sampling};
-use rand::{SeedableRng, seq::SliceRandom};
+use rand::{SeedableRng, seq::index};
 use rand_chacha::ChaCha8Rng;
 use regex::Regex;
 use std::sync::RwLock;
@@ -15,7 +18,7 @@ use tch::{Kind, Tensor};
 use tokenizers::Tokenizer;
 use tokio_util::sync::CancellationToken;
 use tracing::info;
-const GENERATE_UNTIL_MAX_TOKENS: usize = 1024;
+pub(crate) const GENERATE_UNTIL_MAX_TOKENS: usize = 1024;
 
 pub const PROGRESS_BAR_TEMPLATE: &str =
     "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {msg}";
@@ -45,22 +48,21 @@ const TASKS_WITH_ACC_UNCOND: [&str; 4] = [
 pub enum TaskType {
     LogLikelihood(Box<dyn LogLikelihoodTask>),
     GenerateUntil(Box<dyn GenerateUntilTask>),
+    Perplexity(Perplexity),
 }
 
 pub struct Task {
     task_type: TaskType,
     pub num_fewshot: usize,
-    rand: ChaCha8Rng,
+    random_seed: u64,
 }
 
 impl Task {
     pub fn new(task_type: TaskType, num_fewshot: usize, random_seed: u64) -> Self {
-        let mut seed = [0u8; 32];
-        seed[24..32].copy_from_slice(&random_seed.to_be_bytes());
         Task {
             task_type,
             num_fewshot,
-            rand: ChaCha8Rng::from_seed(seed),
+            random_seed,
         }
     }
 }
@@ -70,6 +72,7 @@ impl Display for Task {
         match &self.task_type {
             TaskType::LogLikelihood(x) => write!(f, "{x}"),
             TaskType::GenerateUntil(x) => write!(f, "{x}"),
+            TaskType::Perplexity(x) => write!(f, "{x}"),
         }
     }
 }
@@ -77,16 +80,31 @@ impl Display for Task {
 #[derive(Debug)]
 enum PreparedTaskType {
     LogLikelihood {
-        docs: Vec<TokenizedLLHDocument>,
+        docs: FewshotDocuments,
+        preambles: HashMap<String, String>,
+        tokenizer: Tokenizer,
     },
     GenerateUntil {
-        requests: Vec<TokenizedGenerateUntilDocument>,
+        docs: FewshotDocuments,
+        // normalized expected answer of each document
+        answers: Vec<String>,
+        format_prompt: PromptFormatter,
         tokenizer: Tokenizer,
         // Since a single GenerateUntil request can take a long time to generate a answer, we cache the generated tokens
-        // in case the task gets interrupted, so next time we can resume from where we left off.
-        cache: Arc<RwLock<HashMap<usize, Vec<u32>>>>,
+        // (and the eval step their prompt was built for) in case the task gets interrupted, so next time we can resume
+        // from where we left off.
+        cache: Arc<RwLock<HashMap<usize, (u32, Vec<u32>)>>>,
         stop_tokens: Vec<String>,
         answer_extraction_regex: Regex,
+        normalize_answer: fn(&str) -> String,
+        max_generated_tokens: usize,
+        check_completion: Option<CompletionChecker>,
+    },
+    Perplexity {
+        tokens: Vec<i64>,
+        stride: usize,
+        // to turn the mean NLL per token into bits per character
+        tokens_per_char: f64,
     },
 }
 
@@ -95,6 +113,8 @@ pub struct PreparedTask {
     prepared_task_type: PreparedTaskType,
     name: String,
     pub num: usize,
+    /// Few-shot examples the task was configured with, to run it with as [`EvalTaskOptions::num_shots`]
+    pub num_fewshot: usize,
 }
 
 pub struct PreparedTaskResult {
@@ -103,6 +123,69 @@ pub struct PreparedTaskResult {
     pub cancelled: bool,
 }
 
+/// Documents to evaluate, and the examples their few-shot prompts are picked from
+#[derive(Debug)]
+struct FewshotDocuments {
+    docs: Vec<Document>,
+    fewshot_by_category: HashMap<String, Vec<Document>>,
+    /// Pick random examples instead of the first ones
+    sample: bool,
+    random_seed: u64,
+}
+
+impl FewshotDocuments {
+    /// The `num_shots` examples the `doc_index`th document is prompted with at `eval_step`. Random
+    /// picks only depend on those and the task's seed, so a document is always prompted the same way
+    /// at a step, no matter which documents are evaluated alongside it.
+    fn examples(&self, doc_index: usize, num_shots: usize, eval_step: u32) -> Vec<Document> {
+        if num_shots == 0 {
+            return Vec::new();
+        }
+        // Fallback: use first available category if document's category is not found
+        let Some(examples) = self
+            .fewshot_by_category
+            .get(category(&self.docs[doc_index]))
+            .or_else(|| self.fewshot_by_category.values().next())
+        else {
+            return Vec::new();
+        };
+        let num_shots = num_shots.min(examples.len());
+        if !self.sample {
+            return examples[..num_shots].to_vec();
+        }
+
+        let mut seed = [0u8; 32];
+        seed[8..16].copy_from_slice(&u64::from(eval_step).to_be_bytes());
+        seed[16..24].copy_from_slice(&(doc_index as u64).to_be_bytes());
+        seed[24..32].copy_from_slice(&self.random_seed.to_be_bytes());
+        index::sample(&mut ChaCha8Rng::from_seed(seed), examples.len(), num_shots)
+            .into_iter()
+            .map(|i| examples[i].clone())
+            .collect()
+    }
+}
+
+fn category(doc: &Document) -> &str {
+    doc.category.as_deref().unwrap_or("default")
+}
+
+fn log_likelihood_prompt(
+    docs: &FewshotDocuments,
+    preambles: &HashMap<String, String>,
+    doc_index: usize,
+    num_shots: usize,
+    eval_step: u32,
+) -> String {
+    let doc = &docs.docs[doc_index];
+    let mut prompt = preambles[category(doc)].clone();
+    // Build fewshots to match how test question is tokenized:
+    // text (ends with "Answer:") + " " + choice
+    for example in docs.examples(doc_index, num_shots, eval_step) {
+        prompt += &format!("{} {}\n\n", example.text, example.choices[example.answer]);
+    }
+    prompt + &doc.text
+}
+
 #[derive(Debug)]
 struct TokenizedLLHDocument {
     choices_str: Vec<String>,
@@ -112,15 +195,9 @@ struct TokenizedLLHDocument {
     acc_uncond_tokens_len: Vec<usize>,
 }
 
-#[derive(Debug)]
-pub struct TokenizedGenerateUntilDocument {
-    _request_str: String,
-    request: Vec<i64>,
-    answer: usize,
-}
-
 impl TokenizedLLHDocument {
-    pub fn from_document(doc: Document, tokenizer: &Tokenizer, fewshot_prefix: &str) -> Self {
+    /// `context_string` is the document's prompt, few-shot examples included
+    pub fn from_document(doc: &Document, tokenizer: &Tokenizer, context_string: &str) -> Self {
         // We tokenize (fewshot_prefix + question_text) as one string then tokenize each choice separately.
         // context_tokens = tokenize(fewshot_prefix + doc.text)
         // choice_tokens = tokenize(" " + choice)
@@ -131,16 +208,9 @@ impl TokenizedLLHDocument {
         let mut choices_token_len = Vec::new();
         let mut acc_uncond_tokens_len = Vec::new();
 
-        // Build full context string: fewshot_prefix + doc.text
-        let context_string = if fewshot_prefix.is_empty() {
-            doc.text.clone()
-        } else {
-            format!("{}{}", fewshot_prefix, doc.text)
-        };
-
         // Tokenize context once (full fewshots + question up to "Answer:")
         let context_tokens: Vec<i64> = tokenizer
-            .encode(context_string.as_str(), false)
+            .encode(context_string, false)
             .unwrap()
             .get_ids()
             .iter()
@@ -205,7 +275,7 @@ impl TokenizedLLHDocument {
 }
 
 impl Task {
-    pub fn prepare(mut self, tokenizer: &Tokenizer, limit: Option<usize>) -> PreparedTask {
+    pub fn prepare(self, tokenizer: &Tokenizer, limit: Option<usize>) -> PreparedTask {
         let name = format!("{}", &self);
         info!("Preparing {name}");
         match self.task_type {
@@ -214,65 +284,34 @@ impl Task {
                 if let Some(limit) = limit {
                     docs.truncate(limit);
                 }
-                let fewshot_by_category = llh.get_fewshot_documents();
-
-                // Build individual requests with category-specific fewshot for each document
-                let docs = docs
-                    .into_iter()
-                    .map(|doc| {
-                        // Build fewshot prefix for this document
-                        let category = doc.category.as_deref().unwrap_or("default");
-                        let preamble = llh.get_preamble(category);
-
-                        let fewshot_prefix = if self.num_fewshot > 0 {
-                            // Get fewshot examples for this document's category
-                            let fewshot_examples = fewshot_by_category
-                                .get(category)
-                                .cloned()
-                                .unwrap_or_else(|| {
-                                    // Fallback: use first available category if document's category is not found
-                                    fewshot_by_category
-                                        .values()
-                                        .next()
-                                        .cloned()
-                                        .unwrap_or_else(Vec::new)
-                                });
-
-                            // MMLU/ARC tasks use first_n sampling (deterministic) other tasks like PIQA/Hellaswag use random sampling
-                            let should_shuffle = ![
-                                MMLU::name(),
-                                MMLUCF::name(),
-                                ArcEasy::name(),
-                                ArcChallenge::name(),
-                            ]
-                            .contains(&name.as_str());
-
-                            let mut fewshot_examples = fewshot_examples;
-                            if should_shuffle {
-                                fewshot_examples.shuffle(&mut self.rand);
-                            }
 
-                            // Build fewshots to match how test question is tokenized:
-                            // text (ends with "Answer:") + " " + choice
-                            preamble
-                                + &fewshot_examples
-                                    .into_iter()
-                                    .take(self.num_fewshot)
-                                    .map(|x| format!("{} {}", x.text, x.choices[x.answer]))
-                                    .collect::<Vec<_>>()
-                                    .join("\n\n")
-                                + "\n\n"
-                        } else {
-                            preamble
-                        };
-
-                        TokenizedLLHDocument::from_document(doc, tokenizer, &fewshot_prefix)
-                    })
-                    .collect::<Vec<_>>();
+                let mut preambles = HashMap::new();
+                for doc in &docs {
+                    let category = category(doc);
+                    if !preambles.contains_key(category) {
+                        preambles.insert(category.to_owned(), llh.get_preamble(category));
+                    }
+                }
+
+                // ARC tasks use first_n sampling (deterministic) even though their examples come from the training
+                // split, other tasks with a training split like PIQA/Hellaswag use random sampling
+                let sample = llh.train_split().is_some()
+                    && ![ArcEasy::name(), ArcChallenge::name()].contains(&name.as_str());
+
                 PreparedTask {
                     name,
                     num: docs.len(),
-                    prepared_task_type: PreparedTaskType::LogLikelihood { docs },
+                    num_fewshot: self.num_fewshot,
+                    prepared_task_type: PreparedTaskType::LogLikelihood {
+                        docs: FewshotDocuments {
+                            docs,
+                            fewshot_by_category: llh.get_fewshot_documents(),
+                            sample,
+                            random_seed: self.random_seed,
+                        },
+                        preambles,
+                        tokenizer: tokenizer.clone(),
+                    },
                 }
             }
             TaskType::GenerateUntil(gu_docs) => {
@@ -281,77 +320,11 @@ impl Task {
                     docs.truncate(limit);
                 }
 
-                let fewshot = gu_docs.get_fewshot_documents();
-
-                let mut requests = Vec::new();
-
-                // Prepare prompts for each document
-                for doc in &docs {
-                    // Get the category for this document
-                    let category = doc.category.as_deref().unwrap();
-
-                    // Get fewshot examples for this category
-                    let fewshot_examples = fewshot.get(category).map(|v| v.as_slice()).unwrap();
-
-                    // Build the prompt string
-
-                    let mut request_str = format!(
-                        "The following are multiple choice questions (with answers) about {category}. Think step by step and then finish your answer with \"the answer is (X)\" where X is the correct letter choice.\n"
-                    );
-
-                    // Add fewshot examples with their answers
-                    for example in fewshot_examples.iter().take(self.num_fewshot) {
-                        request_str.push_str("Question:\n");
-
-                        request_str.push_str(&example.text);
-                        request_str.push_str("\nOptions:\n");
-
-                        // Format choices with letter labels
-                        for (i, choice) in example.choices.iter().enumerate() {
-                            let letter = ASCII_UPPERCASE[i];
-                            request_str.push_str(&format!("{letter}. {choice}\n"));
-                        }
-
-                        // Replace "A:" with "Answer:" in cot_content
-                        let mut cot_content = example.cot_content.as_ref().unwrap().clone();
-                        if cot_content.starts_with("A:") {
-                            cot_content = format!("Answer:{}", &cot_content[2..]);
-                        }
-                        request_str.push_str(&cot_content);
-                        request_str.push_str("\n\n");
-                    }
-
-                    // Add the current question without answer
-                    request_str.push_str("Question:\n");
-                    request_str.push_str(&doc.text);
-                    request_str.push_str("\nOptions:\n");
-
-                    // Format choices with letter labels
-                    for (i, choice) in doc.choices.iter().enumerate() {
-                        let letter = ASCII_UPPERCASE[i];
-                        request_str.push_str(&format!("{letter}. {choice}\n"));
-                    }
-
-                    request_str.push_str("Answer: Let's think step by step.");
-
-                    // Tokenize the request
-                    let request = tokenizer
-                        .encode(request_str.clone(), false)
-                        .unwrap()
-                        .get_ids()
-                        .iter()
-                        .map(|x| *x as i64)
-                        .collect::<Vec<_>>();
-
-                    // Create the tokenized document
-                    let tokenized_doc = TokenizedGenerateUntilDocument {
-                        _request_str: request_str,
-                        request,
-                        answer: doc.answer,
-                    };
-
-                    requests.push(tokenized_doc);
-                }
+                let normalize_answer = gu_docs.get_answer_normalizer();
+                let answers = docs
+                    .iter()
+                    .map(|doc| normalize_answer(&gu_docs.get_expected_answer(doc)))
+                    .collect();
 
                 let stop_tokens = gu_docs.get_stop_string();
                 let answer_extraction_regex =
@@ -360,12 +333,48 @@ impl Task {
                 PreparedTask {
                     name,
                     num: docs.len(),
+                    num_fewshot: self.num_fewshot,
                     prepared_task_type: PreparedTaskType::GenerateUntil {
-                        requests,
+                        docs: FewshotDocuments {
+                            docs,
+                            fewshot_by_category: gu_docs.get_fewshot_documents(),
+                            sample: gu_docs.train_split().is_some(),
+                            random_seed: self.random_seed,
+                        },
+                        answers,
+                        format_prompt: gu_docs.get_prompt_formatter(),
                         tokenizer: tokenizer.clone(),
                         cache: Arc::new(RwLock::new(HashMap::new())),
                         stop_tokens,
                         answer_extraction_regex,
+                        normalize_answer,
+                        max_generated_tokens: gu_docs.get_max_generated_tokens(),
+                        check_completion: gu_docs.get_completion_checker(),
+                    },
+                }
+            }
+            TaskType::Perplexity(perplexity) => {
+                let mut tokens = tokenizer
+                    .encode(perplexity.text(), true)
+                    .unwrap()
+                    .get_ids()
+                    .iter()
+                    .map(|x| *x as i64)
+                    .collect::<Vec<_>>();
+                if let Some(limit) = limit {
+                    tokens.truncate(limit * perplexity.stride() + 1);
+                }
+                // the first token has no context, so it's never scored
+                let scored_tokens = tokens.len().saturating_sub(1);
+                let num_chars = perplexity.text().chars().count().max(1);
+                PreparedTask {
+                    name,
+                    num: scored_tokens.div_ceil(perplexity.stride()),
+                    num_fewshot: self.num_fewshot,
+                    prepared_task_type: PreparedTaskType::Perplexity {
+                        tokens,
+                        stride: perplexity.stride(),
+                        tokens_per_char: scored_tokens as f64 / num_chars as f64,
                     },
                 }
             }
@@ -380,6 +389,26 @@ pub struct EvalTaskOptions<'a> {
     pub cancel: Option<CancellationToken>,
     pub limit: Option<usize>,
     pub shared_progress_bar: Option<Arc<ProgressBar>>,
+    /// Extra scores merged into [`PreparedTaskResult::scores`]. Unlike the built-in scores these
+    /// only cover the documents evaluated in this run, not the ones in `live_results`.
+    /// A computed score named like a built-in one is reported with a `custom_` prefix.
+    pub metric_computer: Option<Box<dyn MetricComputer>>,
+    /// Number of few-shot examples solved before every prompt, 0 for zero-shot
+    pub num_shots: usize,
+    /// Training step the eval runs at. With each document's index, it seeds which few-shot examples
+    /// the document is prompted with, so rerunning an eval at a step gives the same prompts.
+    pub eval_step: u32,
+}
+
+/// Adds the scores of a [`MetricComputer`] to the built-in ones, never replacing a built-in score.
+fn merge_computed_scores(scores: &mut HashMap<String, f64>, computed: HashMap<String, f64>) {
+    for (name, value) in computed {
+        let name = match scores.contains_key(&name) {
+            true => format!("custom_{name}"),
+            false => name,
+        };
+        scores.insert(name, value);
+    }
 }
 
 impl PreparedTask {
@@ -405,32 +434,78 @@ impl PreparedTask {
         };
 
         match &self.prepared_task_type {
-            PreparedTaskType::LogLikelihood { docs } => {
-                Self::run_log_likelihood(&self.name, options, docs, pbar)
-            }
+            PreparedTaskType::LogLikelihood {
+                docs,
+                preambles,
+                tokenizer,
+            } => Self::run_log_likelihood(&self.name, options, docs, preambles, tokenizer, pbar),
             PreparedTaskType::GenerateUntil {
-                requests,
+                docs,
+                answers,
+                format_prompt,
                 tokenizer,
                 cache,
                 stop_tokens,
                 answer_extraction_regex,
+                normalize_answer,
+                max_generated_tokens,
+                check_completion,
             } => Self::run_generate_until(
                 &self.name,
                 options,
                 cache.clone(),
-                requests,
+                docs,
+                answers,
+                *format_prompt,
                 tokenizer,
                 stop_tokens,
                 answer_extraction_regex,
+                *normalize_answer,
+                *max_generated_tokens,
+                *check_completion,
                 pbar,
             ),
+            PreparedTaskType::Perplexity {
+                tokens,
+                stride,
+                tokens_per_char,
+            } => Self::run_perplexity(options, tokens, *stride, self.num, *tokens_per_char, pbar),
+        }
+    }
+
+    /// The `doc_index`th document's prompt, after `num_shots` few-shot examples picked for
+    /// `eval_step`. `None` for perplexity, which scores a text instead of prompting, and if there's
+    /// no such document.
+    pub fn format_prompt(
+        &self,
+        doc_index: usize,
+        num_shots: usize,
+        eval_step: u32,
+    ) -> Option<String> {
+        match &self.prepared_task_type {
+            PreparedTaskType::LogLikelihood {
+                docs, preambles, ..
+            } if doc_index < docs.docs.len() => Some(log_likelihood_prompt(
+                docs, preambles, doc_index, num_shots, eval_step,
+            )),
+            PreparedTaskType::GenerateUntil {
+                docs,
+                format_prompt,
+                ..
+            } if doc_index < docs.docs.len() => Some(format_prompt(
+                &docs.docs[doc_index],
+                &docs.examples(doc_index, num_shots, eval_step),
+            )),
+            _ => None,
         }
     }
 
     fn run_log_likelihood(
         eval_name: &String,
         options: EvalTaskOptions,
-        docs: &[TokenizedLLHDocument],
+        docs: &FewshotDocuments,
+        preambles: &HashMap<String, String>,
+        tokenizer: &Tokenizer,
         pbar: Option<Arc<ProgressBar>>,
     ) -> PreparedTaskResult {
         let results = options.live_results.unwrap_or_default();
@@ -439,23 +514,26 @@ impl PreparedTask {
         let min_samples = if pbar.is_some() {
             None
         } else {
-            min_reporting_ratio(eval_name).map(|x| (x * docs.len() as f32) as usize)
+            min_reporting_ratio(eval_name).map(|x| (x * docs.docs.len() as f32) as usize)
         };
 
-        results.add_entry_if_needed("acc", docs.len(), min_samples);
+        results.add_entry_if_needed("acc", docs.docs.len(), min_samples);
         if TASKS_WITH_ACC_NORM.contains(&eval_name.as_str()) {
-            results.add_entry_if_needed("acc_norm", docs.len(), min_samples);
+            results.add_entry_if_needed("acc_norm", docs.docs.len(), min_samples);
         }
         if TASKS_WITH_ACC_UNCOND.contains(&eval_name.as_str()) {
-            results.add_entry_if_needed("acc_uncond", docs.len(), min_samples);
+            results.add_entry_if_needed("acc_uncond", docs.docs.len(), min_samples);
         }
         let mut next_index = skip;
+        let mut predictions = Vec::new();
+        let mut references = Vec::new();
 
-        let fast_forward = (skip / docs.len()) * docs.len();
+        let fast_forward = (skip / docs.docs.len()) * docs.docs.len();
         skip -= fast_forward;
         let mut cancelled = false;
 
         for (num_iterations, (doc_index, doc)) in docs
+            .docs
             .iter()
             .cycle()
             .enumerate()
@@ -470,7 +548,7 @@ impl PreparedTask {
                     break;
                 }
             }
-            if doc_index >= docs.len() {
+            if doc_index >= docs.docs.len() {
                 break;
             }
             if let Some(limit) = options.limit {
@@ -478,6 +556,14 @@ impl PreparedTask {
                     break;
                 }
             }
+            let prompt = log_likelihood_prompt(
+                docs,
+                preambles,
+                doc_index,
+                options.num_shots,
+                options.eval_step,
+            );
+            let doc = TokenizedLLHDocument::from_document(doc, tokenizer, &prompt);
             let mut scores: Vec<(f32, bool)> = Vec::new();
             let mut scores_uncond: Vec<f32> = Vec::new();
             for idx in 0..doc.requests.len() {
@@ -528,7 +614,7 @@ impl PreparedTask {
             if TASKS_WITH_ACC_UNCOND.contains(&eval_name.as_str()) {
                 for idx in 0..doc.requests.len() {
                     let loglikelihood_uncond =
-                        calculate_unconditional_loglikelihood(doc, idx, options.model);
+                        calculate_unconditional_loglikelihood(&doc, idx, options.model);
                     scores_uncond.push(loglikelihood_uncond);
                 }
             }
@@ -548,6 +634,11 @@ impl PreparedTask {
             .try_into()
             .unwrap();
 
+            if options.metric_computer.is_some() {
+                predictions.push(doc.choices_str[selected as usize].clone());
+                references.push(doc.choices_str[doc.answer].clone());
+            }
+
             results.push(
                 "acc",
                 match selected as usize == doc.answer {
@@ -593,12 +684,20 @@ impl PreparedTask {
             };
         }
 
+        let mut scores: HashMap<String, f64> = results
+            .get_all_averages()
+            .into_iter()
+            .map(|(key, value)| (key, value.unwrap_or_default()))
+            .collect();
+        if let Some(metric_computer) = &options.metric_computer {
+            merge_computed_scores(
+                &mut scores,
+                metric_computer.compute(&predictions, &references),
+            );
+        }
+
         PreparedTaskResult {
-            scores: results
-                .get_all_averages()
-                .into_iter()
-                .map(|(key, value)| (key, value.unwrap_or_default()))
-                .collect(),
+            scores,
             next_index: next_index + fast_forward,
             cancelled,
         }
@@ -608,11 +707,16 @@ impl PreparedTask {
     fn run_generate_until(
         eval_name: &String,
         options: EvalTaskOptions,
-        cache: Arc<RwLock<HashMap<usize, Vec<u32>>>>,
-        requests: &[TokenizedGenerateUntilDocument],
+        cache: Arc<RwLock<HashMap<usize, (u32, Vec<u32>)>>>,
+        docs: &FewshotDocuments,
+        answers: &[String],
+        format_prompt: PromptFormatter,
         tokenizer: &Tokenizer,
         stop_tokens: &[String],
         answer_extraction_regex: &Regex,
+        normalize_answer: fn(&str) -> String,
+        max_generated_tokens: usize,
+        check_completion: Option<CompletionChecker>,
         pbar: Option<Arc<ProgressBar>>,
     ) -> PreparedTaskResult {
         let results = options.live_results.unwrap_or_default();
@@ -621,14 +725,16 @@ impl PreparedTask {
         let min_samples = if pbar.is_some() {
             None
         } else {
-            min_reporting_ratio(eval_name).map(|x| (x * requests.len() as f32) as usize)
+            min_reporting_ratio(eval_name).map(|x| (x * docs.docs.len() as f32) as usize)
         };
-        results.add_entry_if_needed("acc", requests.len(), min_samples);
+        results.add_entry_if_needed("acc", docs.docs.len(), min_samples);
 
-        let fast_forward = (skip / requests.len()) * requests.len();
+        let fast_forward = (skip / docs.docs.len()) * docs.docs.len();
         skip -= fast_forward;
         let mut cancelled = false;
         let mut documents_processed = 0;
+        let mut predictions = Vec::new();
+        let mut references = Vec::new();
 
         // Simple sampling setup
         let mut logits_processor = LogitsProcessor::from_sampling(
@@ -639,17 +745,8 @@ impl PreparedTask {
         // Get EOS token IDs from model
         let eos_token_ids = options.model.eos_token_ids();
 
-        for (
-            num_iterations,
-            (
-                doc_index,
-                &TokenizedGenerateUntilDocument {
-                    ref _request_str,
-                    ref request,
-                    answer,
-                },
-            ),
-        ) in requests
+        for (num_iterations, (doc_index, doc)) in docs
+            .docs
             .iter()
             .cycle()
             .enumerate()
@@ -663,7 +760,7 @@ impl PreparedTask {
                     break;
                 }
             }
-            if doc_index >= requests.len() {
+            if doc_index >= docs.docs.len() {
                 break;
             }
             if let Some(limit) = options.limit {
@@ -672,19 +769,31 @@ impl PreparedTask {
                 }
             }
 
-            let mut generated_answer = None;
+            let request_str = format_prompt(
+                doc,
+                &docs.examples(doc_index, options.num_shots, options.eval_step),
+            );
+            let answer = &answers[doc_index];
+
             let mut generation_complete = false;
 
             // Start with the tokenized prompt
-            let mut full_sequence = request.clone();
+            let mut full_sequence = tokenizer
+                .encode(request_str.as_str(), false)
+                .unwrap()
+                .get_ids()
+                .iter()
+                .map(|x| *x as i64)
+                .collect::<Vec<_>>();
 
-            // Check if we have cached generated tokens for this document
+            // Check if we have cached generated tokens for this document, from a generation of this same prompt
             let mut generated_tokens = {
                 cache
                     .read()
                     .unwrap()
                     .get(&doc_index)
-                    .cloned()
+                    .filter(|(eval_step, _)| *eval_step == options.eval_step)
+                    .map(|(_, tokens)| tokens.clone())
                     .unwrap_or_else(Vec::new)
             };
 
@@ -710,7 +819,7 @@ impl PreparedTask {
                         cache
                             .write()
                             .unwrap()
-                            .insert(doc_index, generated_tokens.clone());
+                            .insert(doc_index, (options.eval_step, generated_tokens.clone()));
                         tracing::trace!(
                             "Cancellation requested: saving {} tokens for document {}",
                             generated_tokens.len(),
@@ -761,7 +870,7 @@ impl PreparedTask {
                     }
                 }
 
-                if tokens_generated_count >= GENERATE_UNTIL_MAX_TOKENS {
+                if tokens_generated_count >= max_generated_tokens {
                     generation_complete = true;
                     break;
                 }
@@ -771,31 +880,50 @@ impl PreparedTask {
             if generation_complete {
                 cache.write().unwrap().remove(&doc_index);
 
-                // Extract answer from the complete generated text using regex
-                // Use captures_iter to find all matches and take the last one (final answer)
-                if let Ok(generated_text) = tokenizer.decode(&generated_tokens, false) {
-                    if let Some(last_capture) = answer_extraction_regex
-                        .captures_iter(&generated_text)
-                        .last()
-                    {
-                        // last_capture.get(1) returns just the letter (A, B, C, ...)
-                        if let Some(answer_char) = last_capture.get(1) {
-                            // Gets the index of the letter (A=0, B=1, C=2, ...)
-                            generated_answer = Some(
-                                crate::ASCII_UPPERCASE
-                                    .iter()
-                                    .position(|&c| c == answer_char.as_str())
-                                    .unwrap_or(usize::MAX),
-                            );
+                let (correct, prediction) = match check_completion {
+                    Some(check_completion) => match tokenizer.decode(&generated_tokens, false) {
+                        Ok(generated_text) => {
+                            let completion = stop_tokens
+                                .iter()
+                                .filter_map(|stop| generated_text.find(stop.as_str()))
+                                .min()
+                                .map_or(generated_text.as_str(), |end| &generated_text[..end]);
+                            (
+                                check_completion(&request_str, completion, answer),
+                                completion.to_string(),
+                            )
                         }
+                        Err(_) => (false, String::new()),
+                    },
+                    None => {
+                        let mut generated_answer = None;
+                        // Extract answer from the complete generated text using regex
+                        // Use captures_iter to find all matches and take the last one (final answer)
+                        if let Ok(generated_text) = tokenizer.decode(&generated_tokens, false) {
+                            if let Some(last_capture) = answer_extraction_regex
+                                .captures_iter(&generated_text)
+                                .last()
+                            {
+                                // last_capture.get(1) returns just the answer (a letter, a number, ...)
+                                if let Some(answer_match) = last_capture.get(1) {
+                                    generated_answer =
+                                        Some(normalize_answer(answer_match.as_str()));
+                                }
+                            }
+                        }
+                        (
+                            generated_answer.as_ref() == Some(answer),
+                            generated_answer.unwrap_or_default(),
+                        )
                     }
+                };
+
+                if options.metric_computer.is_some() {
+                    predictions.push(prediction);
+                    references.push(answer.clone());
                 }
 
-                let score = if generated_answer == Some(answer) {
-                    1.
-                } else {
-                    0.
-                };
+                let score = if correct { 1. } else { 0. };
                 results.push("acc", score);
                 documents_processed += 1;
 
@@ -806,13 +934,136 @@ impl PreparedTask {
             }
         }
 
+        let mut scores: HashMap<String, f64> = results
+            .get_all_averages()
+            .into_iter()
+            .map(|(key, value)| (key, value.unwrap_or_default()))
+            .collect();
+        if let Some(metric_computer) = &options.metric_computer {
+            merge_computed_scores(
+                &mut scores,
+                metric_computer.compute(&predictions, &references),
+            );
+        }
+
+        PreparedTaskResult {
+            scores,
+            next_index: fast_forward + skip + (documents_processed * step_by),
+            cancelled,
+        }
+    }
+
+    fn run_perplexity(
+        options: EvalTaskOptions,
+        tokens: &[i64],
+        stride: usize,
+        num_windows: usize,
+        tokens_per_char: f64,
+        pbar: Option<Arc<ProgressBar>>,
+    ) -> PreparedTaskResult {
+        let results = options.live_results.unwrap_or_default();
+        let (mut skip, step_by) = options.skip_and_step_by.unwrap_or((0, 1));
+        // every scored token's negative log-likelihood, so windows of different lengths are weighted correctly.
+        // perplexity & bits per char aren't means themselves, so they're derived from it and only keep their latest value.
+        results.add_entry_if_needed("nll", tokens.len().saturating_sub(1).max(1), None);
+        results.add_entry_if_needed("perplexity", 1, None);
+        results.add_entry_if_needed("bits_per_char", 1, None);
+        let mut next_index = skip;
+
+        if num_windows == 0 {
+            return PreparedTaskResult {
+                scores: HashMap::new(),
+                next_index,
+                cancelled: false,
+            };
+        }
+
+        let fast_forward = (skip / num_windows) * num_windows;
+        skip -= fast_forward;
+        let mut cancelled = false;
+        let max_context_length = options.model.max_context_length();
+
+        for (num_iterations, window_index) in (0..num_windows)
+            .cycle()
+            .enumerate()
+            .skip(skip)
+            .step_by(step_by)
+            .map(|(index, _)| index)
+            .enumerate()
+        {
+            next_index = window_index;
+            if let Some(cancel) = options.cancel.as_ref() {
+                if cancel.is_cancelled() {
+                    cancelled = true;
+                    break;
+                }
+            }
+            if window_index >= num_windows {
+                break;
+            }
+            if let Some(limit) = options.limit {
+                if num_iterations >= limit {
+                    break;
+                }
+            }
+
+            // score tokens[start..end], feeding the model up to max_context_length tokens before each one
+            let end = (1 + (window_index + 1) * stride).min(tokens.len());
+            let start = (1 + window_index * stride).max(end.saturating_sub(max_context_length));
+            let begin = (end - 1).saturating_sub(max_context_length);
+            let targets = &tokens[start..end];
+
+            let input = Tensor::from_slice(&tokens[begin..end - 1])
+                .to(options.model.device())
+                .unsqueeze(0);
+            let (logits, _) = {
+                let _no_grad = tch::no_grad_guard();
+                options
+                    .model
+                    .forward(&input, None, None, None, Some(targets.len() as i64), None)
+            };
+            // Shape: [targets.len(), vocab_size]
+            let logits = logits.unwrap().squeeze_dim(0);
+            let log_probs: Vec<f32> = logits
+                .log_softmax(-1, Kind::Float)
+                .gather(
+                    -1,
+                    &Tensor::from_slice(targets)
+                        .to(logits.device())
+                        .unsqueeze(-1),
+                    false,
+                )
+                .flatten(0, -1)
+                .try_into()
+                .unwrap();
+            for log_prob in log_probs {
+                results.push("nll", -log_prob as f64);
+            }
+
+            if let Some(nll) = results.sample("nll") {
+                results.push("perplexity", nll.exp());
+                results.push(
+                    "bits_per_char",
+                    nll * tokens_per_char / std::f64::consts::LN_2,
+                );
+            }
+
+            if let Some(pbar) = &pbar {
+                pbar.set_message(format!(
+                    "perplexity: {:.3}",
+                    results.sample("perplexity").unwrap_or(f64::NAN)
+                ));
+                pbar.inc(1);
+            };
+        }
+
         PreparedTaskResult {
             scores: results
                 .get_all_averages()
                 .into_iter()
                 .map(|(key, value)| (key, value.unwrap_or_default()))
                 .collect(),
-            next_index: fast_forward + skip + (documents_processed * step_by),
+            next_index: next_index + fast_forward,
             cancelled,
         }
     }
@@ -822,7 +1073,9 @@ impl PreparedTask {
     }
 
     pub fn main_metric_name(&self) -> &str {
-        if TASKS_WITH_ACC_NORM.contains(&self.name()) {
+        if matches!(self.prepared_task_type, PreparedTaskType::Perplexity { .. }) {
+            "perplexity"
+        } else if TASKS_WITH_ACC_NORM.contains(&self.name()) {
             "acc_norm"
         } else {
             "acc"
@@ -868,7 +1121,7 @@ fn calculate_unconditional_loglikelihood(
 }
 
 fn min_reporting_ratio(eval_name: &String) -> Option<f32> {
-    if eval_name == MMLUPro::name() {
+    if eval_name == MMLUPro::name() || eval_name == GSM8K::name() || eval_name == LAMBADA::name() {
         Some(0.1)
     } else if eval_name == ArcChallenge::name()
         || eval_name == BoolQ::name()
@@ -878,6 +1131,8 @@ fn min_reporting_ratio(eval_name: &String) -> Option<f32> {
         || eval_name == MMLU::name()
         || eval_name == MMLUCF::name()
         || eval_name == PIQA::name()
+        || eval_name == TruthfulQA::name()
+        || eval_name == WinoGrande::name()
     {
         Some(0.5)
     } else {
@@ -885,3 +1140,240 @@ fn min_reporting_ratio(eval_name: &String) -> Option<f32> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use psyche_data_provider::Split;
+    use psyche_modeling::{Communicator, EosToks, StableVariableIterator};
+    use std::sync::Mutex;
+    use tch::Device;
+    use tokenizers::{models::wordlevel::WordLevel, pre_tokenizers::whitespace::Whitespace};
+
+    const VOCAB: i64 = 4;
+
+    /// Always predicts token 1, whatever the input
+    struct FavorsTokenOne;
+
+    impl CausalLM for FavorsTokenOne {
+        fn forward(
+            &self,
+            x: &Tensor,
+            _labels: Option<&Tensor>,
+            _position_ids: Option<&Tensor>,
+            _sequence_lengths: Option<&Vec<Vec<i32>>>,
+            num_logits_to_keep: Option<i64>,
+            _loss_scale: Option<f64>,
+        ) -> (Option<Tensor>, Option<Tensor>) {
+            let len = num_logits_to_keep.unwrap_or(x.size()[1]);
+            let logits = Tensor::zeros([1, len, VOCAB], (Kind::Float, Device::Cpu));
+            let _ = logits.narrow(-1, 1, 1).fill_(10.0);
+            (Some(logits), None)
+        }
+
+        fn bos_token_id(&self) -> Option<i64> {
+            None
+        }
+
+        fn eos_token_ids(&self) -> Option<EosToks> {
+            None
+        }
+
+        fn device(&self) -> Device {
+            Device::Cpu
+        }
+
+        fn max_context_length(&self) -> usize {
+            16
+        }
+
+        fn variables(&self) -> StableVariableIterator {
+            Box::new(std::iter::empty())
+        }
+
+        fn communicator(&self) -> Option<Arc<Communicator>> {
+            None
+        }
+
+        fn prepare_for_training(&self) {}
+
+        fn clip_grad_norm(&self, _max_grad_norm: f64) -> Option<f64> {
+            None
+        }
+
+        fn convert(&self, _state_dict: Option<HashMap<String, Tensor>>) -> HashMap<String, Tensor> {
+            HashMap::new()
+        }
+    }
+
+    /// Records what it was given and reports a score clashing with the built-in `acc`
+    #[derive(Default)]
+    struct RecordingMetric {
+        seen: Arc<Mutex<(Vec<String>, Vec<String>)>>,
+    }
+
+    impl MetricComputer for RecordingMetric {
+        fn compute(&self, predictions: &[String], references: &[String]) -> HashMap<String, f64> {
+            *self.seen.lock().unwrap() = (predictions.to_vec(), references.to_vec());
+            HashMap::from([
+                ("acc".to_string(), 1.0),
+                ("count".to_string(), predictions.len() as f64),
+            ])
+        }
+    }
+
+    fn tokenizer() -> Tokenizer {
+        let vocab = [("<unk>", 0), ("one", 1), ("two", 2), ("q", 3)]
+            .into_iter()
+            .map(|(token, id)| (token.to_string(), id))
+            .collect();
+        let mut tokenizer = Tokenizer::new(
+            WordLevel::builder()
+                .vocab(vocab)
+                .unk_token("<unk>".to_string())
+                .build()
+                .unwrap(),
+        );
+        tokenizer.with_pre_tokenizer(Some(Whitespace {}));
+        tokenizer
+    }
+
+    fn doc(answer: usize) -> Document {
+        Document {
+            text: "q".to_string(),
+            choices: vec!["one".to_string(), "two".to_string()],
+            answer,
+            category: None,
+            cot_content: None,
+            eval_name: "stub".to_string(),
+        }
+    }
+
+    #[test]
+    fn metric_computer_sees_every_answer_without_replacing_builtin_scores() {
+        let task = PreparedTask {
+            prepared_task_type: PreparedTaskType::LogLikelihood {
+                docs: FewshotDocuments {
+                    docs: vec![doc(0), doc(1)],
+                    fewshot_by_category: HashMap::new(),
+                    sample: false,
+                    random_seed: 0,
+                },
+                preambles: HashMap::from([("default".to_string(), String::new())]),
+                tokenizer: tokenizer(),
+            },
+            name: "stub".to_string(),
+            num: 2,
+            num_fewshot: 0,
+        };
+        let metric = RecordingMetric::default();
+        let seen = metric.seen.clone();
+
+        let result = task.run(
+            EvalTaskOptions {
+                model: &mut FavorsTokenOne,
+                skip_and_step_by: None,
+                live_results: None,
+                cancel: None,
+                limit: None,
+                shared_progress_bar: None,
+                metric_computer: Some(Box::new(metric)),
+                num_shots: 0,
+                eval_step: 0,
+            },
+            false,
+        );
+
+        assert_eq!(result.scores["acc"], 0.5);
+        assert_eq!(result.scores["custom_acc"], 1.0);
+        assert_eq!(result.scores["count"], 2.0);
+        let (predictions, references) = seen.lock().unwrap().clone();
+        assert_eq!(predictions, ["one", "one"]);
+        assert_eq!(references, ["one", "two"]);
+    }
+
+    /// Questions "q<n>", with "q0" to "q19" as few-shot examples
+    struct Numbered {
+        train_split: Option<Split>,
+    }
+
+    fn numbered(n: usize) -> Document {
+        Document {
+            text: format!("q{n}"),
+            choices: vec![n.to_string()],
+            answer: 0,
+            category: None,
+            cot_content: None,
+            eval_name: "numbered".to_string(),
+        }
+    }
+
+    impl GenerateUntilTask for Numbered {
+        fn get_documents(&self) -> Vec<Document> {
+            vec![numbered(100), numbered(101)]
+        }
+
+        fn get_fewshot_documents(&self) -> HashMap<String, Vec<Document>> {
+            HashMap::from([("default".to_string(), (0..20).map(numbered).collect())])
+        }
+
+        fn train_split(&self) -> Option<Split> {
+            self.train_split
+        }
+
+        fn get_prompt_formatter(&self) -> PromptFormatter {
+            |doc, fewshot_examples| {
+                fewshot_examples
+                    .iter()
+                    .chain([doc])
+                    .map(|doc| doc.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+        }
+
+        fn get_stop_string(&self) -> Vec<String> {
+            Vec::new()
+        }
+
+        fn get_answer_extraction_regex(&self) -> String {
+            String::new()
+        }
+    }
+
+    impl Display for Numbered {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "numbered")
+        }
+    }
+
+    fn prepare_numbered(train_split: Option<Split>) -> PreparedTask {
+        let task_type = TaskType::GenerateUntil(Box::new(Numbered { train_split }));
+        Task::new(task_type, 3, 42).prepare(&tokenizer(), None)
+    }
+
+    #[test]
+    fn fewshot_examples_are_sampled_per_eval_step_and_document() {
+        let task = prepare_numbered(Some(Split::Train));
+        let prompt = |doc_index, eval_step| task.format_prompt(doc_index, 3, eval_step).unwrap();
+
+        assert_eq!(prompt(0, 1), prompt(0, 1));
+        assert_ne!(prompt(0, 1), prompt(0, 2));
+        assert_ne!(prompt(0, 1), prompt(1, 1).replace("q101", "q100"));
+        let words = prompt(0, 1);
+        let words = words.split(' ').collect::<Vec<_>>();
+        assert_eq!(words.len(), 4);
+        assert_eq!(words[3], "q100");
+        assert_eq!(task.format_prompt(0, 0, 1).unwrap(), "q100");
+    }
+
+    #[test]
+    fn fewshot_examples_without_a_training_split_are_the_first_ones() {
+        let task = prepare_numbered(None);
+
+        assert_eq!(task.format_prompt(0, 3, 1).unwrap(), "q0 q1 q2 q100");
+        assert_eq!(task.format_prompt(1, 3, 2).unwrap(), "q0 q1 q2 q101");
+        // never more examples than there are
+        assert_eq!(task.format_prompt(0, 30, 1).unwrap().split(' ').count(), 21);
+    }
+}
@@ -2,6 +2,7 @@ use anyhow::{Result, bail};
 use psyche_data_provider::{Dataset, Split};
 
 mod harness;
+mod metrics;
 mod tasks;
 mod traits;
 
@@ -9,8 +10,11 @@ pub use harness::{
     EvalTaskOptions, PROGRESS_BAR_TEMPLATE, PreparedTask, PreparedTaskResult, Task, TaskType,
     progress_bar_template_with_task,
 };
+pub use metrics::{AccuracyMetric, MetricComputer};
 pub use tasks::{
-    ArcChallenge, ArcEasy, BoolQ, CEval, Hellaswag, MMLU, MMLUCF, MMLUPro, OpenbookQA, PIQA,
+    ArcChallenge, ArcEasy, BoolQ, CEval, CMMLU, DEFAULT_PERPLEXITY_STRIDE, GSM8K, Hellaswag,
+    HumanEval, LAMBADA, MMLU, MMLUCF, MMLUPro, OpenbookQA, PIQA, Perplexity, TruthfulQA,
+    WinoGrande,
 };
 
 pub const ASCII_UPPERCASE: [&str; 26] = [
@@ -18,17 +22,24 @@ pub const ASCII_UPPERCASE: [&str; 26] = [
     "T", "U", "V", "W", "X", "Y", "Z",
 ];
 
-pub const ALL_TASK_NAMES: [&str; 10] = [
+pub const ALL_TASK_NAMES: [&str; 16] = [
     ArcChallenge::name(),
     ArcEasy::name(),
     BoolQ::name(),
     CEval::name(),
+    CMMLU::name(),
+    GSM8K::name(),
     Hellaswag::name(),
+    // runs model-generated code, so it only loads with HF_ALLOW_CODE_EVAL=1, see [`HumanEval`]
+    HumanEval::name(),
+    LAMBADA::name(),
     MMLUPro::name(),
     MMLU::name(),
     MMLUCF::name(),
     OpenbookQA::name(),
     PIQA::name(),
+    TruthfulQA::name(),
+    WinoGrande::name(),
 ];
 
 pub fn load_dataset(
@@ -43,11 +54,25 @@ pub fn load_dataset(
         None,
         None,
         true,
+        psyche_data_provider::DownloadConfig {
+            subset: subset.clone(),
+            ..Default::default()
+        },
     )?;
     Dataset::load_dataset(&repo_files, Some(split), subset)
 }
 
+/// Besides the names in [`ALL_TASK_NAMES`], accepts `perplexity:<path to a text file>` and
+/// `cmmlu:<subject>` to evaluate a single CMMLU subject, e.g. `cmmlu:agronomy`.
 pub fn tasktype_from_name(name: &str) -> Result<TaskType> {
+    if let Some((task, path)) = name.split_once(':') {
+        if task.eq_ignore_ascii_case("perplexity") {
+            return Perplexity::load(path);
+        }
+        if task.eq_ignore_ascii_case("cmmlu") {
+            return CMMLU::load_subject(path);
+        }
+    }
     match name
         .to_lowercase()
         .chars()
@@ -58,13 +83,19 @@ pub fn tasktype_from_name(name: &str) -> Result<TaskType> {
         "arc_challenge" => ArcChallenge::load(),
         "arc_easy" => ArcEasy::load(),
         "boolq" => BoolQ::load(),
-        "ceval_valid" => CEval::load(),
+        "ceval" | "ceval_valid" => CEval::load(),
+        "cmmlu" => CMMLU::load(),
+        "gsm8k" => GSM8K::load(),
         "hellaswag" => Hellaswag::load(),
+        "humaneval" | "human_eval" => HumanEval::load(),
+        "lambada" => LAMBADA::load(),
         "mmlu_pro" => MMLUPro::load(),
         "mmlu" => MMLU::load(),
         "mmlu_cf" => MMLUCF::load(),
         "openbookqa" => OpenbookQA::load(),
         "piqa" => PIQA::load(),
+        "truthfulqa_mc1" => TruthfulQA::load(),
+        "winogrande" => WinoGrande::load(),
         _ => bail!("Unknown task {name}"),
     }
 }
@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+
+/// Computes extra scores for a task from what the model answered, e.g. F1 or BLEU.
+///
+/// `predictions[i]` is the model's answer to the document whose expected answer is
+/// `references[i]`: the highest scoring choice for log-likelihood tasks, or the extracted
+/// answer for generate-until tasks. Perplexity tasks have no predictions and never call this.
+pub trait MetricComputer: Send + Sync {
+    fn compute(&self, predictions: &[String], references: &[String]) -> HashMap<String, f64>;
+}
+
+/// Fraction of predictions that exactly match their reference, reported as `exact_match`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AccuracyMetric;
+
+impl MetricComputer for AccuracyMetric {
+    fn compute(&self, predictions: &[String], references: &[String]) -> HashMap<String, f64> {
+        let total = predictions.len().min(references.len());
+        let correct = predictions
+            .iter()
+            .zip(references)
+            .filter(|(prediction, reference)| prediction == reference)
+            .count();
+        let accuracy = if total == 0 {
+            0.0
+        } else {
+            correct as f64 / total as f64
+        };
+        HashMap::from([("exact_match".to_string(), accuracy)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accuracy_metric_counts_exact_matches() {
+        let predictions = ["a", "b", "c"].map(String::from);
+        let references = ["a", "b", "d"].map(String::from);
+        let scores = AccuracyMetric.compute(&predictions, &references);
+        assert!((scores["exact_match"] - 2.0 / 3.0).abs() < 1e-9);
+
+        assert_eq!(AccuracyMetric.compute(&[], &[])["exact_match"], 0.0);
+    }
+}
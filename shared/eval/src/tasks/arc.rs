@@ -97,6 +97,10 @@ impl LogLikelihoodTask for Arc {
         fewshot_documents.insert("default".to_string(), docs);
         fewshot_documents
     }
+
+    fn train_split(&self) -> Option<Split> {
+        Some(Split::Train)
+    }
 }
 
 impl Display for Arc {
@@ -123,6 +127,10 @@ impl LogLikelihoodTask for ArcEasy {
     fn get_fewshot_documents(&self) -> HashMap<String, Vec<Document>> {
         self.task.get_fewshot_documents()
     }
+
+    fn train_split(&self) -> Option<Split> {
+        self.task.train_split()
+    }
 }
 
 impl Display for ArcEasy {
@@ -149,6 +157,10 @@ impl LogLikelihoodTask for ArcChallenge {
     fn get_fewshot_documents(&self) -> HashMap<String, Vec<Document>> {
         self.task.get_fewshot_documents()
     }
+
+    fn train_split(&self) -> Option<Split> {
+        self.task.train_split()
+    }
 }
 
 impl Display for ArcChallenge {
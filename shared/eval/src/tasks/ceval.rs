@@ -2,17 +2,16 @@
 // We believe this to be because of a mismatch in which tokenizer we use, we should investigate this further.
 // Evals should be very close or the same as those in lm_evals with Llama-like models.
 
+use super::chinese_exam::{ChineseExam, ExamFormat};
 use crate::{
-    TaskType, load_dataset,
+    TaskType,
     traits::{Document, LogLikelihoodTask},
 };
 use anyhow::Result;
-use psyche_data_provider::{Dataset, Row, RowAccessor, Split};
+use psyche_data_provider::Split;
 use std::{collections::HashMap, fmt::Display};
 
-pub struct CEval {
-    datasets: Vec<(Dataset, Dataset, String)>, // (val, dev, subject)
-}
+pub struct CEval(ChineseExam);
 
 const SUBJECT_MAPPING: &[(&str, &str)] = &[
     ("accountant", "注册会计师"),
@@ -80,130 +79,37 @@ const SUBJECT_MAPPING: &[(&str, &str)] = &[
 
 impl CEval {
     pub fn load() -> Result<TaskType> {
-        let mut datasets = Vec::new();
-
-        // Load all subjects
-        for (subject_en, _) in SUBJECT_MAPPING {
-            // Used for evaluation
-            let val_dataset = load_dataset(
-                "ceval/ceval-exam",
-                None,
-                Split::Val,
-                Some(subject_en.to_string()),
-            )?;
-
-            // Used for fewshot examples
-            let dev_dataset = load_dataset(
-                "ceval/ceval-exam",
-                None,
-                Split::Dev,
-                Some(subject_en.to_string()),
-            )?;
-
-            datasets.push((val_dataset, dev_dataset, subject_en.to_string()));
-        }
-
-        let ret = Self { datasets };
+        let format = ExamFormat {
+            eval_name: Self::name(),
+            repo_id: "ceval/ceval-exam",
+            eval_split: Split::Val,
+            question_column: "question",
+            answer_column: "answer",
+            // In CEval the name of the subject is present in the description so we have to include it here
+            description: |subject| {
+                format!("以下是中国关于{subject}的单项选择题，请选出其中的正确答案。\n\n")
+            },
+        };
+        let ret = Self(ChineseExam::load(format, SUBJECT_MAPPING)?);
         Ok(TaskType::LogLikelihood(Box::new(ret)))
     }
 
     pub const fn name() -> &'static str {
         "CEval-valid"
     }
-
-    fn get_subject_chinese_name(subject_en: &str) -> &str {
-        SUBJECT_MAPPING
-            .iter()
-            .find(|(en, _)| *en == subject_en)
-            .map(|(_, ch)| *ch)
-            .unwrap_or("未知学科") // "Unknown Subject", should never reach here though.
-    }
-
-    fn row_to_document(dataset: &Dataset, row: Row, subject_en: &str) -> Document {
-        let question = row
-            .get_string(dataset.get_column_id("question").unwrap())
-            .unwrap()
-            .trim()
-            .to_owned();
-
-        let option_a = row.get_string(dataset.get_column_id("A").unwrap()).unwrap();
-        let option_b = row.get_string(dataset.get_column_id("B").unwrap()).unwrap();
-        let option_c = row.get_string(dataset.get_column_id("C").unwrap()).unwrap();
-        let option_d = row.get_string(dataset.get_column_id("D").unwrap()).unwrap();
-
-        let choices = vec![
-            "A".to_owned(),
-            "B".to_owned(),
-            "C".to_owned(),
-            "D".to_owned(),
-        ];
-
-        // In CEval the name of the subject is present in the description so we have to include it here
-        let subject = Self::get_subject_chinese_name(subject_en);
-        let description =
-            format!("以下是中国关于{subject}的单项选择题，请选出其中的正确答案。\n\n");
-        let doc_to_text = format!(
-            "{question}\nA. {option_a}\nB. {option_b}\nC. {option_c}\nD. {option_d}\n答案："
-        );
-        let text = format!("{description}{doc_to_text}");
-
-        let answer_str = row
-            .get_string(dataset.get_column_id("answer").unwrap())
-            .unwrap();
-        let answer = match answer_str.as_str() {
-            "A" => 0,
-            "B" => 1,
-            "C" => 2,
-            "D" => 3,
-            _ => panic!("Invalid answer: {answer_str}"),
-        };
-
-        Document {
-            text,
-            choices,
-            answer,
-            category: Some(subject.to_owned()),
-            cot_content: None,
-            eval_name: CEval::name().to_string(),
-        }
-    }
 }
 
 impl LogLikelihoodTask for CEval {
     fn get_documents(&self) -> Vec<Document> {
-        let mut all_documents = Vec::new();
-
-        for (val_dataset, _, subject) in &self.datasets {
-            let documents: Vec<Document> = val_dataset
-                .iter()
-                .map(|row| CEval::row_to_document(val_dataset, row, subject))
-                .collect();
-            all_documents.extend(documents);
-        }
-
-        all_documents
+        self.0.get_documents()
     }
 
     fn get_fewshot_documents(&self) -> HashMap<String, Vec<Document>> {
-        let mut fewshot_documents = HashMap::new();
-
-        for (_, dev_dataset, subject) in &self.datasets {
-            let documents: Vec<Document> = dev_dataset
-                .iter()
-                .map(|row| CEval::row_to_document(dev_dataset, row, subject))
-                .collect();
-
-            for doc in documents {
-                if let Some(category) = &doc.category {
-                    fewshot_documents
-                        .entry(category.clone())
-                        .or_insert_with(Vec::new)
-                        .push(doc);
-                }
-            }
-        }
+        self.0.get_fewshot_documents()
+    }
 
-        fewshot_documents
+    fn train_split(&self) -> Option<Split> {
+        None
     }
 }
 
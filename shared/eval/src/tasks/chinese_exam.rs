@@ -0,0 +1,138 @@
+use crate::{load_dataset, traits::Document};
+use anyhow::{Context, Result, bail};
+use psyche_data_provider::{Dataset, Row, RowAccessor, Split};
+use std::collections::HashMap;
+use tracing::warn;
+
+/// How one of the Chinese four-choice exams (C-Eval, CMMLU) is laid out and prompted
+pub(super) struct ExamFormat {
+    pub eval_name: &'static str,
+    pub repo_id: &'static str,
+    /// Split the documents are evaluated on; few-shot examples always come from `dev`
+    pub eval_split: Split,
+    pub question_column: &'static str,
+    pub answer_column: &'static str,
+    /// Text before every question, given the subject's Chinese name
+    pub description: fn(&str) -> String,
+}
+
+/// The loaded subjects of a Chinese four-choice exam
+pub(super) struct ChineseExam {
+    format: ExamFormat,
+    datasets: Vec<(Dataset, Dataset, &'static str)>, // (eval, dev, subject in Chinese)
+}
+
+impl ChineseExam {
+    /// Loads each `(subject_en, subject_zh)`, downloading only that subject's files.
+    pub fn load<'a>(
+        format: ExamFormat,
+        subjects: impl IntoIterator<Item = &'a (&'static str, &'static str)>,
+    ) -> Result<Self> {
+        let mut datasets = Vec::new();
+        for (subject_en, subject_zh) in subjects {
+            let eval_dataset = load_dataset(
+                format.repo_id,
+                None,
+                format.eval_split,
+                Some(subject_en.to_string()),
+            )?;
+            let dev_dataset = load_dataset(
+                format.repo_id,
+                None,
+                Split::Dev,
+                Some(subject_en.to_string()),
+            )?;
+            datasets.push((eval_dataset, dev_dataset, *subject_zh));
+        }
+        Ok(Self { format, datasets })
+    }
+
+    pub fn get_documents(&self) -> Vec<Document> {
+        self.datasets
+            .iter()
+            .flat_map(|(eval_dataset, _, subject)| self.documents(eval_dataset, subject))
+            .collect()
+    }
+
+    pub fn get_fewshot_documents(&self) -> HashMap<String, Vec<Document>> {
+        let mut fewshot_documents = HashMap::new();
+        for (_, dev_dataset, subject) in &self.datasets {
+            fewshot_documents
+                .entry(subject.to_string())
+                .or_insert_with(Vec::new)
+                .extend(self.documents(dev_dataset, subject));
+        }
+        fewshot_documents
+    }
+
+    /// Converts every row of `dataset`, skipping the malformed ones
+    fn documents<'a>(
+        &'a self,
+        dataset: &'a Dataset,
+        subject: &'a str,
+    ) -> impl Iterator<Item = Document> + 'a {
+        dataset.iter().filter_map(move |row| {
+            self.row_to_document(dataset, row, subject)
+                .inspect_err(|err| {
+                    warn!(
+                        eval_name = self.format.eval_name,
+                        subject, "Skipping malformed row: {err:#}"
+                    )
+                })
+                .ok()
+        })
+    }
+
+    fn row_to_document(&self, dataset: &Dataset, row: Row, subject: &str) -> Result<Document> {
+        let column = |name: &str| -> Result<String> {
+            let id = dataset
+                .get_column_id(name)
+                .with_context(|| format!("no column {name}"))?;
+            Ok(row.get_string(id)?.to_owned())
+        };
+
+        let question = column(self.format.question_column)?.trim().to_owned();
+        let option_a = column("A")?;
+        let option_b = column("B")?;
+        let option_c = column("C")?;
+        let option_d = column("D")?;
+        let answer = parse_answer(&column(self.format.answer_column)?)?;
+
+        let description = (self.format.description)(subject);
+        let text = format!(
+            "{description}{question}\nA. {option_a}\nB. {option_b}\nC. {option_c}\nD. {option_d}\n答案："
+        );
+
+        Ok(Document {
+            text,
+            choices: ["A", "B", "C", "D"].map(String::from).to_vec(),
+            answer,
+            category: Some(subject.to_owned()),
+            cot_content: None,
+            eval_name: self.format.eval_name.to_string(),
+        })
+    }
+}
+
+fn parse_answer(answer: &str) -> Result<usize> {
+    match answer.trim() {
+        "A" => Ok(0),
+        "B" => Ok(1),
+        "C" => Ok(2),
+        "D" => Ok(3),
+        _ => bail!("invalid answer {answer:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_answer_letters() {
+        assert_eq!(parse_answer("A").unwrap(), 0);
+        assert_eq!(parse_answer(" D\n").unwrap(), 3);
+        assert!(parse_answer("E").is_err());
+        assert!(parse_answer("").is_err());
+    }
+}
@@ -0,0 +1,133 @@
+/**
+    Follows lm-evaluation-harness' `gsm8k` task with its `strict-match` filter:
+    the model continues the fewshot solutions' format, and the number after its last "####" is compared
+    with the reference answer by exact match.
+*/
+use crate::{
+    TaskType, load_dataset,
+    traits::{Document, GenerateUntilTask, PromptFormatter},
+};
+use anyhow::Result;
+use psyche_data_provider::{Dataset, Row, RowAccessor, Split};
+use std::{collections::HashMap, fmt::Display};
+
+const ANSWER_DELIMITER: &str = "####";
+
+pub struct GSM8K {
+    test_dataset: Dataset,
+    train_dataset: Dataset,
+}
+
+impl GSM8K {
+    pub fn load() -> Result<TaskType> {
+        let ret = Self {
+            test_dataset: load_dataset(
+                "openai/gsm8k",
+                None,
+                Split::Test,
+                Some("main".to_string()),
+            )?,
+            train_dataset: load_dataset(
+                "openai/gsm8k",
+                None,
+                Split::Train,
+                Some("main".to_string()),
+            )?,
+        };
+        Ok(TaskType::GenerateUntil(Box::new(ret)))
+    }
+
+    pub const fn name() -> &'static str {
+        "GSM8K"
+    }
+
+    fn row_to_document(dataset: &Dataset, row: Row) -> Document {
+        let text = row
+            .get_string(dataset.get_column_id("question").unwrap())
+            .unwrap()
+            .to_owned();
+        // the worked solution, ending with "#### <final answer>"
+        let solution = row
+            .get_string(dataset.get_column_id("answer").unwrap())
+            .unwrap()
+            .to_owned();
+        let final_answer = solution
+            .rsplit(ANSWER_DELIMITER)
+            .next()
+            .unwrap()
+            .trim()
+            .to_owned();
+
+        Document {
+            text,
+            choices: vec![final_answer],
+            answer: 0,
+            category: None,
+            cot_content: Some(solution),
+            eval_name: GSM8K::name().to_string(),
+        }
+    }
+}
+
+fn format_prompt(doc: &Document, fewshot_examples: &[Document]) -> String {
+    let mut request_str = String::new();
+    for example in fewshot_examples {
+        request_str.push_str(&format!(
+            "Question: {}\nAnswer: {}\n\n",
+            example.text,
+            example.cot_content.as_ref().unwrap()
+        ));
+    }
+    request_str.push_str(&format!("Question: {}\nAnswer:", doc.text));
+    request_str
+}
+
+impl GenerateUntilTask for GSM8K {
+    fn get_documents(&self) -> Vec<Document> {
+        self.test_dataset
+            .iter()
+            .map(|row| GSM8K::row_to_document(&self.test_dataset, row))
+            .collect()
+    }
+
+    fn get_fewshot_documents(&self) -> HashMap<String, Vec<Document>> {
+        let mut fewshot_documents = HashMap::new();
+        let docs: Vec<Document> = self
+            .train_dataset
+            .iter()
+            .map(|row| GSM8K::row_to_document(&self.train_dataset, row))
+            .collect();
+        fewshot_documents.insert("default".to_string(), docs);
+        fewshot_documents
+    }
+
+    fn train_split(&self) -> Option<Split> {
+        Some(Split::Train)
+    }
+
+    fn get_prompt_formatter(&self) -> PromptFormatter {
+        format_prompt
+    }
+
+    fn get_stop_string(&self) -> Vec<String> {
+        vec![
+            "Question:".to_string(),
+            "</s>".to_string(),
+            "<|im_end|>".to_string(),
+        ]
+    }
+
+    fn get_answer_extraction_regex(&self) -> String {
+        format!(r"{ANSWER_DELIMITER} (-?[0-9.,]+)")
+    }
+
+    fn get_expected_answer(&self, doc: &Document) -> String {
+        doc.choices[doc.answer].clone()
+    }
+}
+
+impl Display for GSM8K {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Self::name())
+    }
+}
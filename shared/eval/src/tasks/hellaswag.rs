@@ -108,6 +108,10 @@ impl LogLikelihoodTask for Hellaswag {
         });
         fewshot_documents
     }
+
+    fn train_split(&self) -> Option<Split> {
+        Some(Split::Train)
+    }
 }
 
 impl Display for Hellaswag {
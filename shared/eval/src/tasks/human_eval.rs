@@ -0,0 +1,173 @@
+/**
+    Follows lm-evaluation-harness' `humaneval` task: the model completes each function stub greedily,
+    and the completion counts as correct (pass@1) if the problem's tests pass when run with `python3`.
+
+    This runs model-generated code on the machine doing the eval, with that user's permissions,
+    so it refuses to load unless `HF_ALLOW_CODE_EVAL=1` is set, like lm-evaluation-harness does.
+    Only enable it somewhere that's fine, e.g. a sandboxed container.
+*/
+use crate::{
+    TaskType, load_dataset,
+    traits::{CompletionChecker, Document, GenerateUntilTask, PromptFormatter},
+};
+use anyhow::{Result, bail};
+use psyche_data_provider::{Dataset, Row, RowAccessor, Split};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::Path,
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+const ALLOW_CODE_EVAL_ENV: &str = "HF_ALLOW_CODE_EVAL";
+/// How long a completion's tests may run before they count as failed.
+const TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Gives every test program its own file, since evals may run on several threads.
+static NEXT_PROGRAM_ID: AtomicUsize = AtomicUsize::new(0);
+
+pub struct HumanEval {
+    test_dataset: Dataset,
+}
+
+impl HumanEval {
+    pub fn load() -> Result<TaskType> {
+        if std::env::var(ALLOW_CODE_EVAL_ENV).as_deref() != Ok("1") {
+            bail!(
+                "{} runs model-generated code on this machine. Set {ALLOW_CODE_EVAL_ENV}=1 to allow it.",
+                Self::name()
+            );
+        }
+        let ret = Self {
+            test_dataset: load_dataset(
+                "openai/openai_humaneval",
+                None,
+                Split::Test,
+                Some("openai_humaneval".to_string()),
+            )?,
+        };
+        Ok(TaskType::GenerateUntil(Box::new(ret)))
+    }
+
+    pub const fn name() -> &'static str {
+        "HumanEval"
+    }
+
+    fn row_to_document(dataset: &Dataset, row: Row) -> Document {
+        let column = |name: &str| {
+            row.get_string(dataset.get_column_id(name).unwrap())
+                .unwrap()
+                .to_owned()
+        };
+        // the test code defines `check(candidate)`, which asserts on calls to the completed function
+        let tests = format!("{}\ncheck({})\n", column("test"), column("entry_point"));
+
+        Document {
+            text: column("prompt"),
+            choices: vec![tests],
+            answer: 0,
+            category: None,
+            cot_content: None,
+            eval_name: HumanEval::name().to_string(),
+        }
+    }
+}
+
+impl GenerateUntilTask for HumanEval {
+    fn get_documents(&self) -> Vec<Document> {
+        self.test_dataset
+            .iter()
+            .map(|row| HumanEval::row_to_document(&self.test_dataset, row))
+            .collect()
+    }
+
+    fn get_fewshot_documents(&self) -> HashMap<String, Vec<Document>> {
+        // zero-shot only
+        HashMap::from([("default".to_string(), Vec::new())])
+    }
+
+    fn train_split(&self) -> Option<Split> {
+        None
+    }
+
+    fn get_prompt_formatter(&self) -> PromptFormatter {
+        |doc, _fewshot_examples| doc.text.clone()
+    }
+
+    fn get_stop_string(&self) -> Vec<String> {
+        vec!["\nclass ".to_string(), "\nif ".to_string()]
+    }
+
+    fn get_answer_extraction_regex(&self) -> String {
+        // unused, completions are checked by running them
+        String::new()
+    }
+
+    fn get_expected_answer(&self, doc: &Document) -> String {
+        doc.choices[doc.answer].clone()
+    }
+
+    fn get_answer_normalizer(&self) -> fn(&str) -> String {
+        |tests| tests.to_owned()
+    }
+
+    fn get_completion_checker(&self) -> Option<CompletionChecker> {
+        Some(passes_tests)
+    }
+}
+
+/// Runs the stub completed by `completion`, followed by `tests`, in a fresh `python3` process.
+fn passes_tests(prompt: &str, completion: &str, tests: &str) -> bool {
+    let path = std::env::temp_dir().join(format!(
+        "psyche-human-eval-{}-{}.py",
+        std::process::id(),
+        NEXT_PROGRAM_ID.fetch_add(1, Ordering::Relaxed)
+    ));
+    if let Err(err) = std::fs::write(&path, format!("{prompt}{completion}\n\n{tests}")) {
+        tracing::warn!(
+            "Couldn't write HumanEval program to {}: {err}",
+            path.display()
+        );
+        return false;
+    }
+    let passed = run_python(&path);
+    let _ = std::fs::remove_file(&path);
+    passed
+}
+
+fn run_python(path: &Path) -> bool {
+    let mut child = match Command::new("python3")
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(err) => {
+            tracing::warn!("Couldn't start python3 to run HumanEval tests: {err}");
+            return false;
+        }
+    };
+    let deadline = Instant::now() + TEST_TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return status.success(),
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(10)),
+            // timed out, or we lost track of the process
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return false;
+            }
+        }
+    }
+}
+
+impl Display for HumanEval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Self::name())
+    }
+}
@@ -0,0 +1,105 @@
+/**
+    Follows lm-evaluation-harness' `lambada_openai`, but scored by generation instead of log-likelihood:
+    the model is given the passage without its last word, and its continuation's first word must match it
+    (ignoring case and punctuation). Words can span several tokens, so a few tokens are generated rather than one.
+*/
+use crate::{
+    TaskType, load_dataset,
+    traits::{Document, GenerateUntilTask, PromptFormatter},
+};
+use anyhow::Result;
+use psyche_data_provider::{Dataset, Row, RowAccessor, Split};
+use std::{collections::HashMap, fmt::Display};
+
+/// Enough for any one English word.
+const MAX_WORD_TOKENS: usize = 8;
+
+pub struct LAMBADA {
+    test_dataset: Dataset,
+}
+
+impl LAMBADA {
+    pub fn load() -> Result<TaskType> {
+        let ret = Self {
+            test_dataset: load_dataset("EleutherAI/lambada_openai", None, Split::Test, None)?,
+        };
+        Ok(TaskType::GenerateUntil(Box::new(ret)))
+    }
+
+    pub const fn name() -> &'static str {
+        "LAMBADA"
+    }
+
+    fn row_to_document(dataset: &Dataset, row: Row) -> Document {
+        let passage = row
+            .get_string(dataset.get_column_id("text").unwrap())
+            .unwrap();
+        let (text, last_word) = passage.rsplit_once(' ').unwrap();
+
+        Document {
+            text: text.to_owned(),
+            choices: vec![last_word.to_owned()],
+            answer: 0,
+            category: None,
+            cot_content: None,
+            eval_name: LAMBADA::name().to_string(),
+        }
+    }
+}
+
+fn normalize_word(word: &str) -> String {
+    word.chars()
+        .filter(|c| !c.is_ascii_punctuation())
+        .collect::<String>()
+        .trim()
+        .to_lowercase()
+}
+
+impl GenerateUntilTask for LAMBADA {
+    fn get_documents(&self) -> Vec<Document> {
+        self.test_dataset
+            .iter()
+            .map(|row| LAMBADA::row_to_document(&self.test_dataset, row))
+            .collect()
+    }
+
+    fn get_fewshot_documents(&self) -> HashMap<String, Vec<Document>> {
+        // there's only a test split, so LAMBADA is zero-shot.
+        HashMap::from([("default".to_string(), Vec::new())])
+    }
+
+    fn train_split(&self) -> Option<Split> {
+        None
+    }
+
+    fn get_prompt_formatter(&self) -> PromptFormatter {
+        |doc, _fewshot_examples| doc.text.clone()
+    }
+
+    fn get_stop_string(&self) -> Vec<String> {
+        vec!["\n".to_string()]
+    }
+
+    fn get_answer_extraction_regex(&self) -> String {
+        // the first word of the continuation
+        r"^\s*(\S+)".to_string()
+    }
+
+    fn get_expected_answer(&self, doc: &Document) -> String {
+        doc.choices[doc.answer].clone()
+    }
+
+    fn get_answer_normalizer(&self) -> fn(&str) -> String {
+        normalize_word
+    }
+
+    fn get_max_generated_tokens(&self) -> usize {
+        MAX_WORD_TOKENS
+    }
+}
+
+impl Display for LAMBADA {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Self::name())
+    }
+}
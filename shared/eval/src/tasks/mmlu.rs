@@ -88,6 +88,10 @@ impl LogLikelihoodTask for MMLU {
         fewshot_documents
     }
 
+    fn train_split(&self) -> Option<Split> {
+        None
+    }
+
     fn get_preamble(&self, category: &str) -> String {
         format!(
             "The following are multiple choice questions (with answers) about {}.\n\n",
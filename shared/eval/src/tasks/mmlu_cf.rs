@@ -99,6 +99,10 @@ impl LogLikelihoodTask for MMLUCF {
         fewshot_documents
     }
 
+    fn train_split(&self) -> Option<Split> {
+        None
+    }
+
     fn get_preamble(&self, category: &str) -> String {
         format!(
             "The following are multiple choice questions (with answers) about {}.\n\n",
@@ -8,7 +8,7 @@
 */
 use crate::{
     ASCII_UPPERCASE, TaskType, load_dataset,
-    traits::{Document, GenerateUntilTask},
+    traits::{Document, GenerateUntilTask, PromptFormatter},
 };
 use anyhow::Result;
 use psyche_data_provider::{Dataset, Row, RowAccessor, Split};
@@ -74,6 +74,49 @@ impl MMLUPro {
     }
 }
 
+fn format_prompt(doc: &Document, fewshot_examples: &[Document]) -> String {
+    let category = doc.category.as_deref().unwrap();
+    let mut request_str = format!(
+        "The following are multiple choice questions (with answers) about {category}. Think step by step and then finish your answer with \"the answer is (X)\" where X is the correct letter choice.\n"
+    );
+
+    // Add fewshot examples with their answers
+    for example in fewshot_examples {
+        request_str.push_str("Question:\n");
+
+        request_str.push_str(&example.text);
+        request_str.push_str("\nOptions:\n");
+
+        // Format choices with letter labels
+        for (i, choice) in example.choices.iter().enumerate() {
+            let letter = ASCII_UPPERCASE[i];
+            request_str.push_str(&format!("{letter}. {choice}\n"));
+        }
+
+        // Replace "A:" with "Answer:" in cot_content
+        let mut cot_content = example.cot_content.as_ref().unwrap().clone();
+        if cot_content.starts_with("A:") {
+            cot_content = format!("Answer:{}", &cot_content[2..]);
+        }
+        request_str.push_str(&cot_content);
+        request_str.push_str("\n\n");
+    }
+
+    // Add the current question without answer
+    request_str.push_str("Question:\n");
+    request_str.push_str(&doc.text);
+    request_str.push_str("\nOptions:\n");
+
+    // Format choices with letter labels
+    for (i, choice) in doc.choices.iter().enumerate() {
+        let letter = ASCII_UPPERCASE[i];
+        request_str.push_str(&format!("{letter}. {choice}\n"));
+    }
+
+    request_str.push_str("Answer: Let's think step by step.");
+    request_str
+}
+
 impl GenerateUntilTask for MMLUPro {
     fn get_documents(&self) -> Vec<Document> {
         self.test_dataset
@@ -95,6 +138,14 @@ impl GenerateUntilTask for MMLUPro {
         fewshot_documents
     }
 
+    fn train_split(&self) -> Option<Split> {
+        None
+    }
+
+    fn get_prompt_formatter(&self) -> PromptFormatter {
+        format_prompt
+    }
+
     fn get_stop_string(&self) -> Vec<String> {
         vec!["Question:".to_string()]
     }
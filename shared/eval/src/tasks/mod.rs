@@ -1,20 +1,35 @@
 mod arc;
 mod boolq;
 mod ceval;
+mod chinese_exam;
+mod cmmlu;
+mod gsm8k;
 mod hellaswag;
+mod human_eval;
+mod lambada;
 mod mmlu;
 mod mmlu_cf;
 mod mmlu_pro;
 mod openbookqa;
+mod perplexity;
 mod piqa;
+mod truthfulqa;
+mod winogrande;
 
 pub use arc::ArcChallenge;
 pub use arc::ArcEasy;
 pub use boolq::BoolQ;
 pub use ceval::CEval;
+pub use cmmlu::CMMLU;
+pub use gsm8k::GSM8K;
 pub use hellaswag::Hellaswag;
+pub use human_eval::HumanEval;
+pub use lambada::LAMBADA;
 pub use mmlu::MMLU;
 pub use mmlu_cf::MMLUCF;
 pub use mmlu_pro::MMLUPro;
 pub use openbookqa::OpenbookQA;
+pub use perplexity::{DEFAULT_PERPLEXITY_STRIDE, Perplexity};
 pub use piqa::PIQA;
+pub use truthfulqa::TruthfulQA;
+pub use winogrande::WinoGrande;
@@ -103,6 +103,10 @@ impl LogLikelihoodTask for OpenbookQA {
         fewshot_documents.insert("default".to_string(), docs);
         fewshot_documents
     }
+
+    fn train_split(&self) -> Option<Split> {
+        None
+    }
 }
 
 impl Display for OpenbookQA {
@@ -0,0 +1,60 @@
+use crate::TaskType;
+use anyhow::{Result, anyhow, bail};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+/// Number of new tokens scored by each window when not given explicitly.
+pub const DEFAULT_PERPLEXITY_STRIDE: usize = 512;
+
+/// Perplexity and bits-per-character of a model on an arbitrary text file, e.g. a validation split.
+///
+/// The tokenized file is scored `stride` tokens at a time, each window seeing as many preceding tokens
+/// as fit in the model's context (so consecutive windows overlap by `max_context_length - stride` tokens).
+pub struct Perplexity {
+    path: PathBuf,
+    stride: usize,
+    text: String,
+}
+
+impl Perplexity {
+    pub fn new(path: impl AsRef<Path>, stride: usize) -> Result<Self> {
+        if stride == 0 {
+            bail!("perplexity stride must be greater than zero");
+        }
+        let path = path.as_ref().to_path_buf();
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow!("failed to read perplexity text {}: {e}", path.display()))?;
+        Ok(Self { path, stride, text })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<TaskType> {
+        Ok(TaskType::Perplexity(Self::new(
+            path,
+            DEFAULT_PERPLEXITY_STRIDE,
+        )?))
+    }
+
+    pub const fn name() -> &'static str {
+        "Perplexity"
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl Display for Perplexity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", Self::name(), self.path.display())
+    }
+}
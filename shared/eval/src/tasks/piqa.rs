@@ -76,6 +76,10 @@ impl LogLikelihoodTask for PIQA {
         fewshot_documents.insert("default".to_string(), docs);
         fewshot_documents
     }
+
+    fn train_split(&self) -> Option<Split> {
+        Some(Split::Train)
+    }
 }
 
 impl Display for PIQA {
@@ -0,0 +1,104 @@
+use crate::{
+    TaskType, load_dataset,
+    traits::{Document, LogLikelihoodTask},
+};
+use anyhow::Result;
+use psyche_data_provider::{Dataset, Row, RowAccessor, Split};
+use std::{collections::HashMap, fmt::Display};
+
+pub struct WinoGrande {
+    train_dataset: Dataset,
+    validation_dataset: Dataset,
+}
+
+impl WinoGrande {
+    pub fn load() -> Result<TaskType> {
+        let ret = Self {
+            train_dataset: load_dataset(
+                "allenai/winogrande",
+                None,
+                Split::Train,
+                Some("winogrande_xl".to_string()),
+            )?,
+            // the test split's answers are hidden
+            validation_dataset: load_dataset(
+                "allenai/winogrande",
+                None,
+                Split::Validation,
+                Some("winogrande_xl".to_string()),
+            )?,
+        };
+        Ok(TaskType::LogLikelihood(Box::new(ret)))
+    }
+
+    pub const fn name() -> &'static str {
+        "WinoGrande"
+    }
+
+    fn row_to_document(dataset: &Dataset, row: Row) -> Document {
+        let sentence = row
+            .get_string(dataset.get_column_id("sentence").unwrap())
+            .unwrap();
+        let option1 = row
+            .get_string(dataset.get_column_id("option1").unwrap())
+            .unwrap();
+        let option2 = row
+            .get_string(dataset.get_column_id("option2").unwrap())
+            .unwrap();
+
+        // the sentence up to the blank is shared, each choice fills the blank and finishes the sentence
+        let (before_blank, after_blank) = sentence.split_once('_').unwrap();
+        let text = before_blank.trim_end().to_owned();
+        let choices = vec![
+            format!("{option1}{after_blank}"),
+            format!("{option2}{after_blank}"),
+        ];
+
+        // "1" or "2"
+        let answer = row
+            .get_string(dataset.get_column_id("answer").unwrap())
+            .unwrap()
+            .parse::<usize>()
+            .unwrap()
+            - 1;
+
+        Document {
+            text,
+            choices,
+            answer,
+            category: None,
+            cot_content: None,
+            eval_name: WinoGrande::name().to_string(),
+        }
+    }
+}
+
+impl LogLikelihoodTask for WinoGrande {
+    fn get_documents(&self) -> Vec<Document> {
+        self.validation_dataset
+            .iter()
+            .map(|row| WinoGrande::row_to_document(&self.validation_dataset, row))
+            .collect()
+    }
+
+    fn get_fewshot_documents(&self) -> HashMap<String, Vec<Document>> {
+        let mut fewshot_documents = HashMap::new();
+        let docs: Vec<Document> = self
+            .train_dataset
+            .iter()
+            .map(|row| WinoGrande::row_to_document(&self.train_dataset, row))
+            .collect();
+        fewshot_documents.insert("default".to_string(), docs);
+        fewshot_documents
+    }
+
+    fn train_split(&self) -> Option<Split> {
+        Some(Split::Train)
+    }
+}
+
+impl Display for WinoGrande {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Self::name())
+    }
+}
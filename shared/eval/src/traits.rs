@@ -1,3 +1,6 @@
+use crate::{ASCII_UPPERCASE, harness::GENERATE_UNTIL_MAX_TOKENS};
+
+use psyche_data_provider::Split;
 use std::{collections::HashMap, fmt::Display};
 
 #[derive(Clone)]
@@ -13,14 +16,57 @@ pub struct Document {
 pub trait LogLikelihoodTask: Send + Display {
     fn get_documents(&self) -> Vec<Document>;
     fn get_fewshot_documents(&self) -> HashMap<String, Vec<Document>>;
+    /// `Some(Split::Train)` if [`Self::get_fewshot_documents`] come from the training split, so
+    /// few-shot examples are sampled at random. Otherwise they're a curated set taken in order.
+    fn train_split(&self) -> Option<Split>;
     fn get_preamble(&self, _category: &str) -> String {
         String::new()
     }
 }
 
+/// Builds the prompt to generate from for a document, with few-shot examples (already limited to
+/// the number of shots) solved before it.
+pub type PromptFormatter = fn(&Document, &[Document]) -> String;
+
+/// Decides whether a generation is correct from its prompt, the generated text (cut off before
+/// the first stop string) and the normalized expected answer.
+pub type CompletionChecker = fn(&str, &str, &str) -> bool;
+
 pub trait GenerateUntilTask: Send + Display {
     fn get_documents(&self) -> Vec<Document>;
     fn get_fewshot_documents(&self) -> HashMap<String, Vec<Document>>;
+    /// Same as [`LogLikelihoodTask::train_split`]
+    fn train_split(&self) -> Option<Split>;
+    /// Prompts are built when evaluating rather than when preparing, since every eval step picks
+    /// its own few-shot examples.
+    fn get_prompt_formatter(&self) -> PromptFormatter;
     fn get_stop_string(&self) -> Vec<String>;
+    /// Its first capture group is compared against [`Self::get_expected_answer`].
     fn get_answer_extraction_regex(&self) -> String;
+    fn get_expected_answer(&self, doc: &Document) -> String {
+        ASCII_UPPERCASE[doc.answer].to_string()
+    }
+    /// Applied to both the extracted and the expected answer before comparing them.
+    fn get_answer_normalizer(&self) -> fn(&str) -> String {
+        strip_number_formatting
+    }
+    fn get_max_generated_tokens(&self) -> usize {
+        GENERATE_UNTIL_MAX_TOKENS
+    }
+    /// Scores generations with this instead of extracting an answer with
+    /// [`Self::get_answer_extraction_regex`], e.g. to run generated code against tests.
+    fn get_completion_checker(&self) -> Option<CompletionChecker> {
+        None
+    }
+}
+
+/// Same as lm-evaluation-harness' `exact_match` with `regexes_to_ignore: [",", "\\$", "\\.$"]`,
+/// so "$1,000." and "1000" are the same answer.
+pub(crate) fn strip_number_formatting(answer: &str) -> String {
+    let answer: String = answer
+        .trim()
+        .chars()
+        .filter(|c| *c != ',' && *c != '$')
+        .collect();
+    answer.strip_suffix('.').unwrap_or(&answer).to_string()
 }
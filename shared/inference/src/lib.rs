@@ -5,7 +5,7 @@ pub mod protocol;
 pub mod protocol_handler;
 pub mod vllm;
 
-pub use node::InferenceNode;
+pub use node::{Generation, InferenceNode, Prompt, SamplingParams};
 pub use protocol::{
     ChatMessage, InferenceGossipMessage, InferenceMessage, InferenceRequest, InferenceResponse,
     ModelSource,
@@ -1,16 +1,59 @@
 //! Inference Node implementation
 
-use crate::protocol::{InferenceRequest, InferenceResponse};
-use crate::vllm;
+use crate::protocol::{ChatMessage, InferenceRequest, InferenceResponse};
+use crate::vllm::{self, TextCallback};
 use anyhow::{Context, Result, anyhow};
+use psyche_metrics::InferenceMetrics;
 use pyo3::prelude::*;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing::{debug, info, warn};
 
+/// Input to a generation: chat messages formatted with the model's chat template,
+/// or a raw text prompt used as-is
+#[derive(Debug, Clone)]
+pub enum Prompt {
+    Chat(Vec<ChatMessage>),
+    Text(String),
+}
+
+/// Sampling parameters forwarded to vLLM's `SamplingParams`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplingParams {
+    pub temperature: f64,
+    pub top_p: f64,
+    pub max_tokens: usize,
+    pub stop: Vec<String>,
+}
+
+impl Default for SamplingParams {
+    fn default() -> Self {
+        Self {
+            temperature: 1.0,
+            top_p: 1.0,
+            max_tokens: 100,
+            stop: vec![],
+        }
+    }
+}
+
+/// Output of a single generation, with token usage
+#[derive(Debug, Clone)]
+pub struct Generation {
+    pub generated_text: String,
+    pub full_text: String,
+    pub finish_reason: Option<String>,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+}
+
 #[derive(Debug)]
 pub struct InferenceNode {
     engine_id: String,
     model_name: String,
     initialized: bool,
+    metrics: Option<Arc<InferenceMetrics>>,
+    max_acceptable_latency: Option<Duration>,
 }
 
 impl InferenceNode {
@@ -25,9 +68,23 @@ impl InferenceNode {
             engine_id,
             model_name,
             initialized: false,
+            metrics: None,
+            max_acceptable_latency: None,
         }
     }
 
+    /// Record each request's latency in `metrics`, warning about any request
+    /// slower than `max_acceptable_latency_ms`
+    pub fn with_latency_metrics(
+        mut self,
+        metrics: Arc<InferenceMetrics>,
+        max_acceptable_latency_ms: Option<u64>,
+    ) -> Self {
+        self.metrics = Some(metrics);
+        self.max_acceptable_latency = max_acceptable_latency_ms.map(Duration::from_millis);
+        self
+    }
+
     /// Initialize the vLLM engine
     pub fn initialize(
         &mut self,
@@ -70,54 +127,129 @@ impl InferenceNode {
 
     /// Run inference on a request
     pub fn inference(&self, request: &InferenceRequest) -> Result<InferenceResponse> {
-        if !self.initialized {
-            return Err(anyhow!("Engine not initialized. Call initialize() first."));
-        }
-
         debug!(
             "Running inference for request: {} with {} messages",
             request.request_id,
             request.messages.len()
         );
 
+        let generation = self.generate(
+            &Prompt::Chat(request.messages.clone()),
+            &SamplingParams {
+                temperature: request.temperature,
+                top_p: request.top_p,
+                max_tokens: request.max_tokens,
+                stop: vec![],
+            },
+        )?;
+
+        debug!(
+            "Inference completed for request: {}, generated {} chars",
+            request.request_id,
+            generation.generated_text.len()
+        );
+
+        Ok(InferenceResponse {
+            request_id: request.request_id.clone(),
+            generated_text: generation.generated_text,
+            full_text: generation.full_text,
+            finish_reason: generation
+                .finish_reason
+                .or_else(|| Some("stop".to_string())),
+        })
+    }
+
+    /// Generate a completion for a prompt with the given sampling parameters
+    pub fn generate(&self, prompt: &Prompt, params: &SamplingParams) -> Result<Generation> {
+        self.run_generation(prompt, params, None)
+    }
+
+    /// Like [`Self::generate`], also passing each new piece of the completion to `on_text` as
+    /// soon as the engine produces it
+    pub fn generate_streaming(
+        &self,
+        prompt: &Prompt,
+        params: &SamplingParams,
+        on_text: impl Fn(String) + Send + 'static,
+    ) -> Result<Generation> {
+        self.run_generation(prompt, params, Some(Box::new(on_text)))
+    }
+
+    fn run_generation(
+        &self,
+        prompt: &Prompt,
+        params: &SamplingParams,
+        on_text: Option<TextCallback>,
+    ) -> Result<Generation> {
+        if !self.initialized {
+            return Err(anyhow!("Engine not initialized. Call initialize() first."));
+        }
+
+        let start = Instant::now();
+        let result = Python::with_gil(|py| {
+            vllm::generate(py, &self.engine_id, prompt, params, on_text)
+                .context("Failed to run inference")
+        });
+        let latency = start.elapsed();
+        if let Some(metrics) = &self.metrics {
+            metrics.record_request_latency(latency);
+        }
+        if let Some(max_latency) = self.max_acceptable_latency {
+            if latency > max_latency {
+                warn!(
+                    "Inference request took {:?}, above the acceptable {:?}",
+                    latency, max_latency
+                );
+            }
+        }
+
+        let result = result?;
+
+        // Check status
+        if !result.success {
+            let error = result.error.unwrap_or_else(|| "Unknown error".to_string());
+            return Err(anyhow!("Inference failed: {}", error));
+        }
+
+        // Extract generated text
+        let generated_text = result
+            .generated_text
+            .ok_or_else(|| anyhow!("Missing generated_text in response"))?;
+
+        let full_text = result
+            .full_text
+            .ok_or_else(|| anyhow!("Missing full_text in response"))?;
+
+        Ok(Generation {
+            generated_text,
+            full_text,
+            finish_reason: result.finish_reason,
+            prompt_tokens: result.prompt_tokens.unwrap_or_default() as usize,
+            completion_tokens: result.completion_tokens.unwrap_or_default() as usize,
+        })
+    }
+
+    /// Load new weights for the same model into the running engine, without restarting it.
+    /// Must not run concurrently with inference.
+    pub fn update_weights(&mut self, checkpoint_path: &str) -> Result<()> {
+        if !self.initialized {
+            return Err(anyhow!("Engine not initialized"));
+        }
+
+        info!(
+            "Updating weights of {} from {}",
+            self.engine_id, checkpoint_path
+        );
+
         Python::with_gil(|py| {
-            let result = vllm::run_inference(
-                py,
-                &self.engine_id,
-                request.messages.clone(),
-                Some(request.temperature),
-                Some(request.top_p),
-                Some(request.max_tokens as i32),
-            )
-            .context("Failed to run inference")?;
+            let result = vllm::update_weights(py, &self.engine_id, checkpoint_path)
+                .context("Failed to update weights")?;
 
-            // Check status
             if !result.success {
                 let error = result.error.unwrap_or_else(|| "Unknown error".to_string());
-                return Err(anyhow!("Inference failed: {}", error));
+                return Err(anyhow!("Weight update failed: {}", error));
             }
-
-            // Extract generated text
-            let generated_text = result
-                .generated_text
-                .ok_or_else(|| anyhow!("Missing generated_text in response"))?;
-
-            let full_text = result
-                .full_text
-                .ok_or_else(|| anyhow!("Missing full_text in response"))?;
-
-            debug!(
-                "Inference completed for request: {}, generated {} chars",
-                request.request_id,
-                generated_text.len()
-            );
-
-            Ok(InferenceResponse {
-                request_id: request.request_id.clone(),
-                generated_text,
-                full_text,
-                finish_reason: Some("stop".to_string()),
-            })
+            Ok(())
         })
     }
 
@@ -32,8 +32,16 @@ pub enum InferenceGossipMessage {
 pub enum InferenceMessage {
     Request(InferenceRequest),
     Response(InferenceResponse),
-    StreamChunk { request_id: String, text: String },
-    Cancel { request_id: String },
+    StreamChunk {
+        request_id: String,
+        text: String,
+    },
+    Cancel {
+        request_id: String,
+    },
+    /// health check, answered with `Pong`
+    Ping,
+    Pong,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,6 +79,16 @@ impl InferenceProtocol {
                     peer_id.fmt_short()
                 );
             }
+            InferenceMessage::Ping => {
+                debug!("Answering ping from {}", peer_id.fmt_short());
+                let pong_bytes = postcard::to_allocvec(&InferenceMessage::Pong)
+                    .context("Failed to serialize pong")?;
+                send.write_all(&pong_bytes).await?;
+                send.finish()?;
+
+                // the pinger closes the connection once it has read the pong
+                connection.closed().await;
+            }
             _ => {
                 error!("Unexpected message type from {}", peer_id.fmt_short());
             }
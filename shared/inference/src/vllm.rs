@@ -7,19 +7,21 @@
 //! For production use in Psyche inference nodes:
 //! - Each inference node runs as a long-running Python subprocess
 //! - The subprocess creates ONE vLLM engine and handles many inference requests
-//! - When checkpoint updates arrive, the subprocess is killed and a new one spawned
-//!   with the new checkpoint path
+//! - When checkpoint updates arrive, the new weights are loaded into the running engine
 //!
 //! # API
 //!
 //! - `create_engine()` - Create and register a vLLM engine (called once per subprocess)
 //! - `run_inference()` - Run inference on a registered engine
+//! - `generate()` - Run inference on a chat or raw prompt with full sampling parameters,
+//!   optionally passing on the text as it's generated
+//! - `update_weights()` - Load a checkpoint's weights into a registered engine
 //! - `get_engine_stats()` - Get engine statistics
 //! - `list_engines()` - List all registered engines
 //! - `shutdown_engine()` - Shutdown and cleanup an engine
 
 use pyo3::prelude::*;
-use pyo3::types::PyDict;
+use pyo3::types::{PyCFunction, PyDict, PyTuple};
 use std::collections::HashMap;
 
 /// Response from engine creation
@@ -37,6 +39,9 @@ pub struct InferenceResult {
     pub request_id: Option<String>,
     pub generated_text: Option<String>,
     pub full_text: Option<String>,
+    pub finish_reason: Option<String>,
+    pub prompt_tokens: Option<i64>,
+    pub completion_tokens: Option<i64>,
     pub error: Option<String>,
 }
 
@@ -48,6 +53,14 @@ pub struct ShutdownResult {
     pub error: Option<String>,
 }
 
+/// Response from weight update request
+#[derive(Debug, Clone)]
+pub struct UpdateWeightsResult {
+    pub success: bool,
+    pub engine_id: Option<String>,
+    pub error: Option<String>,
+}
+
 /// Response from engine stats request
 #[derive(Debug, Clone)]
 pub struct EngineStats {
@@ -138,6 +151,46 @@ pub fn create_engine(
     })
 }
 
+/// Helper to convert chat messages to a Python list of dicts
+fn messages_to_py_list(
+    py: Python,
+    messages: Vec<crate::protocol::ChatMessage>,
+) -> PyResult<Bound<'_, pyo3::types::PyList>> {
+    let py_messages = pyo3::types::PyList::empty(py);
+    for msg in messages {
+        let py_msg = pyo3::types::PyDict::new(py);
+        py_msg.set_item("role", msg.role)?;
+        py_msg.set_item("content", msg.content)?;
+        py_messages.append(py_msg)?;
+    }
+    Ok(py_messages)
+}
+
+/// Helper to call the bridge's `run_inference` and parse its result
+fn call_run_inference(
+    py: Python,
+    rust_bridge: &Bound<'_, PyModule>,
+    kwargs: &Bound<'_, PyDict>,
+) -> PyResult<InferenceResult> {
+    let result = rust_bridge.call_method("run_inference", (), Some(kwargs))?;
+    let dict = result.downcast::<PyDict>()?;
+    let map = py_dict_to_hashmap(dict)?;
+
+    let status = get_optional_string(&map, "status", py).unwrap_or_default();
+    let success = status == "success";
+
+    Ok(InferenceResult {
+        success,
+        request_id: get_optional_string(&map, "request_id", py),
+        generated_text: get_optional_string(&map, "generated_text", py),
+        full_text: get_optional_string(&map, "full_text", py),
+        finish_reason: get_optional_string(&map, "finish_reason", py),
+        prompt_tokens: get_optional_i64(&map, "prompt_tokens", py),
+        completion_tokens: get_optional_i64(&map, "completion_tokens", py),
+        error: get_optional_string(&map, "error", py),
+    })
+}
+
 /// Run inference on an engine
 pub fn run_inference(
     py: Python,
@@ -151,15 +204,7 @@ pub fn run_inference(
 
     let kwargs = PyDict::new(py);
     kwargs.set_item("engine_id", engine_id)?;
-
-    let py_messages = pyo3::types::PyList::empty(py);
-    for msg in messages {
-        let py_msg = pyo3::types::PyDict::new(py);
-        py_msg.set_item("role", msg.role)?;
-        py_msg.set_item("content", msg.content)?;
-        py_messages.append(py_msg)?;
-    }
-    kwargs.set_item("messages", py_messages)?;
+    kwargs.set_item("messages", messages_to_py_list(py, messages)?)?;
 
     if let Some(temp) = temperature {
         kwargs.set_item("temperature", temp)?;
@@ -173,34 +218,87 @@ pub fn run_inference(
         kwargs.set_item("max_tokens", mt)?;
     }
 
-    let result = rust_bridge.call_method("run_inference", (), Some(&kwargs))?;
+    call_run_inference(py, &rust_bridge, &kwargs)
+}
+
+/// Called with each new piece of a completion while it's being generated
+pub type TextCallback = Box<dyn Fn(String) + Send + 'static>;
+
+/// Run inference on an engine for a chat or raw text prompt
+pub fn generate(
+    py: Python,
+    engine_id: &str,
+    prompt: &crate::node::Prompt,
+    params: &crate::node::SamplingParams,
+    on_text: Option<TextCallback>,
+) -> PyResult<InferenceResult> {
+    let rust_bridge = py.import("psyche.vllm.rust_bridge")?;
+
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("engine_id", engine_id)?;
+    match prompt {
+        crate::node::Prompt::Chat(messages) => {
+            kwargs.set_item("messages", messages_to_py_list(py, messages.clone())?)?;
+        }
+        crate::node::Prompt::Text(text) => kwargs.set_item("prompt", text)?,
+    }
+    kwargs.set_item("temperature", params.temperature)?;
+    kwargs.set_item("top_p", params.top_p)?;
+    kwargs.set_item("max_tokens", params.max_tokens as i32)?;
+    if !params.stop.is_empty() {
+        kwargs.set_item("stop", params.stop.clone())?;
+    }
+    if let Some(on_text) = on_text {
+        let on_text = PyCFunction::new_closure(
+            py,
+            None,
+            None,
+            move |args: &Bound<'_, PyTuple>, _kwargs: Option<&Bound<'_, PyDict>>| -> PyResult<()> {
+                on_text(args.get_item(0)?.extract()?);
+                Ok(())
+            },
+        )?;
+        kwargs.set_item("on_text", on_text)?;
+    }
+
+    call_run_inference(py, &rust_bridge, &kwargs)
+}
+
+/// Shutdown an engine
+pub fn shutdown_engine(py: Python, engine_id: &str) -> PyResult<ShutdownResult> {
+    let rust_bridge = py.import("psyche.vllm.rust_bridge")?;
+
+    let result = rust_bridge.call_method1("shutdown_engine", (engine_id,))?;
     let dict = result.downcast::<PyDict>()?;
     let map = py_dict_to_hashmap(dict)?;
 
     let status = get_optional_string(&map, "status", py).unwrap_or_default();
     let success = status == "success";
 
-    Ok(InferenceResult {
+    Ok(ShutdownResult {
         success,
-        request_id: get_optional_string(&map, "request_id", py),
-        generated_text: get_optional_string(&map, "generated_text", py),
-        full_text: get_optional_string(&map, "full_text", py),
+        engine_id: get_optional_string(&map, "engine_id", py),
         error: get_optional_string(&map, "error", py),
     })
 }
 
-/// Shutdown an engine
-pub fn shutdown_engine(py: Python, engine_id: &str) -> PyResult<ShutdownResult> {
+/// Load the weights of the checkpoint at `checkpoint_path` (a local directory or HF repo)
+/// into a running engine
+pub fn update_weights(
+    py: Python,
+    engine_id: &str,
+    checkpoint_path: &str,
+) -> PyResult<UpdateWeightsResult> {
     let rust_bridge = py.import("psyche.vllm.rust_bridge")?;
 
-    let result = rust_bridge.call_method1("shutdown_engine", (engine_id,))?;
+    let result = rust_bridge.call_method1("update_weights", (engine_id, checkpoint_path))?;
     let dict = result.downcast::<PyDict>()?;
     let map = py_dict_to_hashmap(dict)?;
 
     let status = get_optional_string(&map, "status", py).unwrap_or_default();
     let success = status == "success";
 
-    Ok(ShutdownResult {
+    Ok(UpdateWeightsResult {
         success,
         engine_id: get_optional_string(&map, "engine_id", py),
         error: get_optional_string(&map, "error", py),
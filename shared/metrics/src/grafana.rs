@@ -0,0 +1,212 @@
+//! A Grafana dashboard for the metrics [`ClientMetrics`](crate::ClientMetrics) exports,
+//! as scraped by Prometheus.
+//!
+//! The Prometheus exporter appends `_total` to counter names and `_bucket` to histogram
+//! buckets, so queries use those forms. Clients are told apart by the `run_id` label,
+//! which comes from the `run.id` OpenTelemetry resource attribute.
+
+use serde_json::{Value, json};
+
+const TRAINING_LOSS: &str = "psyche_training_loss";
+const WARMUP_LOSS: &str = "psyche_warmup_loss";
+const ROUND_STEP: &str = "psyche_round_step";
+const STEP_DURATION: &str = "psyche_step_duration_seconds";
+const GPU_USAGE: &str = "psyche_gpu_usage_percent";
+const BANDWIDTH: &str = "psyche_bandwidth_bytes_per_second";
+const DOWNLOAD_BYTES: &str = "psyche_download_bytes";
+const GOSSIP_NEIGHBORS: &str = "psyche_gossip_neighbors";
+const DOWNLOADS_STARTED: &str = "psyche_downloads_started";
+const DOWNLOADS_FINISHED: &str = "psyche_downloads_finished";
+
+const RUN_FILTER: &str = r#"run_id="$run_id""#;
+
+fn target(ref_id: &str, expr: String, legend: &str) -> Value {
+    json!({
+        "refId": ref_id,
+        "datasource": { "type": "prometheus", "uid": "${datasource}" },
+        "expr": expr,
+        "legendFormat": legend,
+    })
+}
+
+fn panel(
+    id: u32,
+    title: &str,
+    kind: &str,
+    unit: &str,
+    x: u32,
+    y: u32,
+    targets: Vec<Value>,
+) -> Value {
+    json!({
+        "id": id,
+        "title": title,
+        "type": kind,
+        "datasource": { "type": "prometheus", "uid": "${datasource}" },
+        "gridPos": { "h": 8, "w": 12, "x": x, "y": y },
+        "fieldConfig": { "defaults": { "unit": unit }, "overrides": [] },
+        "targets": targets,
+    })
+}
+
+/// Builds the dashboard JSON, ready to be imported into Grafana.
+pub fn grafana_dashboard() -> Value {
+    let panels = vec![
+        panel(
+            1,
+            "Training loss",
+            "timeseries",
+            "none",
+            0,
+            0,
+            vec![
+                target(
+                    "A",
+                    format!("{TRAINING_LOSS}{{{RUN_FILTER}}}"),
+                    "{{instance}}",
+                ),
+                target("B", format!("max({ROUND_STEP}{{{RUN_FILTER}}})"), "step"),
+                target(
+                    "C",
+                    format!("{WARMUP_LOSS}{{{RUN_FILTER}}}"),
+                    "{{instance}} warmup",
+                ),
+            ],
+        ),
+        {
+            let mut heatmap = panel(
+                2,
+                "Step duration",
+                "heatmap",
+                "s",
+                12,
+                0,
+                vec![target(
+                    "A",
+                    format!(
+                        "sum by (le) (increase({STEP_DURATION}_bucket{{{RUN_FILTER}}}[$__rate_interval]))"
+                    ),
+                    "{{le}}",
+                )],
+            );
+            heatmap["targets"][0]["format"] = json!("heatmap");
+            heatmap["options"] = json!({ "calculate": false, "yAxis": { "unit": "s" } });
+            heatmap
+        },
+        panel(
+            3,
+            "GPU utilization",
+            "timeseries",
+            "percent",
+            0,
+            8,
+            vec![target(
+                "A",
+                format!("{GPU_USAGE}{{{RUN_FILTER}}}"),
+                "{{instance}} gpu {{gpu}}",
+            )],
+        ),
+        panel(
+            4,
+            "P2P bandwidth",
+            "timeseries",
+            "Bps",
+            12,
+            8,
+            vec![
+                target("A", format!("{BANDWIDTH}{{{RUN_FILTER}}}"), "{{instance}}"),
+                target(
+                    "B",
+                    format!("sum(rate({DOWNLOAD_BYTES}_total{{{RUN_FILTER}}}[$__rate_interval]))"),
+                    "blob downloads (all clients)",
+                ),
+            ],
+        ),
+        panel(
+            5,
+            "Gossip neighbors",
+            "timeseries",
+            "none",
+            0,
+            16,
+            vec![target(
+                "A",
+                format!("{GOSSIP_NEIGHBORS}{{{RUN_FILTER}}}"),
+                "{{instance}}",
+            )],
+        ),
+        panel(
+            6,
+            "Download success rate",
+            "timeseries",
+            "percentunit",
+            12,
+            16,
+            vec![target(
+                "A",
+                format!(
+                    "sum by (type) (rate({DOWNLOADS_FINISHED}_total{{{RUN_FILTER}}}[$__rate_interval])) \
+                     / sum by (type) (rate({DOWNLOADS_STARTED}_total{{{RUN_FILTER}}}[$__rate_interval]))"
+                ),
+                "{{type}}",
+            )],
+        ),
+    ];
+
+    json!({
+        "title": "Psyche client",
+        "uid": "psyche-client",
+        "tags": ["psyche"],
+        "timezone": "browser",
+        "schemaVersion": 39,
+        "refresh": "30s",
+        "time": { "from": "now-6h", "to": "now" },
+        "templating": {
+            "list": [
+                {
+                    "name": "datasource",
+                    "label": "Data source",
+                    "type": "datasource",
+                    "query": "prometheus",
+                },
+                {
+                    "name": "run_id",
+                    "label": "Run ID",
+                    "type": "query",
+                    "datasource": { "type": "prometheus", "uid": "${datasource}" },
+                    "query": format!("label_values({ROUND_STEP}, run_id)"),
+                    "refresh": 2,
+                },
+            ]
+        },
+        "panels": panels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dashboard_metrics_exist() {
+        let source = include_str!("lib.rs");
+        for name in [
+            TRAINING_LOSS,
+            WARMUP_LOSS,
+            ROUND_STEP,
+            STEP_DURATION,
+            GPU_USAGE,
+            BANDWIDTH,
+            DOWNLOAD_BYTES,
+            GOSSIP_NEIGHBORS,
+            DOWNLOADS_STARTED,
+            DOWNLOADS_FINISHED,
+        ] {
+            assert!(
+                source.contains(&format!("\"{name}\"")),
+                "{name} isn't a ClientMetrics metric"
+            );
+        }
+        assert_eq!(grafana_dashboard()["panels"].as_array().unwrap().len(), 6);
+    }
+}
@@ -0,0 +1,77 @@
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::{
+    Method, Request, Response, StatusCode, body::Incoming, header::CONTENT_TYPE,
+    server::conn::http1, service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use prometheus::{Encoder, Registry, TextEncoder};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+/// Serves the Prometheus text exposition of `registry` at `GET /metrics`.
+pub(crate) fn start_http_server(port: u16, registry: Registry) -> Arc<tokio::task::JoinHandle<()>> {
+    Arc::new(tokio::spawn(async move {
+        let addr = SocketAddr::from(([0, 0, 0, 0], port));
+        let listener = match TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                warn!(
+                    "[metrics http server] Failed to bind HTTP server on {}: {} -- Continuing without it",
+                    addr, e
+                );
+                return;
+            }
+        };
+        info!("[metrics http server] serving /metrics on {}", addr);
+
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("[metrics http server] Failed to accept connection: {}", e);
+                    continue;
+                }
+            };
+            let registry = registry.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |req| serve_metrics(req, registry.clone()));
+                if let Err(e) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    warn!("[metrics http server] Connection error: {}", e);
+                }
+            });
+        }
+    }))
+}
+
+async fn serve_metrics(
+    req: Request<Incoming>,
+    registry: Registry,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    if req.method() != Method::GET || req.uri().path() != "/metrics" {
+        return Ok(empty_response(StatusCode::NOT_FOUND));
+    }
+
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    if let Err(e) = encoder.encode(&registry.gather(), &mut buffer) {
+        warn!("[metrics http server] Failed to encode metrics: {}", e);
+        return Ok(empty_response(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, encoder.format_type())
+        .body(Full::new(Bytes::from(buffer)))
+        .expect("static response parts are valid"))
+}
+
+fn empty_response(status: StatusCode) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::new()));
+    *response.status_mut() = status;
+    response
+}
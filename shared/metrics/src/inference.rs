@@ -0,0 +1,112 @@
+//! Request latency metrics for inference nodes
+
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use opentelemetry::{
+    global,
+    metrics::{Gauge, Histogram},
+};
+
+/// How many of the most recent requests the latency percentiles cover.
+const LATENCY_WINDOW: usize = 1000;
+
+#[derive(Debug)]
+pub struct InferenceMetrics {
+    request_latency: Histogram<f64>,
+    request_latency_p50: Gauge<f64>,
+    request_latency_p95: Gauge<f64>,
+    request_latency_p99: Gauge<f64>,
+    latency_window: Mutex<LatencyWindow>,
+}
+
+impl InferenceMetrics {
+    pub fn new() -> Self {
+        let meter = global::meter("psyche_inference");
+        Self {
+            request_latency: meter
+                .f64_histogram("psyche_inference_request_latency_seconds")
+                .with_description("Time taken to serve an inference request")
+                .with_unit("s")
+                .build(),
+            request_latency_p50: meter
+                .f64_gauge("psyche_inference_request_latency_p50_seconds")
+                .with_description("Median inference request latency over recent requests")
+                .with_unit("s")
+                .build(),
+            request_latency_p95: meter
+                .f64_gauge("psyche_inference_request_latency_p95_seconds")
+                .with_description("95th percentile inference request latency over recent requests")
+                .with_unit("s")
+                .build(),
+            request_latency_p99: meter
+                .f64_gauge("psyche_inference_request_latency_p99_seconds")
+                .with_description("99th percentile inference request latency over recent requests")
+                .with_unit("s")
+                .build(),
+            latency_window: Mutex::new(LatencyWindow::default()),
+        }
+    }
+
+    pub fn record_request_latency(&self, latency: Duration) {
+        let secs = latency.as_secs_f64();
+        self.request_latency.record(secs, &[]);
+        let [p50, p95, p99] = self.latency_window.lock().unwrap().push(secs);
+        self.request_latency_p50.record(p50, &[]);
+        self.request_latency_p95.record(p95, &[]);
+        self.request_latency_p99.record(p99, &[]);
+    }
+}
+
+impl Default for InferenceMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The last [`LATENCY_WINDOW`] request latencies, oldest first.
+#[derive(Debug, Default)]
+struct LatencyWindow {
+    latencies: VecDeque<f64>,
+}
+
+impl LatencyWindow {
+    /// Records a latency and returns the window's (p50, p95, p99), using
+    /// the nearest-rank method.
+    fn push(&mut self, latency: f64) -> [f64; 3] {
+        self.latencies.push_back(latency);
+        while self.latencies.len() > LATENCY_WINDOW {
+            self.latencies.pop_front();
+        }
+
+        let mut sorted: Vec<f64> = self.latencies.iter().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+        [0.50, 0.95, 0.99].map(|p| {
+            let rank = (p * sorted.len() as f64).ceil() as usize;
+            sorted[rank.clamp(1, sorted.len()) - 1]
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_window_percentiles() {
+        let mut window = LatencyWindow::default();
+        assert_eq!(window.push(1.0), [1.0, 1.0, 1.0]);
+
+        let mut window = LatencyWindow::default();
+        let mut percentiles = [0.0; 3];
+        for latency in 1..=100 {
+            percentiles = window.push(latency as f64);
+        }
+        assert_eq!(percentiles, [50.0, 95.0, 99.0]);
+
+        for _ in 0..LATENCY_WINDOW {
+            percentiles = window.push(2.0);
+        }
+        assert_eq!(window.latencies.len(), LATENCY_WINDOW);
+        assert_eq!(percentiles, [2.0, 2.0, 2.0]);
+    }
+}
@@ -1,6 +1,11 @@
+mod grafana;
+mod http;
+mod inference;
 mod iroh;
+mod ws;
 
 use std::{
+    collections::{HashSet, VecDeque},
     fmt::Display,
     sync::{Arc, Mutex, OnceLock},
     time::{Duration, Instant},
@@ -13,12 +18,37 @@ use opentelemetry::{
 };
 use serde::{Deserialize, Serialize};
 use sysinfo::System;
-use tokio::{io::AsyncWriteExt, net::TcpListener, time::interval};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpListener, TcpStream},
+    sync::broadcast,
+    task::JoinSet,
+    time::interval,
+};
 
+pub use grafana::grafana_dashboard;
+pub use inference::InferenceMetrics;
 pub use iroh::{IrohMetricsCollector, create_iroh_registry};
 pub use iroh_metrics::Registry as IrohMetricsRegistry;
 use tracing::{debug, info, warn};
 
+/// How many of the most recent step losses the rolling loss statistics cover.
+const TRAINING_LOSS_WINDOW: usize = 100;
+
+/// How many losses are kept for each of the warmup and training phases.
+const LOSS_HISTORY_LEN: usize = 50;
+
+/// How many utilization samples are kept per GPU, one every system monitoring tick.
+const GPU_UTIL_HISTORY_LEN: usize = 60;
+
+/// How full a witness bloom filter can get, relative to its capacity, before we warn.
+const BLOOM_OCCUPANCY_WARN: f64 = 0.7;
+
+/// A TCP metrics client that has sent this by the time it's accepted gets a line of JSON every
+/// [`TCP_STREAM_INTERVAL`] instead of a single snapshot.
+const TCP_STREAM_REQUEST: &[u8] = b"GET /stream";
+const TCP_STREAM_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(Debug)]
 /// metrics collector for Psyche clients
 pub struct ClientMetrics {
@@ -41,6 +71,15 @@ pub struct ClientMetrics {
 
     pub(crate) peer_connections: Gauge<u64>,
     pub(crate) gossip_neighbors: Gauge<u64>,
+    pub(crate) gossip_messages_sent_counter: Counter<u64>,
+    pub(crate) gossip_messages_received_counter: Counter<u64>,
+    pub(crate) gossip_delivery_ratio: Gauge<f64>,
+    pub(crate) gossip_broadcasts_rate_limited_counter: Counter<u64>,
+    pub(crate) blob_store_blobs: Gauge<u64>,
+    pub(crate) witness_bloom_occupancy: Gauge<f64>,
+    // blooms past BLOOM_OCCUPANCY_WARN at their last record, so we only warn once they cross it
+    pub(crate) blooms_over_occupancy_warn: Mutex<HashSet<&'static str>>,
+    pub(crate) blob_store_bytes: Gauge<u64>,
 
     // networking stats
     pub(crate) downloads_started_counter: Counter<u64>,
@@ -54,17 +93,29 @@ pub struct ClientMetrics {
     pub(crate) connection_latency: Histogram<f64>,
     pub(crate) bandwidth: Gauge<f64>,
     pub(crate) last_train_time_seconds: Gauge<f64>,
+    pub(crate) step_duration_seconds: Histogram<f64>,
 
     /// Just a boolean
     pub(crate) participating_in_round: Gauge<u64>,
 
     // internal state tracking
+    pub(crate) meter: Meter,
     pub(crate) system_monitor: Arc<tokio::task::JoinHandle<()>>,
     pub(crate) tcp_server: Option<Arc<tokio::task::JoinHandle<()>>>,
+    pub(crate) http_server: Option<Arc<tokio::task::JoinHandle<()>>>,
     pub(crate) print_metrics_task: Option<Arc<tokio::task::JoinHandle<()>>>,
+    pub(crate) ws_server: OnceLock<Arc<tokio::task::JoinHandle<()>>>,
 
     // shared state for TCP server
     pub(crate) tcp_metrics: Arc<Mutex<TcpMetrics>>,
+    // snapshots of `tcp_metrics` pushed to WebSocket clients
+    pub(crate) ws_updates: broadcast::Sender<String>,
+
+    // (time, total gossip messages sent, total gossip messages received) snapshots for the delivery ratio
+    pub(crate) gossip_traffic_history: Mutex<VecDeque<(Instant, u64, u64)>>,
+
+    // recent utilization percentages, one deque per GPU, shared with the system monitor
+    pub(crate) gpu_util_history: Arc<Mutex<Vec<VecDeque<f64>>>>,
 
     // p2p model sharing
     pub(crate) num_params: OnceLock<u64>,
@@ -73,13 +124,24 @@ pub struct ClientMetrics {
 
     // training metrics
     pub(crate) training_loss: Gauge<f64>,
+    pub(crate) warmup_loss: Gauge<f64>,
+    pub(crate) training_loss_mean: Gauge<f64>,
+    pub(crate) training_loss_stddev: Gauge<f64>,
+    pub(crate) training_loss_window: Mutex<TrainingLossWindow>,
     pub(crate) training_perplexity: Gauge<f64>,
     pub(crate) training_confidence: Gauge<f64>,
     pub(crate) learning_rate: Gauge<f64>,
+    pub(crate) gradient_norm_before_clip: Gauge<f64>,
+    pub(crate) gradient_norm_after_clip: Gauge<f64>,
     pub(crate) total_tokens: Gauge<u64>,
     pub(crate) tokens_per_second: Gauge<f64>,
+    pub(crate) local_tokens_per_second: Gauge<f64>,
     pub(crate) token_batch_size: Gauge<u64>,
     pub(crate) training_efficiency: Gauge<f64>,
+    pub(crate) data_cache_hit_ratio: Gauge<f64>,
+
+    // wallet
+    pub(crate) wallet_sol_balance: Gauge<f64>,
 
     // evals & optimizer metrics
     pub(crate) eval_metrics: Gauge<f64>,
@@ -105,12 +167,83 @@ struct TcpMetrics {
     results_downloaded_previous_round: u64,
     witnesses_sent: u64,
     gossip_neighbors: Vec<String>,
+    gossip_messages_sent: u64,
+    gossip_messages_received: u64,
+    gossip_delivery_ratio: f64,
+    gossip_broadcasts_rate_limited: u64,
+    blob_store_blobs: u64,
+    blob_store_bytes: u64,
     downloads_started: u64,
     downloads_finished: u64,
     downloads_retry: u64,
     downloads_failed: u64,
     downloads_perma_failed: u64,
     downloads_bytes: u64,
+    training_loss: f64,
+    training_loss_mean_100: f64,
+    training_loss_stddev_100: f64,
+    /// The last [`LOSS_HISTORY_LEN`] losses during LR warmup, oldest first
+    warmup_loss_history: VecDeque<f32>,
+    /// The last [`LOSS_HISTORY_LEN`] losses after LR warmup, oldest first
+    training_loss_history: VecDeque<f32>,
+    last_step_duration_secs: f64,
+    /// This client's own throughput, as in `psyche_local_tokens_per_second`
+    local_tokens_per_second: f64,
+}
+
+/// The last [`TRAINING_LOSS_WINDOW`] step losses, oldest first.
+#[derive(Debug, Default)]
+struct TrainingLossWindow {
+    last_step: Option<u32>,
+    losses: VecDeque<f32>,
+}
+
+impl TrainingLossWindow {
+    /// Records the loss for `step`, replacing it if this step was already recorded.
+    /// Returns the (mean, population standard deviation) of the window.
+    fn push(&mut self, step: u32, loss: f32) -> (f64, f64) {
+        if self.last_step == Some(step) {
+            self.losses.pop_back();
+        }
+        self.last_step = Some(step);
+        self.losses.push_back(loss);
+        while self.losses.len() > TRAINING_LOSS_WINDOW {
+            self.losses.pop_front();
+        }
+
+        let n = self.losses.len() as f64;
+        let mean = self.losses.iter().map(|l| *l as f64).sum::<f64>() / n;
+        let variance = self
+            .losses
+            .iter()
+            .map(|l| (*l as f64 - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        (mean, variance.sqrt())
+    }
+}
+
+/// Appends `loss`, or replaces the newest entry if it's for the same step.
+fn push_loss_history(history: &mut VecDeque<f32>, loss: f32, repeated_step: bool) {
+    if repeated_step {
+        history.pop_back();
+    }
+    history.push_back(loss);
+    while history.len() > LOSS_HISTORY_LEN {
+        history.pop_front();
+    }
+}
+
+/// Records one utilization sample for each GPU, keeping the last [`GPU_UTIL_HISTORY_LEN`].
+/// Called by the system monitor on every tick.
+fn update_gpu_history(history: &mut Vec<VecDeque<f64>>, per_gpu: &[f64]) {
+    history.resize_with(per_gpu.len(), VecDeque::new);
+    for (samples, util) in history.iter_mut().zip(per_gpu) {
+        samples.push_back(*util);
+        while samples.len() > GPU_UTIL_HISTORY_LEN {
+            samples.pop_front();
+        }
+    }
 }
 
 impl Drop for ClientMetrics {
@@ -119,9 +252,15 @@ impl Drop for ClientMetrics {
         if let Some(server) = &self.tcp_server {
             server.abort();
         }
+        if let Some(server) = &self.http_server {
+            server.abort();
+        }
         if let Some(interval) = &self.print_metrics_task {
             interval.abort();
         }
+        if let Some(server) = self.ws_server.get() {
+            server.abort();
+        }
     }
 }
 
@@ -157,10 +296,21 @@ pub enum ClientRoleInRound {
 }
 
 impl ClientMetrics {
-    pub fn new(metrics_port: Option<u16>, print_metrics_interval: Option<Duration>) -> Self {
+    /// `metrics_port` serves a JSON snapshot over raw TCP.
+    /// `metrics_http_port` serves [`prometheus::default_registry`] at `GET /metrics`. The global
+    /// meter provider only exports into it if logging was set up with
+    /// `psyche_tui::LoggingBuilder::with_prometheus_exporter`.
+    pub fn new(
+        metrics_port: Option<u16>,
+        metrics_http_port: Option<u16>,
+        print_metrics_interval: Option<Duration>,
+    ) -> Self {
         let meter = global::meter("psyche_client");
+        let http_server = metrics_http_port
+            .map(|port| http::start_http_server(port, prometheus::default_registry().clone()));
 
         let tcp_metrics = Arc::new(Mutex::new(TcpMetrics::default()));
+        let gpu_util_history = Arc::new(Mutex::new(Vec::new()));
         let tcp_server = metrics_port.map(|port| Self::start_tcp_server(port, tcp_metrics.clone()));
 
         let print_metrics_task = print_metrics_interval
@@ -266,6 +416,39 @@ impl ClientMetrics {
                 .u64_gauge("psyche_gossip_neighbors")
                 .with_description("Number of neighbors in gossip network")
                 .build(),
+            gossip_messages_sent_counter: meter
+                .u64_counter("psyche_gossip_messages_sent_total")
+                .with_description("Total number of gossip messages broadcast by this node")
+                .build(),
+            gossip_messages_received_counter: meter
+                .u64_counter("psyche_gossip_messages_received_total")
+                .with_description("Total number of valid gossip messages received by this node")
+                .build(),
+            gossip_delivery_ratio: meter
+                .f64_gauge("psyche_gossip_delivery_ratio")
+                .with_description(
+                    "Estimated fraction of expected gossip traffic from our neighbors that actually arrived",
+                )
+                .build(),
+            gossip_broadcasts_rate_limited_counter: meter
+                .u64_counter("psyche_gossip_broadcasts_rate_limited_total")
+                .with_description("Total number of gossip broadcasts refused by the broadcast rate limit")
+                .build(),
+            witness_bloom_occupancy: meter
+                .f64_gauge("psyche_witness_bloom_occupancy")
+                .with_description(
+                    "Estimated items in a witness bloom filter over the number it can hold at the target false positive rate",
+                )
+                .build(),
+            blooms_over_occupancy_warn: Default::default(),
+            blob_store_blobs: meter
+                .u64_gauge("psyche_blob_store_blobs")
+                .with_description("Number of blobs in the in-memory blob store")
+                .build(),
+            blob_store_bytes: meter
+                .u64_gauge("psyche_blob_store_bytes")
+                .with_description("Bytes held by the in-memory blob store")
+                .build(),
             bandwidth: meter
                 .f64_gauge("psyche_bandwidth_bytes_per_second")
                 .with_description("Current bandwidth usage in bytes per second")
@@ -274,6 +457,11 @@ impl ClientMetrics {
                 .f64_gauge("psyche_last_train_time_seconds")
                 .with_description("Last training round's training time")
                 .build(),
+            step_duration_seconds: meter
+                .f64_histogram("psyche_step_duration_seconds")
+                .with_description("Wall-clock duration of each training step")
+                .with_boundaries(vec![1.0, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0])
+                .build(),
             connection_latency: meter
                 .f64_histogram("psyche_connection_latency_seconds")
                 .with_description("Connection latency to peers")
@@ -284,6 +472,19 @@ impl ClientMetrics {
                 .f64_gauge("psyche_training_loss")
                 .with_description("Current training loss")
                 .build(),
+            warmup_loss: meter
+                .f64_gauge("psyche_warmup_loss")
+                .with_description("Training loss of the last step in LR warmup")
+                .build(),
+            training_loss_mean: meter
+                .f64_gauge("psyche_training_loss_mean_100")
+                .with_description("Mean training loss over the last 100 steps")
+                .build(),
+            training_loss_stddev: meter
+                .f64_gauge("psyche_training_loss_stddev_100")
+                .with_description("Standard deviation of the training loss over the last 100 steps")
+                .build(),
+            training_loss_window: Mutex::new(TrainingLossWindow::default()),
             training_perplexity: meter
                 .f64_gauge("psyche_training_perplexity")
                 .with_description("Current training perplexity")
@@ -296,6 +497,14 @@ impl ClientMetrics {
                 .f64_gauge("psyche_learning_rate")
                 .with_description("Current learning rate")
                 .build(),
+            gradient_norm_before_clip: meter
+                .f64_gauge("psyche_gradient_norm_before_clip")
+                .with_description("Global gradient norm before clipping")
+                .build(),
+            gradient_norm_after_clip: meter
+                .f64_gauge("psyche_gradient_norm_after_clip")
+                .with_description("Global gradient norm after clipping")
+                .build(),
             total_tokens: meter
                 .u64_gauge("psyche_total_tokens")
                 .with_description("Total tokens processed")
@@ -304,6 +513,10 @@ impl ClientMetrics {
                 .f64_gauge("psyche_tokens_per_second")
                 .with_description("Tokens processed per second")
                 .build(),
+            local_tokens_per_second: meter
+                .f64_gauge("psyche_local_tokens_per_second")
+                .with_description("Tokens per second this client trained on its own batch")
+                .build(),
             token_batch_size: meter
                 .u64_gauge("psyche_token_batch_size")
                 .with_description("Current token batch size")
@@ -312,6 +525,16 @@ impl ClientMetrics {
                 .f64_gauge("psyche_training_efficiency")
                 .with_description("Training efficiency metric")
                 .build(),
+            data_cache_hit_ratio: meter
+                .f64_gauge("psyche_data_cache_hit_ratio")
+                .with_description("Fraction of training samples read from the data provider's cache")
+                .build(),
+
+            // Wallet
+            wallet_sol_balance: meter
+                .f64_gauge("psyche_wallet_sol_balance")
+                .with_description("SOL balance of the client wallet")
+                .build(),
 
             // Evals &
             eval_metrics: meter
@@ -323,10 +546,15 @@ impl ClientMetrics {
                 .with_description("Optimizer stats")
                 .build(),
 
-            system_monitor: Self::start_system_monitoring(&meter),
+            system_monitor: Self::start_system_monitoring(&meter, gpu_util_history.clone()),
             tcp_server,
+            http_server,
             tcp_metrics,
+            ws_updates: ws::ws_update_channel(),
             print_metrics_task,
+            ws_server: OnceLock::new(),
+            gossip_traffic_history: Mutex::new(VecDeque::new()),
+            gpu_util_history,
 
             num_params: OnceLock::new(),
             p2p_downloaded_params_percent: OnceLock::new(),
@@ -334,6 +562,7 @@ impl ClientMetrics {
                 .u64_counter("psyche_p2p_params_download_failed_counter")
                 .with_description("The total amount of p2p parameter sharing downloads that failed")
                 .build(),
+            meter,
         }
     }
 
@@ -512,6 +741,7 @@ impl ClientMetrics {
             selected_count,
             &[KeyValue::new("connection_type", "selected")],
         );
+        self.push_ws_update();
     }
 
     pub fn update_p2p_gossip_neighbors(&self, neighbors: &[impl Display]) {
@@ -522,9 +752,106 @@ impl ClientMetrics {
         self.tcp_metrics.lock().unwrap().gossip_neighbors = neighbor_ids;
     }
 
+    pub fn record_gossip_messages_sent(&self, count: u64) {
+        self.gossip_messages_sent_counter.add(count, &[]);
+        self.tcp_metrics.lock().unwrap().gossip_messages_sent += count;
+    }
+
+    pub fn record_gossip_message_received(&self) {
+        self.gossip_messages_received_counter.add(1, &[]);
+        self.tcp_metrics.lock().unwrap().gossip_messages_received += 1;
+    }
+
+    /// Estimates how well gossip is being delivered over the last `window`.
+    /// Every node rebroadcasts its live results at roughly the same rate, so each of our gossip
+    /// neighbors is expected to relay about as many messages as we send. The ratio is what we
+    /// received against that expectation, capped at 1.0.
+    pub fn update_gossip_delivery_ratio(&self, window: Duration) {
+        let now = Instant::now();
+        let mut tcp_metrics = self.tcp_metrics.lock().unwrap();
+        let mut history = self.gossip_traffic_history.lock().unwrap();
+        history.push_back((
+            now,
+            tcp_metrics.gossip_messages_sent,
+            tcp_metrics.gossip_messages_received,
+        ));
+        while history
+            .front()
+            .is_some_and(|(time, ..)| now.duration_since(*time) > window)
+        {
+            history.pop_front();
+        }
+
+        let (Some((_, first_sent, first_received)), Some((_, last_sent, last_received))) =
+            (history.front(), history.back())
+        else {
+            return;
+        };
+        let sent = last_sent - first_sent;
+        let received = last_received - first_received;
+        let expected = sent * tcp_metrics.gossip_neighbors.len() as u64;
+        if expected == 0 {
+            return;
+        }
+
+        let ratio = (received as f64 / expected as f64).min(1.0);
+        self.gossip_delivery_ratio.record(ratio, &[]);
+        tcp_metrics.gossip_delivery_ratio = ratio;
+    }
+
+    pub fn record_gossip_broadcast_rate_limited(&self) {
+        self.gossip_broadcasts_rate_limited_counter.add(1, &[]);
+        self.tcp_metrics
+            .lock()
+            .unwrap()
+            .gossip_broadcasts_rate_limited += 1;
+    }
+
+    /// Records how full a witness bloom filter is. `bloom` is which one, e.g. "participant" or "broadcast".
+    /// Past [`BLOOM_OCCUPANCY_WARN`], valid witnesses start getting rejected as false positives.
+    pub fn record_bloom_occupancy(
+        &self,
+        bloom: &'static str,
+        estimated_count: usize,
+        capacity: usize,
+    ) {
+        let occupancy = estimated_count as f64 / capacity as f64;
+        self.witness_bloom_occupancy
+            .record(occupancy, &[KeyValue::new("bloom", bloom)]);
+        let mut over_warn = self.blooms_over_occupancy_warn.lock().unwrap();
+        if occupancy > BLOOM_OCCUPANCY_WARN {
+            if over_warn.insert(bloom) {
+                warn!(
+                    bloom,
+                    estimated_count,
+                    capacity,
+                    "Witness {bloom} bloom filter is {:.0}% full, it may reject valid witnesses as false positives",
+                    occupancy * 100.0
+                );
+            }
+        } else if over_warn.remove(bloom) {
+            info!(
+                bloom,
+                estimated_count,
+                capacity,
+                "Witness {bloom} bloom filter is back down to {:.0}% full",
+                occupancy * 100.0
+            );
+        }
+    }
+
+    pub fn update_blob_store(&self, blobs: u64, bytes: u64) {
+        self.blob_store_blobs.record(blobs, &[]);
+        self.blob_store_bytes.record(bytes, &[]);
+        let mut tcp_metrics = self.tcp_metrics.lock().unwrap();
+        tcp_metrics.blob_store_blobs = blobs;
+        tcp_metrics.blob_store_bytes = bytes;
+    }
+
     pub fn update_bandwidth(&self, bytes_per_second: f64) {
         self.bandwidth.record(bytes_per_second, &[]);
         self.tcp_metrics.lock().unwrap().bandwidth = bytes_per_second;
+        self.push_ws_update();
     }
 
     pub fn update_round_state(&self, step: u32, role: ClientRoleInRound) {
@@ -549,12 +876,12 @@ impl ClientMetrics {
                 },
             )],
         );
+        self.push_ws_update();
     }
 
     pub fn initialize_model_parameters_gauge(&self, num_params: u64) {
-        let meter = global::meter("psyche_client");
         let _ = self.num_params.set(num_params);
-        let _ = self.p2p_downloaded_params_percent.set(meter
+        let _ = self.p2p_downloaded_params_percent.set(self.meter
             .f64_gauge("psyche_p2p_model_params_downloaded")
             .with_description("Percentage of the total model parameters that have been downloaded from other peers")
             .build()
@@ -572,8 +899,31 @@ impl ClientMetrics {
         }
     }
 
-    pub fn record_training_loss(&self, loss: f64) {
-        self.training_loss.record(loss, &[]);
+    /// `is_warmup` is whether `step` is still in LR warmup, where the loss says more about
+    /// initialization than learning. Those losses are also recorded to their own gauge and
+    /// history, which stop changing once warmup is over.
+    pub fn record_training_loss(&self, step: u32, loss: f32, is_warmup: bool) {
+        let (repeated_step, (mean, stddev)) = {
+            let mut window = self.training_loss_window.lock().unwrap();
+            (window.last_step == Some(step), window.push(step, loss))
+        };
+        self.training_loss.record(loss as f64, &[]);
+        if is_warmup {
+            self.warmup_loss.record(loss as f64, &[]);
+        }
+        self.training_loss_mean.record(mean, &[]);
+        self.training_loss_stddev.record(stddev, &[]);
+
+        let mut tcp_metrics = self.tcp_metrics.lock().unwrap();
+        tcp_metrics.training_loss = loss as f64;
+        tcp_metrics.training_loss_mean_100 = mean;
+        tcp_metrics.training_loss_stddev_100 = stddev;
+        let history = if is_warmup {
+            &mut tcp_metrics.warmup_loss_history
+        } else {
+            &mut tcp_metrics.training_loss_history
+        };
+        push_loss_history(history, loss, repeated_step);
     }
 
     pub fn record_training_perplexity(&self, perplexity: f64) {
@@ -588,6 +938,11 @@ impl ClientMetrics {
         self.learning_rate.record(lr, &[]);
     }
 
+    pub fn record_gradient_norm(&self, before_clip: f64, after_clip: f64) {
+        self.gradient_norm_before_clip.record(before_clip, &[]);
+        self.gradient_norm_after_clip.record(after_clip, &[]);
+    }
+
     pub fn record_total_tokens(&self, tokens: u64) {
         self.total_tokens.record(tokens, &[]);
     }
@@ -596,6 +951,13 @@ impl ClientMetrics {
         self.tokens_per_second.record(tokens_per_sec, &[]);
     }
 
+    /// Records how fast this client trained on its own batch, as opposed to the run-wide
+    /// throughput in [`Self::record_tokens_per_second`].
+    pub fn record_local_tokens_per_second(&self, tokens_per_sec: f64) {
+        self.local_tokens_per_second.record(tokens_per_sec, &[]);
+        self.tcp_metrics.lock().unwrap().local_tokens_per_second = tokens_per_sec;
+    }
+
     pub fn record_token_batch_size(&self, batch_size: u64) {
         self.token_batch_size.record(batch_size, &[]);
     }
@@ -604,10 +966,25 @@ impl ClientMetrics {
         self.training_efficiency.record(efficiency, &[]);
     }
 
+    pub fn record_data_cache_hit_ratio(&self, ratio: f64) {
+        self.data_cache_hit_ratio.record(ratio, &[]);
+    }
+
+    pub fn record_wallet_balance(&self, balance_sol: f64) {
+        self.wallet_sol_balance.record(balance_sol, &[]);
+    }
+
     pub fn record_last_train_time(&self, time: f64) {
         self.last_train_time_seconds.record(time, &[]);
     }
 
+    pub fn record_step_duration(&self, step: u32, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        debug!(name: "step_duration", step, secs);
+        self.step_duration_seconds.record(secs, &[]);
+        self.tcp_metrics.lock().unwrap().last_step_duration_secs = secs;
+    }
+
     // Evaluation metrics
     pub fn record_eval_metric(&self, metric_name: &str, value: f64) {
         self.eval_metrics
@@ -620,7 +997,15 @@ impl ClientMetrics {
             .record(value, &[KeyValue::new("stat", stat_name.to_string())]);
     }
 
-    fn start_system_monitoring(meter: &Meter) -> Arc<tokio::task::JoinHandle<()>> {
+    /// Recent utilization percentages for each GPU, oldest first.
+    pub fn gpu_util_history(&self) -> Vec<VecDeque<f64>> {
+        self.gpu_util_history.lock().unwrap().clone()
+    }
+
+    fn start_system_monitoring(
+        meter: &Meter,
+        gpu_util_history: Arc<Mutex<Vec<VecDeque<f64>>>>,
+    ) -> Arc<tokio::task::JoinHandle<()>> {
         let mut interval = interval(Duration::from_secs(5));
         let system = Arc::new(Mutex::new(System::new_all()));
 
@@ -637,9 +1022,13 @@ impl ClientMetrics {
             nvml: Nvml,
             gpu_usage: Gauge<f64>,
             gpu_memory: Gauge<u64>,
+            gpu_memory_fragmentation: Gauge<f64>,
             gpu_temp: Gauge<u64>,
         }
 
+        /// Above this, allocations can fail well before the reported memory usage suggests.
+        const GPU_MEMORY_FRAGMENTATION_WARN_THRESHOLD: f64 = 0.3;
+
         let gpu_meters = Nvml::init().ok().map(|nvml| GpuMeters {
             nvml,
             gpu_usage: meter
@@ -650,12 +1039,20 @@ impl ClientMetrics {
                 .u64_gauge("psyche_gpu_memory")
                 .with_description("GPU memory usage")
                 .build(),
+            gpu_memory_fragmentation: meter
+                .f64_gauge("psyche_gpu_memory_fragmentation")
+                .with_description(
+                    "Fraction of unused GPU memory that isn't reported as free by the driver",
+                )
+                .build(),
             gpu_temp: meter
                 .u64_gauge("psyche_gpu_temp")
                 .with_description("GPU usage percentage")
                 .build(),
         });
         Arc::new(tokio::spawn(async move {
+            // GPUs past the fragmentation threshold at the last tick, so we only warn once they cross it
+            let mut fragmented_gpus = HashSet::new();
             loop {
                 let system_clone = system.clone();
                 tokio::task::spawn_blocking(move || system_clone.lock().unwrap().refresh_all())
@@ -669,25 +1066,52 @@ impl ClientMetrics {
                 if let Some(GpuMeters {
                     gpu_usage,
                     gpu_memory,
+                    gpu_memory_fragmentation,
                     gpu_temp,
                     nvml,
                 }) = &gpu_meters
                 {
                     if let Ok(device_count) = nvml.device_count() {
+                        let mut per_gpu = vec![0.0; device_count as usize];
                         for i in 0..device_count {
                             if let Ok(gpu) = nvml.device_by_index(i) {
                                 let device_info = [KeyValue::new("gpu", i as i64)];
                                 if let Ok(util) = gpu.utilization_rates() {
                                     gpu_usage.record(util.gpu as f64, &device_info);
+                                    per_gpu[i as usize] = util.gpu as f64;
                                 }
                                 if let Ok(mem) = gpu.memory_info() {
                                     gpu_memory.record(mem.used, &device_info);
+                                    let unused = mem.total.saturating_sub(mem.used);
+                                    if unused > 0 {
+                                        let fragmentation = 1.0 - (mem.free as f64 / unused as f64);
+                                        gpu_memory_fragmentation
+                                            .record(fragmentation, &device_info);
+                                        if fragmentation > GPU_MEMORY_FRAGMENTATION_WARN_THRESHOLD {
+                                            if fragmented_gpus.insert(i) {
+                                                warn!(
+                                                    gpu = i,
+                                                    fragmentation,
+                                                    "GPU {i} memory fragmentation is {:.1}%, allocations may fail before memory is exhausted",
+                                                    fragmentation * 100.0
+                                                );
+                                            }
+                                        } else if fragmented_gpus.remove(&i) {
+                                            info!(
+                                                gpu = i,
+                                                fragmentation,
+                                                "GPU {i} memory fragmentation is back down to {:.1}%",
+                                                fragmentation * 100.0
+                                            );
+                                        }
+                                    }
                                 }
                                 if let Ok(temp) = gpu.temperature(TemperatureSensor::Gpu) {
                                     gpu_temp.record(temp as u64, &device_info);
                                 }
                             }
                         }
+                        update_gpu_history(&mut gpu_util_history.lock().unwrap(), &per_gpu);
                     }
                 }
                 interval.tick().await;
@@ -695,6 +1119,28 @@ impl ClientMetrics {
         }))
     }
 
+    /// Pushes the JSON snapshot served on `metrics_port` to WebSocket clients on `port` whenever
+    /// the round state, bandwidth or peer connections are updated, for live dashboards.
+    /// Does nothing if the WebSocket server is already running.
+    pub fn start_ws_server(&self, port: u16) {
+        self.ws_server.get_or_init(|| {
+            ws::start_ws_server(port, self.tcp_metrics.clone(), self.ws_updates.clone())
+        });
+    }
+
+    fn push_ws_update(&self) {
+        if self.ws_updates.receiver_count() == 0 {
+            return;
+        }
+        match serde_json::to_string(&*self.tcp_metrics.lock().unwrap()) {
+            // fails only if every client disconnected since we checked
+            Ok(json) => {
+                let _ = self.ws_updates.send(json);
+            }
+            Err(e) => warn!("[metrics ws server] Failed to serialize metrics: {}", e),
+        }
+    }
+
     fn start_tcp_server(
         port: u16,
         tcp_metrics: Arc<Mutex<TcpMetrics>>,
@@ -713,31 +1159,81 @@ impl ClientMetrics {
             };
             info!("[metrics tcp server] listening on {}", addr);
 
+            // dropped with the server, so streaming connections end with it
+            let mut connections = JoinSet::new();
             loop {
                 tokio::select! {
                     // when someone connects to us
-                    Ok((mut stream, _)) = listener.accept() => {
-                        // grab all the metrics
-                        let stats_json = {
-                            match serde_json::to_string(&*tcp_metrics.lock().unwrap()) {
-                                Ok(json) => json,
-                                Err(e) => {
-                                    warn!("[metrics tcp server] Failed to serialize metrics: {}", e);
-                                    continue;
-                                }
-                            }
-                        };
-
-                        // send metrics - we don't care if it fails
-                        let _ = stream.write_all(stats_json.as_bytes()).await;
-                        // and close the connection
-                        let _ = stream.shutdown().await;
+                    Ok((stream, _)) = listener.accept() => {
+                        connections.spawn(Self::serve_tcp_connection(stream, tcp_metrics.clone()));
                     }
+                    Some(_) = connections.join_next(), if !connections.is_empty() => {}
                 }
             }
         }))
     }
 
+    /// Sends a single JSON snapshot of the metrics and closes the connection, unless the client
+    /// sent `GET /stream` as it connected, in which case it gets one snapshot per line (NDJSON) until
+    /// it hangs up.
+    /// The stream has no HTTP headers, so it can be piped straight into `jq`.
+    async fn serve_tcp_connection(stream: TcpStream, tcp_metrics: Arc<Mutex<TcpMetrics>>) {
+        let serialize = || match serde_json::to_string(&*tcp_metrics.lock().unwrap()) {
+            Ok(json) => Some(json),
+            Err(e) => {
+                warn!("[metrics tcp server] Failed to serialize metrics: {}", e);
+                None
+            }
+        };
+
+        let (mut stream, wants_stream) = match Self::has_stream_request(stream) {
+            Ok(checked) => checked,
+            Err(e) => {
+                warn!(
+                    "[metrics tcp server] Failed to check for a stream request: {}",
+                    e
+                );
+                return;
+            }
+        };
+
+        if !wants_stream {
+            // send metrics - we don't care if it fails
+            if let Some(stats_json) = serialize() {
+                let _ = stream.write_all(stats_json.as_bytes()).await;
+            }
+            // and close the connection
+            let _ = stream.shutdown().await;
+            return;
+        }
+
+        debug!("[metrics tcp server] client subscribed to the metrics stream");
+        let mut ticks = interval(TCP_STREAM_INTERVAL);
+        loop {
+            ticks.tick().await;
+            let Some(mut line) = serialize() else {
+                continue;
+            };
+            line.push('\n');
+            if stream.write_all(line.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Checks, without waiting, whether the client has already sent [`TCP_STREAM_REQUEST`], so
+    /// plain connections get their snapshot as soon as they're accepted.
+    fn has_stream_request(stream: TcpStream) -> std::io::Result<(TcpStream, bool)> {
+        // tokio only learns a fresh socket is readable on its next poll, so peek at the std socket
+        let stream = stream.into_std()?;
+        let mut request = [0u8; TCP_STREAM_REQUEST.len()];
+        let wants_stream = matches!(
+            stream.peek(&mut request),
+            Ok(len) if len == request.len() && request == TCP_STREAM_REQUEST
+        );
+        Ok((TcpStream::from_std(stream)?, wants_stream))
+    }
+
     fn start_print_metrics_task(
         interval: Duration,
         metrics: Arc<Mutex<TcpMetrics>>,
@@ -782,6 +1278,111 @@ impl ClientMetrics {
 
 impl Default for ClientMetrics {
     fn default() -> Self {
-        Self::new(None, None)
+        Self::new(None, None, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_training_loss_window() {
+        let mut window = TrainingLossWindow::default();
+        assert_eq!(window.push(1, 2.0), (2.0, 0.0));
+        assert_eq!(window.push(2, 4.0), (3.0, 1.0));
+        // re-recording the same step replaces its loss
+        assert_eq!(window.push(2, 2.0), (2.0, 0.0));
+
+        for step in 3..(3 + TRAINING_LOSS_WINDOW as u32) {
+            window.push(step, 1.0);
+        }
+        assert_eq!(window.losses.len(), TRAINING_LOSS_WINDOW);
+        assert_eq!(window.push(1000, 1.0), (1.0, 0.0));
+    }
+
+    #[test]
+    fn test_loss_history_is_capped() {
+        let mut history = VecDeque::new();
+        push_loss_history(&mut history, 3.0, false);
+        // re-recording the same step replaces its loss
+        push_loss_history(&mut history, 2.0, true);
+        assert_eq!(history, VecDeque::from([2.0]));
+
+        for i in 0..LOSS_HISTORY_LEN {
+            push_loss_history(&mut history, i as f32, false);
+        }
+        assert_eq!(history.len(), LOSS_HISTORY_LEN);
+        assert_eq!(history.front(), Some(&0.0));
+    }
+
+    #[test]
+    fn test_gpu_history_is_capped() {
+        let mut history = Vec::new();
+        update_gpu_history(&mut history, &[10.0, 20.0]);
+        assert_eq!(
+            history,
+            vec![VecDeque::from([10.0]), VecDeque::from([20.0])]
+        );
+
+        for i in 0..GPU_UTIL_HISTORY_LEN {
+            update_gpu_history(&mut history, &[i as f64, 50.0]);
+        }
+        assert!(
+            history
+                .iter()
+                .all(|samples| samples.len() == GPU_UTIL_HISTORY_LEN)
+        );
+        assert_eq!(history[0].front(), Some(&0.0));
+        assert_eq!(
+            history[0].back(),
+            Some(&(GPU_UTIL_HISTORY_LEN as f64 - 1.0))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tcp_stream_sends_json_lines() {
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+
+        let tcp_metrics = Arc::new(Mutex::new(TcpMetrics {
+            round_step: 7,
+            ..Default::default()
+        }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // a plain connection gets a single snapshot without the server waiting on it
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        tokio::spawn(ClientMetrics::serve_tcp_connection(
+            server,
+            tcp_metrics.clone(),
+        ));
+        let mut snapshot = String::new();
+        tokio::time::timeout(
+            Duration::from_millis(100),
+            client.read_to_string(&mut snapshot),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        let snapshot: serde_json::Value = serde_json::from_str(&snapshot).unwrap();
+        assert_eq!(snapshot["round_step"], 7);
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client
+            .write_all(b"GET /stream HTTP/1.0\r\n\r\n")
+            .await
+            .unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        // make sure the request has arrived before the server looks for it
+        server.readable().await.unwrap();
+        tokio::spawn(ClientMetrics::serve_tcp_connection(server, tcp_metrics));
+        let mut lines = BufReader::new(client).lines();
+        for _ in 0..2 {
+            let line = lines.next_line().await.unwrap().unwrap();
+            let update: serde_json::Value = serde_json::from_str(&line).unwrap();
+            assert_eq!(update["round_step"], 7);
+        }
     }
 }
@@ -0,0 +1,105 @@
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::broadcast,
+};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{info, warn};
+
+use crate::TcpMetrics;
+
+/// How many snapshots a slow WebSocket client can fall behind by before it skips ahead to the latest.
+const WS_UPDATE_BUFFER: usize = 16;
+
+pub(crate) fn ws_update_channel() -> broadcast::Sender<String> {
+    broadcast::channel(WS_UPDATE_BUFFER).0
+}
+
+/// Serves the JSON [`TcpMetrics`] snapshot over WebSocket: each client gets the current one when it
+/// connects, then every snapshot sent on `updates`. Connections are closed once `updates` is dropped.
+pub(crate) fn start_ws_server(
+    port: u16,
+    tcp_metrics: Arc<Mutex<TcpMetrics>>,
+    updates: broadcast::Sender<String>,
+) -> Arc<tokio::task::JoinHandle<()>> {
+    Arc::new(tokio::spawn(async move {
+        let addr = SocketAddr::from(([0, 0, 0, 0], port));
+        let listener = match TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                warn!(
+                    "[metrics ws server] Failed to bind WebSocket server on {}: {} -- Continuing without it",
+                    addr, e
+                );
+                return;
+            }
+        };
+        info!("[metrics ws server] listening on {}", addr);
+
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("[metrics ws server] Failed to accept connection: {}", e);
+                    continue;
+                }
+            };
+            // subscribe before taking the snapshot, so no update between the two is missed
+            let updates = updates.subscribe();
+            let snapshot = match serde_json::to_string(&*tcp_metrics.lock().unwrap()) {
+                Ok(json) => json,
+                Err(e) => {
+                    warn!("[metrics ws server] Failed to serialize metrics: {}", e);
+                    continue;
+                }
+            };
+            tokio::spawn(serve_connection(stream, snapshot, updates));
+        }
+    }))
+}
+
+async fn serve_connection(
+    stream: TcpStream,
+    snapshot: String,
+    mut updates: broadcast::Receiver<String>,
+) {
+    let mut ws = match tokio_tungstenite::accept_async(stream).await {
+        Ok(ws) => ws,
+        Err(e) => {
+            warn!("[metrics ws server] WebSocket handshake failed: {}", e);
+            return;
+        }
+    };
+    if ws.send(Message::Text(snapshot)).await.is_err() {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            update = updates.recv() => match update {
+                Ok(json) => {
+                    if ws.send(Message::Text(json)).await.is_err() {
+                        return;
+                    }
+                }
+                // every update is a full snapshot, so the next one catches us up
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => {
+                    let _ = ws.close(None).await;
+                    return;
+                }
+            },
+            // we don't expect anything from clients, but reading answers pings and notices disconnects
+            message = ws.next() => {
+                if !matches!(message, Some(Ok(_))) {
+                    return;
+                }
+            }
+        }
+    }
+}
@@ -1,14 +1,15 @@
 use anyhow::{Context, Result};
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use psyche_core::{
-    Barrier, BatchId, CancellableBarrier, ClosedInterval, CosineLR, OptimizerDefinition, Shuffle,
+    Barrier, BatchId, CancellableBarrier, ClosedInterval, CosineLR, DistroExtensions,
+    OptimizerDefinition, Shuffle,
 };
 use psyche_data_provider::{
     DataProvider, LengthKnownDataProvider, LocalDataProvider, PreprocessedDataProvider, Split,
     TokenizedDataProvider, download_model_repo_sync,
 };
 use psyche_modeling::{
-    AttentionImplementation, Batch, BatchData, BatchDataCPU, CausalLM, CommunicatorId,
+    AmpDtype, AttentionImplementation, Batch, BatchData, BatchDataCPU, CausalLM, CommunicatorId,
     DataParallel, Devices, LocalTrainer, ModelLoadError, ParallelModels, Trainer,
     auto_model_for_causal_lm_from_pretrained, save_tensors_into_safetensors,
 };
@@ -117,6 +118,12 @@ struct RunArgs {
     #[arg(long, default_value_t = false)]
     grad_accum_in_fp32: bool,
 
+    #[arg(
+        long,
+        help = "Keep FP32 master weights and run forward & backward passes in this dtype (bf16 or fp16)"
+    )]
+    amp_dtype: Option<AmpDtype>,
+
     #[arg(long, default_value_t = 64)]
     compression_chunk: u16,
 
@@ -132,6 +139,12 @@ struct RunArgs {
     #[arg(long, default_value_t = false)]
     distro_quantization: bool,
 
+    #[arg(long, default_value_t = false)]
+    distro_int8_quantization: bool,
+
+    #[arg(long, default_value_t = false)]
+    activation_checkpointing: bool,
+
     #[arg(long)]
     attn_implementation: Option<AttnImpl>,
 
@@ -325,9 +338,12 @@ async fn main() -> Result<()> {
                             model,
                             schedule.into(),
                             optimizer,
+                            DistroExtensions::default(),
                             args.micro_batch,
+                            1,
                             None,
                             args.grad_accum_in_fp32,
+                            None,
                         )?
                         .into())
                     } else {
@@ -347,9 +363,15 @@ async fn main() -> Result<()> {
                             },
                             schedule.into(),
                             optimizer,
+                            DistroExtensions::default(),
                             args.micro_batch,
+                            1,
                             None,
                             args.grad_accum_in_fp32,
+                            None,
+                            None,
+                            None,
+                            args.activation_checkpointing,
                         )
                         .into())
                     }
@@ -394,7 +416,11 @@ async fn main() -> Result<()> {
                                 let model: Box<dyn CausalLM> =
                                     auto_model_for_causal_lm_from_pretrained(
                                         repo_files,
-                                        Some(Kind::BFloat16),
+                                        // with mixed precision the loaded weights are the FP32 masters
+                                        Some(match args.amp_dtype {
+                                            Some(_) => Kind::Float,
+                                            None => Kind::BFloat16,
+                                        }),
                                         attn_implemention,
                                         Some(device),
                                         id.map(|id| (id, tp, tp_world_size)),
@@ -427,9 +453,15 @@ async fn main() -> Result<()> {
                         },
                         schedule.into(),
                         optimizer,
+                        DistroExtensions::default(),
                         args.micro_batch,
+                        1,
                         None,
                         args.grad_accum_in_fp32,
+                        args.amp_dtype,
+                        None,
+                        None,
+                        args.activation_checkpointing,
                     )
                     .into())
                 });
@@ -473,6 +505,7 @@ async fn main() -> Result<()> {
                 let cancel = cancel.clone();
                 let distro = args.distro;
                 let distro_quantization = args.distro_quantization;
+                let distro_int8_quantization = args.distro_int8_quantization;
                 let prev_distro_results = prev_distro_results.clone();
                 std::thread::spawn(move || {
                     #[allow(irrefutable_let_patterns)]
@@ -505,6 +538,10 @@ async fn main() -> Result<()> {
                                         x.into_iter()
                                             .map(|y| Trainer::quantize_results(&y))
                                             .collect()
+                                    } else if distro_int8_quantization {
+                                        x.into_iter()
+                                            .map(|y| Trainer::quantize_results_int8(&y))
+                                            .collect()
                                     } else {
                                         x
                                     }
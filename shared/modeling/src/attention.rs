@@ -2,7 +2,7 @@ use crate::{
     AttentionImplementation, ColumnParallelLinear, Communicator, RoPECache, RowParallelLinear,
 };
 use std::sync::Arc;
-use tch::{Device, Tensor, nn::Module};
+use tch::{Device, Kind, Tensor, nn::Module};
 
 fn repeat_kv(hidden_states: &Tensor, n_rep: i64) -> Tensor {
     let (batch, num_key_value_heads, slen, head_dim) = hidden_states.size4().unwrap();
@@ -43,6 +43,73 @@ pub fn create_cu_seqlens(lengths: &[Vec<i32>], device: Device) -> (Tensor, i32)
     (Tensor::from_slice(&cum).to(device), max)
 }
 
+/// Causal mask that also stops tokens from attending to other documents packed into the same row,
+/// shaped `[batch, 1, seq_len, seq_len]` and `true` where a query may attend to a key.
+/// Any tokens past the end of the last document are treated as one more document.
+pub fn create_document_causal_mask(lengths: &[Vec<i32>], seq_len: i64, device: Device) -> Tensor {
+    let document_ids: Vec<Vec<i64>> = lengths
+        .iter()
+        .map(|row| {
+            let mut ids = Vec::with_capacity(seq_len as usize);
+            let mut num_documents = 0;
+            for &len in row.iter().filter(|&&len| len > 0) {
+                ids.extend(std::iter::repeat_n(num_documents, len as usize));
+                num_documents += 1;
+            }
+            ids.resize(seq_len as usize, num_documents);
+            ids
+        })
+        .collect();
+    let document_ids = Tensor::from_slice2(&document_ids).to(device);
+    let same_document = document_ids
+        .unsqueeze(2)
+        .eq_tensor(&document_ids.unsqueeze(1));
+    let causal = Tensor::ones([seq_len, seq_len], (Kind::Int, device))
+        .tril(0)
+        .eq(1);
+    same_document.logical_and(&causal).unsqueeze(1)
+}
+
+/// How tokens from different documents packed into one sequence are kept from attending to each other.
+#[derive(Debug)]
+pub enum DocumentMasking {
+    /// Cumulative sequence lengths and the longest sequence, for FlashAttention2's varlen kernel.
+    #[cfg_attr(not(feature = "parallelism"), allow(dead_code))]
+    CuSeqlens(Tensor, i32),
+    /// See [`create_document_causal_mask`].
+    Mask(Tensor),
+}
+
+impl DocumentMasking {
+    /// The boolean mask for Sdpa and Eager attention.
+    pub fn mask(&self) -> &Tensor {
+        match self {
+            Self::Mask(mask) => mask,
+            Self::CuSeqlens(..) => {
+                panic!("cumulative sequence lengths are only supported for FlashAttention2")
+            }
+        }
+    }
+
+    pub fn new(
+        lengths: &[Vec<i32>],
+        seq_len: i64,
+        attn_implementation: AttentionImplementation,
+        device: Device,
+    ) -> Self {
+        match attn_implementation {
+            #[cfg(feature = "parallelism")]
+            AttentionImplementation::FlashAttention2 => {
+                let (cum_seq, max_len) = create_cu_seqlens(lengths, device);
+                Self::CuSeqlens(cum_seq, max_len)
+            }
+            AttentionImplementation::Sdpa | AttentionImplementation::Eager => {
+                Self::Mask(create_document_causal_mask(lengths, seq_len, device))
+            }
+        }
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug)]
 pub struct CausalSelfAttention {
@@ -111,7 +178,7 @@ impl CausalSelfAttention {
         &self,
         x: &Tensor,
         position_ids: Option<&Tensor>,
-        sequence_lengths: Option<&(Tensor, i32)>,
+        sequence_lengths: Option<&DocumentMasking>,
         cache: &RoPECache,
     ) -> Tensor {
         let (b, t, c) = x.size3().unwrap();
@@ -150,7 +217,12 @@ impl CausalSelfAttention {
             #[cfg(feature = "parallelism")]
             AttentionImplementation::FlashAttention2 => {
                 let (cum_seq, max_len) = match sequence_lengths {
-                    Some((cum_seq, max_len)) => (Some(cum_seq), *max_len as i64),
+                    Some(DocumentMasking::CuSeqlens(cum_seq, max_len)) => {
+                        (Some(cum_seq), *max_len as i64)
+                    }
+                    Some(DocumentMasking::Mask(_)) => {
+                        panic!("FlashAttention2 needs cumulative sequence lengths, not a mask")
+                    }
                     None => (None, t),
                 };
 
@@ -187,14 +259,15 @@ impl CausalSelfAttention {
                     .reshape([b, t, local_n_head * self.head_dim])
             }
             AttentionImplementation::Sdpa => {
-                assert!(sequence_lengths.is_none());
-                let att = Tensor::scaled_dot_product_attention::<Tensor>(
+                let mask = sequence_lengths.map(DocumentMasking::mask);
+                // the document mask is already causal
+                let att = Tensor::scaled_dot_product_attention(
                     &q,
                     &k,
                     &v,
-                    None,
+                    mask,
                     0.0,
-                    t > 1,
+                    t > 1 && mask.is_none(),
                     Some(scale),
                     false,
                 );
@@ -203,12 +276,15 @@ impl CausalSelfAttention {
                     .reshape([b, t, local_n_head * self.head_dim])
             }
             AttentionImplementation::Eager => {
-                assert!(sequence_lengths.is_none());
                 let att = q.matmul(&k.transpose(-2, -1)) * scale;
-                let mask = Tensor::ones([t, t], (kind, self.device))
-                    .tril(0)
-                    .reshape([1, 1, t, t]);
-                let att = att.masked_fill(&mask.eq(0.), f64::NEG_INFINITY);
+                let masked = match sequence_lengths {
+                    Some(document_masking) => document_masking.mask().logical_not(),
+                    None => Tensor::ones([t, t], (kind, self.device))
+                        .tril(0)
+                        .reshape([1, 1, t, t])
+                        .eq(0.),
+                };
+                let att = att.masked_fill(&masked, f64::NEG_INFINITY);
                 let y = att.softmax(-1, kind).matmul(&v);
                 y.transpose(1, 2)
                     .contiguous()
@@ -219,3 +295,26 @@ impl CausalSelfAttention {
         self.o_proj.forward(&y)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn document_causal_mask_blocks_other_documents() {
+        // two documents of 2 and 3 tokens, then one token of padding
+        let mask = create_document_causal_mask(&[vec![2, 3]], 6, Device::Cpu);
+        assert_eq!(mask.size(), [1, 1, 6, 6]);
+
+        let mask: Vec<Vec<bool>> = Vec::try_from(mask.squeeze().to_kind(Kind::Bool)).unwrap();
+        let expected = [
+            [true, false, false, false, false, false],
+            [true, true, false, false, false, false],
+            [false, false, true, false, false, false],
+            [false, false, true, true, false, false],
+            [false, false, true, true, true, false],
+            [false, false, false, false, false, true],
+        ];
+        assert_eq!(mask, expected.map(Vec::from).to_vec());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
-    DeepseekConfig, Devices, LlamaConfig, LoadSafetensorsError, parallelism::tensor_shard,
+    DeepseekConfig, Devices, LlamaConfig, LoadSafetensorsError,
+    parallelism::{tensor_shard, unsharded_tensor_size},
     safetensor_utils::load_safetensors_into_variables,
 };
 use std::{
@@ -46,6 +47,13 @@ pub enum ModelLoadError {
     #[error("Some parameters were not loaded: {0:?}")]
     LoadTensorError(HashSet<String>),
 
+    #[error("Parameter {name} has shape {actual:?}, but the model expects {expected:?}")]
+    ShapeMismatch {
+        name: String,
+        expected: Vec<i64>,
+        actual: Vec<i64>,
+    },
+
     #[error("Wrong config type")]
     WrongConfigType,
 
@@ -107,7 +115,22 @@ impl<T: serde::Serialize + Clone + serde::de::DeserializeOwned> PretrainedSource
                 let mut variables = variables.variables_.lock().unwrap();
                 let shards = variables.shards.clone();
                 for (name, var) in variables.named_variables.iter_mut() {
-                    let tensor = parameters.get(name).unwrap();
+                    let Some(tensor) = parameters.get(name) else {
+                        continue;
+                    };
+                    // the tensors may come from other peers, so check them against
+                    // the variables we built from the config ourselves
+                    let expected = match shards.get(name) {
+                        Some(shard) => unsharded_tensor_size(&var.size(), shard),
+                        None => var.size(),
+                    };
+                    if tensor.size() != expected {
+                        return Err(ModelLoadError::ShapeMismatch {
+                            name: name.clone(),
+                            expected,
+                            actual: tensor.size(),
+                        });
+                    }
                     if let Some(shard) = shards.get(name) {
                         let tensor = tensor_shard(tensor, shard);
                         var.f_copy_(&tensor)?;
@@ -186,3 +209,53 @@ impl serde::Serialize for AutoConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tch::{Device, Kind, nn::VarStore};
+
+    fn source(name: &str, shape: &[i64]) -> PretrainedSource<serde_json::Value> {
+        PretrainedSource::ConfigAndTensors(
+            serde_json::Value::Null,
+            Arc::new(HashMap::from([(
+                name.to_string(),
+                Tensor::ones(shape, (Kind::Float, Device::Cpu)),
+            )])),
+        )
+    }
+
+    #[test]
+    fn test_load_tensors_checks_shapes() {
+        let mut variables = VarStore::new(Device::Cpu);
+        let weight = variables.root().zeros("weight", &[2, 3]);
+
+        match source("weight", &[3, 2]).load(&mut variables) {
+            Err(ModelLoadError::ShapeMismatch {
+                name,
+                expected,
+                actual,
+            }) => {
+                assert_eq!(name, "weight");
+                assert_eq!(expected, vec![2, 3]);
+                assert_eq!(actual, vec![3, 2]);
+            }
+            other => panic!("expected a shape mismatch, got {other:?}"),
+        }
+        assert_eq!(weight.sum(Kind::Float).double_value(&[]), 0.0);
+
+        source("weight", &[2, 3]).load(&mut variables).unwrap();
+        assert_eq!(weight.sum(Kind::Float).double_value(&[]), 6.0);
+    }
+
+    #[test]
+    fn test_load_tensors_reports_missing_parameters() {
+        let mut variables = VarStore::new(Device::Cpu);
+        variables.root().zeros("weight", &[2, 3]);
+
+        assert!(matches!(
+            source("bias", &[2]).load(&mut variables),
+            Err(ModelLoadError::LoadTensorError(missing)) if missing.contains("weight")
+        ));
+    }
+}
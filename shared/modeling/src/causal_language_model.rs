@@ -1,8 +1,10 @@
 use crate::{
-    AllReduce, AttentionImplementation, Communicator, CommunicatorId, ModelLoadError,
-    PretrainedSource, ReduceType, RoPEConfig, StableVarStoreIterator, StableVariableIterator,
+    AllReduce, AttentionImplementation, Communicator, CommunicatorId, DocumentMasking,
+    ModelLoadError, PretrainedSource, RMSNorm, ReduceType, RoPECache, RoPEConfig,
+    StableVarStoreIterator, StableVariableIterator,
 };
 use std::collections::HashMap;
+use std::ops::Range;
 use std::sync::Arc;
 use std::sync::atomic::Ordering;
 use std::{fmt::Debug, sync::atomic::AtomicBool};
@@ -43,9 +45,40 @@ pub trait CausalLM: Send {
     fn variables(&self) -> StableVariableIterator;
     fn communicator(&self) -> Option<Arc<Communicator>>;
     fn prepare_for_training(&self);
-    fn clip_grad_norm(&self, max_grad_norm: f64);
+    // returns the total gradient norm before clipping, if known
+    fn clip_grad_norm(&self, max_grad_norm: f64) -> Option<f64>;
     fn shutdown(&self) {}
     fn convert(&self, state_dict: Option<HashMap<String, Tensor>>) -> HashMap<String, Tensor>;
+    /// How many pieces activation checkpointing splits the model into.
+    fn checkpoint_segments(&self) -> usize {
+        1
+    }
+    /// Runs piece `segment` of [`Self::checkpoint_segments`], in order.
+    /// The first segment takes token ids, and the last returns the loss.
+    fn forward_segment(
+        &self,
+        x: &Tensor,
+        labels: &Tensor,
+        position_ids: Option<&Tensor>,
+        sequence_lengths: Option<&Vec<Vec<i32>>>,
+        loss_scale: Option<f64>,
+        segment: usize,
+    ) -> Tensor {
+        assert_eq!(
+            segment, 0,
+            "model can't be split for activation checkpointing"
+        );
+        self.forward(
+            x,
+            Some(labels),
+            position_ids,
+            sequence_lengths,
+            None,
+            loss_scale,
+        )
+        .1
+        .expect("forward with labels returns a loss")
+    }
 }
 
 pub trait LanguageModelForward: Send + Debug {
@@ -56,6 +89,101 @@ pub trait LanguageModelForward: Send + Debug {
         sequence_lengths: Option<&Vec<Vec<i32>>>,
         training: bool,
     ) -> Tensor;
+
+    /// How many pieces [`Self::forward_segment`] can split the model into.
+    fn checkpoint_segments(&self) -> usize {
+        1
+    }
+
+    /// Runs piece `segment` of [`Self::checkpoint_segments`], in order.
+    /// The first segment takes token ids, and the last returns what [`Self::forward`] would.
+    fn forward_segment(
+        &self,
+        x: &Tensor,
+        position_ids: Option<&Tensor>,
+        sequence_lengths: Option<&Vec<Vec<i32>>>,
+        training: bool,
+        segment: usize,
+    ) -> Tensor {
+        assert_eq!(
+            segment, 0,
+            "model can't be split for activation checkpointing"
+        );
+        self.forward(x, position_ids, sequence_lengths, training)
+    }
+}
+
+/// The layers in piece `segment` when `num_layers` are split as evenly as possible into `num_segments`.
+fn checkpoint_segment_layers(
+    num_layers: usize,
+    num_segments: usize,
+    segment: usize,
+) -> Range<usize> {
+    (segment * num_layers / num_segments)..((segment + 1) * num_layers / num_segments)
+}
+
+/// Checkpointing every `sqrt(num_layers)` layers balances the stored boundaries against the recomputed segment.
+fn default_checkpoint_segments(num_layers: usize) -> usize {
+    ((num_layers as f64).sqrt().ceil() as usize).max(1)
+}
+
+/// One layer of a [`DecoderLayers`] stack.
+pub(crate) trait DecoderBlock {
+    fn forward(
+        &self,
+        x: &Tensor,
+        position_ids: Option<&Tensor>,
+        sequence_lengths: Option<&DocumentMasking>,
+        cache: &RoPECache,
+    ) -> Tensor;
+}
+
+/// A decoder-only model's token embeddings, blocks and final norm, which is how
+/// [`LanguageModelForward::forward_segment`] splits up such a model.
+pub(crate) struct DecoderLayers<'a, B> {
+    pub embed_tokens: &'a nn::Embedding,
+    pub blocks: &'a [B],
+    pub norm: &'a RMSNorm,
+    pub rope_cache: &'a RoPECache,
+    pub attn_implementation: AttentionImplementation,
+}
+
+impl<B: DecoderBlock> DecoderLayers<'_, B> {
+    pub fn checkpoint_segments(&self) -> usize {
+        default_checkpoint_segments(self.blocks.len())
+    }
+
+    pub fn forward_segment(
+        &self,
+        x: &Tensor,
+        position_ids: Option<&Tensor>,
+        sequence_lengths: Option<&Vec<Vec<i32>>>,
+        segment: usize,
+    ) -> Tensor {
+        let num_segments = self.checkpoint_segments();
+        let blocks =
+            &self.blocks[checkpoint_segment_layers(self.blocks.len(), num_segments, segment)];
+        let sequence_lengths = sequence_lengths.map(|sequence_lengths| {
+            DocumentMasking::new(
+                sequence_lengths,
+                x.size()[1],
+                self.attn_implementation,
+                x.device(),
+            )
+        });
+
+        let mut x = match segment {
+            0 => self.embed_tokens.forward(x),
+            _ => x.shallow_clone(),
+        };
+        for block in blocks {
+            x = block.forward(&x, position_ids, sequence_lengths.as_ref(), self.rope_cache);
+        }
+        match segment + 1 == num_segments {
+            true => self.norm.forward(&x),
+            false => x,
+        }
+    }
 }
 
 pub trait LanguageModelConfig:
@@ -175,25 +303,16 @@ impl<M: LanguageModelForward, C: LanguageModelConfig> CausalLanguageModel<M, C>
             training: AtomicBool::new(false),
         })
     }
-}
 
-impl<M: LanguageModelForward, C: LanguageModelConfig> CausalLM for CausalLanguageModel<M, C> {
-    fn forward(
+    // returns (logits, loss) for the model's final hidden states
+    fn logits_and_loss(
         &self,
-        x: &Tensor,
+        mut x: Tensor,
+        t: i64,
         labels: Option<&Tensor>,
-        position_ids: Option<&Tensor>,
-        sequence_lengths: Option<&Vec<Vec<i32>>>,
         num_logits_to_keep: Option<i64>,
         loss_scale: Option<f64>,
     ) -> (Option<Tensor>, Option<Tensor>) {
-        let (_, t) = x.size2().unwrap();
-        let mut x = self.model.forward(
-            x,
-            position_ids,
-            sequence_lengths,
-            self.training.load(Ordering::Relaxed),
-        );
         if let Some(num_logits_to_keep) = num_logits_to_keep {
             // Only compute necessary logits, and do not upcast them to float if we are not computing the loss
             x = x.slice(1, t - num_logits_to_keep, t, 1);
@@ -224,6 +343,56 @@ impl<M: LanguageModelForward, C: LanguageModelConfig> CausalLM for CausalLanguag
         };
         (Some(logits), loss)
     }
+}
+
+impl<M: LanguageModelForward, C: LanguageModelConfig> CausalLM for CausalLanguageModel<M, C> {
+    fn forward(
+        &self,
+        x: &Tensor,
+        labels: Option<&Tensor>,
+        position_ids: Option<&Tensor>,
+        sequence_lengths: Option<&Vec<Vec<i32>>>,
+        num_logits_to_keep: Option<i64>,
+        loss_scale: Option<f64>,
+    ) -> (Option<Tensor>, Option<Tensor>) {
+        let (_, t) = x.size2().unwrap();
+        let x = self.model.forward(
+            x,
+            position_ids,
+            sequence_lengths,
+            self.training.load(Ordering::Relaxed),
+        );
+        self.logits_and_loss(x, t, labels, num_logits_to_keep, loss_scale)
+    }
+
+    fn checkpoint_segments(&self) -> usize {
+        self.model.checkpoint_segments()
+    }
+
+    fn forward_segment(
+        &self,
+        x: &Tensor,
+        labels: &Tensor,
+        position_ids: Option<&Tensor>,
+        sequence_lengths: Option<&Vec<Vec<i32>>>,
+        loss_scale: Option<f64>,
+        segment: usize,
+    ) -> Tensor {
+        let t = x.size()[1];
+        let x = self.model.forward_segment(
+            x,
+            position_ids,
+            sequence_lengths,
+            self.training.load(Ordering::Relaxed),
+            segment,
+        );
+        if segment + 1 < self.checkpoint_segments() {
+            return x;
+        }
+        self.logits_and_loss(x, t, Some(labels), None, loss_scale)
+            .1
+            .expect("forward with labels returns a loss")
+    }
 
     fn bos_token_id(&self) -> Option<i64> {
         self.config.bos_token_id()
@@ -270,7 +439,7 @@ impl<M: LanguageModelForward, C: LanguageModelConfig> CausalLM for CausalLanguag
     /// The orthogonality of sharded parameters across ranks ensures that:
     /// total_norm = sqrt(all_reduce(||w_shared_local||^2) + ||w_replicated||^2)
     /// gives us the correct global L2 norm as if all parameters were on a single device.
-    fn clip_grad_norm(&self, max_norm: f64) {
+    fn clip_grad_norm(&self, max_norm: f64) -> Option<f64> {
         let mut sharded_norm_sq = Tensor::zeros([], (Kind::Float, self.device));
         let mut replicated_norm_sq = Tensor::zeros([], (Kind::Float, self.device));
 
@@ -304,6 +473,8 @@ impl<M: LanguageModelForward, C: LanguageModelConfig> CausalLM for CausalLanguag
                 }
             }
         }
+
+        Some(total_norm)
     }
 
     fn convert(&self, state_dict: Option<HashMap<String, Tensor>>) -> HashMap<String, Tensor> {
@@ -1,12 +1,18 @@
-use crate::{CausalLM, StableVariableIterator, Variable};
+use crate::{CausalLM, Communicator, StableVariableIterator, Variable};
 
-use std::{cmp::Ordering, collections::HashMap, f64::consts::PI};
+use indexmap::IndexMap;
+use std::{cmp::Ordering, collections::HashMap, f64::consts::PI, sync::Arc};
 use tch::{COptimizer, Device, Kind, Tensor};
+use thiserror::Error;
 
 pub struct TransformDCT {
     shape_dict: HashMap<i64, i64>,
+    // canonical basis matrices, kept on the CPU
     f_dict: HashMap<i64, Tensor>,
     b_dict: HashMap<i64, Tensor>,
+    // copies of the above on the devices they've been used on, made on first use
+    f_device_dict: HashMap<(i64, Device), Tensor>,
+    b_device_dict: HashMap<(i64, Device), Tensor>,
 }
 
 impl TransformDCT {
@@ -34,18 +40,9 @@ impl TransformDCT {
 
                 // Pregenerate DCT basis matrices
                 if let std::collections::hash_map::Entry::Vacant(e) = f_dict.entry(sc) {
-                    let i = Tensor::eye(sc, (Kind::Float, variable.device()));
-                    e.insert(
-                        Self::dct(&i, true)
-                            .to_kind(variable.kind())
-                            .to(variable.device()),
-                    );
-                    b_dict.insert(
-                        sc,
-                        Self::idct(&i, true)
-                            .to_kind(variable.kind())
-                            .to(variable.device()),
-                    );
+                    let i = Tensor::eye(sc, (Kind::Float, Device::Cpu));
+                    e.insert(Self::dct(&i, true).to_kind(variable.kind()));
+                    b_dict.insert(sc, Self::idct(&i, true).to_kind(variable.kind()));
                 }
             }
         }
@@ -53,9 +50,31 @@ impl TransformDCT {
             shape_dict,
             f_dict,
             b_dict,
+            f_device_dict: HashMap::new(),
+            b_device_dict: HashMap::new(),
         }
     }
 
+    fn basis_on_device(
+        dict: &HashMap<i64, Tensor>,
+        device_dict: &mut HashMap<(i64, Device), Tensor>,
+        n: i64,
+        device: Device,
+    ) -> Tensor {
+        device_dict
+            .entry((n, device))
+            .or_insert_with(|| dict.get(&n).unwrap().to_device(device))
+            .shallow_clone()
+    }
+
+    fn forward_basis(&mut self, n: i64, device: Device) -> Tensor {
+        Self::basis_on_device(&self.f_dict, &mut self.f_device_dict, n, device)
+    }
+
+    fn backward_basis(&mut self, n: i64, device: Device) -> Tensor {
+        Self::basis_on_device(&self.b_dict, &mut self.b_device_dict, n, device)
+    }
+
     fn get_prime_divisors(mut n: i64) -> Vec<i64> {
         if n == 0 {
             return Vec::new();
@@ -256,10 +275,8 @@ impl TransformDCT {
             // 2D+ weights - get chunk sizes for last two dimensions
             let n1 = *self.shape_dict.get(&shape[ndim - 2]).unwrap();
             let n2 = *self.shape_dict.get(&shape[ndim - 1]).unwrap();
-            let n1w = self.f_dict.get(&n1).unwrap().to_device(x.device());
-            let n2w = self.f_dict.get(&n2).unwrap().to_device(x.device());
-            self.f_dict.insert(n1, n1w.copy());
-            self.f_dict.insert(n2, n2w.copy());
+            let n1w = self.forward_basis(n1, x.device());
+            let n2w = self.forward_basis(n2, x.device());
 
             // Equivalent to rearrange(x, "... (y h) (x w) -> ... y h x w", h=n1, w=n2)
             let mut new_shape: Vec<i64> = shape[..ndim - 2].to_vec();
@@ -273,8 +290,7 @@ impl TransformDCT {
         } else {
             // 1D weights
             let n1 = *self.shape_dict.get(&shape[0]).unwrap();
-            let n1w = self.f_dict.get(&n1).unwrap().to_device(x.device());
-            self.f_dict.insert(n1, n1w.copy());
+            let n1w = self.forward_basis(n1, x.device());
 
             // Equivalent to rearrange(x, "(x w) -> x w", w=n1)
             let x = x.view([-1, n1]);
@@ -282,6 +298,54 @@ impl TransformDCT {
         }
     }
 
+    /// Encodes every tensor in `xs`, like calling [`Self::encode`] on each. On CUDA, tensors of
+    /// the same shape are stacked and transformed by a single einsum, so a model's repeated layers
+    /// cost one launch per distinct shape rather than one per layer.
+    pub fn encode_batch(&mut self, xs: &[Tensor]) -> Vec<Tensor> {
+        match xs.first().map(|x| x.device()) {
+            Some(Device::Cuda(_)) => self.encode_stacked(xs),
+            _ => xs.iter().map(|x| self.encode(x)).collect(),
+        }
+    }
+
+    fn encode_stacked(&mut self, xs: &[Tensor]) -> Vec<Tensor> {
+        let _no_grad = tch::no_grad_guard();
+        // groups of indices into `xs` with the same shape, kind and device
+        let mut groups: Vec<(Vec<i64>, Kind, Device, Vec<usize>)> = Vec::new();
+        for (index, x) in xs.iter().enumerate() {
+            let (shape, kind, device) = (x.size(), x.kind(), x.device());
+            match groups
+                .iter_mut()
+                .find(|group| group.0 == shape && group.1 == kind && group.2 == device)
+            {
+                Some(group) => group.3.push(index),
+                None => groups.push((shape, kind, device, vec![index])),
+            }
+        }
+
+        let mut ret: Vec<Option<Tensor>> = (0..xs.len()).map(|_| None).collect();
+        for (shape, _, _, indices) in groups {
+            // a stacked 1D tensor would be mistaken for a 2D one, and there's nothing to gain
+            // from stacking a single tensor
+            if shape.len() < 2 || indices.len() == 1 {
+                for index in indices {
+                    ret[index] = Some(self.encode(&xs[index]));
+                }
+                continue;
+            }
+            // the transform only touches the last two dimensions, so the stack dimension is
+            // carried through untouched
+            let stacked = Tensor::stack(
+                &indices.iter().map(|index| &xs[*index]).collect::<Vec<_>>(),
+                0,
+            );
+            for (index, encoded) in indices.into_iter().zip(self.encode(&stacked).unbind(0)) {
+                ret[index] = Some(encoded);
+            }
+        }
+        ret.into_iter().map(|x| x.unwrap()).collect()
+    }
+
     pub fn decode(&mut self, x: &Tensor) -> Tensor {
         let _no_grad = tch::no_grad_guard();
         let x_shape = x.size();
@@ -293,11 +357,8 @@ impl TransformDCT {
             let n2 = x_shape[ndim - 1];
             let device = x.device();
 
-            let n1w = self.b_dict.get(&n1).unwrap().to_device(device);
-            let n2w = self.b_dict.get(&n2).unwrap().to_device(device);
-
-            self.b_dict.insert(n1, n1w.copy());
-            self.b_dict.insert(n2, n2w.copy());
+            let n1w = self.backward_basis(n1, device);
+            let n2w = self.backward_basis(n2, device);
 
             let x = Self::einsum_2d_t(x, &n1w, Some(&n2w));
             let x_shape = x.size();
@@ -314,8 +375,7 @@ impl TransformDCT {
             let n1 = x_shape[1];
             let device = x.device();
 
-            let n1w = self.b_dict.get(&n1).unwrap().to_device(device);
-            self.b_dict.insert(n1, n1w.copy());
+            let n1w = self.backward_basis(n1, device);
 
             let x = Self::einsum_2d_t(x, &n1w, None);
             let x_shape = x.size();
@@ -327,9 +387,53 @@ impl TransformDCT {
     }
 }
 
+/// How many values per chunk [`CompressDCT::compress`] keeps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AdaptiveTopK {
+    /// Always keep this many.
+    Fixed(i64),
+    /// Keep the smallest k whose largest-magnitude values hold at least `retain_fraction`
+    /// of the tensor's total L2 energy. Every chunk of a tensor keeps the same k.
+    EnergyBased { retain_fraction: f64 },
+}
+
+/// How [`Distro::apply`] combines every node's results into one update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AggregationMode {
+    /// Average the values every node sent.
+    #[default]
+    ValueAverage,
+    /// Majority vote: each node's values only count by their sign, and the update follows the
+    /// sign of the sum of votes, so a node with a huge update can't outweigh the others.
+    SignSGD,
+}
+
 pub struct CompressDCT;
 
 impl CompressDCT {
+    fn energy_topk(x: &Tensor, retain_fraction: f64) -> i64 {
+        let last_dim = x.size()[x.dim() - 1];
+        // energy kept by taking the k largest values of every chunk, for each k
+        let kept_energy = x
+            .to_kind(Kind::Double)
+            .square()
+            .sort(-1, true)
+            .0
+            .reshape([-1, last_dim])
+            .sum_dim_intlist(0, false, Kind::Double)
+            .cumsum(0, Kind::Double);
+        let total_energy = kept_energy.double_value(&[last_dim - 1]);
+        if total_energy <= 0.0 {
+            return 1;
+        }
+        // kept_energy is non-decreasing, so this counts the k's that fall short
+        let too_small: i64 = kept_energy
+            .lt(retain_fraction * total_energy)
+            .sum(Kind::Int64)
+            .int64_value(&[]);
+        too_small + 1
+    }
+
     fn clamp_topk(x: &Tensor, topk: i64) -> i64 {
         let last_dim = x.size()[x.dim() - 1];
 
@@ -342,7 +446,7 @@ impl CompressDCT {
         }
     }
 
-    pub fn compress(x: &Tensor, topk: i64) -> (Tensor, Tensor, Vec<i64>, i64) {
+    pub fn compress(x: &Tensor, topk: AdaptiveTopK) -> (Tensor, Tensor, Vec<i64>, i64) {
         let _no_grad = tch::no_grad_guard();
         let xshape = x.size();
         let ndim = xshape.len();
@@ -357,6 +461,10 @@ impl CompressDCT {
         };
 
         let totalk = *x.size().last().unwrap();
+        let topk = match topk {
+            AdaptiveTopK::Fixed(topk) => topk,
+            AdaptiveTopK::EnergyBased { retain_fraction } => Self::energy_topk(&x, retain_fraction),
+        };
         let topk = Self::clamp_topk(&x, topk);
 
         let idx = x.abs().topk(topk, -1, true, false).1;
@@ -409,6 +517,81 @@ impl CompressDCT {
     }
 }
 
+/// Decompresses the results of several nodes one at a time into the same tensor
+/// [`CompressDCT::batch_decompress`] returns for all of them at once, keeping only a running
+/// sum and count per element instead of every node's values.
+pub struct DecompressAccumulator {
+    sum: Tensor,
+    count: Tensor,
+    totalk: i64,
+}
+
+impl DecompressAccumulator {
+    pub fn new(xshape: &[i64], totalk: i64, device: Device) -> Self {
+        Self {
+            sum: Tensor::zeros(xshape, (Kind::Float, device)),
+            count: Tensor::zeros(xshape, (Kind::Float, device)),
+            totalk: totalk.abs(),
+        }
+    }
+
+    /// Adds one node's compressed values, as taken by [`CompressDCT::decompress`].
+    pub fn add(&mut self, idx: &Tensor, val: &Tensor) {
+        let idx = decompress_idx(self.totalk, idx);
+        let val = val.to_kind(Kind::Float);
+        let _ = Self::flatten_last_two(&self.sum).scatter_add_(-1, &idx, &val);
+        let _ = Self::flatten_last_two(&self.count).scatter_add_(-1, &idx, &val.ones_like());
+    }
+
+    pub fn finish(self, kind: Kind) -> Tensor {
+        // elements no node sent stay zero, like in `decompress`
+        (self.sum / self.count.clamp_min(1.0)).to_kind(kind)
+    }
+
+    /// A view of `x` shaped like the tensor `decompress` scatters into.
+    fn flatten_last_two(x: &Tensor) -> Tensor {
+        let xshape = x.size();
+        let ndim = xshape.len();
+        if ndim > 2 {
+            let mut new_shape: Vec<i64> = xshape[..ndim - 2].to_vec();
+            new_shape.push(xshape[ndim - 2] * xshape[ndim - 1]);
+            x.view(new_shape.as_slice())
+        } else {
+            x.shallow_clone()
+        }
+    }
+}
+
+/// Matches `name` against a glob `pattern`, where `*` is any run of characters and `?` any one character.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // where the last `*` was, and how much of `name` it has swallowed so far
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
 fn compress_idx(max_value: i64, idx: &Tensor) -> Tensor {
     if max_value <= 256 {
         idx.to_kind(Kind::Uint8)
@@ -438,6 +621,33 @@ struct State {
     delta: Box<dyn Variable>,
 }
 
+#[derive(Debug, Error)]
+pub enum LoadDistroStateError {
+    #[error("no saved state for {0}")]
+    Missing(String),
+
+    #[error("saved state for {name} has shape {got:?}, expected {expected:?}")]
+    WrongShape {
+        name: String,
+        expected: Vec<i64>,
+        got: Vec<i64>,
+    },
+}
+
+/// Key in [`DistroResult::stats`] of the scale an int8 `sparse_val` was quantized with.
+pub const SPARSE_VAL_SCALE_STAT: &str = "sparse_val_scale";
+
+/// Key in [`DistroResult::stats`] of the number of values per chunk actually kept by [`CompressDCT::compress`].
+pub const TOPK_ACTUAL_STAT: &str = "topk_actual";
+
+/// Key in [`DistroResult::stats`] of the global L2 norm of the deltas before they were clamped
+/// to `max_grad_norm`, set on the first result of each [`Distro::generate`].
+pub const DELTA_NORM_STAT: &str = "delta_norm";
+
+/// Key in [`DistroResult::stats`] of the global L2 norm of the deltas that were compressed,
+/// set on the first result of each [`Distro::generate`].
+pub const CLAMPED_DELTA_NORM_STAT: &str = "clamped_delta_norm";
+
 #[derive(Debug)]
 pub struct DistroResult {
     pub sparse_idx: Tensor,
@@ -447,6 +657,19 @@ pub struct DistroResult {
     pub stats: Option<HashMap<String, f64>>,
 }
 
+impl DistroResult {
+    pub fn sparse_val_scale(&self) -> Option<f64> {
+        self.stat(SPARSE_VAL_SCALE_STAT)
+    }
+
+    fn stat(&self, name: &str) -> Option<f64> {
+        self.stats
+            .as_ref()
+            .and_then(|stats| stats.get(name))
+            .copied()
+    }
+}
+
 impl Clone for DistroResult {
     fn clone(&self) -> Self {
         Self {
@@ -462,20 +685,54 @@ impl Clone for DistroResult {
 pub struct Distro {
     sgd: COptimizer,
     compression_decay: f64,
-    compression_topk: i64,
+    compression_topk: AdaptiveTopK,
+    layer_topk: IndexMap<String, i64>,
     weight_decay: f64,
+    max_grad_norm: Option<f64>,
+    aggregation: AggregationMode,
     state: Vec<State>,
     transform: TransformDCT,
 }
 
+/// Settings for [`Distro::new`]. [`DistroConfig::new`] covers plain DisTrO, the rest are opt-in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistroConfig {
+    pub compression_decay: f64,
+    pub compression_chunk: i64,
+    pub compression_topk: AdaptiveTopK,
+    /// Overrides of `compression_topk` by glob over parameter names, first match wins.
+    pub layer_topk: IndexMap<String, i64>,
+    pub weight_decay: f64,
+    /// If set, the deltas are clamped to this global L2 norm before they're compressed.
+    pub max_grad_norm: Option<f64>,
+    pub aggregation: AggregationMode,
+}
+
+impl DistroConfig {
+    pub fn new(compression_decay: f64, compression_chunk: i64, compression_topk: i64) -> Self {
+        Self {
+            compression_decay,
+            compression_chunk,
+            compression_topk: AdaptiveTopK::Fixed(compression_topk),
+            layer_topk: IndexMap::new(),
+            weight_decay: 0.0,
+            max_grad_norm: None,
+            aggregation: AggregationMode::ValueAverage,
+        }
+    }
+}
+
 impl Distro {
-    pub fn new(
-        vs: &dyn CausalLM,
-        compression_decay: f64,
-        compression_chunk: i64,
-        compression_topk: i64,
-        weight_decay: f64,
-    ) -> Self {
+    pub fn new(vs: &dyn CausalLM, config: DistroConfig) -> Self {
+        let DistroConfig {
+            compression_decay,
+            compression_chunk,
+            compression_topk,
+            layer_topk,
+            weight_decay,
+            max_grad_norm,
+            aggregation,
+        } = config;
         let _no_grad = tch::no_grad_guard();
         let mut sgd = COptimizer::sgd(0.1, 0.0, 0.0, 0.0, false).unwrap();
 
@@ -496,12 +753,24 @@ impl Distro {
             sgd,
             compression_decay,
             compression_topk,
+            layer_topk,
             weight_decay,
+            max_grad_norm,
+            aggregation,
             state,
             transform,
         }
     }
 
+    /// The first `layer_topk` pattern matching `name` wins, otherwise it's `compression_topk`.
+    fn resolve_topk(&self, name: &str) -> AdaptiveTopK {
+        self.layer_topk
+            .iter()
+            .find(|(pattern, _)| glob_matches(pattern, name))
+            .map(|(_, topk)| AdaptiveTopK::Fixed(*topk))
+            .unwrap_or(self.compression_topk)
+    }
+
     pub fn generate(
         &mut self,
         variables: &dyn CausalLM,
@@ -513,6 +782,8 @@ impl Distro {
         let _no_grad = tch::no_grad_guard();
 
         let mut ret = Vec::new();
+        let mut full_deltas = Vec::new();
+        let mut grad_energies = Vec::new();
         for (index, var) in variables.variables().enumerate() {
             let mut variable = var.logical_tensor();
 
@@ -542,14 +813,7 @@ impl Distro {
                 let val_kind: Kind = variable.kind();
                 let values = prev_self_results
                     .iter()
-                    .map(|x| {
-                        let sparse_val = x[index].sparse_val.to_device(device);
-                        if sparse_val.kind() == Kind::Bool {
-                            Self::unpack_tensor_sign_from_boolean(sparse_val, val_kind)
-                        } else {
-                            sparse_val
-                        }
-                    })
+                    .map(|x| Self::unpack_sparse_val(&x[index], device, val_kind))
                     .collect::<Vec<_>>();
 
                 // Decode grad from all nodes
@@ -580,41 +844,162 @@ impl Distro {
             // add delta to new gradient
             let _t = delta.g_add_(&variable.grad().multiply_scalar(lr));
 
-            // Compress delta
             let full_delta = delta_var.gather_full_tensor();
-            let (sparse_idx, sparse_val, xshape, totalk) =
-                CompressDCT::compress(&self.transform.encode(&full_delta), self.compression_topk);
-
-            let delta_energy: Option<f64> = match stats {
-                true => Some(
-                    full_delta
-                        .norm_scalaropt_dtype(1, Kind::Float)
-                        .try_into()
-                        .unwrap(),
-                ),
-                false => None,
-            };
+            match self.max_grad_norm {
+                // clamping needs the norm across every layer before any of them is compressed
+                Some(_) => {
+                    full_deltas.push(full_delta);
+                    grad_energies.push(grad_energy);
+                }
+                None => {
+                    let encoded = self.transform.encode(&full_delta);
+                    ret.push(self.compress_delta(
+                        var.name(),
+                        &full_delta,
+                        &encoded,
+                        grad_energy,
+                        stats,
+                    ));
+                }
+            }
+        }
 
-            ret.push(DistroResult {
-                sparse_idx,
-                sparse_val,
-                xshape,
-                totalk,
-                stats: match stats {
-                    true => {
-                        let name = var.name();
-                        Some(HashMap::from([
-                            (format!("{name}.delta_energy"), delta_energy.unwrap()),
-                            (format!("{name}.grad_energy"), grad_energy.unwrap()),
-                        ]))
-                    }
-                    false => None,
-                },
-            });
+        if let Some(max_norm) = self.max_grad_norm {
+            let (norm, clamped_norm) = self.clamp_grad_norm_(&mut full_deltas, max_norm);
+            let encoded = self.transform.encode_batch(&full_deltas);
+            for (var, ((full_delta, encoded), grad_energy)) in variables
+                .variables()
+                .zip(full_deltas.into_iter().zip(encoded).zip(grad_energies))
+            {
+                ret.push(self.compress_delta(
+                    var.name(),
+                    &full_delta,
+                    &encoded,
+                    grad_energy,
+                    stats,
+                ));
+            }
+            if let (true, Some(first)) = (stats, ret.first_mut()) {
+                let result_stats = first.stats.get_or_insert_with(HashMap::new);
+                result_stats.insert(DELTA_NORM_STAT.to_string(), norm);
+                result_stats.insert(CLAMPED_DELTA_NORM_STAT.to_string(), clamped_norm);
+            }
         }
         ret
     }
 
+    /// Compresses the DCT of a parameter's full delta into the result sent to every client.
+    fn compress_delta(
+        &self,
+        name: &str,
+        full_delta: &Tensor,
+        encoded: &Tensor,
+        grad_energy: Option<f64>,
+        stats: bool,
+    ) -> DistroResult {
+        let topk = self.resolve_topk(name);
+        let (sparse_idx, sparse_val, xshape, totalk) = CompressDCT::compress(encoded, topk);
+
+        let mut result_stats = match stats {
+            true => {
+                let delta_energy: f64 = full_delta
+                    .norm_scalaropt_dtype(1, Kind::Float)
+                    .try_into()
+                    .unwrap();
+                let mut result_stats = HashMap::from([
+                    (format!("{name}.delta_energy"), delta_energy),
+                    (format!("{name}.grad_energy"), grad_energy.unwrap()),
+                ]);
+                if let AdaptiveTopK::Fixed(topk) = topk {
+                    result_stats.insert(format!("{name}.topk"), topk as f64);
+                }
+                Some(result_stats)
+            }
+            false => None,
+        };
+        // with a fixed k there's nothing to analyze unless we're collecting stats anyway
+        if stats || matches!(topk, AdaptiveTopK::EnergyBased { .. }) {
+            let topk_actual = *sparse_val.size().last().unwrap();
+            result_stats
+                .get_or_insert_with(HashMap::new)
+                .insert(TOPK_ACTUAL_STAT.to_string(), topk_actual as f64);
+        }
+
+        DistroResult {
+            sparse_idx,
+            sparse_val,
+            xshape,
+            totalk,
+            stats: result_stats,
+        }
+    }
+
+    /// Scales every delta down by the same factor if their global L2 norm is over `max_norm`, so
+    /// a single exploding gradient can't blow up what every client decompresses. `full_deltas`
+    /// are the gathered deltas about to be compressed, and the deltas kept for error feedback are
+    /// scaled to match. Returns the global norm before and after clamping.
+    fn clamp_grad_norm_(&mut self, full_deltas: &mut [Tensor], max_norm: f64) -> (f64, f64) {
+        let norm = full_deltas
+            .iter()
+            .map(|delta| delta.norm().to_kind(Kind::Double).double_value(&[]).powi(2))
+            .sum::<f64>()
+            .sqrt();
+        if norm <= max_norm {
+            return (norm, norm);
+        }
+
+        let scale = max_norm / (norm + 1e-6);
+        for (full_delta, state) in full_deltas.iter_mut().zip(self.state.iter_mut()) {
+            // scale a copy first, since the full delta may share storage with an unsharded delta
+            *full_delta = full_delta.multiply_scalar(scale);
+            let _t = state.delta.logical_tensor().g_mul_scalar_(scale);
+        }
+        (norm, norm * scale)
+    }
+
+    /// The error feedback accumulated for each parameter, named `<parameter>.delta`.
+    /// Must be called on every rank; only rank zero gets the tensors back.
+    pub fn unsharded_cpu_state(&self, comm: Option<Arc<Communicator>>) -> HashMap<String, Tensor> {
+        let _no_grad = tch::no_grad_guard();
+        let mut ret = match comm.as_ref().map(|x| x.rank() == 0).unwrap_or(true) {
+            true => Some(HashMap::new()),
+            false => None,
+        };
+        for state in &self.state {
+            let tensor = state.delta.gather_full_tensor().to_device(Device::Cpu);
+            if let Some(ret) = ret.as_mut() {
+                ret.insert(state.delta.name().to_owned(), tensor);
+            }
+        }
+        ret.unwrap_or_default()
+    }
+
+    /// Restores error feedback saved by [`Self::unsharded_cpu_state`], sharding it like the parameters.
+    pub fn load_state(
+        &mut self,
+        saved: &HashMap<String, Tensor>,
+    ) -> Result<(), LoadDistroStateError> {
+        let _no_grad = tch::no_grad_guard();
+        for state in &self.state {
+            let name = state.delta.name();
+            let tensor = saved
+                .get(name)
+                .ok_or_else(|| LoadDistroStateError::Missing(name.to_owned()))?;
+            let expected = state.delta.full_tensor_shape();
+            if tensor.size() != expected {
+                return Err(LoadDistroStateError::WrongShape {
+                    name: name.to_owned(),
+                    expected,
+                    got: tensor.size(),
+                });
+            }
+            let mut delta = state.delta.local_tensor();
+            let tensor = tensor.to_device(delta.device()).to_kind(delta.kind());
+            delta.copy_(&state.delta.shard_other_tensor_like_me(tensor));
+        }
+        Ok(())
+    }
+
     pub fn apply(&mut self, vars: &dyn CausalLM, results: &[Vec<DistroResult>], lr: f64) {
         let _no_grad = tch::no_grad_guard();
         if results.is_empty() {
@@ -632,14 +1017,7 @@ impl Distro {
             let val_kind: Kind = variable.kind();
             let values = results
                 .iter()
-                .map(|x| {
-                    let sparse_val = x[index].sparse_val.to_device(device);
-                    if sparse_val.kind() == Kind::Bool {
-                        Self::unpack_tensor_sign_from_boolean(sparse_val, val_kind)
-                    } else {
-                        sparse_val
-                    }
-                })
+                .map(|x| self.aggregated_sparse_val(&x[index], device, val_kind))
                 .collect::<Vec<_>>();
 
             // Decode grad from all nodes
@@ -652,13 +1030,71 @@ impl Distro {
                 device,
             );
 
-            // Set the gradients!!!
-            var.set_grad(self.transform.decode(&decompressed));
+            self.set_sign_grad(var.as_ref(), &decompressed);
+        }
+        self.sgd_step(vars, lr);
+    }
+
+    /// Like [`Self::apply`], but takes each node's results as they come, folding them into a
+    /// [`DecompressAccumulator`] per variable. Peak memory stays at about twice the size of the
+    /// model's gradients however many nodes there are, instead of holding every node's results
+    /// on the device at once.
+    pub fn apply_streamed(
+        &mut self,
+        vars: &dyn CausalLM,
+        results: impl IntoIterator<Item = impl AsRef<[DistroResult]>>,
+        lr: f64,
+    ) {
+        let _no_grad = tch::no_grad_guard();
+
+        let mut accumulators: Vec<DecompressAccumulator> = Vec::new();
+        for node_results in results {
+            let node_results = node_results.as_ref();
+            if accumulators.is_empty() {
+                accumulators = vars
+                    .variables()
+                    .zip(node_results)
+                    .map(|(var, result)| {
+                        let device = var.logical_tensor().device();
+                        DecompressAccumulator::new(&result.xshape, result.totalk, device)
+                    })
+                    .collect();
+            }
+            for ((var, accumulator), result) in
+                vars.variables().zip(&mut accumulators).zip(node_results)
+            {
+                let variable = var.logical_tensor();
+                let device = variable.device();
+                let values = self.aggregated_sparse_val(result, device, variable.kind());
+                accumulator.add(&result.sparse_idx.to_device(device), &values);
+            }
+        }
+        if accumulators.is_empty() {
+            return;
+        }
 
-            // Sign-SGD
-            let _t = variable.grad().sign_();
+        for (var, accumulator) in vars.variables().zip(accumulators) {
+            let decompressed = accumulator.finish(var.logical_tensor().kind());
+            self.set_sign_grad(var.as_ref(), &decompressed);
         }
-        // SGD step
+        self.sgd_step(vars, lr);
+    }
+
+    fn set_sign_grad(&mut self, var: &dyn Variable, decompressed: &Tensor) {
+        let decompressed = match self.aggregation {
+            AggregationMode::ValueAverage => decompressed.shallow_clone(),
+            // the votes were averaged, which keeps the sign of their sum
+            AggregationMode::SignSGD => decompressed.sign(),
+        };
+
+        // Set the gradients!!!
+        var.set_grad(self.transform.decode(&decompressed));
+
+        // Sign-SGD
+        let _t = var.logical_tensor().grad().sign_();
+    }
+
+    fn sgd_step(&mut self, vars: &dyn CausalLM, lr: f64) {
         self.sgd.set_learning_rate(lr).unwrap();
         let _ = self.sgd.step();
         for var in vars.variables() {
@@ -695,6 +1131,63 @@ impl Distro {
     fn unpack_tensor_sign_from_boolean(tensor: Tensor, unpack_kind: Kind) -> Tensor {
         tensor.to_kind(unpack_kind) * -2 + 1
     }
+
+    /// Symmetric per-tensor int8 quantization, returning the scale to multiply the int8 values by.
+    /// The scale is rounded to f32, so it's the same whether it's read from our own result or
+    /// from the serialized copy peers receive.
+    pub fn quantize_tensor_to_int8(tensor: &Tensor) -> (Tensor, f64) {
+        let tensor = tensor.to_kind(Kind::Float);
+        let max_abs: f64 = tensor.abs().max().try_into().unwrap();
+        let scale = match max_abs > 0.0 {
+            true => (max_abs / 127.0) as f32 as f64,
+            false => 1.0,
+        };
+        let quantized = (tensor / scale)
+            .round()
+            .clamp(-127.0, 127.0)
+            .to_kind(Kind::Int8);
+        (quantized, scale)
+    }
+
+    /// Inverse of [`Self::quantize_tensor_to_int8`] for every int8 result, leaving others untouched.
+    pub fn dequantize_int8_results(results: &[DistroResult]) -> Vec<DistroResult> {
+        results
+            .iter()
+            .map(|x| match x.sparse_val.kind() {
+                Kind::Int8 => DistroResult {
+                    sparse_val: x.sparse_val.to_kind(Kind::Float)
+                        * x.sparse_val_scale().unwrap_or(1.0),
+                    ..x.clone()
+                },
+                _ => x.clone(),
+            })
+            .collect()
+    }
+
+    /// Brings a (possibly quantized) `sparse_val` back to `val_kind`, ready to be accumulated.
+    fn unpack_sparse_val(result: &DistroResult, device: Device, val_kind: Kind) -> Tensor {
+        let sparse_val = result.sparse_val.to_device(device);
+        match sparse_val.kind() {
+            Kind::Bool => Self::unpack_tensor_sign_from_boolean(sparse_val, val_kind),
+            Kind::Int8 => sparse_val.to_kind(val_kind) * result.sparse_val_scale().unwrap_or(1.0),
+            _ => sparse_val,
+        }
+    }
+
+    /// A node's values as they're aggregated: as sent, or just their signs for
+    /// [`AggregationMode::SignSGD`]. 1-bit results already unpack to ±1.
+    fn aggregated_sparse_val(
+        &self,
+        result: &DistroResult,
+        device: Device,
+        val_kind: Kind,
+    ) -> Tensor {
+        let values = Self::unpack_sparse_val(result, device, val_kind);
+        match self.aggregation {
+            AggregationMode::ValueAverage => values,
+            AggregationMode::SignSGD => values.sign(),
+        }
+    }
 }
 
 unsafe impl Send for Distro {}
@@ -865,7 +1358,7 @@ mod tests {
             vec![4i64, 4i64],
             4i64,
         );
-        let ret = CompressDCT::compress(&r, 2);
+        let ret = CompressDCT::compress(&r, AdaptiveTopK::Fixed(2));
         assert_eq!(truth.0, ret.0);
         assert!(truth.1.allclose(&ret.1, 1e-4, 1e-8, false));
         assert_eq!(truth.2, ret.2);
@@ -883,13 +1376,56 @@ mod tests {
             vec![8i64],
             8i64,
         );
-        let ret = CompressDCT::compress(&r, 2);
+        let ret = CompressDCT::compress(&r, AdaptiveTopK::Fixed(2));
         assert_eq!(truth.0, ret.0);
         assert!(truth.1.allclose(&ret.1, 1e-4, 1e-8, false));
         assert_eq!(truth.2, ret.2);
         assert_eq!(8, ret.3);
     }
 
+    #[test]
+    fn test_compress_energy_based() {
+        let r = _1d_float(&[
+            0.5223, 0.9625, 0.5487, 0.2152, 0.2161, 0.0363, 0.4944, 0.0974,
+        ]);
+        // 0.9625 alone holds ~50.1% of the energy, with 0.5487 it's ~66.4%
+        let ret = CompressDCT::compress(
+            &r,
+            AdaptiveTopK::EnergyBased {
+                retain_fraction: 0.5,
+            },
+        );
+        assert_eq!(_1d_int(&[1]), ret.0);
+        let ret = CompressDCT::compress(
+            &r,
+            AdaptiveTopK::EnergyBased {
+                retain_fraction: 0.6,
+            },
+        );
+        assert_eq!(_1d_int(&[1, 2]), ret.0);
+        assert!(_1d_float(&[0.9625, 0.5487]).allclose(&ret.1, 1e-4, 1e-8, false));
+
+        let ret = CompressDCT::compress(
+            &r,
+            AdaptiveTopK::EnergyBased {
+                retain_fraction: 1.0,
+            },
+        );
+        assert_eq!(8, *ret.1.size().last().unwrap());
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("*.embed*", "model.embed_tokens.weight"));
+        assert!(glob_matches(
+            "*self_attn.?_proj*",
+            "model.layers.0.self_attn.q_proj.weight"
+        ));
+        assert!(glob_matches("lm_head.weight", "lm_head.weight"));
+        assert!(!glob_matches("*.embed*", "lm_head.weight"));
+        assert!(!glob_matches("lm_head", "lm_head.weight"));
+    }
+
     #[test]
     fn test_decompress_1d() {
         let p = _1d_float(&[0.0]);
@@ -903,6 +1439,25 @@ mod tests {
         assert!(truth.allclose(&ret, 1e-4, 1e-8, false));
     }
 
+    #[test]
+    fn test_decompress_accumulator_matches_batch() {
+        let p = _1d_float(&[0.0]);
+        let idx = [_2d_int(&[[0, 2], [1, 3]]), _2d_int(&[[2, 3], [1, 0]])];
+        let val = [
+            _2d_float(&[[1.0, 2.0], [3.0, 4.0]]),
+            _2d_float(&[[5.0, 6.0], [7.0, 8.0]]),
+        ];
+        let xshape = vec![2i64, 4i64];
+        let batch =
+            CompressDCT::batch_decompress(&idx, &val, &xshape, i64::MAX, p.kind(), p.device());
+
+        let mut accumulator = DecompressAccumulator::new(&xshape, i64::MAX, p.device());
+        for (idx, val) in idx.iter().zip(&val) {
+            accumulator.add(idx, val);
+        }
+        assert!(batch.allclose(&accumulator.finish(p.kind()), 1e-4, 1e-8, false));
+    }
+
     #[test]
     fn test_decompress_2d() {
         let p = _1d_float(&[0.0]);
@@ -958,6 +1513,27 @@ mod tests {
         assert!(truth.allclose(&ret, 1e-4, 1e-4, false));
     }
 
+    #[test]
+    fn test_encode_stacked_matches_encode() {
+        set_torch_rng_seed();
+        let xs = vec![
+            Tensor::randn([8, 16], (Kind::Float, Device::Cpu)),
+            Tensor::randn([16], (Kind::Float, Device::Cpu)),
+            Tensor::randn([8, 16], (Kind::Float, Device::Cpu)),
+            Tensor::randn([16, 8], (Kind::Float, Device::Cpu)),
+            Tensor::randn([16], (Kind::Float, Device::Cpu)),
+            Tensor::randn([8, 16], (Kind::Float, Device::Cpu)),
+        ];
+        let mut transform = TransformDCT::new(vars(xs.iter().map(|x| x.copy()).collect()), 4);
+        let stacked = transform.encode_stacked(&xs);
+        assert_eq!(stacked.len(), xs.len());
+        for (x, encoded) in xs.iter().zip(stacked) {
+            let truth = transform.encode(x);
+            assert_eq!(encoded.size(), truth.size());
+            assert!(truth.allclose(&encoded, 1e-5, 1e-5, false));
+        }
+    }
+
     #[test]
     fn test_decode_1d() {
         let a = Tensor::arange(8, (Kind::Float, Device::Cpu));
@@ -1019,7 +1595,8 @@ mod tests {
 
         let signed_truth = truth.sign();
 
-        let (sparse_idx, sparse_val, xshape, totalk) = CompressDCT::compress(&truth, i64::MAX);
+        let (sparse_idx, sparse_val, xshape, totalk) =
+            CompressDCT::compress(&truth, AdaptiveTopK::Fixed(i64::MAX));
         let signed_sparse_val = sparse_val.sign();
 
         let decompressed_signed = CompressDCT::decompress(
@@ -0,0 +1,92 @@
+use crate::{CausalLM, Communicator, Variable};
+
+use std::{collections::HashMap, sync::Arc};
+use tch::{Device, Tensor};
+
+/// Prefix of the averaged parameters' names when extracted alongside the model's own.
+pub const EMA_PREFIX: &str = "ema.";
+
+/// Exponential moving average of a model's trainable parameters.
+/// The averaged weights can be swapped into the model (e.g. for evals), in which case
+/// the model's own weights are held in the shadow copies until swapped back.
+pub struct ExponentialMovingAverage {
+    decay: f64,
+    parameters: Vec<(Box<dyn Variable>, Box<dyn Variable>)>,
+    swapped_in: bool,
+}
+
+impl ExponentialMovingAverage {
+    pub fn new(model: &dyn CausalLM, decay: f64) -> Self {
+        let _no_grad = tch::no_grad_guard();
+        let parameters = model
+            .variables()
+            .filter(|parameter| parameter.local_tensor().requires_grad())
+            .map(|parameter| {
+                let shadow = parameter.zeros_like(format!("{EMA_PREFIX}{}", parameter.name()));
+                shadow.local_tensor().copy_(&parameter.local_tensor());
+                (parameter, shadow)
+            })
+            .collect();
+        Self {
+            decay,
+            parameters,
+            swapped_in: false,
+        }
+    }
+
+    /// `ema = decay * ema + (1 - decay) * param`, for every parameter.
+    pub fn update(&mut self) {
+        self.use_model_weights();
+        let _no_grad = tch::no_grad_guard();
+        for (parameter, shadow) in &self.parameters {
+            let mut shadow = shadow.local_tensor();
+            let _t = shadow.g_mul_scalar_(self.decay);
+            let _t = shadow.g_add_(&(parameter.local_tensor() * (1.0 - self.decay)));
+        }
+    }
+
+    pub fn use_ema_weights(&mut self) {
+        if !self.swapped_in {
+            self.swap();
+        }
+    }
+
+    pub fn use_model_weights(&mut self) {
+        if self.swapped_in {
+            self.swap();
+        }
+    }
+
+    fn swap(&mut self) {
+        let _no_grad = tch::no_grad_guard();
+        for (parameter, shadow) in &self.parameters {
+            let mut parameter = parameter.local_tensor();
+            let mut shadow = shadow.local_tensor();
+            let model_weights = parameter.copy();
+            parameter.copy_(&shadow);
+            shadow.copy_(&model_weights);
+        }
+        self.swapped_in = !self.swapped_in;
+    }
+
+    /// Like [`crate::unsharded_cpu_variables`], but for the averaged parameters, named with [`EMA_PREFIX`].
+    /// Must be called on every rank; only rank zero gets the tensors back.
+    pub fn unsharded_cpu_variables(
+        &mut self,
+        comm: Option<Arc<Communicator>>,
+    ) -> HashMap<String, Tensor> {
+        self.use_model_weights();
+        let _no_grad = tch::no_grad_guard();
+        let mut ret = match comm.as_ref().map(|x| x.rank() == 0).unwrap_or(true) {
+            true => Some(HashMap::new()),
+            false => None,
+        };
+        for (_, shadow) in &self.parameters {
+            let tensor = shadow.gather_full_tensor().to_device(Device::Cpu);
+            if let Some(ret) = ret.as_mut() {
+                ret.insert(shadow.name().to_owned(), tensor);
+            }
+        }
+        ret.unwrap_or_default()
+    }
+}
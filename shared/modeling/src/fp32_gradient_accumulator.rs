@@ -50,6 +50,8 @@ impl Fp32GradientAccumulator {
         }
     }
 
+    /// Copies the accumulated gradients onto the parameters. [`Self::reduce_gradients`] only
+    /// reduces the FP32 buffer, so under data parallelism this has to run after it.
     pub fn apply_accumulation(&mut self) {
         let _no_grad = tch::no_grad_guard();
         for (param, (start, end)) in &self.parameters {
@@ -71,3 +73,41 @@ impl Fp32GradientAccumulator {
         self.fp32_grads.all_reduce(&Some(comm), ReduceType::Mean);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tch::Device;
+
+    #[test]
+    fn test_apply_accumulation_after_reduce() {
+        let parameter = Tensor::zeros([2], (Kind::BFloat16, Device::Cpu)).set_requires_grad(true);
+        let mut grad_accum = Fp32GradientAccumulator {
+            parameters: vec![(parameter.shallow_clone(), (0, 2))],
+            fp32_grads: Tensor::zeros([2], (Kind::Float, Device::Cpu)),
+        };
+        let scale = Tensor::from_slice(&[1.0f32, 2.0]).to_kind(Kind::BFloat16);
+        for _ in 0..2 {
+            (&parameter * &scale).sum(Kind::Float).backward();
+            grad_accum.accumulate_gradients();
+        }
+        assert!(grad_accum.get_full_grad_buffer().allclose(
+            &Tensor::from_slice(&[2.0f32, 4.0]),
+            0.0,
+            0.0,
+            false
+        ));
+
+        // stand-in for the DP all-reduce, which averages in another rank's [4.0, 6.0]
+        grad_accum
+            .fp32_grads
+            .copy_(&Tensor::from_slice(&[3.0f32, 5.0]));
+        grad_accum.apply_accumulation();
+        assert!(parameter.grad().to_kind(Kind::Float).allclose(
+            &Tensor::from_slice(&[3.0f32, 5.0]),
+            0.0,
+            0.0,
+            false
+        ));
+    }
+}
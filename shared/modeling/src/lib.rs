@@ -7,7 +7,9 @@ mod causal_language_model;
 mod device_utils;
 mod distro;
 mod dummy;
+mod ema;
 mod fp32_gradient_accumulator;
+mod mixed_precision;
 mod models;
 mod optimizer;
 mod parallelism;
@@ -25,7 +27,7 @@ mod token_output_stream;
 mod trainer;
 mod variable;
 
-pub use attention::CausalSelfAttention;
+pub use attention::{CausalSelfAttention, DocumentMasking, create_document_causal_mask};
 pub use auto_config::{AttentionImplementation, AutoConfig, ModelLoadError, PretrainedSource};
 pub use auto_model::auto_model_for_causal_lm_from_pretrained;
 pub use auto_tokenizer::{AutoTokenizerError, auto_tokenizer};
@@ -35,9 +37,15 @@ pub use causal_language_model::{
     LanguageModelForward,
 };
 pub use device_utils::{Devices, get_optimal_devices};
-pub use distro::{CompressDCT, Distro, DistroResult, TransformDCT};
+pub use distro::{
+    AdaptiveTopK, AggregationMode, CLAMPED_DELTA_NORM_STAT, CompressDCT, DELTA_NORM_STAT,
+    DecompressAccumulator, Distro, DistroConfig, DistroResult, LoadDistroStateError,
+    SPARSE_VAL_SCALE_STAT, TOPK_ACTUAL_STAT, TransformDCT,
+};
 pub use dummy::{DummyModel, get_dummy_parameters};
+pub use ema::{EMA_PREFIX, ExponentialMovingAverage};
 pub use fp32_gradient_accumulator::Fp32GradientAccumulator;
+pub use mixed_precision::{AmpDtype, MixedPrecision};
 pub use models::*;
 pub use optimizer::Optimizer;
 pub use parallelism::{
@@ -63,8 +71,9 @@ pub use safetensor_utils::{
 pub use sampling::{LogitsProcessor, Sampling};
 pub use token_output_stream::TokenOutputStream;
 pub use trainer::{
-    ApplyDistroResultError, Batch, BatchData, BatchDataCPU, BatchDataGPU, DataParallel,
-    LocalTrainer, ParallelModels, TrainOutput, Trainer, TrainerThreadCommunicationError,
+    ApplyDistroResultError, Batch, BatchData, BatchDataCPU, BatchDataGPU, DataParallel, GradNorm,
+    LoadOptimizerStateError, LocalTrainer, ParallelModels, TrainOutput, Trainer,
+    TrainerThreadCommunicationError,
 };
 pub use variable::{StableVarStoreIterator, StableVariableIterator, Variable};
 
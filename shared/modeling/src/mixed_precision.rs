@@ -0,0 +1,198 @@
+use crate::CausalLM;
+
+use std::{fmt::Display, str::FromStr};
+use tch::{Kind, Tensor};
+use tracing::{debug, warn};
+
+/// FP16's loss scale starts here, halves whenever the gradients overflow, and doubles after
+/// [`LOSS_SCALE_GROWTH_INTERVAL`] steps in a row without one.
+const INITIAL_LOSS_SCALE: f64 = 65536.0;
+const LOSS_SCALE_GROWTH_INTERVAL: u32 = 2000;
+const MIN_LOSS_SCALE: f64 = 1.0;
+
+/// The dtype [`MixedPrecision`] runs forward and backward passes in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmpDtype {
+    BF16,
+    /// Narrower range than BF16, so losses are scaled up to keep small gradients from underflowing
+    FP16,
+}
+
+impl AmpDtype {
+    pub fn kind(self) -> Kind {
+        match self {
+            AmpDtype::BF16 => Kind::BFloat16,
+            AmpDtype::FP16 => Kind::Half,
+        }
+    }
+}
+
+impl FromStr for AmpDtype {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bf16" | "bfloat16" => Ok(AmpDtype::BF16),
+            "fp16" | "float16" | "half" => Ok(AmpDtype::FP16),
+            _ => Err(format!(
+                "unknown mixed precision dtype {s}, expected bf16 or fp16"
+            )),
+        }
+    }
+}
+
+impl Display for AmpDtype {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AmpDtype::BF16 => write!(f, "bf16"),
+            AmpDtype::FP16 => write!(f, "fp16"),
+        }
+    }
+}
+
+/// Automatic mixed precision for a model loaded in FP32. Its trainable weights are the FP32
+/// master weights, and copies in the [`AmpDtype`] are swapped in for forward and backward passes.
+/// Gradients are meant to be accumulated in FP32 (see [`crate::Fp32GradientAccumulator`]), so
+/// clipping and the optimizer step all happen on the masters and small updates aren't rounded away.
+pub struct MixedPrecision {
+    dtype: AmpDtype,
+    /// Each trainable parameter, and its FP32 master while the low precision copy is swapped in
+    parameters: Vec<(Tensor, Option<Tensor>)>,
+    loss_scale: f64,
+    steps_since_overflow: u32,
+}
+
+impl MixedPrecision {
+    pub fn new(model: &dyn CausalLM, dtype: AmpDtype) -> Self {
+        let parameters: Vec<(Tensor, Option<Tensor>)> = model
+            .variables()
+            .map(|parameter| parameter.local_tensor())
+            .filter(|parameter| parameter.requires_grad())
+            .map(|parameter| (parameter, None))
+            .collect();
+        if let Some((parameter, _)) = parameters.iter().find(|(x, _)| x.kind() != Kind::Float) {
+            warn!(
+                "Mixed precision expects a model loaded in FP32, but found {:?} weights",
+                parameter.kind()
+            );
+        }
+        Self {
+            dtype,
+            parameters,
+            loss_scale: INITIAL_LOSS_SCALE,
+            steps_since_overflow: 0,
+        }
+    }
+
+    /// What the loss should be multiplied by before the backward pass, if anything.
+    pub fn loss_scale(&self) -> Option<f64> {
+        match self.dtype {
+            AmpDtype::BF16 => None,
+            AmpDtype::FP16 => Some(self.loss_scale),
+        }
+    }
+
+    /// Swaps in `dtype` copies of the master weights, to run forward and backward passes on.
+    pub fn use_low_precision_weights(&mut self) {
+        let _no_grad = tch::no_grad_guard();
+        let kind = self.dtype.kind();
+        for (parameter, master) in &mut self.parameters {
+            if master.is_none() {
+                let fp32 = parameter.detach();
+                parameter.set_data(&fp32.to_kind(kind));
+                Self::set_grad_kind(parameter, kind);
+                *master = Some(fp32);
+            }
+        }
+    }
+
+    /// Swaps the FP32 master weights back in, e.g. for the optimizer step.
+    pub fn use_master_weights(&mut self) {
+        let _no_grad = tch::no_grad_guard();
+        for (parameter, master) in &mut self.parameters {
+            if let Some(fp32) = master.take() {
+                parameter.set_data(&fp32);
+                Self::set_grad_kind(parameter, Kind::Float);
+            }
+        }
+    }
+
+    /// Divides `grads`, the accumulated FP32 gradients, by the loss scale. If any overflowed,
+    /// they're zeroed so the step doesn't apply garbage, and the loss scale is lowered.
+    pub fn unscale_gradients(&mut self, grads: &Tensor) {
+        if self.dtype != AmpDtype::FP16 {
+            return;
+        }
+        let _no_grad = tch::no_grad_guard();
+        let mut grads = grads.shallow_clone();
+        if grads.isfinite().all().int64_value(&[]) == 0 {
+            self.loss_scale = (self.loss_scale / 2.0).max(MIN_LOSS_SCALE);
+            self.steps_since_overflow = 0;
+            let _ = grads.zero_();
+            warn!(
+                "FP16 gradients overflowed, skipping this step's update and lowering the loss scale to {}",
+                self.loss_scale
+            );
+            return;
+        }
+
+        let _t = grads.g_mul_scalar_(1.0 / self.loss_scale);
+        self.steps_since_overflow += 1;
+        if self.steps_since_overflow == LOSS_SCALE_GROWTH_INTERVAL {
+            self.loss_scale *= 2.0;
+            self.steps_since_overflow = 0;
+            debug!(loss_scale = self.loss_scale, "Raised FP16 loss scale");
+        }
+    }
+
+    fn set_grad_kind(parameter: &Tensor, kind: Kind) {
+        let mut grad = parameter.grad();
+        if grad.defined() && grad.kind() != kind {
+            grad.set_data(&grad.to_kind(kind));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fp16_scaler() -> MixedPrecision {
+        MixedPrecision {
+            dtype: AmpDtype::FP16,
+            parameters: Vec::new(),
+            loss_scale: INITIAL_LOSS_SCALE,
+            steps_since_overflow: 0,
+        }
+    }
+
+    #[test]
+    fn test_unscale_gradients() {
+        let mut scaler = fp16_scaler();
+        let grads = Tensor::from_slice(&[2.0f32, -4.0]) * INITIAL_LOSS_SCALE;
+        scaler.unscale_gradients(&grads);
+        assert!(grads.allclose(&Tensor::from_slice(&[2.0f32, -4.0]), 1e-6, 1e-6, false));
+        assert_eq!(scaler.loss_scale(), Some(INITIAL_LOSS_SCALE));
+    }
+
+    #[test]
+    fn test_overflow_skips_step_and_lowers_scale() {
+        let mut scaler = fp16_scaler();
+        let grads = Tensor::from_slice(&[1.0f32, f32::INFINITY]);
+        scaler.unscale_gradients(&grads);
+        assert!(grads.allclose(
+            &Tensor::zeros([2], (Kind::Float, tch::Device::Cpu)),
+            0.0,
+            0.0,
+            false
+        ));
+        assert_eq!(scaler.loss_scale(), Some(INITIAL_LOSS_SCALE / 2.0));
+    }
+
+    #[test]
+    fn test_amp_dtype_from_str() {
+        assert_eq!("bf16".parse(), Ok(AmpDtype::BF16));
+        assert_eq!("FP16".parse(), Ok(AmpDtype::FP16));
+        assert!("fp8".parse::<AmpDtype>().is_err());
+    }
+}
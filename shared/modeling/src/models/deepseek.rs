@@ -2,9 +2,11 @@
 
 use crate::{
     AttentionImplementation, AutoConfig, CausalLanguageModel, ColumnParallelLinear, Communicator,
-    CommunicatorId, EosToks, LanguageModelConfig, LanguageModelForward, ModelLoadError,
-    ParallelExpandHeads, PretrainedSource, RMSNorm, RoPECache, RoPEConfig, RoPEType,
-    RowParallelLinear, rotate_half, yarn_get_mscale,
+    CommunicatorId, DocumentMasking, EosToks, LanguageModelConfig, LanguageModelForward,
+    ModelLoadError, ParallelExpandHeads, PretrainedSource, RMSNorm, RoPECache, RoPEConfig,
+    RoPEType, RowParallelLinear,
+    causal_language_model::{DecoderBlock, DecoderLayers},
+    rotate_half, yarn_get_mscale,
 };
 use std::fmt::Debug;
 use std::sync::Arc;
@@ -313,7 +315,7 @@ impl MLAAttention {
         &self,
         x: &Tensor,
         position_ids: Option<&Tensor>,
-        sequence_lengths: Option<&(Tensor, i32)>,
+        sequence_lengths: Option<&DocumentMasking>,
         cache: &RoPECache,
     ) -> Tensor {
         let (b, t, _) = x.size3().unwrap();
@@ -392,7 +394,12 @@ impl MLAAttention {
                 }
 
                 let (cum_seq, max_len) = match sequence_lengths {
-                    Some((cum_seq, max_len)) => (Some(cum_seq), *max_len as i64),
+                    Some(DocumentMasking::CuSeqlens(cum_seq, max_len)) => {
+                        (Some(cum_seq), *max_len as i64)
+                    }
+                    Some(DocumentMasking::Mask(_)) => {
+                        panic!("FlashAttention2 needs cumulative sequence lengths, not a mask")
+                    }
                     None => (None, t),
                 };
 
@@ -454,13 +461,15 @@ impl MLAAttention {
                     );
                 }
 
-                let att = Tensor::scaled_dot_product_attention::<Tensor>(
+                let mask = sequence_lengths.map(DocumentMasking::mask);
+                // the document mask is already causal
+                let att = Tensor::scaled_dot_product_attention(
                     &query_states,
                     &key_states,
                     &padded_value_states,
-                    None,
+                    mask,
                     0.0,
-                    true,
+                    mask.is_none(),
                     Some(self.softmax_scale),
                     false,
                 );
@@ -474,10 +483,14 @@ impl MLAAttention {
             }
             AttentionImplementation::Eager => {
                 let att = query_states.matmul(&key_states.transpose(-2, -1)) * self.softmax_scale;
-                let mask = Tensor::ones([t, t], (kind, self.device))
-                    .tril(0)
-                    .reshape([1, 1, t, t]);
-                let att = att.masked_fill(&mask.eq(0.), f64::NEG_INFINITY);
+                let masked = match sequence_lengths {
+                    Some(document_masking) => document_masking.mask().logical_not(),
+                    None => Tensor::ones([t, t], (kind, self.device))
+                        .tril(0)
+                        .reshape([1, 1, t, t])
+                        .eq(0.),
+                };
+                let att = att.masked_fill(&masked, f64::NEG_INFINITY);
                 att.softmax(-1, kind).matmul(&value_states).transpose(1, 2)
             }
         };
@@ -874,7 +887,7 @@ impl DeepseekBlock {
         &self,
         x: &Tensor,
         position_ids: Option<&Tensor>,
-        sequence_lengths: Option<&(Tensor, i32)>,
+        sequence_lengths: Option<&DocumentMasking>,
         cache: &RoPECache,
     ) -> Tensor {
         let residual = x;
@@ -894,6 +907,18 @@ impl DeepseekBlock {
     }
 }
 
+impl DecoderBlock for DeepseekBlock {
+    fn forward(
+        &self,
+        x: &Tensor,
+        position_ids: Option<&Tensor>,
+        sequence_lengths: Option<&DocumentMasking>,
+        cache: &RoPECache,
+    ) -> Tensor {
+        DeepseekBlock::forward(self, x, position_ids, sequence_lengths, cache)
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug)]
 pub struct Deepseek {
@@ -953,6 +978,18 @@ impl Deepseek {
     }
 }
 
+impl Deepseek {
+    fn layers(&self) -> DecoderLayers<'_, DeepseekBlock> {
+        DecoderLayers {
+            embed_tokens: &self.embed_tokens,
+            blocks: &self.blocks,
+            norm: &self.norm,
+            rope_cache: &self.rope_cache,
+            attn_implementation: self.attn_implementation,
+        }
+    }
+}
+
 impl LanguageModelForward for Deepseek {
     #[allow(unused_variables)]
     fn forward(
@@ -967,19 +1004,12 @@ impl LanguageModelForward for Deepseek {
         }
 
         let sequence_lengths = sequence_lengths.map(|sequence_lengths| {
-            #[cfg(feature = "parallelism")]
-            {
-                if self.attn_implementation == AttentionImplementation::FlashAttention2 {
-                    crate::attention::create_cu_seqlens(sequence_lengths, x.device())
-                } else {
-                    panic!("`sequence_lengths` only supported for FlashAttention2");
-                }
-            }
-
-            #[cfg(not(feature = "parallelism"))]
-            {
-                panic!("`sequence_lengths` only supported for FlashAttention2");
-            }
+            DocumentMasking::new(
+                sequence_lengths,
+                x.size()[1],
+                self.attn_implementation,
+                x.device(),
+            )
         });
 
         let mut hidden_states = self.embed_tokens.forward(x);
@@ -995,6 +1025,26 @@ impl LanguageModelForward for Deepseek {
 
         self.norm.forward(&hidden_states)
     }
+
+    fn checkpoint_segments(&self) -> usize {
+        self.layers().checkpoint_segments()
+    }
+
+    fn forward_segment(
+        &self,
+        x: &Tensor,
+        position_ids: Option<&Tensor>,
+        sequence_lengths: Option<&Vec<Vec<i32>>>,
+        training: bool,
+        segment: usize,
+    ) -> Tensor {
+        if let NetworkBlock::MoE(_) = &self.blocks[0].network {
+            assert!(!training, "DeepseekMoE training not yet supported");
+        }
+
+        self.layers()
+            .forward_segment(x, position_ids, sequence_lengths, segment)
+    }
 }
 
 pub type DeepseekForCausalLM = CausalLanguageModel<Deepseek, DeepseekConfig>;
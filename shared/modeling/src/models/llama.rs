@@ -1,8 +1,11 @@
 use crate::{
     AttentionImplementation, AutoConfig, CausalLanguageModel, CausalSelfAttention,
-    ColumnParallelLinear, CommunicatorId, EosToks, LanguageModelConfig, LanguageModelForward,
-    ModelLoadError, PretrainedSource, RMSNorm, RoPECache, RoPEConfig, RowParallelLinear,
-    default_rope, parallelism::Communicator,
+    ColumnParallelLinear, CommunicatorId, DocumentMasking, EosToks, LanguageModelConfig,
+    LanguageModelForward, ModelLoadError, PretrainedSource, RMSNorm, RoPECache, RoPEConfig,
+    RowParallelLinear,
+    causal_language_model::{DecoderBlock, DecoderLayers},
+    default_rope,
+    parallelism::Communicator,
 };
 use std::sync::Arc;
 use tch::{
@@ -157,7 +160,7 @@ impl Block {
         &self,
         x: &Tensor,
         position_ids: Option<&Tensor>,
-        sequence_lengths: Option<&(Tensor, i32)>,
+        sequence_lengths: Option<&DocumentMasking>,
         cache: &RoPECache,
     ) -> Tensor {
         let x = self.attn.forward(
@@ -170,6 +173,18 @@ impl Block {
     }
 }
 
+impl DecoderBlock for Block {
+    fn forward(
+        &self,
+        x: &Tensor,
+        position_ids: Option<&Tensor>,
+        sequence_lengths: Option<&DocumentMasking>,
+        cache: &RoPECache,
+    ) -> Tensor {
+        Block::forward(self, x, position_ids, sequence_lengths, cache)
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug)]
 pub struct Llama {
@@ -224,6 +239,18 @@ impl Llama {
     }
 }
 
+impl Llama {
+    fn layers(&self) -> DecoderLayers<'_, Block> {
+        DecoderLayers {
+            embed_tokens: &self.wte,
+            blocks: &self.blocks,
+            norm: &self.ln_f,
+            rope_cache: &self.rope_cache,
+            attn_implementation: self.attn_implementation,
+        }
+    }
+}
+
 impl LanguageModelForward for Llama {
     #[allow(unused_variables)]
     fn forward(
@@ -234,19 +261,12 @@ impl LanguageModelForward for Llama {
         _training: bool,
     ) -> Tensor {
         let sequence_lengths = sequence_lengths.map(|sequence_lengths| {
-            #[cfg(feature = "parallelism")]
-            {
-                if self.attn_implementation == AttentionImplementation::FlashAttention2 {
-                    crate::attention::create_cu_seqlens(sequence_lengths, x.device())
-                } else {
-                    panic!("`sequence_lengths` only supported for FlashAttention2");
-                }
-            }
-
-            #[cfg(not(feature = "parallelism"))]
-            {
-                panic!("`sequence_lengths` only supported for FlashAttention2");
-            }
+            DocumentMasking::new(
+                sequence_lengths,
+                x.size()[1],
+                self.attn_implementation,
+                x.device(),
+            )
         });
 
         let mut x = self.wte.forward(x);
@@ -260,6 +280,22 @@ impl LanguageModelForward for Llama {
         }
         self.ln_f.forward(&x)
     }
+
+    fn checkpoint_segments(&self) -> usize {
+        self.layers().checkpoint_segments()
+    }
+
+    fn forward_segment(
+        &self,
+        x: &Tensor,
+        position_ids: Option<&Tensor>,
+        sequence_lengths: Option<&Vec<Vec<i32>>>,
+        _training: bool,
+        segment: usize,
+    ) -> Tensor {
+        self.layers()
+            .forward_segment(x, position_ids, sequence_lengths, segment)
+    }
 }
 
 pub type LlamaForCausalLM = CausalLanguageModel<Llama, LlamaConfig>;
@@ -1,5 +1,5 @@
-use crate::{CausalLM, Distro};
-use psyche_core::OptimizerDefinition;
+use crate::{AdaptiveTopK, AggregationMode, CausalLM, Distro, DistroConfig};
+use psyche_core::{DistroExtensions, OptimizerDefinition};
 use tch::COptimizer;
 
 pub enum Optimizer {
@@ -16,7 +16,12 @@ pub enum Optimizer {
 }
 
 impl Optimizer {
-    pub fn new(definition: OptimizerDefinition, model: &dyn CausalLM) -> Self {
+    /// `extensions` only apply to [`OptimizerDefinition::Distro`].
+    pub fn new(
+        definition: OptimizerDefinition,
+        extensions: DistroExtensions,
+        model: &dyn CausalLM,
+    ) -> Self {
         match definition {
             OptimizerDefinition::AdamW {
                 betas,
@@ -52,10 +57,31 @@ impl Optimizer {
             } => Self::Distro {
                 optimizer: Distro::new(
                     model,
-                    compression_decay as f64,
-                    compression_chunk as i64,
-                    compression_topk as i64,
-                    weight_decay.unwrap_or(0.0) as f64,
+                    DistroConfig {
+                        compression_topk: match extensions.topk_retain_fraction > 0.0 {
+                            true => AdaptiveTopK::EnergyBased {
+                                retain_fraction: extensions.topk_retain_fraction as f64,
+                            },
+                            false => AdaptiveTopK::Fixed(compression_topk as i64),
+                        },
+                        layer_topk: extensions
+                            .layer_topk
+                            .iter()
+                            .map(|layer| (layer.pattern.to_string(), layer.topk as i64))
+                            .collect(),
+                        weight_decay: weight_decay.unwrap_or(0.0) as f64,
+                        max_grad_norm: (extensions.max_delta_norm > 0.0)
+                            .then_some(extensions.max_delta_norm as f64),
+                        aggregation: match extensions.sign_sgd {
+                            true => AggregationMode::SignSGD,
+                            false => AggregationMode::ValueAverage,
+                        },
+                        ..DistroConfig::new(
+                            compression_decay as f64,
+                            compression_chunk as i64,
+                            compression_topk as i64,
+                        )
+                    },
                 )
                 .into(),
                 clip_grad_norm,
@@ -505,7 +505,6 @@ pub fn tensor_shard(full_tensor: &Tensor, shard: &Shard) -> Tensor {
     full_tensor.slice(dim as i64, start, Some(end), 1)
 }
 
-#[allow(unused)]
 pub fn unsharded_tensor_size(reference_shape: &[i64], shard: &Shard) -> Vec<i64> {
     let Shard {
         dim, world_size, ..
@@ -285,12 +285,12 @@ impl CausalLM for PythonCausalLM {
         Box::new(self.order.clone())
     }
 
-    fn clip_grad_norm(&self, max_grad_norm: f64) {
+    fn clip_grad_norm(&self, max_grad_norm: f64) -> Option<f64> {
         assert!(
             self.pure_fsdp.unwrap_or(true),
             "Only pure FSDP supports `clip_grad_norm`"
         );
-        let result: PyResult<()> = Python::with_gil(|py| {
+        let result: PyResult<f64> = Python::with_gil(|py| {
             let module = py.import("torch.nn.utils")?;
             let clip_grad_norm = module.getattr("clip_grad_norm_")?;
             let tensors: Vec<_> = self
@@ -299,10 +299,10 @@ impl CausalLM for PythonCausalLM {
                 .iter()
                 .map(|x| x.python.clone_ref(py))
                 .collect();
-            clip_grad_norm.call1((tensors, max_grad_norm))?;
-            Ok(())
+            let total_norm = clip_grad_norm.call1((tensors, max_grad_norm))?;
+            total_norm.call_method0("item")?.extract()
         });
-        result.unwrap();
+        Some(result.unwrap())
     }
 
     fn bos_token_id(&self) -> Option<i64> {
@@ -638,8 +638,8 @@ impl CausalLM for WrappedPythonCausalLM {
         self.local.prepare_for_training();
     }
 
-    fn clip_grad_norm(&self, max_grad_norm: f64) {
-        self.local.clip_grad_norm(max_grad_norm);
+    fn clip_grad_norm(&self, max_grad_norm: f64) -> Option<f64> {
+        self.local.clip_grad_norm(max_grad_norm)
     }
 
     fn convert(&self, state_dict: Option<HashMap<String, Tensor>>) -> HashMap<String, Tensor> {
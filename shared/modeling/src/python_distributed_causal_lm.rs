@@ -506,8 +506,8 @@ impl CausalLM for PythonDistributedCausalLM {
         self.local.variables()
     }
 
-    fn clip_grad_norm(&self, max_grad_norm: f64) {
-        self.local.clip_grad_norm(max_grad_norm);
+    fn clip_grad_norm(&self, max_grad_norm: f64) -> Option<f64> {
+        self.local.clip_grad_norm(max_grad_norm)
     }
 
     fn bos_token_id(&self) -> Option<i64> {
@@ -1,11 +1,13 @@
 use crate::{
-    ApplyDistroResultError, Batch, BatchData, CausalLM, Communicator, EosToks, LocalTrainer,
-    ParallelModels, PythonDistributedCausalLM, ReduceType, StableVariableIterator,
+    ApplyDistroResultError, Batch, BatchData, CausalLM, Communicator, Distro, EosToks,
+    LocalTrainer, ParallelModels, PythonDistributedCausalLM, ReduceType, StableVariableIterator,
     TorchDistributedCommunicator, TrainOutput, Trainer, TrainerThreadCommunicationError,
     python_causal_lm::WrappedPythonCausalLM, trainer::DistroResults,
 };
 
-use psyche_core::{Barrier, CancelledBarrier, LearningRateSchedule, OptimizerDefinition};
+use psyche_core::{
+    Barrier, CancelledBarrier, DistroExtensions, LearningRateSchedule, OptimizerDefinition,
+};
 use pyo3::{PyErr, PyResult};
 use std::{
     collections::HashMap,
@@ -41,6 +43,9 @@ pub enum PythonDistributedTrainerError {
 
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
+
+    #[error("The Python ranks don't implement the run's DisTrO extensions")]
+    UnsupportedDistroExtensions,
 }
 
 #[derive(Debug)]
@@ -70,10 +75,13 @@ impl PythonDistributedTrainer {
     pub fn new(
         model: PythonDistributedCausalLM,
         lr_scheduler: LearningRateSchedule,
-        optimizer: OptimizerDefinition,
-        mut micro_batch_size: usize,
+        mut optimizer: OptimizerDefinition,
+        distro_extensions: DistroExtensions,
+        micro_batch_size: usize,
+        gradient_accumulation_steps: usize,
         stats: Option<u32>,
         grad_accum_in_fp32: bool,
+        gradient_clip_norm: Option<f64>,
     ) -> Result<Self, PythonDistributedTrainerError> {
         let comm = match model.communicator() {
             Some(comm) => match comm.as_ref() {
@@ -83,6 +91,13 @@ impl PythonDistributedTrainer {
             None => return Err(PythonDistributedTrainerError::WrongCommunicator),
         };
 
+        if distro_extensions != DistroExtensions::default() {
+            return Err(PythonDistributedTrainerError::UnsupportedDistroExtensions);
+        }
+
+        // the Python ranks accumulate gradients over every micro batch they split a batch into
+        let mut micro_batch_size = micro_batch_size / gradient_accumulation_steps;
+
         if model.parallelism.dp > 1 {
             debug!(
                 "Increasing micro batch size from {} to {} to account for FSDP sharding size of {}",
@@ -94,6 +109,17 @@ impl PythonDistributedTrainer {
             micro_batch_size *= model.parallelism.dp;
         }
 
+        // every rank takes part in clipping, so they all need to agree on the norm
+        if let Some(gradient_clip_norm) = gradient_clip_norm {
+            match &mut optimizer {
+                OptimizerDefinition::AdamW { clip_grad_norm, .. }
+                | OptimizerDefinition::Distro { clip_grad_norm, .. } => {
+                    *clip_grad_norm = Some(gradient_clip_norm as f32);
+                }
+                OptimizerDefinition::Dummy => {}
+            }
+        }
+
         let hyperparameters = serde_json::json!({
             "operation": "hyperparameters",
             "lr_scheduler": lr_scheduler,
@@ -125,9 +151,16 @@ impl PythonDistributedTrainer {
             },
             lr_scheduler,
             optimizer,
+            distro_extensions,
             micro_batch_size,
+            1,
             stats,
             grad_accum_in_fp32,
+            None,
+            gradient_clip_norm,
+            // the other ranks live in Python and don't keep averaged weights
+            None,
+            false,
         ));
 
         Ok(Self {
@@ -272,6 +305,14 @@ impl PythonDistributedTrainer {
     ) -> Result<Self, ApplyDistroResultError> {
         let _no_grad = tch::no_grad_guard();
 
+        // the Python ranks only receive the tensors, not the int8 scales, so hand everyone dequantized values
+        let distro_results = distro_results.map(|distro_results| {
+            distro_results
+                .iter()
+                .map(|results| Distro::dequantize_int8_results(results))
+                .collect::<Vec<_>>()
+        });
+
         let results_len = match &distro_results {
             // we assume (as we do else where) that each result is identically shaped
             Some(distro_results) => distro_results.len(),
@@ -394,6 +435,10 @@ impl PythonDistributedTrainer {
         })
     }
 
+    pub fn set_forward_on_ema_weights(&mut self, ema_weights: bool) {
+        self.local.set_forward_on_ema_weights(ema_weights);
+    }
+
     pub fn can_do_inference(&self) -> bool {
         self.local.can_do_inference()
     }
@@ -449,8 +494,8 @@ impl CausalLM for PythonDistributedTrainer {
         self.model.prepare_for_training();
     }
 
-    fn clip_grad_norm(&self, max_grad_norm: f64) {
-        self.model.clip_grad_norm(max_grad_norm);
+    fn clip_grad_norm(&self, max_grad_norm: f64) -> Option<f64> {
+        self.model.clip_grad_norm(max_grad_norm)
     }
 
     fn max_context_length(&self) -> usize {
@@ -1,10 +1,11 @@
 use crate::{
-    AllReduce, CausalLM, Communicator, CommunicatorId, CudaSynchronize, Distro, DistroResult,
-    EosToks, Fp32GradientAccumulator, Optimizer, ReduceType, StableVariableIterator,
+    AllReduce, AmpDtype, CausalLM, Communicator, CommunicatorId, CudaSynchronize, Distro,
+    DistroResult, EosToks, ExponentialMovingAverage, Fp32GradientAccumulator, LoadDistroStateError,
+    MixedPrecision, Optimizer, ReduceType, SPARSE_VAL_SCALE_STAT, StableVariableIterator,
     unsharded_cpu_variables,
 };
-use anyhow::{Error, Result};
-use psyche_core::{Barrier, BatchId, LearningRateSchedule, OptimizerDefinition};
+use anyhow::{Error, Result, bail};
+use psyche_core::{Barrier, BatchId, DistroExtensions, LearningRateSchedule, OptimizerDefinition};
 use std::{
     collections::HashMap,
     ops::ControlFlow,
@@ -38,6 +39,68 @@ pub struct BatchDataCPU {
     pub sequence_lengths: Option<Vec<i32>>,
 }
 
+impl BatchDataCPU {
+    /// Packs `sequences`, each followed by `eos_token_id`, into rows of `max_len` tokens
+    /// instead of padding every sequence to a row of its own.
+    /// Each row records the lengths of the documents in it and restarts its position ids at every
+    /// document, so that attention stays within a document. A document that doesn't fit in the
+    /// rest of a row carries on at the start of the next one as a new document.
+    /// The last row is padded with `eos_token_id`, labeled -100 so it's ignored by the loss, and the
+    /// padding counts as one more document so every row's lengths add up to `max_len`.
+    pub fn pack(sequences: Vec<Vec<i32>>, max_len: usize, eos_token_id: i32) -> Vec<Self> {
+        assert!(max_len > 0, "can't pack into empty rows");
+        let mut rows = Vec::new();
+        let mut input_ids = Vec::with_capacity(max_len);
+        let mut position_ids = Vec::with_capacity(max_len);
+        let mut sequence_lengths = Vec::new();
+        for mut document in sequences {
+            document.push(eos_token_id);
+            let mut remaining = document.as_slice();
+            while !remaining.is_empty() {
+                let len = remaining.len().min(max_len - input_ids.len());
+                input_ids.extend_from_slice(&remaining[..len]);
+                position_ids.extend(0..len as i32);
+                sequence_lengths.push(len as i32);
+                remaining = &remaining[len..];
+                if input_ids.len() == max_len {
+                    rows.push(Self {
+                        labels: Some(input_ids.clone()),
+                        input_ids: std::mem::replace(&mut input_ids, Vec::with_capacity(max_len)),
+                        position_ids: Some(std::mem::replace(
+                            &mut position_ids,
+                            Vec::with_capacity(max_len),
+                        )),
+                        sequence_lengths: Some(std::mem::take(&mut sequence_lengths)),
+                    });
+                }
+            }
+        }
+        if !input_ids.is_empty() {
+            let padding = max_len - input_ids.len();
+            let mut labels = input_ids.clone();
+            labels.resize(max_len, -100);
+            input_ids.resize(max_len, eos_token_id);
+            position_ids.extend(0..padding as i32);
+            sequence_lengths.push(padding as i32);
+            rows.push(Self {
+                input_ids,
+                labels: Some(labels),
+                position_ids: Some(position_ids),
+                sequence_lengths: Some(sequence_lengths),
+            });
+        }
+        for row in &rows {
+            let sequence_lengths = row.sequence_lengths.as_deref().unwrap_or_default();
+            assert_eq!(
+                sequence_lengths.iter().sum::<i32>() as usize,
+                max_len,
+                "packed row's document lengths don't cover it"
+            );
+        }
+        rows
+    }
+}
+
 #[derive(Debug)]
 pub struct BatchDataGPU {
     pub input_ids: Tensor,
@@ -228,6 +291,13 @@ impl Batch {
     }
 }
 
+/// Global gradient norm measured when clipping, before and after scaling.
+#[derive(Debug, Clone, Copy)]
+pub struct GradNorm {
+    pub before_clip: f64,
+    pub after_clip: f64,
+}
+
 pub struct TrainOutput {
     pub batch_id: BatchId,
     pub trainer: Trainer,
@@ -235,7 +305,10 @@ pub struct TrainOutput {
     pub step: u32,
     pub nonce: u32,
     pub distro_results: Option<DistroResults>,
+    pub grad_norm: Option<GradNorm>,
     pub cancelled: bool,
+    /// Tokens in the batch over the time spent training on it, including generating the DisTrO results
+    pub tokens_per_second: f64,
 }
 
 #[derive(Clone, Debug)]
@@ -269,9 +342,19 @@ enum ParallelAssignment {
         sequence_lengths: Option<Vec<Vec<i32>>>,
         num_logits_to_keep: Option<i64>,
         loss_scale: Option<f64>,
+        ema_weights: bool,
     },
     Extract,
+    ExtractOptimizerState,
+    LoadOptimizerState {
+        state: HashMap<String, Tensor>,
+    },
     TruncateBf16,
+    FindLr {
+        batch: Batch,
+        lr: f64,
+    },
+    FindLrRestore,
 }
 
 #[derive(Debug)]
@@ -281,6 +364,8 @@ enum ParallelResult {
         nonce: u32,
         cancelled: bool,
         distro_results: Option<DistroResults>,
+        grad_norm: Option<GradNorm>,
+        tokens_per_second: f64,
     },
     Optimize,
     Forward {
@@ -289,7 +374,17 @@ enum ParallelResult {
     Extract {
         variables: HashMap<String, Tensor>,
     },
+    ExtractOptimizerState {
+        state: Option<HashMap<String, Tensor>>,
+    },
+    LoadOptimizerState {
+        result: Result<(), LoadOptimizerStateError>,
+    },
     TruncateBf16,
+    FindLr {
+        loss: f32,
+    },
+    FindLrRestore,
 }
 
 #[derive(Debug)]
@@ -359,6 +454,28 @@ impl Trainer {
         }
     }
 
+    pub fn extract_optimizer_state(
+        &mut self,
+    ) -> Result<Option<HashMap<String, Tensor>>, TrainerThreadCommunicationError> {
+        match self {
+            Trainer::Local(local_trainer) => local_trainer.extract_optimizer_state(),
+            // the Python ranks keep their own optimizer state
+            #[cfg(feature = "python")]
+            Trainer::PythonDistributed(_) => Ok(None),
+        }
+    }
+
+    pub fn load_optimizer_state(
+        &mut self,
+        state: HashMap<String, Tensor>,
+    ) -> Result<(), LoadOptimizerStateError> {
+        match self {
+            Trainer::Local(local_trainer) => local_trainer.load_optimizer_state(state),
+            #[cfg(feature = "python")]
+            Trainer::PythonDistributed(_) => Err(LoadOptimizerStateError::Unsupported),
+        }
+    }
+
     pub fn truncate_bf16(&mut self) -> Result<(), TrainerThreadCommunicationError> {
         match self {
             Trainer::Local(local_trainer) => local_trainer.truncate_bf16(),
@@ -367,6 +484,18 @@ impl Trainer {
         }
     }
 
+    /// Whether forward passes (i.e. evals) run on the EMA weights, when the trainer keeps any.
+    /// Swapping them in and out copies every parameter, so only ask for them when needed.
+    pub fn set_forward_on_ema_weights(&mut self, ema_weights: bool) {
+        match self {
+            Trainer::Local(local_trainer) => local_trainer.set_forward_on_ema_weights(ema_weights),
+            #[cfg(feature = "python")]
+            Trainer::PythonDistributed(python_distributed_trainer) => {
+                python_distributed_trainer.set_forward_on_ema_weights(ema_weights)
+            }
+        }
+    }
+
     pub fn can_do_inference(&self) -> bool {
         match self {
             Trainer::Local(local_trainer) => local_trainer.can_do_inference(),
@@ -404,6 +533,26 @@ impl Trainer {
             })
             .collect()
     }
+
+    /// Quantizes each result's `sparse_val` to int8, recording its scale in `stats`
+    /// under [`crate::SPARSE_VAL_SCALE_STAT`].
+    pub fn quantize_results_int8(results: &DistroResults) -> DistroResults {
+        results
+            .iter()
+            .map(|x| {
+                let (sparse_val, scale) = Distro::quantize_tensor_to_int8(&x.sparse_val);
+                let mut stats = x.stats.clone().unwrap_or_default();
+                stats.insert(SPARSE_VAL_SCALE_STAT.to_string(), scale);
+                DistroResult {
+                    sparse_idx: x.sparse_idx.copy(),
+                    sparse_val,
+                    xshape: x.xshape.clone(),
+                    totalk: x.totalk,
+                    stats: Some(stats),
+                }
+            })
+            .collect()
+    }
 }
 
 impl From<LocalTrainer> for Trainer {
@@ -423,6 +572,7 @@ pub struct LocalTrainer {
     barrier: Arc<dyn Barrier>,
     data_parallel: Option<Vec<DataParallel>>,
     can_do_inferences: Vec<Arc<AtomicBool>>,
+    forward_on_ema_weights: bool,
 }
 
 #[derive(Debug, Error)]
@@ -444,15 +594,33 @@ pub enum TrainerThreadCommunicationError {
     PythonError(#[from] pyo3::PyErr),
 }
 
+#[derive(Debug, Error)]
+pub enum LoadOptimizerStateError {
+    #[error("{0}")]
+    Communication(#[from] TrainerThreadCommunicationError),
+
+    #[error("DisTrO state doesn't match the model: {0}")]
+    Distro(#[from] LoadDistroStateError),
+
+    #[error("This optimizer's state can't be restored")]
+    Unsupported,
+}
+
 impl LocalTrainer {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         models: ParallelModels,
         lr_scheduler: LearningRateSchedule,
         optimizer: OptimizerDefinition,
+        distro_extensions: DistroExtensions,
         micro_batch_size: usize,
+        gradient_accumulation_steps: usize,
         stats: Option<u32>,
         grad_accum_in_fp32: bool,
+        amp_dtype: Option<AmpDtype>,
+        gradient_clip_norm: Option<f64>,
+        ema_decay: Option<f64>,
+        activation_checkpointing: bool,
     ) -> Self {
         let ParallelModels {
             models,
@@ -461,6 +629,13 @@ impl LocalTrainer {
         } = models;
 
         assert!(!models.is_empty());
+        assert!(
+            gradient_accumulation_steps > 0 && micro_batch_size % gradient_accumulation_steps == 0,
+            "micro batch size {micro_batch_size} can't be split into {gradient_accumulation_steps} gradient accumulation steps"
+        );
+        // the model threads accumulate gradients over every forward/backward pass of a batch,
+        // so splitting each micro batch into smaller passes is all accumulation takes
+        let micro_batch_size = micro_batch_size / gradient_accumulation_steps;
         let first_model_device = models[0].device();
         let first_model_max_context_length = models[0].max_context_length();
 
@@ -484,8 +659,6 @@ impl LocalTrainer {
             let (result_tx, result_rx) = flume::unbounded();
             ret.push((assignment_tx, result_rx));
 
-            let optimizer = Optimizer::new(optimizer, model.as_ref());
-
             let barrier = barrier.clone();
             let data_parallel = data_parallel.clone();
             let can_do_inference = Arc::new(AtomicBool::new(false));
@@ -497,12 +670,17 @@ impl LocalTrainer {
                     assignment_rx,
                     result_tx,
                     optimizer,
+                    distro_extensions,
                     index,
                     micro_batch_size,
                     lr_scheduler,
                     barrier,
                     stats,
                     grad_accum_in_fp32,
+                    amp_dtype,
+                    gradient_clip_norm,
+                    ema_decay,
+                    activation_checkpointing,
                     data_parallel,
                     can_do_inference,
                 )
@@ -516,9 +694,11 @@ impl LocalTrainer {
             barrier,
             data_parallel,
             can_do_inferences,
+            forward_on_ema_weights: false,
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn forward_backward(
         model: &mut dyn CausalLM,
         inputs: Tensor,
@@ -527,28 +707,98 @@ impl LocalTrainer {
         sequence_lengths: Option<Vec<Vec<i32>>>,
         barrier: &Arc<dyn Barrier>,
         loss_scale: Option<f64>,
+        activation_checkpointing: bool,
     ) -> Result<Option<Tensor>> {
         let labels = labels.unwrap_or_else(|| inputs.copy());
         if barrier.wait().is_err() {
             return Ok(None);
         }
         let device = inputs.device();
-        let (_, loss) = model.forward(
-            &inputs,
-            Some(&labels),
-            position_ids.as_ref(),
-            sequence_lengths.as_ref(),
-            None,
-            loss_scale,
-        );
-        let loss = loss.ok_or(Error::msg("No loss"))?;
-        loss.backward();
+        let loss = match activation_checkpointing {
+            true => Self::checkpointed_forward_backward(
+                model,
+                &inputs,
+                &labels,
+                position_ids.as_ref(),
+                sequence_lengths.as_ref(),
+                loss_scale,
+            ),
+            false => {
+                let (_, loss) = model.forward(
+                    &inputs,
+                    Some(&labels),
+                    position_ids.as_ref(),
+                    sequence_lengths.as_ref(),
+                    None,
+                    loss_scale,
+                );
+                let loss = loss.ok_or(Error::msg("No loss"))?;
+                loss.backward();
+                loss
+            }
+        };
         if device.is_cuda() {
             device.cuda_synchronize();
         }
         Ok(Some(loss.detach()))
     }
 
+    /// Like `torch.utils.checkpoint.checkpoint_sequential`: the forward pass only keeps the activations
+    /// at the boundaries of the model's [`CausalLM::checkpoint_segments`], and the backward pass
+    /// recomputes each segment's activations right before backpropagating through it.
+    fn checkpointed_forward_backward(
+        model: &dyn CausalLM,
+        inputs: &Tensor,
+        labels: &Tensor,
+        position_ids: Option<&Tensor>,
+        sequence_lengths: Option<&Vec<Vec<i32>>>,
+        loss_scale: Option<f64>,
+    ) -> Tensor {
+        let num_segments = model.checkpoint_segments().max(1);
+        let segment_forward = |x: &Tensor, segment: usize| {
+            model.forward_segment(
+                x,
+                labels,
+                position_ids,
+                sequence_lengths,
+                loss_scale,
+                segment,
+            )
+        };
+
+        let mut segment_inputs = Vec::with_capacity(num_segments);
+        segment_inputs.push(inputs.shallow_clone());
+        {
+            let _no_grad = tch::no_grad_guard();
+            for segment in 0..num_segments - 1 {
+                let output = segment_forward(segment_inputs.last().unwrap(), segment);
+                segment_inputs.push(output);
+            }
+        }
+
+        let mut loss = None;
+        let mut grad_output: Option<Tensor> = None;
+        for (segment, input) in segment_inputs.into_iter().enumerate().rev() {
+            // the first segment's input is token ids, there's nothing to backpropagate into
+            let input = match segment {
+                0 => input,
+                _ => input.detach().set_requires_grad(true),
+            };
+            let output = segment_forward(&input, segment);
+            match grad_output.take() {
+                Some(grad_output) => (output * grad_output).sum(Kind::Float).backward(),
+                None => {
+                    output.backward();
+                    loss = Some(output);
+                }
+            }
+            if segment > 0 {
+                grad_output = Some(input.grad());
+            }
+        }
+        loss.expect("last segment returns the loss")
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn forward(
         model: &mut dyn CausalLM,
@@ -615,8 +865,10 @@ impl LocalTrainer {
 
         let mut final_loss = 0.0;
         let mut final_distro_results = None;
+        let mut final_grad_norm = None;
         let mut final_cancelled = false;
         let mut final_nonce = 0;
+        let mut final_tokens_per_second = 0.0;
         for (_, rx) in &self.models {
             match rx
                 .recv()
@@ -625,12 +877,18 @@ impl LocalTrainer {
                 ParallelResult::Train {
                     loss,
                     distro_results,
+                    grad_norm,
                     cancelled,
                     nonce,
+                    tokens_per_second,
                 } => {
                     if final_distro_results.is_none() {
                         final_distro_results = distro_results;
                         final_nonce = nonce;
+                        final_tokens_per_second = tokens_per_second;
+                    }
+                    if final_grad_norm.is_none() {
+                        final_grad_norm = grad_norm;
                     }
                     final_cancelled = cancelled;
                     final_loss += loss;
@@ -649,8 +907,10 @@ impl LocalTrainer {
             loss: final_loss,
             step,
             distro_results: final_distro_results,
+            grad_norm: final_grad_norm,
             cancelled: final_cancelled,
             nonce: final_nonce,
+            tokens_per_second: final_tokens_per_second,
         })
     }
 
@@ -715,6 +975,76 @@ impl LocalTrainer {
         Ok(extracted)
     }
 
+    /// The optimizer's own state (DisTrO's error feedback), unsharded and on the CPU.
+    /// `None` if the optimizer's state can't be extracted.
+    pub fn extract_optimizer_state(
+        &mut self,
+    ) -> Result<Option<HashMap<String, Tensor>>, TrainerThreadCommunicationError> {
+        self.barrier.reset();
+        for (tx, _) in &self.models {
+            tx.send(ParallelAssignment::ExtractOptimizerState)
+                .map_err(|_| TrainerThreadCommunicationError::SendCommand)?;
+        }
+        let mut extracted = Some(HashMap::new());
+        for (_, rx) in &self.models {
+            match rx
+                .recv()
+                .map_err(|_| TrainerThreadCommunicationError::RecvResult)?
+            {
+                ParallelResult::ExtractOptimizerState { state } => {
+                    // only rank zero gets the tensors back, and there's no state if any rank can't extract it
+                    extracted = match (extracted, state) {
+                        (Some(current), Some(state)) if current.is_empty() => Some(state),
+                        (_, None) => None,
+                        (current, _) => current,
+                    };
+                }
+                result => {
+                    return Err(TrainerThreadCommunicationError::UnexpectedResult(format!(
+                        "{result:?}"
+                    )));
+                }
+            }
+        }
+        Ok(extracted)
+    }
+
+    /// Restores state saved by [`Self::extract_optimizer_state`] into every rank's optimizer.
+    pub fn load_optimizer_state(
+        &mut self,
+        state: HashMap<String, Tensor>,
+    ) -> Result<(), LoadOptimizerStateError> {
+        self.barrier.reset();
+        for (tx, _) in &self.models {
+            let state = state
+                .iter()
+                .map(|(name, tensor)| (name.clone(), tensor.shallow_clone()))
+                .collect();
+            tx.send(ParallelAssignment::LoadOptimizerState { state })
+                .map_err(|_| TrainerThreadCommunicationError::SendCommand)?;
+        }
+        let mut ret = Ok(());
+        for (_, rx) in &self.models {
+            match rx
+                .recv()
+                .map_err(|_| TrainerThreadCommunicationError::RecvResult)?
+            {
+                ParallelResult::LoadOptimizerState { result } => {
+                    if ret.is_ok() {
+                        ret = result;
+                    }
+                }
+                result => {
+                    return Err(TrainerThreadCommunicationError::UnexpectedResult(format!(
+                        "{result:?}"
+                    ))
+                    .into());
+                }
+            }
+        }
+        ret
+    }
+
     pub fn truncate_bf16(&mut self) -> Result<(), TrainerThreadCommunicationError> {
         self.barrier.reset();
         for (tx, _) in &self.models {
@@ -737,19 +1067,107 @@ impl LocalTrainer {
         Ok(())
     }
 
+    /// Learning rate range test: trains one step on each of `batches` (cycling through them if there
+    /// are fewer than `num_steps`) with the learning rate going from `min_lr` to `max_lr` in `num_steps`
+    /// log-spaced steps, returning each step's learning rate and loss.
+    /// Stops early, with the steps done so far, once `deadline` has passed.
+    /// The model's parameters are restored afterwards, and the optimizer is started over, so this
+    /// is meant to run before training.
+    pub fn find_lr(
+        &mut self,
+        batches: Vec<Batch>,
+        min_lr: f64,
+        max_lr: f64,
+        num_steps: usize,
+        deadline: Option<Instant>,
+    ) -> Result<Vec<(f64, f32)>> {
+        if batches.is_empty() {
+            bail!("no batches to find a learning rate with");
+        }
+        if !(min_lr > 0.0 && max_lr >= min_lr) {
+            bail!("learning rate range must be positive and increasing, got {min_lr} to {max_lr}");
+        }
+        let lrs = (0..num_steps).map(|step| match num_steps {
+            1 => min_lr,
+            _ => min_lr * (max_lr / min_lr).powf(step as f64 / (num_steps - 1) as f64),
+        });
+
+        let mut results = Vec::with_capacity(num_steps);
+        for (lr, batch) in lrs.zip(batches.iter().cycle()) {
+            // checked here rather than on the model threads, so every rank stops at the same step
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                warn!(
+                    "Learning rate sweep ran out of time after {} of {num_steps} steps",
+                    results.len()
+                );
+                break;
+            }
+            self.barrier.reset();
+            for (tx, _) in &self.models {
+                tx.send(ParallelAssignment::FindLr {
+                    batch: batch.clone(),
+                    lr,
+                })
+                .map_err(|_| TrainerThreadCommunicationError::SendCommand)?;
+            }
+            let mut total_loss = 0.0;
+            for (_, rx) in &self.models {
+                match rx
+                    .recv()
+                    .map_err(|_| TrainerThreadCommunicationError::RecvResult)?
+                {
+                    ParallelResult::FindLr { loss } => total_loss += loss,
+                    result => {
+                        return Err(TrainerThreadCommunicationError::UnexpectedResult(format!(
+                            "{result:?}"
+                        ))
+                        .into());
+                    }
+                }
+            }
+            results.push((lr, total_loss / self.models.len() as f32));
+        }
+
+        self.barrier.reset();
+        for (tx, _) in &self.models {
+            tx.send(ParallelAssignment::FindLrRestore)
+                .map_err(|_| TrainerThreadCommunicationError::SendCommand)?;
+        }
+        for (_, rx) in &self.models {
+            match rx
+                .recv()
+                .map_err(|_| TrainerThreadCommunicationError::RecvResult)?
+            {
+                ParallelResult::FindLrRestore => {}
+                result => {
+                    return Err(TrainerThreadCommunicationError::UnexpectedResult(format!(
+                        "{result:?}"
+                    ))
+                    .into());
+                }
+            }
+        }
+        Ok(results)
+    }
+
     // todo: refactor args into a struct
     #[allow(clippy::too_many_arguments)]
     fn model_thread(
         mut model: Box<dyn CausalLM>,
         assignment: flume::Receiver<ParallelAssignment>,
         submission: flume::Sender<ParallelResult>,
-        mut optimizer: Optimizer,
+        optimizer_definition: OptimizerDefinition,
+        distro_extensions: DistroExtensions,
         index: usize,
         micro_batch_size: usize,
         lr_scheduler: LearningRateSchedule,
         barrier: Arc<dyn Barrier>,
         optim_stats_every_n_steps: Option<u32>,
         grad_accum_in_fp32: bool,
+        amp_dtype: Option<AmpDtype>,
+        gradient_clip_norm: Option<f64>,
+        ema_decay: Option<f64>,
+        activation_checkpointing: bool,
         data_parallel_def: Option<DataParallel>,
         can_do_inference: Arc<AtomicBool>,
     ) {
@@ -794,10 +1212,45 @@ impl LocalTrainer {
         }
         model.prepare_for_training();
 
+        let new_optimizer = |model: &dyn CausalLM| {
+            let mut optimizer = Optimizer::new(optimizer_definition, distro_extensions, model);
+            // a locally configured clip norm takes precedence over the one in the run's optimizer definition
+            if let Some(gradient_clip_norm) = gradient_clip_norm {
+                match &mut optimizer {
+                    Optimizer::Torch { clip_grad_norm, .. }
+                    | Optimizer::Distro { clip_grad_norm, .. } => {
+                        *clip_grad_norm = Some(gradient_clip_norm as f32);
+                    }
+                    Optimizer::Null => {}
+                }
+            }
+            optimizer
+        };
+        let mut optimizer = new_optimizer(model.as_ref());
+
         let mut grad_accum: Option<Fp32GradientAccumulator> = None;
+        let mut mixed_precision = amp_dtype.map(|dtype| MixedPrecision::new(model.as_ref(), dtype));
+        // the model's own weights stay loaded unless a forward pass asks for the averaged ones
+        let mut ema = ema_decay.map(|decay| ExponentialMovingAverage::new(model.as_ref(), decay));
         let mut nonce = 0;
+        // the parameters from before a learning rate sweep, while one is running
+        let mut find_lr_parameters: Option<Vec<Tensor>> = None;
         loop {
-            match assignment.recv() {
+            let next = assignment.recv();
+            if let Some(ema) = &mut ema {
+                match &next {
+                    Ok(ParallelAssignment::Forward {
+                        ema_weights: true, ..
+                    }) => ema.use_ema_weights(),
+                    // the optimizer's state doesn't depend on which weights are loaded
+                    Ok(
+                        ParallelAssignment::ExtractOptimizerState
+                        | ParallelAssignment::LoadOptimizerState { .. },
+                    ) => {}
+                    _ => ema.use_model_weights(),
+                }
+            }
+            match next {
                 Ok(ParallelAssignment::Train {
                     batch,
                     step,
@@ -821,65 +1274,29 @@ impl LocalTrainer {
 
                     debug!(batch_id=%batch.id, "model thread training on batch {}", batch.id);
 
+                    let train_start = Instant::now();
                     let batch_size = batch.data.size();
 
-                    let mut grad_accum_steps = batch_size / micro_batch_size;
-                    if batch_size % micro_batch_size != 0 {
-                        grad_accum_steps += 1;
-                    }
-                    if grad_accum_in_fp32 && grad_accum_steps != 1 && grad_accum.is_none() {
+                    let grad_accum_steps = grad_accum_steps(batch_size, micro_batch_size);
+                    // mixed precision always accumulates into FP32, that's where the master weights' gradients live
+                    let fp32_grads =
+                        (grad_accum_in_fp32 && grad_accum_steps != 1) || mixed_precision.is_some();
+                    if fp32_grads && grad_accum.is_none() {
                         debug!("Allocating FP32 gradient accumulator");
                         grad_accum = Some(Fp32GradientAccumulator::new(model.as_ref()))
                     }
                     let grad_accum_divisor = grad_accum_steps as f64;
 
-                    // collate data for batch
-                    let BatchDataGPU {
-                        input_ids,
-                        labels,
-                        position_ids,
-                        sequence_lengths,
-                    } = batch.data.gpu(model.device());
-                    // note: torch chunk argument is total number of chunks,
-                    // rust iter chunk is number of elements per chunk
-                    let input_ids = input_ids.chunk(grad_accum_steps as i64, 0);
-                    let labels = labels
-                        .map(|x| {
-                            x.chunk(grad_accum_steps as i64, 0)
-                                .into_iter()
-                                .map(Some)
-                                .collect::<Vec<_>>()
-                        })
-                        .unwrap_or_else(|| {
-                            std::iter::from_fn(|| Some(None))
-                                .take(grad_accum_steps)
-                                .collect()
-                        });
-                    let position_ids = position_ids
-                        .map(|x| {
-                            x.chunk(grad_accum_steps as i64, 0)
-                                .into_iter()
-                                .map(Some)
-                                .collect::<Vec<_>>()
-                        })
-                        .unwrap_or_else(|| {
-                            std::iter::from_fn(|| Some(None))
-                                .take(grad_accum_steps)
-                                .collect()
-                        });
-                    let sequence_lengths = sequence_lengths
-                        .map(|x| {
-                            x.chunks(micro_batch_size)
-                                .map(|y| Some(y.to_vec()))
-                                .collect::<Vec<_>>()
-                        })
-                        .unwrap_or_else(|| vec![None; grad_accum_steps]);
-                    assert_eq!(input_ids.len(), grad_accum_steps);
-                    assert_eq!(labels.len(), grad_accum_steps);
-                    assert_eq!(position_ids.len(), grad_accum_steps);
-                    assert_eq!(sequence_lengths.len(), grad_accum_steps);
-                    let micro_batches =
-                        itertools::izip!(input_ids, labels, position_ids, sequence_lengths);
+                    let micro_batches = split_micro_batches(
+                        batch,
+                        grad_accum_steps,
+                        micro_batch_size,
+                        model.device(),
+                    );
+                    let sequence_length = micro_batches
+                        .first()
+                        .map(|(input_ids, ..)| input_ids.size()[1] as usize)
+                        .unwrap_or_default();
 
                     if let Some(grad_accum) = &mut grad_accum {
                         grad_accum.zero_grad();
@@ -927,6 +1344,11 @@ impl LocalTrainer {
                         Optimizer::Null => {}
                     };
 
+                    let loss_scale = mixed_precision.as_ref().and_then(|x| x.loss_scale());
+                    if let Some(mixed_precision) = &mut mixed_precision {
+                        mixed_precision.use_low_precision_weights();
+                    }
+
                     let mut loss = None;
                     let mut cancelled = false;
                     for (index, (input_ids, labels, position_ids, sequence_lengths)) in
@@ -945,7 +1367,8 @@ impl LocalTrainer {
                             position_ids,
                             sequence_lengths,
                             &barrier,
-                            Some(grad_accum_divisor),
+                            Some(grad_accum_divisor / loss_scale.unwrap_or(1.0)),
+                            activation_checkpointing,
                         ) {
                             Ok(Some(batch_loss)) => {
                                 if batch_loss.double_value(&[]).is_finite() {
@@ -973,8 +1396,11 @@ impl LocalTrainer {
                         }
                         trace!(micro_batch = index, "Finished micro batch forward/backward");
                     }
-                    if let Some(grad_accum) = &mut grad_accum {
-                        grad_accum.apply_accumulation();
+                    if let Some(mixed_precision) = &mut mixed_precision {
+                        mixed_precision.use_master_weights();
+                    }
+                    if let (Some(loss), Some(loss_scale)) = (loss.as_mut(), loss_scale) {
+                        let _t = loss.g_div_scalar_(loss_scale);
                     }
 
                     // reduce grads across DP ranks
@@ -1000,7 +1426,17 @@ impl LocalTrainer {
                         }
                         dp_barrier.wait().unwrap(); // cannot cancel dp
                     }
+                    // after the DP reduce, so every rank makes the same overflow call
+                    if let (Some(mixed_precision), Some(grad_accum)) =
+                        (&mut mixed_precision, &grad_accum)
+                    {
+                        mixed_precision.unscale_gradients(grad_accum.get_full_grad_buffer());
+                    }
+                    if let Some(grad_accum) = &mut grad_accum {
+                        grad_accum.apply_accumulation();
+                    }
 
+                    let mut grad_norm = None;
                     let distro_results = match cancelled {
                         false => match &mut optimizer {
                             Optimizer::Torch {
@@ -1016,7 +1452,10 @@ impl LocalTrainer {
                                     Some(clip_grad_norm) => match barrier.wait() {
                                         Ok(_) => {
                                             if *clip_grad_norm > 0. {
-                                                model.clip_grad_norm(*clip_grad_norm as f64);
+                                                grad_norm = clip_gradients(
+                                                    model.as_ref(),
+                                                    *clip_grad_norm as f64,
+                                                );
                                             }
                                             barrier.wait().is_ok()
                                         }
@@ -1050,6 +1489,8 @@ impl LocalTrainer {
                     };
                     // with Python FSDP we need to do a full forward/backward correctly before we can do inference
                     can_do_inference.store(true, Ordering::Relaxed);
+                    let tokens_per_second =
+                        (batch_size * sequence_length) as f64 / train_start.elapsed().as_secs_f64();
                     if submission
                         .send(ParallelResult::Train {
                             loss: match loss {
@@ -1057,8 +1498,10 @@ impl LocalTrainer {
                                 None => 0.,
                             },
                             distro_results,
+                            grad_norm,
                             cancelled,
                             nonce,
+                            tokens_per_second,
                         })
                         .is_err()
                     {
@@ -1094,6 +1537,9 @@ impl LocalTrainer {
                     {
                         return;
                     }
+                    if let Some(ema) = &mut ema {
+                        ema.update();
+                    }
                     if submission.send(ParallelResult::Optimize).is_err() {
                         return;
                     }
@@ -1105,6 +1551,7 @@ impl LocalTrainer {
                     sequence_lengths,
                     num_logits_to_keep,
                     loss_scale,
+                    ema_weights: _,
                 }) => {
                     if !can_do_inference.load(Ordering::Relaxed) {
                         error!("This model not set up for inference, but got inference request");
@@ -1129,7 +1576,10 @@ impl LocalTrainer {
                 }
                 Ok(ParallelAssignment::Extract) => {
                     match unsharded_cpu_variables(model.as_ref(), model.communicator()) {
-                        Ok(variables) => {
+                        Ok(mut variables) => {
+                            if let Some(ema) = &mut ema {
+                                variables.extend(ema.unsharded_cpu_variables(model.communicator()));
+                            }
                             if submission
                                 .send(ParallelResult::Extract { variables })
                                 .is_err()
@@ -1143,6 +1593,37 @@ impl LocalTrainer {
                         }
                     }
                 }
+                Ok(ParallelAssignment::ExtractOptimizerState) => {
+                    let state = match &optimizer {
+                        Optimizer::Distro { optimizer, .. } => {
+                            Some(optimizer.unsharded_cpu_state(model.communicator()))
+                        }
+                        // torch doesn't expose AdamW's moments
+                        Optimizer::Torch { .. } => None,
+                        Optimizer::Null => Some(HashMap::new()),
+                    };
+                    if submission
+                        .send(ParallelResult::ExtractOptimizerState { state })
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+                Ok(ParallelAssignment::LoadOptimizerState { state }) => {
+                    let result = match &mut optimizer {
+                        Optimizer::Distro { optimizer, .. } => {
+                            optimizer.load_state(&state).map_err(Into::into)
+                        }
+                        Optimizer::Torch { .. } => Err(LoadOptimizerStateError::Unsupported),
+                        Optimizer::Null => Ok(()),
+                    };
+                    if submission
+                        .send(ParallelResult::LoadOptimizerState { result })
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
                 Ok(ParallelAssignment::TruncateBf16) => {
                     let _no_grad = tch::no_grad_guard();
                     for var in model.variables() {
@@ -1155,6 +1636,96 @@ impl LocalTrainer {
                         return;
                     }
                 }
+                Ok(ParallelAssignment::FindLr { batch, lr }) => {
+                    // the first step of a sweep keeps the parameters for FindLrRestore,
+                    // on the CPU so the sweep doesn't need twice the GPU memory
+                    if find_lr_parameters.is_none() {
+                        let _no_grad = tch::no_grad_guard();
+                        find_lr_parameters = Some(
+                            model
+                                .variables()
+                                .map(|var| {
+                                    let tensor = var.local_tensor();
+                                    let mut copy =
+                                        Tensor::empty(tensor.size(), (tensor.kind(), Device::Cpu));
+                                    copy.copy_(&tensor);
+                                    copy
+                                })
+                                .collect::<Vec<_>>(),
+                        );
+                    }
+                    for var in model.variables() {
+                        var.zero_grad();
+                    }
+                    let grad_accum_steps = grad_accum_steps(batch.data.size(), micro_batch_size);
+                    let mut loss = 0.0;
+                    for (input_ids, labels, position_ids, sequence_lengths) in split_micro_batches(
+                        batch,
+                        grad_accum_steps,
+                        micro_batch_size,
+                        model.device(),
+                    ) {
+                        match Self::forward_backward(
+                            &mut *model,
+                            input_ids,
+                            labels,
+                            position_ids,
+                            sequence_lengths,
+                            &barrier,
+                            Some(grad_accum_steps as f64),
+                            activation_checkpointing,
+                        ) {
+                            Ok(Some(batch_loss)) => {
+                                loss += f32::try_from(batch_loss).unwrap_or(f32::NAN)
+                            }
+                            Ok(None) => return,
+                            Err(err) => {
+                                error!("Learning rate sweep error: {err:#}");
+                                return;
+                            }
+                        }
+                    }
+                    // a DisTrO step with only our own results, as if we were the only client
+                    let own_results = match &mut optimizer {
+                        Optimizer::Distro { optimizer, .. } => Some(vec![optimizer.generate(
+                            model.as_ref(),
+                            &[],
+                            0.0,
+                            lr,
+                            false,
+                        )]),
+                        _ => None,
+                    };
+                    if optimize_step(
+                        &mut model,
+                        lr,
+                        &mut optimizer,
+                        own_results.as_ref(),
+                        &barrier,
+                    )
+                    .is_break()
+                    {
+                        return;
+                    }
+                    if submission.send(ParallelResult::FindLr { loss }).is_err() {
+                        return;
+                    }
+                }
+                Ok(ParallelAssignment::FindLrRestore) => {
+                    if let Some(original_parameters) = find_lr_parameters.take() {
+                        let _no_grad = tch::no_grad_guard();
+                        for (var, original) in model.variables().zip(original_parameters) {
+                            var.local_tensor().copy_(&original);
+                            var.zero_grad();
+                        }
+                    }
+                    // the sweep's steps went into the optimizer's state too (AdamW's moments
+                    // can't be copied out), so start it over
+                    optimizer = new_optimizer(model.as_ref());
+                    if submission.send(ParallelResult::FindLrRestore).is_err() {
+                        return;
+                    }
+                }
                 Err(_) => {
                     return;
                 }
@@ -1181,6 +1752,10 @@ impl LocalTrainer {
             .unwrap_or(1)
     }
 
+    pub fn set_forward_on_ema_weights(&mut self, ema_weights: bool) {
+        self.forward_on_ema_weights = ema_weights;
+    }
+
     pub fn can_do_inference(&self) -> bool {
         for can in &self.can_do_inferences {
             if !can.load(Ordering::Relaxed) {
@@ -1229,6 +1804,7 @@ impl CausalLM for LocalTrainer {
                 sequence_lengths: sequence_lengths.cloned(),
                 num_logits_to_keep,
                 loss_scale,
+                ema_weights: self.forward_on_ema_weights,
             })
             .expect("Error getting result from forward");
         }
@@ -1272,13 +1848,101 @@ impl CausalLM for LocalTrainer {
 
     fn prepare_for_training(&self) {}
 
-    fn clip_grad_norm(&self, _max_grad_norm: f64) {}
+    fn clip_grad_norm(&self, _max_grad_norm: f64) -> Option<f64> {
+        None
+    }
 
     fn convert(&self, _state_dict: Option<HashMap<String, Tensor>>) -> HashMap<String, Tensor> {
         unimplemented!();
     }
 }
 
+fn clip_gradients(model: &dyn CausalLM, max_norm: f64) -> Option<GradNorm> {
+    let before_clip = model.clip_grad_norm(max_norm)?;
+    // mirrors the scaling applied by `clip_grad_norm`
+    let after_clip = match before_clip > max_norm {
+        true => before_clip * max_norm / (before_clip + 1e-6),
+        false => before_clip,
+    };
+    trace!(
+        before_clip = before_clip,
+        after_clip = after_clip,
+        max_norm = max_norm,
+        "Clipped gradients"
+    );
+    Some(GradNorm {
+        before_clip,
+        after_clip,
+    })
+}
+
+type MicroBatch = (
+    Tensor,
+    Option<Tensor>,
+    Option<Tensor>,
+    Option<Vec<Vec<i32>>>,
+);
+
+/// Number of micro batches [`split_micro_batches`] splits a batch of `batch_size` samples into.
+fn grad_accum_steps(batch_size: usize, micro_batch_size: usize) -> usize {
+    batch_size.div_ceil(micro_batch_size)
+}
+
+/// Splits `batch` into `grad_accum_steps` micro batches of (input ids, labels, position ids, sequence lengths) on `device`.
+fn split_micro_batches(
+    batch: Batch,
+    grad_accum_steps: usize,
+    micro_batch_size: usize,
+    device: Device,
+) -> Vec<MicroBatch> {
+    // collate data for batch
+    let BatchDataGPU {
+        input_ids,
+        labels,
+        position_ids,
+        sequence_lengths,
+    } = batch.data.gpu(device);
+    // note: torch chunk argument is total number of chunks,
+    // rust iter chunk is number of elements per chunk
+    let input_ids = input_ids.chunk(grad_accum_steps as i64, 0);
+    let labels = labels
+        .map(|x| {
+            x.chunk(grad_accum_steps as i64, 0)
+                .into_iter()
+                .map(Some)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_else(|| {
+            std::iter::from_fn(|| Some(None))
+                .take(grad_accum_steps)
+                .collect()
+        });
+    let position_ids = position_ids
+        .map(|x| {
+            x.chunk(grad_accum_steps as i64, 0)
+                .into_iter()
+                .map(Some)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_else(|| {
+            std::iter::from_fn(|| Some(None))
+                .take(grad_accum_steps)
+                .collect()
+        });
+    let sequence_lengths = sequence_lengths
+        .map(|x| {
+            x.chunks(micro_batch_size)
+                .map(|y| Some(y.to_vec()))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_else(|| vec![None; grad_accum_steps]);
+    assert_eq!(input_ids.len(), grad_accum_steps);
+    assert_eq!(labels.len(), grad_accum_steps);
+    assert_eq!(position_ids.len(), grad_accum_steps);
+    assert_eq!(sequence_lengths.len(), grad_accum_steps);
+    itertools::izip!(input_ids, labels, position_ids, sequence_lengths).collect()
+}
+
 fn optimize_step(
     model: &mut Box<dyn CausalLM>,
     lr: f64,
@@ -1297,7 +1961,7 @@ fn optimize_step(
                     return ControlFlow::Break(());
                 }
                 if *clip_grad_norm > 0. {
-                    model.clip_grad_norm(*clip_grad_norm as f64);
+                    clip_gradients(model.as_ref(), *clip_grad_norm as f64);
                 }
                 if barrier.wait().is_err() {
                     return ControlFlow::Break(());
@@ -1313,7 +1977,7 @@ fn optimize_step(
                     if barrier.wait().is_err() {
                         return ControlFlow::Break(());
                     }
-                    optimizer.apply(model.as_ref(), results, lr);
+                    optimizer.apply_streamed(model.as_ref(), results, lr);
                     if barrier.wait().is_err() {
                         return ControlFlow::Break(());
                     }
@@ -1433,7 +2097,7 @@ impl CausalLM for Trainer {
         }
     }
 
-    fn clip_grad_norm(&self, max_grad_norm: f64) {
+    fn clip_grad_norm(&self, max_grad_norm: f64) -> Option<f64> {
         match self {
             Trainer::Local(local_trainer) => local_trainer.clip_grad_norm(max_grad_norm),
             #[cfg(feature = "python")]
@@ -1453,3 +2117,130 @@ impl CausalLM for Trainer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        AttentionImplementation, Llama, LlamaConfig, LlamaForCausalLM, StableVarStoreIterator,
+        attention::create_cu_seqlens,
+    };
+    use std::sync::atomic::AtomicBool;
+    use tch::nn;
+
+    #[test]
+    fn test_pack_sequences() {
+        const EOS: i32 = 99;
+        let rows = BatchDataCPU::pack(vec![vec![1, 2], vec![3, 4, 5, 6], vec![7]], 4, EOS);
+        assert_eq!(rows.len(), 3);
+
+        assert_eq!(rows[0].input_ids, vec![1, 2, EOS, 3]);
+        assert_eq!(rows[0].labels, Some(vec![1, 2, EOS, 3]));
+        assert_eq!(rows[0].position_ids, Some(vec![0, 1, 2, 0]));
+        assert_eq!(rows[0].sequence_lengths, Some(vec![3, 1]));
+
+        // the rest of the second document starts the next row as a new document
+        assert_eq!(rows[1].input_ids, vec![4, 5, 6, EOS]);
+        assert_eq!(rows[1].position_ids, Some(vec![0, 1, 2, 3]));
+        assert_eq!(rows[1].sequence_lengths, Some(vec![4]));
+
+        assert_eq!(rows[2].input_ids, vec![7, EOS, EOS, EOS]);
+        assert_eq!(rows[2].labels, Some(vec![7, EOS, -100, -100]));
+        assert_eq!(rows[2].position_ids, Some(vec![0, 1, 0, 1]));
+        assert_eq!(rows[2].sequence_lengths, Some(vec![2, 2]));
+    }
+
+    #[test]
+    fn test_packed_rows_cu_seqlens() {
+        const EOS: i32 = 99;
+        const MAX_LEN: usize = 8;
+        let sequences = vec![vec![1; 5], vec![2; 9], vec![3; 2], vec![4; 3]];
+        let rows = BatchDataCPU::pack(sequences, MAX_LEN, EOS);
+        assert_eq!(rows.len(), 3);
+
+        // documents never cross a row boundary once the rows are flattened
+        let lengths: Vec<Vec<i32>> = rows
+            .iter()
+            .map(|row| row.sequence_lengths.clone().unwrap())
+            .collect();
+        let (cu_seqlens, max) = create_cu_seqlens(&lengths, Device::Cpu);
+        let cu_seqlens = Vec::<i32>::try_from(&cu_seqlens).unwrap();
+        assert_eq!(cu_seqlens, vec![0, 6, 8, 16, 19, 23, 24]);
+        assert_eq!(max, 8);
+        for row_end in (1..=rows.len()).map(|row| (row * MAX_LEN) as i32) {
+            assert!(cu_seqlens.contains(&row_end));
+        }
+    }
+
+    #[test]
+    fn test_checkpointed_forward_backward_matches_full_backward() {
+        tch::manual_seed(0);
+        let config = LlamaConfig {
+            hidden_size: 16,
+            intermediate_size: 32,
+            vocab_size: 32,
+            num_hidden_layers: 5,
+            num_attention_heads: 2,
+            num_key_value_heads: Some(2),
+            max_position_embeddings: 16,
+            ..LlamaConfig::dummy()
+        };
+        let var_store = nn::VarStore::new(Device::Cpu);
+        let model = Llama::new(
+            var_store.root(),
+            &config,
+            AttentionImplementation::Eager,
+            None,
+        );
+        let lm_head = nn::linear(
+            &var_store.root() / "lm_head",
+            16,
+            32,
+            nn::LinearConfig {
+                bias: false,
+                ..Default::default()
+            },
+        );
+        let model = LlamaForCausalLM {
+            model,
+            config,
+            variables: StableVarStoreIterator::new(&var_store, None),
+            device: Device::Cpu,
+            lm_head,
+            comm: None,
+            training: AtomicBool::new(true),
+        };
+        // 5 layers split unevenly, so every kind of segment gets checked
+        assert_eq!(model.checkpoint_segments(), 3);
+
+        let inputs = Tensor::randint(32, [2, 8], (Kind::Int64, Device::Cpu));
+        let take_grads = || {
+            var_store
+                .trainable_variables()
+                .into_iter()
+                .map(|mut var| {
+                    let grad = var.grad().copy();
+                    var.zero_grad();
+                    grad
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let (_, loss) = model.forward(&inputs, Some(&inputs), None, None, None, None);
+        let loss = loss.unwrap();
+        loss.backward();
+        let grads = take_grads();
+
+        let checkpointed_loss =
+            LocalTrainer::checkpointed_forward_backward(&model, &inputs, &inputs, None, None, None);
+        let checkpointed_grads = take_grads();
+
+        let loss = loss.double_value(&[]);
+        assert!((loss - checkpointed_loss.double_value(&[])).abs() <= 1e-6 * loss.abs());
+        assert_eq!(grads.len(), checkpointed_grads.len());
+        for (grad, checkpointed_grad) in grads.iter().zip(&checkpointed_grads) {
+            assert!(grad.abs().sum(Kind::Float).double_value(&[]) > 0.0);
+            assert!(grad.allclose(checkpointed_grad, 1e-5, 1e-6, false));
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Checks that the AdamW optimizer built by `Optimizer::new` decouples weight decay from the
+//! gradient, i.e. that it decays the parameter directly instead of adding `weight_decay * param`
+//! to the gradient the way L2-regularized Adam does.
+
+mod common;
+
+use common::TestModel;
+use psyche_core::{DistroExtensions, OptimizerDefinition};
+use psyche_modeling::Optimizer;
+use tch::{
+    Device, Kind, Tensor,
+    nn::{self, Module, VarStore},
+};
+
+const LR: f64 = 0.01;
+const BETAS: [f64; 2] = [0.9, 0.999];
+const WEIGHT_DECAY: f64 = 0.1;
+const EPS: f64 = 1e-8;
+const STEPS: usize = 5;
+
+const INITIAL_WEIGHTS: [f32; 4] = [10.0, -10.0, 5.0, -2.0];
+/// The gradient of the loss, constant since the loss is linear in the weights
+const GRADIENT: [f32; 4] = [0.5, 0.5, -1.0, 2.0];
+
+/// A single weight vector, whose output for the input `GRADIENT` is the loss
+fn linear() -> TestModel<nn::Func<'static>> {
+    let var_store = VarStore::new(Device::Cpu);
+    let weight = var_store
+        .root()
+        .var_copy("weight", &Tensor::from_slice(&INITIAL_WEIGHTS));
+    TestModel::new(var_store, nn::func(move |x| (&weight * x).sum(Kind::Float)))
+}
+
+fn loss(model: &TestModel<nn::Func<'static>>) -> Tensor {
+    model.module.forward(&Tensor::from_slice(&GRADIENT))
+}
+
+/// Runs `STEPS` steps of Adam by hand, either with decoupled (AdamW) or coupled (L2) weight decay
+fn reference_adam(decoupled: bool) -> Vec<f64> {
+    INITIAL_WEIGHTS
+        .iter()
+        .zip(GRADIENT)
+        .map(|(weight, grad)| {
+            let (mut weight, mut m, mut v) = (*weight as f64, 0.0, 0.0);
+            for step in 1..=STEPS as i32 {
+                let mut grad = grad as f64;
+                if decoupled {
+                    weight *= 1.0 - LR * WEIGHT_DECAY;
+                } else {
+                    grad += WEIGHT_DECAY * weight;
+                }
+                m = BETAS[0] * m + (1.0 - BETAS[0]) * grad;
+                v = BETAS[1] * v + (1.0 - BETAS[1]) * grad * grad;
+                let m_hat = m / (1.0 - BETAS[0].powi(step));
+                let v_hat = v / (1.0 - BETAS[1].powi(step));
+                weight -= LR * m_hat / (v_hat.sqrt() + EPS);
+            }
+            weight
+        })
+        .collect()
+}
+
+#[test]
+fn test_adamw_weight_decay_is_decoupled() {
+    let model = linear();
+    let Optimizer::Torch { mut optimizer, .. } = Optimizer::new(
+        OptimizerDefinition::AdamW {
+            betas: [BETAS[0] as f32, BETAS[1] as f32],
+            weight_decay: WEIGHT_DECAY as f32,
+            eps: EPS as f32,
+            clip_grad_norm: None,
+        },
+        DistroExtensions::default(),
+        &model,
+    ) else {
+        panic!("AdamW should be a torch optimizer");
+    };
+
+    // the same cycle as the trainer's optimize step
+    optimizer.set_learning_rate(LR).unwrap();
+    for _ in 0..STEPS {
+        loss(&model).backward();
+        optimizer.step().unwrap();
+        optimizer.zero_grad().unwrap();
+    }
+
+    let weights = Vec::<f32>::try_from(&model.var_store.variables()["weight"]).unwrap();
+    let decoupled = reference_adam(true);
+    let coupled = reference_adam(false);
+    for (i, weight) in weights.into_iter().enumerate() {
+        // the two formulations differ by about LR * WEIGHT_DECAY * weight per step, far above this
+        assert!(
+            (weight as f64 - decoupled[i]).abs() < 1e-4,
+            "weight {i} is {weight}, expected {} from decoupled decay (coupled decay gives {})",
+            decoupled[i],
+            coupled[i]
+        );
+    }
+}
@@ -0,0 +1,78 @@
+//! A minimal `CausalLM` for the optimizer integration tests
+
+use psyche_modeling::{
+    CausalLM, Communicator, EosToks, StableVarStoreIterator, StableVariableIterator,
+};
+use std::{collections::HashMap, sync::Arc};
+use tch::{
+    Device, Tensor,
+    nn::{Module, VarStore},
+};
+
+/// Runs `module` on the CPU, with a mean squared error loss against the labels
+pub struct TestModel<M> {
+    pub var_store: VarStore,
+    pub module: M,
+}
+
+impl<M: Module> TestModel<M> {
+    pub fn new(var_store: VarStore, module: M) -> Self {
+        Self { var_store, module }
+    }
+}
+
+impl<M: Module> CausalLM for TestModel<M> {
+    fn forward(
+        &self,
+        x: &Tensor,
+        labels: Option<&Tensor>,
+        _position_ids: Option<&Tensor>,
+        _sequence_lengths: Option<&Vec<Vec<i32>>>,
+        _num_logits_to_keep: Option<i64>,
+        loss_scale: Option<f64>,
+    ) -> (Option<Tensor>, Option<Tensor>) {
+        let logits = self.module.forward(x);
+        let loss = labels.map(|labels| {
+            let loss = logits.mse_loss(labels, tch::Reduction::Mean);
+            match loss_scale {
+                Some(loss_scale) => loss / loss_scale,
+                None => loss,
+            }
+        });
+        (Some(logits), loss)
+    }
+
+    fn bos_token_id(&self) -> Option<i64> {
+        None
+    }
+
+    fn eos_token_ids(&self) -> Option<EosToks> {
+        None
+    }
+
+    fn device(&self) -> Device {
+        Device::Cpu
+    }
+
+    fn max_context_length(&self) -> usize {
+        usize::MAX
+    }
+
+    fn variables(&self) -> StableVariableIterator {
+        Box::new(StableVarStoreIterator::new(&self.var_store, None))
+    }
+
+    fn communicator(&self) -> Option<Arc<Communicator>> {
+        None
+    }
+
+    fn prepare_for_training(&self) {}
+
+    fn clip_grad_norm(&self, _max_grad_norm: f64) -> Option<f64> {
+        None
+    }
+
+    fn convert(&self, state_dict: Option<HashMap<String, Tensor>>) -> HashMap<String, Tensor> {
+        state_dict.unwrap_or_default()
+    }
+}
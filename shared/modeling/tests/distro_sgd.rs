@@ -0,0 +1,420 @@
+//! Trains the same small MLP with DisTrO and with plain SGD, and checks
+//! that DisTrO's generate/apply cycle ends up about as good as the reference.
+
+mod common;
+
+use common::TestModel;
+use psyche_modeling::{
+    AggregationMode, CLAMPED_DELTA_NORM_STAT, CausalLM, DELTA_NORM_STAT, Distro, DistroConfig,
+    DistroResult, save_tensors_into_safetensors,
+};
+use std::{collections::HashMap, path::Path};
+use tch::{
+    COptimizer, Device, Kind, Tensor,
+    nn::{self, Module, VarStore},
+};
+
+const INPUT_SIZE: i64 = 32;
+const HIDDEN_SIZE: i64 = 64;
+const OUTPUT_SIZE: i64 = 8;
+const NUM_SAMPLES: i64 = 64;
+const STEPS: usize = 50;
+const SEED: i64 = 1234;
+
+const SGD_LR: f64 = 0.05;
+const DISTRO_LR: f64 = 0.002;
+const COMPRESSION_DECAY: f64 = 0.999;
+const COMPRESSION_CHUNK: i64 = 64;
+const COMPRESSION_TOPK: i64 = 1024;
+
+type Mlp = TestModel<nn::Sequential>;
+
+/// Two layer MLP regressing the labels from `x` with mean squared error
+fn mlp() -> Mlp {
+    tch::manual_seed(SEED);
+    let var_store = VarStore::new(Device::Cpu);
+    let root = var_store.root();
+    let model = nn::seq()
+        .add(nn::linear(
+            &root / "fc1",
+            INPUT_SIZE,
+            HIDDEN_SIZE,
+            Default::default(),
+        ))
+        .add_fn(|x| x.relu())
+        .add(nn::linear(
+            &root / "fc2",
+            HIDDEN_SIZE,
+            OUTPUT_SIZE,
+            Default::default(),
+        ));
+    TestModel::new(var_store, model)
+}
+
+fn loss(model: &Mlp, x: &Tensor, labels: &Tensor) -> Tensor {
+    model
+        .module
+        .forward(x)
+        .mse_loss(labels, tch::Reduction::Mean)
+}
+
+fn eval_loss(model: &Mlp, x: &Tensor, labels: &Tensor) -> f64 {
+    tch::no_grad(|| loss(model, x, labels).double_value(&[]))
+}
+
+fn synthetic_data() -> (Tensor, Tensor) {
+    tch::manual_seed(SEED + 1);
+    let x = Tensor::randn([NUM_SAMPLES, INPUT_SIZE], (Kind::Float, Device::Cpu));
+    let y = Tensor::randn([NUM_SAMPLES, OUTPUT_SIZE], (Kind::Float, Device::Cpu));
+    (x, y)
+}
+
+/// Returns the loss before the first step and after every step
+fn train_sgd(x: &Tensor, y: &Tensor) -> Vec<f64> {
+    let model = mlp();
+    let mut sgd = COptimizer::sgd(SGD_LR, 0.0, 0.0, 0.0, false).unwrap();
+    for var in model.variables() {
+        sgd.add_parameters(&var.logical_tensor(), 0).unwrap();
+    }
+    let mut losses = vec![eval_loss(&model, x, y)];
+    for _ in 0..STEPS {
+        sgd.zero_grad().unwrap();
+        loss(&model, x, y).backward();
+        sgd.step().unwrap();
+        losses.push(eval_loss(&model, x, y));
+    }
+    losses
+}
+
+fn distro_config() -> DistroConfig {
+    DistroConfig::new(COMPRESSION_DECAY, COMPRESSION_CHUNK, COMPRESSION_TOPK)
+}
+
+/// Returns the loss before the first step and after every step
+fn train_distro(x: &Tensor, y: &Tensor, aggregation: AggregationMode) -> Vec<f64> {
+    let model = mlp();
+    let mut losses = vec![eval_loss(&model, x, y)];
+    let mut distro = Distro::new(
+        &model,
+        DistroConfig {
+            aggregation,
+            ..distro_config()
+        },
+    );
+
+    losses.extend(run_distro(&model, &mut distro, x, y, STEPS));
+    losses
+}
+
+/// Runs `steps` steps of the cycle the trainer runs with a single client, the first of them with
+/// no previous results like at the start of an epoch, returning the loss after each step
+fn run_distro(model: &Mlp, distro: &mut Distro, x: &Tensor, y: &Tensor, steps: usize) -> Vec<f64> {
+    let mut losses = Vec::new();
+    let mut prev_results = Vec::new();
+    let mut prev_lr = 0.0;
+    for _ in 0..steps {
+        for var in model.variables() {
+            var.zero_grad();
+        }
+        distro.error_correction(model, prev_lr);
+        loss(model, x, y).backward();
+        let results = distro.generate(model, &prev_results, prev_lr, DISTRO_LR, false);
+        prev_results = vec![results];
+        distro.apply(model, &prev_results, DISTRO_LR);
+        prev_lr = DISTRO_LR;
+        losses.push(eval_loss(model, x, y));
+    }
+    losses
+}
+
+#[test]
+fn test_distro_matches_sgd() {
+    let (x, y) = synthetic_data();
+
+    let sgd_losses = train_sgd(&x, &y);
+    let distro_losses = train_distro(&x, &y, AggregationMode::ValueAverage);
+
+    let sgd_loss = *sgd_losses.last().unwrap();
+    let (initial_loss, distro_loss) = (distro_losses[0], *distro_losses.last().unwrap());
+    assert!(
+        distro_loss < initial_loss,
+        "DisTrO didn't train: loss went from {initial_loss} to {distro_loss}, losses {distro_losses:?}"
+    );
+    assert!(
+        (distro_loss - sgd_loss).abs() <= 0.1 * sgd_loss,
+        "DisTrO loss {distro_loss} isn't within 10% of SGD loss {sgd_loss}, \
+         DisTrO losses {distro_losses:?}, SGD losses {sgd_losses:?}"
+    );
+}
+
+#[test]
+fn test_distro_sign_sgd_trains() {
+    let (x, y) = synthetic_data();
+
+    let losses = train_distro(&x, &y, AggregationMode::SignSGD);
+
+    let (initial_loss, distro_loss) = (losses[0], *losses.last().unwrap());
+    assert!(
+        distro_loss < initial_loss,
+        "sign-SGD DisTrO didn't train: loss went from {initial_loss} to {distro_loss}, losses {losses:?}"
+    );
+}
+
+/// How much each parameter of a fresh model moves when `results` are applied to it
+fn applied_update(
+    x: &Tensor,
+    y: &Tensor,
+    aggregation: AggregationMode,
+    results: &[Vec<DistroResult>],
+) -> Vec<Tensor> {
+    applied_update_with(x, y, aggregation, |distro, model| {
+        distro.apply(model, results, DISTRO_LR)
+    })
+}
+
+/// Like [`applied_update`], applying the results with `apply`
+fn applied_update_with(
+    x: &Tensor,
+    y: &Tensor,
+    aggregation: AggregationMode,
+    apply: impl FnOnce(&mut Distro, &Mlp),
+) -> Vec<Tensor> {
+    let model = mlp();
+    let mut distro = Distro::new(
+        &model,
+        DistroConfig {
+            aggregation,
+            ..distro_config()
+        },
+    );
+    let before: Vec<Tensor> = model
+        .variables()
+        .map(|var| var.logical_tensor().copy())
+        .collect();
+    // apply writes into the gradients, so they have to exist
+    loss(&model, x, y).backward();
+    apply(&mut distro, &model);
+    model
+        .variables()
+        .zip(before)
+        .map(|(var, before)| var.logical_tensor() - before)
+        .collect()
+}
+
+#[test]
+fn test_distro_sign_sgd_majority_wins() {
+    let (x, y) = synthetic_data();
+    let model = mlp();
+    let mut distro = Distro::new(&model, distro_config());
+    loss(&model, &x, &y).backward();
+    let results = distro.generate(&model, &[], 0.0, DISTRO_LR, false);
+    let scaled = |factor: f64| {
+        results
+            .iter()
+            .map(|result| DistroResult {
+                sparse_val: &result.sparse_val * factor,
+                ..result.clone()
+            })
+            .collect::<Vec<_>>()
+    };
+    // two nodes agree, and a third sends the opposite, a hundred times larger
+    let conflicting = [scaled(1.0), scaled(2.0), scaled(-100.0)];
+    let agreeing = [scaled(1.0)];
+
+    let majority = applied_update(&x, &y, AggregationMode::SignSGD, &conflicting);
+    let expected = applied_update(&x, &y, AggregationMode::SignSGD, &agreeing);
+    for (majority, expected) in majority.iter().zip(&expected) {
+        assert!(expected.abs().sum(Kind::Float).double_value(&[]) > 0.0);
+        assert!(
+            majority.allclose(expected, 1e-4, 1e-6, false),
+            "sign-SGD didn't follow the two agreeing nodes"
+        );
+    }
+
+    // averaging lets the outlier flip the whole update
+    let average = applied_update(&x, &y, AggregationMode::ValueAverage, &conflicting);
+    let expected = applied_update(&x, &y, AggregationMode::ValueAverage, &agreeing);
+    for (average, expected) in average.iter().zip(&expected) {
+        assert!(
+            average.allclose(&-expected, 1e-4, 1e-6, false),
+            "averaging should have followed the outlier"
+        );
+    }
+}
+
+#[test]
+fn test_distro_apply_streamed_matches_apply() {
+    let (x, y) = synthetic_data();
+    // each node trains on its own slice of the data, so their top-k indices differ
+    let nodes = 4;
+    let results: Vec<Vec<DistroResult>> = (0..nodes)
+        .map(|node| {
+            let model = mlp();
+            let mut distro = Distro::new(&model, distro_config());
+            let rows = NUM_SAMPLES / nodes;
+            loss(
+                &model,
+                &x.narrow(0, node * rows, rows),
+                &y.narrow(0, node * rows, rows),
+            )
+            .backward();
+            distro.generate(&model, &[], 0.0, DISTRO_LR, false)
+        })
+        .collect();
+
+    for aggregation in [AggregationMode::ValueAverage, AggregationMode::SignSGD] {
+        let batched = applied_update(&x, &y, aggregation, &results);
+        let streamed = applied_update_with(&x, &y, aggregation, |distro, model| {
+            distro.apply_streamed(model, &results, DISTRO_LR)
+        });
+        for (batched, streamed) in batched.iter().zip(&streamed) {
+            assert!(batched.abs().sum(Kind::Float).double_value(&[]) > 0.0);
+            assert!(
+                streamed.allclose(batched, 1e-4, 1e-6, false),
+                "apply_streamed and apply disagree with {aggregation:?}"
+            );
+        }
+    }
+}
+
+/// Global L2 norm of the error feedback `distro` holds
+fn state_norm(distro: &Distro) -> f64 {
+    distro
+        .unsharded_cpu_state(None)
+        .values()
+        .map(|delta| delta.norm().double_value(&[]).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+#[test]
+fn test_distro_clamps_delta_norm() {
+    let (x, y) = synthetic_data();
+    let max_norm = 1e-4;
+    // the same first step, without and with clamping
+    let first_step = |max_grad_norm| {
+        let model = mlp();
+        let mut distro = Distro::new(
+            &model,
+            DistroConfig {
+                max_grad_norm,
+                ..distro_config()
+            },
+        );
+        loss(&model, &x, &y).backward();
+        let results = distro.generate(&model, &[], 0.0, DISTRO_LR, true);
+        (state_norm(&distro), results)
+    };
+    let (norm, unclamped) = first_step(None);
+    let (clamped_norm, clamped) = first_step(Some(max_norm));
+
+    assert!(
+        norm > max_norm,
+        "delta norm {norm} should start above {max_norm}"
+    );
+    assert!(
+        clamped_norm <= max_norm && clamped_norm >= 0.99 * max_norm,
+        "error feedback norm {clamped_norm} wasn't clamped to {max_norm}"
+    );
+
+    // the DCT is linear, so clamping scales what's sent without changing which values are picked
+    let scale = clamped_norm / norm;
+    for (unclamped, clamped) in unclamped.iter().zip(&clamped) {
+        assert!(clamped.sparse_idx.equal(&unclamped.sparse_idx));
+        let expected = &unclamped.sparse_val * scale;
+        assert!(
+            clamped.sparse_val.allclose(&expected, 1e-4, 1e-12, false),
+            "sent values weren't scaled by {scale}"
+        );
+    }
+
+    let stats = clamped[0].stats.as_ref().unwrap();
+    assert!((stats[DELTA_NORM_STAT] - norm).abs() <= 1e-4 * norm);
+    assert!((stats[CLAMPED_DELTA_NORM_STAT] - clamped_norm).abs() <= 1e-4 * clamped_norm);
+}
+
+#[test]
+fn test_distro_state_round_trip() {
+    let (x, y) = synthetic_data();
+    let model = mlp();
+    let new_distro = || Distro::new(&model, distro_config());
+
+    let mut distro = new_distro();
+    loss(&model, &x, &y).backward();
+    distro.generate(&model, &[], 0.0, DISTRO_LR, false);
+    let state = distro.unsharded_cpu_state(None);
+    assert_eq!(state.len(), model.variables().count());
+    assert!(
+        state
+            .values()
+            .any(|delta| delta.abs().sum(Kind::Float).double_value(&[]) > 0.0),
+        "generate should leave error feedback behind"
+    );
+
+    let mut restored = new_distro();
+    restored.load_state(&state).unwrap();
+    let restored_state = restored.unsharded_cpu_state(None);
+    for (name, delta) in &state {
+        assert!(delta.equal(&restored_state[name]), "{name} wasn't restored");
+    }
+
+    let mut missing = state;
+    let name = missing.keys().next().unwrap().clone();
+    missing.remove(&name);
+    assert!(restored.load_state(&missing).is_err());
+}
+
+fn read_safetensors_dir(dir: &Path) -> HashMap<String, Tensor> {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .flat_map(|entry| Tensor::read_safetensors(entry.unwrap().path()).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_distro_resumes_from_disk() {
+    let (x, y) = synthetic_data();
+    let model = mlp();
+    let mut distro = Distro::new(&model, distro_config());
+    run_distro(&model, &mut distro, &x, &y, STEPS / 2);
+
+    // what a checkpoint holds: the weights, and the optimizer state next to them
+    let dir = tempfile::tempdir().unwrap();
+    let weights = model
+        .variables()
+        .map(|var| (var.name().to_owned(), var.logical_tensor().copy()))
+        .collect();
+    save_tensors_into_safetensors(weights, dir.path().join("model")).unwrap();
+    save_tensors_into_safetensors(
+        distro.unsharded_cpu_state(None),
+        dir.path().join("optimizer"),
+    )
+    .unwrap();
+
+    let resumed = mlp();
+    let weights = read_safetensors_dir(&dir.path().join("model"));
+    tch::no_grad(|| {
+        for var in resumed.variables() {
+            let mut tensor = var.logical_tensor();
+            tensor.copy_(&weights[var.name()]);
+        }
+    });
+    let mut resumed_distro = Distro::new(&resumed, distro_config());
+    resumed_distro
+        .load_state(&read_safetensors_dir(&dir.path().join("optimizer")))
+        .unwrap();
+
+    let losses = run_distro(&model, &mut distro, &x, &y, STEPS / 2);
+    let resumed_losses = run_distro(&resumed, &mut resumed_distro, &x, &y, STEPS / 2);
+    assert_eq!(
+        losses, resumed_losses,
+        "the resumed run should train exactly like the one that kept going"
+    );
+    for (var, resumed_var) in model.variables().zip(resumed.variables()) {
+        assert!(
+            var.logical_tensor().equal(&resumed_var.logical_tensor()),
+            "{} diverged after resuming",
+            var.name()
+        );
+    }
+}
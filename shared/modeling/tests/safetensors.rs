@@ -0,0 +1,48 @@
+//! Checks that checkpoints written by `save_tensors_into_safetensors` read back unchanged.
+
+use psyche_modeling::save_tensors_into_safetensors;
+use std::collections::HashMap;
+use tch::{Device, Kind, Tensor};
+
+#[test]
+fn safetensors_roundtrip() {
+    let dir = tempfile::tempdir().unwrap();
+    let params = HashMap::from([
+        (
+            "model.embed_tokens.weight".to_string(),
+            Tensor::randn([16, 8], (Kind::Float, Device::Cpu)),
+        ),
+        (
+            "model.norm.weight".to_string(),
+            Tensor::ones([8], (Kind::BFloat16, Device::Cpu)),
+        ),
+    ]);
+
+    let files = save_tensors_into_safetensors(
+        params
+            .iter()
+            .map(|(name, tensor)| (name.clone(), tensor.shallow_clone()))
+            .collect(),
+        dir.path().to_path_buf(),
+    )
+    .unwrap();
+    assert_eq!(files, vec![dir.path().join("model.safetensors")]);
+
+    let loaded: HashMap<String, Tensor> = Tensor::read_safetensors(&files[0])
+        .unwrap()
+        .into_iter()
+        .collect();
+    assert_eq!(loaded.len(), params.len());
+    for (name, tensor) in &params {
+        let loaded = &loaded[name];
+        assert_eq!(loaded.kind(), tensor.kind());
+        assert_eq!(loaded.size(), tensor.size());
+        assert!(loaded.equal(tensor), "{name} changed in the roundtrip");
+    }
+}
+
+#[test]
+fn save_without_tensors_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(save_tensors_into_safetensors(HashMap::new(), dir.path().to_path_buf()).is_err());
+}
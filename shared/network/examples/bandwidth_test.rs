@@ -7,8 +7,8 @@ use psyche_metrics::ClientMetrics;
 use psyche_network::Hash;
 use psyche_network::RelayKind;
 use psyche_network::{
-    BlobTicket, DiscoveryMode, DownloadType, NetworkConnection, NetworkEvent, NetworkTUIState,
-    NetworkTui, allowlist, fmt_bytes,
+    BlobTicket, DiscoveryMode, DownloadPriority, DownloadType, Downloadable, NetworkConfig,
+    NetworkConnection, NetworkEvent, NetworkTUIState, NetworkTui, allowlist, fmt_bytes,
 };
 use psyche_tui::{
     CustomWidget, LogOutput,
@@ -191,6 +191,7 @@ impl App {
                     blob_ticket,
                     Tag::from(step.to_string()),
                     DownloadType::DistroResult(Vec::new()),
+                    DownloadPriority::Critical,
                 )
             }
             NetworkEvent::DownloadComplete(result) => {
@@ -321,8 +322,9 @@ async fn main() -> Result<()> {
         single_endpoint_id.into_iter().collect(),
         secret_key,
         allowlist::AllowAll,
-        Arc::new(ClientMetrics::new(None, None)),
+        Arc::new(ClientMetrics::new(None, None, None)),
         Some(cancel.clone()),
+        NetworkConfig::default(),
     )
     .await?;
 
@@ -364,3 +366,5 @@ struct DistroResultBlob {
     step: u32,
     data: Vec<u8>,
 }
+
+impl Downloadable for DistroResultBlob {}
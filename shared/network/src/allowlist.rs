@@ -1,12 +1,25 @@
-use std::collections::HashSet;
-use std::sync::{Arc, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, SystemTime};
 
+use anyhow::{Context, Result};
 use iroh::EndpointId;
 use iroh::endpoint::{AfterHandshakeOutcome, ConnectionInfo, EndpointHooks};
+use notify::{EventKind, RecursiveMode, Watcher};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// How often a file watcher checks whether its allowlist has been dropped.
+const WATCH_DROP_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
 pub trait Allowlist: std::fmt::Debug + Clone {
     fn allowed(&self, addr: EndpointId) -> bool;
     fn force_allow(&self, addr: EndpointId);
+    /// Allows `addr` until `expires_at`, after which it's treated as if it was never added.
+    fn allow_until(&self, addr: EndpointId, expires_at: SystemTime);
+    /// Drops any entries that have expired.
+    fn remove_expired(&self) {}
 }
 
 #[derive(Debug, Clone)]
@@ -19,25 +32,31 @@ impl Allowlist for AllowAll {
     fn force_allow(&self, _addr: EndpointId) {
         // all allowed!
     }
+    fn allow_until(&self, _addr: EndpointId, _expires_at: SystemTime) {
+        // all allowed, forever!
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct AllowDynamic {
-    allowed_nodes: Arc<RwLock<HashSet<EndpointId>>>,
+    /// allowed nodes, with when their access expires (if ever)
+    allowed_nodes: Arc<RwLock<HashMap<EndpointId, Option<SystemTime>>>>,
     force_allowed_nodes: Arc<RwLock<HashSet<EndpointId>>>,
 }
 
 impl AllowDynamic {
     pub fn new() -> Self {
         AllowDynamic {
-            allowed_nodes: Arc::new(RwLock::new(HashSet::new())),
+            allowed_nodes: Arc::new(RwLock::new(HashMap::new())),
             force_allowed_nodes: Arc::new(RwLock::new(HashSet::new())),
         }
     }
 
     pub fn with_nodes(nodes: impl IntoIterator<Item = EndpointId>) -> Self {
         AllowDynamic {
-            allowed_nodes: Arc::new(RwLock::new(nodes.into_iter().collect())),
+            allowed_nodes: Arc::new(RwLock::new(
+                nodes.into_iter().map(|node| (node, None)).collect(),
+            )),
             force_allowed_nodes: Arc::new(RwLock::new(HashSet::new())),
         }
     }
@@ -46,7 +65,7 @@ impl AllowDynamic {
         self.allowed_nodes
             .write()
             .expect("RwLock poisoned")
-            .insert(addr);
+            .insert(addr, None);
     }
 
     pub fn remove(&self, addr: &EndpointId) {
@@ -56,21 +75,148 @@ impl AllowDynamic {
             .remove(addr);
     }
 
+    /// Replaces the permanently allowed nodes with `nodes`.
+    /// Nodes allowed through [`Allowlist::allow_until`] stay allowed until they expire.
     pub fn set(&self, nodes: impl IntoIterator<Item = EndpointId>) {
-        *self.allowed_nodes.write().expect("RwLock poisoned") = nodes.into_iter().collect();
+        replace_permanent_nodes(
+            &mut self.allowed_nodes.write().expect("RwLock poisoned"),
+            nodes,
+        );
     }
 
     pub fn clear(&self) {
         self.allowed_nodes.write().expect("RwLock poisoned").clear();
     }
+
+    /// Keeps the allowlist in sync with a file containing a JSON array of node ids,
+    /// re-reading it every time it changes, like [`Self::set`].
+    /// If the file can't be parsed, a warning is logged and the previous allowlist is kept.
+    /// The returned task stops once every clone of this allowlist has been dropped.
+    pub fn watch_file(&self, path: PathBuf) -> Result<JoinHandle<()>> {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        })
+        .context("Failed to create allowlist file watcher")?;
+        // watch the directory rather than the file, since editors often
+        // save by replacing the file, which would end a watch on the file itself
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch allowlist file {}", path.display()))?;
+
+        // only hold a weak reference, so the watcher doesn't keep the allowlist alive
+        let allowed_nodes = Arc::downgrade(&self.allowed_nodes);
+        reload_allowlist_file(&allowed_nodes, &path);
+
+        Ok(tokio::spawn(async move {
+            let _watcher = watcher;
+            let mut drop_check = tokio::time::interval(WATCH_DROP_CHECK_INTERVAL);
+            loop {
+                tokio::select! {
+                    event = rx.recv() => match event {
+                        Some(Ok(event)) => {
+                            if is_allowlist_file_change(&event, &path)
+                                && !reload_allowlist_file(&allowed_nodes, &path)
+                            {
+                                break;
+                            }
+                        }
+                        Some(Err(err)) => warn!("Error watching allowlist file {}: {err}", path.display()),
+                        None => break,
+                    },
+                    _ = drop_check.tick() => {
+                        if allowed_nodes.strong_count() == 0 {
+                            break;
+                        }
+                    }
+                }
+            }
+        }))
+    }
+}
+
+fn is_allowlist_file_change(event: &notify::Event, path: &Path) -> bool {
+    matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+        && event
+            .paths
+            .iter()
+            .any(|changed| changed.file_name() == path.file_name())
+}
+
+/// Replaces every entry without an expiry with `nodes`, keeping the unexpired ones
+fn replace_permanent_nodes(
+    allowed_nodes: &mut HashMap<EndpointId, Option<SystemTime>>,
+    nodes: impl IntoIterator<Item = EndpointId>,
+) {
+    let now = SystemTime::now();
+    allowed_nodes.retain(|_, expires_at| expires_at.is_some_and(|t| now < t));
+    allowed_nodes.extend(nodes.into_iter().map(|node| (node, None)));
+}
+
+fn parse_allowlist_file(path: &Path) -> Result<HashSet<EndpointId>> {
+    let contents = std::fs::read_to_string(path)?;
+    let ids: Vec<String> = serde_json::from_str(&contents)?;
+    ids.iter()
+        .map(|id| {
+            id.parse::<EndpointId>()
+                .with_context(|| format!("Invalid node id {id:?}"))
+        })
+        .collect()
+}
+
+/// Replaces the permanently allowed nodes with the contents of `path`.
+/// Returns false if the allowlist has been dropped.
+fn reload_allowlist_file(
+    allowed_nodes: &Weak<RwLock<HashMap<EndpointId, Option<SystemTime>>>>,
+    path: &Path,
+) -> bool {
+    let Some(allowed_nodes) = allowed_nodes.upgrade() else {
+        return false;
+    };
+    match parse_allowlist_file(path) {
+        Ok(nodes) => {
+            info!(
+                "Loaded {} allowed nodes from {}",
+                nodes.len(),
+                path.display()
+            );
+            replace_permanent_nodes(&mut allowed_nodes.write().expect("RwLock poisoned"), nodes);
+        }
+        Err(err) => warn!(
+            "Failed to load allowlist from {}, keeping the previous allowlist: {err:#}",
+            path.display()
+        ),
+    }
+    true
 }
 
 impl Allowlist for AllowDynamic {
     fn allowed(&self, addr: EndpointId) -> bool {
-        self.allowed_nodes
+        let expires_at = self
+            .allowed_nodes
             .read()
             .expect("RwLock poisoned")
-            .contains(&addr)
+            .get(&addr)
+            .copied();
+        let allowed = match expires_at {
+            Some(expires_at) => {
+                let unexpired = expires_at.map(|t| SystemTime::now() < t).unwrap_or(true);
+                if !unexpired {
+                    let mut allowed_nodes = self.allowed_nodes.write().expect("RwLock poisoned");
+                    // it may have been re-allowed since we checked
+                    if allowed_nodes.get(&addr) == Some(&expires_at) {
+                        allowed_nodes.remove(&addr);
+                    }
+                }
+                unexpired
+            }
+            None => false,
+        };
+        allowed
             || self
                 .force_allowed_nodes
                 .read()
@@ -83,6 +229,19 @@ impl Allowlist for AllowDynamic {
             .expect("RwLock poisoned")
             .insert(addr);
     }
+    fn allow_until(&self, addr: EndpointId, expires_at: SystemTime) {
+        self.allowed_nodes
+            .write()
+            .expect("RwLock poisoned")
+            .insert(addr, Some(expires_at));
+    }
+    fn remove_expired(&self) {
+        let now = SystemTime::now();
+        self.allowed_nodes
+            .write()
+            .expect("RwLock poisoned")
+            .retain(|_, expires_at| expires_at.map(|t| now < t).unwrap_or(true));
+    }
 }
 
 impl Default for AllowDynamic {
@@ -114,3 +273,91 @@ impl<A: Allowlist + Send + Sync> EndpointHooks for AllowlistHook<A> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh::SecretKey;
+
+    fn node_id() -> EndpointId {
+        SecretKey::generate(&mut rand::rng()).public()
+    }
+
+    fn write_allowlist(path: &Path, nodes: &[EndpointId]) {
+        let ids: Vec<String> = nodes.iter().map(|node| node.to_string()).collect();
+        std::fs::write(path, serde_json::to_string(&ids).unwrap()).unwrap();
+    }
+
+    async fn wait_until(mut condition: impl FnMut() -> bool) {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while !condition() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("timed out waiting for the allowlist to change");
+    }
+
+    #[test]
+    fn test_allow_until_expires() {
+        let allowlist = AllowDynamic::new();
+        let (expired, unexpired) = (node_id(), node_id());
+        allowlist.allow_until(expired, SystemTime::now() - Duration::from_secs(1));
+        allowlist.allow_until(unexpired, SystemTime::now() + Duration::from_secs(60));
+
+        assert!(!allowlist.allowed(expired));
+        assert!(allowlist.allowed(unexpired));
+
+        allowlist.allow_until(expired, SystemTime::now() - Duration::from_secs(1));
+        allowlist.remove_expired();
+        let allowed_nodes = allowlist.allowed_nodes.read().unwrap();
+        assert!(!allowed_nodes.contains_key(&expired));
+        assert!(allowed_nodes.contains_key(&unexpired));
+    }
+
+    #[test]
+    fn test_set_keeps_unexpired_entries() {
+        let (old, new, temporary, expired) = (node_id(), node_id(), node_id(), node_id());
+        let allowlist = AllowDynamic::with_nodes([old]);
+        allowlist.allow_until(temporary, SystemTime::now() + Duration::from_secs(60));
+        allowlist.allow_until(expired, SystemTime::now() - Duration::from_secs(1));
+
+        allowlist.set([new]);
+
+        assert!(!allowlist.allowed(old));
+        assert!(allowlist.allowed(new));
+        assert!(allowlist.allowed(temporary));
+        assert!(!allowlist.allowed(expired));
+        // still expires
+        assert!(allowlist.allowed_nodes.read().unwrap()[&temporary].is_some());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_watch_file_follows_replaced_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("allowlist.json");
+        let (first, second, temporary) = (node_id(), node_id(), node_id());
+        write_allowlist(&path, &[first]);
+
+        let allowlist = AllowDynamic::new();
+        allowlist.allow_until(temporary, SystemTime::now() + Duration::from_secs(60));
+        let watch = allowlist.watch_file(path.clone()).unwrap();
+        assert!(allowlist.allowed(first));
+        assert!(allowlist.allowed(temporary));
+
+        // save the way editors do, by writing a new file and renaming it over the old one
+        for (previous, next) in [(first, second), (second, first)] {
+            let tmp_path = dir.path().join("allowlist.json.tmp");
+            write_allowlist(&tmp_path, &[next]);
+            std::fs::rename(&tmp_path, &path).unwrap();
+            wait_until(|| allowlist.allowed(next) && !allowlist.allowed(previous)).await;
+            assert!(allowlist.allowed(temporary));
+        }
+
+        drop(allowlist);
+        tokio::time::timeout(Duration::from_secs(10), watch)
+            .await
+            .expect("watcher didn't stop after the allowlist was dropped")
+            .unwrap();
+    }
+}
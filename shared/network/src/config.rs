@@ -0,0 +1,126 @@
+use std::{
+    collections::HashSet,
+    num::NonZeroU32,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use iroh_blobs::{
+    Hash,
+    api::{Store, Tag},
+    store::{ProtectCb, ProtectOutcome},
+};
+use iroh_gossip::proto::{HyparviewConfig, PlumtreeConfig};
+use tracing::warn;
+
+const DEFAULT_GC_INTERVAL: Duration = Duration::from_secs(10);
+const DEFAULT_GOSSIP_MESSAGE_ID_RETENTION: Duration = Duration::from_secs(2 * 60);
+const DEFAULT_MAX_IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+const DEFAULT_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(5);
+const DEFAULT_GOSSIP_MAX_MESSAGE_SIZE: usize = 4096;
+const DEFAULT_BLOB_STORE_WARN_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+const DEFAULT_MAX_CONCURRENT_DOWNLOADS: usize = 64;
+
+/// Tunables for a [`crate::NetworkConnection`] that don't affect which peers we talk to.
+#[derive(Debug, Clone)]
+pub struct NetworkConfig {
+    /// How often the in-memory blob store garbage-collects untagged blobs.
+    /// Large models with many parameter shards may want this longer to avoid stutter during training.
+    pub gc_interval: Duration,
+    /// Tags whose blobs are always kept alive by the GC, and never removed by
+    /// [`crate::NetworkConnection::remove_staled_tags`].
+    pub gc_protected_tags: Vec<Tag>,
+    pub gossip: GossipConfig,
+    /// How long a QUIC connection may go without traffic before it's closed.
+    pub max_idle_timeout: Duration,
+    /// How often to ping otherwise idle QUIC connections, keeping them (and NAT mappings) alive.
+    /// Should be well under `max_idle_timeout`.
+    pub keep_alive_interval: Duration,
+    /// Warn when the in-memory blob store holds more than this many bytes,
+    /// a sign the GC isn't keeping up and we may run out of RAM.
+    pub blob_store_warn_bytes: u64,
+    /// If set, [`crate::NetworkConnection::broadcast`] fails with [`crate::NetworkError::RateLimited`]
+    /// when called more often than this, with bursts of up to one second's worth allowed.
+    pub max_broadcasts_per_second: Option<NonZeroU32>,
+    /// Most blob downloads transferring at once. Past this, downloads wait for a slot,
+    /// which goes to the highest [`crate::DownloadPriority`] first.
+    pub max_concurrent_downloads: usize,
+}
+
+/// How the gossip overlay is built and how messages are broadcast over it.
+/// Runs with hundreds of clients want a bigger active view, or the overlay can partition.
+#[derive(Debug, Clone)]
+pub struct GossipConfig {
+    pub membership: HyparviewConfig,
+    pub broadcast: PlumtreeConfig,
+    /// Largest gossip message in bytes, bigger ones are dropped. Peers reject messages over their
+    /// own limit, so every client in a run should use the same value.
+    pub max_message_size: usize,
+}
+
+impl Default for GossipConfig {
+    fn default() -> Self {
+        Self {
+            membership: HyparviewConfig {
+                active_view_capacity: 8,
+                shuffle_interval: Duration::from_secs(30),
+                neighbor_request_timeout: Duration::from_secs(2),
+                ..HyparviewConfig::default()
+            },
+            broadcast: PlumtreeConfig {
+                graft_timeout_2: Duration::from_millis(200),
+                message_cache_retention: Duration::from_secs(60),
+                message_id_retention: DEFAULT_GOSSIP_MESSAGE_ID_RETENTION,
+                ..PlumtreeConfig::default()
+            },
+            max_message_size: DEFAULT_GOSSIP_MAX_MESSAGE_SIZE,
+        }
+    }
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            gc_interval: DEFAULT_GC_INTERVAL,
+            gc_protected_tags: Vec::new(),
+            gossip: GossipConfig::default(),
+            max_idle_timeout: DEFAULT_MAX_IDLE_TIMEOUT,
+            keep_alive_interval: DEFAULT_KEEP_ALIVE_INTERVAL,
+            blob_store_warn_bytes: DEFAULT_BLOB_STORE_WARN_BYTES,
+            max_broadcasts_per_second: None,
+            max_concurrent_downloads: DEFAULT_MAX_CONCURRENT_DOWNLOADS,
+        }
+    }
+}
+
+impl NetworkConfig {
+    pub(crate) fn is_gc_protected(&self, tag_name: &str) -> bool {
+        self.gc_protected_tags
+            .iter()
+            .any(|tag| tag.0.as_ref() == tag_name.as_bytes())
+    }
+}
+
+/// Builds a GC callback that marks the blobs behind `tags` as live.
+/// The store is only available after it has been created with this callback, so it's filled in later.
+pub(crate) fn protect_tags_cb(store: Arc<OnceLock<Store>>, tags: Vec<Tag>) -> ProtectCb {
+    Arc::new(move |live: &mut HashSet<Hash>| {
+        let store = store.get().cloned();
+        let tags = tags.clone();
+        Box::pin(async move {
+            let Some(store) = store else {
+                return ProtectOutcome::Continue;
+            };
+            for tag in tags {
+                match store.tags().get(tag.0.clone()).await {
+                    Ok(Some(info)) => {
+                        live.insert(info.hash);
+                    }
+                    Ok(None) => {}
+                    Err(err) => warn!("Failed to resolve GC-protected tag {tag:?}: {err:#}"),
+                }
+            }
+            ProtectOutcome::Continue
+        })
+    })
+}
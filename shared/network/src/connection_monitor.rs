@@ -1,5 +1,5 @@
 use iroh::endpoint::{AfterHandshakeOutcome, ConnectionInfo, EndpointHooks};
-use iroh::{EndpointId, Watcher};
+use iroh::{EndpointId, TransportAddr, Watcher};
 use n0_future::task::AbortOnDropHandle;
 use psyche_event_sourcing::event;
 use psyche_metrics::SelectedPath;
@@ -18,12 +18,27 @@ pub enum PeerBandwidth {
     Measured(f64),
 }
 
+/// How we're currently talking to a peer, best first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConnectionType {
+    /// The selected path is a direct IP path
+    Direct,
+    /// The selected path goes through a relay, but we also have a direct path open
+    /// (e.g. while holepunching)
+    Mixed,
+    /// Only relay paths
+    Relay,
+    /// No open connection
+    None,
+}
+
 #[derive(Debug, Clone)]
 pub struct ConnectionData {
     pub endpoint_id: EndpointId,
     /// Throughput measured from actual downloads
     pub bandwidth: PeerBandwidth,
     pub selected_path: Option<SelectedPath>,
+    pub connection_type: ConnectionType,
 }
 
 impl ConnectionData {
@@ -80,6 +95,7 @@ impl ConnectionMonitor {
                     let alpn = String::from_utf8_lossy(conn.alpn()).to_string();
 
                     let selected_path = Self::extract_selected_path(&conn.paths());
+                    let connection_type = Self::extract_connection_type(&conn.paths());
 
                     if let Some(ref path) = selected_path {
                         info!(
@@ -106,6 +122,7 @@ impl ConnectionMonitor {
                             endpoint_id: remote_id,
                             bandwidth: prev_bandwidth,
                             selected_path: selected_path.clone(),
+                            connection_type,
                         });
                     }
 
@@ -122,6 +139,7 @@ impl ConnectionMonitor {
                             tokio::select! {
                                 _ = update_interval.tick() => {
                                     let selected_path = Self::extract_selected_path(&paths_watcher);
+                                    let connection_type = Self::extract_connection_type(&paths_watcher);
 
                                     let mut conns = connections_clone.write().unwrap();
                                     if let Some(data) = conns.get_mut(&remote_id) {
@@ -136,6 +154,7 @@ impl ConnectionMonitor {
                                         };
 
                                         data.selected_path = selected_path.clone();
+                                        data.connection_type = connection_type;
 
                                         if path_changed {
                                             if let Some(ref path) = selected_path {
@@ -188,6 +207,7 @@ impl ConnectionMonitor {
                                     // Keep the entry (preserving bandwidth) but clear path info
                                     if let Some(data) = connections_clone.write().unwrap().get_mut(&remote_id) {
                                         data.selected_path = None;
+                                        data.connection_type = ConnectionType::None;
                                     }
                                     break;
                                 }
@@ -221,6 +241,23 @@ impl ConnectionMonitor {
             })
     }
 
+    /// classify the paths in a paths watcher by whether they're direct or relayed
+    fn extract_connection_type<T: Watcher<Value = iroh::endpoint::PathInfoList>>(
+        paths_watcher: &T,
+    ) -> ConnectionType {
+        let paths = paths_watcher.peek();
+        match paths.iter().find(|p| p.is_selected()) {
+            Some(path) if Self::is_direct(&path) => ConnectionType::Direct,
+            Some(_) if paths.iter().any(|p| Self::is_direct(&p)) => ConnectionType::Mixed,
+            Some(_) => ConnectionType::Relay,
+            None => ConnectionType::None,
+        }
+    }
+
+    fn is_direct(path: &iroh::endpoint::PathInfo) -> bool {
+        matches!(path.remote_addr(), TransportAddr::Ip(_))
+    }
+
     /// update bandwidth for a specific peer from application-level download data
     pub fn update_peer_bandwidth(&self, endpoint_id: &EndpointId, bandwidth: PeerBandwidth) {
         let mut conns = self.connections.write().unwrap();
@@ -247,6 +284,15 @@ impl ConnectionMonitor {
         conns.get(endpoint_id).and_then(|data| data.latency())
     }
 
+    /// get the connection type for a specific endpoint, [`ConnectionType::None`] if we've never connected
+    pub fn get_connection_type(&self, endpoint_id: &EndpointId) -> ConnectionType {
+        let conns = self.connections.read().unwrap();
+        conns
+            .get(endpoint_id)
+            .map(|data| data.connection_type)
+            .unwrap_or(ConnectionType::None)
+    }
+
     /// get measured throughput for a specific endpoint
     pub fn get_bandwidth(&self, endpoint_id: &EndpointId) -> Option<PeerBandwidth> {
         let conns = self.connections.read().unwrap();
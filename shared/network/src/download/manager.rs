@@ -1,7 +1,9 @@
 use crate::{
-    ModelRequestType, Networkable,
-    p2p_model_sharing::{TransmittableModelConfig, TransmittableModelParameter},
-    serialized_distro::TransmittableDistroResult,
+    Downloadable, ModelRequestType, Networkable,
+    p2p_model_sharing::{
+        TransmittableModelConfig, TransmittableModelParameter, TransmittableModelParameterBorrowed,
+    },
+    serialized_distro::{TransmittableDistroResult, TransmittableDistroResultV0},
 };
 
 use anyhow::{Result, anyhow};
@@ -12,21 +14,94 @@ use iroh_blobs::api::Tag;
 use iroh_blobs::api::downloader::DownloadProgressItem;
 use iroh_blobs::ticket::BlobTicket;
 use psyche_event_sourcing::event;
-use serde::{Deserialize, Serialize};
-use std::{fmt::Debug, future::Future, marker::PhantomData, pin::Pin, sync::Arc};
+use serde::{Deserialize, Serialize, Serializer};
+use std::{borrow::Cow, fmt::Debug, future::Future, marker::PhantomData, pin::Pin, sync::Arc};
 use tokio::{
     sync::{Mutex, mpsc, oneshot},
     task::JoinHandle,
 };
 use tracing::{error, info, trace, warn};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(from = "TransmittableDownloadWire<'static>")]
 pub enum TransmittableDownload {
     DistroResult(TransmittableDistroResult),
     ModelParameter(TransmittableModelParameter),
     ModelConfig(TransmittableModelConfig),
 }
 
+/// Wire layout of [`TransmittableDownload`]. Variants are only ever appended, so a client
+/// that doesn't know a newer variant fails to decode it instead of misreading it.
+#[derive(Serialize, Deserialize)]
+enum TransmittableDownloadWire<'a> {
+    DistroResult(TransmittableDistroResultV0<'a>),
+    ModelParameter(Cow<'a, TransmittableModelParameter>),
+    ModelConfig(Cow<'a, TransmittableModelConfig>),
+    /// Distro results with int8 sparse values, which need their `sparse_val_scale`.
+    DistroResultV1(Cow<'a, TransmittableDistroResult>),
+}
+
+impl From<TransmittableDownloadWire<'_>> for TransmittableDownload {
+    fn from(value: TransmittableDownloadWire<'_>) -> Self {
+        match value {
+            TransmittableDownloadWire::DistroResult(result) => Self::DistroResult(result.into()),
+            TransmittableDownloadWire::ModelParameter(parameter) => {
+                Self::ModelParameter(parameter.into_owned())
+            }
+            TransmittableDownloadWire::ModelConfig(config) => {
+                Self::ModelConfig(config.into_owned())
+            }
+            TransmittableDownloadWire::DistroResultV1(result) => {
+                Self::DistroResult(result.into_owned())
+            }
+        }
+    }
+}
+
+impl Serialize for TransmittableDownload {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let wire = match self {
+            Self::DistroResult(result) => match result.as_v0() {
+                Some(result) => TransmittableDownloadWire::DistroResult(result),
+                None => TransmittableDownloadWire::DistroResultV1(Cow::Borrowed(result)),
+            },
+            Self::ModelParameter(parameter) => {
+                TransmittableDownloadWire::ModelParameter(Cow::Borrowed(parameter))
+            }
+            Self::ModelConfig(config) => {
+                TransmittableDownloadWire::ModelConfig(Cow::Borrowed(config))
+            }
+        };
+        wire.serialize(serializer)
+    }
+}
+
+/// Mirrors [`TransmittableDownloadWire`] variant for variant, so the wire format is the same,
+/// but borrows model parameters out of the buffer it's deserialized from.
+#[derive(Deserialize)]
+enum TransmittableDownloadRef<'a> {
+    DistroResult(TransmittableDistroResultV0<'a>),
+    #[serde(borrow)]
+    ModelParameterBorrowed(TransmittableModelParameterBorrowed<'a>),
+    ModelConfig(TransmittableModelConfig),
+    DistroResultV1(TransmittableDistroResult),
+}
+
+impl Downloadable for TransmittableDownload {
+    /// Deserializes a downloaded blob without copying model parameter values out of it,
+    /// they keep a reference to `blob` instead.
+    fn from_blob(blob: &Bytes) -> Result<Self> {
+        Ok(match postcard::from_bytes(blob)? {
+            TransmittableDownloadRef::DistroResult(result) => Self::DistroResult(result.into()),
+            TransmittableDownloadRef::ModelParameterBorrowed(parameter) => {
+                Self::ModelParameter(parameter.into_owned(blob))
+            }
+            TransmittableDownloadRef::ModelConfig(config) => Self::ModelConfig(config),
+            TransmittableDownloadRef::DistroResultV1(result) => Self::DistroResult(result),
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum DownloadType {
     // Distro result variant with the list of possible peers that we might ask for the blob in case of failure with the original
@@ -42,6 +117,24 @@ impl DownloadType {
             Self::ModelSharing(..) => "model_sharing",
         }
     }
+
+    /// Distro results block the training step, so they jump ahead of model sharing transfers.
+    pub fn default_priority(&self) -> DownloadPriority {
+        match self {
+            Self::DistroResult(..) => DownloadPriority::Critical,
+            Self::ModelSharing(..) => DownloadPriority::Normal,
+        }
+    }
+}
+
+/// Which downloads get a transfer slot first when more than [`crate::NetworkConfig::max_concurrent_downloads`]
+/// are waiting, and the order in which the download manager services in-flight transfers.
+/// Variants are declared from lowest to highest priority so that `Ord` sorts them accordingly.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DownloadPriority {
+    Background,
+    Normal,
+    Critical,
 }
 
 #[derive(Debug)]
@@ -52,6 +145,7 @@ struct Download {
     last_offset: u64,
     total_size: u64,
     download_type: DownloadType,
+    priority: DownloadPriority,
 }
 
 struct ReadingFinishedDownload {
@@ -59,12 +153,14 @@ struct ReadingFinishedDownload {
     tag: Tag,
     download: oneshot::Receiver<Bytes>,
     download_type: DownloadType,
+    priority: DownloadPriority,
 }
 
 impl Debug for ReadingFinishedDownload {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ReadingFinishedDownload")
             .field("blob_ticket", &self.blob_ticket)
+            .field("priority", &self.priority)
             .field("reading", &"...")
             .finish()
     }
@@ -76,6 +172,7 @@ impl Download {
         tag: Tag,
         download: mpsc::UnboundedReceiver<Result<DownloadProgressItem>>,
         download_type: DownloadType,
+        priority: DownloadPriority,
     ) -> Self {
         Self {
             blob_ticket,
@@ -84,10 +181,19 @@ impl Download {
             last_offset: 0,
             total_size: 0,
             download_type,
+            priority,
         }
     }
 }
 
+/// Inserts `item` after every entry of equal or higher priority, keeping the queue sorted
+/// highest-priority-first while preserving FIFO order within a priority level.
+fn insert_by_priority<T>(queue: &mut Vec<T>, item: T, priority: impl Fn(&T) -> DownloadPriority) {
+    let item_priority = priority(&item);
+    let pos = queue.partition_point(|queued| priority(queued) >= item_priority);
+    queue.insert(pos, item);
+}
+
 #[derive(Clone, Debug)]
 pub struct DownloadUpdate {
     pub blob_ticket: BlobTicket,
@@ -97,6 +203,7 @@ pub struct DownloadUpdate {
     pub total_size: u64,
     pub all_done: bool,
     pub download_type: DownloadType,
+    pub priority: DownloadPriority,
 }
 
 pub struct DownloadComplete<D: Networkable> {
@@ -157,7 +264,7 @@ impl<D: Networkable> Debug for DownloadManager<D> {
     }
 }
 
-impl<D: Networkable + Send + 'static> DownloadManager<D> {
+impl<D: Downloadable + Send + 'static> DownloadManager<D> {
     pub fn new() -> Result<Self> {
         let (event_sender, event_receiver) = mpsc::unbounded_channel();
         let (tx_new_item, mut rx_new_item) = mpsc::unbounded_channel();
@@ -207,14 +314,16 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
         tag: Tag,
         progress: mpsc::UnboundedReceiver<Result<DownloadProgressItem>>,
         download_type: DownloadType,
+        priority: DownloadPriority,
     ) {
         let downloads = self.downloads.clone();
         let sender = self.tx_new_item.clone();
         tokio::spawn(async move {
-            downloads
-                .lock()
-                .await
-                .push(Download::new(blob_ticket, tag, progress, download_type));
+            insert_by_priority(
+                &mut *downloads.lock().await,
+                Download::new(blob_ticket, tag, progress, download_type, priority),
+                |d| d.priority,
+            );
 
             if let Err(err) = sender.send(()) {
                 error!("{err:#}");
@@ -228,16 +337,22 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
         tag: Tag,
         download: oneshot::Receiver<Bytes>,
         download_type: DownloadType,
+        priority: DownloadPriority,
     ) {
         let reading = self.reading.clone();
         let sender = self.tx_new_item.clone();
         tokio::spawn(async move {
-            reading.lock().await.push(ReadingFinishedDownload {
-                blob_ticket,
-                tag,
-                download,
-                download_type,
-            });
+            insert_by_priority(
+                &mut *reading.lock().await,
+                ReadingFinishedDownload {
+                    blob_ticket,
+                    tag,
+                    download,
+                    download_type,
+                    priority,
+                },
+                |r| r.priority,
+            );
             if let Err(err) = sender.send(()) {
                 error!("{err:#}");
             }
@@ -261,6 +376,8 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
             Read(usize, Result<Bytes>),
         }
 
+        let read_priorities: Vec<DownloadPriority> = reading.iter().map(|r| r.priority).collect();
+
         let download_futures = downloads.iter_mut().enumerate().map(|(i, download)| {
             Box::pin(async move {
                 FutureResult::Download(
@@ -281,8 +398,18 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
             }) as Pin<Box<dyn Future<Output = FutureResult> + Send>>
         });
 
-        let all_futures: Vec<Pin<Box<dyn Future<Output = FutureResult> + Send>>> =
-            download_futures.chain(read_futures).collect();
+        // Both queues are kept sorted highest-priority-first and `select_all` polls in order,
+        // so when several futures are ready at once the critical ones are handled first.
+        // Reads of finished critical downloads go ahead of every in-flight download.
+        let (critical_reads, other_reads): (Vec<_>, Vec<_>) = read_futures
+            .zip(read_priorities)
+            .partition(|(_, priority)| *priority == DownloadPriority::Critical);
+        let all_futures: Vec<Pin<Box<dyn Future<Output = FutureResult> + Send>>> = critical_reads
+            .into_iter()
+            .map(|(f, _)| f)
+            .chain(download_futures)
+            .chain(other_reads.into_iter().map(|(f, _)| f))
+            .collect();
 
         let result = select_all(all_futures).await.0;
 
@@ -291,7 +418,7 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
                 Self::handle_download_progress(downloads, result, index)
             }
             FutureResult::Read(index, result) => {
-                let downloader: ReadingFinishedDownload = reading.swap_remove(index);
+                let downloader: ReadingFinishedDownload = reading.remove(index);
                 tokio::task::spawn_blocking(move || Self::handle_read_result(downloader, result))
                     .await
                     .unwrap()
@@ -325,6 +452,7 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
                         total_size: 0,
                         all_done: false,
                         download_type: download.download_type.clone(),
+                        priority: download.priority,
                     }))
                 }
                 DownloadProgressItem::Progress(bytes_amount) => {
@@ -338,6 +466,7 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
                         total_size: download.total_size,
                         all_done: false,
                         download_type: download.download_type.clone(),
+                        priority: download.priority,
                     }))
                 }
                 // We're using the Blob format so there's only one part for each blob
@@ -350,6 +479,7 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
                         total_size: download.total_size,
                         all_done: true,
                         download_type: download.download_type.clone(),
+                        priority: download.priority,
                     }))
                 }
                 DownloadProgressItem::DownloadError => {
@@ -385,6 +515,7 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
                         total_size: download.total_size,
                         all_done: false,
                         download_type: download.download_type.clone(),
+                        priority: download.priority,
                     }))
                 }
             },
@@ -397,7 +528,7 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
         };
         match &event {
             Some(DownloadManagerEvent::Update(DownloadUpdate { all_done, .. })) if *all_done => {
-                let removed = downloads.swap_remove(index);
+                let removed = downloads.remove(index);
                 trace!(
                     "Since download is complete, removing it: idx {index}, hash {}",
                     removed.blob_ticket.hash()
@@ -406,7 +537,7 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
             Some(DownloadManagerEvent::Failed(DownloadFailed {
                 blob_ticket, error, ..
             })) => {
-                downloads.swap_remove(index);
+                downloads.remove(index);
                 warn!(
                     "Download error, removing it. idx {index}, hash {}, node provider {}: {}",
                     blob_ticket.hash(),
@@ -426,7 +557,7 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
         result: Result<Bytes>,
     ) -> Option<DownloadManagerEvent<D>> {
         match result {
-            Ok(bytes) => match postcard::from_bytes(&bytes) {
+            Ok(bytes) => match D::from_blob(&bytes) {
                 Ok(decoded) => Some(DownloadManagerEvent::Complete(DownloadComplete {
                     data: decoded,
                     from: downloader.blob_ticket.addr().id,
@@ -435,7 +566,7 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
                 Err(err) => Some(DownloadManagerEvent::Failed(DownloadFailed {
                     blob_ticket: downloader.blob_ticket,
                     tag: downloader.tag,
-                    error: err.into(),
+                    error: err,
                     download_type: downloader.download_type.clone(),
                 })),
             },
@@ -448,3 +579,111 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ModelRequestType;
+
+    #[test]
+    fn test_insert_by_priority_orders_highest_first() {
+        let mut queue = Vec::new();
+        for (id, priority) in [
+            (0, DownloadPriority::Normal),
+            (1, DownloadPriority::Background),
+            (2, DownloadPriority::Critical),
+            (3, DownloadPriority::Normal),
+            (4, DownloadPriority::Critical),
+        ] {
+            insert_by_priority(&mut queue, (id, priority), |(_, p)| *p);
+        }
+        let ids: Vec<_> = queue.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![2, 4, 0, 3, 1]);
+    }
+
+    #[test]
+    fn test_default_priority() {
+        assert_eq!(
+            DownloadType::DistroResult(vec![]).default_priority(),
+            DownloadPriority::Critical
+        );
+        assert_eq!(
+            DownloadType::ModelSharing(ModelRequestType::Config).default_priority(),
+            DownloadPriority::Normal
+        );
+    }
+
+    #[test]
+    fn test_from_blob_borrows_model_parameter() {
+        let value = vec![7u8; 1024];
+        let download = TransmittableDownload::ModelParameter(TransmittableModelParameter::new(
+            b"model.embed_tokens.weight".to_vec(),
+            value.clone(),
+        ));
+        let blob = Bytes::from(download.to_bytes());
+
+        let TransmittableDownload::ModelParameter(parameter) =
+            TransmittableDownload::from_blob(&blob).unwrap()
+        else {
+            panic!("expected a model parameter");
+        };
+        assert_eq!(parameter.name().unwrap(), "model.embed_tokens.weight");
+        assert_eq!(parameter.value_bytes(), &value[..]);
+        let blob_range = blob.as_ptr_range();
+        assert!(blob_range.contains(&parameter.value_bytes().as_ptr()));
+
+        // the owned deserializer still reads the same bytes
+        let TransmittableDownload::ModelParameter(owned) =
+            TransmittableDownload::from_bytes(&blob).unwrap()
+        else {
+            panic!("expected a model parameter");
+        };
+        assert_eq!(owned.value_bytes(), &value[..]);
+    }
+
+    fn distro_download(sparse_val_scale: Option<f32>) -> TransmittableDownload {
+        let sparse_val = match sparse_val_scale {
+            Some(_) => tch::Tensor::from_slice(&[-127i8, 3, 64]),
+            None => tch::Tensor::from_slice(&[-1.0f32, 0.25, 0.5]),
+        };
+        TransmittableDownload::DistroResult(TransmittableDistroResult {
+            step: 3,
+            trainer_nonce: 1,
+            batch_id: psyche_core::BatchId((0, 7).into()),
+            distro_results: vec![crate::SerializedDistroResult {
+                sparse_idx: (&tch::Tensor::from_slice(&[0i64, 5, 9]))
+                    .try_into()
+                    .unwrap(),
+                sparse_val: (&sparse_val).try_into().unwrap(),
+                xshape: vec![4, 4],
+                totalk: 16,
+                sparse_val_scale,
+            }],
+        })
+    }
+
+    #[test]
+    fn test_distro_result_wire_versions() {
+        for (sparse_val_scale, variant) in [(None, 0u8), (Some(0.5), 3u8)] {
+            let download = distro_download(sparse_val_scale);
+            let bytes = download.to_bytes();
+            // postcard writes the variant index first, unscaled results keep the original one
+            assert_eq!(bytes[0], variant);
+
+            for decoded in [
+                TransmittableDownload::from_bytes(&bytes).unwrap(),
+                TransmittableDownload::from_blob(&Bytes::from(bytes.clone())).unwrap(),
+            ] {
+                let (
+                    TransmittableDownload::DistroResult(decoded),
+                    TransmittableDownload::DistroResult(original),
+                ) = (decoded, &download)
+                else {
+                    panic!("expected a distro result");
+                };
+                assert_eq!(decoded.distro_results, original.distro_results);
+                assert_eq!(decoded.comptue_hash(), original.comptue_hash());
+            }
+        }
+    }
+}
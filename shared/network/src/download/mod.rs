@@ -1,8 +1,10 @@
 mod manager;
 mod scheduler;
+mod slots;
 
 pub use manager::{
-    DownloadComplete, DownloadFailed, DownloadManager, DownloadManagerEvent, DownloadType,
-    DownloadUpdate, TransmittableDownload,
+    DownloadComplete, DownloadFailed, DownloadManager, DownloadManagerEvent, DownloadPriority,
+    DownloadType, DownloadUpdate, TransmittableDownload,
 };
 pub use scheduler::{DownloadSchedulerHandle, ReadyRetry, RetryConfig, RetryQueueResult};
+pub(crate) use slots::DownloadSlots;
@@ -0,0 +1,192 @@
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::oneshot;
+
+use super::DownloadPriority;
+
+/// Limits how many blob transfers run at once. Freed slots go to the highest priority download
+/// waiting for one, and to the one that asked first within a priority.
+#[derive(Clone, Debug)]
+pub struct DownloadSlots(Arc<Mutex<SlotsState>>);
+
+#[derive(Debug)]
+struct SlotsState {
+    free: usize,
+    next_ticket: u64,
+    waiting: BinaryHeap<Waiter>,
+}
+
+#[derive(Debug)]
+struct Waiter {
+    priority: DownloadPriority,
+    ticket: u64,
+    tx: oneshot::Sender<()>,
+}
+
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap pops the greatest, so earlier tickets have to compare greater
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.ticket.cmp(&self.ticket))
+    }
+}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+/// A running transfer's slot, handed to the next waiting download when dropped.
+#[derive(Debug)]
+pub struct DownloadSlot(DownloadSlots);
+
+impl Drop for DownloadSlot {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+/// A slot request that may have been granted after its download stopped waiting for it.
+struct PendingSlot {
+    rx: Option<oneshot::Receiver<()>>,
+    slots: DownloadSlots,
+}
+
+impl Drop for PendingSlot {
+    fn drop(&mut self) {
+        if let Some(mut rx) = self.rx.take() {
+            rx.close();
+            if rx.try_recv().is_ok() {
+                self.slots.release();
+            }
+        }
+    }
+}
+
+impl DownloadSlots {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self(Arc::new(Mutex::new(SlotsState {
+            free: max_concurrent.max(1),
+            next_ticket: 0,
+            waiting: BinaryHeap::new(),
+        })))
+    }
+
+    /// Waits until a slot is free for a download of `priority`.
+    pub async fn acquire(&self, priority: DownloadPriority) -> DownloadSlot {
+        let rx = {
+            let mut state = self.0.lock().unwrap();
+            if state.free > 0 && state.waiting.is_empty() {
+                state.free -= 1;
+                return DownloadSlot(self.clone());
+            }
+            let (tx, rx) = oneshot::channel();
+            let ticket = state.next_ticket;
+            state.next_ticket += 1;
+            state.waiting.push(Waiter {
+                priority,
+                ticket,
+                tx,
+            });
+            rx
+        };
+        let mut pending = PendingSlot {
+            rx: Some(rx),
+            slots: self.clone(),
+        };
+        // a waiter's sender is only ever dropped by sending it a slot
+        let _ = pending.rx.as_mut().unwrap().await;
+        pending.rx = None;
+        DownloadSlot(self.clone())
+    }
+
+    fn release(&self) {
+        let mut state = self.0.lock().unwrap();
+        while let Some(waiter) = state.waiting.pop() {
+            if waiter.tx.send(()).is_ok() {
+                return;
+            }
+        }
+        state.free += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_slots_go_to_highest_priority_first() {
+        let slots = DownloadSlots::new(1);
+        let first = slots.acquire(DownloadPriority::Background).await;
+
+        let (granted_tx, mut granted_rx) = mpsc::unbounded_channel();
+        for (name, priority) in [
+            ("background", DownloadPriority::Background),
+            ("normal 1", DownloadPriority::Normal),
+            ("critical", DownloadPriority::Critical),
+            ("normal 2", DownloadPriority::Normal),
+        ] {
+            let slots = slots.clone();
+            let granted_tx = granted_tx.clone();
+            tokio::spawn(async move {
+                let _slot = slots.acquire(priority).await;
+                granted_tx.send(name).unwrap();
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            });
+            // let each task queue up before the next, so request order is known
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(granted_rx.try_recv().is_err());
+
+        drop(first);
+        let mut order = Vec::new();
+        for _ in 0..4 {
+            order.push(granted_rx.recv().await.unwrap());
+        }
+        assert_eq!(
+            order,
+            vec!["critical", "normal 1", "normal 2", "background"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_frees_its_slot() {
+        let slots = DownloadSlots::new(1);
+        let first = slots.acquire(DownloadPriority::Normal).await;
+
+        let waiting = tokio::spawn({
+            let slots = slots.clone();
+            async move { slots.acquire(DownloadPriority::Critical).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        waiting.abort();
+        let _ = waiting.await;
+        drop(first);
+
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            slots.acquire(DownloadPriority::Background),
+        )
+        .await
+        .expect("the cancelled waiter's slot should be free again");
+    }
+}
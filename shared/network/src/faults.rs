@@ -0,0 +1,154 @@
+//! Network faults for tests to inject into a [`crate::TcpServer`] and into blob downloads.
+//! They only exist with the `testing` feature; without it, every check here is a no-op.
+
+use iroh::PublicKey;
+
+#[cfg(feature = "testing")]
+use crate::Hash;
+#[cfg(feature = "testing")]
+use rand::Rng;
+#[cfg(feature = "testing")]
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+#[cfg(feature = "testing")]
+use tokio::sync::{broadcast, mpsc};
+#[cfg(feature = "testing")]
+use tracing::info;
+
+/// Extra time to wait before starting the download of a blob, for simulating slow peers
+#[cfg(feature = "testing")]
+pub type DownloadDelays = Arc<Mutex<HashMap<Hash, Duration>>>;
+
+/// Faults a [`crate::TcpServer`] can be told to simulate through
+/// [`crate::TcpServer::fault_injector`]
+#[cfg(feature = "testing")]
+#[derive(Debug, Clone)]
+pub enum ServerFault {
+    /// Drop the client's connection and refuse it until `duration` has passed
+    Disconnect {
+        client: PublicKey,
+        duration: Duration,
+    },
+    /// Silently drop this fraction of the messages to and from the client
+    DropMessages { client: PublicKey, fraction: f64 },
+    /// Remove all previously injected faults
+    Clear,
+}
+
+#[cfg(feature = "testing")]
+#[derive(Default)]
+struct Faults {
+    refused_until: HashMap<PublicKey, Instant>,
+    drop_fractions: HashMap<PublicKey, f64>,
+}
+
+#[cfg(feature = "testing")]
+impl Faults {
+    fn apply(&mut self, fault: ServerFault) {
+        match fault {
+            ServerFault::Disconnect { client, duration } => {
+                self.refused_until.insert(client, Instant::now() + duration);
+            }
+            ServerFault::DropMessages { client, fraction } => {
+                self.drop_fractions.insert(client, fraction.clamp(0.0, 1.0));
+            }
+            ServerFault::Clear => *self = Self::default(),
+        }
+    }
+}
+
+/// The faults injected into one server, shared by all of its connections
+#[derive(Clone)]
+pub(crate) struct ServerFaults {
+    #[cfg(feature = "testing")]
+    faults: Arc<Mutex<Faults>>,
+    #[cfg(feature = "testing")]
+    kick_tx: broadcast::Sender<PublicKey>,
+}
+
+impl ServerFaults {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "testing")]
+            faults: Default::default(),
+            #[cfg(feature = "testing")]
+            kick_tx: broadcast::channel(16).0,
+        }
+    }
+
+    /// Spawns the task applying every fault sent to the returned channel
+    #[cfg(feature = "testing")]
+    pub fn spawn_injector(&self) -> mpsc::UnboundedSender<ServerFault> {
+        let (faults_tx, mut faults_rx) = mpsc::unbounded_channel();
+        let faults = self.faults.clone();
+        let kick_tx = self.kick_tx.clone();
+        tokio::spawn(async move {
+            while let Some(fault) = faults_rx.recv().await {
+                info!("Injecting fault {fault:?}");
+                if let ServerFault::Disconnect { client, .. } = &fault {
+                    let _ = kick_tx.send(*client);
+                }
+                faults.lock().unwrap().apply(fault);
+            }
+        });
+        faults_tx
+    }
+
+    pub fn is_refused(&self, _client: &PublicKey) -> bool {
+        #[cfg(feature = "testing")]
+        return self
+            .faults
+            .lock()
+            .unwrap()
+            .refused_until
+            .get(_client)
+            .is_some_and(|until| Instant::now() < *until);
+        #[cfg(not(feature = "testing"))]
+        false
+    }
+
+    pub fn should_drop(&self, _client: &PublicKey) -> bool {
+        #[cfg(feature = "testing")]
+        return self
+            .faults
+            .lock()
+            .unwrap()
+            .drop_fractions
+            .get(_client)
+            .is_some_and(|fraction| rand::rng().random_bool(*fraction));
+        #[cfg(not(feature = "testing"))]
+        false
+    }
+
+    /// Subscribes to disconnects, before the connection knows who it's talking to
+    pub fn kicks(&self) -> Kicks {
+        Kicks {
+            #[cfg(feature = "testing")]
+            kick_rx: self.kick_tx.subscribe(),
+        }
+    }
+}
+
+/// One connection's subscription to [`ServerFault::Disconnect`]
+pub(crate) struct Kicks {
+    #[cfg(feature = "testing")]
+    kick_rx: broadcast::Receiver<PublicKey>,
+}
+
+impl Kicks {
+    /// Resolves once `client` is told to disconnect, never without the `testing` feature
+    pub async fn wait_for(&mut self, _client: PublicKey) {
+        #[cfg(feature = "testing")]
+        loop {
+            match self.kick_rx.recv().await {
+                Ok(kicked) if kicked == _client => return,
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+        std::future::pending::<()>().await
+    }
+}
@@ -0,0 +1,91 @@
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// Marks a gossip payload that packs several signed messages together.
+/// It's followed by the [`BATCH_VERSION`] byte, then each message as a big-endian `u32` length and its bytes.
+const BATCH_MAGIC: &[u8; 4] = b"PSYB";
+/// Layout of the batch after the magic. Bump it whenever that layout changes,
+/// so older clients drop batches they can't read instead of misparsing them.
+const BATCH_VERSION: u8 = 1;
+const LENGTH_PREFIX_SIZE: usize = 4;
+
+/// Size of the payload [`encode`] would produce for these messages.
+pub(crate) fn encoded_len(messages: &[Bytes]) -> usize {
+    BATCH_MAGIC.len()
+        + 1
+        + messages
+            .iter()
+            .map(|m| LENGTH_PREFIX_SIZE + m.len())
+            .sum::<usize>()
+}
+
+pub(crate) fn encode(messages: &[Bytes]) -> Bytes {
+    let mut buf = BytesMut::with_capacity(encoded_len(messages));
+    buf.put_slice(BATCH_MAGIC);
+    buf.put_u8(BATCH_VERSION);
+    for message in messages {
+        buf.put_u32(message.len() as u32);
+        buf.put_slice(message);
+    }
+    buf.freeze()
+}
+
+/// Splits a batched payload back into its messages.
+/// Returns `None` if `payload` isn't a well-formed batch of this version, in which case it should be treated as a single message.
+pub(crate) fn decode(payload: &[u8]) -> Option<Vec<&[u8]>> {
+    let (&version, mut rest) = payload.strip_prefix(BATCH_MAGIC)?.split_first()?;
+    if version != BATCH_VERSION {
+        return None;
+    }
+    let mut messages = Vec::new();
+    while !rest.is_empty() {
+        let (len, tail) = rest.split_first_chunk::<LENGTH_PREFIX_SIZE>()?;
+        let len = u32::from_be_bytes(*len) as usize;
+        if tail.len() < len {
+            return None;
+        }
+        let (message, tail) = tail.split_at(len);
+        messages.push(message);
+        rest = tail;
+    }
+    (!messages.is_empty()).then_some(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let messages = vec![
+            Bytes::from_static(b"first"),
+            Bytes::from_static(b""),
+            Bytes::from_static(b"third message"),
+        ];
+        let encoded = encode(&messages);
+        assert_eq!(encoded.len(), encoded_len(&messages));
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(
+            decoded,
+            vec![&b"first"[..], &b""[..], &b"third message"[..]]
+        );
+    }
+
+    #[test]
+    fn test_rejects_non_batches() {
+        assert!(decode(b"not a batch").is_none());
+        assert!(decode(BATCH_MAGIC).is_none());
+        assert!(decode(&[&BATCH_MAGIC[..], &[BATCH_VERSION]].concat()).is_none());
+
+        let mut truncated = encode(&[Bytes::from_static(b"hello")]).to_vec();
+        truncated.pop();
+        assert!(decode(&truncated).is_none());
+    }
+
+    #[test]
+    fn test_rejects_other_versions() {
+        let mut encoded = encode(&[Bytes::from_static(b"hello")]).to_vec();
+        assert_eq!(encoded[BATCH_MAGIC.len()], BATCH_VERSION);
+        encoded[BATCH_MAGIC.len()] = BATCH_VERSION + 1;
+        assert!(decode(&encoded).is_none());
+    }
+}
@@ -1,7 +1,7 @@
 use allowlist::Allowlist;
 use anyhow::{Context, Result, anyhow};
 use bytes::Bytes;
-use download::{DownloadManager, DownloadManagerEvent, DownloadUpdate};
+use download::{DownloadManager, DownloadManagerEvent, DownloadSlots, DownloadUpdate};
 use futures_util::{StreamExt, TryFutureExt};
 use iroh::{EndpointAddr, RelayConfig};
 use iroh::{endpoint::QuicTransportConfig, protocol::Router};
@@ -9,14 +9,13 @@ use iroh_blobs::api::Tag;
 use iroh_blobs::store::GcConfig;
 use iroh_blobs::{
     BlobsProtocol,
-    api::downloader::Downloader,
+    api::{blobs::BlobStatus, downloader::Downloader},
     store::mem::{MemStore, Options as MemStoreOptions},
     util::connection_pool::Options as PoolOptions,
 };
 use iroh_gossip::{
     api::{GossipReceiver, GossipSender},
     net::Gossip,
-    proto::{HyparviewConfig, PlumtreeConfig},
 };
 use iroh_services::{API_SECRET_ENV_VAR_NAME, ApiSecret, caps::NetDiagnosticsCap};
 use n0_future::task::AbortOnDropHandle;
@@ -30,13 +29,15 @@ use router::{SupportedProtocols, spawn_router};
 use state::State;
 use std::str::FromStr;
 use std::{
+    collections::VecDeque,
     fmt::Debug,
     hash::{DefaultHasher, Hash as _, Hasher},
     marker::PhantomData,
     net::{IpAddr, Ipv4Addr, SocketAddrV4},
-    sync::Arc,
+    sync::{Arc, Mutex, OnceLock},
     time::Duration,
 };
+use thiserror::Error;
 use tokio::{
     io::AsyncReadExt,
     select,
@@ -56,11 +57,15 @@ pub use iroh_blobs::{BlobFormat, Hash, ticket::BlobTicket};
 
 pub mod allowlist;
 mod authenticable_identity;
+mod config;
 mod connection_monitor;
 mod download;
+mod faults;
+mod gossip_batch;
 mod latency_sorted;
 mod local_discovery;
 mod p2p_model_sharing;
+mod rate_limit;
 pub mod router;
 mod serde;
 mod serializable_kind;
@@ -76,11 +81,14 @@ mod util;
 mod test;
 
 pub use authenticable_identity::raw_p2p_verify;
-pub use connection_monitor::{ConnectionData, ConnectionMonitor, PeerBandwidth};
+pub use config::{GossipConfig, NetworkConfig};
+pub use connection_monitor::{ConnectionData, ConnectionMonitor, ConnectionType, PeerBandwidth};
 pub use download::{
-    DownloadComplete, DownloadFailed, DownloadSchedulerHandle, DownloadType, ReadyRetry,
-    RetryConfig, RetryQueueResult, TransmittableDownload,
+    DownloadComplete, DownloadFailed, DownloadPriority, DownloadSchedulerHandle, DownloadType,
+    ReadyRetry, RetryConfig, RetryQueueResult, TransmittableDownload,
 };
+#[cfg(feature = "testing")]
+pub use faults::{DownloadDelays, ServerFault};
 pub use iroh::protocol::ProtocolHandler;
 pub use iroh::{Endpoint, EndpointId, PublicKey, SecretKey};
 use iroh_relay::{RelayMap, RelayQuicConfig};
@@ -88,23 +96,27 @@ pub use latency_sorted::LatencySorted;
 pub use p2p_model_sharing::{
     ALPN, ModelRequestType, SharableModel, SharableModelError, TransmittableModelConfig,
 };
-pub use serde::Networkable;
+pub use serde::{Downloadable, Networkable};
 pub use serialized_distro::{
     SerializeDistroResultError, SerializedDistroResult, TransmittableDistroResult,
     distro_results_from_reader, distro_results_to_bytes,
 };
 pub use signed_message::SignedMessage;
-pub use tcp::{ClientNotification, TcpClient, TcpServer};
+pub use tcp::{ClientNotification, ClientTlsConfig, ServerTlsConfig, TcpClient, TcpServer};
 pub use tui::{NetworkTUIState, NetworkTui};
 use url::Url;
 pub use util::fmt_bytes;
 
 use crate::allowlist::AllowlistHook;
 use crate::p2p_model_sharing::ModelSharing;
+use crate::rate_limit::TokenBucket;
 
 const USE_RELAY_HOSTNAME: &str = "use1-1.relay.nousresearch.psyche.iroh.link";
 const USW_RELAY_HOSTNAME: &str = "usw1-1.relay.nousresearch.psyche.iroh.link";
 
+/// Broadcasts bigger than this fraction of the gossip message size limit are logged as a warning.
+const GOSSIP_MESSAGE_SIZE_WARN_FRACTION: f64 = 0.8;
+
 /// How should this node discover other nodes?
 ///
 /// In almost all cases, you want "N0", for over-the-internet communication.
@@ -174,6 +186,20 @@ impl From<P2PEndpointInfo> for PeerConnection {
     }
 }
 
+#[derive(Error, Debug)]
+pub enum NetworkError {
+    /// Broadcasting faster than [`NetworkConfig::max_broadcasts_per_second`]
+    #[error("gossip broadcast rate limit exceeded")]
+    RateLimited,
+}
+
+/// How much is held in a [`NetworkConnection`]'s in-memory blob store.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BlobStoreStats {
+    pub total_blobs: usize,
+    pub total_bytes: u64,
+}
+
 pub struct NetworkConnection<BroadcastMessage, Download>
 where
     BroadcastMessage: Networkable,
@@ -188,12 +214,23 @@ where
     rx_model_parameter_req: UnboundedReceiver<ParameterSharingMessage>,
     rx_model_config_req: UnboundedReceiver<ModelConfigSharingMessage>,
     download_manager: DownloadManager<Download>,
+    download_slots: DownloadSlots,
+    /// Messages unpacked from a batched gossip payload that haven't been returned by `poll_next` yet.
+    pending_messages: VecDeque<(PublicKey, BroadcastMessage)>,
     _broadcast_message: PhantomData<BroadcastMessage>,
     _download: PhantomData<Download>,
     update_stats_interval: Interval,
+    /// sweeps expired entries out of the allowlist we were created with
+    remove_expired_allowlist_entries: Box<dyn Fn() + Send + Sync>,
     metrics: Arc<ClientMetrics>,
     endpoint: Endpoint,
     connection_monitor: ConnectionMonitor,
+    config: NetworkConfig,
+    /// whether the blob store was over `config.blob_store_warn_bytes` at the last stats update
+    blob_store_over_warn: bool,
+    #[cfg(feature = "testing")]
+    download_delays: DownloadDelays,
+    broadcast_rate_limiter: Option<Mutex<TokenBucket>>,
     _iroh_services_client: Option<iroh_services::Client>,
     _iroh_diagnostics_task: Option<AbortOnDropHandle<()>>,
 }
@@ -219,7 +256,7 @@ where
 impl<BroadcastMessage, Download> NetworkConnection<BroadcastMessage, Download>
 where
     BroadcastMessage: Networkable,
-    Download: Networkable,
+    Download: Downloadable,
 {
     #[allow(clippy::too_many_arguments)]
     pub async fn init<A: Allowlist + 'static + Send + std::marker::Sync>(
@@ -233,6 +270,7 @@ where
         allowlist: A,
         metrics: Arc<ClientMetrics>,
         cancel: Option<CancellationToken>,
+        config: NetworkConfig,
     ) -> Result<Self> {
         Self::init_internal::<A, iroh_gossip::net::Gossip>(
             run_id,
@@ -245,6 +283,7 @@ where
             allowlist,
             metrics,
             cancel,
+            config,
             None,
         )
         .await
@@ -265,6 +304,7 @@ where
         allowlist: A,
         metrics: Arc<ClientMetrics>,
         cancel: Option<CancellationToken>,
+        config: NetworkConfig,
         additional_protocol: (&'static [u8], P),
     ) -> Result<Self> {
         Self::init_internal(
@@ -278,6 +318,7 @@ where
             allowlist,
             metrics,
             cancel,
+            config,
             Some(additional_protocol),
         )
         .await
@@ -298,6 +339,7 @@ where
         allowlist: A,
         metrics: Arc<ClientMetrics>,
         cancel: Option<CancellationToken>,
+        config: NetworkConfig,
         additional_protocol: Option<(&'static [u8], P)>,
     ) -> Result<Self> {
         let secret_key = match secret_key {
@@ -339,11 +381,19 @@ where
         let connection_monitor = ConnectionMonitor::default();
 
         let allowlist_hook = AllowlistHook::new(allowlist.clone());
+        let remove_expired_allowlist_entries = {
+            let allowlist = allowlist.clone();
+            Box::new(move || allowlist.remove_expired())
+        };
 
         let endpoint = {
+            debug!(
+                "QUIC max idle timeout {:?}, keep-alive interval {:?}",
+                config.max_idle_timeout, config.keep_alive_interval
+            );
             let transport_config = QuicTransportConfig::builder()
-                .max_idle_timeout(Some(Duration::from_secs(120).try_into()?))
-                .keep_alive_interval(Duration::from_secs(5))
+                .max_idle_timeout(Some(config.max_idle_timeout.try_into()?))
+                .keep_alive_interval(config.keep_alive_interval)
                 .set_max_remote_nat_traversal_addresses(12)
                 .build();
 
@@ -436,17 +486,20 @@ where
 
         trace!("creating blobs store...");
 
-        let gc_interval: u64 = std::env::var("BLOBS_GC_INTERVAL_MILLIS")
-            .ok()
-            .and_then(|gc_interval_str| gc_interval_str.parse().ok())
-            .unwrap_or(10000);
-
+        info!(
+            "Blob store GC interval: {:?}, protected tags: {:?}",
+            config.gc_interval, config.gc_protected_tags
+        );
+        let gc_store = Arc::new(OnceLock::new());
         let store = MemStore::new_with_opts(MemStoreOptions {
             gc_config: Some(GcConfig {
-                interval: Duration::from_millis(gc_interval),
-                add_protected: None,
+                interval: config.gc_interval,
+                add_protected: (!config.gc_protected_tags.is_empty()).then(|| {
+                    config::protect_tags_cb(gc_store.clone(), config.gc_protected_tags.clone())
+                }),
             }),
         });
+        let _ = gc_store.set(store.as_ref().clone());
         let pool_options = PoolOptions {
             idle_timeout: Duration::from_secs(60),
             connect_timeout: Duration::from_secs(5),
@@ -458,19 +511,9 @@ where
 
         trace!("creating gossip...");
         let gossip = Gossip::builder()
-            .max_message_size(4096)
-            .membership_config(HyparviewConfig {
-                active_view_capacity: 8,
-                shuffle_interval: Duration::from_secs(30),
-                neighbor_request_timeout: Duration::from_secs(2),
-                ..HyparviewConfig::default()
-            })
-            .broadcast_config(PlumtreeConfig {
-                graft_timeout_2: Duration::from_millis(200),
-                message_cache_retention: Duration::from_secs(60),
-                message_id_retention: Duration::from_secs(2 * 60),
-                ..PlumtreeConfig::default()
-            })
+            .max_message_size(config.gossip.max_message_size)
+            .membership_config(config.gossip.membership.clone())
+            .broadcast_config(config.gossip.broadcast.clone())
             .spawn(endpoint.clone());
         trace!("gossip created!");
 
@@ -518,12 +561,22 @@ where
             metrics,
 
             update_stats_interval,
+            remove_expired_allowlist_entries,
             state: State::new(15),
             download_manager: DownloadManager::new()?,
+            download_slots: DownloadSlots::new(config.max_concurrent_downloads),
+            pending_messages: VecDeque::new(),
             _broadcast_message: Default::default(),
             _download: Default::default(),
             endpoint,
             connection_monitor,
+            broadcast_rate_limiter: config
+                .max_broadcasts_per_second
+                .map(|rate| Mutex::new(TokenBucket::new(rate))),
+            config,
+            blob_store_over_warn: false,
+            #[cfg(feature = "testing")]
+            download_delays: Default::default(),
             _iroh_services_client: iroh_services_client,
             _iroh_diagnostics_task: iroh_diagnostics_task,
         })
@@ -566,22 +619,118 @@ where
     }
 
     pub fn broadcast(&self, message: &BroadcastMessage) -> Result<()> {
+        self.check_broadcast_rate_limit()?;
         let gossip_tx = self.gossip_tx.clone();
         let encoded_message =
             SignedMessage::sign_and_encode(self.router.endpoint().secret_key(), message)?;
         let message_hash = hash_bytes(&encoded_message);
+        let size = encoded_message.len();
         debug!(
             name: "gossip_broadcast",
             message_hash = message_hash,
+            size = size,
             "broadcasted gossip message with hash {message_hash}: {:?}",
             message
         );
+        self.check_gossip_message_size(size);
 
         tokio::spawn(async move { gossip_tx.broadcast(encoded_message).await });
+        self.metrics.record_gossip_messages_sent(1);
         Ok(())
     }
 
-    pub fn start_download(&mut self, ticket: BlobTicket, tag: Tag, download_type: DownloadType) {
+    /// Takes a token from the broadcast rate limiter, if there is one.
+    fn check_broadcast_rate_limit(&self) -> Result<(), NetworkError> {
+        let Some(limiter) = &self.broadcast_rate_limiter else {
+            return Ok(());
+        };
+        if limiter.lock().unwrap().try_take() {
+            return Ok(());
+        }
+        self.metrics.record_gossip_broadcast_rate_limited();
+        Err(NetworkError::RateLimited)
+    }
+
+    /// Gossip drops messages over its size limit without telling us, so warn before we get there.
+    fn check_gossip_message_size(&self, size: usize) {
+        let max_message_size = self.config.gossip.max_message_size;
+        if size as f64 > max_message_size as f64 * GOSSIP_MESSAGE_SIZE_WARN_FRACTION {
+            warn!(
+                size,
+                max_message_size,
+                "Gossip message of {size} bytes is close to the {max_message_size} byte limit, it'll be dropped if it goes over"
+            );
+        }
+    }
+
+    /// Broadcasts several messages as a single gossip payload to cut per-message overhead.
+    /// If they don't fit together in one gossip message, they're sent individually instead.
+    /// Counts as a single call against the broadcast rate limit.
+    pub fn broadcast_batch(&self, messages: &[&BroadcastMessage]) -> Result<()> {
+        self.check_broadcast_rate_limit()?;
+        let secret_key = self.router.endpoint().secret_key();
+        let encoded_messages = messages
+            .iter()
+            .map(|message| SignedMessage::sign_and_encode(secret_key, *message))
+            .collect::<Result<Vec<_>>>()?;
+        let gossip_tx = self.gossip_tx.clone();
+
+        if encoded_messages.len() > 1
+            && gossip_batch::encoded_len(&encoded_messages) <= self.config.gossip.max_message_size
+        {
+            let payload = gossip_batch::encode(&encoded_messages);
+            let message_hash = hash_bytes(&payload);
+            debug!(
+                name: "gossip_broadcast_batch",
+                message_hash = message_hash,
+                count = messages.len(),
+                size = payload.len(),
+                "broadcasted batch of {} gossip messages with hash {message_hash}: {:?}",
+                messages.len(),
+                messages
+            );
+            tokio::spawn(async move { gossip_tx.broadcast(payload).await });
+            self.metrics
+                .record_gossip_messages_sent(encoded_messages.len() as u64);
+            return Ok(());
+        }
+
+        for (message, encoded_message) in messages.iter().zip(&encoded_messages) {
+            let message_hash = hash_bytes(encoded_message);
+            debug!(
+                name: "gossip_broadcast",
+                message_hash = message_hash,
+                size = encoded_message.len(),
+                "broadcasted gossip message with hash {message_hash}: {:?}",
+                message
+            );
+            self.check_gossip_message_size(encoded_message.len());
+        }
+        self.metrics
+            .record_gossip_messages_sent(encoded_messages.len() as u64);
+        tokio::spawn(async move {
+            for encoded_message in encoded_messages {
+                if let Err(err) = gossip_tx.broadcast(encoded_message).await {
+                    warn!("Failed to broadcast gossip message: {err:#}");
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Shares a set of per-blob download delays with this connection, for testing
+    #[cfg(feature = "testing")]
+    pub fn set_download_delays(&mut self, download_delays: DownloadDelays) {
+        self.download_delays = download_delays;
+    }
+
+    pub fn start_download(
+        &mut self,
+        ticket: BlobTicket,
+        tag: Tag,
+        download_type: DownloadType,
+        priority: DownloadPriority,
+    ) {
         let provider_endpoint_id = ticket.addr().clone();
         let ticket_hash = ticket.hash();
         let additional_peers_to_try = match download_type.clone() {
@@ -593,8 +742,8 @@ where
         let (tx, rx) = mpsc::unbounded_channel();
         // We share the tag with the download manager to keep track of the download progress on this blob but we actually set the tag here
         self.download_manager
-            .add(ticket, tag.clone(), rx, download_type.clone());
-        debug!(name: "blob_download_start", hash = %ticket_hash.fmt_short(), "started downloading blob {}", ticket_hash);
+            .add(ticket, tag.clone(), rx, download_type.clone(), priority);
+        debug!(name: "blob_download_start", hash = %ticket_hash.fmt_short(), ?priority, "started downloading blob {}", ticket_hash);
         event!(p2p::BlobDownloadRequested { blob: ticket_hash });
         let latency_sorted = LatencySorted::new(
             std::iter::once(provider_endpoint_id.id)
@@ -604,7 +753,25 @@ where
         );
         let download = self.downloader.download(ticket_hash, latency_sorted);
         let blob_store_clone = self.blobs_store.clone();
+        let download_slots = self.download_slots.clone();
+        #[cfg(feature = "testing")]
+        let delay = self
+            .download_delays
+            .lock()
+            .unwrap()
+            .get(&ticket_hash)
+            .copied();
         tokio::spawn(async move {
+            #[cfg(feature = "testing")]
+            if let Some(delay) = delay {
+                debug!(
+                    "delaying download of blob {} by {delay:?}",
+                    ticket_hash.fmt_short()
+                );
+                tokio::time::sleep(delay).await;
+            }
+            // held until the transfer is done, a timeout included
+            let _slot = download_slots.acquire(priority).await;
             let _ = blob_store_clone.tags().set(tag, ticket_hash).await;
             let progress = download.stream().await;
 
@@ -659,13 +826,14 @@ where
     }
 
     /// Removes all the tags from the store that are lower than the target tag.
-    /// Also removes all the tags used for the parameter sharing since this will run only in the Train state
+    /// Also removes all the tags used for the parameter sharing since this will run only in the Train state.
+    /// Tags listed in [`NetworkConfig::gc_protected_tags`] are left untouched.
     pub async fn remove_staled_tags(
         &mut self,
         target_distro_result_step: u32,
     ) -> anyhow::Result<()> {
         let store = self.blobs_store.as_ref().clone();
-        let model_tags_deleted = store.tags().delete_prefix("model-").await?;
+        let mut model_tags_deleted = 0;
         let mut distro_results_deleted = 0;
         let mut tags = store.tags().list().await?;
 
@@ -682,7 +850,21 @@ where
                 continue;
             };
 
-            // Since tags related to model parameter sharing have been already deleted, it is assumed that
+            if self.config.is_gc_protected(&tag_name) {
+                continue;
+            }
+
+            if tag_name.starts_with("model-") {
+                match store.tags().delete(&tag_name).await {
+                    Ok(_) => model_tags_deleted += 1,
+                    Err(err) => {
+                        warn!("There was an error while trying to delete tag {tag_name}: {err:?}")
+                    }
+                }
+                continue;
+            }
+
+            // Since tags related to model parameter sharing have been handled above, it is assumed that
             // all remaining tags are related to Distro result blobs
             let tag_name_splitted: Vec<&str> = tag_name.split("_").collect();
             let Some(tag_name_distro_result_step) = tag_name_splitted.get(1) else {
@@ -719,6 +901,61 @@ where
         self.router.endpoint().addr()
     }
 
+    /// Counts the blobs in the store and the bytes they take up.
+    pub async fn blob_store_stats(&self) -> Result<BlobStoreStats> {
+        let blobs = self.blobs_store.blobs();
+        let hashes = blobs.list().hashes().await?;
+        let mut total_bytes = 0;
+        for hash in &hashes {
+            total_bytes += match blobs.status(*hash).await? {
+                BlobStatus::Complete { size } => size,
+                BlobStatus::Partial { size } => size.unwrap_or(0),
+                BlobStatus::NotFound => 0,
+            };
+        }
+        Ok(BlobStoreStats {
+            total_blobs: hashes.len(),
+            total_bytes,
+        })
+    }
+
+    async fn on_update_blob_store_stats(&mut self) {
+        let stats = match self.blob_store_stats().await {
+            Ok(stats) => stats,
+            Err(err) => {
+                warn!("Failed to get blob store stats: {err:#}");
+                return;
+            }
+        };
+        self.metrics
+            .update_blob_store(stats.total_blobs as u64, stats.total_bytes);
+        // only log when crossing the threshold, this runs every second
+        let over_warn = stats.total_bytes > self.config.blob_store_warn_bytes;
+        if over_warn && !self.blob_store_over_warn {
+            warn!(
+                total_blobs = stats.total_blobs,
+                total_bytes = stats.total_bytes,
+                "Blob store holds {}, over the {} warning threshold. The blob GC may not be keeping up",
+                fmt_bytes(stats.total_bytes as f64),
+                fmt_bytes(self.config.blob_store_warn_bytes as f64),
+            );
+        } else if !over_warn && self.blob_store_over_warn {
+            info!(
+                total_blobs = stats.total_blobs,
+                total_bytes = stats.total_bytes,
+                "Blob store is back under the {} warning threshold at {}",
+                fmt_bytes(self.config.blob_store_warn_bytes as f64),
+                fmt_bytes(stats.total_bytes as f64),
+            );
+        }
+        self.blob_store_over_warn = over_warn;
+    }
+
+    /// How we're currently connected to `endpoint_id`.
+    pub fn conn_type(&self, endpoint_id: &EndpointId) -> ConnectionType {
+        self.connection_monitor.get_connection_type(endpoint_id)
+    }
+
     pub fn remote_infos(&self) -> Vec<P2PEndpointInfo> {
         // start with our own endpoint
         let mut infos = vec![P2PEndpointInfo {
@@ -750,11 +987,19 @@ where
     }
 
     pub async fn poll_next(&mut self) -> Result<Option<NetworkEvent<BroadcastMessage, Download>>> {
+        if let Some(message) = self.pending_messages.pop_front() {
+            return Ok(Some(NetworkEvent::MessageReceived(message)));
+        }
+
         // these are factored out to separate fns so rustfmt works on their contents :)
         select! {
             Some(event) = self.gossip_rx.next() => {
-                match parse_gossip_event(event.map_err(|ee| ee.into()), &self.gossip_rx, &self.metrics) {
-                    Some(result) => Ok(Some(NetworkEvent::MessageReceived(result))),
+                let mut messages = parse_gossip_event(event.map_err(|ee| ee.into()), &self.gossip_rx, &self.metrics).into_iter();
+                match messages.next() {
+                    Some(result) => {
+                        self.pending_messages.extend(messages);
+                        Ok(Some(NetworkEvent::MessageReceived(result)))
+                    }
                     None => Ok(None),
                 }
             }
@@ -786,6 +1031,9 @@ where
             }
             _ = self.update_stats_interval.tick() => {
                 on_update_stats(&self.endpoint, self.remote_infos(), &mut self.state).await?;
+                self.on_update_blob_store_stats().await;
+                self.metrics.update_gossip_delivery_ratio(self.config.gossip.broadcast.message_id_retention);
+                (self.remove_expired_allowlist_entries)();
                 Ok(None)
             }
             else => { Ok(None) }
@@ -843,8 +1091,13 @@ where
                 }
             });
 
-            self.download_manager
-                .read(update.blob_ticket, update.tag, recv, update.download_type);
+            self.download_manager.read(
+                update.blob_ticket,
+                update.tag,
+                recv,
+                update.download_type,
+                update.priority,
+            );
         } else {
             self.state.download_progesses.insert(hash, update);
         }
@@ -895,31 +1148,41 @@ pub async fn request_model_blob_ticket(
     result.map_err(|e| anyhow!("Error received from peer: {e}"))
 }
 
+/// Returns every message carried by a gossip event - more than one if the sender batched them.
 fn parse_gossip_event<BroadcastMessage: Networkable>(
     event: Result<iroh_gossip::api::Event>,
     gossip: &GossipReceiver,
     metrics: &ClientMetrics,
-) -> Option<(PublicKey, BroadcastMessage)> {
+) -> Vec<(PublicKey, BroadcastMessage)> {
     match event {
         Ok(iroh_gossip::api::Event::Received(msg)) => {
             let message_hash = hash_bytes(&msg.content);
-            match SignedMessage::<BroadcastMessage>::verify_and_decode(&msg.content) {
-                Ok(result) => {
-                    debug!(
-                        name: "gossip_rx",
-                        message_hash = message_hash,
-                        "received gossip message with hash {message_hash}: {:?}",
-                        result
-                    );
-                    return Some(result);
-                }
-                Err(err) => {
-                    warn!(
-                        "Got a gossip message delivered from {}, but could not verify / decode it! {err}",
-                        msg.delivered_from
-                    );
-                }
-            }
+            let payloads =
+                gossip_batch::decode(&msg.content).unwrap_or_else(|| vec![&msg.content[..]]);
+            return SignedMessage::<BroadcastMessage>::verify_and_decode_batch(&payloads)
+                .into_iter()
+                .filter_map(|result| {
+                    match result {
+                        Ok(result) => {
+                            metrics.record_gossip_message_received();
+                            debug!(
+                                name: "gossip_rx",
+                                message_hash = message_hash,
+                                "received gossip message with hash {message_hash}: {:?}",
+                                result
+                            );
+                            Some(result)
+                        }
+                        Err(err) => {
+                            warn!(
+                                "Got a gossip message delivered from {}, but could not verify / decode it! {err}",
+                                msg.delivered_from
+                            );
+                            None
+                        }
+                    }
+                })
+                .collect();
         }
         Ok(iroh_gossip::api::Event::NeighborUp(endpoint_id)) => {
             let peers: Vec<_> = gossip.neighbors().collect();
@@ -942,7 +1205,7 @@ fn parse_gossip_event<BroadcastMessage: Networkable>(
         }
     }
 
-    None
+    Vec::new()
 }
 
 #[derive(Debug)]
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use bytes::Bytes;
 use iroh::EndpointId;
 use iroh::protocol::AcceptError;
 use iroh::{endpoint::Connection, protocol::ProtocolHandler};
@@ -354,20 +355,43 @@ pub enum ModelConfigSharingMessage {
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct TransmittableModelParameter {
     param_name_bytes: Vec<u8>,
-    param_value_bytes: Vec<u8>,
+    param_value_bytes: Bytes,
 }
 
 impl TransmittableModelParameter {
-    fn new(param_name_bytes: Vec<u8>, param_value_bytes: Vec<u8>) -> Self {
+    pub(crate) fn new(param_name_bytes: Vec<u8>, param_value_bytes: Vec<u8>) -> Self {
         Self {
             param_name_bytes,
-            param_value_bytes,
+            param_value_bytes: param_value_bytes.into(),
         }
     }
 
     pub fn name(&self) -> Result<String, SharableModelError> {
         Ok(String::from_utf8(self.param_name_bytes.clone())?)
     }
+
+    pub fn value_bytes(&self) -> &[u8] {
+        &self.param_value_bytes
+    }
+}
+
+/// A [`TransmittableModelParameter`] that borrows its contents from the downloaded blob.
+/// Same wire format as the owned struct.
+#[derive(serde::Deserialize, Debug)]
+pub(crate) struct TransmittableModelParameterBorrowed<'a> {
+    param_name_bytes: &'a [u8],
+    param_value_bytes: &'a [u8],
+}
+
+impl TransmittableModelParameterBorrowed<'_> {
+    /// `blob` must be the buffer this was deserialized from, the parameter value is
+    /// sliced out of it rather than copied.
+    pub(crate) fn into_owned(self, blob: &Bytes) -> TransmittableModelParameter {
+        TransmittableModelParameter {
+            param_name_bytes: self.param_name_bytes.to_vec(),
+            param_value_bytes: blob.slice_ref(self.param_value_bytes),
+        }
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
@@ -0,0 +1,66 @@
+use std::{num::NonZeroU32, time::Instant};
+
+/// Token bucket allowing bursts of up to `rate` calls, refilled at `rate` tokens per second.
+#[derive(Debug)]
+pub(crate) struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: NonZeroU32) -> Self {
+        let rate = rate.get() as f64;
+        Self {
+            rate,
+            tokens: rate,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Takes a token if there's one left, returns false if the bucket is empty.
+    pub fn try_take(&mut self) -> bool {
+        self.try_take_at(Instant::now())
+    }
+
+    fn try_take_at(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate).min(self.rate);
+        self.last_refill = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_token_bucket_refills_at_rate() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket {
+            rate: 2.0,
+            tokens: 2.0,
+            last_refill: start,
+        };
+        assert!(bucket.try_take_at(start));
+        assert!(bucket.try_take_at(start));
+        assert!(!bucket.try_take_at(start));
+
+        // half a second refills one token at 2/s
+        let later = start + Duration::from_millis(500);
+        assert!(bucket.try_take_at(later));
+        assert!(!bucket.try_take_at(later));
+
+        // never holds more than one second's worth
+        let much_later = later + Duration::from_secs(60);
+        assert!(bucket.try_take_at(much_later));
+        assert!(bucket.try_take_at(much_later));
+        assert!(!bucket.try_take_at(much_later));
+    }
+}
@@ -1,6 +1,7 @@
 use std::fmt::Debug;
 
 use anyhow::Result;
+use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 
 pub trait Networkable: Serialize + for<'a> Deserialize<'a> + Debug + Send + Sync + 'static {
@@ -13,3 +14,14 @@ pub trait Networkable: Serialize + for<'a> Deserialize<'a> + Debug + Send + Sync
 }
 
 impl<T: Serialize + for<'a> Deserialize<'a> + Debug + Send + Sync + 'static> Networkable for T {}
+
+/// Something that can be fetched with the download manager.
+pub trait Downloadable: Networkable {
+    /// Decodes a downloaded blob. Types that can keep references into `blob`,
+    /// instead of copying out of it, override this.
+    fn from_blob(blob: &Bytes) -> Result<Self> {
+        Self::from_bytes(blob)
+    }
+}
+
+impl Downloadable for () {}
@@ -1,8 +1,10 @@
 use psyche_core::BatchId;
-use psyche_modeling::DistroResult;
+use psyche_modeling::{DistroResult, SPARSE_VAL_SCALE_STAT};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::{
+    borrow::Cow,
+    collections::HashMap,
     error::Error,
     fmt,
     io::{BufReader, Read},
@@ -19,6 +21,8 @@ pub struct SerializedDistroResult {
     pub sparse_val: SerializableTensor,
     pub xshape: Vec<u16>,
     pub totalk: u32,
+    /// Set when `sparse_val` is int8-quantized: multiply by it to get the original values back.
+    pub sparse_val_scale: Option<f32>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -29,6 +33,46 @@ pub struct TransmittableDistroResult {
     pub distro_results: Vec<SerializedDistroResult>,
 }
 
+/// [`SerializedDistroResult`] as it was sent before `sparse_val_scale` existed.
+#[derive(Serialize, Deserialize)]
+struct SerializedDistroResultV0<'a> {
+    sparse_idx: Cow<'a, SerializableTensor>,
+    sparse_val: Cow<'a, SerializableTensor>,
+    xshape: Cow<'a, [u16]>,
+    totalk: u32,
+}
+
+/// [`TransmittableDistroResult`] as it was sent before `sparse_val_scale` existed.
+/// Results without a scale are still sent in this layout, so older clients can read them.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct TransmittableDistroResultV0<'a> {
+    step: u32,
+    trainer_nonce: u32,
+    batch_id: BatchId,
+    distro_results: Vec<SerializedDistroResultV0<'a>>,
+}
+
+impl From<TransmittableDistroResultV0<'_>> for TransmittableDistroResult {
+    fn from(value: TransmittableDistroResultV0<'_>) -> Self {
+        Self {
+            step: value.step,
+            trainer_nonce: value.trainer_nonce,
+            batch_id: value.batch_id,
+            distro_results: value
+                .distro_results
+                .into_iter()
+                .map(|result| SerializedDistroResult {
+                    sparse_idx: result.sparse_idx.into_owned(),
+                    sparse_val: result.sparse_val.into_owned(),
+                    xshape: result.xshape.into_owned(),
+                    totalk: result.totalk,
+                    sparse_val_scale: None,
+                })
+                .collect(),
+        }
+    }
+}
+
 impl TransmittableDistroResult {
     pub fn comptue_hash(&self) -> [u8; 32] {
         let mut hasher = Sha256::new();
@@ -38,9 +82,39 @@ impl TransmittableDistroResult {
         for result in &self.distro_results {
             hasher.update(result.sparse_idx.raw_tensor_data());
             hasher.update(result.sparse_val.raw_tensor_data());
+            if let Some(scale) = result.sparse_val_scale {
+                hasher.update(scale.to_be_bytes());
+            }
         }
         hasher.finalize().into()
     }
+
+    /// Borrows this result in the [`TransmittableDistroResultV0`] layout,
+    /// or returns `None` if any of its results carries a `sparse_val_scale`.
+    pub(crate) fn as_v0(&self) -> Option<TransmittableDistroResultV0<'_>> {
+        if self
+            .distro_results
+            .iter()
+            .any(|result| result.sparse_val_scale.is_some())
+        {
+            return None;
+        }
+        Some(TransmittableDistroResultV0 {
+            step: self.step,
+            trainer_nonce: self.trainer_nonce,
+            batch_id: self.batch_id,
+            distro_results: self
+                .distro_results
+                .iter()
+                .map(|result| SerializedDistroResultV0 {
+                    sparse_idx: Cow::Borrowed(&result.sparse_idx),
+                    sparse_val: Cow::Borrowed(&result.sparse_val),
+                    xshape: Cow::Borrowed(&result.xshape),
+                    totalk: result.totalk,
+                })
+                .collect(),
+        })
+    }
 }
 
 #[derive(Debug, Error)]
@@ -63,6 +137,7 @@ impl TryFrom<&DistroResult> for SerializedDistroResult {
                 .map(|&x| u16::try_from(x))
                 .collect::<Result<Vec<u16>, _>>()?,
             totalk: value.totalk as u32,
+            sparse_val_scale: value.sparse_val_scale().map(|scale| scale as f32),
         })
     }
 }
@@ -76,7 +151,9 @@ impl TryFrom<&SerializedDistroResult> for DistroResult {
             sparse_val: (&value.sparse_val).try_into()?,
             xshape: value.xshape.iter().map(|x| *x as i64).collect(),
             totalk: value.totalk as i64,
-            stats: None,
+            stats: value
+                .sparse_val_scale
+                .map(|scale| HashMap::from([(SPARSE_VAL_SCALE_STAT.to_string(), scale as f64)])),
         };
         // only pin if we have a device to pin to
         let potential_cuda_device = Device::cuda_if_available();
@@ -180,7 +257,7 @@ impl<R: Read> Iterator for DistroResultIterator<R> {
 
 #[cfg(test)]
 mod tests {
-    use psyche_modeling::CompressDCT;
+    use psyche_modeling::{AdaptiveTopK, CompressDCT};
     use tch::{Device, Kind, Tensor};
 
     use crate::serializable_tensor::SerializableTensor;
@@ -196,7 +273,8 @@ mod tests {
         .to_kind(Kind::Float)
         .to(Device::Cpu);
 
-        let (sparse_idx, raw_sparse_val, xshape, totalk) = CompressDCT::compress(&truth, i64::MAX);
+        let (sparse_idx, raw_sparse_val, xshape, totalk) =
+            CompressDCT::compress(&truth, AdaptiveTopK::Fixed(i64::MAX));
         // turn raw sparse vals into bools
         let bool_sparse_val = raw_sparse_val.greater(0);
 
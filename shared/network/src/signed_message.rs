@@ -2,6 +2,7 @@ use crate::Networkable;
 
 use anyhow::Result;
 use bytes::Bytes;
+use ed25519_dalek::VerifyingKey;
 use iroh::{PublicKey, SecretKey};
 use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
@@ -15,12 +16,63 @@ pub struct SignedMessage<T: Networkable> {
 }
 
 impl<T: Networkable> SignedMessage<T> {
+    /// Verifies and decodes a single message, see [`Self::verify_and_decode_batch`].
     pub fn verify_and_decode(bytes: &[u8]) -> Result<(PublicKey, T)> {
-        let signed_message: Self = postcard::from_bytes(bytes)?;
-        let key: PublicKey = signed_message.from;
-        key.verify(&signed_message.data, &signed_message.signature)?;
-        let message: T = postcard::from_bytes(&signed_message.data)?;
-        Ok((signed_message.from, message))
+        Self::verify_and_decode_batch(&[bytes])
+            .pop()
+            .expect("one result per message")
+    }
+
+    /// Verifies and decodes many messages, returning one result per message in the same order.
+    /// A message is accepted exactly when its signature passes `verify_strict`, like iroh's
+    /// [`PublicKey::verify`] checks it, whatever else it's batched with.
+    /// The signatures that can't pass a batch check unless they'd pass `verify_strict` are first
+    /// checked with a single batch verification. If that fails, or there's only one of them,
+    /// each signature is checked on its own.
+    pub fn verify_and_decode_batch(messages: &[impl AsRef<[u8]>]) -> Vec<Result<(PublicKey, T)>> {
+        let signed_messages: Vec<Result<(Self, VerifyingKey, ed25519_dalek::Signature)>> = messages
+            .iter()
+            .map(|bytes| {
+                let signed_message: Self = postcard::from_bytes(bytes.as_ref())?;
+                let key = VerifyingKey::from_bytes(signed_message.from.as_bytes())?;
+                let signature =
+                    ed25519_dalek::Signature::from_bytes(&signed_message.signature.to_bytes());
+                Ok((signed_message, key, signature))
+            })
+            .collect();
+
+        let batchable: Vec<bool> = signed_messages
+            .iter()
+            .map(|m| {
+                m.as_ref()
+                    .is_ok_and(|(_, key, signature)| batch_matches_strict(key, signature))
+            })
+            .collect();
+        let batch = signed_messages
+            .iter()
+            .zip(&batchable)
+            .filter(|(_, batchable)| **batchable)
+            .filter_map(|(m, _)| m.as_ref().ok());
+        let datas: Vec<&[u8]> = batch.clone().map(|(m, _, _)| &m.data[..]).collect();
+        let signatures: Vec<_> = batch.clone().map(|(_, _, sig)| *sig).collect();
+        let keys: Vec<_> = batch.map(|(_, key, _)| *key).collect();
+        // a failed batch only tells us that *some* signature is bad,
+        // so in that case we fall back to checking each one individually.
+        let batch_valid =
+            datas.len() > 1 && ed25519_dalek::verify_batch(&datas, &signatures, &keys).is_ok();
+
+        signed_messages
+            .into_iter()
+            .zip(batchable)
+            .map(|(signed_message, batchable)| {
+                let (signed_message, key, signature) = signed_message?;
+                if !(batchable && batch_valid) {
+                    key.verify_strict(&signed_message.data, &signature)?;
+                }
+                let message: T = postcard::from_bytes(&signed_message.data)?;
+                Ok((signed_message.from, message))
+            })
+            .collect()
     }
 
     pub fn sign_and_encode(secret_key: &SecretKey, message: &T) -> Result<Bytes> {
@@ -37,3 +89,100 @@ impl<T: Networkable> SignedMessage<T> {
         Ok(encoded.into())
     }
 }
+
+/// Whether a batch verification including this signature can only pass if `verify_strict` accepts it.
+/// `verify_strict` rejects small order keys and `R`s, and non-canonical `R` encodings, which the batch
+/// equation doesn't. Its random coefficients can also cancel out the torsion components of mixed order
+/// points, so those are left to `verify_strict` too.
+fn batch_matches_strict(key: &VerifyingKey, signature: &ed25519_dalek::Signature) -> bool {
+    let Ok(r) = VerifyingKey::from_bytes(signature.r_bytes()) else {
+        return false;
+    };
+    !key.is_weak()
+        && key.to_edwards().is_torsion_free()
+        && !r.is_weak()
+        && r.to_edwards().is_torsion_free()
+        && r.to_edwards().compress().as_bytes() == signature.r_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_and_decode_batch() {
+        let secret_key = SecretKey::generate(&mut rand::rng());
+        let good = SignedMessage::<u32>::sign_and_encode(&secret_key, &1).unwrap();
+        let mut bad_signature = SignedMessage::<u32>::sign_and_encode(&secret_key, &2)
+            .unwrap()
+            .to_vec();
+        *bad_signature.last_mut().unwrap() ^= 1;
+        let garbage = vec![0u8; 3];
+
+        let results =
+            SignedMessage::<u32>::verify_and_decode_batch(&[good.to_vec(), bad_signature, garbage]);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap(), &(secret_key.public(), 1));
+        assert!(results[1].is_err());
+        assert!(results[2].is_err());
+
+        let (from, message) = SignedMessage::<u32>::verify_and_decode(&good).unwrap();
+        assert_eq!((from, message), (secret_key.public(), 1));
+    }
+
+    #[test]
+    fn test_verify_and_decode_batch_all_valid() {
+        let secret_key = SecretKey::generate(&mut rand::rng());
+        let messages: Vec<Bytes> = (0..4)
+            .map(|i| SignedMessage::<u32>::sign_and_encode(&secret_key, &i).unwrap())
+            .collect();
+        let results = SignedMessage::<u32>::verify_and_decode_batch(&messages);
+        for (i, result) in results.into_iter().enumerate() {
+            assert_eq!(result.unwrap(), (secret_key.public(), i as u32));
+        }
+    }
+
+    #[test]
+    fn test_verify_and_decode_batch_single() {
+        let secret_key = SecretKey::generate(&mut rand::rng());
+        let mut bad_signature = SignedMessage::<u32>::sign_and_encode(&secret_key, &1)
+            .unwrap()
+            .to_vec();
+        *bad_signature.last_mut().unwrap() ^= 1;
+        let results = SignedMessage::<u32>::verify_and_decode_batch(&[bad_signature]);
+        assert!(results[0].is_err());
+    }
+
+    #[test]
+    fn test_batch_rejects_what_verify_strict_rejects() {
+        // the identity is a small order key, for which s = 0 and R = identity satisfies the
+        // batch equation on any message, but verify_strict rejects it
+        let mut identity = [0u8; 32];
+        identity[0] = 1;
+        let mut forged_signature = [0u8; 64];
+        forged_signature[..32].copy_from_slice(&identity);
+        let forged = postcard::to_stdvec(&SignedMessage::<u32> {
+            from: PublicKey::from_bytes(&identity).unwrap(),
+            data: postcard::to_stdvec(&3u32).unwrap().into(),
+            signature: iroh::Signature::from_bytes(&forged_signature),
+            _t: PhantomData,
+        })
+        .unwrap();
+
+        let secret_key = SecretKey::generate(&mut rand::rng());
+        let mut messages: Vec<Vec<u8>> = (0..2)
+            .map(|i| {
+                SignedMessage::<u32>::sign_and_encode(&secret_key, &i)
+                    .unwrap()
+                    .to_vec()
+            })
+            .collect();
+        messages.push(forged.clone());
+
+        let results = SignedMessage::<u32>::verify_and_decode_batch(&messages);
+        assert_eq!(results[0].as_ref().unwrap(), &(secret_key.public(), 0));
+        assert_eq!(results[1].as_ref().unwrap(), &(secret_key.public(), 1));
+        assert!(results[2].is_err());
+        assert!(SignedMessage::<u32>::verify_and_decode(&forged).is_err());
+    }
+}
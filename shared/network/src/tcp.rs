@@ -1,13 +1,25 @@
 use crate::Networkable;
+#[cfg(feature = "testing")]
+use crate::ServerFault;
+use crate::faults::{Kicks, ServerFaults};
 
-use anyhow::{anyhow, bail};
+use anyhow::{Context, anyhow, bail};
 use futures_util::{SinkExt, StreamExt};
 use iroh::{PublicKey, SecretKey};
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fmt::Debug, io, marker::PhantomData, net::SocketAddr, sync::Arc};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    io,
+    marker::PhantomData,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use thiserror::Error;
 use tokio::{
+    io::{AsyncRead, AsyncWrite},
     net::{TcpListener, TcpStream},
     select,
     sync::{
@@ -15,8 +27,17 @@ use tokio::{
         mpsc::{self, error::SendError},
     },
 };
+use tokio_rustls::{
+    TlsAcceptor, TlsConnector,
+    rustls::{
+        self, DigitallySignedStruct, RootCertStore, SignatureScheme,
+        client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        crypto::{CryptoProvider, verify_tls12_signature, verify_tls13_signature},
+        pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime, pem::PemObject},
+    },
+};
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use crate::SignedMessage;
 
@@ -37,6 +58,130 @@ enum ClientToServerMessage<T: Debug> {
 pub enum ClientNotification<T: Debug, U: Debug> {
     Message(T),
     Disconnected(U),
+    /// A connection from this address completed its TLS handshake
+    ConnectionUpgraded(SocketAddr),
+}
+
+/// Certificate and private key the server presents to clients, both PEM-encoded
+#[derive(Debug, Clone)]
+pub struct ServerTlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// How a client verifies the server's TLS certificate
+#[derive(Debug, Clone)]
+pub enum ClientTlsConfig {
+    /// Trust certificates signed by the CA in this PEM file
+    Ca(PathBuf),
+    /// Accept any certificate. Only for testing with self-signed certificates.
+    Insecure,
+}
+
+trait AsyncStream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> AsyncStream for T {}
+
+/// Either a plain [`TcpStream`] or one wrapped in TLS
+type BoxedStream = Box<dyn AsyncStream>;
+
+fn crypto_provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn read_certs(path: &Path) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .with_context(|| format!("failed to read certificates from {}", path.display()))?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("failed to parse certificates in {}", path.display()))?;
+    if certs.is_empty() {
+        bail!("no certificates found in {}", path.display());
+    }
+    Ok(certs)
+}
+
+impl ServerTlsConfig {
+    fn acceptor(&self) -> anyhow::Result<TlsAcceptor> {
+        let certs = read_certs(&self.cert)?;
+        let key = PrivateKeyDer::from_pem_file(&self.key)
+            .with_context(|| format!("failed to read private key from {}", self.key.display()))?;
+        let config = rustls::ServerConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)?;
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+impl ClientTlsConfig {
+    fn connector(&self) -> anyhow::Result<TlsConnector> {
+        let provider = crypto_provider();
+        let builder = rustls::ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+        let config = match self {
+            ClientTlsConfig::Ca(path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in read_certs(path)? {
+                    roots.add(cert)?;
+                }
+                builder.with_root_certificates(roots).with_no_client_auth()
+            }
+            ClientTlsConfig::Insecure => builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(NoCertificateVerification(provider)))
+                .with_no_client_auth(),
+        };
+        Ok(TlsConnector::from(Arc::new(config)))
+    }
+}
+
+/// Accepts any server certificate, still checking the handshake signatures
+/// so the connection is at least encrypted.
+#[derive(Debug)]
+struct NoCertificateVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoCertificateVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
 }
 
 pub struct TcpServer<ToServerMessage, ToClientMessage>
@@ -52,6 +197,9 @@ where
     send_msg: mpsc::UnboundedSender<(PublicKey, ToClientMessage)>,
     local_addr: SocketAddr,
     disconnected_rx: mpsc::UnboundedReceiver<PublicKey>,
+    upgraded_rx: mpsc::UnboundedReceiver<SocketAddr>,
+    #[cfg(feature = "testing")]
+    faults_tx: mpsc::UnboundedSender<ServerFault>,
 }
 
 #[derive(Error, Debug)]
@@ -60,6 +208,8 @@ pub enum ConnectError {
     Bind(io::Error),
     #[error("failed to get local addr: {0}")]
     GetLocalAddr(io::Error),
+    #[error("failed to load TLS config: {0:#}")]
+    Tls(anyhow::Error),
 }
 
 impl<ToServer, ToClient> TcpServer<ToServer, ToClient>
@@ -68,6 +218,19 @@ where
     ToClient: Networkable + Clone + Debug + Send + Sync + 'static,
 {
     pub async fn start(addr: SocketAddr) -> Result<Self, ConnectError> {
+        Self::start_with_tls(addr, None).await
+    }
+
+    /// Like [`TcpServer::start`], but if `tls` is set every accepted connection
+    /// must complete a TLS handshake before the challenge is sent.
+    pub async fn start_with_tls(
+        addr: SocketAddr,
+        tls: Option<ServerTlsConfig>,
+    ) -> Result<Self, ConnectError> {
+        let acceptor = tls
+            .map(|tls| tls.acceptor())
+            .transpose()
+            .map_err(ConnectError::Tls)?;
         let listener = TcpListener::bind(addr).await.map_err(ConnectError::Bind)?;
         let local_addr = listener.local_addr().map_err(ConnectError::GetLocalAddr)?;
         info!("Server listening on: {}", local_addr);
@@ -75,20 +238,47 @@ where
         let (incoming_tx, incoming_rx) = mpsc::unbounded_channel();
         let (send_msg, mut outgoing_rx) = mpsc::unbounded_channel();
         let (disconnected_tx, disconnected_rx) = mpsc::unbounded_channel();
+        let (upgraded_tx, upgraded_rx) = mpsc::unbounded_channel();
 
         let clients = Arc::new(Mutex::new(HashMap::new()));
+        let faults = ServerFaults::new();
+        #[cfg(feature = "testing")]
+        let faults_tx = faults.spawn_injector();
 
         tokio::spawn({
             let clients = clients.clone();
             async move {
-                while let Ok((stream, _)) = listener.accept().await {
+                while let Ok((stream, peer_addr)) = listener.accept().await {
                     let clients = clients.clone();
                     let incoming_tx = incoming_tx.clone();
                     let disconnected_tx = disconnected_tx.clone();
+                    let upgraded_tx = upgraded_tx.clone();
+                    let acceptor = acceptor.clone();
+                    let faults = faults.clone();
+                    let kicks = faults.kicks();
                     tokio::spawn(async move {
-                        if let Err(err) =
-                            Self::handle_connection(stream, clients, incoming_tx, disconnected_tx)
-                                .await
+                        let stream: BoxedStream = match acceptor {
+                            Some(acceptor) => match acceptor.accept(stream).await {
+                                Ok(stream) => {
+                                    let _ = upgraded_tx.send(peer_addr);
+                                    Box::new(stream)
+                                }
+                                Err(err) => {
+                                    warn!("TLS handshake with {peer_addr} failed: {err:#}");
+                                    return;
+                                }
+                            },
+                            None => Box::new(stream),
+                        };
+                        if let Err(err) = Self::handle_connection(
+                            stream,
+                            clients,
+                            incoming_tx,
+                            disconnected_tx,
+                            faults,
+                            kicks,
+                        )
+                        .await
                         {
                             error!("Error handling connection: {err:#}");
                         }
@@ -117,6 +307,9 @@ where
             send_msg,
             local_addr,
             disconnected_rx,
+            upgraded_rx,
+            #[cfg(feature = "testing")]
+            faults_tx,
         })
     }
 
@@ -125,10 +318,12 @@ where
     }
 
     async fn handle_connection(
-        stream: TcpStream,
+        stream: BoxedStream,
         clients: Arc<Mutex<HashMap<PublicKey, mpsc::UnboundedSender<ToClient>>>>,
         incoming_tx: mpsc::UnboundedSender<(PublicKey, ToServer)>,
         disconnected_tx: mpsc::UnboundedSender<PublicKey>,
+        faults: ServerFaults,
+        mut kicks: Kicks,
     ) -> anyhow::Result<()> {
         let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
 
@@ -169,6 +364,9 @@ where
                 challenge
             );
         }
+        if faults.is_refused(&identity) {
+            bail!("Refusing connection from {identity}: simulated disconnect");
+        }
         debug!("Challenge response accepted! welcome, {:?}!", identity);
         let (client_tx, mut client_rx) = mpsc::unbounded_channel();
         clients.lock().await.insert(identity, client_tx);
@@ -176,8 +374,16 @@ where
         loop {
             tokio::select! {
                 Some(message) = client_rx.recv() => {
+                    if faults.should_drop(&identity) {
+                        debug!("Dropping message to {identity}: simulated fault");
+                        continue;
+                    }
                     framed.send(ServerToClientMessage::Else(message).to_bytes().into()).await?;
                 }
+                _ = kicks.wait_for(identity) => {
+                    info!("Disconnecting {identity}: simulated fault");
+                    break;
+                }
                 result = framed.next() => match result {
                     Some(Ok(bytes)) => {
                         let message = ClientToServerMessage::<ToServer>::from_bytes(&bytes)?;
@@ -186,7 +392,11 @@ where
                                bail!("Unexpected challenge message");
                             }
                             ClientToServerMessage::Else(m) => {
-                                incoming_tx.send((identity, m))?;
+                                if faults.should_drop(&identity) {
+                                    debug!("Dropping message from {identity}: simulated fault");
+                                } else {
+                                    incoming_tx.send((identity, m))?;
+                                }
                             }
                         }
                     }
@@ -204,6 +414,12 @@ where
         Ok(())
     }
 
+    /// A channel for simulating network faults, for testing
+    #[cfg(feature = "testing")]
+    pub fn fault_injector(&self) -> mpsc::UnboundedSender<ServerFault> {
+        self.faults_tx.clone()
+    }
+
     pub async fn get_connected_clients(&self) -> Vec<PublicKey> {
         self.clients.lock().await.keys().cloned().collect()
     }
@@ -216,6 +432,9 @@ where
             Some(msg) = self.disconnected_rx.recv() => {
                 Some(ClientNotification::Disconnected(msg))
             }
+            Some(addr) = self.upgraded_rx.recv() => {
+                Some(ClientNotification::ConnectionUpgraded(addr))
+            }
             else => None
         }
     }
@@ -248,7 +467,7 @@ where
     ToClientMessage: Networkable + Debug + Send + Sync + 'static,
 {
     identity: PublicKey,
-    framed: Framed<TcpStream, LengthDelimitedCodec>,
+    framed: Framed<BoxedStream, LengthDelimitedCodec>,
     _phantom: PhantomData<(ToServerMessage, ToClientMessage)>,
 }
 
@@ -258,9 +477,31 @@ where
     ToClient: Networkable + Debug + Send + Sync + 'static,
 {
     pub async fn connect(addr: &str, secret_key: SecretKey) -> anyhow::Result<Self> {
+        Self::connect_with_tls(addr, secret_key, None).await
+    }
+
+    /// Like [`TcpClient::connect`], but wraps the connection in TLS if `tls` is set.
+    pub async fn connect_with_tls(
+        addr: &str,
+        secret_key: SecretKey,
+        tls: Option<ClientTlsConfig>,
+    ) -> anyhow::Result<Self> {
         let stream = TcpStream::connect(addr).await?;
         info!("Connected to server at: {}", addr);
 
+        let stream: BoxedStream = match tls {
+            Some(tls) => {
+                let host = addr.rsplit_once(':').map_or(addr, |(host, _)| host);
+                let host = host.trim_start_matches('[').trim_end_matches(']');
+                let server_name = ServerName::try_from(host.to_string())
+                    .with_context(|| format!("invalid TLS server name {host}"))?;
+                let stream = tls.connector()?.connect(server_name, stream).await?;
+                info!("TLS handshake with {} complete", addr);
+                Box::new(stream)
+            }
+            None => Box::new(stream),
+        };
+
         let mut codec = LengthDelimitedCodec::new();
         codec.set_max_frame_length(MAX_FRAME_LENGTH);
         let mut framed = Framed::new(stream, codec);
@@ -289,7 +530,7 @@ where
     }
 
     async fn receive_message(
-        framed: &mut Framed<TcpStream, LengthDelimitedCodec>,
+        framed: &mut Framed<BoxedStream, LengthDelimitedCodec>,
     ) -> anyhow::Result<ServerToClientMessage<ToClient>> {
         let bytes = framed
             .next()
@@ -474,4 +715,65 @@ mod tests {
             TestToClient::Pong(text) => assert_eq!(text, "hewwo :3"),
         }
     }
+
+    #[tokio::test]
+    async fn test_tls_connection() {
+        let rcgen::CertifiedKey { cert, signing_key } =
+            rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let dir = std::env::temp_dir().join(format!("psyche-tls-test-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cert_path = dir.join("cert.pem");
+        let key_path = dir.join("key.pem");
+        std::fs::write(&cert_path, cert.pem()).unwrap();
+        std::fs::write(&key_path, signing_key.serialize_pem()).unwrap();
+
+        let mut server = TcpServer::<TestToServer, TestToClient>::start_with_tls(
+            "127.0.0.1:0".parse().unwrap(),
+            Some(ServerTlsConfig {
+                cert: cert_path.clone(),
+                key: key_path,
+            }),
+        )
+        .await
+        .unwrap();
+        let port = server.local_addr().port();
+
+        let secret_key = SecretKey::generate(&mut rand::rng());
+        let pub_key = secret_key.public();
+        let mut client = TcpClient::<TestToServer, TestToClient>::connect_with_tls(
+            &format!("localhost:{port}"),
+            secret_key,
+            Some(ClientTlsConfig::Ca(cert_path)),
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            server.next().await.unwrap(),
+            ClientNotification::ConnectionUpgraded(_)
+        ));
+
+        client
+            .send(TestToServer::Ping("secret".into()))
+            .await
+            .unwrap();
+        match server.next().await.unwrap() {
+            ClientNotification::Message((from, TestToServer::Ping(text))) => {
+                assert_eq!(from, pub_key);
+                assert_eq!(text, "secret");
+            }
+            _ => panic!("Expected message from client"),
+        }
+
+        // self-signed certs are accepted without a CA in insecure mode
+        let _insecure_client = TcpClient::<TestToServer, TestToClient>::connect_with_tls(
+            &format!("127.0.0.1:{port}"),
+            SecretKey::generate(&mut rand::rng()),
+            Some(ClientTlsConfig::Insecure),
+        )
+        .await
+        .unwrap();
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
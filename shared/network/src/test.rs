@@ -17,7 +17,10 @@ use tokio::{
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info};
 
-use crate::{DiscoveryMode, DownloadType, NetworkConnection, NetworkEvent, allowlist};
+use crate::{
+    DiscoveryMode, DownloadPriority, DownloadType, Downloadable, NetworkConfig, NetworkConnection,
+    NetworkEvent, allowlist,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 enum Message {
@@ -31,6 +34,8 @@ struct DistroResultBlob {
     data: Vec<u8>,
 }
 
+impl Downloadable for DistroResultBlob {}
+
 type NC = NetworkConnection<Message, DistroResultBlob>;
 
 #[derive(Debug)]
@@ -100,6 +105,7 @@ impl App {
                     blob_ticket,
                     Tag::from(step.to_string()),
                     DownloadType::DistroResult(peers),
+                    DownloadPriority::Critical,
                 );
 
                 if !self.should_wait_before {
@@ -163,6 +169,23 @@ impl App {
     }
 }
 
+async fn init_node(peers: Vec<EndpointAddr>) -> Result<NC> {
+    NC::init(
+        "test",
+        None,
+        None,
+        DiscoveryMode::Local,
+        RelayKind::Psyche,
+        peers,
+        None,
+        allowlist::AllowAll,
+        Arc::new(ClientMetrics::new(None, None, None)),
+        None,
+        NetworkConfig::default(),
+    )
+    .await
+}
+
 async fn spawn_new_node(
     is_sender: bool,
     peers: Vec<EndpointAddr>,
@@ -190,19 +213,7 @@ async fn spawn_new_node(
 
     println!("joining gossip room");
 
-    let network = NC::init(
-        "test",
-        None,
-        None,
-        DiscoveryMode::Local,
-        RelayKind::Psyche,
-        peers,
-        None,
-        allowlist::AllowAll,
-        Arc::new(ClientMetrics::new(None, None)),
-        None,
-    )
-    .await?;
+    let network = init_node(peers).await?;
 
     let addr = network.router().endpoint().addr();
     let join_id = vec![addr.clone()];
@@ -396,3 +407,39 @@ async fn test_retry_connection_mid_download() -> Result<()> {
         }
     }
 }
+
+#[tokio::test]
+async fn test_broadcast_batch_delivers_every_message() -> Result<()> {
+    let sender = init_node(vec![]).await?;
+    let mut receiver = init_node(vec![sender.router().endpoint().addr()]).await?;
+
+    let messages: Vec<Message> = (0..3)
+        .map(|i| Message::Message {
+            text: format!("message {i}"),
+        })
+        .collect();
+    let batch: Vec<&Message> = messages.iter().collect();
+
+    let received = timeout(Duration::from_secs(30), async {
+        // keep sending until gossip has connected the two nodes,
+        // the repeats are the same payload so gossip drops them as duplicates
+        let mut send_interval = tokio::time::interval(Duration::from_millis(500));
+        let mut received = Vec::new();
+        while received.len() < messages.len() {
+            select! {
+                _ = send_interval.tick() => sender.broadcast_batch(&batch)?,
+                event = receiver.poll_next() => {
+                    if let Some(NetworkEvent::MessageReceived((from, Message::Message { text }))) = event? {
+                        assert_eq!(from, sender.endpoint_id());
+                        received.push(text);
+                    }
+                }
+            }
+        }
+        anyhow::Ok(received)
+    })
+    .await??;
+
+    assert_eq!(received, ["message 0", "message 1", "message 2"]);
+    Ok(())
+}
@@ -1,4 +1,6 @@
-use crate::{NetworkConnection, Networkable, P2PEndpointInfo, util::fmt_bytes};
+use crate::{
+    BlobStoreStats, Downloadable, NetworkConnection, Networkable, P2PEndpointInfo, util::fmt_bytes,
+};
 
 use futures_util::StreamExt;
 use iroh::EndpointId;
@@ -54,6 +56,10 @@ impl psyche_tui::CustomWidget for NetworkTui {
                 )
                 .render(chunks[0], buf);
 
+                let peer_chunks =
+                    Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                        .split(chunks[1]);
+
                 List::new(state.endpoint_connections.iter().map(
                     |P2PEndpointInfo {
                          id: endpoint_id,
@@ -84,7 +90,27 @@ impl psyche_tui::CustomWidget for NetworkTui {
                         .title("Recently Seen Peers")
                         .borders(Borders::ALL),
                 )
-                .render(chunks[1], buf);
+                .render(peer_chunks[0], buf);
+
+                let gossip_neighbors = if state.gossip_neighbors.is_empty() {
+                    vec![ListItem::new("no gossip neighbors").fg(Color::Red)]
+                } else {
+                    state
+                        .gossip_neighbors
+                        .iter()
+                        .map(|endpoint_id| ListItem::new(short_id(endpoint_id)))
+                        .collect()
+                };
+                List::new(gossip_neighbors)
+                    .block(
+                        Block::default()
+                            .title(format!(
+                                "Gossip Neighbors ({})",
+                                state.gossip_neighbors.len()
+                            ))
+                            .borders(Borders::ALL),
+                    )
+                    .render(peer_chunks[1], buf);
             }
 
             // Upload & Download
@@ -179,7 +205,11 @@ impl psyche_tui::CustomWidget for NetworkTui {
                     }))
                     .block(
                         Block::default()
-                            .title(format!("Blobs ({})", state.blob_hashes.len()))
+                            .title(format!(
+                                "Blobs ({}, {})",
+                                state.blob_store_stats.total_blobs,
+                                fmt_bytes(state.blob_store_stats.total_bytes as f64)
+                            ))
                             .borders(Borders::ALL),
                     );
 
@@ -199,6 +229,10 @@ impl psyche_tui::CustomWidget for NetworkTui {
     }
 }
 
+fn short_id(endpoint_id: &EndpointId) -> String {
+    endpoint_id.to_string().chars().take(8).collect()
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct UIDownloadProgress {
     downloaded: u64,
@@ -209,6 +243,8 @@ pub struct UIDownloadProgress {
 pub struct NetworkTUIStateInner {
     pub endpoint_id: Option<EndpointId>,
     pub endpoint_connections: Vec<P2PEndpointInfo>,
+    /// Our own gossip neighbors
+    pub gossip_neighbors: Vec<EndpointId>,
     // pub data_per_sec_per_client: HashMap<PublicKey, f64>,
     pub total_data_per_sec: f64,
     pub download_bandwidth_history: VecDeque<f64>,
@@ -216,6 +252,7 @@ pub struct NetworkTUIStateInner {
     pub downloads: HashMap<String, UIDownloadProgress>,
 
     pub blob_hashes: Vec<String>,
+    pub blob_store_stats: BlobStoreStats,
 }
 
 #[derive(Default, Debug, Clone)]
@@ -227,7 +264,7 @@ impl NetworkTUIState {
     pub async fn from_network_connection<M, D>(nc: &NetworkConnection<M, D>) -> anyhow::Result<Self>
     where
         M: Networkable,
-        D: Networkable,
+        D: Downloadable,
     {
         let s = &nc.state;
         let blob_hashes = nc
@@ -238,11 +275,13 @@ impl NetworkTUIState {
             .filter_map(|hash_result| async move { hash_result.ok().map(|h| h.to_string()) })
             .collect::<Vec<_>>()
             .await;
+        let blob_store_stats = nc.blob_store_stats().await?;
 
         Ok(Self {
             inner: Some(NetworkTUIStateInner {
                 endpoint_id: s.endpoint_id,
                 endpoint_connections: s.connection_info.clone(),
+                gossip_neighbors: nc.neighbors().collect(),
                 total_data_per_sec: s.bandwidth_tracker.get_total_bandwidth(),
                 download_bandwidth_history: s.bandwidth_history.clone(),
                 downloads: s
@@ -259,6 +298,7 @@ impl NetworkTUIState {
                     })
                     .collect(),
                 blob_hashes,
+                blob_store_stats,
             }),
         })
     }
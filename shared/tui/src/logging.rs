@@ -1,4 +1,4 @@
-use std::{fs::OpenOptions, path::PathBuf, time::Duration};
+use std::{collections::HashSet, fs::OpenOptions, path::PathBuf, time::Duration};
 
 use crate::CustomWidget;
 use clap::ValueEnum;
@@ -7,7 +7,7 @@ use logfire::{
     bridges::tracing::LogfireTracingPendingSpanNotSentLayer,
     config::{AdvancedOptions, MetricsOptions},
 };
-use opentelemetry::{KeyValue, trace::TracerProvider};
+use opentelemetry::{Key, KeyValue, trace::TracerProvider};
 use opentelemetry_otlp::{WithExportConfig, WithHttpConfig};
 use opentelemetry_sdk::{
     Resource,
@@ -103,6 +103,7 @@ pub struct LoggingBuilder {
     trace_destination: Option<TraceDestination>,
     service_info: Option<ServiceInfo>,
     metrics_destination: Option<MetricsDestination>,
+    prometheus_exporter: bool,
 }
 
 impl Default for LoggingBuilder {
@@ -150,6 +151,13 @@ impl LoggingBuilder {
         self
     }
 
+    /// Also export metrics into [`prometheus::default_registry`], for serving at `/metrics`.
+    /// Every metric is labelled with the service info's `run_id`.
+    pub fn with_prometheus_exporter(mut self, enabled: bool) -> Self {
+        self.prometheus_exporter = enabled;
+        self
+    }
+
     /// Set the service info for telemetry
     pub fn with_service_info(mut self, info: ServiceInfo) -> Self {
         self.service_info = Some(info);
@@ -167,6 +175,7 @@ impl LoggingBuilder {
             service_info: None,
             metrics_destination: None,
             trace_destination: None,
+            prometheus_exporter: false,
         }
     }
     pub fn init(self) -> anyhow::Result<ShutdownHandler> {
@@ -178,6 +187,7 @@ impl LoggingBuilder {
             self.remote_logs_destination,
             self.metrics_destination,
             self.trace_destination,
+            self.prometheus_exporter,
         )
     }
 }
@@ -261,37 +271,52 @@ impl ServiceInfo {
     }
 }
 
+/// Builds the global meter provider, exporting over OTLP if `config` is set and into
+/// [`prometheus::default_registry`] if `prometheus_exporter` is.
 fn create_otel_metrics_handler(
-    config: &OpenTelemetry,
+    config: Option<&OpenTelemetry>,
+    prometheus_exporter: bool,
     service_name: Option<ServiceInfo>,
 ) -> anyhow::Result<OtelMetricsHandler> {
-    let mut exporter_builder = opentelemetry_otlp::MetricExporter::builder()
-        .with_http()
-        .with_endpoint(&config.endpoint);
-
-    if let Some(header) = &config.authorization_header {
-        exporter_builder = exporter_builder.with_headers(std::collections::HashMap::from([(
-            "authorization".to_string(),
-            header.to_string(),
-        )]));
-    }
-
-    let exporter = exporter_builder.build()?;
-
     let mut resource_builder = Resource::builder_empty();
     if let Some(info) = service_name {
         resource_builder = resource_builder.with_attributes(info.into_attributes())
     }
     let resource = resource_builder.build();
 
-    let reader = PeriodicReader::builder(exporter)
-        .with_interval(config.report_interval)
-        .build();
+    let mut provider_builder = SdkMeterProvider::builder().with_resource(resource);
 
-    let provider = SdkMeterProvider::builder()
-        .with_resource(resource)
-        .with_reader(reader)
-        .build();
+    if let Some(config) = config {
+        let mut exporter_builder = opentelemetry_otlp::MetricExporter::builder()
+            .with_http()
+            .with_endpoint(&config.endpoint);
+
+        if let Some(header) = &config.authorization_header {
+            exporter_builder = exporter_builder.with_headers(std::collections::HashMap::from([(
+                "authorization".to_string(),
+                header.to_string(),
+            )]));
+        }
+
+        let exporter = exporter_builder.build()?;
+
+        let reader = PeriodicReader::builder(exporter)
+            .with_interval(config.report_interval)
+            .build();
+        provider_builder = provider_builder.with_reader(reader);
+    }
+
+    if prometheus_exporter {
+        // OTLP carries the resource alongside every export, Prometheus only has labels,
+        // so put the run id on every metric. It comes out as `run_id`.
+        let exporter = opentelemetry_prometheus::exporter()
+            .with_registry(prometheus::default_registry().clone())
+            .with_resource_selector(HashSet::from([Key::from_static_str("run.id")]))
+            .build()?;
+        provider_builder = provider_builder.with_reader(exporter);
+    }
+
+    let provider = provider_builder.build();
 
     opentelemetry::global::set_meter_provider(provider.clone());
 
@@ -413,6 +438,7 @@ fn init_logging_impl(
     remote_logs_destination: Option<RemoteLogsDestination>,
     metrics_destination: Option<MetricsDestination>,
     trace_destination: Option<TraceDestination>,
+    prometheus_exporter: bool,
 ) -> anyhow::Result<ShutdownHandler> {
     let mut shutdown_handlers: Vec<Box<dyn Shutdownable>> = vec![];
 
@@ -493,14 +519,23 @@ fn init_logging_impl(
 
     // Handle OpenTelemetry metrics if not using Logfire
     let otel_metrics_handler = if !logfire_handles_metrics {
-        if let Some(MetricsDestination::OpenTelemetry(otel_config)) = &metrics_destination {
+        let otel_config = match &metrics_destination {
+            Some(MetricsDestination::OpenTelemetry(otel_config)) => Some(otel_config),
+            _ => None,
+        };
+        if otel_config.is_some() || prometheus_exporter {
             Some(create_otel_metrics_handler(
                 otel_config,
+                prometheus_exporter,
                 service_info.clone(),
             )?)
         } else {
             None
         }
+    } else if prometheus_exporter {
+        return Err(anyhow::anyhow!(
+            "The Prometheus metrics endpoint can't be used with Logfire metrics"
+        ));
     } else {
         None
     };
@@ -1,7 +1,10 @@
 use anyhow::{Result, bail};
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use futures::future::try_join_all;
-use std::process::{Command, Stdio};
+use std::{
+    process::{Command, Stdio},
+    time::Duration,
+};
 use tracing::{error, info, warn};
 
 #[derive(Parser, Debug)]
@@ -40,10 +43,19 @@ enum Commands {
         /// Backend for torch.distributed (default: nccl)
         #[arg(long, default_value = "nccl")]
         backend: String,
-    },
 
-    /// Run Rust sidecar process (TODO: implement)
-    Rust,
+        /// When to restart a Python sidecar process after it exits
+        #[arg(long, value_enum, default_value_t = RestartPolicy::Never)]
+        restart_policy: RestartPolicy,
+
+        /// Maximum number of times each sidecar process is restarted
+        #[arg(long, default_value_t = 3)]
+        max_restarts: u32,
+
+        /// Delay before restarting a sidecar process, in milliseconds
+        #[arg(long, default_value_t = 1000)]
+        restart_delay_ms: u64,
+    },
 
     // Prints the help, optionally as markdown. Used for docs generation.
     #[clap(hide = true)]
@@ -53,6 +65,23 @@ enum Commands {
     },
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum RestartPolicy {
+    /// Restart whenever the process exits
+    Always,
+    /// Restart only when the process exits with an error
+    OnFailure,
+    /// Never restart
+    Never,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct RestartConfig {
+    policy: RestartPolicy,
+    max_restarts: u32,
+    delay: Duration,
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
@@ -68,11 +97,19 @@ async fn main() -> Result<()> {
             start_device,
             num_local_ranks,
             backend,
+            restart_policy,
+            max_restarts,
+            restart_delay_ms,
         } => {
             if !tch::Cuda::is_available() {
                 bail!("CUDA not avaiable");
             }
 
+            let restart = RestartConfig {
+                policy: restart_policy,
+                max_restarts,
+                delay: Duration::from_millis(restart_delay_ms),
+            };
             let num_local_ranks =
                 num_local_ranks.unwrap_or_else(|| tch::Cuda::device_count() as usize);
             let computed_last_rank = start_rank + num_local_ranks - 1;
@@ -103,7 +140,7 @@ async fn main() -> Result<()> {
                     let device = rank - start_rank + start_device;
 
                     run_python_sidecar(
-                        main_host, port, world_size, rank, device, backend, parent_pid,
+                        main_host, port, world_size, rank, device, backend, parent_pid, restart,
                     )
                     .await
                 }));
@@ -116,9 +153,6 @@ async fn main() -> Result<()> {
 
             Ok(())
         }
-        Commands::Rust => {
-            unimplemented!("Rust sidecar not yet implemented");
-        }
         Commands::PrintAllHelp { markdown } => {
             // This is a required argument for the time being.
             assert!(markdown);
@@ -138,6 +172,7 @@ async fn run_python_sidecar(
     device: usize,
     backend: String,
     parent_pid: u32,
+    restart: RestartConfig,
 ) -> Result<()> {
     let init_method = format!("tcp://{main_host}:{port}");
 
@@ -167,17 +202,39 @@ async fn run_python_sidecar(
 
     info!("Executing: {cmd:?}",);
 
-    let mut child = cmd.spawn()?;
-    let exit_status = child.wait()?;
+    let mut restarts = 0;
+    loop {
+        let mut child = cmd.spawn()?;
+        let exit_status = child.wait()?;
+
+        if exit_status.success() {
+            info!("Python sidecar for rank {rank} completed successfully");
+        } else {
+            error!(
+                "Python sidecar for rank {rank} failed with exit code: {:?}",
+                exit_status.code()
+            );
+        }
+
+        let should_restart = match restart.policy {
+            RestartPolicy::Always => true,
+            RestartPolicy::OnFailure => !exit_status.success(),
+            RestartPolicy::Never => false,
+        };
+        if !should_restart || restarts >= restart.max_restarts {
+            if exit_status.success() {
+                return Ok(());
+            }
+            bail!("Python sidecar process failed");
+        }
 
-    if exit_status.success() {
-        info!("Python sidecar completed successfully");
-        Ok(())
-    } else {
-        error!(
-            "Python sidecar failed with exit code: {:?}",
-            exit_status.code()
+        restarts += 1;
+        warn!(
+            "Restarting Python sidecar for rank {rank} after exit code {:?} in {:?} (restart {restarts}/{})",
+            exit_status.code(),
+            restart.delay,
+            restart.max_restarts
         );
-        bail!("Python sidecar process failed")
+        tokio::time::sleep(restart.delay).await;
     }
 }
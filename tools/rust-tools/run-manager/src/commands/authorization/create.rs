@@ -34,16 +34,19 @@ impl Command for CommandJoinAuthorizationCreate {
         println!("Authorization Lamports: {}", authorization_lamports);
 
         if authorization_lamports == 0 {
+            let instruction =
+                instructions::authorizer_authorization_create(&payer, &grantor, &grantee, scope);
+            backend
+                .check_sufficient_balance(
+                    backend
+                        .estimate_fees(std::slice::from_ref(&instruction))
+                        .await?,
+                )
+                .await?;
             println!(
                 "Created authorization in transaction: {}",
                 backend
-                    .send_and_retry(
-                        "Authorization create",
-                        &[instructions::authorizer_authorization_create(
-                            &payer, &grantor, &grantee, scope,
-                        )],
-                        &[],
-                    )
+                    .send_and_retry("Authorization create", &[instruction], &[])
                     .await?
             );
         }
@@ -52,16 +55,20 @@ impl Command for CommandJoinAuthorizationCreate {
         println!("Authorization Active: {}", authorization_content.active);
 
         if !authorization_content.active {
+            let instruction = instructions::authorizer_authorization_grantor_update(
+                &grantor, &grantee, scope, true,
+            );
+            backend
+                .check_sufficient_balance(
+                    backend
+                        .estimate_fees(std::slice::from_ref(&instruction))
+                        .await?,
+                )
+                .await?;
             println!(
                 "Activated authorization in transaction: {}",
                 backend
-                    .send_and_retry(
-                        "Authorization activate",
-                        &[instructions::authorizer_authorization_grantor_update(
-                            &grantor, &grantee, scope, true
-                        )],
-                        &[],
-                    )
+                    .send_and_retry("Authorization activate", &[instruction], &[])
                     .await?
             );
         }
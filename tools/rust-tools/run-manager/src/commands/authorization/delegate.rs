@@ -46,21 +46,25 @@ impl Command for CommandJoinAuthorizationDelegate {
             println!("- Delegate added: {}", delegate_added);
         }
 
+        let instruction = instructions::authorizer_authorization_grantee_update(
+            &payer,
+            &grantor,
+            &grantee,
+            scope,
+            delegates_clear,
+            delegates_added,
+        );
+        backend
+            .check_sufficient_balance(
+                backend
+                    .estimate_fees(std::slice::from_ref(&instruction))
+                    .await?,
+            )
+            .await?;
         println!(
             "Updated authorization delegates in transaction: {}",
             backend
-                .send_and_retry(
-                    "Authorization set delegates",
-                    &[instructions::authorizer_authorization_grantee_update(
-                        &payer,
-                        &grantor,
-                        &grantee,
-                        scope,
-                        delegates_clear,
-                        delegates_added,
-                    )],
-                    &[]
-                )
+                .send_and_retry("Authorization set delegates", &[instruction], &[])
                 .await?
         );
 
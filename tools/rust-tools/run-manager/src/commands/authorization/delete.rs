@@ -34,16 +34,20 @@ impl Command for CommandJoinAuthorizationDelete {
         println!("Authorization Active: {}", authorization_content.active);
 
         if authorization_content.active {
+            let instruction = instructions::authorizer_authorization_grantor_update(
+                &grantor, &grantee, scope, false,
+            );
+            backend
+                .check_sufficient_balance(
+                    backend
+                        .estimate_fees(std::slice::from_ref(&instruction))
+                        .await?,
+                )
+                .await?;
             println!(
                 "Deactivated authorization in transaction: {}",
                 backend
-                    .send_and_retry(
-                        "Authorization deactivate",
-                        &[instructions::authorizer_authorization_grantor_update(
-                            &grantor, &grantee, scope, false,
-                        )],
-                        &[],
-                    )
+                    .send_and_retry("Authorization deactivate", &[instruction], &[])
                     .await?
             );
         }
@@ -48,6 +48,13 @@ impl Command for CommandCheckpoint {
             &user,
             psyche_coordinator::model::Checkpoint::Hub(repo),
         );
+        backend
+            .check_sufficient_balance(
+                backend
+                    .estimate_fees(std::slice::from_ref(&instruction))
+                    .await?,
+            )
+            .await?;
         let signature = backend
             .send_and_retry("Checkpoint", &[instruction], &[])
             .await?;
@@ -34,6 +34,13 @@ impl Command for CommandCloseRun {
             &coordinator_account,
             &payer,
         );
+        backend
+            .check_sufficient_balance(
+                backend
+                    .estimate_fees(std::slice::from_ref(&instruction))
+                    .await?,
+            )
+            .await?;
         let signature = backend
             .send_and_retry("Close run", &[instruction], &[])
             .await?;
@@ -100,10 +100,14 @@ impl Command for CommandCreateRun {
             )
         };
 
+        let instructions = [instruction_create, instruction_init];
+        let fees = backend.estimate_fees(&instructions).await?;
+        backend.check_sufficient_balance(fees + rent).await?;
+
         let signature = backend
             .send_and_retry(
                 "Create and init run",
-                &[instruction_create, instruction_init],
+                &instructions,
                 &[coordinator_account_signer],
             )
             .await?;
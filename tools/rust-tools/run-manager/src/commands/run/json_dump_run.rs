@@ -2,6 +2,7 @@ use crate::commands::Command;
 use anchor_spl::associated_token;
 use anyhow::Result;
 use async_trait::async_trait;
+use chrono::Utc;
 use clap::Args;
 use serde_json::Map;
 use serde_json::json;
@@ -72,6 +73,7 @@ impl Command for CommandJsonDumpRun {
                         client.id.to_string(),
                         json!({
                             "active": client.active,
+                            "last_seen_unix_timestamp": (client.last_seen != 0).then_some(client.last_seen),
                             "earned": client.earned,
                             "slashed": client.slashed,
                         }),
@@ -101,6 +103,21 @@ impl Command for CommandJsonDumpRun {
             .map(|client| client.slashed)
             .sum::<u64>();
 
+        let coordinator = &coordinator_account_state.state.coordinator;
+        let coordinator_round_json = coordinator.current_round().map(|round| {
+            // results reach the coordinator as witnesses, each carrying a bloom of the results it saw
+            json!({
+                "height": round.height,
+                "witnesses_received_this_step": round.witnesses.len(),
+                "expected_witnesses_this_step": coordinator.num_witness_nodes(),
+                "bloom_occupancy": round.broadcast_bloom_occupancy(),
+            })
+        });
+        let time_in_current_state_secs = match coordinator.run_state_start_unix_timestamp {
+            0 => None,
+            start => Some((Utc::now().timestamp_millis() as f64 / 1000.0 - start as f64).max(0.0)),
+        };
+
         let coordinator_account_json = json!({
             "address": coordinator_account_address.to_string(),
             "run_id": coordinator_account_state.state.coordinator.run_id,
@@ -115,6 +132,8 @@ impl Command for CommandJsonDumpRun {
                 "state": coordinator_account_state.state.coordinator.run_state.to_string(),
                 "epoch": coordinator_account_state.state.coordinator.progress.epoch,
                 "step": coordinator_account_state.state.coordinator.progress.step,
+                "time_in_current_state_secs": time_in_current_state_secs,
+                "round": coordinator_round_json,
             },
             "epoch": {
                 "clients": {
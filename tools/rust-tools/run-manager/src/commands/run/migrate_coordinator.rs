@@ -0,0 +1,50 @@
+use crate::commands::Command;
+use async_trait::async_trait;
+
+use anyhow::Result;
+use clap::Args;
+
+use crate::{SolanaBackend, instructions};
+
+#[derive(Debug, Clone, Args)]
+#[command()]
+pub struct CommandMigrateCoordinator {
+    #[clap(short, long, env)]
+    pub run_id: String,
+}
+
+#[async_trait]
+impl Command for CommandMigrateCoordinator {
+    async fn execute(self, backend: SolanaBackend) -> Result<()> {
+        let Self { run_id } = self;
+
+        let payer = backend.get_payer();
+
+        let coordinator_instance = psyche_solana_coordinator::find_coordinator_instance(&run_id);
+        let coordinator_instance_state = backend
+            .get_coordinator_instance(&coordinator_instance)
+            .await?;
+        let coordinator_account = coordinator_instance_state.coordinator_account;
+
+        let instruction =
+            instructions::coordinator_migrate(&coordinator_instance, &coordinator_account, &payer);
+        backend
+            .check_sufficient_balance(
+                backend
+                    .estimate_fees(std::slice::from_ref(&instruction))
+                    .await?,
+            )
+            .await?;
+        let signature = backend
+            .send_and_retry("Migrate coordinator", &[instruction], &[])
+            .await?;
+        println!("Migrated coordinator account of run {run_id} with transaction {signature}");
+
+        println!("\n===== Logs =====");
+        for log in backend.get_logs(&signature).await? {
+            println!("{log}");
+        }
+
+        Ok(())
+    }
+}
@@ -4,6 +4,7 @@ pub mod create_run;
 pub mod download_results;
 pub mod json_dump_run;
 pub mod json_dump_user;
+pub mod migrate_coordinator;
 pub mod run_down_service;
 pub mod set_future_epoch_rates;
 pub mod set_paused;
@@ -17,6 +18,7 @@ pub use create_run::*;
 pub use download_results::*;
 pub use json_dump_run::*;
 pub use json_dump_user::*;
+pub use migrate_coordinator::*;
 pub use set_future_epoch_rates::*;
 pub use set_paused::*;
 pub use tick::*;
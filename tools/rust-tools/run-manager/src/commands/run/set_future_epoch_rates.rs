@@ -85,6 +85,13 @@ impl Command for CommandSetFutureEpochRates {
             println!(" - Set slashing rate to {slashing_rate_per_client} (per failing client)");
         }
 
+        backend
+            .check_sufficient_balance(
+                backend
+                    .estimate_fees(std::slice::from_ref(&instruction))
+                    .await?,
+            )
+            .await?;
         let signature = backend
             .send_and_retry("Set future epoch rates", &[instruction], &[])
             .await?;
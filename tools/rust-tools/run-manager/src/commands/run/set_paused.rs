@@ -64,6 +64,13 @@ impl Command for CommandSetPaused {
             )
         };
 
+        backend
+            .check_sufficient_balance(
+                backend
+                    .estimate_fees(std::slice::from_ref(&instruction))
+                    .await?,
+            )
+            .await?;
         let signature = backend
             .send_and_retry("Set paused", &[instruction], &[])
             .await?;
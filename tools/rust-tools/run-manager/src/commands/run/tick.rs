@@ -44,6 +44,13 @@ impl Command for CommandTick {
                 &coordinator_account,
                 &ticker,
             );
+            backend
+                .check_sufficient_balance(
+                    backend
+                        .estimate_fees(std::slice::from_ref(&instruction))
+                        .await?,
+                )
+                .await?;
             let signature = backend.send_and_retry("Tick", &[instruction], &[]).await?;
             println!("Ticked run {run_id} with transaction {signature}");
 
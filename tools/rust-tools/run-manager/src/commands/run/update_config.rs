@@ -196,6 +196,9 @@ impl Command for CommandUpdateConfig {
 
             instructions
         };
+        backend
+            .check_sufficient_balance(backend.estimate_fees(&instructions).await?)
+            .await?;
         let signature = backend
             .send_and_retry("Update config", &instructions, &[])
             .await?;
@@ -69,6 +69,13 @@ impl Command for CommandTreasurerClaimRewards {
             &treasurer_run_state.collateral_mint,
             &token::ID,
         );
+            backend
+                .check_sufficient_balance(
+                    backend
+                        .estimate_fees(std::slice::from_ref(&instruction))
+                        .await?,
+                )
+                .await?;
             let signature = backend
                 .send_and_retry("Create user ATA", &[instruction], &[])
                 .await?;
@@ -92,6 +99,13 @@ impl Command for CommandTreasurerClaimRewards {
                 treasurer_index,
                 &user,
             );
+            backend
+                .check_sufficient_balance(
+                    backend
+                        .estimate_fees(std::slice::from_ref(&instruction))
+                        .await?,
+                )
+                .await?;
             let participant_create_signature = backend
                 .send_and_retry("Create participant PDA", &[instruction], &[])
                 .await?;
@@ -148,6 +162,13 @@ impl Command for CommandTreasurerClaimRewards {
             &user,
             claim_earned_points,
         );
+        backend
+            .check_sufficient_balance(
+                backend
+                    .estimate_fees(std::slice::from_ref(&instruction))
+                    .await?,
+            )
+            .await?;
         let claim_signature = backend
             .send_and_retry("Claim rewards", &[instruction], &[])
             .await?;
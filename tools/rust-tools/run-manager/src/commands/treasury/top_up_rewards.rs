@@ -85,6 +85,13 @@ impl Command for CommandTreasurerTopUpRewards {
             &[],
             collateral_amount,
         )?;
+        backend
+            .check_sufficient_balance(
+                backend
+                    .estimate_fees(std::slice::from_ref(&instruction))
+                    .await?,
+            )
+            .await?;
         let signature = backend
             .send_and_retry("Top-up rewards", &[instruction], &[])
             .await?;
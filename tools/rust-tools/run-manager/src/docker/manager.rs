@@ -2,14 +2,17 @@ use anchor_client::solana_sdk::bs58;
 use anchor_client::solana_sdk::pubkey::Pubkey;
 use anchor_client::solana_sdk::signature::{EncodableKey, Keypair, Signer};
 use anyhow::{Context, Result, anyhow, bail};
+use serde::Deserialize;
 use std::io::{BufRead, BufReader, Cursor};
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
 use tokio::signal;
 use tracing::{debug, error, info, warn};
 
-use crate::docker::RunInfo;
 use crate::docker::coordinator_client::CoordinatorClient;
+use crate::docker::{RunInfo, RunManagerState};
 use crate::get_env_var;
 use crate::load_and_apply_env_file;
 use crate::load_wallet_key;
@@ -17,6 +20,8 @@ use psyche_coordinator::RunState;
 
 const RETRY_DELAY_SECS: u64 = 5;
 const VERSION_MISMATCH_EXIT_CODE: i32 = 10;
+const HEALTH_CHECK_INTERVAL_SECS: u64 = 5;
+const HEALTH_CHECK_LOG_LINES: u32 = 50;
 
 pub struct RunManager {
     env_file: PathBuf,
@@ -26,6 +31,17 @@ pub struct RunManager {
     coordinator_client: CoordinatorClient,
     scratch_dir: Option<String>,
     client_authorizer: Pubkey,
+    state_file: PathBuf,
+    health_check_timeout: Option<Duration>,
+}
+
+/// The parts of the client's metrics TCP server output that show it's making progress
+#[derive(Debug, Deserialize)]
+struct ClientProgress {
+    #[serde(default)]
+    round_step: u32,
+    #[serde(default)]
+    downloads_started: u64,
 }
 
 #[derive(Debug)]
@@ -40,6 +56,8 @@ impl RunManager {
         env_file: PathBuf,
         local_docker: bool,
         authorizer: Option<Pubkey>,
+        state_file: PathBuf,
+        health_check_timeout: Option<Duration>,
     ) -> Result<Self> {
         // Verify docker is available
         Command::new("docker")
@@ -85,6 +103,8 @@ impl RunManager {
                     local_docker,
                     scratch_dir,
                     client_authorizer,
+                    state_file,
+                    health_check_timeout,
                 });
             }
         }
@@ -111,6 +131,8 @@ impl RunManager {
             local_docker,
             scratch_dir,
             client_authorizer,
+            state_file,
+            health_check_timeout,
         })
     }
 
@@ -314,37 +336,154 @@ impl RunManager {
         Ok(())
     }
 
-    pub async fn run(&self, entrypoint: Option<Entrypoint>) -> Result<()> {
-        loop {
-            let docker_tag = self.prepare_image().await?;
-            info!("Starting container...");
+    /// Starts a container from `image_name`, recording it in the state file
+    /// (along with the image it replaces) first.
+    fn start_container(&self, image_name: &str, entrypoint: &Option<Entrypoint>) -> Result<String> {
+        let mut state = RunManagerState::load(&self.state_file)?;
+        state.record_image(image_name);
+        state.save(&self.state_file)?;
+
+        let container_id = self.run_container(image_name, entrypoint)?;
+        state.container_id = Some(container_id.clone());
+        state.save(&self.state_file)?;
+        Ok(container_id)
+    }
 
-            let start_time = tokio::time::Instant::now();
-            let container_id = self.run_container(&docker_tag, &entrypoint)?;
-
-            // Race between container completion and Ctrl+C
-            let exit_code = tokio::select! {
-                result = async {
-                        self.stream_logs(&container_id).await?;
-                        self.wait_for_container(&container_id)
-                } => {
-                    result?
-                },
-                _ = signal::ctrl_c() => {
-                    info!("\nReceived interrupt signal, cleaning up container...");
-                    self.stop_and_remove_container(&container_id)?;
-                    info!("Container stopped successfully");
+    /// Polls the client's metrics TCP server until it reports that it has started
+    /// stepping or downloading. If it doesn't within `timeout`, returns an error
+    /// with the container's last log lines.
+    pub async fn wait_healthy(
+        &self,
+        container_id: &str,
+        metrics_port: u16,
+        timeout: Duration,
+    ) -> Result<()> {
+        info!(
+            "Waiting up to {:?} for container {} to become healthy",
+            timeout, container_id
+        );
+        let deadline = tokio::time::Instant::now() + timeout;
+        let last_status = loop {
+            let status = match query_client_progress(metrics_port).await {
+                Ok(progress) if progress.round_step > 0 || progress.downloads_started > 0 => {
+                    info!(
+                        "Container {} is healthy (round step {}, {} downloads started)",
+                        container_id, progress.round_step, progress.downloads_started
+                    );
                     return Ok(());
                 }
+                Ok(_) => "metrics server responded, but the client hasn't made progress".to_string(),
+                Err(e) => format!("metrics server on port {metrics_port} didn't respond: {e:#}"),
             };
+            debug!("Container {} not healthy yet: {}", container_id, status);
 
-            let duration = start_time.elapsed().as_secs();
-            info!(
-                "Container exited with code: {} after {} seconds",
-                exit_code, duration
-            );
+            let now = tokio::time::Instant::now();
+            if now >= deadline {
+                break status;
+            }
+            tokio::time::sleep(Duration::from_secs(HEALTH_CHECK_INTERVAL_SECS).min(deadline - now))
+                .await;
+        };
+
+        bail!(
+            "Container {} did not become healthy within {:?} ({}). Last {} log lines:\n{}",
+            container_id,
+            timeout,
+            last_status,
+            HEALTH_CHECK_LOG_LINES,
+            self.container_log_tail(container_id, HEALTH_CHECK_LOG_LINES)
+        )
+    }
+
+    fn container_log_tail(&self, container_id: &str, lines: u32) -> String {
+        match Command::new("docker")
+            .arg("logs")
+            .arg("--tail")
+            .arg(lines.to_string())
+            .arg(container_id)
+            .output()
+        {
+            Ok(output) => format!(
+                "{}{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            ),
+            Err(e) => format!("<failed to get container logs: {e}>"),
+        }
+    }
+
+    /// Stops and removes a container, and forgets it in the state file.
+    fn finish_container(&self, container_id: &str) -> Result<()> {
+        self.stop_and_remove_container(container_id)?;
+        let mut state = RunManagerState::load(&self.state_file)?;
+        if state.container_id.as_deref() == Some(container_id) {
+            state.container_id = None;
+            state.save(&self.state_file)?;
+        }
+        Ok(())
+    }
+
+    /// Runs [`Self::wait_healthy`] if a health check timeout was configured,
+    /// cleaning up the container if it fails.
+    async fn check_health(&self, container_id: &str) -> Result<()> {
+        let Some(timeout) = self.health_check_timeout else {
+            return Ok(());
+        };
+        let Some(metrics_port) = std::env::var("METRICS_LOCAL_PORT")
+            .ok()
+            .and_then(|port| port.parse::<u16>().ok())
+        else {
+            warn!("Health check requested, but METRICS_LOCAL_PORT is not set. Skipping it.");
+            return Ok(());
+        };
+        if let Err(e) = self.wait_healthy(container_id, metrics_port, timeout).await {
+            self.finish_container(container_id)?;
+            return Err(e.context("New client container is unhealthy, consider `run-manager rollback`"));
+        }
+        Ok(())
+    }
+
+    /// Follows a container's logs until it exits, returning its exit code,
+    /// or `None` if we were interrupted (in which case the container has been cleaned up).
+    async fn follow_container(&self, container_id: &str) -> Result<Option<i32>> {
+        let start_time = tokio::time::Instant::now();
+
+        // Race between container completion and Ctrl+C
+        let exit_code = tokio::select! {
+            result = async {
+                    self.stream_logs(container_id).await?;
+                    self.wait_for_container(container_id)
+            } => {
+                result?
+            },
+            _ = signal::ctrl_c() => {
+                info!("\nReceived interrupt signal, cleaning up container...");
+                self.finish_container(container_id)?;
+                info!("Container stopped successfully");
+                return Ok(None);
+            }
+        };
+
+        let duration = start_time.elapsed().as_secs();
+        info!(
+            "Container exited with code: {} after {} seconds",
+            exit_code, duration
+        );
+
+        self.finish_container(container_id)?;
+        Ok(Some(exit_code))
+    }
+
+    pub async fn run(&self, entrypoint: Option<Entrypoint>) -> Result<()> {
+        loop {
+            let docker_tag = self.prepare_image().await?;
+            info!("Starting container...");
 
-            self.stop_and_remove_container(&container_id)?;
+            let container_id = self.start_container(&docker_tag, &entrypoint)?;
+            self.check_health(&container_id).await?;
+            let Some(exit_code) = self.follow_container(&container_id).await? else {
+                return Ok(());
+            };
 
             // Only retry on version mismatch (exit code 10)
             if exit_code == VERSION_MISMATCH_EXIT_CODE {
@@ -357,6 +496,46 @@ impl RunManager {
             }
         }
     }
+
+    /// Stops the current client container and starts it again from the image it replaced.
+    pub async fn rollback(&self, entrypoint: Option<Entrypoint>) -> Result<()> {
+        let state = RunManagerState::load(&self.state_file)?;
+        let Some(previous_image) = state.previous_image.clone() else {
+            bail!(
+                "No previous image recorded in {}, nothing to roll back to",
+                self.state_file.display()
+            );
+        };
+        info!(
+            "Rolling back from {} to {}",
+            state.current_image.as_deref().unwrap_or("<unknown>"),
+            previous_image
+        );
+
+        if let Some(container_id) = &state.container_id {
+            self.finish_container(container_id)?;
+        }
+
+        if self.local_docker {
+            info!("Using local image (skipping pull): {}", previous_image);
+        } else {
+            self.pull_image(&previous_image)?;
+        }
+
+        let container_id = self.start_container(&previous_image, &entrypoint)?;
+        self.check_health(&container_id).await?;
+        if let Some(exit_code) = self.follow_container(&container_id).await? {
+            info!("Container exited with code {}, shutting down", exit_code);
+        }
+        Ok(())
+    }
+}
+
+async fn query_client_progress(metrics_port: u16) -> Result<ClientProgress> {
+    let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", metrics_port)).await?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    serde_json::from_slice(&response).context("Failed to parse client metrics")
 }
 
 /// Parse wallet key string to extract the user's pubkey.
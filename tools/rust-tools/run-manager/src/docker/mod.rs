@@ -1,5 +1,6 @@
 pub mod coordinator_client;
 pub mod manager;
+pub mod state;
 
 // Re-exports
 pub use coordinator_client::RunInfo;
@@ -7,3 +8,4 @@ pub use manager::{
     Entrypoint, RunManager, find_joinable_runs, parse_delegate_authorizer_from_env,
     parse_wallet_pubkey,
 };
+pub use state::RunManagerState;
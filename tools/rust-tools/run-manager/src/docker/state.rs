@@ -0,0 +1,46 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// What the run manager remembers between invocations, so a bad client update can be rolled back.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RunManagerState {
+    /// Image the client container was last started from
+    pub current_image: Option<String>,
+    /// Image that was in use before `current_image`
+    pub previous_image: Option<String>,
+    /// Container started from `current_image`, if it may still be running
+    pub container_id: Option<String>,
+}
+
+impl RunManagerState {
+    /// Loads the state from `path`, or an empty state if the file doesn't exist yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse state file {}", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => {
+                Err(err).with_context(|| format!("Failed to read state file {}", path.display()))
+            }
+        }
+    }
+
+    /// Writes the state to a temporary file and renames it over `path`,
+    /// so the state file is never left half-written.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write state file {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to replace state file {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Records that `image` is about to be used, keeping the image it replaces as `previous_image`.
+    pub fn record_image(&mut self, image: &str) {
+        if self.current_image.as_deref() != Some(image) {
+            self.previous_image = self.current_image.replace(image.to_string());
+        }
+    }
+}
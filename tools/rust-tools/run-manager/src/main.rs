@@ -14,6 +14,7 @@ use run_manager::parse_optional_pubkey;
 use std::io::Cursor;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::error;
 
 // Command parameter imports
@@ -24,8 +25,8 @@ use commands::authorization::{
 use commands::can_join::CommandCanJoin;
 use commands::run::{
     CommandCheckpoint, CommandCloseRun, CommandCreateRun, CommandDownloadResults,
-    CommandJsonDumpRun, CommandJsonDumpUser, CommandSetFutureEpochRates, CommandSetPaused,
-    CommandTick, CommandUpdateConfig, CommandUploadData,
+    CommandJsonDumpRun, CommandJsonDumpUser, CommandMigrateCoordinator, CommandSetFutureEpochRates,
+    CommandSetPaused, CommandTick, CommandUpdateConfig, CommandUploadData,
 };
 use commands::treasury::{CommandTreasurerClaimRewards, CommandTreasurerTopUpRewards};
 use run_manager::docker::coordinator_client::CoordinatorClient;
@@ -73,6 +74,15 @@ struct CliArgs {
     #[arg(long)]
     entrypoint: Option<String>,
 
+    /// File recording the client images in use, for `rollback` (Docker mode)
+    #[arg(long, default_value = "state.json")]
+    state_file: PathBuf,
+
+    /// After starting the client, wait this long for it to report progress on METRICS_LOCAL_PORT,
+    /// stopping it if it doesn't (Docker mode)
+    #[arg(long)]
+    health_check_timeout_secs: Option<u64>,
+
     /// Arguments to pass to the entrypoint (use after --) (Docker mode)
     #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
     entrypoint_args: Vec<String>,
@@ -153,6 +163,14 @@ enum Commands {
         #[clap(flatten)]
         params: CommandTick,
     },
+    MigrateCoordinator {
+        #[clap(flatten)]
+        cluster: ClusterArgs,
+        #[clap(flatten)]
+        wallet: WalletArgs,
+        #[clap(flatten)]
+        params: CommandMigrateCoordinator,
+    },
     JsonDumpRun {
         #[clap(flatten)]
         cluster: ClusterArgs,
@@ -255,6 +273,29 @@ enum Commands {
         authorizer: Option<String>,
     },
 
+    /// Stop the client container and restart it from the previously used image
+    Rollback {
+        /// Path to .env file with environment variables
+        #[arg(long)]
+        env_file: PathBuf,
+        /// Coordinator program ID
+        #[arg(long, default_value = "4SHugWqSXwKE5fqDchkJcPEqnoZE22VYKtSTVm7axbT7")]
+        coordinator_program_id: String,
+        /// Use a local Docker image instead of pulling from registry
+        #[arg(long)]
+        local: bool,
+        /// Only join runs where this pubkey is the join_authority
+        #[arg(long)]
+        authorizer: Option<String>,
+        /// File recording the client images in use
+        #[arg(long, default_value = "state.json")]
+        state_file: PathBuf,
+        /// After starting the client, wait this long for it to report progress on METRICS_LOCAL_PORT,
+        /// stopping it if it doesn't
+        #[arg(long)]
+        health_check_timeout_secs: Option<u64>,
+    },
+
     // Docs generation
     #[clap(hide = true)]
     PrintAllHelp {
@@ -335,6 +376,8 @@ async fn async_main() -> Result<()> {
             env_file,
             args.local,
             authorizer,
+            args.state_file,
+            args.health_check_timeout_secs.map(Duration::from_secs),
         )?;
         let result = run_mgr.run(entrypoint).await;
         if let Err(e) = &result {
@@ -381,6 +424,11 @@ async fn async_main() -> Result<()> {
             wallet,
             params,
         } => params.execute(create_backend(cluster, wallet)?).await,
+        Commands::MigrateCoordinator {
+            cluster,
+            wallet,
+            params,
+        } => params.execute(create_backend(cluster, wallet)?).await,
         Commands::JsonDumpRun { cluster, params } => {
             params.execute(create_backend_readonly(cluster)?).await
         }
@@ -424,6 +472,26 @@ async fn async_main() -> Result<()> {
             coordinator_program_id,
             authorizer,
         } => list_runs(env_file, cluster, coordinator_program_id, authorizer),
+        Commands::Rollback {
+            env_file,
+            coordinator_program_id,
+            local,
+            authorizer,
+            state_file,
+            health_check_timeout_secs,
+        } => {
+            let authorizer = parse_optional_pubkey(authorizer.as_ref(), "authorizer")?;
+            RunManager::new(
+                coordinator_program_id,
+                env_file,
+                local,
+                authorizer,
+                state_file,
+                health_check_timeout_secs.map(Duration::from_secs),
+            )?
+            .rollback(None)
+            .await
+        }
         Commands::DownloadResults {
             cluster,
             wallet,